@@ -0,0 +1,127 @@
+use crate::audio_device::enumeration::{DeviceEnumerator, DeviceInfo};
+use crate::audio_device::input::{InputCaptureSource, InputRing};
+use crate::audio_device::negotiation::NegotiatedConfig;
+use crate::audio_device::stream::{
+    ASSUMED_BUFFER_FRAMES, ErrorState, StreamError, StreamResult, StreamState, build_input_stream, stream_monitor,
+};
+use crate::rt_processing::performance::{PerformanceAggregator, PerformanceMonitor, StreamId};
+use cpal::BufferSize;
+use cpal::traits::StreamTrait;
+use spin::Mutex;
+use std::sync::Arc;
+
+/// Owns a capture-only stream on an input device and feeds an `InputRing`.
+///
+/// Unlike the input side of a duplex `StreamManager`, the device runs on its
+/// own clock; `open` returns the `InputCaptureSource` to add to the router,
+/// which plays the captured audio like any other source. The config must run
+/// at the engine's sample rate. Errors and monitoring work as in `StreamManager`.
+pub struct InputCapture {
+    device: cpal::Device,
+    info: DeviceInfo,
+    config: NegotiatedConfig,
+    ring: Arc<InputRing>,
+    stream: Option<cpal::Stream>,
+    state: StreamState,
+    errors: Arc<Mutex<ErrorState>>,
+    monitor: Arc<PerformanceMonitor>,
+}
+
+impl InputCapture {
+    pub fn open(
+        enumerator: &DeviceEnumerator,
+        info: &DeviceInfo,
+        config: NegotiatedConfig,
+    ) -> StreamResult<(Self, InputCaptureSource)> {
+        let device = enumerator.select_device(info)?.clone();
+        let block = match config.buffer_size {
+            BufferSize::Fixed(frames) => frames as usize,
+            BufferSize::Default => ASSUMED_BUFFER_FRAMES,
+        };
+        // more slack than duplex: the clocks drift apart
+        let ring = Arc::new(InputRing::new(config.channels as usize, block * 16));
+        let source = InputCaptureSource::new(Arc::clone(&ring), block * 2, block * 4);
+
+        let capture = Self {
+            device,
+            info: info.clone(),
+            monitor: stream_monitor(&config),
+            config,
+            ring,
+            stream: None,
+            state: StreamState::Stopped,
+            errors: Arc::new(Mutex::new(ErrorState::default())),
+        };
+        Ok((capture, source))
+    }
+
+    pub fn device_info(&self) -> &DeviceInfo {
+        &self.info
+    }
+
+    pub fn config(&self) -> &NegotiatedConfig {
+        &self.config
+    }
+
+    /// Timing of this stream's capture callbacks
+    pub fn monitor(&self) -> &Arc<PerformanceMonitor> {
+        &self.monitor
+    }
+
+    /// Report this stream in `aggregator`, labelled with the device name
+    pub fn register_monitor(&self, aggregator: &PerformanceAggregator) -> StreamId {
+        aggregator.register(self.info.name.clone(), Arc::clone(&self.monitor))
+    }
+
+    /// Captured audio, e.g. to watch overruns
+    pub fn ring(&self) -> Arc<InputRing> {
+        Arc::clone(&self.ring)
+    }
+
+    pub fn state(&self) -> StreamState {
+        if self.errors.lock().error.is_some() { StreamState::Failed } else { self.state }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state() == StreamState::Running
+    }
+
+    /// Call `handler` with every error the backend reports while capturing
+    pub fn on_error(&mut self, handler: impl Fn(&StreamError) + Send + Sync + 'static) {
+        self.errors.lock().handler = Some(Arc::new(handler));
+    }
+
+    /// The last runtime error, clearing the `Failed` state
+    pub fn take_error(&mut self) -> Option<StreamError> {
+        self.errors.lock().error.take()
+    }
+
+    /// Build the stream if needed and start (or resume) capturing
+    pub fn start(&mut self) -> StreamResult<()> {
+        if self.stream.is_none() {
+            let monitor = Some(Arc::clone(&self.monitor));
+            self.stream = Some(build_input_stream(&self.device, &self.config, &self.ring, &self.errors, monitor)?);
+        }
+        if self.state != StreamState::Running {
+            self.monitor.restart_warmup();
+        }
+        if let Some(stream) = &self.stream {
+            stream.play().map_err(|e| StreamError::PlayFailed(e.to_string()))?;
+        }
+        self.state = StreamState::Running;
+        Ok(())
+    }
+
+    pub fn pause(&mut self) -> StreamResult<()> {
+        let Some(stream) = &self.stream else { return Ok(()) };
+        stream.pause().map_err(|e| StreamError::PauseFailed(e.to_string()))?;
+        self.state = StreamState::Paused;
+        Ok(())
+    }
+
+    /// Close the stream; the next `start` builds a new one
+    pub fn stop(&mut self) {
+        self.stream = None;
+        self.state = StreamState::Stopped;
+    }
+}
@@ -0,0 +1,129 @@
+//! Converting the engine's f32 output into the sample format a device takes.
+//!
+//! Float and 32-bit formats are converted directly. Quantizing to 16 bits
+//! leaves an error correlated with the signal, heard as distortion on quiet
+//! material; `FormatConverter` can add TPDF dither first, which turns it into
+//! a steady hiss, and shape that noise away from the midrange.
+
+use cpal::{FromSample, SizedSample};
+
+use crate::rt_processing::waveform::noise::FastRng;
+
+/// Quantization error fed back, in LSBs; past clipping the error would grow
+/// without bound
+const MAX_SHAPED_ERROR: f32 = 2.0;
+
+/// Spectrum of the quantization noise
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NoiseShaping {
+    /// White, like the dither
+    #[default]
+    None,
+    /// First-order error feedback: the noise rises 6 dB per octave, away from
+    /// the lows, for 3 dB more overall
+    FirstOrder,
+    /// Second-order error feedback: 12 dB per octave, most of the noise close
+    /// to Nyquist, 8 dB more overall
+    SecondOrder,
+}
+
+/// How the stream converts to an integer device format, from
+/// `ConfigurationRequest::with_format_conversion`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatConversion {
+    /// Add triangular (TPDF) dither of ±1 LSB before rounding
+    pub dither: bool,
+    pub noise_shaping: NoiseShaping,
+}
+
+impl Default for FormatConversion {
+    fn default() -> Self {
+        Self { dither: true, noise_shaping: NoiseShaping::None }
+    }
+}
+
+/// A sample format a device may take from `FormatConverter`
+pub trait DeviceSample: SizedSample + FromSample<f32> {
+    /// Largest positive code of formats coarse enough to dither, `None` for
+    /// formats converted directly
+    const FULL_SCALE: Option<f32> = None;
+
+    /// The sample for a signed code in `-FULL_SCALE - 1..=FULL_SCALE`; only
+    /// used for formats with a `FULL_SCALE`
+    fn from_code(code: i32) -> Self {
+        Self::from_sample_(code as f32)
+    }
+}
+
+impl DeviceSample for f32 {}
+impl DeviceSample for f64 {}
+impl DeviceSample for i32 {}
+
+impl DeviceSample for i16 {
+    const FULL_SCALE: Option<f32> = Some(i16::MAX as f32);
+
+    fn from_code(code: i32) -> Self {
+        code as i16
+    }
+}
+
+impl DeviceSample for u16 {
+    const FULL_SCALE: Option<f32> = Some(i16::MAX as f32);
+
+    fn from_code(code: i32) -> Self {
+        (code + 0x8000) as u16
+    }
+}
+
+/// Interleaved f32 to a device format, keeping the dither generator and the
+/// noise shaping state of each channel between calls. RT-safe.
+pub struct FormatConverter {
+    conversion: FormatConversion,
+    /// Per channel: the last two quantization errors, newest first
+    errors: Vec<[f32; 2]>,
+    rng: FastRng,
+}
+
+impl FormatConverter {
+    pub fn new(channels: usize, conversion: FormatConversion) -> Self {
+        Self { conversion, errors: vec![[0.0; 2]; channels.max(1)], rng: FastRng::from_state(0x6c07_8965) }
+    }
+
+    pub fn conversion(&self) -> FormatConversion {
+        self.conversion
+    }
+
+    pub fn channels(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Convert interleaved `input` into `output`, as far as the shorter one
+    /// goes. Both must start on a frame.
+    pub fn convert<T: DeviceSample>(&mut self, input: &[f32], output: &mut [T]) {
+        let Some(full_scale) = T::FULL_SCALE else {
+            for (out, &sample) in output.iter_mut().zip(input) {
+                *out = T::from_sample_(sample);
+            }
+            return;
+        };
+        let channels = self.errors.len();
+        for (i, (out, &sample)) in output.iter_mut().zip(input).enumerate() {
+            let dither = if self.conversion.dither { self.rng.next_f32() - self.rng.next_f32() } else { 0.0 };
+            let error = &mut self.errors[i % channels];
+            let target = sample * full_scale
+                - match self.conversion.noise_shaping {
+                    NoiseShaping::None => 0.0,
+                    NoiseShaping::FirstOrder => error[0],
+                    NoiseShaping::SecondOrder => 2.0 * error[0] - error[1],
+                };
+            let code = (target + dither).round().clamp(-full_scale - 1.0, full_scale);
+            *error = [(code - target).clamp(-MAX_SHAPED_ERROR, MAX_SHAPED_ERROR), error[0]];
+            *out = T::from_code(code as i32);
+        }
+    }
+
+    /// Clear the noise shaping state, e.g. when the stream restarts
+    pub fn reset(&mut self) {
+        self.errors.fill([0.0; 2]);
+    }
+}
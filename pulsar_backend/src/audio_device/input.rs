@@ -0,0 +1,225 @@
+use crate::rt_processing::analysis::Analyzer;
+use crate::rt_processing::routing::AudioSource;
+use crate::rt_processing::trace;
+use crossbeam::queue::ArrayQueue;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Captured audio between an input callback and whoever plays it.
+///
+/// Interleaved f32 in a lock-free queue: one producer (the device's input
+/// callback), one consumer (an `InputSource` on the output callback). Both
+/// sides only ever move whole frames.
+pub struct InputRing {
+    queue: ArrayQueue<f32>,
+    channels: usize,
+    /// Frames the producer dropped because the queue was full
+    overruns: AtomicU64,
+    /// Frames the consumer wanted but weren't captured yet
+    underruns: AtomicU64,
+}
+
+impl InputRing {
+    /// Room for `frames` frames of `channels` channels
+    pub fn new(channels: usize, frames: usize) -> Self {
+        let channels = channels.max(1);
+        Self {
+            queue: ArrayQueue::new(frames.max(1) * channels),
+            channels,
+            overruns: AtomicU64::new(0),
+            underruns: AtomicU64::new(0),
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Frames captured and not read yet
+    pub fn available(&self) -> usize {
+        self.queue.len() / self.channels
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.queue.capacity() / self.channels
+    }
+
+    pub fn overruns(&self) -> u64 {
+        self.overruns.load(Ordering::Relaxed)
+    }
+
+    pub fn underruns(&self) -> u64 {
+        self.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Producer side: queue interleaved samples, converting each with `convert`.
+    /// Frames that don't fit are dropped and counted as overruns.
+    pub fn write<T: Copy>(&self, samples: &[T], convert: impl Fn(T) -> f32) {
+        for (i, frame) in samples.chunks_exact(self.channels).enumerate() {
+            // single producer: free space only grows while we push
+            if self.queue.capacity() - self.queue.len() < self.channels {
+                let dropped = samples.len() / self.channels - i;
+                self.overruns.fetch_add(dropped as u64, Ordering::Relaxed);
+                trace::xrun("input_ring", true, dropped as u64);
+                return;
+            }
+            for &s in frame {
+                let _ = self.queue.push(convert(s));
+            }
+        }
+    }
+
+    /// Consumer side: pop one frame; channels beyond `frame`'s length are discarded
+    fn read_frame(&self, frame: &mut [f32]) -> bool {
+        if self.queue.len() < self.channels {
+            return false;
+        }
+        for ch in 0..self.channels {
+            let s = self.queue.pop().unwrap_or(0.0);
+            if let Some(slot) = frame.get_mut(ch) {
+                *slot = s;
+            }
+        }
+        true
+    }
+
+    /// Drop frames beyond `frames`, oldest first
+    fn trim_to(&self, frames: usize) {
+        let excess = self.available().saturating_sub(frames);
+        for _ in 0..excess * self.channels {
+            let _ = self.queue.pop();
+        }
+    }
+}
+
+/// Most channels an `InputSource` reads per frame
+pub const MAX_INPUT_CHANNELS: usize = 32;
+
+/// Routed source playing captured input, e.g. a mic through the router's
+/// gain, pan and inserts.
+///
+/// Reads one block of frames per render. Missing frames play as silence and
+/// count as underruns. If the input runs ahead by more than `max_latency`
+/// frames (start-up, a stalled output), the oldest frames are skipped so
+/// monitoring latency stays bounded. Engine channels beyond the input's wrap
+/// around its channels, so a mono mic feeds every channel.
+///
+/// Analyzers added with `with_analyzer` (a `PitchDetector` for a tuner) see
+/// each block as captured, before the router's gain and inserts, on the
+/// audio thread.
+pub struct InputSource {
+    ring: Arc<InputRing>,
+    max_latency: usize,
+    analyzers: Vec<Box<dyn Analyzer + Sync>>,
+}
+
+impl InputSource {
+    pub fn new(ring: Arc<InputRing>, max_latency: usize) -> Self {
+        Self { ring, max_latency, analyzers: Vec::new() }
+    }
+
+    /// Run `analyzer` on the captured input; it keeps its own result handles
+    pub fn with_analyzer(mut self, analyzer: impl Analyzer + Sync + 'static) -> Self {
+        self.analyzers.push(Box::new(analyzer));
+        self
+    }
+
+    /// Feed the block's input channels, not their wrapped copies, to the analyzers
+    fn analyze(&mut self, output: &[&mut [f32]], frames: usize, sample_rate: f32) {
+        if self.analyzers.is_empty() {
+            return;
+        }
+        let channels = self.ring.channels().min(output.len()).min(MAX_INPUT_CHANNELS);
+        let mut input: [&[f32]; MAX_INPUT_CHANNELS] = [&[]; MAX_INPUT_CHANNELS];
+        for (view, channel) in input.iter_mut().zip(output) {
+            *view = &channel[..frames];
+        }
+        for analyzer in &mut self.analyzers {
+            analyzer.analyze(&input[..channels], frames, sample_rate);
+        }
+    }
+
+    /// The ring, e.g. to watch its overrun and underrun counts
+    pub fn ring(&self) -> Arc<InputRing> {
+        Arc::clone(&self.ring)
+    }
+}
+
+impl AudioSource for InputSource {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        let channels = self.ring.channels().min(MAX_INPUT_CHANNELS);
+        let mut frame = [0.0f32; MAX_INPUT_CHANNELS];
+        self.ring.trim_to(self.max_latency.max(frames));
+
+        for i in 0..frames {
+            if !self.ring.read_frame(&mut frame) {
+                self.ring.underruns.fetch_add((frames - i) as u64, Ordering::Relaxed);
+                trace::xrun("input_ring", false, (frames - i) as u64);
+                for out in output.iter_mut() {
+                    out[i..frames].fill(0.0);
+                }
+                break;
+            }
+            for (ch, out) in output.iter_mut().enumerate() {
+                out[i] = frame[ch % channels];
+            }
+        }
+        self.analyze(output, frames, sample_rate);
+    }
+}
+
+/// Routed source for an input device running on its own clock (a USB mic
+/// next to the output interface), fed by an `InputCapture`.
+///
+/// The two clocks drift, so the ring slowly fills up or runs dry. Running
+/// ahead is handled like `InputSource` (oldest frames skipped past
+/// `max_latency`); running dry makes the source go quiet and wait until
+/// `prefill` frames are queued again, so drift costs one short gap instead of
+/// a crackle on every block.
+pub struct InputCaptureSource {
+    source: InputSource,
+    prefill: usize,
+    primed: bool,
+}
+
+impl InputCaptureSource {
+    pub fn new(ring: Arc<InputRing>, prefill: usize, max_latency: usize) -> Self {
+        Self { source: InputSource::new(ring, max_latency.max(prefill)), prefill, primed: false }
+    }
+
+    pub fn ring(&self) -> Arc<InputRing> {
+        self.source.ring()
+    }
+
+    /// Run `analyzer` on the captured input, as `InputSource::with_analyzer`;
+    /// blocks waiting for the prefill aren't analyzed
+    pub fn with_analyzer(mut self, analyzer: impl Analyzer + Sync + 'static) -> Self {
+        self.source = self.source.with_analyzer(analyzer);
+        self
+    }
+
+    /// Whether the source is playing input rather than waiting for the prefill
+    pub fn is_primed(&self) -> bool {
+        self.primed
+    }
+}
+
+impl AudioSource for InputCaptureSource {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        let ring = &self.source.ring;
+        if !self.primed {
+            if ring.available() < self.prefill.max(frames) {
+                for out in output.iter_mut() {
+                    out[..frames].fill(0.0);
+                }
+                return;
+            }
+            self.primed = true;
+        }
+        let underruns = ring.underruns();
+        self.source.render(output, frames, sample_rate);
+        if self.source.ring.underruns() != underruns {
+            self.primed = false;
+        }
+    }
+}
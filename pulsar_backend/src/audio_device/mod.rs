@@ -1,2 +1,8 @@
 pub mod enumeration;
 pub mod negotiation;
+pub mod input;
+pub mod stream;
+pub mod format_convert;
+pub mod capture;
+pub mod null;
+pub mod watcher;
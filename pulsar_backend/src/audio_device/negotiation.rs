@@ -1,4 +1,5 @@
 use crate::audio_device::enumeration::DeviceInfo;
+use crate::audio_device::format_convert::FormatConversion;
 use cpal::{SampleFormat, SampleRate, StreamConfig, BufferSize};
 use std::fmt;
 
@@ -39,7 +40,14 @@ pub struct ConfigurationRequest {
     pub buffer_size_priority: BufferSizePriority,
     
     pub sample_format: Option<SampleFormat>,
+    /// Let the stream convert to a device format other than f32; otherwise
+    /// such devices fail to start
     pub allow_format_conversion: bool,
+    /// Dither and noise shaping when converting to a 16-bit format
+    pub format_conversion: FormatConversion,
+    /// With an `Exact` rate the device can't run, take its closest rate and
+    /// resample to the requested one instead of failing
+    pub allow_sample_rate_conversion: bool,
 }
 
 impl ConfigurationRequest {
@@ -53,6 +61,8 @@ impl ConfigurationRequest {
             buffer_size_priority: BufferSizePriority::Balanced,
             sample_format: None,
             allow_format_conversion: true,
+            format_conversion: FormatConversion::default(),
+            allow_sample_rate_conversion: false,
         }
     }
 
@@ -95,6 +105,16 @@ impl ConfigurationRequest {
         self.allow_format_conversion = allow;
         self
     }
+
+    pub fn with_format_conversion(mut self, conversion: FormatConversion) -> Self {
+        self.format_conversion = conversion;
+        self
+    }
+    
+    pub fn allow_sample_rate_conversion(mut self, allow: bool) -> Self {
+        self.allow_sample_rate_conversion = allow;
+        self
+    }
     
     pub fn low_latency() -> Self {
         Self::new()
@@ -137,10 +157,17 @@ impl Default for ConfigurationRequest {
 
 #[derive(Debug, Clone)]
 pub struct NegotiatedConfig {
+    /// Rate the device runs at
     pub sample_rate: u32,
+    /// Rate the engine renders at; differs from `sample_rate` when the stream
+    /// converts between the two
+    pub engine_sample_rate: u32,
     pub channels: u16,
     pub buffer_size: BufferSize,
     pub sample_format: SampleFormat,
+    /// How the engine's f32 is converted to `sample_format`; `None` if the
+    /// request didn't allow conversion
+    pub format_conversion: Option<FormatConversion>,
     pub stream_config: StreamConfig,
     
     pub sample_rate_matched: bool,
@@ -149,6 +176,13 @@ pub struct NegotiatedConfig {
     pub format_matched: bool,
 }
 
+impl NegotiatedConfig {
+    /// Whether the stream converts between the engine and device rates
+    pub fn is_resampled(&self) -> bool {
+        self.engine_sample_rate != self.sample_rate
+    }
+}
+
 impl fmt::Display for NegotiatedConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -158,7 +192,11 @@ impl fmt::Display for NegotiatedConfig {
             self.sample_rate,
             self.buffer_size,
             self.sample_format
-        )
+        )?;
+        if self.is_resampled() {
+            write!(f, " (engine @ {}Hz, resampled)", self.engine_sample_rate)?;
+        }
+        Ok(())
     }
 }
 
@@ -170,6 +208,8 @@ pub enum NegotiationError {
     BufferSizeNotSupported { requested: u32 },
     NoCompatibleConfiguration,
     DeviceQueryFailed(String),
+    /// Duplex needs the input and output side of one device
+    DeviceMismatch { output: String, input: String },
 }
 
 impl fmt::Display for NegotiationError {
@@ -193,12 +233,23 @@ impl fmt::Display for NegotiationError {
             Self::DeviceQueryFailed(msg) => {
                 write!(f, "Device query failed: {}", msg)
             }
+            Self::DeviceMismatch { output, input } => {
+                write!(f, "Duplex needs one device, got output {} and input {}", output, input)
+            }
         }
     }
 }
 
 impl std::error::Error for NegotiationError {}
 
+/// Matching input and output configs for a duplex stream: same rate and
+/// buffer size, each side with its own channels and format
+#[derive(Debug, Clone)]
+pub struct DuplexConfig {
+    pub output: NegotiatedConfig,
+    pub input: NegotiatedConfig,
+}
+
 pub type NegotiationResult<T> = Result<T, NegotiationError>;
 
 pub struct ConfigNegotiator;
@@ -207,14 +258,22 @@ impl ConfigNegotiator {
         device_info: &DeviceInfo,
         request: &ConfigurationRequest,
     ) -> NegotiationResult<NegotiatedConfig> {
-        let sample_rate = Self::negotiate_sample_rate(device_info, request)?;
+        let (sample_rate, engine_sample_rate) = match Self::negotiate_sample_rate(device_info, request) {
+            Ok(rate) => (rate, rate),
+            Err(NegotiationError::SampleRateNotSupported { requested, .. }) if request.allow_sample_rate_conversion => {
+                let rate = Self::find_closest_sample_rate(device_info, requested)
+                    .ok_or(NegotiationError::NoCompatibleConfiguration)?;
+                (rate, requested)
+            }
+            Err(e) => return Err(e),
+        };
         let channels = Self::negotiate_channels(device_info, request)?;
         let sample_format = Self::negotiate_sample_format(device_info, request)?;
         let buffer_size = Self::negotiate_buffer_size(request);
         
-        let sample_rate_matched = request.sample_rate.map_or(true, |r| r == sample_rate);
-        let channels_matched = request.channels.map_or(true, |r| r == channels);
-        let format_matched = request.sample_format.map_or(true, |r| r == sample_format);
+        let sample_rate_matched = request.sample_rate.is_none_or(|r| r == sample_rate);
+        let channels_matched = request.channels.is_none_or(|r| r == channels);
+        let format_matched = request.sample_format.is_none_or(|r| r == sample_format);
         let buffer_size_matched = match (&request.buffer_size, &buffer_size) {
             (Some(req), BufferSize::Fixed(actual)) => *req == *actual,
             (None, _) => true,
@@ -224,14 +283,16 @@ impl ConfigNegotiator {
         let stream_config = StreamConfig {
             channels,
             sample_rate: SampleRate(sample_rate),
-            buffer_size: buffer_size.clone(),
+            buffer_size,
         };
         
         Ok(NegotiatedConfig {
             sample_rate,
+            engine_sample_rate,
             channels,
             buffer_size,
             sample_format,
+            format_conversion: request.allow_format_conversion.then_some(request.format_conversion),
             stream_config,
             sample_rate_matched,
             channels_matched,
@@ -288,12 +349,9 @@ impl ConfigNegotiator {
     }
     
     fn find_best_standard_rate(device_info: &DeviceInfo) -> Option<u32> {
-        for &rate in &[48000, 44100, 96000, 88200] {
-            if Self::is_sample_rate_supported(device_info, rate) {
-                return Some(rate);
-            }
-        }
-        None
+        [48000, 44100, 96000, 88200]
+            .into_iter()
+            .find(|&rate| Self::is_sample_rate_supported(device_info, rate))
     }
     
     fn is_sample_rate_supported(device_info: &DeviceInfo, rate: u32) -> bool {
@@ -396,6 +454,42 @@ impl ConfigNegotiator {
         }
     }
     
+    /// Negotiate both directions of one device. The output side leads: the
+    /// input must run at the rate (and fixed buffer size) the output got, so
+    /// both callbacks share one clock. If the output is resampled, so is the
+    /// input, to the same engine rate.
+    pub fn negotiate_duplex(
+        output_info: &DeviceInfo,
+        input_info: &DeviceInfo,
+        output_request: &ConfigurationRequest,
+        input_request: &ConfigurationRequest,
+    ) -> NegotiationResult<DuplexConfig> {
+        if output_info.name != input_info.name || output_info.host_id != input_info.host_id {
+            return Err(NegotiationError::DeviceMismatch {
+                output: output_info.name.clone(),
+                input: input_info.name.clone(),
+            });
+        }
+        let output = Self::negotiate(output_info, output_request)?;
+
+        let mut input_request = input_request.clone();
+        input_request.sample_rate = Some(output.engine_sample_rate);
+        input_request.sample_rate_priority = SampleRatePriority::Exact;
+        input_request.allow_sample_rate_conversion = output.is_resampled();
+        match output.buffer_size {
+            BufferSize::Fixed(frames) => {
+                input_request.buffer_size = Some(frames);
+                input_request.buffer_size_priority = BufferSizePriority::Exact;
+            }
+            BufferSize::Default => {
+                input_request.buffer_size = None;
+                input_request.buffer_size_priority = BufferSizePriority::Default;
+            }
+        }
+        let input = Self::negotiate(input_info, &input_request)?;
+        Ok(DuplexConfig { output, input })
+    }
+
     pub fn calculate_latency_ms(sample_rate: u32, buffer_size: u32) -> f32 {
         (buffer_size as f32 / sample_rate as f32) * 1000.0
     }
@@ -413,10 +507,7 @@ impl ConfigNegotiator {
         
         device_info.supported_sample_rates
             .iter()
-            .min_by_key(|&&rate| {
-                let diff = (rate as i64 - target as i64).abs();
-                diff
-            })
+            .min_by_key(|&&rate| (rate as i64 - target as i64).abs())
             .copied()
     }
     
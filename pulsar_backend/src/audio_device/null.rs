@@ -0,0 +1,292 @@
+use crate::audio_device::enumeration::DeviceInfo;
+use crate::audio_device::negotiation::NegotiatedConfig;
+use crate::audio_device::stream::{
+    ASSUMED_BUFFER_FRAMES, ErrorState, StreamError, StreamResult, StreamState, renderer, stream_monitor,
+};
+use crate::rt_processing::callback::CallbackSlot;
+use crate::rt_processing::performance::{PerformanceAggregator, PerformanceMonitor, StreamId};
+use cpal::{BufferSize, SampleFormat};
+use spin::Mutex;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Name reported by `null_device`
+pub const NULL_DEVICE_NAME: &str = "Null Output";
+
+/// An output device that plays nothing. Negotiates like a real one, with the
+/// common rates and up to 8 channels of f32, but has no cpal device behind it:
+/// open it with `NullStream`, not `StreamManager`.
+pub fn null_device() -> DeviceInfo {
+    let supported_sample_rates = vec![22050, 44100, 48000, 88200, 96000, 176400, 192000];
+    DeviceInfo {
+        name: NULL_DEVICE_NAME.to_string(),
+        host_id: cpal::ALL_HOSTS[0],
+        is_default: false,
+        is_input: false,
+        is_output: true,
+        min_sample_rate: supported_sample_rates[0],
+        max_sample_rate: supported_sample_rates[supported_sample_rates.len() - 1],
+        default_sample_rate: 48000,
+        supported_sample_rates,
+        supported_channels: (1..=8).collect(),
+        max_channels: 8,
+        default_channels: 2,
+        supported_sample_formats: vec![SampleFormat::F32],
+        default_sample_format: SampleFormat::F32,
+        // never matches an enumerated device
+        device_index: usize::MAX,
+    }
+}
+
+/// How a running `NullStream` calls back
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NullPacing {
+    /// One block per buffer period, like a real device
+    #[default]
+    Realtime,
+    /// Back to back, as fast as the graph renders
+    FreeRunning,
+}
+
+type RenderFn = Box<dyn FnMut(&mut [f32]) + Send>;
+type OutputTap = Box<dyn FnMut(&[f32]) + Send>;
+
+/// The callback side: what a device's audio thread would own
+struct NullRender {
+    render: RenderFn,
+    buffer: Vec<f32>,
+    tap: Option<OutputTap>,
+    frames: Arc<AtomicU64>,
+    channels: usize,
+}
+
+impl NullRender {
+    fn block(&mut self) {
+        (self.render)(&mut self.buffer);
+        if let Some(tap) = &mut self.tap {
+            tap(&self.buffer);
+        }
+        self.frames.fetch_add((self.buffer.len() / self.channels.max(1)) as u64, Ordering::Relaxed);
+    }
+}
+
+/// Drives a `CallbackSlot` from a device that doesn't exist, for tests, CI
+/// machines without audio hardware, and headless rendering.
+///
+/// Takes a config negotiated against `null_device` and renders through the
+/// same path as `StreamManager` (resampling, monitoring, and the `chaos`
+/// feature's faults), in blocks of the negotiated buffer size. `start` runs the
+/// callbacks on a thread, paced like a real device or free-running; while the
+/// stream is stopped or paused, `render_blocks` runs them on the caller's
+/// thread instead, so a test can step the graph deterministically. The device
+/// output is discarded unless `on_output` taps it.
+pub struct NullStream {
+    info: DeviceInfo,
+    config: NegotiatedConfig,
+    slot: Arc<CallbackSlot>,
+    pacing: NullPacing,
+    render: Arc<Mutex<NullRender>>,
+    thread: Option<JoinHandle<()>>,
+    running: Arc<AtomicBool>,
+    frames: Arc<AtomicU64>,
+    state: StreamState,
+    errors: Arc<Mutex<ErrorState>>,
+    monitor: Arc<PerformanceMonitor>,
+}
+
+impl NullStream {
+    /// `slot` must run at the negotiated rate and channel count
+    pub fn new(config: NegotiatedConfig, slot: Arc<CallbackSlot>) -> StreamResult<Self> {
+        let slot_config = (slot.sample_rate() as u32, slot.channels());
+        let stream_config = (config.engine_sample_rate, config.channels as usize);
+        if slot_config != stream_config {
+            return Err(StreamError::ConfigMismatch { slot: slot_config, config: stream_config });
+        }
+        let monitor = stream_monitor(&config);
+        let errors = Arc::new(Mutex::new(ErrorState::default()));
+        let frames = Arc::new(AtomicU64::new(0));
+        let block = match config.buffer_size {
+            BufferSize::Fixed(frames) => frames as usize,
+            BufferSize::Default => ASSUMED_BUFFER_FRAMES,
+        };
+        let render = NullRender {
+            render: Box::new(renderer(&slot, &monitor, &config, &errors)),
+            buffer: vec![0.0; block * config.channels as usize],
+            tap: None,
+            frames: Arc::clone(&frames),
+            channels: config.channels as usize,
+        };
+
+        Ok(Self {
+            info: null_device(),
+            config,
+            slot,
+            pacing: NullPacing::default(),
+            render: Arc::new(Mutex::new(render)),
+            thread: None,
+            running: Arc::new(AtomicBool::new(false)),
+            frames,
+            state: StreamState::Stopped,
+            errors,
+            monitor,
+        })
+    }
+
+    /// Applies from the next `start`
+    pub fn with_pacing(mut self, pacing: NullPacing) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    pub fn set_pacing(&mut self, pacing: NullPacing) {
+        self.pacing = pacing;
+    }
+
+    pub fn pacing(&self) -> NullPacing {
+        self.pacing
+    }
+
+    pub fn device_info(&self) -> &DeviceInfo {
+        &self.info
+    }
+
+    pub fn config(&self) -> &NegotiatedConfig {
+        &self.config
+    }
+
+    pub fn slot(&self) -> &Arc<CallbackSlot> {
+        &self.slot
+    }
+
+    /// Timing of this stream's callbacks
+    pub fn monitor(&self) -> &Arc<PerformanceMonitor> {
+        &self.monitor
+    }
+
+    /// Report this stream in `aggregator`, labelled with the device name
+    pub fn register_monitor(&self, aggregator: &PerformanceAggregator) -> StreamId {
+        aggregator.register(self.info.name.clone(), Arc::clone(&self.monitor))
+    }
+
+    /// Frames per callback, at the device rate
+    pub fn block_frames(&self) -> usize {
+        self.render.lock().buffer.len() / self.config.channels.max(1) as usize
+    }
+
+    /// Frames rendered since the stream was created, at the device rate
+    pub fn frames_rendered(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// Call `handler` with every block the device would have played
+    /// (interleaved, at the device rate), on the rendering thread
+    pub fn on_output(&mut self, handler: impl FnMut(&[f32]) + Send + 'static) {
+        self.render.lock().tap = Some(Box::new(handler));
+    }
+
+    pub fn state(&self) -> StreamState {
+        if self.errors.lock().error.is_some() { StreamState::Failed } else { self.state }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state() == StreamState::Running
+    }
+
+    /// Call `handler` with every error raised while rendering
+    pub fn on_error(&mut self, handler: impl Fn(&StreamError) + Send + Sync + 'static) {
+        self.errors.lock().handler = Some(Arc::new(handler));
+    }
+
+    /// The last runtime error, clearing the `Failed` state
+    pub fn take_error(&mut self) -> Option<StreamError> {
+        self.errors.lock().error.take()
+    }
+
+    /// Run `blocks` callbacks on this thread. Does nothing while the stream is
+    /// running, since the blocks would interleave with its thread's.
+    pub fn render_blocks(&mut self, blocks: usize) {
+        if self.thread.is_some() {
+            return;
+        }
+        let mut render = self.render.lock();
+        for _ in 0..blocks {
+            render.block();
+        }
+    }
+
+    /// Start (or resume) calling back on the stream's thread
+    pub fn start(&mut self) -> StreamResult<()> {
+        if self.thread.is_none() {
+            self.monitor.restart_warmup();
+            self.running.store(true, Ordering::Release);
+            let running = Arc::clone(&self.running);
+            let render = Arc::clone(&self.render);
+            let period = match self.pacing {
+                NullPacing::Realtime => {
+                    Some(Duration::from_secs_f64(self.block_frames() as f64 / self.config.sample_rate as f64))
+                }
+                NullPacing::FreeRunning => None,
+            };
+            let thread = thread::Builder::new()
+                .name("pulsar-null-device".into())
+                .spawn(move || {
+                    let mut deadline = Instant::now();
+                    while running.load(Ordering::Acquire) {
+                        render.lock().block();
+                        if let Some(period) = period {
+                            // paced against the start, so slow blocks don't drift the clock
+                            deadline += period;
+                            if let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+                                thread::sleep(wait);
+                            }
+                        }
+                    }
+                })
+                .map_err(|e| StreamError::PlayFailed(e.to_string()))?;
+            self.thread = Some(thread);
+        }
+        self.state = StreamState::Running;
+        Ok(())
+    }
+
+    /// Stop calling back; `start` resumes
+    pub fn pause(&mut self) -> StreamResult<()> {
+        self.join();
+        if self.state == StreamState::Running {
+            self.state = StreamState::Paused;
+        }
+        Ok(())
+    }
+
+    /// Stop calling back and reset the render path (resampler state, a
+    /// simulated lost device), as closing a real stream would
+    pub fn stop(&mut self) {
+        self.join();
+        let mut render = self.render.lock();
+        render.render = Box::new(renderer(&self.slot, &self.monitor, &self.config, &self.errors));
+        render.buffer.fill(0.0);
+        self.state = StreamState::Stopped;
+    }
+
+    /// Stop, clear any error, and start again
+    pub fn restart(&mut self) -> StreamResult<()> {
+        self.stop();
+        self.take_error();
+        self.start()
+    }
+
+    fn join(&mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for NullStream {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
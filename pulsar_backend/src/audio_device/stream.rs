@@ -0,0 +1,496 @@
+use crate::audio_device::enumeration::{DeviceEnumerator, DeviceInfo, EnumError};
+use crate::audio_device::format_convert::{DeviceSample, FormatConversion, FormatConverter};
+use crate::audio_device::input::{InputRing, InputSource};
+use crate::audio_device::negotiation::{DuplexConfig, NegotiatedConfig};
+use crate::rt_processing::callback::CallbackSlot;
+use crate::rt_processing::performance::{
+    DEFAULT_EMA_ALPHA, DEFAULT_WARMUP, PerformanceAggregator, PerformanceMonitor, StreamId,
+};
+use crate::rt_processing::resampler::Resampler;
+use crate::rt_processing::trace;
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{BufferSize, FromSample, SampleFormat, SizedSample};
+use spin::Mutex;
+use std::fmt;
+use std::sync::Arc;
+
+/// Frames converted per pass for devices that don't take f32 directly
+const CONVERT_FRAMES: usize = 4096;
+/// Device buffer assumed for sizing when the backend picks it
+pub(super) const ASSUMED_BUFFER_FRAMES: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamState {
+    /// No stream built (never started, or stopped)
+    Stopped,
+    Running,
+    /// Stream built but not pulling audio
+    Paused,
+    /// The backend reported an error; see `StreamManager::take_error`
+    Failed,
+}
+
+pub type StreamResult<T> = Result<T, StreamError>;
+
+#[derive(Debug, Clone)]
+pub enum StreamError {
+    Device(String),
+    /// The callback slot runs at a different rate or channel count than the config
+    ConfigMismatch { slot: (u32, usize), config: (u32, usize) },
+    UnsupportedFormat(SampleFormat),
+    /// The device doesn't take f32 and the request didn't allow converting
+    ConversionNotAllowed(SampleFormat),
+    BuildFailed(String),
+    PlayFailed(String),
+    PauseFailed(String),
+    /// The device went away while running (unplugged, driver reset)
+    DeviceLost,
+    /// Runtime error reported by the backend
+    Backend(String),
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Device(msg) => write!(f, "Device error: {}", msg),
+            Self::ConfigMismatch { slot, config } => write!(
+                f,
+                "Callback runs at {}ch @ {}Hz but stream is {}ch @ {}Hz",
+                slot.1, slot.0, config.1, config.0
+            ),
+            Self::UnsupportedFormat(format) => write!(f, "Unsupported sample format: {:?}", format),
+            Self::ConversionNotAllowed(format) => {
+                write!(f, "Device takes {:?} but format conversion is not allowed", format)
+            }
+            Self::BuildFailed(msg) => write!(f, "Failed to build stream: {}", msg),
+            Self::PlayFailed(msg) => write!(f, "Failed to start stream: {}", msg),
+            Self::PauseFailed(msg) => write!(f, "Failed to pause stream: {}", msg),
+            Self::DeviceLost => write!(f, "Audio device is no longer available"),
+            Self::Backend(msg) => write!(f, "Stream error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+impl From<EnumError> for StreamError {
+    fn from(e: EnumError) -> Self {
+        Self::Device(e.to_string())
+    }
+}
+
+impl From<cpal::StreamError> for StreamError {
+    fn from(e: cpal::StreamError) -> Self {
+        match e {
+            cpal::StreamError::DeviceNotAvailable => Self::DeviceLost,
+            other => Self::Backend(other.to_string()),
+        }
+    }
+}
+
+/// Capture half of a duplex stream
+struct InputSide {
+    device: cpal::Device,
+    config: NegotiatedConfig,
+    ring: Arc<InputRing>,
+    stream: Option<cpal::Stream>,
+}
+
+pub(super) type ErrorHandler = Arc<dyn Fn(&StreamError) + Send + Sync>;
+
+/// Shared between the manager and the backend's error callback
+#[derive(Default)]
+pub(super) struct ErrorState {
+    pub(super) error: Option<StreamError>,
+    pub(super) handler: Option<ErrorHandler>,
+}
+
+/// Owns an output stream for one device: builds it from a negotiated config,
+/// drives a `CallbackSlot` from the device callback, and tracks start, pause
+/// and stop.
+///
+/// In duplex mode (`duplex`) the manager also runs the device's input side on
+/// the same clock; captured audio reaches the graph through the returned
+/// `InputSource`, routed like any other source.
+///
+/// Devices that don't take f32 get the slot's output converted into their
+/// format without allocating on the audio thread, dithered and noise shaped
+/// for 16-bit formats as `NegotiatedConfig::format_conversion` says; a config
+/// that doesn't allow conversion only opens f32 devices. When negotiation settled on
+/// a device rate other than the engine's (`NegotiatedConfig::is_resampled`),
+/// a `Resampler` sits between the slot and the device, and captured input is
+/// converted back to the engine rate before it reaches the ring. Backend errors raised while
+/// running mark the stream `Failed`; they are kept for `take_error` and passed
+/// to the handler set with `on_error`, which runs on the backend's thread.
+///
+/// Every output callback is timed by the stream's own `PerformanceMonitor`;
+/// `register_monitor` adds it to a `PerformanceAggregator` under the device name.
+/// Each start opens the monitor's warm-up window (`DEFAULT_WARMUP`).
+pub struct StreamManager {
+    device: cpal::Device,
+    info: DeviceInfo,
+    config: NegotiatedConfig,
+    slot: Arc<CallbackSlot>,
+    stream: Option<cpal::Stream>,
+    input: Option<InputSide>,
+    state: StreamState,
+    errors: Arc<Mutex<ErrorState>>,
+    monitor: Arc<PerformanceMonitor>,
+}
+
+/// Monitor sized for callbacks of `config`
+pub(super) fn stream_monitor(config: &NegotiatedConfig) -> Arc<PerformanceMonitor> {
+    let frames = match config.buffer_size {
+        BufferSize::Fixed(frames) => frames as usize,
+        BufferSize::Default => ASSUMED_BUFFER_FRAMES,
+    };
+    Arc::new(PerformanceMonitor::new(frames, config.sample_rate as f32, DEFAULT_EMA_ALPHA).with_warmup(DEFAULT_WARMUP))
+}
+
+impl StreamManager {
+    /// `slot` must run at the negotiated rate and channel count
+    pub fn new(
+        enumerator: &DeviceEnumerator,
+        info: &DeviceInfo,
+        config: NegotiatedConfig,
+        slot: Arc<CallbackSlot>,
+    ) -> StreamResult<Self> {
+        let slot_config = (slot.sample_rate() as u32, slot.channels());
+        let stream_config = (config.engine_sample_rate, config.channels as usize);
+        if slot_config != stream_config {
+            return Err(StreamError::ConfigMismatch { slot: slot_config, config: stream_config });
+        }
+        let device = enumerator.select_device(info)?.clone();
+
+        Ok(Self {
+            device,
+            info: info.clone(),
+            monitor: stream_monitor(&config),
+            config,
+            slot,
+            stream: None,
+            input: None,
+            state: StreamState::Stopped,
+            errors: Arc::new(Mutex::new(ErrorState::default())),
+        })
+    }
+
+    /// Capture and playback on one device, from `ConfigNegotiator::negotiate_duplex`.
+    /// `slot` runs at the output config; add the returned source to the router
+    /// to hear (and process) the input.
+    pub fn duplex(
+        enumerator: &DeviceEnumerator,
+        output_info: &DeviceInfo,
+        input_info: &DeviceInfo,
+        config: DuplexConfig,
+        slot: Arc<CallbackSlot>,
+    ) -> StreamResult<(Self, InputSource)> {
+        let mut manager = Self::new(enumerator, output_info, config.output, slot)?;
+        let device = enumerator.select_device(input_info)?.clone();
+
+        let block = match config.input.buffer_size {
+            BufferSize::Fixed(frames) => frames as usize,
+            BufferSize::Default => ASSUMED_BUFFER_FRAMES,
+        };
+        // a few device buffers of slack; the source keeps about two queued
+        let ring = Arc::new(InputRing::new(config.input.channels as usize, block * 8));
+        let source = InputSource::new(Arc::clone(&ring), block * 2);
+        manager.input = Some(InputSide { device, config: config.input, ring, stream: None });
+        Ok((manager, source))
+    }
+
+    pub fn device_info(&self) -> &DeviceInfo {
+        &self.info
+    }
+
+    pub fn config(&self) -> &NegotiatedConfig {
+        &self.config
+    }
+
+    pub fn slot(&self) -> &Arc<CallbackSlot> {
+        &self.slot
+    }
+
+    /// Timing of this stream's output callbacks
+    pub fn monitor(&self) -> &Arc<PerformanceMonitor> {
+        &self.monitor
+    }
+
+    /// Report this stream in `aggregator`, labelled with the device name
+    pub fn register_monitor(&self, aggregator: &PerformanceAggregator) -> StreamId {
+        aggregator.register(self.info.name.clone(), Arc::clone(&self.monitor))
+    }
+
+    pub fn is_duplex(&self) -> bool {
+        self.input.is_some()
+    }
+
+    /// Capture config of a duplex stream
+    pub fn input_config(&self) -> Option<&NegotiatedConfig> {
+        self.input.as_ref().map(|input| &input.config)
+    }
+
+    /// Captured audio of a duplex stream, e.g. to watch overruns
+    pub fn input_ring(&self) -> Option<Arc<InputRing>> {
+        self.input.as_ref().map(|input| Arc::clone(&input.ring))
+    }
+
+    pub fn state(&self) -> StreamState {
+        if self.errors.lock().error.is_some() { StreamState::Failed } else { self.state }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state() == StreamState::Running
+    }
+
+    /// Call `handler` with every error the backend reports while running
+    pub fn on_error(&mut self, handler: impl Fn(&StreamError) + Send + Sync + 'static) {
+        self.errors.lock().handler = Some(Arc::new(handler));
+    }
+
+    /// The last runtime error, clearing the `Failed` state
+    pub fn take_error(&mut self) -> Option<StreamError> {
+        self.errors.lock().error.take()
+    }
+
+    /// Build the stream if needed and start (or resume) it
+    pub fn start(&mut self) -> StreamResult<()> {
+        if let Some(input) = &self.input
+            && input.stream.is_none()
+        {
+            let stream = self.build_input(input)?;
+            if let Some(input) = &mut self.input {
+                input.stream = Some(stream);
+            }
+        }
+        if self.stream.is_none() {
+            self.stream = Some(self.build()?);
+        }
+        if self.state != StreamState::Running {
+            self.monitor.restart_warmup();
+        }
+        // capture first, so the output's first blocks find input queued
+        if let Some(stream) = self.input.as_ref().and_then(|input| input.stream.as_ref()) {
+            stream.play().map_err(|e| StreamError::PlayFailed(e.to_string()))?;
+        }
+        if let Some(stream) = &self.stream {
+            stream.play().map_err(|e| StreamError::PlayFailed(e.to_string()))?;
+        }
+        self.state = StreamState::Running;
+        Ok(())
+    }
+
+    /// Stop pulling audio but keep the stream, so `start` resumes quickly.
+    /// Not every backend can pause; those report `PauseFailed`.
+    pub fn pause(&mut self) -> StreamResult<()> {
+        let Some(stream) = &self.stream else { return Ok(()) };
+        stream.pause().map_err(|e| StreamError::PauseFailed(e.to_string()))?;
+        if let Some(stream) = self.input.as_ref().and_then(|input| input.stream.as_ref()) {
+            stream.pause().map_err(|e| StreamError::PauseFailed(e.to_string()))?;
+        }
+        self.state = StreamState::Paused;
+        Ok(())
+    }
+
+    /// Close the stream; the next `start` builds a new one
+    pub fn stop(&mut self) {
+        self.stream = None;
+        if let Some(input) = &mut self.input {
+            input.stream = None;
+        }
+        self.state = StreamState::Stopped;
+    }
+
+    /// Close and reopen the stream, e.g. after `DeviceLost` once the device is back
+    pub fn restart(&mut self) -> StreamResult<()> {
+        self.stop();
+        self.take_error();
+        self.start()
+    }
+
+    fn build(&self) -> StreamResult<cpal::Stream> {
+        let format = self.config.sample_format;
+        if format == SampleFormat::F32 {
+            return self.build_f32();
+        }
+        let Some(conversion) = self.config.format_conversion else {
+            return Err(StreamError::ConversionNotAllowed(format));
+        };
+        match format {
+            SampleFormat::I16 => self.build_converted::<i16>(conversion),
+            SampleFormat::I32 => self.build_converted::<i32>(conversion),
+            SampleFormat::U16 => self.build_converted::<u16>(conversion),
+            SampleFormat::F64 => self.build_converted::<f64>(conversion),
+            other => Err(StreamError::UnsupportedFormat(other)),
+        }
+    }
+
+    fn renderer(&self) -> impl FnMut(&mut [f32]) + Send + 'static {
+        renderer(&self.slot, &self.monitor, &self.config, &self.errors)
+    }
+
+    fn build_f32(&self) -> StreamResult<cpal::Stream> {
+        let mut render = self.renderer();
+        self.device
+            .build_output_stream(
+                &self.config.stream_config,
+                move |data: &mut [f32], _| render(data),
+                self.error_callback(),
+                None,
+            )
+            .map_err(|e| StreamError::BuildFailed(e.to_string()))
+    }
+
+    fn build_converted<T: DeviceSample>(&self, conversion: FormatConversion) -> StreamResult<cpal::Stream> {
+        let mut render = self.renderer();
+        let channels = self.config.channels as usize;
+        let mut converter = FormatConverter::new(channels, conversion);
+        let mut scratch = vec![0.0f32; CONVERT_FRAMES * channels];
+        self.device
+            .build_output_stream(
+                &self.config.stream_config,
+                move |data: &mut [T], _| {
+                    for chunk in data.chunks_mut(scratch.len()) {
+                        let block = &mut scratch[..chunk.len()];
+                        render(block);
+                        converter.convert(block, chunk);
+                    }
+                },
+                self.error_callback(),
+                None,
+            )
+            .map_err(|e| StreamError::BuildFailed(e.to_string()))
+    }
+
+    fn build_input(&self, input: &InputSide) -> StreamResult<cpal::Stream> {
+        // shares the output's clock; the output monitor covers the pair
+        build_input_stream(&input.device, &input.config, &input.ring, &self.errors, None)
+    }
+
+    fn error_callback(&self) -> impl FnMut(cpal::StreamError) + Send + 'static {
+        error_callback(&self.errors)
+    }
+}
+
+/// Renders `slot` at the config's device rate, resampling if needed, and
+/// times each call in `monitor`
+pub(super) fn renderer(
+    slot: &Arc<CallbackSlot>,
+    monitor: &Arc<PerformanceMonitor>,
+    config: &NegotiatedConfig,
+    #[cfg_attr(not(feature = "chaos"), allow(unused_variables))] errors: &Arc<Mutex<ErrorState>>,
+) -> impl FnMut(&mut [f32]) + Send + 'static {
+    let slot = Arc::clone(slot);
+    let monitor = Arc::clone(monitor);
+    let channels = config.channels as usize;
+    let mut resampler =
+        config.is_resampled().then(|| Resampler::new(channels, config.engine_sample_rate, config.sample_rate));
+    #[cfg(feature = "chaos")]
+    let errors = Arc::clone(errors);
+    #[cfg(feature = "chaos")]
+    let mut lost = false;
+    move |data: &mut [f32]| {
+        // a simulated lost device reports like a real one, then stays
+        // silent until the stream is restarted (which builds a new renderer)
+        #[cfg(feature = "chaos")]
+        if lost || slot.chaos().is_some_and(|chaos| chaos.disconnect()) {
+            data.fill(0.0);
+            if !lost {
+                lost = true;
+                report_error(&errors, StreamError::DeviceLost);
+            }
+            return;
+        }
+        let _timer = monitor.scoped_callback();
+        monitor.add_frames_processed((data.len() / channels.max(1)) as u64);
+        match &mut resampler {
+            Some(resampler) => resampler.pull(data, |block| {
+                slot.process_realtime(block);
+            }),
+            None => {
+                slot.process_realtime(data);
+            }
+        }
+    }
+}
+
+/// Capture stream from `device` feeding `ring`, converting to f32 as needed;
+/// callbacks are timed by `monitor` when given
+pub(super) fn build_input_stream(
+    device: &cpal::Device,
+    config: &NegotiatedConfig,
+    ring: &Arc<InputRing>,
+    errors: &Arc<Mutex<ErrorState>>,
+    monitor: Option<Arc<PerformanceMonitor>>,
+) -> StreamResult<cpal::Stream> {
+    match config.sample_format {
+        SampleFormat::F32 => build_input_as::<f32>(device, config, ring, errors, monitor),
+        SampleFormat::I16 => build_input_as::<i16>(device, config, ring, errors, monitor),
+        SampleFormat::I32 => build_input_as::<i32>(device, config, ring, errors, monitor),
+        SampleFormat::U16 => build_input_as::<u16>(device, config, ring, errors, monitor),
+        SampleFormat::F64 => build_input_as::<f64>(device, config, ring, errors, monitor),
+        other => Err(StreamError::UnsupportedFormat(other)),
+    }
+}
+
+fn build_input_as<T>(
+    device: &cpal::Device,
+    config: &NegotiatedConfig,
+    ring: &Arc<InputRing>,
+    errors: &Arc<Mutex<ErrorState>>,
+    monitor: Option<Arc<PerformanceMonitor>>,
+) -> StreamResult<cpal::Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let ring = Arc::clone(ring);
+    let channels = config.channels as usize;
+    let mut resampler =
+        config.is_resampled().then(|| Resampler::new(channels, config.sample_rate, config.engine_sample_rate));
+    let mut scratch = vec![0.0f32; CONVERT_FRAMES * channels];
+    device
+        .build_input_stream(
+            &config.stream_config,
+            move |data: &[T], _| {
+                let _timer = monitor.as_ref().map(|monitor| {
+                    monitor.add_frames_processed((data.len() / channels.max(1)) as u64);
+                    monitor.scoped_callback()
+                });
+                match &mut resampler {
+                    Some(resampler) => {
+                        for chunk in data.chunks(scratch.len()) {
+                            let block = &mut scratch[..chunk.len()];
+                            for (out, &sample) in block.iter_mut().zip(chunk) {
+                                *out = f32::from_sample_(sample);
+                            }
+                            resampler.push(block, |converted| ring.write(converted, |s| s));
+                        }
+                    }
+                    None => ring.write(data, f32::from_sample_),
+                }
+            },
+            error_callback(errors),
+            None,
+        )
+        .map_err(|e| StreamError::BuildFailed(e.to_string()))
+}
+
+/// Records backend errors in `errors` and forwards them to its handler
+pub(super) fn error_callback(errors: &Arc<Mutex<ErrorState>>) -> impl FnMut(cpal::StreamError) + Send + 'static {
+    let errors = Arc::clone(errors);
+    move |e| report_error(&errors, StreamError::from(e))
+}
+
+/// Stores `error` as the stream's latest and calls the handler, if any
+fn report_error(errors: &Mutex<ErrorState>, error: StreamError) {
+    trace::device_event("error", &error);
+    let handler = {
+        let mut state = errors.lock();
+        state.error = Some(error.clone());
+        state.handler.clone()
+    };
+    if let Some(handler) = handler {
+        handler(&error);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::audio_device::enumeration::{DeviceEnumerator, DeviceInfo, EnumError, EnumResult};
+use crate::rt_processing::trace;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Default time between rescans
+pub const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub enum DeviceEvent {
+    DeviceAdded(DeviceInfo),
+    DeviceRemoved(DeviceInfo),
+    /// The host's default input or output moved; `None` when there is no
+    /// default device left
+    DefaultChanged { is_input: bool, device: Option<DeviceInfo> },
+}
+
+/// Identity of a device across scans (indices change between scans)
+fn same_device(a: &DeviceInfo, b: &DeviceInfo) -> bool {
+    a.host_id == b.host_id && a.name == b.name && a.is_input == b.is_input && a.is_output == b.is_output
+}
+
+fn scan() -> EnumResult<Vec<DeviceInfo>> {
+    match DeviceEnumerator::new() {
+        Ok(enumerator) => Ok(enumerator.all_devices().into_iter().cloned().collect()),
+        // everything unplugged is a state to report, not an error
+        Err(EnumError::NoDevicesFound) => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Device list from the last scan, diffed against each new one
+struct Snapshot {
+    devices: Vec<DeviceInfo>,
+}
+
+impl Snapshot {
+    fn default_device(&self, is_input: bool) -> Option<&DeviceInfo> {
+        self.devices
+            .iter()
+            .find(|d| d.is_default && if is_input { d.is_input } else { d.is_output })
+    }
+
+    /// Replace the list with `devices` and return what changed
+    fn update(&mut self, devices: Vec<DeviceInfo>) -> Vec<DeviceEvent> {
+        let mut events = Vec::new();
+        for old in &self.devices {
+            if !devices.iter().any(|d| same_device(d, old)) {
+                events.push(DeviceEvent::DeviceRemoved(old.clone()));
+            }
+        }
+        for new in &devices {
+            if !self.devices.iter().any(|d| same_device(d, new)) {
+                events.push(DeviceEvent::DeviceAdded(new.clone()));
+            }
+        }
+
+        let next = Snapshot { devices };
+        for is_input in [false, true] {
+            let before = self.default_device(is_input);
+            let after = next.default_device(is_input);
+            let changed = match (before, after) {
+                (Some(a), Some(b)) => !same_device(a, b),
+                (None, None) => false,
+                _ => true,
+            };
+            if changed {
+                events.push(DeviceEvent::DefaultChanged { is_input, device: after.cloned() });
+            }
+        }
+        *self = next;
+        events
+    }
+}
+
+/// Watches for audio devices coming and going.
+///
+/// cpal has no device-change notifications, so the watcher rescans every
+/// host on a background thread at a fixed interval and sends the differences
+/// as `DeviceEvent`s on a channel. Scans that fail (a host briefly
+/// unavailable) are skipped. The thread stops when the watcher is dropped.
+pub struct DeviceWatcher {
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DeviceWatcher {
+    /// Start watching, taking the current devices as the baseline.
+    /// Returns the watcher and the receiving end of its event channel.
+    pub fn start(interval: Duration) -> EnumResult<(Self, Receiver<DeviceEvent>)> {
+        let mut snapshot = Snapshot { devices: scan()? };
+        let (sender, receiver) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+
+        let flag = Arc::clone(&running);
+        let thread = thread::Builder::new()
+            .name("pulsar-device-watcher".into())
+            .spawn(move || Self::run(&flag, interval, &mut snapshot, &sender))
+            .map_err(|e| EnumError::QueryFailed(format!("Failed to start device watcher: {}", e)))?;
+
+        Ok((Self { running, thread: Some(thread) }, receiver))
+    }
+
+    fn run(running: &AtomicBool, interval: Duration, snapshot: &mut Snapshot, sender: &Sender<DeviceEvent>) {
+        // sleep in short steps so dropping the watcher doesn't wait a whole interval
+        let step = interval.min(Duration::from_millis(50));
+        let mut next_scan = Instant::now() + interval;
+        while running.load(Ordering::Relaxed) {
+            thread::sleep(step);
+            if Instant::now() < next_scan {
+                continue;
+            }
+            next_scan = Instant::now() + interval;
+            let Ok(devices) = scan() else { continue };
+            for event in snapshot.update(devices) {
+                match &event {
+                    DeviceEvent::DeviceAdded(device) => trace::device_event("added", &device.name),
+                    DeviceEvent::DeviceRemoved(device) => trace::device_event("removed", &device.name),
+                    DeviceEvent::DefaultChanged { device, .. } => {
+                        let name = device.as_ref().map_or("none", |d| d.name.as_str());
+                        trace::device_event("default_changed", &name);
+                    }
+                }
+                if sender.send(event).is_err() {
+                    // nobody is listening anymore
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|t| !t.is_finished())
+    }
+
+    /// Stop the background thread and wait for it
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for DeviceWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,74 @@
+//! Headless engine process for `pulsar_client`.
+//!
+//! Usage: `pulsar-engine [ENDPOINT]`, where ENDPOINT is a socket path or
+//! `host:port` (default: `Endpoint::default_local()`). Plays through the
+//! default output device and runs until a client sends `Shutdown`.
+
+use cpal::traits::{DeviceTrait, StreamTrait};
+use pulsar_backend::audio_device::enumeration::DeviceEnumerator;
+use pulsar_backend::engine::{Engine, EngineConfig};
+use pulsar_backend::remote::{RemoteOutput, RemoteServer};
+use pulsar_client::Endpoint;
+
+fn open_output(output: RemoteOutput) -> Result<(cpal::Stream, EngineConfig), Box<dyn std::error::Error>> {
+    let enumerator = DeviceEnumerator::new()?;
+    let info = enumerator.default_output_device()?;
+    let device = enumerator.select_device(info)?;
+    let supported = device.default_output_config()?;
+    if supported.sample_format() != cpal::SampleFormat::F32 {
+        return Err(format!("unsupported sample format {:?}", supported.sample_format()).into());
+    }
+    let stream_config = supported.config();
+    let stream = device.build_output_stream(
+        &stream_config,
+        move |data: &mut [f32], _| {
+            output.process(data);
+        },
+        |err| eprintln!("pulsar-engine: stream error: {}", err),
+        None,
+    )?;
+    stream.play()?;
+    let config = EngineConfig {
+        sample_rate: stream_config.sample_rate.0 as f32,
+        channels: stream_config.channels as usize,
+        ..EngineConfig::default()
+    };
+    Ok((stream, config))
+}
+
+fn main() {
+    let endpoint = match std::env::args().nth(1) {
+        Some(arg) => Endpoint::parse(&arg).unwrap_or_else(|e| {
+            eprintln!("pulsar-engine: {}", e);
+            std::process::exit(2);
+        }),
+        None => Endpoint::default_local(),
+    };
+
+    let server = RemoteServer::new(Engine::new());
+    // without a device the engine still serves clients, it just isn't heard
+    let (stream, config) = match open_output(server.output()) {
+        Ok((stream, config)) => (Some(stream), config),
+        Err(e) => {
+            eprintln!("pulsar-engine: no audio output ({}), running silent", e);
+            (None, EngineConfig::default())
+        }
+    };
+    if let Err(e) = server.with_engine(|engine| engine.configure(config)) {
+        eprintln!("pulsar-engine: {}", e);
+        std::process::exit(1);
+    }
+
+    let listener = match endpoint.bind() {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("pulsar-engine: cannot listen on {:?}: {}", endpoint, e);
+            std::process::exit(1);
+        }
+    };
+    eprintln!("pulsar-engine: listening on {:?}", endpoint);
+    if let Err(e) = server.serve(listener) {
+        eprintln!("pulsar-engine: {}", e);
+    }
+    drop(stream);
+}
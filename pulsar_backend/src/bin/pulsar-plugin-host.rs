@@ -0,0 +1,9 @@
+//! Sandbox process for bridged plugins, started by the engine
+//! (`pulsar_backend::plugins::BridgedPlugin`); not meant to be run by hand.
+
+fn main() {
+    if let Err(e) = pulsar_backend::plugins::bridge::run_host(std::env::args().skip(1)) {
+        eprintln!("pulsar-plugin-host: {}", e);
+        std::process::exit(1);
+    }
+}
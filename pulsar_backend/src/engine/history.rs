@@ -0,0 +1,323 @@
+use std::collections::VecDeque;
+
+use crate::project::{
+    NodeDescriptor, ParamValue, ProjectError, ProjectFile, ProjectResult, SendDescriptor, SourceDescriptor,
+};
+use crate::rt_processing::filters::Trim;
+
+/// Undo steps kept by default
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// A project node addressed by position
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NodeTarget {
+    Source(usize),
+    BusEffect { bus: usize, index: usize },
+}
+
+/// An undoable edit of the graph, applied through `Engine::edit`
+#[derive(Clone, Debug, PartialEq)]
+pub enum EditCommand {
+    AddSource(SourceDescriptor),
+    RemoveSource(usize),
+    /// Set a batch of parameters on one node
+    SetParams { target: NodeTarget, params: Vec<(String, ParamValue)> },
+    SetSourceMix { source: usize, gain: f32, pan: f32 },
+    SetSourceBus { source: usize, bus: usize },
+    SetSourceTrims { source: usize, high_pass: Option<Trim>, low_pass: Option<Trim> },
+    /// Replace the aux sends of a source
+    SetSourceSends { source: usize, sends: Vec<SendDescriptor> },
+    SetBusReturn { bus: usize, gain: f32 },
+    /// Insert an effect at `index` on `bus`; `None` appends
+    AddBusEffect { bus: usize, index: Option<usize>, node: NodeDescriptor },
+    RemoveBusEffect { bus: usize, index: usize },
+    MoveBusEffect { bus: usize, from: usize, to: usize },
+}
+
+impl EditCommand {
+    /// Short description for undo/redo menus
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::AddSource(_) => "Add source",
+            Self::RemoveSource(_) => "Remove source",
+            Self::SetParams { .. } => "Change parameters",
+            Self::SetSourceMix { .. } => "Change source mix",
+            Self::SetSourceBus { .. } => "Change source routing",
+            Self::SetSourceTrims { .. } => "Change source trims",
+            Self::SetSourceSends { .. } => "Change source sends",
+            Self::SetBusReturn { .. } => "Change bus return",
+            Self::AddBusEffect { .. } => "Add effect",
+            Self::RemoveBusEffect { .. } => "Remove effect",
+            Self::MoveBusEffect { .. } => "Move effect",
+        }
+    }
+
+    /// Apply the edit to a project document. Node kinds and parameter values
+    /// are checked when the engine rebuilds the graph.
+    pub fn apply(&self, project: &mut ProjectFile) -> ProjectResult<()> {
+        let num_buses = project.buses.len();
+        let check_bus = |bus: usize| if bus < num_buses { Ok(()) } else { Err(ProjectError::InvalidBus(bus)) };
+        // sends and returns only exist on aux buses
+        let check_send = |bus: usize| if bus > 0 { check_bus(bus) } else { Err(ProjectError::InvalidBus(bus)) };
+
+        match self {
+            Self::AddSource(source) => {
+                check_bus(source.bus)?;
+                source.sends.iter().try_for_each(|send| check_send(send.bus))?;
+                project.sources.push(source.clone());
+            }
+            Self::RemoveSource(index) => {
+                source_mut(project, *index)?;
+                project.sources.remove(*index);
+            }
+            Self::SetParams { target, params } => {
+                let node = match *target {
+                    NodeTarget::Source(index) => &mut source_mut(project, index)?.node,
+                    NodeTarget::BusEffect { bus, index } => effect_mut(project, bus, index)?,
+                };
+                for (name, value) in params {
+                    node.params.insert(name.clone(), value.clone());
+                }
+            }
+            Self::SetSourceMix { source, gain, pan } => {
+                let source = source_mut(project, *source)?;
+                source.gain = *gain;
+                source.pan = pan.clamp(-1.0, 1.0);
+            }
+            Self::SetSourceBus { source, bus } => {
+                check_bus(*bus)?;
+                source_mut(project, *source)?.bus = *bus;
+            }
+            Self::SetSourceTrims { source, high_pass, low_pass } => {
+                let source = source_mut(project, *source)?;
+                source.high_pass = *high_pass;
+                source.low_pass = *low_pass;
+            }
+            Self::SetSourceSends { source, sends } => {
+                sends.iter().try_for_each(|send| check_send(send.bus))?;
+                source_mut(project, *source)?.sends = sends.clone();
+            }
+            Self::SetBusReturn { bus, gain } => {
+                check_send(*bus)?;
+                project.buses[*bus].return_gain = gain.max(0.0);
+            }
+            Self::AddBusEffect { bus, index, node } => {
+                check_bus(*bus)?;
+                let effects = &mut project.buses[*bus].effects;
+                let index = index.unwrap_or(effects.len()).min(effects.len());
+                effects.insert(index, node.clone());
+            }
+            Self::RemoveBusEffect { bus, index } => {
+                effect_mut(project, *bus, *index)?;
+                project.buses[*bus].effects.remove(*index);
+            }
+            Self::MoveBusEffect { bus, from, to } => {
+                effect_mut(project, *bus, *from)?;
+                let effects = &mut project.buses[*bus].effects;
+                let node = effects.remove(*from);
+                effects.insert((*to).min(effects.len()), node);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parameter changes on any number of nodes, applied together by
+/// `Engine::apply_params` (a preset, a macro knob). Changes to one node merge
+/// into a single `SetParams`; a parameter set twice keeps the later value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParamBatch {
+    nodes: Vec<(NodeTarget, Vec<(String, ParamValue)>)>,
+}
+
+impl ParamBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_param(mut self, target: NodeTarget, name: impl Into<String>, value: impl Into<ParamValue>) -> Self {
+        self.set(target, name, value);
+        self
+    }
+
+    pub fn set(&mut self, target: NodeTarget, name: impl Into<String>, value: impl Into<ParamValue>) {
+        let (name, value) = (name.into(), value.into());
+        let params = match self.nodes.iter().position(|(t, _)| *t == target) {
+            Some(index) => &mut self.nodes[index].1,
+            None => {
+                self.nodes.push((target, Vec::new()));
+                &mut self.nodes.last_mut().unwrap().1
+            }
+        };
+        match params.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => *existing = value,
+            None => params.push((name, value)),
+        }
+    }
+
+    /// Parameters changed, over all nodes
+    pub fn len(&self) -> usize {
+        self.nodes.iter().map(|(_, params)| params.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// One `SetParams` per node, in the order the nodes were first set
+    pub fn into_commands(self) -> Vec<EditCommand> {
+        self.nodes.into_iter().map(|(target, params)| EditCommand::SetParams { target, params }).collect()
+    }
+}
+
+fn source_mut(project: &mut ProjectFile, index: usize) -> ProjectResult<&mut SourceDescriptor> {
+    project.sources.get_mut(index).ok_or(ProjectError::InvalidSource(index))
+}
+
+fn effect_mut(project: &mut ProjectFile, bus: usize, index: usize) -> ProjectResult<&mut NodeDescriptor> {
+    let effects = &mut project.buses.get_mut(bus).ok_or(ProjectError::InvalidBus(bus))?.effects;
+    effects.get_mut(index).ok_or(ProjectError::InvalidEffect { bus, index })
+}
+
+/// One undo step: the project before and after
+#[derive(Clone, Debug)]
+struct Edit {
+    label: String,
+    before: ProjectFile,
+    after: ProjectFile,
+}
+
+struct OpenGroup {
+    depth: usize,
+    edit: Edit,
+}
+
+/// Undo/redo stacks of project snapshots.
+///
+/// Each step stores the document before and after, so undo and redo are a
+/// rebuild from a known-good state rather than an inverse command. Groups
+/// merge every edit recorded while open into one step; they nest, and only
+/// the outermost `end_group` closes the step. The oldest steps are dropped
+/// beyond `limit`.
+pub struct EditHistory {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    limit: usize,
+    group: Option<OpenGroup>,
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_LIMIT)
+    }
+}
+
+impl EditHistory {
+    pub fn new(limit: usize) -> Self {
+        Self { undo: VecDeque::new(), redo: Vec::new(), limit, group: None }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Change the number of steps kept, dropping the oldest if needed
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo_label(&self) -> Option<&str> {
+        self.undo.back().map(|e| e.label.as_str())
+    }
+
+    pub fn redo_label(&self) -> Option<&str> {
+        self.redo.last().map(|e| e.label.as_str())
+    }
+
+    pub fn is_grouping(&self) -> bool {
+        self.group.is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.group = None;
+    }
+
+    /// Record a change from `before` to `after`. Clears the redo stack.
+    pub fn record(&mut self, label: &str, before: &ProjectFile, after: &ProjectFile) {
+        self.redo.clear();
+        match &mut self.group {
+            Some(group) => group.edit.after = after.clone(),
+            None => self.push(Edit { label: label.to_string(), before: before.clone(), after: after.clone() }),
+        }
+    }
+
+    /// Start merging edits into one step labelled `label`
+    pub fn begin_group(&mut self, label: &str, current: &ProjectFile) {
+        match &mut self.group {
+            Some(group) => group.depth += 1,
+            None => {
+                let edit = Edit { label: label.to_string(), before: current.clone(), after: current.clone() };
+                self.group = Some(OpenGroup { depth: 1, edit });
+            }
+        }
+    }
+
+    /// Close the innermost group. A group that changed nothing leaves no step.
+    pub fn end_group(&mut self) {
+        let Some(group) = &mut self.group else { return };
+        group.depth -= 1;
+        if group.depth == 0
+            && let Some(group) = self.group.take()
+            && group.edit.before != group.edit.after
+        {
+            self.push(group.edit);
+        }
+    }
+
+    /// Move the last step to the redo stack and return the project to go back
+    /// to. Closes any open group first.
+    pub fn undo(&mut self) -> Option<ProjectFile> {
+        self.close_groups();
+        let edit = self.undo.pop_back()?;
+        let before = edit.before.clone();
+        self.redo.push(edit);
+        Some(before)
+    }
+
+    /// Move the last undone step back and return the project to go forward to
+    pub fn redo(&mut self) -> Option<ProjectFile> {
+        self.close_groups();
+        let edit = self.redo.pop()?;
+        let after = edit.after.clone();
+        self.undo.push_back(edit);
+        Some(after)
+    }
+
+    fn close_groups(&mut self) {
+        while self.group.is_some() {
+            self.end_group();
+        }
+    }
+
+    fn push(&mut self, edit: Edit) {
+        self.undo.push_back(edit);
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        while self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+    }
+}
@@ -0,0 +1,929 @@
+pub mod history;
+pub mod state;
+
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+use crossbeam::atomic::AtomicCell;
+use crossbeam::channel::{self, Receiver, Sender};
+use spin::Mutex;
+
+use crate::jobs::{PoolConfig, WorkerPool};
+use crate::project::{
+    AutomationLane, BusDescriptor, DevicePreferences, NodeContext, NodeDescriptor, NodeRegistry, ProjectError,
+    ProjectFile, ProjectResult, SourceDescriptor, TransportSettings,
+};
+use crate::rt_processing::black_box::{self, BlackBox};
+use crate::rt_processing::effects::Effect;
+use crate::rt_processing::effects::chain::{CHAIN_CAPACITY, EffectChain};
+use crate::rt_processing::metering::Meters;
+use crate::rt_processing::modulation::ModulationMonitor;
+use crate::rt_processing::prefault::{Prefault, PrefaultMode, PrefaultReport};
+use crate::rt_processing::routing::{AudioSource, Pan, RouterCommand, RouterCommands, SourceId};
+use crate::rt_processing::transport::Transport;
+use crate::rt_processing::callback::{AudioCallback, CallbackSlot};
+#[cfg(feature = "chaos")]
+use crate::rt_processing::chaos::Chaos;
+use crate::rt_processing::voice_renderer::VoiceProcessor;
+
+pub use history::{EditCommand, EditHistory, NodeTarget, ParamBatch};
+pub use state::{EngineError, EngineEvent, EngineResult, EngineState};
+
+/// Settings applied by `Engine::configure`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EngineConfig {
+    pub sample_rate: f32,
+    pub channels: usize,
+    /// Largest block the audio path is called with
+    pub max_frames: usize,
+    pub num_buses: usize,
+    /// Size of the background worker pool
+    pub pool: PoolConfig,
+    /// What `start` does with the graph's memory before playback
+    pub prefault: PrefaultMode,
+    /// Seconds of master audio the black box keeps (see `black_box`); 0.0
+    /// keeps events only
+    pub black_box_seconds: f32,
+    /// Noise added ahead of every effect chain so decaying feedback doesn't
+    /// end up in denormals, in dBFS (see `rt_processing::denormal`); `None`
+    /// adds nothing
+    pub denormal_floor_db: Option<f32>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 48000.0,
+            channels: 2,
+            max_frames: 1024,
+            num_buses: 4,
+            pool: PoolConfig::default(),
+            prefault: PrefaultMode::default(),
+            black_box_seconds: 0.0,
+            denormal_floor_db: None,
+        }
+    }
+}
+
+impl EngineConfig {
+    fn validate(&self) -> EngineResult<()> {
+        if !self.sample_rate.is_finite() || self.sample_rate <= 0.0 {
+            return Err(EngineError::InvalidConfig(format!("sample rate {}", self.sample_rate)));
+        }
+        if self.channels == 0 || self.max_frames == 0 {
+            return Err(EngineError::InvalidConfig("channels and max_frames must be non-zero".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Nodes built for a graph update, applied in one go
+#[derive(Default)]
+struct GraphChanges {
+    /// (bus, change) for each changed bus chain
+    buses: Vec<(usize, ChainChange)>,
+    /// (bus, gain) for each changed return
+    returns: Vec<(usize, f32)>,
+    /// Sources kept in place whose descriptor changed
+    edits: Vec<SourceEdit>,
+    /// Positions in the source list replaced by `added`
+    removed: Range<usize>,
+    added: Vec<(SourceDescriptor, Box<dyn AudioSource>, Effects)>,
+}
+
+/// Built effects, in chain order
+type Effects = Vec<Box<dyn Effect>>;
+
+/// New effects for an insert chain
+enum ChainChange {
+    /// In place of the run in `range`, see `EffectChain::splice`; `effects`
+    /// has room for the replaced ones as well
+    Splice { range: Range<usize>, effects: Effects },
+    /// The whole chain, once it outgrows `CHAIN_CAPACITY`
+    Replace(Effects),
+}
+
+impl ChainChange {
+    fn effects(&self) -> &[Box<dyn Effect>] {
+        match self {
+            Self::Splice { effects, .. } | Self::Replace(effects) => effects,
+        }
+    }
+}
+
+/// Changes to the source at `index`, going from `from` to `to`
+struct SourceEdit {
+    index: usize,
+    from: SourceDescriptor,
+    to: SourceDescriptor,
+    /// Rebuilt node, when its descriptor changed
+    node: Option<Box<dyn AudioSource>>,
+    effects: Vec<ChainChange>,
+}
+
+/// Puts the shared voice processor into the callback slot
+struct SharedProcessor(Arc<Mutex<VoiceProcessor>>);
+
+impl AudioCallback for SharedProcessor {
+    fn process(&mut self, output: &mut [f32], sample_rate: f32, channels: usize, frames: usize) {
+        match self.0.try_lock() {
+            Some(mut processor) => processor.process(output, sample_rate, channels, frames),
+            None => output.fill(0.0),
+        }
+    }
+}
+
+/// Channels covered by the output meter
+pub const METER_CHANNELS: usize = 8;
+
+/// Output levels of the last rendered block, linear, per channel
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MeterReading {
+    pub channels: usize,
+    pub peak: [f32; METER_CHANNELS],
+    pub rms: [f32; METER_CHANNELS],
+}
+
+impl MeterReading {
+    fn measure(output: &[f32], channels: usize) -> Self {
+        let mut reading = Self { channels: channels.min(METER_CHANNELS), ..Self::default() };
+        if channels == 0 || output.len() < channels {
+            return reading;
+        }
+        for frame in output.chunks_exact(channels) {
+            for (ch, &x) in frame.iter().take(METER_CHANNELS).enumerate() {
+                reading.peak[ch] = reading.peak[ch].max(x.abs());
+                reading.rms[ch] += x * x;
+            }
+        }
+        let frames = (output.len() / channels) as f32;
+        for rms in &mut reading.rms[..reading.channels] {
+            *rms = (*rms / frames).sqrt();
+        }
+        reading
+    }
+}
+
+/// The latest `MeterReading`, one atomic per field so the audio thread can
+/// publish it lock-free. A read racing a store may mix channels from two
+/// consecutive blocks, which a meter doesn't mind.
+#[derive(Default)]
+pub struct OutputMeter {
+    channels: AtomicUsize,
+    /// f32 bits
+    peak: [AtomicU32; METER_CHANNELS],
+    rms: [AtomicU32; METER_CHANNELS],
+}
+
+impl OutputMeter {
+    /// RT-safe
+    pub fn store(&self, reading: &MeterReading) {
+        for (level, value) in self.peak.iter().zip(reading.peak) {
+            level.store(value.to_bits(), Ordering::Relaxed);
+        }
+        for (level, value) in self.rms.iter().zip(reading.rms) {
+            level.store(value.to_bits(), Ordering::Relaxed);
+        }
+        self.channels.store(reading.channels, Ordering::Release);
+    }
+
+    pub fn load(&self) -> MeterReading {
+        let channels = self.channels.load(Ordering::Acquire);
+        MeterReading {
+            channels,
+            peak: std::array::from_fn(|ch| f32::from_bits(self.peak[ch].load(Ordering::Relaxed))),
+            rms: std::array::from_fn(|ch| f32::from_bits(self.rms[ch].load(Ordering::Relaxed))),
+        }
+    }
+}
+
+/// Audio-thread side of the engine. Cheap to clone into a device callback.
+/// A handle belongs to one configuration: after `configure` runs again it only
+/// outputs silence, and a new handle must be fetched.
+#[derive(Clone)]
+pub struct EngineAudioHandle {
+    state: Arc<AtomicCell<EngineState>>,
+    generation: Arc<AtomicU64>,
+    handle_generation: u64,
+    slot: Arc<CallbackSlot>,
+    channels: usize,
+    meter: Arc<OutputMeter>,
+    black_box: &'static BlackBox,
+}
+
+impl EngineAudioHandle {
+    /// RT entry point: renders while the engine is `Running`, silence otherwise.
+    /// Returns whether audio was rendered. Never blocks or allocates.
+    pub fn process(&self, output: &mut [f32]) -> bool {
+        let current = self.generation.load(Ordering::Acquire) == self.handle_generation;
+        let rendered = if current && self.state.load().is_rendering() {
+            self.slot.process_realtime(output)
+        } else {
+            output.fill(0.0);
+            false
+        };
+        if current {
+            self.meter.store(&MeterReading::measure(output, self.channels));
+            self.black_box.capture(output, self.channels);
+        }
+        rendered
+    }
+}
+
+/// Lets a handle drive a `CallbackSlot` of its own, as streams take
+impl AudioCallback for EngineAudioHandle {
+    fn process(&mut self, output: &mut [f32], _sample_rate: f32, _channels: usize, _frames: usize) {
+        EngineAudioHandle::process(self, output);
+    }
+}
+
+/// Engine lifecycle owner.
+///
+/// Holds the processing graph and moves through `EngineState` with validated
+/// transitions, so operations that need a configured graph can't be called in
+/// the wrong order. Subscribers get an `EngineEvent` after each transition.
+///
+/// Sources and effects added through `edit` (or `add_source_node` /
+/// `add_bus_effect_node`) are recorded in a `ProjectFile` that `snapshot`
+/// returns and `restore` rebuilds, and can be undone. An edit changes the
+/// running graph in place: mix settings are set on the live sources, and only
+/// nodes whose descriptor changed are rebuilt, so the rest keep their state.
+/// Edits reach the audio thread through the router's command queue, whole at
+/// one block boundary, and never lock the processor the callback renders.
+/// Anything added directly through `with_processor` is not saved, and effects
+/// added that way shift the chain positions edits address.
+///
+/// Creating an engine starts the global black box (`black_box::global`), and
+/// its audio handles feed it the master output.
+pub struct Engine {
+    state: Arc<AtomicCell<EngineState>>,
+    // bumped by every configure/stop, invalidating older audio handles
+    generation: Arc<AtomicU64>,
+    config: Option<EngineConfig>,
+    processor: Option<Arc<Mutex<VoiceProcessor>>>,
+    // queue to the processor's router, which edits go through
+    commands: Option<RouterCommands>,
+    slot: Option<Arc<CallbackSlot>>,
+    subscribers: Vec<Sender<EngineEvent>>,
+    registry: NodeRegistry,
+    project: ProjectFile,
+    history: EditHistory,
+    // router id of each project source, in project order
+    source_ids: Vec<SourceId>,
+    meter: Arc<OutputMeter>,
+    // per bus, one entry per effect in the chain
+    modulation: Vec<Vec<Option<ModulationMonitor>>>,
+    prefault_report: Option<PrefaultReport>,
+    // driven by the current router, following the project's tempo and meter
+    clock: Option<Transport>,
+    #[cfg(feature = "chaos")]
+    chaos: Option<Arc<Chaos>>,
+    // background jobs: loads, scans, offline renders, batches
+    pool: WorkerPool,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        black_box::global();
+        Self {
+            state: Arc::new(AtomicCell::new(EngineState::Created)),
+            generation: Arc::new(AtomicU64::new(0)),
+            config: None,
+            processor: None,
+            commands: None,
+            slot: None,
+            subscribers: Vec::new(),
+            registry: NodeRegistry::with_builtins(),
+            project: ProjectFile::default(),
+            history: EditHistory::default(),
+            source_ids: Vec::new(),
+            meter: Arc::new(OutputMeter::default()),
+            modulation: Vec::new(),
+            prefault_report: None,
+            clock: None,
+            #[cfg(feature = "chaos")]
+            chaos: None,
+            pool: WorkerPool::new(PoolConfig::default()),
+        }
+    }
+
+    pub fn state(&self) -> EngineState {
+        self.state.load()
+    }
+
+    pub fn config(&self) -> Option<&EngineConfig> {
+        self.config.as_ref()
+    }
+
+    /// Worker pool for this engine's background jobs, sized by
+    /// `EngineConfig::pool` from the next `configure` on
+    pub fn pool(&self) -> &WorkerPool {
+        &self.pool
+    }
+
+    /// Inject faults into the audio path from the next `configure` on, or stop
+    /// with `None`; for soak tests only
+    #[cfg(feature = "chaos")]
+    pub fn set_chaos(&mut self, chaos: Option<Arc<Chaos>>) {
+        self.chaos = chaos;
+    }
+
+    /// Receive an event for every future transition
+    pub fn subscribe(&mut self) -> Receiver<EngineEvent> {
+        let (tx, rx) = channel::unbounded();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Build the processing graph. Valid from Created, Configured, Suspended and
+    /// Stopped; reconfiguring replaces the graph and drops all sources and
+    /// effects and clears the undo history. Transport, device preferences and
+    /// automation are kept.
+    pub fn configure(&mut self, config: EngineConfig) -> EngineResult<()> {
+        self.check(EngineState::Configured)?;
+        config.validate()?;
+
+        self.project.engine = config.into();
+        self.project.buses = vec![BusDescriptor::default(); config.num_buses.max(1)];
+        self.project.sources.clear();
+        self.source_ids.clear();
+        self.history.clear();
+
+        self.pool.resize(config.pool);
+        black_box::global().set_audio(config.black_box_seconds, config.sample_rate, config.channels);
+        let mut processor = VoiceProcessor::new(config.channels, config.sample_rate, config.max_frames, config.num_buses);
+        processor.router_mut().set_noise_floor(config.denormal_floor_db);
+        let clock = Transport::new(config.sample_rate)
+            .with_tempo(self.project.transport.tempo_bpm)
+            .with_signature(self.project.transport.signature());
+        processor.router_mut().set_transport(Some(clock.clone()));
+        let commands = processor.router().commands();
+        let processor = Arc::new(Mutex::new(processor));
+        let slot = CallbackSlot::new(
+            Box::new(SharedProcessor(Arc::clone(&processor))),
+            config.sample_rate,
+            config.channels,
+        );
+        #[cfg(feature = "chaos")]
+        let slot = match &self.chaos {
+            Some(chaos) => slot.with_chaos(Arc::clone(chaos)),
+            None => slot,
+        };
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.processor = Some(processor);
+        self.commands = Some(commands);
+        self.slot = Some(Arc::new(slot));
+        self.config = Some(config);
+        self.clock = Some(clock);
+        self.modulation = vec![Vec::new(); config.num_buses.max(1)];
+        self.transition(EngineState::Configured)
+    }
+
+    pub fn start(&mut self) -> EngineResult<()> {
+        if self.state() != EngineState::Configured {
+            return Err(EngineError::InvalidTransition { from: self.state(), to: EngineState::Running });
+        }
+        self.prefault();
+        self.transition(EngineState::Running)
+    }
+
+    /// Outcome of the memory pass run by the last `start`, if it ran one
+    pub fn prefault_report(&self) -> Option<&PrefaultReport> {
+        self.prefault_report.as_ref()
+    }
+
+    // before the first callback, so the audio thread doesn't take the page faults
+    fn prefault(&mut self) {
+        self.prefault_report = match (self.config, &self.processor) {
+            (Some(config), Some(processor)) if config.prefault != PrefaultMode::Off => {
+                let mut memory = Prefault::new(config.prefault, config.channels, config.max_frames);
+                processor.lock().prefault(&mut memory);
+                Some(memory.finish())
+            }
+            _ => None,
+        };
+    }
+
+    pub fn suspend(&mut self) -> EngineResult<()> {
+        self.transition(EngineState::Suspended)
+    }
+
+    pub fn resume(&mut self) -> EngineResult<()> {
+        if self.state() != EngineState::Suspended {
+            return Err(EngineError::InvalidTransition { from: self.state(), to: EngineState::Running });
+        }
+        self.transition(EngineState::Running)
+    }
+
+    /// Shut down and release the graph
+    pub fn stop(&mut self) -> EngineResult<()> {
+        self.transition(EngineState::Stopped)?;
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.processor = None;
+        self.commands = None;
+        self.slot = None;
+        self.clock = None;
+        self.modulation.clear();
+        self.source_ids.clear();
+        self.meter.store(&MeterReading::default());
+        Ok(())
+    }
+
+    /// Handle for the device callback. Fails before the engine is configured.
+    pub fn audio_handle(&self) -> EngineResult<EngineAudioHandle> {
+        match &self.slot {
+            Some(slot) => Ok(EngineAudioHandle {
+                state: Arc::clone(&self.state),
+                generation: Arc::clone(&self.generation),
+                handle_generation: self.generation.load(Ordering::Acquire),
+                slot: Arc::clone(slot),
+                channels: self.config.map_or(0, |c| c.channels),
+                meter: Arc::clone(&self.meter),
+                black_box: black_box::global(),
+            }),
+            None => Err(EngineError::NotConfigured(self.state())),
+        }
+    }
+
+    /// Run `f` on the voice processor (add sources, bus effects, ...), with
+    /// every queued edit applied. Available once configured; blocks the audio
+    /// path for the duration of `f`, which edits never do.
+    pub fn with_processor<R>(&self, f: impl FnOnce(&mut VoiceProcessor) -> R) -> EngineResult<R> {
+        match &self.processor {
+            Some(processor) => {
+                let mut processor = processor.lock();
+                processor.router_mut().apply_commands();
+                Ok(f(&mut processor))
+            }
+            None => Err(EngineError::NotConfigured(self.state())),
+        }
+    }
+
+    /// Output levels, updated by the current audio handle after every block.
+    /// Shared for the engine's lifetime, so it survives reconfiguration.
+    pub fn meter_handle(&self) -> Arc<OutputMeter> {
+        Arc::clone(&self.meter)
+    }
+
+    /// Peak and RMS of every source and bus, see `rt_processing::metering`,
+    /// and the output loudness (`rt_processing::loudness::LoudnessMeter`).
+    /// Belongs to the current configuration, like an audio handle.
+    pub fn meters(&self) -> EngineResult<Meters> {
+        self.with_processor(|processor| processor.router().meters())
+    }
+
+    /// Modulation readings of effect `index` on `bus`, for effects that
+    /// modulate parameters internally. Valid until an edit rebuilds that effect.
+    pub fn effect_modulation(&self, bus: usize, index: usize) -> Option<ModulationMonitor> {
+        self.modulation.get(bus)?.get(index)?.clone()
+    }
+
+    /// Frames rendered since the last `configure`
+    pub fn frame_count(&self) -> u64 {
+        self.slot.as_ref().map_or(0, |slot| slot.frame_count())
+    }
+
+    /// Factories used to build project nodes
+    pub fn registry(&self) -> &NodeRegistry {
+        &self.registry
+    }
+
+    pub fn registry_mut(&mut self) -> &mut NodeRegistry {
+        &mut self.registry
+    }
+
+    /// Build a source from the registry, route it and record it in the project.
+    /// Undoable; shorthand for `edit(EditCommand::AddSource(..))`.
+    pub fn add_source_node(&mut self, source: SourceDescriptor) -> ProjectResult<()> {
+        self.edit(EditCommand::AddSource(source))
+    }
+
+    /// Build an effect from the registry and append it to `bus`. Undoable.
+    pub fn add_bus_effect_node(&mut self, bus: usize, node: NodeDescriptor) -> ProjectResult<()> {
+        self.edit(EditCommand::AddBusEffect { bus, index: None, node })
+    }
+
+    /// Apply an edit to the graph and record it for undo. Nothing changes if
+    /// the edit is invalid or a node fails to build.
+    pub fn edit(&mut self, command: EditCommand) -> ProjectResult<()> {
+        self.edit_atomic(command.label(), [command])
+    }
+
+    /// Apply several edits as one undo step, all at the same block boundary:
+    /// every edit is checked and the nodes built before the graph changes, in
+    /// one go, so no block plays a partial result. Unlike `edit_batch`, nothing
+    /// changes if any edit fails.
+    pub fn edit_atomic(&mut self, label: &str, commands: impl IntoIterator<Item = EditCommand>) -> ProjectResult<()> {
+        let mut next = self.project.clone();
+        for command in commands {
+            command.apply(&mut next)?;
+        }
+        if next == self.project {
+            return Ok(());
+        }
+        self.sync_graph(&next)?;
+        self.history.record(label, &self.project, &next);
+        self.project = next;
+        Ok(())
+    }
+
+    /// Set a group of parameters together, e.g. a preset; see `edit_atomic`
+    pub fn apply_params(&mut self, label: &str, batch: ParamBatch) -> ProjectResult<()> {
+        self.edit_atomic(label, batch.into_commands())
+    }
+
+    /// Apply several edits as one undo step; stops at the first failing one
+    /// (earlier ones stay applied, and undo as a group). Each edit reaches the
+    /// graph on its own; use `edit_atomic` when they must be heard together.
+    pub fn edit_batch(&mut self, label: &str, commands: impl IntoIterator<Item = EditCommand>) -> ProjectResult<()> {
+        self.begin_group(label);
+        let result = commands.into_iter().try_for_each(|command| self.edit(command));
+        self.end_group();
+        result
+    }
+
+    /// Merge every edit until the matching `end_group` into one undo step
+    pub fn begin_group(&mut self, label: &str) {
+        self.history.begin_group(label, &self.project);
+    }
+
+    pub fn end_group(&mut self) {
+        self.history.end_group();
+    }
+
+    /// Revert the last edit or group. Returns `false` if there was nothing to undo.
+    pub fn undo(&mut self) -> ProjectResult<bool> {
+        let Some(previous) = self.history.undo() else { return Ok(false) };
+        if let Err(e) = self.sync_graph(&previous) {
+            self.history.redo();
+            return Err(e);
+        }
+        self.project = previous;
+        Ok(true)
+    }
+
+    /// Re-apply the last undone edit. Returns `false` if there was nothing to redo.
+    pub fn redo(&mut self) -> ProjectResult<bool> {
+        let Some(next) = self.history.redo() else { return Ok(false) };
+        if let Err(e) = self.sync_graph(&next) {
+            self.history.undo();
+            return Err(e);
+        }
+        self.project = next;
+        Ok(true)
+    }
+
+    /// Undo/redo stacks, for labels and limits
+    pub fn history(&self) -> &EditHistory {
+        &self.history
+    }
+
+    pub fn history_mut(&mut self) -> &mut EditHistory {
+        &mut self.history
+    }
+
+    /// Name a bus in the project
+    pub fn set_bus_name(&mut self, bus: usize, name: impl Into<String>) -> ProjectResult<()> {
+        let descriptor = self.project.buses.get_mut(bus).ok_or(ProjectError::InvalidBus(bus))?;
+        descriptor.name = name.into();
+        Ok(())
+    }
+
+    pub fn transport(&self) -> TransportSettings {
+        self.project.transport
+    }
+
+    /// Tempo and meter. Tempo-synced nodes pick up the tempo when built, so
+    /// this should be set before adding them.
+    pub fn set_transport(&mut self, transport: TransportSettings) {
+        self.project.transport = transport;
+        self.sync_clock();
+    }
+
+    /// Playhead of the current configuration, for sources to read and the app
+    /// to play, stop and locate; tempo and meter follow `set_transport`
+    pub fn clock(&self) -> EngineResult<Transport> {
+        self.clock.clone().ok_or(EngineError::NotConfigured(self.state()))
+    }
+
+    fn sync_clock(&self) {
+        if let Some(clock) = &self.clock {
+            clock.set_tempo(self.project.transport.tempo_bpm);
+            clock.set_signature(self.project.transport.signature());
+        }
+    }
+
+    pub fn device_preferences(&self) -> &DevicePreferences {
+        &self.project.device
+    }
+
+    pub fn set_device_preferences(&mut self, device: DevicePreferences) {
+        self.project.device = device;
+    }
+
+    pub fn automation(&self) -> &[AutomationLane] {
+        &self.project.automation
+    }
+
+    pub fn set_automation(&mut self, lanes: Vec<AutomationLane>) {
+        self.project.automation = lanes;
+    }
+
+    /// The current graph as a project document
+    pub fn snapshot(&self) -> ProjectFile {
+        self.project.clone()
+    }
+
+    /// Replace the graph with `project`: reconfigure with its engine settings
+    /// (keeping the current worker pool size) and rebuild every bus effect and
+    /// source. All nodes are built first, so an unknown kind or bad parameter
+    /// leaves the engine untouched. Clears the undo history.
+    pub fn restore(&mut self, mut project: ProjectFile) -> ProjectResult<()> {
+        self.check(EngineState::Configured)?;
+        let pool = self.config.map_or_else(PoolConfig::default, |c| c.pool);
+        let config = project.engine.to_config(pool);
+        config.validate()?;
+
+        let num_buses = config.num_buses.max(1);
+        if project.buses.len() > num_buses {
+            return Err(ProjectError::InvalidBus(project.buses.len() - 1));
+        }
+        project.buses.resize_with(num_buses, BusDescriptor::default);
+        let ctx = NodeContext {
+            channels: config.channels,
+            sample_rate: config.sample_rate,
+            max_frames: config.max_frames,
+            tempo_bpm: project.transport.tempo_bpm,
+        };
+        let changes = self.build_changes(&ProjectFile::new(project.engine), &project, &ctx)?;
+
+        self.configure(config)?;
+        self.apply_changes(changes)?;
+        self.project = project;
+        self.sync_clock();
+        Ok(())
+    }
+
+    pub fn save_project(&self, path: impl AsRef<Path>) -> ProjectResult<()> {
+        self.project.save(path)
+    }
+
+    pub fn load_project(&mut self, path: impl AsRef<Path>) -> ProjectResult<()> {
+        self.restore(ProjectFile::load(path)?)
+    }
+
+    fn node_context(&self) -> EngineResult<NodeContext> {
+        match &self.config {
+            Some(config) if self.processor.is_some() => Ok(NodeContext {
+                channels: config.channels,
+                sample_rate: config.sample_rate,
+                max_frames: config.max_frames,
+                tempo_bpm: self.project.transport.tempo_bpm,
+            }),
+            _ => Err(EngineError::NotConfigured(self.state())),
+        }
+    }
+
+    /// Bring the running graph from the current project to `next`
+    fn sync_graph(&mut self, next: &ProjectFile) -> ProjectResult<()> {
+        let ctx = self.node_context()?;
+        let changes = self.build_changes(&self.project, next, &ctx)?;
+        self.apply_changes(changes)
+    }
+
+    /// Build the nodes needed to go from `current` to `next`. Sources that
+    /// stay in place are edited where they are, rebuilding only a node or
+    /// insert whose descriptor changed; when sources come or go, the run
+    /// between the unchanged ones at either end is replaced. Bus chains
+    /// rebuild only the effects that changed.
+    fn build_changes(&self, current: &ProjectFile, next: &ProjectFile, ctx: &NodeContext) -> ProjectResult<GraphChanges> {
+        let mut changes = GraphChanges::default();
+        for (bus, descriptor) in next.buses.iter().enumerate() {
+            if current.buses.get(bus).is_none_or(|b| b.return_gain != descriptor.return_gain) {
+                changes.returns.push((bus, descriptor.return_gain));
+            }
+            let old = current.buses.get(bus).map_or(&[][..], |b| &b.effects[..]);
+            for change in self.chain_changes(old, &descriptor.effects, ctx)? {
+                changes.buses.push((bus, change));
+            }
+        }
+
+        let (old, new) = (&current.sources, &next.sources);
+        for source in new {
+            if source.bus >= next.buses.len() {
+                return Err(ProjectError::InvalidBus(source.bus));
+            }
+            if let Some(send) = source.sends.iter().find(|send| send.bus == 0 || send.bus >= next.buses.len()) {
+                return Err(ProjectError::InvalidBus(send.bus));
+            }
+        }
+        if old.len() == new.len() {
+            for (index, (from, to)) in old.iter().zip(new).enumerate().filter(|(_, (from, to))| from != to) {
+                let node = if from.node != to.node { Some(self.registry.build_source(&to.node, ctx)?) } else { None };
+                let effects = self.chain_changes(&from.effects, &to.effects, ctx)?;
+                changes.edits.push(SourceEdit { index, from: from.clone(), to: to.clone(), node, effects });
+            }
+        } else {
+            let (start, end) = common_ends(old, new);
+            changes.removed = start..old.len() - end;
+            for source in &new[start..new.len() - end] {
+                let built = self.registry.build_source(&source.node, ctx)?;
+                let effects = self.build_effects(&source.effects, 0, ctx)?;
+                changes.added.push((source.clone(), built, effects));
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Effects to build to turn chain `old` into `new`: each changed effect
+    /// when the chains are the same length, otherwise the run between their
+    /// common start and end, or all of them once the chain outgrows
+    /// `CHAIN_CAPACITY`
+    fn chain_changes(
+        &self,
+        old: &[NodeDescriptor],
+        new: &[NodeDescriptor],
+        ctx: &NodeContext,
+    ) -> ProjectResult<Vec<ChainChange>> {
+        if new.len() > CHAIN_CAPACITY && new.len() > old.len() {
+            return Ok(vec![ChainChange::Replace(self.build_effects(new, 0, ctx)?)]);
+        }
+        let splice = |nodes: &[NodeDescriptor], range: Range<usize>| -> ProjectResult<ChainChange> {
+            let effects = self.build_effects(nodes, range.len(), ctx)?;
+            Ok(ChainChange::Splice { range, effects })
+        };
+        if old.len() == new.len() {
+            let changed = old.iter().zip(new).enumerate().filter(|(_, (from, to))| from != to);
+            return changed.map(|(index, (_, node))| splice(std::slice::from_ref(node), index..index + 1)).collect();
+        }
+        let (start, end) = common_ends(old, new);
+        Ok(vec![splice(&new[start..new.len() - end], start..old.len() - end)?])
+    }
+
+    /// Build `nodes`, leaving room for `spare` more effects
+    fn build_effects(&self, nodes: &[NodeDescriptor], spare: usize, ctx: &NodeContext) -> ProjectResult<Effects> {
+        let mut effects = Vec::with_capacity(nodes.len() + spare);
+        for node in nodes {
+            effects.push(self.registry.build_effect(node, ctx)?);
+        }
+        Ok(effects)
+    }
+
+    fn apply_changes(&mut self, changes: GraphChanges) -> ProjectResult<()> {
+        let (Some(config), Some(commands)) = (self.config, &self.commands) else {
+            return Err(EngineError::NotConfigured(self.state()).into());
+        };
+        let mut batch = Vec::new();
+        for (bus, change) in changes.buses {
+            if let Some(monitors) = self.modulation.get_mut(bus) {
+                let modulation = change.effects().iter().map(|effect| effect.modulation());
+                match &change {
+                    ChainChange::Splice { range, .. } => {
+                        let end = range.end.min(monitors.len());
+                        monitors.splice(range.start.min(end)..end, modulation);
+                    }
+                    ChainChange::Replace(_) => *monitors = modulation.collect(),
+                }
+            }
+            batch.push(match change {
+                ChainChange::Splice { range, effects } => {
+                    RouterCommand::SpliceBusEffects { bus, range, effects: Box::new(effects) }
+                }
+                ChainChange::Replace(effects) => RouterCommand::SetBusEffects { bus, chain: chain(effects, &config) },
+            });
+        }
+        for (bus, gain) in changes.returns {
+            batch.push(RouterCommand::SetBusReturn { bus, gain });
+        }
+        for edit in changes.edits {
+            edit_source(commands, self.source_ids[edit.index], edit, &config, &mut batch);
+        }
+        for &id in &self.source_ids[changes.removed.clone()] {
+            batch.push(RouterCommand::RemoveSource(id));
+        }
+        let mut added = Vec::with_capacity(changes.added.len());
+        for (source, built, effects) in changes.added {
+            added.push(add_source(commands, &source, built, effects, &config, &mut batch));
+        }
+        self.send(batch)?;
+        self.source_ids.splice(changes.removed, added);
+        Ok(())
+    }
+
+    /// Queue `batch` for the audio thread, to land at one block boundary. The
+    /// processor is only locked when the queue is full, e.g. while stopped
+    /// and nothing drains it, to apply what is waiting first.
+    fn send(&self, mut batch: Vec<RouterCommand>) -> EngineResult<()> {
+        let (Some(commands), Some(processor)) = (&self.commands, &self.processor) else {
+            return Err(EngineError::NotConfigured(self.state()));
+        };
+        while let Err(returned) = commands.send_batch(batch) {
+            processor.lock().router_mut().apply_commands();
+            batch = returned;
+        }
+        Ok(())
+    }
+
+    fn check(&self, to: EngineState) -> EngineResult<()> {
+        let from = self.state();
+        if from.can_transition_to(to) { Ok(()) } else { Err(EngineError::InvalidTransition { from, to }) }
+    }
+
+    fn transition(&mut self, to: EngineState) -> EngineResult<()> {
+        self.check(to)?;
+        let event = EngineEvent { from: self.state(), to };
+        self.state.store(to);
+        self.subscribers.retain(|tx| tx.send(event).is_ok());
+        Ok(())
+    }
+}
+
+/// Lengths of the common start and end of `old` and `new`, not overlapping
+fn common_ends<T: PartialEq>(old: &[T], new: &[T]) -> (usize, usize) {
+    let start = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old, new) = (&old[start..], &new[start..]);
+    let end = old.iter().rev().zip(new.iter().rev()).take_while(|(a, b)| a == b).count();
+    (start, end)
+}
+
+fn pan(source: &SourceDescriptor) -> Pan {
+    Pan { value: source.pan.clamp(-1.0, 1.0), law: source.pan_law }
+}
+
+/// A chain of `effects`, prepared for the router
+fn chain(effects: Effects, config: &EngineConfig) -> Box<EffectChain> {
+    let mut chain = EffectChain::new();
+    effects.into_iter().for_each(|effect| chain.push(effect));
+    chain.prepare(config.channels, config.max_frames);
+    Box::new(chain)
+}
+
+/// Queue routing a built source as `source` describes it; returns its id
+fn add_source(
+    commands: &RouterCommands,
+    source: &SourceDescriptor,
+    node: Box<dyn AudioSource>,
+    effects: Effects,
+    config: &EngineConfig,
+    batch: &mut Vec<RouterCommand>,
+) -> SourceId {
+    let (handle, command) = commands.source_command(node, source.gain, pan(source), source.bus);
+    let id = handle.id();
+    batch.push(command);
+    batch.push(RouterCommand::SetTrims { id, high_pass: source.high_pass, low_pass: source.low_pass });
+    // prepared even when empty, so later splices fit
+    batch.push(RouterCommand::SetEffects { id, chain: chain(effects, config) });
+    for send in &source.sends {
+        batch.push(RouterCommand::SetSend { id, bus: send.bus, level: send.level, pre_fader: send.pre_fader });
+    }
+    id
+}
+
+/// Queue bringing live source `id` from `edit.from` to `edit.to`, touching
+/// only what changed
+fn edit_source(
+    commands: &RouterCommands,
+    id: SourceId,
+    edit: SourceEdit,
+    config: &EngineConfig,
+    batch: &mut Vec<RouterCommand>,
+) {
+    let (from, to) = (&edit.from, &edit.to);
+    if let Some(node) = edit.node {
+        batch.push(commands.replace_source_command(id, node));
+    }
+    for change in edit.effects {
+        batch.push(match change {
+            ChainChange::Splice { range, effects } => {
+                RouterCommand::SpliceEffects { id, range, effects: Box::new(effects) }
+            }
+            ChainChange::Replace(effects) => RouterCommand::SetEffects { id, chain: chain(effects, config) },
+        });
+    }
+    if from.gain != to.gain {
+        batch.push(RouterCommand::SetGain { id, gain: to.gain });
+    }
+    if (from.pan, from.pan_law) != (to.pan, to.pan_law) {
+        batch.push(RouterCommand::SetPan { id, pan: pan(to) });
+    }
+    if from.bus != to.bus {
+        batch.push(RouterCommand::SetBus { id, bus: to.bus });
+    }
+    if (from.high_pass, from.low_pass) != (to.high_pass, to.low_pass) {
+        batch.push(RouterCommand::SetTrims { id, high_pass: to.high_pass, low_pass: to.low_pass });
+    }
+    for send in from.sends.iter().filter(|send| to.sends.iter().all(|kept| kept.bus != send.bus)) {
+        batch.push(RouterCommand::RemoveSend { id, bus: send.bus });
+    }
+    for send in to.sends.iter().filter(|send| !from.sends.contains(send)) {
+        batch.push(RouterCommand::SetSend { id, bus: send.bus, level: send.level, pre_fader: send.pre_fader });
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+
+/// Engine lifecycle states.
+///
+/// Valid transitions:
+/// - Created → Configured (`configure`)
+/// - Configured → Configured (reconfigure), Running (`start`), Stopped
+/// - Running → Suspended (`suspend`), Stopped (`stop`)
+/// - Suspended → Running (`resume`), Configured (reconfigure), Stopped
+/// - Stopped → Configured, to restart with new settings
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EngineState {
+    /// Constructed, nothing allocated yet
+    Created,
+    /// Processing graph built for a sample rate / channel layout, not rendering
+    Configured,
+    /// Rendering audio
+    Running,
+    /// Paused: the audio path outputs silence, all state kept
+    Suspended,
+    /// Shut down; resources released
+    Stopped,
+}
+
+impl EngineState {
+    /// Whether moving from `self` to `to` is a valid lifecycle transition
+    pub fn can_transition_to(self, to: EngineState) -> bool {
+        use EngineState::*;
+        matches!(
+            (self, to),
+            (Created, Configured)
+                | (Configured, Configured)
+                | (Configured, Running)
+                | (Configured, Stopped)
+                | (Running, Suspended)
+                | (Running, Stopped)
+                | (Suspended, Running)
+                | (Suspended, Configured)
+                | (Suspended, Stopped)
+                | (Stopped, Configured)
+        )
+    }
+
+    /// Whether the audio path renders in this state
+    pub fn is_rendering(self) -> bool {
+        self == EngineState::Running
+    }
+}
+
+impl fmt::Display for EngineState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EngineState::Created => "created",
+            EngineState::Configured => "configured",
+            EngineState::Running => "running",
+            EngineState::Suspended => "suspended",
+            EngineState::Stopped => "stopped",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Emitted to subscribers after every successful transition
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EngineEvent {
+    pub from: EngineState,
+    pub to: EngineState,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineError {
+    InvalidTransition { from: EngineState, to: EngineState },
+    /// The operation needs a configured graph; carries the current state
+    NotConfigured(EngineState),
+    InvalidConfig(String),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidTransition { from, to } => {
+                write!(f, "Invalid engine transition from {} to {}", from, to)
+            }
+            Self::NotConfigured(state) => write!(f, "Engine is not configured (state: {})", state),
+            Self::InvalidConfig(msg) => write!(f, "Invalid engine configuration: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+pub type EngineResult<T> = Result<T, EngineError>;
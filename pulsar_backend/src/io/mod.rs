@@ -0,0 +1,4 @@
+pub mod wav;
+pub mod sample_cache;
+pub mod peaks;
+pub mod recorder;
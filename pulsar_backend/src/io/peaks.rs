@@ -0,0 +1,196 @@
+//! `.peaks` sidecar files: min/max per window of a WAV file, so waveform
+//! displays of long recordings can be drawn without rescanning the audio.
+//!
+//! Layout, little endian: `PEAK` magic, format version (u16), channel count
+//! (u16), sample rate (u32), window length in frames (u32), then one record
+//! per window with a `(min, max)` pair of i16 per channel. The last window may
+//! cover fewer frames. Records are appended as audio arrives, so a partially
+//! written file is still readable.
+
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::wav::{WavAudio, WavError, WavResult};
+
+/// Frames per min/max window
+pub const DEFAULT_PEAKS_WINDOW: u32 = 256;
+
+const MAGIC: &[u8; 4] = b"PEAK";
+const VERSION: u16 = 1;
+const HEADER_BYTES: usize = 16;
+
+/// Sidecar path for `audio`: the same name with a `.peaks` extension
+pub fn sidecar_path(audio: impl AsRef<Path>) -> PathBuf {
+    audio.as_ref().with_extension("peaks")
+}
+
+/// Decoded peaks, `[channel][window]` as `(min, max)` in -1.0..1.0
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Peaks {
+    pub channels: Vec<Vec<(f32, f32)>>,
+    pub sample_rate: u32,
+    pub window: u32,
+}
+
+impl Peaks {
+    /// Scan decoded audio, for files recorded without a sidecar
+    pub fn from_audio(audio: &WavAudio, window: u32) -> Self {
+        let window = window.max(1);
+        let channels = audio
+            .channels
+            .iter()
+            .map(|samples| {
+                samples
+                    .chunks(window as usize)
+                    .map(|chunk| chunk.iter().fold((f32::MAX, f32::MIN), |(lo, hi), &s| (lo.min(s), hi.max(s))))
+                    .collect()
+            })
+            .collect();
+        Self { channels, sample_rate: audio.sample_rate, window }
+    }
+
+    pub fn windows(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+
+    /// Min/max of `channel` over frames `start..end`, merging the windows they touch
+    pub fn range(&self, channel: usize, start: usize, end: usize) -> Option<(f32, f32)> {
+        let windows = self.channels.get(channel)?;
+        let window = self.window.max(1) as usize;
+        let first = start / window;
+        let last = end.div_ceil(window).min(windows.len());
+        windows
+            .get(first..last)
+            .filter(|w| !w.is_empty())
+            .map(|w| w.iter().fold((f32::MAX, f32::MIN), |(lo, hi), &(l, h)| (lo.min(l), hi.max(h))))
+    }
+}
+
+/// Read a `.peaks` file
+pub fn read_file(path: impl AsRef<Path>) -> WavResult<Peaks> {
+    read_bytes(&fs::read(path)?)
+}
+
+pub fn read_bytes(bytes: &[u8]) -> WavResult<Peaks> {
+    if bytes.len() < HEADER_BYTES || &bytes[0..4] != MAGIC {
+        return Err(WavError::InvalidFormat("missing peaks header".into()));
+    }
+    let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+    let u32_at = |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+    if u16_at(4) != VERSION {
+        return Err(WavError::Unsupported(format!("peaks version {}", u16_at(4))));
+    }
+    let channels = u16_at(6) as usize;
+    if channels == 0 {
+        return Err(WavError::InvalidFormat("zero channels".into()));
+    }
+
+    let mut peaks = Peaks { channels: vec![Vec::new(); channels], sample_rate: u32_at(8), window: u32_at(12) };
+    // a record cut short by an in-progress write is ignored
+    for record in bytes[HEADER_BYTES..].chunks_exact(channels * 4) {
+        for (channel, pair) in peaks.channels.iter_mut().zip(record.chunks_exact(4)) {
+            let lo = i16::from_le_bytes([pair[0], pair[1]]);
+            let hi = i16::from_le_bytes([pair[2], pair[3]]);
+            channel.push((dequantize(lo), dequantize(hi)));
+        }
+    }
+    Ok(peaks)
+}
+
+/// Incremental `.peaks` writer, fed the same audio as the recording. Not RT-safe.
+pub struct PeaksWriter {
+    file: BufWriter<fs::File>,
+    window: u32,
+    /// Frames accumulated in the current window
+    filled: u32,
+    current: Vec<(f32, f32)>,
+    record: Vec<u8>,
+}
+
+impl PeaksWriter {
+    pub fn create(path: impl AsRef<Path>, channels: usize, sample_rate: u32, window: u32) -> WavResult<Self> {
+        if channels == 0 || channels > u16::MAX as usize {
+            return Err(WavError::InvalidFormat(format!("cannot write {} channels", channels)));
+        }
+        let window = window.max(1);
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&VERSION.to_le_bytes())?;
+        file.write_all(&(channels as u16).to_le_bytes())?;
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&window.to_le_bytes())?;
+        Ok(Self {
+            file,
+            window,
+            filled: 0,
+            current: vec![(f32::MAX, f32::MIN); channels],
+            record: Vec::with_capacity(channels * 4),
+        })
+    }
+
+    /// Add non-interleaved `[channel][frame]` audio; missing channels count as silence
+    pub fn write(&mut self, channels: &[&[f32]], frames: usize) -> WavResult<()> {
+        for frame in 0..frames {
+            for (channel, peak) in self.current.iter_mut().enumerate() {
+                let s = channels.get(channel).and_then(|c| c.get(frame)).copied().unwrap_or(0.0);
+                *peak = (peak.0.min(s), peak.1.max(s));
+            }
+            self.advance()?;
+        }
+        Ok(())
+    }
+
+    /// Add interleaved audio
+    pub fn write_interleaved(&mut self, samples: &[f32]) -> WavResult<()> {
+        for frame in samples.chunks_exact(self.current.len()) {
+            for (peak, &s) in self.current.iter_mut().zip(frame) {
+                *peak = (peak.0.min(s), peak.1.max(s));
+            }
+            self.advance()?;
+        }
+        Ok(())
+    }
+
+    fn advance(&mut self) -> WavResult<()> {
+        self.filled += 1;
+        if self.filled == self.window {
+            self.write_window()?;
+        }
+        Ok(())
+    }
+
+    fn write_window(&mut self) -> WavResult<()> {
+        self.record.clear();
+        for peak in &mut self.current {
+            self.record.extend_from_slice(&quantize(peak.0).to_le_bytes());
+            self.record.extend_from_slice(&quantize(peak.1).to_le_bytes());
+            *peak = (f32::MAX, f32::MIN);
+        }
+        self.filled = 0;
+        self.file.write_all(&self.record)?;
+        Ok(())
+    }
+
+    /// Push complete windows to disk so readers see them
+    pub fn flush(&mut self) -> WavResult<()> {
+        self.file.flush()?;
+        Ok(())
+    }
+
+    /// Write the last, partial window and close the file
+    pub fn finish(mut self) -> WavResult<()> {
+        if self.filled > 0 {
+            self.write_window()?;
+        }
+        self.flush()
+    }
+}
+
+fn quantize(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * 32767.0).round() as i16
+}
+
+fn dequantize(value: i16) -> f32 {
+    value as f32 / 32767.0
+}
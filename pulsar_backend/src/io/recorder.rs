@@ -0,0 +1,366 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crossbeam::queue::ArrayQueue;
+
+use crate::rt_processing::effects::Effect;
+use crate::rt_processing::routing::AudioSource;
+
+use super::peaks::{self, DEFAULT_PEAKS_WINDOW, PeaksWriter};
+use super::wav::{WavAudio, WavResult, WavSpec, WavStream, WavWriter};
+
+/// Audio the tap can queue ahead of the disk writer
+pub const DEFAULT_RECORD_BUFFER_SECONDS: f32 = 2.0;
+
+struct Shared {
+    /// Interleaved samples, whole frames only
+    queue: ArrayQueue<f32>,
+    channels: usize,
+    recording: AtomicBool,
+    /// Frames lost because the writer fell behind
+    dropped: AtomicUsize,
+    /// Frames on disk with an up-to-date header, readable by `RecordingReader`
+    written: AtomicUsize,
+}
+
+/// Audio-thread side of a recording: a pass-through effect that queues what
+/// flows through it for the `Recorder`. Insert it wherever the signal should be
+/// captured (a bus, the master). Never blocks: when the queue is full the frames
+/// are dropped and counted.
+pub struct RecordTap {
+    shared: Arc<Shared>,
+}
+
+impl Effect for RecordTap {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        let shared = &self.shared;
+        if !shared.recording.load(Ordering::Relaxed) {
+            return;
+        }
+        for i in 0..frames {
+            // single producer: the free space can only grow while we push
+            if shared.queue.capacity() - shared.queue.len() < shared.channels {
+                shared.dropped.fetch_add(frames - i, Ordering::Relaxed);
+                return;
+            }
+            for channel in 0..shared.channels {
+                let _ = shared.queue.push(buffer.get(channel).map_or(0.0, |b| b[i]));
+            }
+        }
+    }
+}
+
+/// One pass of the loop in a loop recording, in frames of the recorded file
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Take {
+    pub index: usize,
+    pub start: usize,
+    pub frames: usize,
+    /// Covers the whole loop; the last take is partial while recording
+    pub complete: bool,
+}
+
+/// Summary of a finished recording
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordingInfo {
+    pub path: PathBuf,
+    /// `.peaks` sidecar written alongside
+    pub peaks_path: PathBuf,
+    pub frames: usize,
+    pub dropped_frames: usize,
+    /// Loop passes, empty unless loop recording was on
+    pub takes: Vec<Take>,
+    pub selected_take: Option<usize>,
+}
+
+/// Disk side of a recording: writes what the `RecordTap` queued to a WAV file
+/// and its `.peaks` sidecar (see `io::peaks`), so waveforms of long takes can be
+/// drawn without rescanning the audio. Call `drain` regularly from a non-RT
+/// thread (a UI timer, a job); both files are kept readable between drains.
+pub struct Recorder {
+    shared: Arc<Shared>,
+    path: PathBuf,
+    peaks_path: PathBuf,
+    wav: WavWriter,
+    peaks: PeaksWriter,
+    scratch: Vec<f32>,
+    /// Loop recording: file frame where the first pass starts, and loop length
+    takes: Option<(usize, usize)>,
+    selected_take: Option<usize>,
+}
+
+impl Recorder {
+    /// Create the WAV file at `path` and its sidecar, and the tap feeding them.
+    /// Recording starts right away.
+    pub fn create(path: impl AsRef<Path>, channels: usize, spec: WavSpec) -> WavResult<(RecordTap, Recorder)> {
+        Self::with_options(path, channels, spec, DEFAULT_RECORD_BUFFER_SECONDS, DEFAULT_PEAKS_WINDOW)
+    }
+
+    /// `create` with the queue length in seconds and the peaks window in frames
+    pub fn with_options(
+        path: impl AsRef<Path>,
+        channels: usize,
+        spec: WavSpec,
+        buffer_seconds: f32,
+        peaks_window: u32,
+    ) -> WavResult<(RecordTap, Recorder)> {
+        let path = path.as_ref().to_path_buf();
+        let peaks_path = peaks::sidecar_path(&path);
+        let wav = WavWriter::create(&path, channels, spec)?;
+        let peaks = PeaksWriter::create(&peaks_path, channels, spec.sample_rate, peaks_window)?;
+
+        let frames = ((buffer_seconds.max(0.0) * spec.sample_rate as f32) as usize).max(1);
+        let shared = Arc::new(Shared {
+            queue: ArrayQueue::new(frames * channels),
+            channels,
+            recording: AtomicBool::new(true),
+            dropped: AtomicUsize::new(0),
+            written: AtomicUsize::new(0),
+        });
+        let recorder = Recorder {
+            shared: Arc::clone(&shared),
+            path,
+            peaks_path,
+            wav,
+            peaks,
+            scratch: Vec::with_capacity(frames * channels),
+            takes: None,
+            selected_take: None,
+        };
+        Ok((RecordTap { shared }, recorder))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn peaks_path(&self) -> &Path {
+        &self.peaks_path
+    }
+
+    /// Frames written to disk so far
+    pub fn frames(&self) -> usize {
+        self.wav.frames()
+    }
+
+    pub fn dropped_frames(&self) -> usize {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Pause or resume capture; the files stay open
+    pub fn set_recording(&self, recording: bool) {
+        self.shared.recording.store(recording, Ordering::Relaxed);
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.shared.recording.load(Ordering::Relaxed)
+    }
+
+    /// Loop-record mode: from the next frame captured on, every `loop_frames`
+    /// frames (one pass of the transport loop) form a new take. The recording
+    /// itself stays one continuous file; takes are regions in it. `None`
+    /// turns the mode off and forgets the takes.
+    ///
+    /// Takes follow written frames, so frames dropped by an overloaded writer
+    /// shift later takes against the loop.
+    pub fn set_loop_record(&mut self, loop_frames: Option<usize>) {
+        // audio still queued by the tap predates the loop
+        let start = self.wav.frames() + self.shared.queue.len() / self.shared.channels;
+        self.takes = loop_frames.filter(|&frames| frames > 0).map(|frames| (start, frames));
+        self.selected_take = None;
+    }
+
+    pub fn is_loop_recording(&self) -> bool {
+        self.takes.is_some()
+    }
+
+    /// Takes recorded so far, oldest first, including the pass in progress
+    pub fn takes(&self) -> Vec<Take> {
+        let Some((first, length)) = self.takes else { return Vec::new() };
+        let recorded = self.wav.frames().saturating_sub(first);
+        (0..recorded.div_ceil(length))
+            .map(|index| {
+                let frames = (recorded - index * length).min(length);
+                Take { index, start: first + index * length, frames, complete: frames == length }
+            })
+            .collect()
+    }
+
+    pub fn take(&self, index: usize) -> Option<Take> {
+        self.takes().get(index).copied()
+    }
+
+    /// Choose the take to play back; false if there is no such take
+    pub fn select_take(&mut self, index: usize) -> bool {
+        let exists = self.take(index).is_some();
+        if exists {
+            self.selected_take = Some(index);
+        }
+        exists
+    }
+
+    /// The selected take, or else the latest complete one
+    pub fn selected_take(&self) -> Option<Take> {
+        let takes = self.takes();
+        match self.selected_take {
+            Some(index) => takes.get(index).copied(),
+            None => takes.iter().rev().find(|take| take.complete).copied(),
+        }
+    }
+
+    /// Reader over the audio written so far, for reviewing a take while it is
+    /// still recording. Each reader has its own file handle.
+    pub fn reader(&self) -> WavResult<RecordingReader> {
+        Ok(RecordingReader { stream: WavStream::open(&self.path)?, shared: Arc::clone(&self.shared) })
+    }
+
+    /// Write queued audio to both files. Returns the frames written.
+    pub fn drain(&mut self) -> WavResult<usize> {
+        self.scratch.clear();
+        let channels = self.shared.channels;
+        // take whole frames only; the tap may be mid-frame
+        let available = self.shared.queue.len() / channels * channels;
+        for _ in 0..available {
+            match self.shared.queue.pop() {
+                Some(s) => self.scratch.push(s),
+                None => break,
+            }
+        }
+        if self.scratch.is_empty() {
+            return Ok(0);
+        }
+        self.wav.write_interleaved(&self.scratch)?;
+        self.peaks.write_interleaved(&self.scratch)?;
+        self.wav.flush()?;
+        self.peaks.flush()?;
+        self.shared.written.store(self.wav.frames(), Ordering::Release);
+        Ok(self.scratch.len() / channels)
+    }
+
+    /// Stop capture, write the rest and close both files
+    pub fn finish(mut self) -> WavResult<RecordingInfo> {
+        self.set_recording(false);
+        self.drain()?;
+        let info = RecordingInfo {
+            takes: self.takes(),
+            selected_take: self.selected_take().map(|take| take.index),
+            path: self.path,
+            peaks_path: self.peaks_path,
+            frames: self.wav.frames(),
+            dropped_frames: self.shared.dropped.load(Ordering::Relaxed),
+        };
+        self.wav.finish()?;
+        self.peaks.finish()?;
+        Ok(info)
+    }
+}
+
+/// Read access to a recording in progress, without closing it.
+///
+/// Sees every frame up to the recorder's last `drain`; the length grows as
+/// recording goes on. Not RT-safe: load the audio here, then hand it to a
+/// `RecordingPlayer` for playback.
+pub struct RecordingReader {
+    stream: WavStream,
+    shared: Arc<Shared>,
+}
+
+impl RecordingReader {
+    pub fn channels(&self) -> usize {
+        self.stream.channels()
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.stream.sample_rate()
+    }
+
+    /// Frames readable now
+    pub fn frames(&self) -> usize {
+        self.shared.written.load(Ordering::Acquire)
+    }
+
+    /// Decode up to `frames` frames starting at frame `start`
+    pub fn read(&mut self, start: usize, frames: usize) -> WavResult<WavAudio> {
+        let written = self.frames();
+        if self.stream.frames() < written {
+            self.stream.refresh()?;
+        }
+        let end = start.saturating_add(frames).min(written);
+        self.stream.read(start, end.saturating_sub(start))
+    }
+
+    /// The audio of one take, e.g. the selected one for playback
+    pub fn read_take(&mut self, take: &Take) -> WavResult<WavAudio> {
+        self.read(take.start, take.frames)
+    }
+
+    /// Everything recorded so far
+    pub fn read_all(&mut self) -> WavResult<WavAudio> {
+        self.read(0, self.frames())
+    }
+}
+
+/// In-memory playback of (part of) a recording, e.g. to review a take or
+/// play it back under an overdub. Plays once or loops; engine channels beyond
+/// the recording's wrap around its channels.
+pub struct RecordingPlayer {
+    audio: Arc<WavAudio>,
+    position: usize,
+    looping: bool,
+}
+
+impl RecordingPlayer {
+    pub fn new(audio: impl Into<Arc<WavAudio>>) -> Self {
+        Self { audio: audio.into(), position: 0, looping: false }
+    }
+
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn audio(&self) -> &Arc<WavAudio> {
+        &self.audio
+    }
+
+    /// Playback position in frames
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn seek(&mut self, frame: usize) {
+        self.position = frame.min(self.audio.frames());
+    }
+
+    /// Reached the end (never when looping)
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.position >= self.audio.frames()
+    }
+}
+
+impl AudioSource for RecordingPlayer {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        let length = self.audio.frames();
+        let channels = self.audio.channels.len();
+        for i in 0..frames {
+            if self.position >= length {
+                if !self.looping || length == 0 {
+                    for out in output.iter_mut() {
+                        out[i..frames].fill(0.0);
+                    }
+                    return;
+                }
+                self.position = 0;
+            }
+            for (ch, out) in output.iter_mut().enumerate() {
+                out[i] = self.audio.channels[ch % channels][self.position];
+            }
+            self.position += 1;
+        }
+    }
+    /// Recordings made at another rate are resampled by the router
+    fn native_sample_rate(&self) -> Option<u32> {
+        Some(self.audio.sample_rate)
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::io::wav::{self, WavAudio, WavResult, WavStream};
+use crate::jobs::{JobHandle, JobPriority, WorkerPool};
+
+/// Decoded sample data shared by every player using it, `[channel][frame]`
+#[derive(Debug)]
+pub struct SampleBuffer {
+    path: PathBuf,
+    channels: Vec<Vec<f32>>,
+    sample_rate: u32,
+}
+
+impl SampleBuffer {
+    /// Wrap decoded audio, outside any cache
+    pub fn from_audio(path: impl Into<PathBuf>, audio: WavAudio) -> Self {
+        Self { path: path.into(), channels: audio.channels, sample_rate: audio.sample_rate }
+    }
+
+    /// Decode a whole WAV file, outside any cache. Not RT-safe.
+    pub fn load(path: impl AsRef<Path>) -> WavResult<Self> {
+        let path = path.as_ref();
+        Ok(Self::from_audio(path, wav::read_file(path)?))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn channels(&self) -> &[Vec<f32>] {
+        &self.channels
+    }
+
+    pub fn channel(&self, index: usize) -> Option<&[f32]> {
+        self.channels.get(index).map(Vec::as_slice)
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn frames(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Memory held by the decoded audio
+    pub fn bytes(&self) -> usize {
+        self.channels.iter().map(|c| c.len() * std::mem::size_of::<f32>()).sum()
+    }
+}
+
+/// A sample that did not fit the memory budget and is read from disk on demand
+#[derive(Clone, Debug)]
+pub struct StreamingSample {
+    pub path: PathBuf,
+    pub channels: usize,
+    pub sample_rate: u32,
+    pub frames: usize,
+}
+
+impl StreamingSample {
+    /// Open a reader; each player streaming the sample needs its own
+    pub fn open(&self) -> WavResult<WavStream> {
+        WavStream::open(&self.path)
+    }
+}
+
+/// Result of a cache load
+#[derive(Clone, Debug)]
+pub enum SampleHandle {
+    /// In memory. Hold on to the `Arc` for as long as the sample is playing;
+    /// the cache only evicts samples nobody else references.
+    Resident(Arc<SampleBuffer>),
+    Streaming(StreamingSample),
+}
+
+impl SampleHandle {
+    pub fn is_resident(&self) -> bool {
+        matches!(self, SampleHandle::Resident(_))
+    }
+
+    pub fn resident(&self) -> Option<&Arc<SampleBuffer>> {
+        match self {
+            SampleHandle::Resident(buffer) => Some(buffer),
+            SampleHandle::Streaming(_) => None,
+        }
+    }
+}
+
+/// Cache usage snapshot
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub budget_bytes: usize,
+    /// Bytes of all resident samples
+    pub resident_bytes: usize,
+    /// Bytes of resident samples currently referenced by a player
+    pub in_use_bytes: usize,
+    pub resident_samples: usize,
+    pub in_use_samples: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// Loads that fell back to streaming
+    pub streamed: u64,
+}
+
+struct Entry {
+    sample: Arc<SampleBuffer>,
+    last_used: u64,
+}
+
+impl Entry {
+    fn in_use(&self) -> bool {
+        Arc::strong_count(&self.sample) > 1
+    }
+}
+
+#[derive(Default)]
+struct CacheState {
+    budget: usize,
+    resident: usize,
+    entries: HashMap<PathBuf, Entry>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+impl CacheState {
+    /// Evict least recently used samples that no player holds until `needed`
+    /// more bytes fit. Returns whether they fit.
+    fn make_room(&mut self, needed: usize) -> bool {
+        while self.resident + needed > self.budget {
+            let victim = self
+                .entries
+                .iter()
+                .filter(|(_, entry)| !entry.in_use())
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(path, _)| path.clone());
+            let Some(path) = victim else { return false };
+            if let Some(entry) = self.entries.remove(&path) {
+                self.resident -= entry.sample.bytes();
+                self.stats.evictions += 1;
+            }
+        }
+        true
+    }
+
+    fn touch(&mut self, path: &Path) -> Option<Arc<SampleBuffer>> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.entries.get_mut(path).map(|entry| {
+            entry.last_used = clock;
+            Arc::clone(&entry.sample)
+        })
+    }
+}
+
+/// Sample memory manager with a byte budget.
+///
+/// Decoded samples are shared as `Arc<SampleBuffer>`, so players loading the
+/// same file share one copy. When a load would exceed the budget, the least
+/// recently used samples that no player references are evicted; if that still
+/// isn't enough, the load falls back to a `StreamingSample` read from disk.
+/// Internally synchronized, so one cache can be shared (`Arc<SampleCache>`)
+/// between loader jobs and control threads. Not for use on the RT thread.
+pub struct SampleCache {
+    state: Mutex<CacheState>,
+}
+
+impl SampleCache {
+    pub fn new(budget_bytes: usize) -> Self {
+        Self { state: Mutex::new(CacheState { budget: budget_bytes, ..CacheState::default() }) }
+    }
+
+    /// Budget in megabytes
+    pub fn with_budget_mb(budget_mb: usize) -> Self {
+        Self::new(budget_mb * 1024 * 1024)
+    }
+
+    pub fn budget(&self) -> usize {
+        self.lock().budget
+    }
+
+    /// Change the budget, evicting unused samples if it shrank
+    pub fn set_budget(&self, budget_bytes: usize) {
+        let mut state = self.lock();
+        state.budget = budget_bytes;
+        state.make_room(0);
+    }
+
+    /// Load a WAV file, sharing it if already resident
+    pub fn load(&self, path: impl AsRef<Path>) -> WavResult<SampleHandle> {
+        let path = path.as_ref();
+        {
+            let mut state = self.lock();
+            if let Some(sample) = state.touch(path) {
+                state.stats.hits += 1;
+                return Ok(SampleHandle::Resident(sample));
+            }
+            state.stats.misses += 1;
+        }
+
+        let mut stream = WavStream::open(path)?;
+        let streaming = StreamingSample {
+            path: path.to_path_buf(),
+            channels: stream.channels(),
+            sample_rate: stream.sample_rate(),
+            frames: stream.frames(),
+        };
+        let bytes = stream.decoded_bytes();
+        if !self.lock().make_room(bytes) {
+            self.lock().stats.streamed += 1;
+            return Ok(SampleHandle::Streaming(streaming));
+        }
+
+        // decode without holding the lock
+        let audio = stream.read(0, stream.frames())?;
+        let sample = Arc::new(SampleBuffer::from_audio(path, audio));
+
+        let mut state = self.lock();
+        // another thread may have loaded it meanwhile
+        if let Some(existing) = state.touch(path) {
+            return Ok(SampleHandle::Resident(existing));
+        }
+        // room may have been taken by a concurrent load
+        if !state.make_room(bytes) {
+            state.stats.streamed += 1;
+            return Ok(SampleHandle::Streaming(streaming));
+        }
+        state.resident += bytes;
+        let last_used = state.clock;
+        state.entries.insert(path.to_path_buf(), Entry { sample: Arc::clone(&sample), last_used });
+        Ok(SampleHandle::Resident(sample))
+    }
+
+    /// `load` on a background job
+    pub fn load_job(
+        self: &Arc<Self>,
+        pool: &WorkerPool,
+        path: impl Into<PathBuf>,
+    ) -> JobHandle<WavResult<SampleHandle>> {
+        let cache = Arc::clone(self);
+        let path = path.into();
+        pool.spawn(JobPriority::Io, format!("sample load {}", path.display()), move |_| cache.load(path))
+    }
+
+    /// Resident sample for `path`, if any
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<Arc<SampleBuffer>> {
+        self.lock().touch(path.as_ref())
+    }
+
+    pub fn contains(&self, path: impl AsRef<Path>) -> bool {
+        self.lock().entries.contains_key(path.as_ref())
+    }
+
+    /// Drop every resident sample no player references
+    pub fn purge_unused(&self) {
+        let mut state = self.lock();
+        let before = state.entries.len();
+        state.entries.retain(|_, entry| entry.in_use());
+        state.resident = state.entries.values().map(|e| e.sample.bytes()).sum();
+        state.stats.evictions += (before - state.entries.len()) as u64;
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let state = self.lock();
+        let in_use: Vec<&Entry> = state.entries.values().filter(|e| e.in_use()).collect();
+        CacheStats {
+            budget_bytes: state.budget,
+            resident_bytes: state.resident,
+            in_use_bytes: in_use.iter().map(|e| e.sample.bytes()).sum(),
+            resident_samples: state.entries.len(),
+            in_use_samples: in_use.len(),
+            ..state.stats
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,452 @@
+use std::fmt;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::jobs::{JobHandle, JobPriority, WorkerPool};
+use crate::rt_processing::waveform::noise::FastRng;
+
+pub type WavResult<T> = Result<T, WavError>;
+
+#[derive(Debug)]
+pub enum WavError {
+    Io(std::io::Error),
+    InvalidFormat(String),
+    Unsupported(String),
+}
+
+impl fmt::Display for WavError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "WAV I/O error: {}", e),
+            Self::InvalidFormat(msg) => write!(f, "Invalid WAV file: {}", msg),
+            Self::Unsupported(msg) => write!(f, "Unsupported WAV encoding: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for WavError {}
+
+impl From<std::io::Error> for WavError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Decoded audio, non-interleaved `[channel][frame]`, normalized to -1.0..1.0
+#[derive(Clone, Debug, Default)]
+pub struct WavAudio {
+    pub channels: Vec<Vec<f32>>,
+    pub sample_rate: u32,
+}
+
+impl WavAudio {
+    pub fn frames(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+}
+
+const FORMAT_PCM: u16 = 1;
+const FORMAT_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Read a WAV file (PCM 8/16/24/32-bit or 32/64-bit float). Not RT-safe.
+pub fn read_file(path: impl AsRef<Path>) -> WavResult<WavAudio> {
+    read_bytes(&fs::read(path)?)
+}
+
+/// Read a WAV file on a background job (large files, UI-driven loads)
+pub fn load_job(pool: &WorkerPool, path: impl Into<PathBuf>) -> JobHandle<WavResult<WavAudio>> {
+    let path = path.into();
+    pool.spawn(JobPriority::Io, format!("wav load {}", path.display()), move |_| read_file(path))
+}
+
+/// Decode an in-memory WAV file
+pub fn read_bytes(bytes: &[u8]) -> WavResult<WavAudio> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(WavError::InvalidFormat("missing RIFF/WAVE header".into()));
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let id = &bytes[pos..pos + 4];
+        let size = u32::from_le_bytes([bytes[pos + 4], bytes[pos + 5], bytes[pos + 6], bytes[pos + 7]]) as usize;
+        let body = pos + 8;
+        let end = body.saturating_add(size).min(bytes.len());
+        match id {
+            b"fmt " => format = Some(parse_format(&bytes[body..end])?),
+            b"data" => data = Some(&bytes[body..end]),
+            _ => {}
+        }
+        // chunks are word aligned
+        pos = body.saturating_add(size).saturating_add(size & 1);
+    }
+
+    let format = format.ok_or_else(|| WavError::InvalidFormat("missing fmt chunk".into()))?;
+    let data = data.ok_or_else(|| WavError::InvalidFormat("missing data chunk".into()))?;
+    decode(&format, data)
+}
+
+/// Random-access reader over a WAV file's data chunk, for audio too large to
+/// keep in memory. Only the header is read on `open`. Not RT-safe.
+pub struct WavStream {
+    file: fs::File,
+    format: Format,
+    data_start: u64,
+    frames: usize,
+}
+
+impl WavStream {
+    pub fn open(path: impl AsRef<Path>) -> WavResult<Self> {
+        let mut file = fs::File::open(path)?;
+        let mut header = [0u8; 12];
+        file.read_exact(&mut header)?;
+        if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+            return Err(WavError::InvalidFormat("missing RIFF/WAVE header".into()));
+        }
+
+        let mut format = None;
+        loop {
+            let mut chunk = [0u8; 8];
+            if file.read_exact(&mut chunk).is_err() {
+                return Err(WavError::InvalidFormat("missing data chunk".into()));
+            }
+            let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as u64;
+            match &chunk[0..4] {
+                b"fmt " => {
+                    let mut body = vec![0u8; size as usize];
+                    file.read_exact(&mut body)?;
+                    format = Some(parse_format(&body)?);
+                    file.seek(SeekFrom::Current((size & 1) as i64))?;
+                }
+                b"data" => {
+                    let format = format.ok_or_else(|| WavError::InvalidFormat("missing fmt chunk".into()))?;
+                    if format.frame_bytes() == 0 {
+                        return Err(WavError::InvalidFormat("zero channels or bits".into()));
+                    }
+                    let data_start = file.stream_position()?;
+                    // a streamed/truncated file may claim more than is there
+                    let available = file.metadata()?.len().saturating_sub(data_start);
+                    let frames = (size.min(available) as usize) / format.frame_bytes();
+                    return Ok(Self { file, format, data_start, frames });
+                }
+                _ => {
+                    file.seek(SeekFrom::Current((size + (size & 1)) as i64))?;
+                }
+            }
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.format.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.format.sample_rate
+    }
+
+    /// Length in frames
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Size of the audio once decoded to `f32`, in bytes
+    pub fn decoded_bytes(&self) -> usize {
+        self.frames * self.format.channels * std::mem::size_of::<f32>()
+    }
+
+    /// Re-read the data size of a file that is still being written (see
+    /// `WavWriter`) and return the new length in frames
+    pub fn refresh(&mut self) -> WavResult<usize> {
+        let mut size = [0u8; 4];
+        self.file.seek(SeekFrom::Start(self.data_start - 4))?;
+        self.file.read_exact(&mut size)?;
+        let size = u32::from_le_bytes(size) as u64;
+        let available = self.file.metadata()?.len().saturating_sub(self.data_start);
+        self.frames = (size.min(available) as usize) / self.format.frame_bytes();
+        Ok(self.frames)
+    }
+
+    /// Decode up to `frames` frames starting at frame `start`
+    pub fn read(&mut self, start: usize, frames: usize) -> WavResult<WavAudio> {
+        let start = start.min(self.frames);
+        let frames = frames.min(self.frames - start);
+        let frame_bytes = self.format.frame_bytes();
+        let mut bytes = vec![0u8; frames * frame_bytes];
+        self.file.seek(SeekFrom::Start(self.data_start + (start * frame_bytes) as u64))?;
+        self.file.read_exact(&mut bytes)?;
+        decode(&self.format, &bytes)
+    }
+}
+
+struct Format {
+    encoding: u16,
+    channels: usize,
+    sample_rate: u32,
+    bits: u16,
+}
+
+impl Format {
+    fn frame_bytes(&self) -> usize {
+        (self.bits as usize).div_ceil(8) * self.channels
+    }
+}
+
+fn parse_format(chunk: &[u8]) -> WavResult<Format> {
+    if chunk.len() < 16 {
+        return Err(WavError::InvalidFormat("fmt chunk too short".into()));
+    }
+    let u16_at = |i: usize| u16::from_le_bytes([chunk[i], chunk[i + 1]]);
+    let mut encoding = u16_at(0);
+    if encoding == FORMAT_EXTENSIBLE && chunk.len() >= 26 {
+        // the first two bytes of the sub-format GUID carry the actual encoding
+        encoding = u16_at(24);
+    }
+    Ok(Format {
+        encoding,
+        channels: u16_at(2) as usize,
+        sample_rate: u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+        bits: u16_at(14),
+    })
+}
+
+fn decode(format: &Format, data: &[u8]) -> WavResult<WavAudio> {
+    if format.channels == 0 {
+        return Err(WavError::InvalidFormat("zero channels".into()));
+    }
+    let width = (format.bits as usize).div_ceil(8);
+
+    let convert: fn(&[u8]) -> f32 = match (format.encoding, format.bits) {
+        (FORMAT_PCM, 8) => |b| (b[0] as f32 - 128.0) / 128.0,
+        (FORMAT_PCM, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+        (FORMAT_PCM, 24) => |b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0,
+        (FORMAT_PCM, 32) => |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0,
+        (FORMAT_FLOAT, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        (FORMAT_FLOAT, 64) => |b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32,
+        (encoding, bits) => {
+            return Err(WavError::Unsupported(format!("format {} with {} bits", encoding, bits)));
+        }
+    };
+
+    let frame_bytes = format.frame_bytes();
+    let frames = data.len() / frame_bytes;
+    let mut channels = vec![Vec::with_capacity(frames); format.channels];
+    for frame in data.chunks_exact(frame_bytes) {
+        for (channel, sample) in channels.iter_mut().zip(frame.chunks_exact(width)) {
+            channel.push(convert(sample));
+        }
+    }
+
+    Ok(WavAudio { channels, sample_rate: format.sample_rate })
+}
+
+/// Sample encoding written by `write_file`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SampleFormat {
+    Int16,
+    Int24,
+    Float32,
+}
+
+impl SampleFormat {
+    pub fn bits(self) -> u16 {
+        match self {
+            SampleFormat::Int16 => 16,
+            SampleFormat::Int24 => 24,
+            SampleFormat::Float32 => 32,
+        }
+    }
+}
+
+/// Output settings for `write_file`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WavSpec {
+    pub sample_rate: u32,
+    pub format: SampleFormat,
+    /// Add TPDF dither before quantizing to an integer format
+    pub dither: bool,
+}
+
+/// Write non-interleaved `[channel][frame]` audio as a WAV file. Not RT-safe.
+pub fn write_file(path: impl AsRef<Path>, channels: &[Vec<f32>], spec: WavSpec) -> WavResult<()> {
+    fs::write(path, write_bytes(channels, spec)?)?;
+    Ok(())
+}
+
+/// Encode non-interleaved audio as an in-memory WAV file
+pub fn write_bytes(channels: &[Vec<f32>], spec: WavSpec) -> WavResult<Vec<u8>> {
+    if channels.is_empty() || channels.len() > u16::MAX as usize {
+        return Err(WavError::InvalidFormat(format!("cannot write {} channels", channels.len())));
+    }
+    let frames = channels.iter().map(Vec::len).min().unwrap_or(0);
+    let width = spec.format.bits() as usize / 8;
+    let data_len = frames * channels.len() * width;
+    if u32::try_from(data_len + 36).is_err() {
+        return Err(WavError::Unsupported("file larger than 4 GiB".into()));
+    }
+
+    let mut out = Vec::with_capacity(data_len + HEADER_BYTES);
+    out.extend_from_slice(&header(channels.len() as u16, spec, data_len as u32));
+
+    let mut dither = Dither::new(spec.dither);
+    for frame in 0..frames {
+        for channel in channels {
+            encode(&mut out, channel[frame], spec.format, &mut dither);
+        }
+    }
+    Ok(out)
+}
+
+/// Size of the header written by `write_bytes` and `WavWriter`
+const HEADER_BYTES: usize = 44;
+
+fn header(channels: u16, spec: WavSpec, data_len: u32) -> [u8; HEADER_BYTES] {
+    let encoding = match spec.format {
+        SampleFormat::Float32 => FORMAT_FLOAT,
+        _ => FORMAT_PCM,
+    };
+    let block_align = channels * spec.format.bits() / 8;
+
+    let mut out = [0u8; HEADER_BYTES];
+    out[0..4].copy_from_slice(b"RIFF");
+    out[4..8].copy_from_slice(&data_len.saturating_add(36).to_le_bytes());
+    out[8..16].copy_from_slice(b"WAVEfmt ");
+    out[16..20].copy_from_slice(&16u32.to_le_bytes());
+    out[20..22].copy_from_slice(&encoding.to_le_bytes());
+    out[22..24].copy_from_slice(&channels.to_le_bytes());
+    out[24..28].copy_from_slice(&spec.sample_rate.to_le_bytes());
+    out[28..32].copy_from_slice(&(spec.sample_rate * block_align as u32).to_le_bytes());
+    out[32..34].copy_from_slice(&block_align.to_le_bytes());
+    out[34..36].copy_from_slice(&spec.format.bits().to_le_bytes());
+    out[36..40].copy_from_slice(b"data");
+    out[40..44].copy_from_slice(&data_len.to_le_bytes());
+    out
+}
+
+#[inline]
+fn encode(out: &mut Vec<u8>, sample: f32, format: SampleFormat, dither: &mut Dither) {
+    match format {
+        SampleFormat::Int16 => {
+            let q = quantize(sample, 32767.0, dither.next()) as i16;
+            out.extend_from_slice(&q.to_le_bytes());
+        }
+        SampleFormat::Int24 => {
+            let q = quantize(sample, 8_388_607.0, dither.next());
+            out.extend_from_slice(&q.to_le_bytes()[..3]);
+        }
+        SampleFormat::Float32 => out.extend_from_slice(&sample.to_le_bytes()),
+    }
+}
+
+/// Incremental WAV writer for audio of unknown length (recordings).
+///
+/// The header's sizes are patched on every `flush`, so the file is a valid,
+/// readable WAV of everything written so far even while the writer is open.
+/// Not RT-safe.
+pub struct WavWriter {
+    file: fs::File,
+    channels: usize,
+    spec: WavSpec,
+    data_len: u32,
+    dither: Dither,
+    buffer: Vec<u8>,
+}
+
+impl WavWriter {
+    /// Create (or truncate) `path` for `channels` channels
+    pub fn create(path: impl AsRef<Path>, channels: usize, spec: WavSpec) -> WavResult<Self> {
+        if channels == 0 || channels > u16::MAX as usize {
+            return Err(WavError::InvalidFormat(format!("cannot write {} channels", channels)));
+        }
+        let mut file = fs::File::create(path)?;
+        file.write_all(&header(channels as u16, spec, 0))?;
+        Ok(Self { file, channels, spec, data_len: 0, dither: Dither::new(spec.dither), buffer: Vec::new() })
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    /// Frames written so far
+    pub fn frames(&self) -> usize {
+        self.data_len as usize / (self.channels * self.spec.format.bits() as usize / 8)
+    }
+
+    /// Append non-interleaved `[channel][frame]` audio; missing channels are
+    /// written as silence
+    pub fn write(&mut self, channels: &[&[f32]], frames: usize) -> WavResult<()> {
+        self.buffer.clear();
+        for frame in 0..frames {
+            for channel in 0..self.channels {
+                let s = channels.get(channel).and_then(|c| c.get(frame)).copied().unwrap_or(0.0);
+                encode(&mut self.buffer, s, self.spec.format, &mut self.dither);
+            }
+        }
+        self.write_encoded()
+    }
+
+    /// Append interleaved audio
+    pub fn write_interleaved(&mut self, samples: &[f32]) -> WavResult<()> {
+        self.buffer.clear();
+        for &s in &samples[..samples.len() - samples.len() % self.channels] {
+            encode(&mut self.buffer, s, self.spec.format, &mut self.dither);
+        }
+        self.write_encoded()
+    }
+
+    fn write_encoded(&mut self) -> WavResult<()> {
+        let len = u32::try_from(self.buffer.len())
+            .ok()
+            .and_then(|len| self.data_len.checked_add(len))
+            .filter(|total| *total <= u32::MAX - 36)
+            .ok_or_else(|| WavError::Unsupported("file larger than 4 GiB".into()))?;
+        self.file.write_all(&self.buffer)?;
+        self.data_len = len;
+        Ok(())
+    }
+
+    /// Update the header to cover everything written so far
+    pub fn flush(&mut self) -> WavResult<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header(self.channels as u16, self.spec, self.data_len))?;
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.flush()?;
+        Ok(())
+    }
+
+    /// Flush and close the file
+    pub fn finish(mut self) -> WavResult<()> {
+        self.flush()?;
+        self.file.sync_all()?;
+        Ok(())
+    }
+}
+
+#[inline]
+fn quantize(sample: f32, full_scale: f32, dither: f32) -> i32 {
+    (sample * full_scale + dither).round().clamp(-full_scale - 1.0, full_scale) as i32
+}
+
+/// Triangular (TPDF) dither, ±1 LSB
+struct Dither {
+    enabled: bool,
+    rng: FastRng,
+}
+
+impl Dither {
+    fn new(enabled: bool) -> Self {
+        Self { enabled, rng: FastRng::new(1) }
+    }
+
+    /// Dither offset in LSBs
+    #[inline]
+    fn next(&mut self) -> f32 {
+        if self.enabled { self.rng.next_f32() - self.rng.next_f32() } else { 0.0 }
+    }
+}
@@ -0,0 +1,302 @@
+use std::fmt;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crossbeam::atomic::AtomicCell;
+
+pub mod pool;
+
+pub use pool::{JobPriority, PoolConfig, WorkerPool};
+
+/// Why a job produced no value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JobError {
+    /// `cancel` was called before the job finished
+    Cancelled,
+    /// The job panicked; carries the panic message if it was a string
+    Panicked(String),
+    /// The result was already taken through another accessor
+    Taken,
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::Cancelled => write!(f, "job cancelled"),
+            JobError::Panicked(msg) => write!(f, "job panicked: {}", msg),
+            JobError::Taken => write!(f, "job result already taken"),
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
+
+pub type JobOutcome<T> = Result<T, JobError>;
+
+/// Progress and cancellation state shared by a job and its handle
+#[derive(Default)]
+struct Control {
+    progress: AtomicCell<f32>,
+    cancelled: AtomicBool,
+}
+
+/// Handed to the running job: report progress and poll for cancellation.
+///
+/// Cancellation is cooperative. Job code should check `is_cancelled` between
+/// units of work (blocks, files, partitions) and return early when it is set.
+#[derive(Clone)]
+pub struct JobProgress {
+    control: Arc<Control>,
+}
+
+impl JobProgress {
+    /// Progress that nobody observes and that is never cancelled, for running
+    /// job-aware code synchronously.
+    pub fn detached() -> Self {
+        Self { control: Arc::new(Control::default()) }
+    }
+
+    /// Report progress, 0.0 to 1.0
+    pub fn set(&self, fraction: f32) {
+        self.control.progress.store(fraction.clamp(0.0, 1.0));
+    }
+
+    pub fn get(&self) -> f32 {
+        self.control.progress.load()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.control.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+type Callback<T> = Box<dyn FnOnce(&JobOutcome<T>) + Send>;
+
+enum Slot<T> {
+    Pending { callbacks: Vec<Callback<T>>, waker: Option<Waker> },
+    Done(Option<JobOutcome<T>>),
+}
+
+struct Completion<T> {
+    slot: Mutex<Slot<T>>,
+    finished: Condvar,
+}
+
+impl<T> Completion<T> {
+    fn new() -> Self {
+        Self { slot: Mutex::new(Slot::Pending { callbacks: Vec::new(), waker: None }), finished: Condvar::new() }
+    }
+
+    fn complete(&self, outcome: JobOutcome<T>) {
+        loop {
+            let mut slot = self.slot.lock().unwrap_or_else(|e| e.into_inner());
+            let Slot::Pending { callbacks, waker } = &mut *slot else { return };
+            if callbacks.is_empty() {
+                let waker = waker.take();
+                *slot = Slot::Done(Some(outcome));
+                drop(slot);
+                self.finished.notify_all();
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+                return;
+            }
+            // callbacks run without the lock so they may use the handle; any
+            // registered meanwhile are picked up on the next pass
+            let batch = std::mem::take(callbacks);
+            drop(slot);
+            for callback in batch {
+                // a panicking callback must not unwind through the worker
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| callback(&outcome)));
+            }
+        }
+    }
+}
+
+/// Handle to a long-running non-RT operation (file load, offline render, IR
+/// preparation, ...) queued on a `WorkerPool`.
+///
+/// Exposes progress, cooperative cancellation, completion callbacks, a
+/// blocking `wait`, and `Future` for async hosts. Dropping the handle does not
+/// cancel the job; cancelling a job that hasn't started yet skips it.
+pub struct JobHandle<T> {
+    name: String,
+    control: Arc<Control>,
+    completion: Arc<Completion<T>>,
+}
+
+impl<T> JobHandle<T> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Last reported progress, 0.0 to 1.0
+    pub fn progress(&self) -> f32 {
+        self.control.progress.load()
+    }
+
+    /// Ask the job to stop. Its outcome becomes `JobError::Cancelled` unless it
+    /// had already finished.
+    pub fn cancel(&self) {
+        self.control.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.control.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(*self.completion.slot.lock().unwrap_or_else(|e| e.into_inner()), Slot::Done(_))
+    }
+
+    /// Run `callback` with the outcome once the job finishes, on the worker thread.
+    /// If it already finished, the callback runs immediately on the calling thread
+    /// (with `JobError::Taken` if the result was already taken).
+    pub fn on_complete(&self, callback: impl FnOnce(&JobOutcome<T>) + Send + 'static) {
+        let mut slot = self.completion.slot.lock().unwrap_or_else(|e| e.into_inner());
+        match &mut *slot {
+            Slot::Pending { callbacks, .. } => callbacks.push(Box::new(callback)),
+            Slot::Done(outcome) => match outcome {
+                Some(outcome) => callback(outcome),
+                None => callback(&Err(JobError::Taken)),
+            },
+        }
+    }
+
+    /// Take the outcome if the job has finished
+    pub fn try_take(&self) -> Option<JobOutcome<T>> {
+        match &mut *self.completion.slot.lock().unwrap_or_else(|e| e.into_inner()) {
+            Slot::Pending { .. } => None,
+            Slot::Done(outcome) => Some(outcome.take().unwrap_or(Err(JobError::Taken))),
+        }
+    }
+
+    /// Block until the job finishes
+    pub fn wait(self) -> JobOutcome<T> {
+        let mut slot = self.completion.slot.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            match &mut *slot {
+                Slot::Done(outcome) => return outcome.take().unwrap_or(Err(JobError::Taken)),
+                Slot::Pending { .. } => {
+                    slot = self.completion.finished.wait(slot).unwrap_or_else(|e| e.into_inner());
+                }
+            }
+        }
+    }
+}
+
+impl<T> Future for JobHandle<T> {
+    type Output = JobOutcome<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match &mut *self.completion.slot.lock().unwrap_or_else(|e| e.into_inner()) {
+            Slot::Done(outcome) => Poll::Ready(outcome.take().unwrap_or(Err(JobError::Taken))),
+            Slot::Pending { waker, .. } => {
+                *waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Create a handle and the task that completes it. The task runs the job when
+/// called with `true` and completes it as cancelled when called with `false`.
+fn job_task<T, F>(name: String, job: F) -> (JobHandle<T>, impl FnOnce(bool) + Send + 'static)
+where
+    T: Send + 'static,
+    F: FnOnce(&JobProgress) -> T + Send + 'static,
+{
+    let control = Arc::new(Control::default());
+    let completion = Arc::new(Completion::new());
+
+    let progress = JobProgress { control: Arc::clone(&control) };
+    let job_completion = Arc::clone(&completion);
+    let task = move |run: bool| {
+        if !run || progress.is_cancelled() {
+            job_completion.complete(Err(JobError::Cancelled));
+            return;
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| job(&progress)));
+        let outcome = match result {
+            _ if progress.is_cancelled() => Err(JobError::Cancelled),
+            Ok(value) => {
+                progress.set(1.0);
+                Ok(value)
+            }
+            Err(payload) => Err(JobError::Panicked(panic_message(payload.as_ref()))),
+        };
+        job_completion.complete(outcome);
+    };
+
+    (JobHandle { name, control, completion }, task)
+}
+
+/// Create one handle for a job split over `items`, and a task per item. A task
+/// runs `job` on its item unless the job was cancelled; the last task to finish
+/// completes the handle with the results in item order.
+fn split_tasks<I, T, F>(
+    name: String,
+    items: Vec<I>,
+    job: F,
+) -> (JobHandle<Vec<T>>, Vec<impl FnOnce(bool) + Send + 'static>)
+where
+    I: Send + 'static,
+    T: Send + 'static,
+    F: Fn(I, &JobProgress) -> T + Send + Sync + 'static,
+{
+    let control = Arc::new(Control::default());
+    let completion = Arc::new(Completion::new());
+    if items.is_empty() {
+        completion.complete(Ok(Vec::new()));
+    }
+
+    let job = Arc::new(job);
+    let results: Arc<Mutex<Vec<Option<JobOutcome<T>>>>> = Arc::new(Mutex::new(items.iter().map(|_| None).collect()));
+    let left = Arc::new(AtomicUsize::new(items.len()));
+    let tasks = items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let (job, results, left) = (Arc::clone(&job), Arc::clone(&results), Arc::clone(&left));
+            let progress = JobProgress { control: Arc::clone(&control) };
+            let job_completion = Arc::clone(&completion);
+            move |run: bool| {
+                if run && !progress.is_cancelled() {
+                    let outcome = panic::catch_unwind(AssertUnwindSafe(|| job(item, &progress)))
+                        .map_err(|payload| JobError::Panicked(panic_message(payload.as_ref())));
+                    results.lock().unwrap_or_else(|e| e.into_inner())[index] = Some(outcome);
+                }
+                if left.fetch_sub(1, Ordering::AcqRel) > 1 {
+                    return;
+                }
+                // items that never ran leave the whole job cancelled
+                let results = std::mem::take(&mut *results.lock().unwrap_or_else(|e| e.into_inner()));
+                let outcome: JobOutcome<Vec<T>> =
+                    results.into_iter().map(|result| result.unwrap_or(Err(JobError::Cancelled))).collect();
+                let outcome = match outcome {
+                    _ if progress.is_cancelled() => Err(JobError::Cancelled),
+                    Ok(values) => {
+                        progress.set(1.0);
+                        Ok(values)
+                    }
+                    Err(error) => Err(error),
+                };
+                job_completion.complete(outcome);
+            }
+        })
+        .collect();
+
+    (JobHandle { name, control, completion }, tasks)
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".into())
+}
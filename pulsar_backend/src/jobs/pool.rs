@@ -0,0 +1,238 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+use super::{JobHandle, JobProgress, job_task, split_tasks};
+
+/// Scheduling class of a job
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum JobPriority {
+    /// Short, latency-sensitive work someone is waiting on (file loads,
+    /// directory scans). Always dequeued before compute jobs.
+    Io,
+    /// Long CPU-bound work (offline renders, IR preparation, analysis)
+    Compute,
+}
+
+/// Worker pool size
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Total worker threads
+    pub threads: usize,
+    /// Threads kept free of compute jobs so IO never waits behind a long render
+    pub io_reserved: usize,
+}
+
+impl Default for PoolConfig {
+    /// One thread per core (at least two), one of them reserved for IO
+    fn default() -> Self {
+        let cores = thread::available_parallelism().map_or(2, |n| n.get());
+        Self { threads: cores.max(2), io_reserved: 1 }
+    }
+}
+
+impl PoolConfig {
+    pub fn new(threads: usize, io_reserved: usize) -> Self {
+        Self { threads, io_reserved }
+    }
+
+    fn sanitize(self) -> Self {
+        let threads = self.threads.max(1);
+        // at least one thread must be able to run compute jobs
+        Self { threads, io_reserved: self.io_reserved.min(threads - 1) }
+    }
+
+    fn compute_slots(&self) -> usize {
+        self.threads - self.io_reserved
+    }
+}
+
+/// A queued job: runs it with `true`, or completes it as cancelled with `false`
+type Task = Box<dyn FnOnce(bool) + Send>;
+
+struct PoolState {
+    config: PoolConfig,
+    io: VecDeque<Task>,
+    compute: VecDeque<Task>,
+    live: usize,
+    running_compute: usize,
+    shutdown: bool,
+}
+
+struct PoolShared {
+    state: Mutex<PoolState>,
+    changed: Condvar,
+}
+
+impl PoolShared {
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Pool statistics
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub threads: usize,
+    pub queued_io: usize,
+    pub queued_compute: usize,
+    pub running_compute: usize,
+}
+
+/// Background worker pool shared by the non-RT subsystems (IO, analysis,
+/// offline rendering, watchers). Each `Engine` owns one (`Engine::pool`), and
+/// the `*_job` functions take the pool to queue on.
+///
+/// Jobs go into one of two FIFO queues by `JobPriority`; idle workers take IO
+/// jobs first. Compute jobs never occupy more than `threads - io_reserved`
+/// workers. The pool can be resized while running; dropping it cancels queued
+/// jobs and lets running ones finish in the background.
+pub struct WorkerPool {
+    shared: Arc<PoolShared>,
+}
+
+impl WorkerPool {
+    pub fn new(config: PoolConfig) -> Self {
+        let pool = Self {
+            shared: Arc::new(PoolShared {
+                state: Mutex::new(PoolState {
+                    config: config.sanitize(),
+                    io: VecDeque::new(),
+                    compute: VecDeque::new(),
+                    live: 0,
+                    running_compute: 0,
+                    shutdown: false,
+                }),
+                changed: Condvar::new(),
+            }),
+        };
+        pool.spawn_workers();
+        pool
+    }
+
+    pub fn config(&self) -> PoolConfig {
+        self.shared.lock().config
+    }
+
+    /// Change the pool size. Extra workers exit once they finish their current job.
+    pub fn resize(&self, config: PoolConfig) {
+        self.shared.lock().config = config.sanitize();
+        self.shared.changed.notify_all();
+        self.spawn_workers();
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let state = self.shared.lock();
+        PoolStats {
+            threads: state.live,
+            queued_io: state.io.len(),
+            queued_compute: state.compute.len(),
+            running_compute: state.running_compute,
+        }
+    }
+
+    /// Queue `job` and return its handle
+    pub fn spawn<T, F>(&self, priority: JobPriority, name: impl Into<String>, job: F) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&JobProgress) -> T + Send + 'static,
+    {
+        let (handle, task) = job_task(name.into(), job);
+        self.queue(priority, vec![Box::new(task)]);
+        handle
+    }
+
+    /// Queue a job split into one task per item, so the items run side by side
+    /// on as many workers as `priority` allows. The handle reports the items'
+    /// results, in order, once all of them have run; a panicking item fails the
+    /// job but not the others. Don't wait on it from a job on the same pool.
+    pub fn spawn_each<I, T, F>(
+        &self,
+        priority: JobPriority,
+        name: impl Into<String>,
+        items: Vec<I>,
+        job: F,
+    ) -> JobHandle<Vec<T>>
+    where
+        I: Send + 'static,
+        T: Send + 'static,
+        F: Fn(I, &JobProgress) -> T + Send + Sync + 'static,
+    {
+        let (handle, tasks) = split_tasks(name.into(), items, job);
+        self.queue(priority, tasks.into_iter().map(|task| Box::new(task) as Task).collect());
+        handle
+    }
+
+    fn queue(&self, priority: JobPriority, tasks: Vec<Task>) {
+        let mut state = self.shared.lock();
+        if state.shutdown {
+            drop(state);
+            tasks.into_iter().for_each(|task| task(false));
+            return;
+        }
+        match priority {
+            JobPriority::Io => state.io.extend(tasks),
+            JobPriority::Compute => state.compute.extend(tasks),
+        }
+        drop(state);
+        self.shared.changed.notify_all();
+    }
+
+    fn spawn_workers(&self) {
+        let mut state = self.shared.lock();
+        while state.live < state.config.threads {
+            let shared = Arc::clone(&self.shared);
+            let index = state.live;
+            let spawned = thread::Builder::new()
+                .name(format!("pulsar-worker-{}", index))
+                .spawn(move || worker_loop(&shared));
+            if spawned.is_err() {
+                break;
+            }
+            state.live += 1;
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.shutdown = true;
+        let mut queued: Vec<Task> = state.io.drain(..).collect();
+        queued.extend(state.compute.drain(..));
+        drop(state);
+        self.shared.changed.notify_all();
+        for task in queued {
+            task(false);
+        }
+    }
+}
+
+fn worker_loop(shared: &PoolShared) {
+    let mut state = shared.lock();
+    loop {
+        if state.shutdown || state.live > state.config.threads {
+            state.live -= 1;
+            return;
+        }
+        if let Some(task) = state.io.pop_front() {
+            drop(state);
+            task(true);
+            state = shared.lock();
+            continue;
+        }
+        if state.running_compute < state.config.compute_slots()
+            && let Some(task) = state.compute.pop_front()
+        {
+            state.running_compute += 1;
+            drop(state);
+            task(true);
+            state = shared.lock();
+            state.running_compute -= 1;
+            // a compute slot opened up
+            shared.changed.notify_all();
+            continue;
+        }
+        state = shared.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+    }
+}
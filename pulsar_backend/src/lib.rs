@@ -1,2 +1,11 @@
 pub mod rt_processing;
-pub mod audio_device;
\ No newline at end of file
+pub mod audio_device;
+pub mod io;
+pub mod offline;
+pub mod jobs;
+pub mod engine;
+pub mod project;
+pub mod remote;
+pub mod plugins;
+pub mod midi;
+pub mod media;
@@ -0,0 +1,231 @@
+//! OS media controls: play/pause/next from media keys, headset buttons and the
+//! desktop's now-playing widget, for when Pulsar runs as a playback engine.
+//!
+//! A `MediaSession` collects commands from the OS (and from anything else
+//! holding its sender) and shows the transport state and the current track
+//! back to it. `apply` runs the transport commands against an engine's
+//! `Transport`; track changes are handed back to the caller, who owns the
+//! playlist.
+//!
+//! The OS side needs the `media-keys` feature: MPRIS on Linux, the System
+//! Media Transport Controls on Windows, and MPNowPlayingInfoCenter with
+//! MPRemoteCommandCenter on macOS. Elsewhere, or without the feature,
+//! `connect` reports `Unsupported`, and a session from `new` still works for
+//! in-process commands.
+
+#[cfg(all(target_os = "linux", feature = "media-keys"))]
+mod mpris;
+#[cfg(all(target_os = "macos", feature = "media-keys"))]
+mod now_playing;
+#[cfg(all(target_os = "windows", feature = "media-keys"))]
+mod smtc;
+
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use crate::engine::{Engine, EngineResult};
+use crate::rt_processing::transport::TransportState;
+
+pub type MediaResult<T> = Result<T, MediaError>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MediaError {
+    /// No media controls for this platform or build
+    Unsupported,
+    /// The OS service refused or went away
+    Backend(String),
+}
+
+impl fmt::Display for MediaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported => write!(f, "media controls are not supported on this platform or build"),
+            Self::Backend(msg) => write!(f, "media controls failed: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for MediaError {}
+
+/// What the OS asked for
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MediaCommand {
+    Play,
+    Pause,
+    PlayPause,
+    Stop,
+    Next,
+    Previous,
+}
+
+impl MediaCommand {
+    /// Whether `apply` handles it; the rest are for the caller
+    pub fn is_transport(self) -> bool {
+        !matches!(self, MediaCommand::Next | MediaCommand::Previous)
+    }
+}
+
+/// Transport state as the OS shows it: stopped is parked at the start,
+/// paused is stopped anywhere else
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    #[default]
+    Stopped,
+}
+
+impl From<TransportState> for PlaybackStatus {
+    fn from(state: TransportState) -> Self {
+        match (state.playing, state.frame) {
+            (true, _) => PlaybackStatus::Playing,
+            (false, 0) => PlaybackStatus::Stopped,
+            (false, _) => PlaybackStatus::Paused,
+        }
+    }
+}
+
+/// The track shown in the OS widget
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NowPlaying {
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub length: Option<Duration>,
+}
+
+/// OS side of a session
+trait MediaBackend: Send {
+    fn update(&self, status: PlaybackStatus, now_playing: &NowPlaying) -> MediaResult<()>;
+}
+
+/// Media controls for one player.
+///
+/// Commands queue until the caller takes them (`try_recv`, `apply`), so the
+/// OS never calls into the engine directly.
+pub struct MediaSession {
+    sender: Sender<MediaCommand>,
+    commands: Receiver<MediaCommand>,
+    status: PlaybackStatus,
+    now_playing: NowPlaying,
+    backend: Option<Box<dyn MediaBackend>>,
+}
+
+impl Default for MediaSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MediaSession {
+    /// Session without OS integration; commands come only through `sender`
+    pub fn new() -> Self {
+        let (sender, commands) = mpsc::channel();
+        Self { sender, commands, status: PlaybackStatus::Stopped, now_playing: NowPlaying::default(), backend: None }
+    }
+
+    /// Register with the OS media controls as `identity` (the name the
+    /// desktop shows; macOS shows the app's own name). `MediaError::Unsupported`
+    /// without the `media-keys` feature or outside Linux, Windows and macOS.
+    pub fn connect(identity: &str) -> MediaResult<Self> {
+        let mut session = Self::new();
+        session.backend = Some(Self::os_backend(identity, session.sender.clone())?);
+        Ok(session)
+    }
+
+    #[cfg(all(target_os = "linux", feature = "media-keys"))]
+    fn os_backend(identity: &str, sender: Sender<MediaCommand>) -> MediaResult<Box<dyn MediaBackend>> {
+        Ok(Box::new(mpris::Mpris::connect(identity, sender)?))
+    }
+
+    #[cfg(all(target_os = "windows", feature = "media-keys"))]
+    fn os_backend(identity: &str, sender: Sender<MediaCommand>) -> MediaResult<Box<dyn MediaBackend>> {
+        Ok(Box::new(smtc::Smtc::connect(identity, sender)?))
+    }
+
+    #[cfg(all(target_os = "macos", feature = "media-keys"))]
+    fn os_backend(_identity: &str, sender: Sender<MediaCommand>) -> MediaResult<Box<dyn MediaBackend>> {
+        Ok(Box::new(now_playing::NowPlayingCenter::connect(sender)?))
+    }
+
+    /// No backend on other platforms, or without `media-keys`
+    #[cfg(not(all(any(target_os = "linux", target_os = "windows", target_os = "macos"), feature = "media-keys")))]
+    fn os_backend(_identity: &str, _sender: Sender<MediaCommand>) -> MediaResult<Box<dyn MediaBackend>> {
+        Err(MediaError::Unsupported)
+    }
+
+    /// Whether the OS side is registered
+    pub fn is_connected(&self) -> bool {
+        self.backend.is_some()
+    }
+
+    /// For other command sources (UI buttons, remote clients)
+    pub fn sender(&self) -> Sender<MediaCommand> {
+        self.sender.clone()
+    }
+
+    /// Next pending command, if any
+    pub fn try_recv(&self) -> Option<MediaCommand> {
+        self.commands.try_recv().ok()
+    }
+
+    pub fn status(&self) -> PlaybackStatus {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: PlaybackStatus) -> MediaResult<()> {
+        if status == self.status {
+            return Ok(());
+        }
+        self.status = status;
+        self.publish()
+    }
+
+    pub fn now_playing(&self) -> &NowPlaying {
+        &self.now_playing
+    }
+
+    pub fn set_now_playing(&mut self, now_playing: NowPlaying) -> MediaResult<()> {
+        self.now_playing = now_playing;
+        self.publish()
+    }
+
+    fn publish(&self) -> MediaResult<()> {
+        match &self.backend {
+            Some(backend) => backend.update(self.status, &self.now_playing),
+            None => Ok(()),
+        }
+    }
+
+    /// Run the pending transport commands on the engine's `Transport` and
+    /// show the result to the OS. Play plays, pause stops where the playhead
+    /// is, and stop also goes back to the start. The engine's lifecycle is the
+    /// caller's: the playhead only moves while the engine is running. Fails
+    /// before the engine is configured. Returns the track commands for the
+    /// caller, in order.
+    pub fn apply(&mut self, engine: &Engine) -> EngineResult<Vec<MediaCommand>> {
+        let transport = engine.clock()?;
+        // the queued changes land at the next block, so follow them here
+        let mut state = transport.state();
+        let mut unhandled = Vec::new();
+        while let Some(command) = self.try_recv() {
+            match command {
+                MediaCommand::Play => state.playing |= transport.play(),
+                MediaCommand::Pause => state.playing &= !transport.stop(),
+                MediaCommand::PlayPause if state.playing => state.playing = !transport.stop(),
+                MediaCommand::PlayPause => state.playing = transport.play(),
+                MediaCommand::Stop => {
+                    state.playing &= !transport.stop();
+                    if transport.locate(0) {
+                        state.frame = 0;
+                    }
+                }
+                MediaCommand::Next | MediaCommand::Previous => unhandled.push(command),
+            }
+        }
+        // the OS keeps its old state if it can't be told; the transport is what counts
+        let _ = self.set_status(state.into());
+        Ok(unhandled)
+    }
+}
@@ -0,0 +1,254 @@
+//! MPRIS over the D-Bus session bus. zbus serves the method calls on its own
+//! thread; they only queue commands.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+
+use zbus::blocking::Connection;
+use zbus::blocking::connection::Builder;
+use zbus::interface;
+use zbus::zvariant::{ObjectPath, Value};
+
+use super::{MediaBackend, MediaCommand, MediaError, MediaResult, NowPlaying, PlaybackStatus};
+
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+/// There's one track at a time, so one id
+const TRACK_ID: &str = "/org/pulsar/track";
+
+impl From<zbus::Error> for MediaError {
+    fn from(e: zbus::Error) -> Self {
+        MediaError::Backend(e.to_string())
+    }
+}
+
+/// What the player properties read
+#[derive(Default)]
+struct State {
+    status: PlaybackStatus,
+    now_playing: NowPlaying,
+}
+
+fn status_name(status: PlaybackStatus) -> &'static str {
+    match status {
+        PlaybackStatus::Playing => "Playing",
+        PlaybackStatus::Paused => "Paused",
+        PlaybackStatus::Stopped => "Stopped",
+    }
+}
+
+fn metadata(now_playing: &NowPlaying) -> HashMap<String, Value<'static>> {
+    let mut map = HashMap::new();
+    map.insert("mpris:trackid".to_string(), Value::from(ObjectPath::from_static_str_unchecked(TRACK_ID)));
+    map.insert("xesam:title".to_string(), Value::from(now_playing.title.clone()));
+    if let Some(artist) = &now_playing.artist {
+        map.insert("xesam:artist".to_string(), Value::from(vec![artist.clone()]));
+    }
+    if let Some(album) = &now_playing.album {
+        map.insert("xesam:album".to_string(), Value::from(album.clone()));
+    }
+    if let Some(length) = now_playing.length {
+        map.insert("mpris:length".to_string(), Value::from(length.as_micros() as i64));
+    }
+    map
+}
+
+/// `org.mpris.MediaPlayer2`
+struct Root {
+    identity: String,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        self.identity.clone()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// `org.mpris.MediaPlayer2.Player`
+struct Player {
+    commands: Sender<MediaCommand>,
+    state: Arc<spin::Mutex<State>>,
+}
+
+impl Player {
+    fn send(&self, command: MediaCommand) {
+        // the session is gone; nothing left to drive
+        let _ = self.commands.send(command);
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn next(&self) {
+        self.send(MediaCommand::Next);
+    }
+
+    fn previous(&self) {
+        self.send(MediaCommand::Previous);
+    }
+
+    fn pause(&self) {
+        self.send(MediaCommand::Pause);
+    }
+
+    fn play_pause(&self) {
+        self.send(MediaCommand::PlayPause);
+    }
+
+    fn stop(&self) {
+        self.send(MediaCommand::Stop);
+    }
+
+    fn play(&self) {
+        self.send(MediaCommand::Play);
+    }
+
+    fn seek(&self, _offset: i64) {}
+
+    fn set_position(&self, _track: ObjectPath<'_>, _position: i64) {}
+
+    fn open_uri(&self, _uri: String) {}
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        status_name(self.state.lock().status).to_string()
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, Value<'static>> {
+        metadata(&self.state.lock().now_playing)
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// A registered MPRIS player
+pub(super) struct Mpris {
+    connection: Connection,
+    state: Arc<spin::Mutex<State>>,
+}
+
+impl Mpris {
+    pub(super) fn connect(identity: &str, commands: Sender<MediaCommand>) -> MediaResult<Self> {
+        let state = Arc::new(spin::Mutex::new(State::default()));
+        // bus names take letters, digits and underscores; the pid keeps instances apart
+        let suffix: String =
+            identity.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+        let name = format!("org.mpris.MediaPlayer2.{}.instance{}", suffix, std::process::id());
+        let player = Player { commands, state: Arc::clone(&state) };
+        let connection = Builder::session()?
+            .name(name)?
+            .serve_at(OBJECT_PATH, Root { identity: identity.to_string() })?
+            .serve_at(OBJECT_PATH, player)?
+            .build()?;
+        Ok(Self { connection, state })
+    }
+}
+
+impl MediaBackend for Mpris {
+    fn update(&self, status: PlaybackStatus, now_playing: &NowPlaying) -> MediaResult<()> {
+        let changed = {
+            let mut state = self.state.lock();
+            state.status = status;
+            state.now_playing = now_playing.clone();
+            let mut changed: HashMap<&str, Value<'static>> = HashMap::new();
+            changed.insert("PlaybackStatus", Value::from(status_name(status)));
+            changed.insert("Metadata", Value::from(metadata(now_playing)));
+            changed
+        };
+        let body = (PLAYER_INTERFACE, changed, Vec::<&str>::new());
+        self.connection.emit_signal(
+            None::<()>,
+            OBJECT_PATH,
+            "org.freedesktop.DBus.Properties",
+            "PropertiesChanged",
+            &body,
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+//! MPNowPlayingInfoCenter and MPRemoteCommandCenter on macOS. The command
+//! handlers run on the main thread's run loop, which any AppKit app runs;
+//! they only queue commands.
+
+use std::sync::mpsc::Sender;
+
+use block2::RcBlock;
+use objc2::rc::Retained;
+use objc2::runtime::AnyObject;
+use objc2::{class, msg_send};
+use objc2_foundation::{NSDictionary, NSNumber, NSString};
+
+use super::{MediaBackend, MediaCommand, MediaError, MediaResult, NowPlaying, PlaybackStatus};
+
+/// `MPRemoteCommandHandlerStatusSuccess`
+const HANDLER_SUCCESS: isize = 0;
+
+#[link(name = "MediaPlayer", kind = "framework")]
+unsafe extern "C" {
+    static MPMediaItemPropertyTitle: &'static NSString;
+    static MPMediaItemPropertyArtist: &'static NSString;
+    static MPMediaItemPropertyAlbumTitle: &'static NSString;
+    static MPMediaItemPropertyPlaybackDuration: &'static NSString;
+}
+
+/// `MPNowPlayingPlaybackState`
+fn playback_state(status: PlaybackStatus) -> usize {
+    match status {
+        PlaybackStatus::Playing => 1,
+        PlaybackStatus::Paused => 2,
+        PlaybackStatus::Stopped => 3,
+    }
+}
+
+fn info_center() -> MediaResult<Retained<AnyObject>> {
+    let center: Option<Retained<AnyObject>> = unsafe { msg_send![class!(MPNowPlayingInfoCenter), defaultCenter] };
+    center.ok_or_else(|| MediaError::Backend("no now playing info center".into()))
+}
+
+/// Handlers registered on the shared remote command center
+pub(super) struct NowPlayingCenter {
+    // each command with the target `addTargetWithHandler:` returned for it
+    targets: Vec<(Retained<AnyObject>, Retained<AnyObject>)>,
+}
+
+// SAFETY: both centers are process-wide singletons that may be messaged from
+// any thread, and the targets are opaque tokens only handed back to
+// `removeTarget:`.
+unsafe impl Send for NowPlayingCenter {}
+
+impl NowPlayingCenter {
+    pub(super) fn connect(commands: Sender<MediaCommand>) -> MediaResult<Self> {
+        let center: Option<Retained<AnyObject>> =
+            unsafe { msg_send![class!(MPRemoteCommandCenter), sharedCommandCenter] };
+        let center = center.ok_or_else(|| MediaError::Backend("no remote command center".into()))?;
+        let remote: [(Option<Retained<AnyObject>>, MediaCommand); 6] = unsafe {
+            [
+                (msg_send![&*center, playCommand], MediaCommand::Play),
+                (msg_send![&*center, pauseCommand], MediaCommand::Pause),
+                (msg_send![&*center, togglePlayPauseCommand], MediaCommand::PlayPause),
+                (msg_send![&*center, stopCommand], MediaCommand::Stop),
+                (msg_send![&*center, nextTrackCommand], MediaCommand::Next),
+                (msg_send![&*center, previousTrackCommand], MediaCommand::Previous),
+            ]
+        };
+
+        let mut targets = Vec::with_capacity(remote.len());
+        for (command, media_command) in remote {
+            let command = command.ok_or_else(|| MediaError::Backend("missing remote command".into()))?;
+            let sender = commands.clone();
+            let handler = RcBlock::new(move |_event: *mut AnyObject| -> isize {
+                // the session is gone; nothing left to drive
+                let _ = sender.send(media_command);
+                HANDLER_SUCCESS
+            });
+            // the command center keeps its own copy of the block
+            let target: Retained<AnyObject> = unsafe { msg_send![&*command, addTargetWithHandler: &*handler] };
+            let () = unsafe { msg_send![&*command, setEnabled: true] };
+            targets.push((command, target));
+        }
+        Ok(Self { targets })
+    }
+}
+
+impl MediaBackend for NowPlayingCenter {
+    fn update(&self, status: PlaybackStatus, now_playing: &NowPlaying) -> MediaResult<()> {
+        let title = NSString::from_str(&now_playing.title);
+        let artist = now_playing.artist.as_deref().map(NSString::from_str);
+        let album = now_playing.album.as_deref().map(NSString::from_str);
+        let length = now_playing.length.map(|length| NSNumber::new_f64(length.as_secs_f64()));
+
+        let mut keys: Vec<&NSString> = Vec::new();
+        let mut values: Vec<&AnyObject> = Vec::new();
+        unsafe {
+            keys.push(MPMediaItemPropertyTitle);
+            values.push(&title);
+            if let Some(artist) = &artist {
+                keys.push(MPMediaItemPropertyArtist);
+                values.push(artist);
+            }
+            if let Some(album) = &album {
+                keys.push(MPMediaItemPropertyAlbumTitle);
+                values.push(album);
+            }
+            if let Some(length) = &length {
+                keys.push(MPMediaItemPropertyPlaybackDuration);
+                values.push(length);
+            }
+        }
+        let info = NSDictionary::from_slices(&keys, &values);
+
+        let center = info_center()?;
+        unsafe {
+            let () = msg_send![&*center, setNowPlayingInfo: &*info];
+            let () = msg_send![&*center, setPlaybackState: playback_state(status)];
+        }
+        Ok(())
+    }
+}
+
+impl Drop for NowPlayingCenter {
+    fn drop(&mut self) {
+        for (command, target) in &self.targets {
+            let () = unsafe { msg_send![&**command, removeTarget: &**target] };
+        }
+        if let Ok(center) = info_center() {
+            let () = unsafe { msg_send![&*center, setNowPlayingInfo: None::<&AnyObject>] };
+        }
+    }
+}
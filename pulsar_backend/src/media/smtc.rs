@@ -0,0 +1,110 @@
+//! System Media Transport Controls on Windows. A desktop app without a window
+//! reaches them through a `MediaPlayer` whose own command handling is turned
+//! off; button presses arrive on a WinRT thread and only queue commands.
+
+use std::sync::mpsc::Sender;
+
+use windows::Foundation::{TimeSpan, TypedEventHandler};
+use windows::Media::Playback::MediaPlayer;
+use windows::Media::{
+    MediaPlaybackStatus, MediaPlaybackType, SystemMediaTransportControls, SystemMediaTransportControlsButton,
+    SystemMediaTransportControlsButtonPressedEventArgs, SystemMediaTransportControlsTimelineProperties,
+};
+use windows::core::HSTRING;
+
+use super::{MediaBackend, MediaCommand, MediaError, MediaResult, NowPlaying, PlaybackStatus};
+
+impl From<windows::core::Error> for MediaError {
+    fn from(e: windows::core::Error) -> Self {
+        MediaError::Backend(e.to_string())
+    }
+}
+
+fn status_value(status: PlaybackStatus) -> MediaPlaybackStatus {
+    match status {
+        PlaybackStatus::Playing => MediaPlaybackStatus::Playing,
+        PlaybackStatus::Paused => MediaPlaybackStatus::Paused,
+        PlaybackStatus::Stopped => MediaPlaybackStatus::Stopped,
+    }
+}
+
+type ButtonHandler =
+    TypedEventHandler<SystemMediaTransportControls, SystemMediaTransportControlsButtonPressedEventArgs>;
+
+/// `TimeSpan` counts 100 ns ticks
+fn ticks(duration: std::time::Duration) -> TimeSpan {
+    TimeSpan { Duration: (duration.as_nanos() / 100) as i64 }
+}
+
+/// Controls registered for this process
+pub(super) struct Smtc {
+    // the controls belong to the player and go away with it
+    _player: MediaPlayer,
+    controls: SystemMediaTransportControls,
+    button_token: i64,
+    identity: HSTRING,
+}
+
+impl Smtc {
+    pub(super) fn connect(identity: &str, commands: Sender<MediaCommand>) -> MediaResult<Self> {
+        let player = MediaPlayer::new()?;
+        player.CommandManager()?.SetIsEnabled(false)?;
+        let controls = player.SystemMediaTransportControls()?;
+        controls.SetIsEnabled(true)?;
+        controls.SetIsPlayEnabled(true)?;
+        controls.SetIsPauseEnabled(true)?;
+        controls.SetIsStopEnabled(true)?;
+        controls.SetIsNextEnabled(true)?;
+        controls.SetIsPreviousEnabled(true)?;
+
+        let handler = ButtonHandler::new(move |_, args| {
+            let Some(args) = args.as_ref() else {
+                return Ok(());
+            };
+            let command = match args.Button()? {
+                SystemMediaTransportControlsButton::Play => MediaCommand::Play,
+                SystemMediaTransportControlsButton::Pause => MediaCommand::Pause,
+                SystemMediaTransportControlsButton::Stop => MediaCommand::Stop,
+                SystemMediaTransportControlsButton::Next => MediaCommand::Next,
+                SystemMediaTransportControlsButton::Previous => MediaCommand::Previous,
+                _ => return Ok(()),
+            };
+            // the session is gone; nothing left to drive
+            let _ = commands.send(command);
+            Ok(())
+        });
+        let button_token = controls.ButtonPressed(&handler)?;
+        Ok(Self { _player: player, controls, button_token, identity: HSTRING::from(identity) })
+    }
+}
+
+impl MediaBackend for Smtc {
+    fn update(&self, status: PlaybackStatus, now_playing: &NowPlaying) -> MediaResult<()> {
+        self.controls.SetPlaybackStatus(status_value(status))?;
+
+        let display = self.controls.DisplayUpdater()?;
+        display.SetType(MediaPlaybackType::Music)?;
+        display.SetAppMediaId(&self.identity)?;
+        let music = display.MusicProperties()?;
+        music.SetTitle(&HSTRING::from(now_playing.title.as_str()))?;
+        music.SetArtist(&HSTRING::from(now_playing.artist.as_deref().unwrap_or_default()))?;
+        music.SetAlbumTitle(&HSTRING::from(now_playing.album.as_deref().unwrap_or_default()))?;
+        display.Update()?;
+
+        // without a length the controls show no timeline
+        let timeline = SystemMediaTransportControlsTimelineProperties::new()?;
+        let end = ticks(now_playing.length.unwrap_or_default());
+        timeline.SetEndTime(end)?;
+        timeline.SetMaxSeekTime(end)?;
+        self.controls.UpdateTimelineProperties(&timeline)?;
+        Ok(())
+    }
+}
+
+impl Drop for Smtc {
+    fn drop(&mut self) {
+        let _ = self.controls.RemoveButtonPressed(self.button_token);
+        let _ = self.controls.DisplayUpdater().and_then(|display| display.ClearAll());
+        let _ = self.controls.SetIsEnabled(false);
+    }
+}
@@ -0,0 +1,366 @@
+use super::controllers::SmoothedController;
+use super::{Controller, ControllerDecoder, ControllerSmoothing, ControllerValue, MidiMessage, MidiQueue};
+use crate::rt_processing::chords::{ChordShape, Strum, Strummer, Voicing};
+use crate::rt_processing::notes::{NoteExpression, SharedScale};
+use crate::rt_processing::prefault::Prefault;
+use crate::rt_processing::routing::AudioSource;
+use crate::rt_processing::voices::{MAX_VOICE_CHANNELS, VoicePool};
+
+/// Pitch-bend range of a fresh allocator, in semitones either way
+pub const DEFAULT_BEND_RANGE: f32 = 2.0;
+/// Per-note pitch-bend range of a fresh allocator, in semitones either way
+/// (the MPE default)
+pub const DEFAULT_NOTE_BEND_RANGE: f32 = 48.0;
+
+const CC_SUSTAIN: u8 = 64;
+const CC_ALL_SOUND_OFF: u8 = 120;
+const CC_RESET_CONTROLLERS: u8 = 121;
+const CC_ALL_NOTES_OFF: u8 = 123;
+/// Registered per-note controller for brightness (sound controller 5)
+const PER_NOTE_BRIGHTNESS: u8 = 74;
+/// Registered parameter holding the pitch-bend range
+const RPN_BEND_RANGE: u16 = 0;
+
+/// Plays MIDI from a `MidiQueue` on a `VoicePool`, as a routed source.
+///
+/// Every block starts by draining the queue, so events take effect at block
+/// boundaries. Notes go to the pool's allocator; the sustain pedal (CC 64)
+/// holds released notes until it is lifted, CC 120 cuts every voice and
+/// CC 123 releases them. Pitch bend reaches the voices scaled by the bend
+/// range, which RPN 0 sets; poly aftertouch becomes the pressure expression
+/// of the notes on that key. Listens on every channel unless limited to one
+/// with `with_channel`.
+///
+/// Other controllers are decoded by a `ControllerDecoder` (14-bit pairs,
+/// NRPN) and conditioned by `ControllerSmoothing` before they reach the
+/// voices: changes within the deadband are dropped and the rest glide at
+/// block rate, so 7-bit steps don't zipper. NRPN values pass straight through.
+///
+/// MIDI 2.0 messages arrive already decoded and at full resolution. Per-note
+/// pitch bend (scaled by its own range) and per-note pitch become the pitch
+/// offset of the notes on that key, and per-note brightness their brightness.
+///
+/// With a `SharedScale` set, every note-on is moved to the nearest note of
+/// the scale. Later messages for the key follow the note it started, even if
+/// the scale has changed since; keys that land on one note share it.
+///
+/// In chord mode every note-on plays a `ChordShape` on the played note
+/// instead, each note of it on the scale, optionally strummed. Strummed notes
+/// start on their exact frame; releasing the key releases the whole chord and
+/// drops any notes not yet strummed. Per-note messages for the key reach every
+/// note of its chord.
+pub struct MidiVoiceAllocator {
+    queue: MidiQueue,
+    pool: VoicePool,
+    channel: Option<u8>,
+    bend_range: f32,
+    note_bend_range: f32,
+    // last pitch bend, -1.0 to 1.0
+    bend: f32,
+    sustain: bool,
+    // released while the pedal was down, still sounding
+    sustained: [bool; 128],
+    scale: SharedScale,
+    chord: Option<ChordShape>,
+    strum: Strum,
+    strummer: Strummer,
+    // notes each key is playing, after the scale and chord
+    played: [Voicing; 128],
+    decoder: ControllerDecoder,
+    smoothing: ControllerSmoothing,
+    controllers: [SmoothedController; 128],
+}
+
+impl MidiVoiceAllocator {
+    pub fn new(queue: MidiQueue, pool: VoicePool) -> Self {
+        Self {
+            queue,
+            pool,
+            channel: None,
+            bend_range: DEFAULT_BEND_RANGE,
+            note_bend_range: DEFAULT_NOTE_BEND_RANGE,
+            bend: 0.0,
+            sustain: false,
+            sustained: [false; 128],
+            scale: SharedScale::default(),
+            chord: None,
+            strum: Strum::default(),
+            strummer: Strummer::default(),
+            played: std::array::from_fn(|key| Voicing::single(key as u8)),
+            decoder: ControllerDecoder::new(),
+            smoothing: ControllerSmoothing::default(),
+            controllers: [SmoothedController::default(); 128],
+        }
+    }
+
+    /// Only play `channel` (0-based); `None` listens to all
+    pub fn with_channel(mut self, channel: Option<u8>) -> Self {
+        self.set_channel(channel);
+        self
+    }
+
+    pub fn set_channel(&mut self, channel: Option<u8>) {
+        self.channel = channel.map(|c| c & 0x0F);
+    }
+
+    /// Semitones a full pitch-bend reaches
+    pub fn with_bend_range(mut self, semitones: f32) -> Self {
+        self.set_bend_range(semitones);
+        self
+    }
+
+    pub fn set_bend_range(&mut self, semitones: f32) {
+        self.bend_range = semitones.max(0.0);
+        self.pool.pitch_bend(self.bend * self.bend_range);
+    }
+
+    /// Semitones a full per-note pitch bend reaches
+    pub fn with_note_bend_range(mut self, semitones: f32) -> Self {
+        self.set_note_bend_range(semitones);
+        self
+    }
+
+    pub fn set_note_bend_range(&mut self, semitones: f32) {
+        self.note_bend_range = semitones.max(0.0);
+    }
+
+    /// Constrain note-ons to `scale`, which may be shared with other
+    /// generators and changed while playing
+    pub fn with_scale(mut self, scale: SharedScale) -> Self {
+        self.set_scale(scale);
+        self
+    }
+
+    pub fn set_scale(&mut self, scale: SharedScale) {
+        self.scale = scale;
+    }
+
+    pub fn scale(&self) -> &SharedScale {
+        &self.scale
+    }
+
+    /// Chord mode: each note plays `chord` on it; see the type docs
+    pub fn with_chord(mut self, chord: Option<ChordShape>, strum: Strum) -> Self {
+        self.set_chord(chord);
+        self.set_strum(strum);
+        self
+    }
+
+    /// Takes effect from the next note-on; `None` plays single notes
+    pub fn set_chord(&mut self, chord: Option<ChordShape>) {
+        self.chord = chord.filter(|chord| !chord.is_empty());
+    }
+
+    pub fn chord(&self) -> Option<ChordShape> {
+        self.chord
+    }
+
+    pub fn set_strum(&mut self, strum: Strum) {
+        self.strum = strum.sanitize();
+    }
+
+    pub fn strum(&self) -> Strum {
+        self.strum
+    }
+
+    /// Glide and deadband for incoming controllers
+    pub fn with_controller_smoothing(mut self, smoothing: ControllerSmoothing) -> Self {
+        self.set_controller_smoothing(smoothing);
+        self
+    }
+
+    pub fn set_controller_smoothing(&mut self, smoothing: ControllerSmoothing) {
+        self.smoothing =
+            ControllerSmoothing { time: smoothing.time.max(0.0), deadband: smoothing.deadband.clamp(0.0, 1.0) };
+    }
+
+    pub fn controller_smoothing(&self) -> ControllerSmoothing {
+        self.smoothing
+    }
+
+    pub fn channel(&self) -> Option<u8> {
+        self.channel
+    }
+
+    pub fn bend_range(&self) -> f32 {
+        self.bend_range
+    }
+
+    pub fn note_bend_range(&self) -> f32 {
+        self.note_bend_range
+    }
+
+    pub fn queue(&self) -> &MidiQueue {
+        &self.queue
+    }
+
+    pub fn pool(&self) -> &VoicePool {
+        &self.pool
+    }
+
+    pub fn pool_mut(&mut self) -> &mut VoicePool {
+        &mut self.pool
+    }
+
+    /// Apply one message now, bypassing the queue
+    pub fn handle(&mut self, message: MidiMessage) {
+        if self.channel.is_some_and(|channel| channel != message.channel()) {
+            return;
+        }
+        match message {
+            MidiMessage::NoteOn { note: key, velocity, .. } => {
+                let key = key & 0x7F;
+                let root = self.scale.quantize(key);
+                let voicing = match self.chord {
+                    Some(chord) => chord.voicing(root, |note| self.scale.quantize(note)),
+                    None => Voicing::single(root),
+                };
+                self.strummer.cancel(key);
+                self.played[key as usize] = voicing;
+                for (note, velocity, delay) in self.strum.spread(voicing, velocity) {
+                    if delay == 0.0 || !self.strummer.schedule(key, note, velocity, delay) {
+                        self.start(note, velocity);
+                    }
+                }
+            }
+            MidiMessage::NoteOff { note: key, .. } => {
+                self.strummer.cancel(key & 0x7F);
+                for &note in self.played(key).notes() {
+                    if self.sustain {
+                        self.sustained[note as usize] = true;
+                    } else {
+                        self.pool.note_off(note);
+                    }
+                }
+            }
+            MidiMessage::ControlChange { controller: CC_SUSTAIN, value, .. } => self.set_sustain(value >= 64),
+            MidiMessage::ControlChange { controller: CC_ALL_SOUND_OFF, .. } => {
+                self.sustained = [false; 128];
+                self.strummer.clear();
+                self.pool.reset();
+            }
+            MidiMessage::PolyAftertouch { note, pressure, .. } => {
+                self.key_expression(note, NoteExpression::Pressure, |_| pressure)
+            }
+            MidiMessage::ControlChange { controller: CC_RESET_CONTROLLERS, .. } => {
+                self.set_sustain(false);
+                self.bend = 0.0;
+                self.pool.pitch_bend(0.0);
+            }
+            MidiMessage::ControlChange { controller: CC_ALL_NOTES_OFF, .. } => {
+                self.sustained = [false; 128];
+                self.strummer.clear();
+                self.pool.all_notes_off();
+            }
+            MidiMessage::ControlChange { channel, controller, value } => {
+                if let Some(decoded) = self.decoder.feed(channel, controller, value) {
+                    self.controller(decoded.controller, decoded.value, decoded.raw);
+                }
+            }
+            MidiMessage::PitchBend { value, .. } => {
+                self.bend = value;
+                self.pool.pitch_bend(value * self.bend_range);
+            }
+            // channel mode and pedal controllers act as their 7-bit forms do
+            MidiMessage::Controller { channel, value: ControllerValue { controller: Controller::Cc(controller), value, .. } }
+                if matches!(controller, CC_SUSTAIN | CC_ALL_SOUND_OFF | CC_RESET_CONTROLLERS | CC_ALL_NOTES_OFF) =>
+            {
+                self.handle(MidiMessage::ControlChange { channel, controller, value: (value * 127.0).round() as u8 })
+            }
+            MidiMessage::Controller { value, .. } => self.controller(value.controller, value.value, value.raw),
+            MidiMessage::PerNotePitchBend { note, value, .. } => {
+                let semitones = value * self.note_bend_range;
+                self.key_expression(note, NoteExpression::PitchOffset, |_| semitones)
+            }
+            MidiMessage::PerNotePitch { note: key, pitch, .. } => {
+                // every note of a chord moves by as much as the first
+                let offset = pitch - self.played(key).notes().first().copied().unwrap_or(key) as f32;
+                self.key_expression(key, NoteExpression::PitchOffset, |_| offset)
+            }
+            MidiMessage::PerNoteController { note, controller: PER_NOTE_BRIGHTNESS, value, .. } => {
+                self.key_expression(note, NoteExpression::Brightness, |_| value)
+            }
+            MidiMessage::PerNoteController { .. } => {}
+        }
+    }
+
+    /// Notes `key` is playing, after the scale and chord
+    fn played(&self, key: u8) -> Voicing {
+        self.played[key as usize & 0x7F]
+    }
+
+    fn start(&mut self, note: u8, velocity: f32) {
+        self.sustained[note as usize] = false;
+        self.pool.note_on(note, velocity);
+    }
+
+    /// Set `expression` on every note `key` is playing
+    fn key_expression(&mut self, key: u8, expression: NoteExpression, value: impl Fn(u8) -> f32) {
+        for &note in self.played(key).notes() {
+            self.pool.key_expression(note, expression, value(note));
+        }
+    }
+
+    fn controller(&mut self, controller: Controller, value: f32, raw: u16) {
+        match controller {
+            Controller::Cc(cc) => {
+                if let Some(value) = self.controllers[cc as usize & 0x7F].set(value, &self.smoothing) {
+                    self.pool.control_change(cc, value);
+                }
+            }
+            Controller::Nrpn(parameter) => self.pool.nrpn(parameter, value),
+            // semitones in the MSB, cents in the LSB
+            Controller::Rpn(RPN_BEND_RANGE) => self.set_bend_range((raw >> 7) as f32 + (raw & 0x7F) as f32 / 100.0),
+            Controller::Rpn(_) => {}
+        }
+    }
+
+    fn set_sustain(&mut self, down: bool) {
+        self.sustain = down;
+        if down {
+            return;
+        }
+        for (note, sustained) in self.sustained.iter_mut().enumerate() {
+            if std::mem::take(sustained) {
+                self.pool.note_off(note as u8);
+            }
+        }
+    }
+}
+
+impl AudioSource for MidiVoiceAllocator {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        while let Some(event) = self.queue.pop() {
+            self.handle(event.message);
+        }
+        for (cc, controller) in self.controllers.iter_mut().enumerate() {
+            if let Some(value) = controller.advance(frames, sample_rate, &self.smoothing) {
+                self.pool.control_change(cc as u8, value);
+            }
+        }
+        if self.strummer.is_empty() {
+            self.pool.render(output, frames, sample_rate);
+            return;
+        }
+        // render up to each strummed note, so it starts on its frame
+        let mut done = 0;
+        while done < frames {
+            while let Some((_, note, velocity)) = self.strummer.pop_due() {
+                self.start(note, velocity);
+            }
+            let until = self.strummer.next_due(sample_rate).map_or(frames, |due| due.max(1)).min(frames - done);
+            let mut views: [&mut [f32]; MAX_VOICE_CHANNELS] = Default::default();
+            let channels = output.len().min(MAX_VOICE_CHANNELS);
+            for (view, samples) in views.iter_mut().zip(output.iter_mut()) {
+                *view = &mut samples[done..done + until];
+            }
+            self.pool.render(&mut views[..channels], until, sample_rate);
+            self.strummer.advance(until, sample_rate);
+            done += until;
+        }
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        self.pool.prefault(memory);
+    }
+}
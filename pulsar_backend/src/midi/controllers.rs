@@ -0,0 +1,221 @@
+/// Data entry for the selected (N)RPN, coarse and fine
+const CC_DATA_ENTRY: u8 = 6;
+const CC_DATA_ENTRY_LSB: u8 = 38;
+const CC_NRPN_LSB: u8 = 98;
+const CC_NRPN_MSB: u8 = 99;
+const CC_RPN_LSB: u8 = 100;
+const CC_RPN_MSB: u8 = 101;
+/// Largest 14-bit value
+const MAX_14_BIT: f32 = 16_383.0;
+
+/// A controller after pairing and parameter-number decoding
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Controller {
+    /// A plain controller (7-bit, or 14-bit once its LSB has been seen)
+    Cc(u8),
+    /// Non-registered parameter number, 14-bit
+    Nrpn(u16),
+    /// Registered parameter number, 14-bit (0 = pitch-bend range)
+    Rpn(u16),
+}
+
+/// A decoded controller value
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ControllerValue {
+    pub controller: Controller,
+    /// 0.0 to 1.0
+    pub value: f32,
+    /// 0 to 16383 for 14-bit values (the top 14 bits of 32-bit ones), 0 to
+    /// 127 for 7-bit ones
+    pub raw: u16,
+}
+
+impl ControllerValue {
+    fn seven_bit(controller: Controller, value: u8) -> Self {
+        Self { controller, value: value as f32 / 127.0, raw: value as u16 }
+    }
+
+    fn fourteen_bit(controller: Controller, raw: u16) -> Self {
+        Self { controller, value: raw as f32 / MAX_14_BIT, raw }
+    }
+
+    pub(crate) fn thirty_two_bit(controller: Controller, data: u32) -> Self {
+        Self { controller, value: (data as f64 / u32::MAX as f64) as f32, raw: (data >> 18) as u16 }
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Selection {
+    None,
+    Nrpn,
+    Rpn,
+}
+
+/// Per-channel decoding state
+#[derive(Copy, Clone)]
+struct ChannelState {
+    // last MSB of each of controllers 0..32
+    msb: [u8; 32],
+    // controllers whose LSB (32..64) has been seen
+    fine: u32,
+    selection: Selection,
+    parameter: [u8; 2],
+    data: [u8; 2],
+}
+
+impl Default for ChannelState {
+    fn default() -> Self {
+        Self { msb: [0; 32], fine: 0, selection: Selection::None, parameter: [0x7F; 2], data: [0; 2] }
+    }
+}
+
+/// Turns raw 7-bit controller messages into controller values: 14-bit
+/// MSB/LSB pairs (controllers 0-31 with 32-63) and NRPN/RPN parameter
+/// changes (99/98 and 101/100 select, 6/38 enter data).
+///
+/// A controller is treated as 7-bit until its LSB arrives; from then on its
+/// MSB is scaled as the top of a 14-bit value, with the LSB reset to zero as
+/// the MIDI spec has it, and the LSB that follows completes it. Selecting the
+/// null RPN (127/127) ends (N)RPN data entry. Data increment and decrement
+/// (96/97) are not supported and pass through as plain controllers.
+#[derive(Clone)]
+pub struct ControllerDecoder {
+    channels: [ChannelState; 16],
+}
+
+impl Default for ControllerDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ControllerDecoder {
+    pub fn new() -> Self {
+        Self { channels: [ChannelState::default(); 16] }
+    }
+
+    /// Decode one controller message; `None` when it only selects a
+    /// parameter or holds an MSB for later
+    pub fn feed(&mut self, channel: u8, controller: u8, value: u8) -> Option<ControllerValue> {
+        let state = &mut self.channels[channel as usize & 0x0F];
+        let value = value & 0x7F;
+        match controller {
+            CC_NRPN_MSB | CC_NRPN_LSB | CC_RPN_MSB | CC_RPN_LSB => {
+                let nrpn = matches!(controller, CC_NRPN_MSB | CC_NRPN_LSB);
+                let msb = matches!(controller, CC_NRPN_MSB | CC_RPN_MSB);
+                state.parameter[if msb { 0 } else { 1 }] = value;
+                state.selection = match (nrpn, state.parameter) {
+                    (_, [0x7F, 0x7F]) => Selection::None,
+                    (true, _) => Selection::Nrpn,
+                    (false, _) => Selection::Rpn,
+                };
+                state.data = [0; 2];
+                None
+            }
+            CC_DATA_ENTRY | CC_DATA_ENTRY_LSB if !matches!(state.selection, Selection::None) => {
+                if controller == CC_DATA_ENTRY {
+                    state.data = [value, 0];
+                } else {
+                    state.data[1] = value;
+                }
+                let number = (state.parameter[0] as u16) << 7 | state.parameter[1] as u16;
+                let raw = (state.data[0] as u16) << 7 | state.data[1] as u16;
+                let controller = match state.selection {
+                    Selection::Rpn => Controller::Rpn(number),
+                    _ => Controller::Nrpn(number),
+                };
+                Some(ControllerValue::fourteen_bit(controller, raw))
+            }
+            0..32 => {
+                state.msb[controller as usize] = value;
+                if state.fine & 1 << controller != 0 {
+                    Some(ControllerValue::fourteen_bit(Controller::Cc(controller), (value as u16) << 7))
+                } else {
+                    Some(ControllerValue::seven_bit(Controller::Cc(controller), value))
+                }
+            }
+            32..64 => {
+                let coarse = controller - 32;
+                state.fine |= 1 << coarse;
+                let raw = (state.msb[coarse as usize] as u16) << 7 | value as u16;
+                Some(ControllerValue::fourteen_bit(Controller::Cc(coarse), raw))
+            }
+            _ => Some(ControllerValue::seven_bit(Controller::Cc(controller), value)),
+        }
+    }
+
+    /// Forget every pairing and parameter selection
+    pub fn reset(&mut self) {
+        self.channels = [ChannelState::default(); 16];
+    }
+}
+
+/// How incoming controller streams are conditioned before they reach voices
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ControllerSmoothing {
+    /// Time constant of the glide towards each new value, in seconds (0 = jump)
+    pub time: f32,
+    /// Changes smaller than this (normalized) are ignored, against jitter
+    /// from noisy controls
+    pub deadband: f32,
+}
+
+impl Default for ControllerSmoothing {
+    /// 20 ms glide, no deadband
+    fn default() -> Self {
+        Self { time: 0.02, deadband: 0.0 }
+    }
+}
+
+/// Smoothing state of one controller
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SmoothedController {
+    target: Option<f32>,
+    current: f32,
+}
+
+/// Values closer to the target than this are snapped to it
+const SETTLE: f32 = 1e-4;
+
+impl SmoothedController {
+    /// Take a new value. Returns it when it applies at once (the first value,
+    /// or no glide); `None` while it is glided to by `advance`, or ignored
+    /// within the deadband.
+    pub fn set(&mut self, value: f32, smoothing: &ControllerSmoothing) -> Option<f32> {
+        match self.target {
+            Some(target) if (value - target).abs() < smoothing.deadband => None,
+            Some(_) if smoothing.time > 0.0 => {
+                self.target = Some(value);
+                None
+            }
+            _ => {
+                self.target = Some(value);
+                self.current = value;
+                Some(value)
+            }
+        }
+    }
+
+    /// Glide over one block of `frames`; the new value if it moved
+    pub fn advance(&mut self, frames: usize, sample_rate: f32, smoothing: &ControllerSmoothing) -> Option<f32> {
+        let target = self.target?;
+        if self.current == target {
+            return None;
+        }
+        let coefficient = 1.0 - (-(frames as f32) / (smoothing.time * sample_rate).max(1.0)).exp();
+        self.current += (target - self.current) * coefficient;
+        if (target - self.current).abs() < SETTLE {
+            self.current = target;
+        }
+        Some(self.current)
+    }
+
+    /// Last value handed out
+    pub fn value(&self) -> f32 {
+        self.current
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
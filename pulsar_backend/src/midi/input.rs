@@ -0,0 +1,106 @@
+use super::{MidiError, MidiEvent, MidiMessage, MidiQueue, MidiResult};
+use midir::{Ignore, MidiInputConnection};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Client name shown to other MIDI software
+const CLIENT_NAME: &str = "Pulsar";
+
+/// An input port as the platform reports it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MidiPortInfo {
+    pub name: String,
+    /// Stable across sessions where the platform allows it
+    pub id: String,
+}
+
+fn client() -> MidiResult<midir::MidiInput> {
+    let mut input = midir::MidiInput::new(CLIENT_NAME).map_err(|e| MidiError::Init(e.to_string()))?;
+    // sysex, clock and active sensing are never played
+    input.ignore(Ignore::All);
+    Ok(input)
+}
+
+/// Every MIDI input port currently available
+pub fn list_ports() -> MidiResult<Vec<MidiPortInfo>> {
+    let input = client()?;
+    input
+        .ports()
+        .iter()
+        .map(|port| {
+            let name = input.port_name(port).map_err(|e| MidiError::PortInfo(e.to_string()))?;
+            Ok(MidiPortInfo { name, id: port.id() })
+        })
+        .collect()
+}
+
+/// An open MIDI input port feeding a `MidiQueue`.
+///
+/// Messages are parsed on the backend's thread as they arrive; those the
+/// engine doesn't play are counted and dropped. The port stays open until the
+/// `MidiInput` is closed or dropped.
+pub struct MidiInput {
+    port: MidiPortInfo,
+    queue: MidiQueue,
+    received: Arc<AtomicU64>,
+    connection: MidiInputConnection<()>,
+}
+
+impl MidiInput {
+    /// Open the port whose id or name is `port`, or failing that the first
+    /// whose name contains it (case-insensitive)
+    pub fn open(port: &str, queue: MidiQueue) -> MidiResult<Self> {
+        let needle = port.to_lowercase();
+        let ports = list_ports()?;
+        let info = ports
+            .iter()
+            .find(|p| p.id == port || p.name == port)
+            .or_else(|| ports.iter().find(|p| p.name.to_lowercase().contains(&needle)))
+            .ok_or_else(|| MidiError::PortNotFound(port.to_string()))?;
+        Self::connect(info.clone(), queue)
+    }
+
+    /// Open the first port there is
+    pub fn open_default(queue: MidiQueue) -> MidiResult<Self> {
+        let info = list_ports()?.into_iter().next().ok_or_else(|| MidiError::PortNotFound("any".into()))?;
+        Self::connect(info, queue)
+    }
+
+    fn connect(port: MidiPortInfo, queue: MidiQueue) -> MidiResult<Self> {
+        let input = client()?;
+        let handle = input.find_port_by_id(port.id.clone()).ok_or_else(|| MidiError::PortNotFound(port.name.clone()))?;
+        let received = Arc::new(AtomicU64::new(0));
+        let (sink, counter) = (queue.clone(), Arc::clone(&received));
+        let connection = input
+            .connect(
+                &handle,
+                "pulsar-midi-in",
+                move |timestamp_us, bytes, _| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    if let Some(message) = MidiMessage::parse(bytes) {
+                        sink.push(MidiEvent { timestamp_us, message });
+                    }
+                },
+                (),
+            )
+            .map_err(|e| MidiError::Connect(e.to_string()))?;
+        Ok(Self { port, queue, received, connection })
+    }
+
+    pub fn port(&self) -> &MidiPortInfo {
+        &self.port
+    }
+
+    pub fn queue(&self) -> &MidiQueue {
+        &self.queue
+    }
+
+    /// Messages received, including ones that weren't queued
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    pub fn close(self) {
+        self.connection.close();
+    }
+}
@@ -0,0 +1,190 @@
+//! MIDI input: hardware and virtual ports (through midir), message parsing,
+//! and delivery to the audio thread.
+//!
+//! A `MidiInput` parses every message on the backend's thread and pushes it
+//! into a `MidiQueue`, a fixed-size lock-free queue that several ports can
+//! share. On the audio thread a `MidiVoiceAllocator` drains the queue at the
+//! start of each block and plays the notes on a `VoicePool`.
+//!
+//! midir delivers MIDI 1.0 bytes only. Where a platform hands over MIDI 2.0
+//! Universal MIDI Packets instead, `MidiQueue::push_ump` parses them (see
+//! `ump`) into the same messages at their full resolution.
+
+pub mod allocator;
+pub mod controllers;
+pub mod input;
+pub mod ump;
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crossbeam::queue::ArrayQueue;
+
+pub use allocator::MidiVoiceAllocator;
+pub use controllers::{Controller, ControllerDecoder, ControllerSmoothing, ControllerValue};
+pub use input::{MidiInput, MidiPortInfo, list_ports};
+
+/// Events a `MidiQueue` holds by default; far more than a block's worth
+pub const DEFAULT_MIDI_QUEUE_CAPACITY: usize = 1024;
+
+pub type MidiResult<T> = Result<T, MidiError>;
+
+#[derive(Debug, Clone)]
+pub enum MidiError {
+    /// The platform's MIDI service couldn't be reached
+    Init(String),
+    PortNotFound(String),
+    PortInfo(String),
+    Connect(String),
+}
+
+impl fmt::Display for MidiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Init(msg) => write!(f, "MIDI unavailable: {}", msg),
+            Self::PortNotFound(name) => write!(f, "MIDI port not found: {}", name),
+            Self::PortInfo(msg) => write!(f, "MIDI port query failed: {}", msg),
+            Self::Connect(msg) => write!(f, "Failed to open MIDI port: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for MidiError {}
+
+/// A channel message. `channel` is 0-based (0 to 15).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MidiMessage {
+    /// `velocity` is normalized (0.0 to 1.0)
+    NoteOn { channel: u8, note: u8, velocity: f32 },
+    /// `velocity` is the release velocity, normalized
+    NoteOff { channel: u8, note: u8, velocity: f32 },
+    /// Pressure on one held key; `pressure` is normalized
+    PolyAftertouch { channel: u8, note: u8, pressure: f32 },
+    ControlChange { channel: u8, controller: u8, value: u8 },
+    /// -1.0 (full down) to just under 1.0 (full up), 0.0 at rest
+    PitchBend { channel: u8, value: f32 },
+    /// A controller at full resolution, already decoded: MIDI 2.0 sends
+    /// 32-bit controllers and whole RPN/NRPN changes in one message
+    Controller { channel: u8, value: ControllerValue },
+    /// Bend of the notes on one key, scaled as `PitchBend` (MIDI 2.0)
+    PerNotePitchBend { channel: u8, note: u8, value: f32 },
+    /// Absolute pitch of the notes on one key, in semitones on the MIDI note
+    /// scale (MIDI 2.0)
+    PerNotePitch { channel: u8, note: u8, pitch: f32 },
+    /// A registered per-note controller (MIDI 2.0); `value` is normalized
+    PerNoteController { channel: u8, note: u8, controller: u8, value: f32 },
+}
+
+impl MidiMessage {
+    /// Parse one complete message. Anything but note, poly aftertouch,
+    /// controller and pitch-bend messages (including running status) gives `None`. A note-on
+    /// with velocity 0 is a note-off, as the MIDI spec has it.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let &[status, data1, data2, ..] = bytes else { return None };
+        let channel = status & 0x0F;
+        let (data1, data2) = (data1 & 0x7F, data2 & 0x7F);
+        match status & 0xF0 {
+            0x80 => Some(Self::NoteOff { channel, note: data1, velocity: data2 as f32 / 127.0 }),
+            // release velocity 64 is the spec's default
+            0x90 if data2 == 0 => Some(Self::NoteOff { channel, note: data1, velocity: 64.0 / 127.0 }),
+            0x90 => Some(Self::NoteOn { channel, note: data1, velocity: data2 as f32 / 127.0 }),
+            0xA0 => Some(Self::PolyAftertouch { channel, note: data1, pressure: data2 as f32 / 127.0 }),
+            0xB0 => Some(Self::ControlChange { channel, controller: data1, value: data2 }),
+            0xE0 => {
+                let value = ((data2 as i32) << 7 | data1 as i32) - 8192;
+                Some(Self::PitchBend { channel, value: value as f32 / 8192.0 })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn channel(&self) -> u8 {
+        match *self {
+            Self::NoteOn { channel, .. }
+            | Self::NoteOff { channel, .. }
+            | Self::PolyAftertouch { channel, .. }
+            | Self::ControlChange { channel, .. }
+            | Self::PitchBend { channel, .. }
+            | Self::Controller { channel, .. }
+            | Self::PerNotePitchBend { channel, .. }
+            | Self::PerNotePitch { channel, .. }
+            | Self::PerNoteController { channel, .. } => channel,
+        }
+    }
+}
+
+/// A message with the time it arrived
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MidiEvent {
+    /// Microseconds from an arbitrary point fixed per connection
+    pub timestamp_us: u64,
+    pub message: MidiMessage,
+}
+
+/// Lock-free queue carrying MIDI from input threads to the audio thread.
+///
+/// Clones share the queue. When it is full, new events are dropped and
+/// counted rather than blocking the sender; size it so that never happens.
+#[derive(Clone)]
+pub struct MidiQueue {
+    events: Arc<ArrayQueue<MidiEvent>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Default for MidiQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIDI_QUEUE_CAPACITY)
+    }
+}
+
+impl MidiQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self { events: Arc::new(ArrayQueue::new(capacity.max(1))), dropped: Arc::new(AtomicU64::new(0)) }
+    }
+
+    /// Queue `event`; false if the queue was full and it was dropped
+    pub fn push(&self, event: MidiEvent) -> bool {
+        if self.events.push(event).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Queue a message stamped 0, e.g. from a UI keyboard or a test
+    pub fn send(&self, message: MidiMessage) -> bool {
+        self.push(MidiEvent { timestamp_us: 0, message })
+    }
+
+    /// Parse a stream of Universal MIDI Packets and queue the messages, all
+    /// stamped `timestamp_us`; the number queued
+    pub fn push_ump(&self, timestamp_us: u64, words: &[u32]) -> usize {
+        ump::packets(words)
+            .filter_map(ump::parse)
+            .filter(|&message| self.push(MidiEvent { timestamp_us, message }))
+            .count()
+    }
+
+    /// Oldest pending event. Never blocks; safe on the audio thread.
+    pub fn pop(&self) -> Option<MidiEvent> {
+        self.events.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.events.capacity()
+    }
+
+    /// Events dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,118 @@
+use super::{Controller, ControllerValue, MidiMessage};
+
+/// Message type of MIDI 1.0 channel voice messages, 32-bit
+const MT_MIDI1_CHANNEL_VOICE: u8 = 0x2;
+/// Message type of MIDI 2.0 channel voice messages, 64-bit
+const MT_MIDI2_CHANNEL_VOICE: u8 = 0x4;
+
+const STATUS_REGISTERED_PER_NOTE: u8 = 0x0;
+const STATUS_REGISTERED_CONTROLLER: u8 = 0x2;
+const STATUS_ASSIGNABLE_CONTROLLER: u8 = 0x3;
+const STATUS_PER_NOTE_PITCH_BEND: u8 = 0x6;
+const STATUS_NOTE_OFF: u8 = 0x8;
+const STATUS_NOTE_ON: u8 = 0x9;
+const STATUS_POLY_PRESSURE: u8 = 0xA;
+const STATUS_CONTROL_CHANGE: u8 = 0xB;
+const STATUS_PITCH_BEND: u8 = 0xE;
+
+/// Registered per-note controller holding the note's absolute pitch, as 7.25
+/// fixed point (note number, then fraction)
+pub const PER_NOTE_PITCH: u8 = 3;
+/// Centre of 32-bit bend values
+const BEND_CENTRE: f64 = 2_147_483_648.0;
+
+/// 32-bit words in a packet, from its first word
+pub fn packet_len(word: u32) -> usize {
+    match word >> 28 {
+        0x0..=0x2 | 0x6 | 0x7 => 1,
+        0x3 | 0x4 | 0x8..=0xA => 2,
+        0xB | 0xC => 3,
+        _ => 4,
+    }
+}
+
+/// Splits a stream of words into whole packets; a trailing partial packet
+/// is left out
+pub fn packets(words: &[u32]) -> impl Iterator<Item = &[u32]> {
+    let mut rest = words;
+    std::iter::from_fn(move || {
+        let len = packet_len(*rest.first()?);
+        if rest.len() < len {
+            return None;
+        }
+        let (packet, tail) = rest.split_at(len);
+        rest = tail;
+        Some(packet)
+    })
+}
+
+/// Parse one Universal MIDI Packet.
+///
+/// MIDI 1.0 channel voice packets parse as their byte form does. MIDI 2.0
+/// channel voice packets keep their resolution: 16-bit velocities, 32-bit
+/// pressure, bend and controllers, and RPN/NRPN as single messages, decoded
+/// as the `Controller` message. Per-note pitch bend and registered per-note
+/// controllers address the notes on one key; a note-on's pitch attribute is
+/// dropped. Groups are folded together, so only the channel is kept, and
+/// anything else (system, data and stream packets, program change, channel
+/// pressure, relative and assignable per-note controllers) gives `None`.
+/// Unlike MIDI 1.0, a note-on with velocity 0 is still a note-on.
+pub fn parse(packet: &[u32]) -> Option<MidiMessage> {
+    let &first = packet.first()?;
+    let [kind, status, index1, index2] = first.to_be_bytes();
+    match kind >> 4 {
+        MT_MIDI1_CHANNEL_VOICE => MidiMessage::parse(&[status, index1, index2]),
+        MT_MIDI2_CHANNEL_VOICE => {
+            parse_midi2(status >> 4, status & 0x0F, index1 & 0x7F, index2 & 0x7F, *packet.get(1)?)
+        }
+        _ => None,
+    }
+}
+
+fn parse_midi2(status: u8, channel: u8, index1: u8, index2: u8, data: u32) -> Option<MidiMessage> {
+    let normalized = data as f64 / u32::MAX as f64;
+    let velocity = (data >> 16) as f32 / u16::MAX as f32;
+    match status {
+        STATUS_NOTE_OFF => Some(MidiMessage::NoteOff { channel, note: index1, velocity }),
+        STATUS_NOTE_ON => Some(MidiMessage::NoteOn { channel, note: index1, velocity }),
+        STATUS_POLY_PRESSURE => Some(MidiMessage::PolyAftertouch { channel, note: index1, pressure: normalized as f32 }),
+        STATUS_CONTROL_CHANGE => controller(channel, Controller::Cc(index1), data),
+        // bank and index make up the parameter number, as MSB and LSB do in MIDI 1.0
+        STATUS_REGISTERED_CONTROLLER => controller(channel, Controller::Rpn(number(index1, index2)), data),
+        STATUS_ASSIGNABLE_CONTROLLER => controller(channel, Controller::Nrpn(number(index1, index2)), data),
+        STATUS_PITCH_BEND => Some(MidiMessage::PitchBend { channel, value: bend(data) }),
+        STATUS_PER_NOTE_PITCH_BEND => Some(MidiMessage::PerNotePitchBend { channel, note: index1, value: bend(data) }),
+        STATUS_REGISTERED_PER_NOTE if index2 == PER_NOTE_PITCH => {
+            let pitch = data as f64 / (1u64 << 25) as f64;
+            Some(MidiMessage::PerNotePitch { channel, note: index1, pitch: pitch as f32 })
+        }
+        STATUS_REGISTERED_PER_NOTE => {
+            Some(MidiMessage::PerNoteController { channel, note: index1, controller: index2, value: normalized as f32 })
+        }
+        _ => None,
+    }
+}
+
+fn controller(channel: u8, controller: Controller, data: u32) -> Option<MidiMessage> {
+    Some(MidiMessage::Controller { channel, value: ControllerValue::thirty_two_bit(controller, data) })
+}
+
+fn number(bank: u8, index: u8) -> u16 {
+    (bank as u16) << 7 | index as u16
+}
+
+/// -1.0 to just under 1.0 from a 32-bit bend
+fn bend(data: u32) -> f32 {
+    ((data as f64 - BEND_CENTRE) / BEND_CENTRE) as f32
+}
+
+/// First word of a MIDI 2.0 channel voice packet, `status` being the high
+/// nibble of the status byte (0x9 note-on, 0xB control change, ...)
+pub fn channel_voice(group: u8, status: u8, channel: u8, index1: u8, index2: u8) -> u32 {
+    u32::from_be_bytes([
+        MT_MIDI2_CHANNEL_VOICE << 4 | group & 0x0F,
+        (status & 0x0F) << 4 | channel & 0x0F,
+        index1 & 0x7F,
+        index2 & 0x7F,
+    ])
+}
@@ -0,0 +1,218 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crossbeam::atomic::AtomicCell;
+
+use crate::io::wav::{self, WavResult};
+use crate::jobs::{JobHandle, JobPriority, JobProgress, WorkerPool};
+use crate::rt_processing::analysis::loudness;
+use crate::rt_processing::effects::Effect;
+use crate::rt_processing::effects::chain::EffectChain;
+
+use super::RenderedAudio;
+use super::bounce::{self, BouncePreset};
+use super::downmix;
+
+/// Builds a fresh processing chain for a file with the given channel count.
+/// Called once per file, on the worker thread processing it.
+pub type ChainFactory = Arc<dyn Fn(usize) -> EffectChain + Send + Sync>;
+
+/// Input normalization applied before the chain
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Normalize {
+    /// Sample peak in dBFS
+    Peak(f32),
+    /// Integrated loudness in LUFS
+    Loudness(f32),
+}
+
+/// What to do with every file of a batch: normalize, run a chain, then write
+/// the result with a bounce preset (layout, loudness, rate, ceiling, format).
+#[derive(Clone)]
+pub struct BatchPreset {
+    pub normalize: Option<Normalize>,
+    chain: Option<ChainFactory>,
+    /// Output stage. Stem presets are ignored here; files are always written whole.
+    pub output: BouncePreset,
+    /// Silence appended to each input so reverb/delay tails can ring out
+    pub tail_seconds: f32,
+}
+
+impl BatchPreset {
+    pub fn new(output: BouncePreset) -> Self {
+        Self { normalize: None, chain: None, output: BouncePreset { stems: false, ..output }, tail_seconds: 0.0 }
+    }
+
+    pub fn with_normalize(mut self, normalize: Normalize) -> Self {
+        self.normalize = Some(normalize);
+        self
+    }
+
+    /// Chain (EQ, dynamics, ...) run on each normalized file
+    pub fn with_chain(mut self, factory: impl Fn(usize) -> EffectChain + Send + Sync + 'static) -> Self {
+        self.chain = Some(Arc::new(factory));
+        self
+    }
+
+    pub fn with_tail(mut self, seconds: f32) -> Self {
+        self.tail_seconds = seconds.max(0.0);
+        self
+    }
+}
+
+/// Progress report passed to the batch callback
+#[derive(Clone, Debug)]
+pub struct BatchProgress {
+    /// Index of the file in the input list
+    pub index: usize,
+    pub input: PathBuf,
+    /// Progress of this file, 0.0 to 1.0
+    pub fraction: f32,
+    /// Files finished so far (including failed ones)
+    pub files_done: usize,
+    pub total_files: usize,
+}
+
+/// Runs a `BatchPreset` over many files on a `WorkerPool`, one compute job per
+/// file, so long and short files balance out over the pool's compute workers
+/// and its IO workers stay free. Outputs are written to the output directory as
+/// `<input stem>_<preset name>.wav`.
+#[derive(Clone)]
+pub struct BatchProcessor {
+    preset: BatchPreset,
+    block_size: usize,
+}
+
+impl BatchProcessor {
+    pub fn new(preset: BatchPreset) -> Self {
+        Self { preset, block_size: 1024 }
+    }
+
+    /// Block size the chain is run with
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size.max(1);
+        self
+    }
+
+    pub fn preset(&self) -> &BatchPreset {
+        &self.preset
+    }
+
+    /// Process every input on `pool` and wait for it, reporting progress from
+    /// the workers. Returns one result (the written files) per input, in input
+    /// order. Don't call it from a job on the same pool.
+    pub fn run(
+        &self,
+        pool: &WorkerPool,
+        inputs: &[PathBuf],
+        output_dir: impl AsRef<Path>,
+        on_progress: impl Fn(&BatchProgress) + Send + Sync + 'static,
+    ) -> Vec<WavResult<Vec<PathBuf>>> {
+        let handle = self.clone().spawn(pool, inputs.to_vec(), output_dir.as_ref().to_path_buf(), on_progress);
+        match handle.wait() {
+            Ok(results) => results,
+            // nobody else holds the handle, so this is a file job's panic
+            Err(error) => panic!("batch failed: {}", error),
+        }
+    }
+
+    /// Queue the batch on `pool`, one job per file. Job progress is the overall
+    /// fraction over all files; per-file reports still go to `on_progress`. On
+    /// cancel, files not yet finished are not written and the outcome is
+    /// `JobError::Cancelled`.
+    pub fn spawn(
+        self,
+        pool: &WorkerPool,
+        inputs: Vec<PathBuf>,
+        output_dir: PathBuf,
+        on_progress: impl Fn(&BatchProgress) + Send + Sync + 'static,
+    ) -> JobHandle<Vec<WavResult<Vec<PathBuf>>>> {
+        let file_fractions: Vec<AtomicCell<f32>> = inputs.iter().map(|_| AtomicCell::new(0.0)).collect();
+        let done = AtomicUsize::new(0);
+        let total_files = inputs.len();
+
+        let files = inputs.into_iter().enumerate().collect();
+        pool.spawn_each(JobPriority::Compute, "batch", files, move |(index, input): (usize, PathBuf), job| {
+            let report = |fraction: f32, files_done: usize| {
+                file_fractions[index].store(fraction);
+                job.set(file_fractions.iter().map(AtomicCell::load).sum::<f32>() / total_files as f32);
+                on_progress(&BatchProgress { index, input: input.clone(), fraction, files_done, total_files });
+            };
+            let result = self.process_file_inner(&input, &output_dir, job, |fraction| {
+                report(fraction, done.load(Ordering::Relaxed));
+            });
+            if !job.is_cancelled() {
+                report(1.0, done.fetch_add(1, Ordering::Relaxed) + 1);
+            }
+            result
+        })
+    }
+
+    /// Process one file on the calling thread. `progress` receives the fraction
+    /// of the file processed so far.
+    pub fn process_file(
+        &self,
+        input: &Path,
+        output_dir: &Path,
+        progress: impl FnMut(f32),
+    ) -> WavResult<Vec<PathBuf>> {
+        self.process_file_inner(input, output_dir, &JobProgress::detached(), progress)
+    }
+
+    fn process_file_inner(
+        &self,
+        input: &Path,
+        output_dir: &Path,
+        job: &JobProgress,
+        mut progress: impl FnMut(f32),
+    ) -> WavResult<Vec<PathBuf>> {
+        let audio = wav::read_file(input)?;
+        let sample_rate = audio.sample_rate as f32;
+        let mut channels = audio.channels;
+
+        match self.preset.normalize {
+            Some(Normalize::Peak(target)) => {
+                let peak = loudness::sample_peak_db(&channels);
+                if peak > loudness::SILENT_LUFS {
+                    downmix::apply_gain_db(&mut channels, target - peak);
+                }
+            }
+            Some(Normalize::Loudness(target)) => {
+                let measured = loudness::integrated_loudness(&channels, sample_rate);
+                if measured > loudness::SILENT_LUFS {
+                    downmix::apply_gain_db(&mut channels, target - measured);
+                }
+            }
+            None => {}
+        }
+
+        let tail = (self.preset.tail_seconds * sample_rate) as usize;
+        channels.iter_mut().for_each(|c| c.resize(c.len() + tail, 0.0));
+
+        if let Some(factory) = &self.preset.chain {
+            let mut chain = factory(channels.len());
+            let frames = channels.first().map_or(0, Vec::len);
+            let mut start = 0;
+            let mut reported = 0.0;
+            while start < frames {
+                if job.is_cancelled() {
+                    return Ok(Vec::new());
+                }
+                let n = (frames - start).min(self.block_size);
+                let mut views: Vec<&mut [f32]> = channels.iter_mut().map(|c| &mut c[start..start + n]).collect();
+                chain.process(&mut views, n, sample_rate);
+                start += n;
+                let fraction = start as f32 / frames as f32;
+                if fraction < 1.0 && fraction - reported >= 0.01 {
+                    reported = fraction;
+                    progress(fraction);
+                }
+            }
+        }
+
+        let rendered = RenderedAudio { master: channels, stems: Vec::new(), sample_rate };
+        let name = input.file_stem().map_or_else(|| "output".into(), |s| s.to_string_lossy());
+        bounce::write_deliverables(&rendered, &self.preset.output, output_dir, &name)
+    }
+}
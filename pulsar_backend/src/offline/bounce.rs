@@ -0,0 +1,180 @@
+use std::path::{Path, PathBuf};
+
+use crate::io::wav::{self, SampleFormat, WavResult, WavSpec};
+use crate::rt_processing::analysis::loudness;
+
+use super::downmix::{self, ChannelLayout};
+use super::resample::resample;
+use super::{OfflineRenderer, RenderedAudio};
+
+/// One deliverable format: rate, bit depth, layout and level processing
+#[derive(Clone, Debug, PartialEq)]
+pub struct BouncePreset {
+    /// Appended to the file name (`<name>_<preset>.wav`)
+    pub name: String,
+    /// Output rate; `None` keeps the render rate
+    pub sample_rate: Option<u32>,
+    pub format: SampleFormat,
+    pub dither: bool,
+    pub layout: ChannelLayout,
+    /// Normalize to this integrated loudness (LUFS) before the ceiling
+    pub loudness_target: Option<f32>,
+    /// Sample-peak ceiling in dBFS; the file is turned down (never limited) to fit
+    pub ceiling_db: f32,
+    /// One file per bus instead of the master mix
+    pub stems: bool,
+}
+
+impl BouncePreset {
+    /// CD master: stereo, 44.1 kHz, 16-bit with TPDF dither
+    pub fn cd_stereo() -> Self {
+        Self {
+            name: "cd".into(),
+            sample_rate: Some(44_100),
+            format: SampleFormat::Int16,
+            dither: true,
+            layout: ChannelLayout::Stereo,
+            loudness_target: None,
+            ceiling_db: -0.1,
+            stems: false,
+        }
+    }
+
+    /// Per-bus stems at the render rate, 24-bit, no level changes
+    pub fn stems() -> Self {
+        Self {
+            name: "stem".into(),
+            sample_rate: None,
+            format: SampleFormat::Int24,
+            dither: false,
+            layout: ChannelLayout::Stereo,
+            loudness_target: None,
+            ceiling_db: 0.0,
+            stems: true,
+        }
+    }
+
+    /// 5.1 at 48 kHz / 24-bit; stereo mixes are bass-managed into the LFE
+    pub fn surround_5_1() -> Self {
+        Self {
+            name: "5.1".into(),
+            sample_rate: Some(48_000),
+            format: SampleFormat::Int24,
+            dither: false,
+            layout: ChannelLayout::Surround51,
+            loudness_target: None,
+            ceiling_db: -0.1,
+            stems: false,
+        }
+    }
+
+    /// Stereo master normalized to `target_lufs` (e.g. -14 for streaming) with a
+    /// -1 dBFS ceiling, 48 kHz / 24-bit
+    pub fn loudness_master(target_lufs: f32) -> Self {
+        Self {
+            name: format!("{}lufs", target_lufs.round() as i32),
+            sample_rate: Some(48_000),
+            format: SampleFormat::Int24,
+            dither: true,
+            layout: ChannelLayout::Stereo,
+            loudness_target: Some(target_lufs),
+            ceiling_db: -1.0,
+            stems: false,
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+}
+
+/// Processed audio ready to be written, `[channel][frame]`
+#[derive(Clone, Debug)]
+pub struct Deliverable {
+    /// File name suffix: the preset name, plus `_bus<N>` for stems
+    pub name: String,
+    pub channels: Vec<Vec<f32>>,
+    pub spec: WavSpec,
+    /// Integrated loudness of the final audio, in LUFS
+    pub loudness: f32,
+}
+
+/// Apply `preset` to rendered audio: layout conversion, loudness normalization,
+/// resampling and the peak ceiling, in that order.
+pub fn prepare_deliverables(rendered: &RenderedAudio, preset: &BouncePreset) -> Vec<Deliverable> {
+    let sources: Vec<(String, &Vec<Vec<f32>>)> = if preset.stems {
+        rendered.stems.iter().enumerate().map(|(bus, stem)| (format!("{}_bus{bus}", preset.name), stem)).collect()
+    } else {
+        vec![(preset.name.clone(), &rendered.master)]
+    };
+
+    let render_rate = rendered.sample_rate;
+    let output_rate = preset.sample_rate.unwrap_or(render_rate as u32);
+
+    sources
+        .into_iter()
+        .map(|(name, audio)| {
+            let mut channels = downmix::convert(audio, render_rate, preset.layout);
+            if let Some(target) = preset.loudness_target {
+                let measured = loudness::integrated_loudness(&channels, render_rate);
+                if measured > loudness::SILENT_LUFS {
+                    downmix::apply_gain_db(&mut channels, target - measured);
+                }
+            }
+            if output_rate != render_rate as u32 {
+                channels = channels.iter().map(|c| resample(c, render_rate as u32, output_rate)).collect();
+            }
+            downmix::apply_ceiling(&mut channels, preset.ceiling_db);
+
+            Deliverable {
+                name,
+                loudness: loudness::integrated_loudness(&channels, output_rate as f32),
+                channels,
+                spec: WavSpec { sample_rate: output_rate, format: preset.format, dither: preset.dither },
+            }
+        })
+        .collect()
+}
+
+/// Write every deliverable of `preset` into `directory` as `<name>_<suffix>.wav`.
+/// Returns the written paths.
+pub fn write_deliverables(
+    rendered: &RenderedAudio,
+    preset: &BouncePreset,
+    directory: impl AsRef<Path>,
+    name: &str,
+) -> WavResult<Vec<PathBuf>> {
+    prepare_deliverables(rendered, preset)
+        .into_iter()
+        .map(|deliverable| {
+            let path = directory.as_ref().join(format!("{name}_{}.wav", deliverable.name));
+            wav::write_file(&path, &deliverable.channels, deliverable.spec)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+impl OfflineRenderer<'_> {
+    /// Render `seconds` once and write the deliverables of every preset.
+    /// Stems are only rendered when a preset asks for them.
+    pub fn bounce(
+        &mut self,
+        seconds: f32,
+        presets: &[BouncePreset],
+        directory: impl AsRef<Path>,
+        name: &str,
+    ) -> WavResult<Vec<PathBuf>> {
+        let rendered = self.render_seconds(seconds, presets.iter().any(|p| p.stems));
+        let mut paths = Vec::new();
+        for preset in presets {
+            paths.extend(write_deliverables(&rendered, preset, directory.as_ref(), name)?);
+        }
+        Ok(paths)
+    }
+}
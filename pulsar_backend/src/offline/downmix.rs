@@ -0,0 +1,127 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::rt_processing::filters::{CrossoverCoefficients, CrossoverState};
+
+/// Bass-management crossover between the mains and the LFE, in Hz
+pub const LFE_CROSSOVER: f32 = 120.0;
+
+/// Output channel layout. 5.1 uses the WAV/SMPTE order L R C LFE Ls Rs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChannelLayout {
+    Mono,
+    Stereo,
+    Surround51,
+}
+
+impl ChannelLayout {
+    pub fn channels(self) -> usize {
+        match self {
+            ChannelLayout::Mono => 1,
+            ChannelLayout::Stereo => 2,
+            ChannelLayout::Surround51 => 6,
+        }
+    }
+
+    /// Layout with `channels` channels, if there is one
+    pub fn from_channels(channels: usize) -> Option<Self> {
+        match channels {
+            1 => Some(ChannelLayout::Mono),
+            2 => Some(ChannelLayout::Stereo),
+            6 => Some(ChannelLayout::Surround51),
+            _ => None,
+        }
+    }
+}
+
+/// Convert non-interleaved audio to `layout`.
+///
+/// - 5.1 → stereo: ITU-R BS.775 coefficients (center and surrounds at -3 dB).
+///   The LFE is band-limited with an LR4 low-pass at `LFE_CROSSOVER` and added to
+///   both sides, so bass that was managed into the LFE is not lost.
+/// - stereo → 5.1: bass management. An LR4 split at `LFE_CROSSOVER` keeps the
+///   highs in L/R and sends the summed lows to the LFE; center and surrounds stay
+///   silent. Because LR4 halves sum flat, folding back down restores the input
+///   (with the bass in mono).
+/// - anything → mono: via stereo, then (L + R) / 2.
+///
+/// Other channel counts are returned unchanged.
+pub fn convert(audio: &[Vec<f32>], sample_rate: f32, layout: ChannelLayout) -> Vec<Vec<f32>> {
+    let Some(from) = ChannelLayout::from_channels(audio.len()) else {
+        return audio.to_vec();
+    };
+    match (from, layout) {
+        (a, b) if a == b => audio.to_vec(),
+        (ChannelLayout::Mono, ChannelLayout::Stereo) => vec![audio[0].clone(), audio[0].clone()],
+        (ChannelLayout::Mono, ChannelLayout::Surround51) => {
+            let stereo = [audio[0].clone(), audio[0].clone()];
+            stereo_to_surround(&stereo, sample_rate)
+        }
+        (ChannelLayout::Stereo, ChannelLayout::Mono) => vec![fold_mono(&audio[0], &audio[1])],
+        (ChannelLayout::Stereo, ChannelLayout::Surround51) => stereo_to_surround(audio, sample_rate),
+        (ChannelLayout::Surround51, ChannelLayout::Stereo) => surround_to_stereo(audio, sample_rate),
+        (ChannelLayout::Surround51, ChannelLayout::Mono) => {
+            let stereo = surround_to_stereo(audio, sample_rate);
+            vec![fold_mono(&stereo[0], &stereo[1])]
+        }
+        _ => audio.to_vec(),
+    }
+}
+
+fn fold_mono(left: &[f32], right: &[f32]) -> Vec<f32> {
+    left.iter().zip(right).map(|(l, r)| (l + r) * 0.5).collect()
+}
+
+fn surround_to_stereo(audio: &[Vec<f32>], sample_rate: f32) -> Vec<Vec<f32>> {
+    let [l, r, c, lfe, ls, rs] = [0, 1, 2, 3, 4, 5].map(|ch| &audio[ch]);
+    let crossover = CrossoverCoefficients::new(LFE_CROSSOVER, sample_rate);
+    let mut lfe_filter = CrossoverState::default();
+
+    let frames = audio.iter().map(Vec::len).min().unwrap_or(0);
+    let mut left = Vec::with_capacity(frames);
+    let mut right = Vec::with_capacity(frames);
+    for i in 0..frames {
+        let (bass, _) = lfe_filter.split(&crossover, lfe[i]);
+        let center = c[i] * FRAC_1_SQRT_2;
+        left.push(l[i] + center + ls[i] * FRAC_1_SQRT_2 + bass);
+        right.push(r[i] + center + rs[i] * FRAC_1_SQRT_2 + bass);
+    }
+    vec![left, right]
+}
+
+fn stereo_to_surround(audio: &[Vec<f32>], sample_rate: f32) -> Vec<Vec<f32>> {
+    let crossover = CrossoverCoefficients::new(LFE_CROSSOVER, sample_rate);
+    let mut splits = [CrossoverState::default(); 2];
+
+    let frames = audio[0].len().min(audio[1].len());
+    let mut out: Vec<Vec<f32>> = (0..6).map(|_| Vec::with_capacity(frames)).collect();
+    for (&l, &r) in audio[0].iter().zip(&audio[1]) {
+        let (l_low, l_high) = splits[0].split(&crossover, l);
+        let (r_low, r_high) = splits[1].split(&crossover, r);
+        out[0].push(l_high);
+        out[1].push(r_high);
+        out[3].push((l_low + r_low) * 0.5);
+    }
+    for silent in [2, 4, 5] {
+        out[silent].resize(frames, 0.0);
+    }
+    out
+}
+
+/// Scale `audio` down so its sample peak does not exceed `ceiling_db` dBFS.
+/// Never adds gain. Returns the applied gain in dB.
+pub fn apply_ceiling(audio: &mut [Vec<f32>], ceiling_db: f32) -> f32 {
+    let peak = audio.iter().flatten().fold(0.0f32, |m, s| m.max(s.abs()));
+    let ceiling = 10f32.powf(ceiling_db / 20.0);
+    if peak <= ceiling {
+        return 0.0;
+    }
+    let gain = ceiling / peak;
+    audio.iter_mut().flatten().for_each(|s| *s *= gain);
+    20.0 * gain.log10()
+}
+
+/// Multiply every sample by a gain in dB
+pub fn apply_gain_db(audio: &mut [Vec<f32>], gain_db: f32) {
+    let gain = 10f32.powf(gain_db / 20.0);
+    audio.iter_mut().flatten().for_each(|s| *s *= gain);
+}
@@ -0,0 +1,84 @@
+//! Block-size invariance checks: render the same graph with several block
+//! sizes and compare the results sample by sample.
+//!
+//! Meant for tests and for vetting new nodes: a node that keeps all of its
+//! block-rate state on the router's control grid renders identically at any
+//! device block size.
+
+use crate::rt_processing::routing::Router;
+
+use super::OfflineRenderer;
+
+/// Outcome of `check_block_sizes`
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvarianceReport {
+    /// Block size of the reference render (the first one given)
+    pub reference_block_size: usize,
+    /// Largest absolute difference from the reference over all renders
+    pub max_difference: f32,
+    /// Block size that produced `max_difference`
+    pub worst_block_size: Option<usize>,
+    /// First frame where the worst render leaves the tolerance
+    pub first_divergent_frame: Option<usize>,
+}
+
+impl InvarianceReport {
+    /// Every render matched the reference within the tolerance it was checked with
+    pub fn is_invariant(&self) -> bool {
+        self.first_divergent_frame.is_none()
+    }
+}
+
+/// Render `frames` of a fresh router from `build` once per block size and
+/// compare each render to the first. Samples further apart than `tolerance`
+/// count as divergent; pass 0.0 for a bit-exact check.
+///
+/// `build` must return identically configured routers (same nodes, seeds and
+/// parameters) on every call.
+pub fn check_block_sizes(
+    build: impl Fn() -> Router,
+    frames: usize,
+    block_sizes: &[usize],
+    tolerance: f32,
+) -> InvarianceReport {
+    let Some((&reference_block_size, others)) = block_sizes.split_first() else {
+        return InvarianceReport {
+            reference_block_size: 0,
+            max_difference: 0.0,
+            worst_block_size: None,
+            first_divergent_frame: None,
+        };
+    };
+    let render = |block_size: usize| {
+        let mut router = build();
+        OfflineRenderer::new(&mut router).with_block_size(block_size).render(frames, false).master
+    };
+
+    let reference = render(reference_block_size);
+    let mut report = InvarianceReport {
+        reference_block_size,
+        max_difference: 0.0,
+        worst_block_size: None,
+        first_divergent_frame: None,
+    };
+    for &block_size in others {
+        let rendered = render(block_size);
+        let mut max_difference = 0.0f32;
+        let mut first_divergent_frame = None;
+        for (expected, actual) in reference.iter().zip(&rendered) {
+            for (frame, (a, b)) in expected.iter().zip(actual).enumerate() {
+                let difference = (a - b).abs();
+                max_difference = max_difference.max(difference);
+                if difference > tolerance {
+                    first_divergent_frame = Some(first_divergent_frame.map_or(frame, |f: usize| f.min(frame)));
+                }
+            }
+        }
+        if max_difference > report.max_difference || report.worst_block_size.is_none() {
+            report.max_difference = max_difference;
+            report.worst_block_size = Some(block_size);
+            report.first_divergent_frame = first_divergent_frame;
+        }
+    }
+    report
+}
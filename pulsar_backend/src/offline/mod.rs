@@ -0,0 +1,178 @@
+pub mod batch;
+pub mod bounce;
+pub mod downmix;
+pub mod invariance;
+pub mod resample;
+
+use std::path::Path;
+use std::time::Duration;
+
+use crate::io::wav::{WavResult, WavSpec, WavWriter};
+use crate::jobs::{JobHandle, JobPriority, JobProgress, WorkerPool};
+use crate::rt_processing::callback::AudioCallback;
+use crate::rt_processing::routing::{CONTROL_BLOCK_FRAMES, Router};
+
+/// Block size `render_offline` calls the processor with. The router renders on
+/// this grid anyway, so larger blocks wouldn't change the output, and it fits
+/// any processor's `max_frames`.
+pub const OFFLINE_BLOCK_FRAMES: usize = CONTROL_BLOCK_FRAMES;
+/// Frames `render_offline_to_file` renders between writes
+const FILE_CHUNK_FRAMES: usize = 64 * OFFLINE_BLOCK_FRAMES;
+
+/// Result of an offline render, non-interleaved `[channel][frame]`
+#[derive(Clone, Debug, Default)]
+pub struct RenderedAudio {
+    pub master: Vec<Vec<f32>>,
+    /// `[bus][channel][frame]`; empty unless stems were requested
+    pub stems: Vec<Vec<Vec<f32>>>,
+    pub sample_rate: f32,
+}
+
+impl RenderedAudio {
+    pub fn frames(&self) -> usize {
+        self.master.first().map_or(0, Vec::len)
+    }
+}
+
+/// Drives a `Router` block by block, as fast as the CPU allows, without an
+/// audio device. Runs on the calling thread and allocates freely.
+pub struct OfflineRenderer<'a> {
+    router: &'a mut Router,
+    block_size: usize,
+}
+
+impl<'a> OfflineRenderer<'a> {
+    /// Renders in blocks of the router's `max_frames`
+    pub fn new(router: &'a mut Router) -> Self {
+        let block_size = router.max_frames().max(1);
+        Self { router, block_size }
+    }
+
+    /// Smaller blocks; clamped to the router's `max_frames`
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size.clamp(1, self.router.max_frames().max(1));
+        self
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.router.sample_rate()
+    }
+
+    /// Render `seconds` of the router's output
+    pub fn render_seconds(&mut self, seconds: f32, with_stems: bool) -> RenderedAudio {
+        let frames = (seconds.max(0.0) * self.router.sample_rate()) as usize;
+        self.render(frames, with_stems)
+    }
+
+    /// Render `frames` of the master mix, plus one stem per bus if `with_stems`
+    pub fn render(&mut self, frames: usize, with_stems: bool) -> RenderedAudio {
+        self.render_with_progress(frames, with_stems, &JobProgress::detached())
+    }
+
+    /// `render`, reporting progress per block. Stops early when cancelled.
+    pub fn render_with_progress(&mut self, frames: usize, with_stems: bool, progress: &JobProgress) -> RenderedAudio {
+        let channels = self.router.channels();
+        let buses = if with_stems { self.router.num_buses() } else { 0 };
+
+        let mut rendered = RenderedAudio {
+            master: (0..channels).map(|_| Vec::with_capacity(frames)).collect(),
+            stems: (0..buses).map(|_| (0..channels).map(|_| Vec::with_capacity(frames)).collect()).collect(),
+            sample_rate: self.router.sample_rate(),
+        };
+
+        let mut block = vec![0.0; self.block_size * channels];
+        let mut stem_blocks = vec![vec![0.0; self.block_size * channels]; buses];
+
+        let mut done = 0;
+        while done < frames && !progress.is_cancelled() {
+            let n = (frames - done).min(self.block_size);
+            let output = &mut block[..n * channels];
+            if with_stems {
+                let mut stems: Vec<&mut [f32]> = stem_blocks.iter_mut().map(|s| &mut s[..n * channels]).collect();
+                self.router.process_with_stems(output, &mut stems, None);
+            } else {
+                self.router.process(output, None);
+            }
+
+            deinterleave(output, &mut rendered.master);
+            for (stem, interleaved) in rendered.stems.iter_mut().zip(&stem_blocks) {
+                deinterleave(&interleaved[..n * channels], stem);
+            }
+            done += n;
+            progress.set(done as f32 / frames as f32);
+        }
+        rendered
+    }
+}
+
+/// Render `frames` of `router` on a background job. The router is handed back
+/// with the audio so it can be reused.
+pub fn render_job(
+    pool: &WorkerPool,
+    mut router: Router,
+    frames: usize,
+    with_stems: bool,
+) -> JobHandle<(Router, RenderedAudio)> {
+    pool.spawn(JobPriority::Compute, "offline render", move |progress| {
+        let rendered = OfflineRenderer::new(&mut router).render_with_progress(frames, with_stems, progress);
+        (router, rendered)
+    })
+}
+
+/// Render `duration` of any processor (an engine's audio handle, a
+/// `VoiceProcessor`, a patch under test) without an audio device, as fast as
+/// the CPU allows. Returns interleaved audio. Runs on the calling thread and
+/// allocates; the processor sees the calls a device would make.
+pub fn render_offline(
+    processor: &mut dyn AudioCallback,
+    duration: Duration,
+    sample_rate: f32,
+    channels: usize,
+) -> Vec<f32> {
+    let mut output = vec![0.0; frames_of(duration, sample_rate) * channels];
+    render_blocks(processor, &mut output, sample_rate, channels);
+    output
+}
+
+/// `render_offline` straight into a WAV file at `path`, rendered at
+/// `spec.sample_rate`. Written in chunks, so long renders don't sit in memory.
+pub fn render_offline_to_file(
+    processor: &mut dyn AudioCallback,
+    duration: Duration,
+    channels: usize,
+    path: impl AsRef<Path>,
+    spec: WavSpec,
+) -> WavResult<()> {
+    let sample_rate = spec.sample_rate as f32;
+    let mut writer = WavWriter::create(path, channels, spec)?;
+    let mut chunk = vec![0.0; FILE_CHUNK_FRAMES * channels];
+    let mut remaining = frames_of(duration, sample_rate);
+    while remaining > 0 {
+        let frames = remaining.min(FILE_CHUNK_FRAMES);
+        let output = &mut chunk[..frames * channels];
+        render_blocks(processor, output, sample_rate, channels);
+        writer.write_interleaved(output)?;
+        remaining -= frames;
+    }
+    writer.finish()
+}
+
+fn frames_of(duration: Duration, sample_rate: f32) -> usize {
+    (duration.as_secs_f64() * sample_rate as f64).round() as usize
+}
+
+/// Fill interleaved `output` in `OFFLINE_BLOCK_FRAMES` blocks
+fn render_blocks(processor: &mut dyn AudioCallback, output: &mut [f32], sample_rate: f32, channels: usize) {
+    for block in output.chunks_mut(OFFLINE_BLOCK_FRAMES * channels.max(1)) {
+        let frames = block.len() / channels.max(1);
+        processor.process(block, sample_rate, channels, frames);
+    }
+}
+
+/// Append an interleaved block to non-interleaved channels
+fn deinterleave(interleaved: &[f32], channels: &mut [Vec<f32>]) {
+    let count = channels.len();
+    for (ch, samples) in channels.iter_mut().enumerate() {
+        samples.extend(interleaved.iter().skip(ch).step_by(count));
+    }
+}
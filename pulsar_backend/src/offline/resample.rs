@@ -0,0 +1,49 @@
+use std::f64::consts::PI;
+
+/// Zero crossings of the sinc kernel on each side (at the lower of the two rates)
+const HALF_TAPS: usize = 32;
+
+/// Band-limited sample rate conversion with a Blackman-windowed sinc.
+///
+/// Offline quality, not speed: every output sample evaluates the kernel directly.
+/// When downsampling, the kernel is stretched so the cutoff sits at the new
+/// Nyquist frequency.
+pub fn resample(input: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || from_rate == 0 || to_rate == 0 || input.is_empty() {
+        return input.to_vec();
+    }
+    let ratio = to_rate as f64 / from_rate as f64;
+    // cutoff relative to the input Nyquist; slightly below to leave room for the window
+    let cutoff = ratio.min(1.0) * 0.97;
+    let half_width = HALF_TAPS as f64 / cutoff;
+    let out_len = (input.len() as f64 * ratio).round() as usize;
+
+    (0..out_len)
+        .map(|n| {
+            let center = n as f64 / ratio;
+            let first = (center - half_width).ceil().max(0.0) as usize;
+            let last = ((center + half_width).floor() as usize).min(input.len() - 1);
+            let mut sum = 0.0;
+            for (k, &x) in input.iter().enumerate().take(last + 1).skip(first) {
+                let t = center - k as f64;
+                sum += x as f64 * cutoff * sinc(cutoff * t) * blackman(t / half_width);
+            }
+            sum as f32
+        })
+        .collect()
+}
+
+#[inline]
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 { 1.0 } else { (PI * x).sin() / (PI * x) }
+}
+
+/// Blackman window over -1..1
+#[inline]
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        return 0.0;
+    }
+    let phase = PI * (x + 1.0);
+    0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos()
+}
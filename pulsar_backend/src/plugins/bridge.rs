@@ -0,0 +1,655 @@
+//! Out-of-process plugin hosting.
+//!
+//! A `BridgedPlugin` runs the real plugin in a `pulsar-plugin-host` child
+//! process and exchanges audio and events with it through `SharedRing`s, so a
+//! plugin that crashes takes down only its host. A supervisor thread restarts
+//! the host with fresh rings, restores the plugin state last saved or loaded
+//! through the `BridgeHandle`, and replays the last value of every parameter.
+//! While the host is down the node is bypassed: effects pass their input
+//! through, instruments are silent.
+//!
+//! The host runs one block behind the engine (it primes its output ring with
+//! `max_frames` of silence), so a bridged plugin adds `max_frames` of latency.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use pulsar_client::shm::{self, SharedRing};
+use serde::{Deserialize, Serialize};
+
+use crate::rt_processing::notes::NoteEvent;
+
+use super::{
+    EVENT_CAPACITY, MAX_PLUGIN_CHANNELS, ParamInfo, PluginError, PluginEvent, PluginFormat, PluginInfo,
+    PluginProcessor, PluginResult,
+};
+
+/// Name of the sandbox executable
+pub const HOST_BINARY: &str = "pulsar-plugin-host";
+/// Restarts attempted after crashes before a plugin stays bypassed
+pub const DEFAULT_MAX_RESTARTS: u32 = 5;
+
+/// Marks the host's messages on its stdout, which plugins may also write to
+const MESSAGE_PREFIX: &str = "pulsar-bridge:";
+/// How long the host may take to load the plugin or answer a request
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the supervisor checks on the host
+const WATCH_INTERVAL: Duration = Duration::from_millis(50);
+/// How often an idle host checks for input
+const HOST_POLL: Duration = Duration::from_micros(100);
+/// Blocks each audio ring holds
+const RING_BLOCKS: usize = 4;
+
+// events travel as [kind, id or note (as bits), value] frames
+const EVENT_PARAM: f32 = 0.0;
+const EVENT_NOTE_ON: f32 = 1.0;
+const EVENT_NOTE_OFF: f32 = 2.0;
+const EVENT_CHANNELS: usize = 3;
+
+/// Host to engine, once the plugin is loaded
+#[derive(Serialize, Deserialize)]
+struct Description {
+    info: PluginInfo,
+    params: Vec<ParamInfo>,
+    inputs: usize,
+    outputs: usize,
+}
+
+/// Engine to host: where to exchange audio, and the state and parameter
+/// values to start from
+#[derive(Serialize, Deserialize)]
+struct Setup {
+    input: PathBuf,
+    output: PathBuf,
+    events: PathBuf,
+    state: Option<Vec<u8>>,
+    params: Vec<(u32, f64)>,
+}
+
+/// Engine to host, one per line after the setup
+#[derive(Serialize, Deserialize)]
+enum Request {
+    SaveState,
+    LoadState(Vec<u8>),
+}
+
+/// Host to engine, answering a `Request`
+#[derive(Serialize, Deserialize)]
+enum Reply {
+    State(Option<Vec<u8>>),
+    Loaded(Result<(), String>),
+}
+
+/// What to run in the sandbox
+#[derive(Clone, Debug)]
+pub struct BridgeConfig {
+    pub format: PluginFormat,
+    /// The `.clap` file or LV2 bundle directory
+    pub path: PathBuf,
+    pub id: String,
+    /// The `pulsar-plugin-host` executable
+    pub host: PathBuf,
+    pub max_restarts: u32,
+}
+
+impl BridgeConfig {
+    /// Uses the host named by `PULSAR_PLUGIN_HOST`, else the one next to the
+    /// running executable
+    pub fn new(format: PluginFormat, path: impl Into<PathBuf>, id: impl Into<String>) -> Self {
+        Self { format, path: path.into(), id: id.into(), host: default_host(), max_restarts: DEFAULT_MAX_RESTARTS }
+    }
+
+    pub fn with_host(mut self, host: impl Into<PathBuf>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    pub fn with_max_restarts(mut self, max_restarts: u32) -> Self {
+        self.max_restarts = max_restarts;
+        self
+    }
+}
+
+fn default_host() -> PathBuf {
+    if let Some(host) = std::env::var_os("PULSAR_PLUGIN_HOST") {
+        return host.into();
+    }
+    let name = format!("{}{}", HOST_BINARY, std::env::consts::EXE_SUFFIX);
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(&name)))
+        .unwrap_or_else(|| name.into())
+}
+
+/// The rings of one host generation; dropping them removes the files
+struct Rings {
+    input: SharedRing,
+    output: SharedRing,
+    events: SharedRing,
+}
+
+impl Rings {
+    fn create(description: &Description, sample_rate: f32, max_frames: usize) -> io::Result<Self> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let base = format!("pulsar-bridge-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+        let path = |suffix: &str| shm::default_dir().join(format!("{}-{}", base, suffix));
+        let capacity = max_frames * RING_BLOCKS;
+        let rate = sample_rate as u32;
+        Ok(Self {
+            // instruments still get a (silent) input ring: it paces the host
+            input: SharedRing::create(path("in"), description.inputs.max(1), capacity, rate)?,
+            output: SharedRing::create(path("out"), description.outputs.max(1), capacity, rate)?,
+            events: SharedRing::create(path("events"), EVENT_CHANNELS, EVENT_CAPACITY * 2, rate)?,
+        })
+    }
+}
+
+/// Engine side of a host's pipes
+struct Link {
+    // the host exits when this closes
+    stdin: ChildStdin,
+    /// Messages from the host's stdout
+    messages: mpsc::Receiver<String>,
+}
+
+/// State shared by the audio side, the supervisor and the handles
+struct Shared {
+    /// `None` while the host is down
+    rings: spin::Mutex<Option<Rings>>,
+    /// `None` while the host is down
+    link: Mutex<Option<Link>>,
+    /// Plugin state last saved or loaded, restored after a restart
+    state: Mutex<Option<Vec<u8>>>,
+    /// Last value of each parameter as f64 bits, in `BridgedPlugin::params` order
+    values: Vec<AtomicU64>,
+    ids: Vec<u32>,
+    restarts: AtomicU32,
+    shutdown: AtomicBool,
+}
+
+impl Shared {
+    fn param_values(&self) -> Vec<(u32, f64)> {
+        self.ids.iter().zip(&self.values).map(|(&id, v)| (id, f64::from_bits(v.load(Ordering::Relaxed)))).collect()
+    }
+
+    fn link(&self) -> MutexGuard<'_, Option<Link>> {
+        self.link.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn state(&self) -> MutexGuard<'_, Option<Vec<u8>>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Send `request` to the host and wait for the reply; `None` while the
+    /// host is down
+    fn request(&self, request: &Request) -> PluginResult<Option<Reply>> {
+        let mut link = self.link();
+        let Some(link) = link.as_mut() else { return Ok(None) };
+        // replies to requests that timed out
+        while link.messages.try_recv().is_ok() {}
+
+        let state_err = |msg: String| PluginError::State(format!("plugin host: {}", msg));
+        let line = ron::to_string(request).map_err(|e| state_err(e.to_string()))?;
+        writeln!(link.stdin, "{}", line).and_then(|_| link.stdin.flush()).map_err(|e| state_err(e.to_string()))?;
+        let reply = link.messages.recv_timeout(REPLY_TIMEOUT).map_err(|_| state_err("no reply".into()))?;
+        ron::from_str(&reply).map(Some).map_err(|e| state_err(e.to_string()))
+    }
+}
+
+/// Control-thread view of a bridged plugin's sandbox
+#[derive(Clone)]
+pub struct BridgeHandle {
+    shared: Arc<Shared>,
+}
+
+impl BridgeHandle {
+    /// Whether the host process is up (the node is bypassed otherwise)
+    pub fn is_running(&self) -> bool {
+        self.shared.rings.lock().is_some()
+    }
+
+    /// Restarts after crashes so far
+    pub fn restarts(&self) -> u32 {
+        self.shared.restarts.load(Ordering::Relaxed)
+    }
+
+    /// Ask the plugin for its state, and keep it to restore after a crash.
+    /// While the host is down, the state last kept.
+    pub fn save_state(&self) -> PluginResult<Option<Vec<u8>>> {
+        let state = match self.shared.request(&Request::SaveState)? {
+            Some(Reply::State(state)) => state,
+            Some(Reply::Loaded(_)) => return Err(PluginError::State("plugin host: unexpected reply".into())),
+            None => return Ok(self.shared.state().clone()),
+        };
+        *self.shared.state() = state.clone();
+        Ok(state)
+    }
+
+    /// Load `state` into the plugin, and keep it to restore after a crash.
+    /// While the host is down, it is only kept, for the next start.
+    pub fn load_state(&self, state: Vec<u8>) -> PluginResult<()> {
+        match self.shared.request(&Request::LoadState(state.clone()))? {
+            Some(Reply::Loaded(Err(msg))) => return Err(PluginError::State(msg)),
+            Some(Reply::State(_)) => return Err(PluginError::State("plugin host: unexpected reply".into())),
+            Some(Reply::Loaded(Ok(()))) | None => {}
+        }
+        *self.shared.state() = Some(state);
+        Ok(())
+    }
+}
+
+/// A plugin running in a sandbox process
+pub struct BridgedPlugin {
+    shared: Arc<Shared>,
+    info: PluginInfo,
+    params: Vec<ParamInfo>,
+    inputs: usize,
+    outputs: usize,
+    max_frames: usize,
+    /// Interleaved audio for the rings
+    scratch: Vec<f32>,
+    events: Vec<f32>,
+}
+
+impl BridgedPlugin {
+    /// Start the host and load the plugin in it. Fails like an in-process
+    /// load would if the host cannot load the plugin.
+    pub fn load(config: BridgeConfig, sample_rate: f32, max_frames: usize) -> PluginResult<Self> {
+        let (child, link, description) = spawn_host(&config, sample_rate, max_frames)?;
+        let shared = Arc::new(Shared {
+            rings: spin::Mutex::new(None),
+            link: Mutex::new(None),
+            state: Mutex::new(None),
+            values: description.params.iter().map(|p| AtomicU64::new(p.default.to_bits())).collect(),
+            ids: description.params.iter().map(|p| p.id).collect(),
+            restarts: AtomicU32::new(0),
+            shutdown: AtomicBool::new(false),
+        });
+        let mut supervisor = Supervisor { shared: Arc::clone(&shared), config, sample_rate, max_frames, child };
+        supervisor.connect(link, &description)?;
+
+        let channels = description.inputs.max(description.outputs).max(1);
+        let plugin = Self {
+            shared,
+            info: description.info,
+            params: description.params,
+            inputs: description.inputs,
+            outputs: description.outputs,
+            max_frames,
+            scratch: vec![0.0; channels * max_frames],
+            events: vec![0.0; EVENT_CAPACITY * EVENT_CHANNELS],
+        };
+        thread::Builder::new()
+            .name(format!("plugin-bridge {}", plugin.info.id))
+            .spawn(move || supervisor.run())
+            .map_err(|e| PluginError::Init(e.to_string()))?;
+        Ok(plugin)
+    }
+
+    pub fn handle(&self) -> BridgeHandle {
+        BridgeHandle { shared: Arc::clone(&self.shared) }
+    }
+
+    fn bypass(&self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], frames: usize) {
+        for (ch, output) in outputs.iter_mut().enumerate() {
+            match inputs.len() {
+                0 => output[..frames].fill(0.0),
+                len => output[..frames].copy_from_slice(&inputs[ch % len][..frames]),
+            }
+        }
+    }
+}
+
+impl PluginProcessor for BridgedPlugin {
+    fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    fn params(&self) -> &[ParamInfo] {
+        &self.params
+    }
+
+    fn input_channels(&self) -> usize {
+        self.inputs
+    }
+
+    fn output_channels(&self) -> usize {
+        self.outputs
+    }
+
+    fn save_state(&mut self) -> Option<Vec<u8>> {
+        self.handle().save_state().ok().flatten()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> PluginResult<()> {
+        self.handle().load_state(state.to_vec())
+    }
+
+    fn process(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], frames: usize, events: &[PluginEvent]) {
+        let frames = frames.min(self.max_frames);
+        for event in events {
+            if let PluginEvent::Param { id, value } = *event
+                && let Some(index) = self.shared.ids.iter().position(|&p| p == id)
+            {
+                self.shared.values[index].store(value.to_bits(), Ordering::Relaxed);
+            }
+        }
+
+        let Some(guard) = self.shared.rings.try_lock() else {
+            return self.bypass(inputs, outputs, frames);
+        };
+        let Some(rings) = guard.as_ref() else {
+            drop(guard);
+            return self.bypass(inputs, outputs, frames);
+        };
+
+        // events first: the host reads them once the block's input is visible
+        let mut written = 0;
+        for (event, frame) in events.iter().zip(self.events.chunks_exact_mut(EVENT_CHANNELS)) {
+            frame.copy_from_slice(&encode_event(event));
+            written += EVENT_CHANNELS;
+        }
+        rings.events.write(&self.events[..written]);
+
+        let channels = rings.input.channels();
+        let block = &mut self.scratch[..frames * channels];
+        for (i, frame) in block.chunks_exact_mut(channels).enumerate() {
+            for (ch, sample) in frame.iter_mut().enumerate() {
+                *sample = inputs.get(ch).map_or(0.0, |input| input[i]);
+            }
+        }
+        rings.input.write(block);
+
+        let channels = rings.output.channels();
+        let block = &mut self.scratch[..frames * channels];
+        let read = rings.output.read(block);
+        block[read * channels..].fill(0.0);
+        for (ch, output) in outputs.iter_mut().enumerate() {
+            let source = ch % channels;
+            for (i, sample) in output[..frames].iter_mut().enumerate() {
+                *sample = block[i * channels + source];
+            }
+        }
+    }
+}
+
+impl Drop for BridgedPlugin {
+    fn drop(&mut self) {
+        // the supervisor stops the host and removes the rings
+        self.shared.shutdown.store(true, Ordering::Release);
+    }
+}
+
+/// Owns the host process and brings it back after a crash
+struct Supervisor {
+    shared: Arc<Shared>,
+    config: BridgeConfig,
+    sample_rate: f32,
+    max_frames: usize,
+    child: Child,
+}
+
+impl Supervisor {
+    /// Give the freshly spawned host its rings, state and parameter values
+    fn connect(&mut self, link: Link, description: &Description) -> PluginResult<()> {
+        let result = self.try_connect(link, description);
+        if result.is_err() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+        result
+    }
+
+    fn try_connect(&mut self, mut link: Link, description: &Description) -> PluginResult<()> {
+        if description.inputs > MAX_PLUGIN_CHANNELS || description.outputs > MAX_PLUGIN_CHANNELS {
+            return Err(PluginError::Unsupported(format!("{}: too many channels", description.info.id)));
+        }
+        let init_err = |e: io::Error| PluginError::Init(format!("plugin host setup: {}", e));
+        let rings = Rings::create(description, self.sample_rate, self.max_frames).map_err(init_err)?;
+        let setup = Setup {
+            input: rings.input.path().to_path_buf(),
+            output: rings.output.path().to_path_buf(),
+            events: rings.events.path().to_path_buf(),
+            state: self.shared.state().clone(),
+            params: self.shared.param_values(),
+        };
+        let line = ron::to_string(&setup).map_err(|e| PluginError::Init(e.to_string()))?;
+        writeln!(link.stdin, "{}", line).and_then(|_| link.stdin.flush()).map_err(init_err)?;
+        *self.shared.link() = Some(link);
+        *self.shared.rings.lock() = Some(rings);
+        Ok(())
+    }
+
+    fn run(mut self) {
+        loop {
+            if self.shared.shutdown.load(Ordering::Acquire) {
+                return self.stop();
+            }
+            if let Ok(None) = self.child.try_wait() {
+                thread::sleep(WATCH_INTERVAL);
+                continue;
+            }
+
+            // the host died: bypass until a new one is up
+            self.shared.rings.lock().take();
+            self.shared.link().take();
+            let _ = self.child.wait();
+            if !self.restart() {
+                return;
+            }
+        }
+    }
+
+    /// Returns `false` once restarts are exhausted or shutdown was requested
+    fn restart(&mut self) -> bool {
+        loop {
+            let attempt = self.shared.restarts.load(Ordering::Relaxed);
+            if attempt >= self.config.max_restarts {
+                return false;
+            }
+            self.shared.restarts.store(attempt + 1, Ordering::Relaxed);
+            // back off: 100 ms, 200 ms, ... capped at 3.2 s
+            thread::sleep(Duration::from_millis(100 << attempt.min(5)));
+            if self.shared.shutdown.load(Ordering::Acquire) {
+                return false;
+            }
+
+            if let Ok((child, link, description)) = spawn_host(&self.config, self.sample_rate, self.max_frames) {
+                self.child = child;
+                if self.connect(link, &description).is_ok() {
+                    return true;
+                }
+            }
+        }
+    }
+
+    fn stop(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        self.shared.rings.lock().take();
+        self.shared.link().take();
+    }
+}
+
+/// Start a host and wait for it to describe the loaded plugin
+fn spawn_host(config: &BridgeConfig, sample_rate: f32, max_frames: usize) -> PluginResult<(Child, Link, Description)> {
+    let format = match config.format {
+        PluginFormat::Clap => "clap",
+        PluginFormat::Lv2 => "lv2",
+    };
+    let mut child = Command::new(&config.host)
+        .arg(format)
+        .arg(&config.path)
+        .arg(&config.id)
+        .arg(sample_rate.to_string())
+        .arg(max_frames.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| PluginError::Load(format!("{}: {}", config.host.display(), e)))?;
+    let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        let _ = child.kill();
+        return Err(PluginError::Init("plugin host has no pipes".into()));
+    };
+
+    let (tx, messages) = mpsc::channel();
+    thread::spawn(move || {
+        // keep draining so plugin output never blocks the host
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if let Some(message) = line.strip_prefix(MESSAGE_PREFIX) {
+                let _ = tx.send(message.to_string());
+            }
+        }
+    });
+
+    let description = match messages.recv_timeout(REPLY_TIMEOUT) {
+        Ok(line) => ron::from_str::<Result<Description, String>>(&line).unwrap_or_else(|e| Err(e.to_string())),
+        Err(mpsc::RecvTimeoutError::Timeout) => Err("plugin host did not respond".into()),
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err("plugin host exited".into()),
+    };
+    match description {
+        Ok(description) => Ok((child, Link { stdin, messages }, description)),
+        Err(msg) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            Err(PluginError::Init(msg))
+        }
+    }
+}
+
+fn encode_event(event: &PluginEvent) -> [f32; EVENT_CHANNELS] {
+    match *event {
+        PluginEvent::Param { id, value } => [EVENT_PARAM, f32::from_bits(id), value as f32],
+        PluginEvent::Note(NoteEvent::NoteOn { note, velocity }) => [EVENT_NOTE_ON, f32::from_bits(note as u32), velocity],
+        PluginEvent::Note(NoteEvent::NoteOff { note }) => [EVENT_NOTE_OFF, f32::from_bits(note as u32), 0.0],
+    }
+}
+
+fn decode_event(frame: &[f32]) -> Option<PluginEvent> {
+    let id = frame[1].to_bits();
+    match frame[0] {
+        EVENT_PARAM => Some(PluginEvent::Param { id, value: frame[2] as f64 }),
+        EVENT_NOTE_ON => Some(PluginEvent::Note(NoteEvent::NoteOn { note: id as u8, velocity: frame[2] })),
+        EVENT_NOTE_OFF => Some(PluginEvent::Note(NoteEvent::NoteOff { note: id as u8 })),
+        _ => None,
+    }
+}
+
+/// Entry point of `pulsar-plugin-host`. Arguments: `<clap|lv2> <path> <id>
+/// <sample_rate> <max_frames>`. Returns only on errors; exits when the engine
+/// closes stdin.
+pub fn run_host(args: impl IntoIterator<Item = String>) -> PluginResult<()> {
+    let args: Vec<String> = args.into_iter().collect();
+    let usage = || PluginError::Load(format!("usage: {} <clap|lv2> <path> <id> <sample_rate> <max_frames>", HOST_BINARY));
+    let [format, path, id, sample_rate, max_frames] = args.as_slice() else { return Err(usage()) };
+    let format = match format.as_str() {
+        "clap" => PluginFormat::Clap,
+        "lv2" => PluginFormat::Lv2,
+        _ => return Err(usage()),
+    };
+    let sample_rate: f32 = sample_rate.parse().map_err(|_| usage())?;
+    let max_frames: usize = max_frames.parse().map_err(|_| usage())?;
+
+    let mut plugin = match super::load(format, Path::new(path), id, sample_rate, max_frames) {
+        Ok(plugin) => plugin,
+        Err(e) => {
+            send(&Err::<Description, String>(e.to_string()));
+            return Err(e);
+        }
+    };
+    send(&Ok::<Description, String>(Description {
+        info: plugin.info().clone(),
+        params: plugin.params().to_vec(),
+        inputs: plugin.input_channels(),
+        outputs: plugin.output_channels(),
+    }));
+
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line).map_err(|e| PluginError::Init(e.to_string()))?;
+    let setup: Setup = ron::from_str(&line).map_err(|e| PluginError::Init(format!("bad setup: {}", e)))?;
+    let open = |path: &Path| SharedRing::open(path).map_err(|e| PluginError::Init(format!("{}: {}", path.display(), e)));
+    let (input, output, events) = (open(&setup.input)?, open(&setup.output)?, open(&setup.events)?);
+    // the state first, so the replayed parameter values win over it
+    if let Some(Err(e)) = setup.state.map(|state| plugin.load_state(&state)) {
+        eprintln!("{}: {}", HOST_BINARY, e);
+    }
+
+    let (tx, requests) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if let Ok(request) = ron::from_str::<Request>(&line) {
+                let _ = tx.send(request);
+            }
+        }
+        // the engine closes stdin (or dies) when it no longer needs us
+        std::process::exit(0);
+    });
+
+    let inputs = plugin.input_channels();
+    let outputs = plugin.output_channels();
+    let mut in_buffers = vec![vec![0.0f32; max_frames]; inputs];
+    let mut out_buffers = vec![vec![0.0f32; max_frames]; outputs];
+    let mut interleaved = vec![0.0f32; max_frames * input.channels().max(output.channels())];
+    let mut event_frames = vec![0.0f32; EVENT_CAPACITY * EVENT_CHANNELS];
+    let mut pending: Vec<PluginEvent> =
+        setup.params.iter().map(|&(id, value)| PluginEvent::Param { id, value }).collect();
+
+    // one block of headroom, so the engine never waits on us
+    output.write(&vec![0.0; max_frames * output.channels()]);
+    loop {
+        // between blocks, so state calls never overlap processing
+        while let Ok(request) = requests.try_recv() {
+            send(&match request {
+                Request::SaveState => Reply::State(plugin.save_state()),
+                Request::LoadState(state) => Reply::Loaded(plugin.load_state(&state).map_err(|e| e.to_string())),
+            });
+        }
+
+        let frames = input.readable().min(max_frames);
+        if frames == 0 {
+            thread::sleep(HOST_POLL);
+            continue;
+        }
+
+        let channels = input.channels();
+        let block = &mut interleaved[..frames * channels];
+        input.read(block);
+        for (ch, buffer) in in_buffers.iter_mut().enumerate() {
+            for (i, sample) in buffer[..frames].iter_mut().enumerate() {
+                *sample = block[i * channels + ch % channels];
+            }
+        }
+
+        let count = events.readable().min(EVENT_CAPACITY);
+        let read = events.read(&mut event_frames[..count * EVENT_CHANNELS]);
+        pending.extend(event_frames[..read * EVENT_CHANNELS].chunks_exact(EVENT_CHANNELS).filter_map(decode_event));
+
+        let in_views: Vec<&[f32]> = in_buffers.iter().map(|b| &b[..frames]).collect();
+        let mut out_views: Vec<&mut [f32]> = out_buffers.iter_mut().map(|b| &mut b[..frames]).collect();
+        plugin.process(&in_views, &mut out_views, frames, &pending);
+        pending.clear();
+
+        let channels = output.channels();
+        let block = &mut interleaved[..frames * channels];
+        for (i, frame) in block.chunks_exact_mut(channels).enumerate() {
+            for (ch, sample) in frame.iter_mut().enumerate() {
+                *sample = out_buffers.get(ch).map_or(0.0, |buffer| buffer[i]);
+            }
+        }
+        output.write(block);
+    }
+}
+
+/// Write a message for the engine to stdout
+fn send(message: &impl Serialize) {
+    if let Ok(line) = ron::to_string(message) {
+        let mut stdout = io::stdout().lock();
+        let _ = writeln!(stdout, "{}{}", MESSAGE_PREFIX, line);
+        let _ = stdout.flush();
+    }
+}
@@ -0,0 +1,648 @@
+//! CLAP plugin hosting.
+//!
+//! Libraries are opened once and shared between instances; `clap_entry.deinit`
+//! runs when the last instance from a library is gone. Instances are created,
+//! activated and destroyed on the calling (control) thread and processed on
+//! the audio thread, as the CLAP threading rules require. The host exposes no
+//! extensions of its own: plugins that need one to run are expected to refuse
+//! `init`, which surfaces as `PluginError::Init`.
+
+pub mod ffi;
+
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::{Arc, Mutex, Weak};
+
+use libloading::Library;
+
+use crate::jobs::{JobHandle, JobPriority, WorkerPool};
+use crate::rt_processing::notes::{NoteEvent, NoteExpression};
+
+use super::{
+    MAX_PLUGIN_CHANNELS, ParamInfo, PluginError, PluginEvent, PluginFormat, PluginInfo, PluginKind, PluginProcessor,
+    PluginResult,
+};
+use ffi::*;
+
+/// Channels assumed for the main ports of a plugin without the audio-ports extension
+const DEFAULT_CHANNELS: u32 = 2;
+
+/// Open libraries, so instances share one `init`/`deinit` pair
+static LIBRARIES: Mutex<Vec<(PathBuf, Weak<ClapLibrary>)>> = Mutex::new(Vec::new());
+
+struct ClapLibrary {
+    entry: *const clap_plugin_entry,
+    path: PathBuf,
+    // must outlive `entry`
+    _library: Library,
+}
+
+// SAFETY: the entry and factory are immutable tables of thread-safe function
+// pointers (CLAP requires the factory to be callable from any thread).
+unsafe impl Send for ClapLibrary {}
+unsafe impl Sync for ClapLibrary {}
+
+impl ClapLibrary {
+    /// Open `path` (a `.clap` file, or bundle on macOS), reusing it if already open
+    fn open(path: &Path) -> PluginResult<Arc<Self>> {
+        let mut libraries = LIBRARIES.lock().unwrap_or_else(|e| e.into_inner());
+        libraries.retain(|(_, library)| library.strong_count() > 0);
+        if let Some(library) = libraries.iter().find(|(p, _)| p == path).and_then(|(_, l)| l.upgrade()) {
+            return Ok(library);
+        }
+
+        let load_err = |e: libloading::Error| PluginError::Load(format!("{}: {}", path.display(), e));
+        // SAFETY: loading runs the library's initializers; that is the point
+        // of hosting it
+        let library = unsafe { Library::new(binary_path(path)) }.map_err(load_err)?;
+        // SAFETY: `clap_entry` is a `const clap_plugin_entry` data symbol
+        let entry = unsafe { *library.get::<*const clap_plugin_entry>(b"clap_entry\0").map_err(load_err)? };
+        if entry.is_null() {
+            return Err(PluginError::Load(format!("{}: null clap_entry", path.display())));
+        }
+        // SAFETY: non-null pointer into the loaded library
+        let entry_ref = unsafe { &*entry };
+        if !entry_ref.clap_version.is_compatible() {
+            let v = entry_ref.clap_version;
+            return Err(PluginError::Unsupported(format!("CLAP version {}.{}.{}", v.major, v.minor, v.revision)));
+        }
+        let c_path = CString::new(path.to_string_lossy().as_bytes())
+            .map_err(|_| PluginError::Load(format!("{}: invalid path", path.display())))?;
+        // SAFETY: called once per load, before any other entry function
+        if !unsafe { (entry_ref.init)(c_path.as_ptr()) } {
+            return Err(PluginError::Init(format!("{}: clap_entry.init failed", path.display())));
+        }
+
+        let library = Arc::new(Self { entry, path: path.to_path_buf(), _library: library });
+        libraries.push((path.to_path_buf(), Arc::downgrade(&library)));
+        Ok(library)
+    }
+
+    fn factory(&self) -> PluginResult<&clap_plugin_factory> {
+        // SAFETY: entry is valid while the library is loaded and init succeeded
+        let factory = unsafe { ((*self.entry).get_factory)(CLAP_PLUGIN_FACTORY_ID.as_ptr()) };
+        if factory.is_null() {
+            return Err(PluginError::Unsupported(format!("{}: no plugin factory", self.path.display())));
+        }
+        // SAFETY: the factory lives as long as the library
+        Ok(unsafe { &*(factory as *const clap_plugin_factory) })
+    }
+
+    fn plugins(&self) -> PluginResult<Vec<PluginInfo>> {
+        let factory = self.factory()?;
+        // SAFETY: factory functions may be called from any thread after init
+        let count = unsafe { (factory.get_plugin_count)(factory) };
+        let plugins = (0..count)
+            .filter_map(|index| {
+                let desc = unsafe { (factory.get_plugin_descriptor)(factory, index) };
+                // SAFETY: descriptors live as long as the library
+                (!desc.is_null()).then(|| plugin_info(unsafe { &*desc }, &self.path))
+            })
+            .collect();
+        Ok(plugins)
+    }
+}
+
+impl Drop for ClapLibrary {
+    fn drop(&mut self) {
+        // SAFETY: every instance holds an Arc, so none is left
+        unsafe { ((*self.entry).deinit)() }
+    }
+}
+
+/// Directories searched for plugins: `CLAP_PATH` entries first, then the
+/// platform's standard locations
+pub fn search_paths() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = std::env::var_os("CLAP_PATH")
+        .map(|value| std::env::split_paths(&value).collect())
+        .unwrap_or_default();
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+
+    if cfg!(target_os = "macos") {
+        if let Some(home) = &home {
+            paths.push(home.join("Library/Audio/Plug-Ins/CLAP"));
+        }
+        paths.push(PathBuf::from("/Library/Audio/Plug-Ins/CLAP"));
+    } else if cfg!(windows) {
+        if let Some(common) = std::env::var_os("COMMONPROGRAMFILES") {
+            paths.push(PathBuf::from(common).join("CLAP"));
+        }
+        if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+            paths.push(PathBuf::from(local).join("Programs/Common/CLAP"));
+        }
+    } else {
+        if let Some(home) = &home {
+            paths.push(home.join(".clap"));
+        }
+        paths.push(PathBuf::from("/usr/lib/clap"));
+    }
+    paths
+}
+
+/// Plugins in one `.clap` file
+pub fn scan_file(path: impl AsRef<Path>) -> PluginResult<Vec<PluginInfo>> {
+    ClapLibrary::open(path.as_ref())?.plugins()
+}
+
+/// Plugins in every `.clap` file under `dirs`, recursively. Files that fail
+/// to load are skipped. Not RT-safe, and runs plugin code: prefer `scan_job`
+/// from a UI.
+pub fn scan(dirs: &[PathBuf]) -> Vec<PluginInfo> {
+    let mut plugins = Vec::new();
+    for dir in dirs {
+        scan_dir(dir, &mut plugins);
+    }
+    plugins
+}
+
+/// Scan on a background job
+pub fn scan_job(pool: &WorkerPool, dirs: Vec<PathBuf>) -> JobHandle<Vec<PluginInfo>> {
+    pool.spawn(JobPriority::Io, "CLAP scan", move |_| scan(&dirs))
+}
+
+fn scan_dir(dir: &Path, plugins: &mut Vec<PluginInfo>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("clap")) {
+            if let Ok(found) = scan_file(&path) {
+                plugins.extend(found);
+            }
+        } else if path.is_dir() {
+            scan_dir(&path, plugins);
+        }
+    }
+}
+
+/// The loadable binary: macOS bundles keep it under `Contents/MacOS`
+/// The per-note expression a CLAP `expression_id` drives, for hosts feeding
+/// CLAP note-expression events to a `VoicePool`. CLAP's tuning is in
+/// semitones and its pressure and brightness are 0..1, as `NoteExpression`
+/// has them, so values pass through unchanged. Volume, pan, vibrato and
+/// expression have no counterpart and give `None`.
+pub fn note_expression(expression_id: i32) -> Option<NoteExpression> {
+    match expression_id {
+        CLAP_NOTE_EXPRESSION_TUNING => Some(NoteExpression::PitchOffset),
+        CLAP_NOTE_EXPRESSION_PRESSURE => Some(NoteExpression::Pressure),
+        CLAP_NOTE_EXPRESSION_BRIGHTNESS => Some(NoteExpression::Brightness),
+        _ => None,
+    }
+}
+
+fn binary_path(path: &Path) -> PathBuf {
+    match path.file_stem() {
+        Some(stem) if path.is_dir() => path.join("Contents/MacOS").join(stem),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn plugin_info(desc: &clap_plugin_descriptor, path: &Path) -> PluginInfo {
+    let mut kind = PluginKind::Effect;
+    if !desc.features.is_null() {
+        // SAFETY: null-terminated array of C strings
+        let mut feature = desc.features;
+        unsafe {
+            while !(*feature).is_null() {
+                if CStr::from_ptr(*feature).to_bytes() == b"instrument" {
+                    kind = PluginKind::Instrument;
+                }
+                feature = feature.add(1);
+            }
+        }
+    }
+    PluginInfo {
+        format: PluginFormat::Clap,
+        id: c_string(desc.id),
+        name: c_string(desc.name),
+        vendor: c_string(desc.vendor),
+        version: c_string(desc.version),
+        path: path.to_path_buf(),
+        kind,
+    }
+}
+
+fn c_string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    // SAFETY: CLAP strings are null-terminated and outlive this call
+    unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned()
+}
+
+/// One event as laid out for the plugin; every variant starts with the header
+#[repr(C)]
+#[derive(Copy, Clone)]
+union ClapEvent {
+    header: clap_event_header,
+    note: clap_event_note,
+    param: clap_event_param_value,
+}
+
+impl ClapEvent {
+    fn new(event: &PluginEvent) -> Self {
+        let header = |type_: u16, size: usize| clap_event_header {
+            size: size as u32,
+            time: 0,
+            space_id: CLAP_CORE_EVENT_SPACE_ID,
+            type_,
+            flags: 0,
+        };
+        match *event {
+            PluginEvent::Param { id, value } => ClapEvent {
+                param: clap_event_param_value {
+                    header: header(CLAP_EVENT_PARAM_VALUE, size_of::<clap_event_param_value>()),
+                    param_id: id,
+                    cookie: ptr::null_mut(),
+                    note_id: -1,
+                    port_index: -1,
+                    channel: -1,
+                    key: -1,
+                    value,
+                },
+            },
+            PluginEvent::Note(note) => {
+                let (type_, velocity) = match note {
+                    NoteEvent::NoteOn { velocity, .. } => (CLAP_EVENT_NOTE_ON, velocity as f64),
+                    NoteEvent::NoteOff { .. } => (CLAP_EVENT_NOTE_OFF, 0.0),
+                };
+                ClapEvent {
+                    note: clap_event_note {
+                        header: header(type_, size_of::<clap_event_note>()),
+                        note_id: -1,
+                        port_index: 0,
+                        channel: 0,
+                        key: note.note() as i16,
+                        velocity,
+                    },
+                }
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn input_events_size(list: *const clap_input_events) -> u32 {
+    // SAFETY: ctx is the processing instance's event Vec, alive for the call
+    unsafe { (*((*list).ctx as *const Vec<ClapEvent>)).len() as u32 }
+}
+
+unsafe extern "C" fn input_events_get(list: *const clap_input_events, index: u32) -> *const clap_event_header {
+    // SAFETY: as above
+    let events = unsafe { &*((*list).ctx as *const Vec<ClapEvent>) };
+    events.get(index as usize).map_or(ptr::null(), |event| event as *const ClapEvent as *const clap_event_header)
+}
+
+/// Output events (parameter gestures, note ends) aren't used; accept and drop them
+unsafe extern "C" fn output_events_push(_list: *const clap_output_events, _event: *const clap_event_header) -> bool {
+    true
+}
+
+unsafe extern "C" fn ostream_write(stream: *const clap_ostream, buffer: *const c_void, size: u64) -> i64 {
+    // SAFETY: ctx is the Vec being saved into, alive for the save call; the
+    // plugin passes `size` readable bytes
+    unsafe {
+        let state = &mut *((*stream).ctx as *mut Vec<u8>);
+        state.extend_from_slice(std::slice::from_raw_parts(buffer as *const u8, size as usize));
+    }
+    size as i64
+}
+
+unsafe extern "C" fn istream_read(stream: *const clap_istream, buffer: *mut c_void, size: u64) -> i64 {
+    // SAFETY: ctx is the unread rest of the state, alive for the load call;
+    // the plugin passes room for `size` bytes
+    unsafe {
+        let rest = &mut *((*stream).ctx as *mut &[u8]);
+        let count = rest.len().min(size as usize);
+        ptr::copy_nonoverlapping(rest.as_ptr(), buffer as *mut u8, count);
+        *rest = &rest[count..];
+        count as i64
+    }
+}
+
+unsafe extern "C" fn host_get_extension(_host: *const clap_host, _id: *const c_char) -> *const c_void {
+    ptr::null()
+}
+
+unsafe extern "C" fn host_request(_host: *const clap_host) {}
+
+/// An activated CLAP plugin instance
+pub struct ClapPlugin {
+    plugin: *const clap_plugin,
+    // the plugin keeps a pointer to it
+    _host: Box<clap_host>,
+    events: Vec<ClapEvent>,
+    info: PluginInfo,
+    params: Vec<ParamInfo>,
+    input_channels: usize,
+    output_channels: usize,
+    max_frames: usize,
+    /// One buffer per port; the first of each direction is the main port
+    input_ports: Vec<clap_audio_buffer>,
+    output_ports: Vec<clap_audio_buffer>,
+    /// Channel pointers the port buffers point into
+    input_ptrs: Vec<*mut f32>,
+    output_ptrs: Vec<*mut f32>,
+    /// Silence for extra input ports, scratch for extra output ports
+    silence: Vec<f32>,
+    discard: Vec<f32>,
+    active: bool,
+    processing: bool,
+    steady_time: i64,
+    // dropped last: the plugin's code lives here
+    _library: Arc<ClapLibrary>,
+}
+
+// SAFETY: the instance is only touched through &mut self, from one thread at
+// a time; CLAP allows main-thread and audio-thread calls on different threads.
+unsafe impl Send for ClapPlugin {}
+unsafe impl Sync for ClapPlugin {}
+
+impl ClapPlugin {
+    /// Create and activate plugin `id` from the `.clap` file at `path`
+    pub fn load(path: impl AsRef<Path>, id: &str, sample_rate: f32, max_frames: usize) -> PluginResult<Self> {
+        let library = ClapLibrary::open(path.as_ref())?;
+        let info = library
+            .plugins()?
+            .into_iter()
+            .find(|info| info.id == id)
+            .ok_or_else(|| PluginError::NotFound(id.to_string()))?;
+        let factory = library.factory()?;
+
+        let host = Box::new(clap_host {
+            clap_version: CLAP_VERSION,
+            host_data: ptr::null_mut(),
+            name: c"Pulsar".as_ptr(),
+            vendor: c"TF3K".as_ptr(),
+            url: c"".as_ptr(),
+            version: c"0.1.0".as_ptr(),
+            get_extension: host_get_extension,
+            request_restart: host_request,
+            request_process: host_request,
+            request_callback: host_request,
+        });
+        let c_id = CString::new(id).map_err(|_| PluginError::NotFound(id.to_string()))?;
+        // SAFETY: host outlives the instance (both owned by Self)
+        let plugin = unsafe { (factory.create_plugin)(factory, &*host, c_id.as_ptr()) };
+        if plugin.is_null() {
+            return Err(PluginError::Init(format!("{}: create_plugin failed", id)));
+        }
+        // SAFETY: freshly created; init must come first, and destroy is the
+        // only valid call after a failed init
+        unsafe {
+            if !((*plugin).init)(plugin) {
+                ((*plugin).destroy)(plugin);
+                return Err(PluginError::Init(format!("{}: init failed", id)));
+            }
+        }
+
+        let mut instance = Self {
+            plugin,
+            _host: host,
+            events: Vec::with_capacity(super::EVENT_CAPACITY),
+            params: Vec::new(),
+            input_channels: 0,
+            output_channels: 0,
+            max_frames,
+            input_ports: Vec::new(),
+            output_ports: Vec::new(),
+            input_ptrs: Vec::new(),
+            output_ptrs: Vec::new(),
+            silence: vec![0.0; max_frames],
+            discard: Vec::new(),
+            active: false,
+            processing: false,
+            steady_time: 0,
+            _library: library,
+            info,
+        };
+        // from here on, Drop destroys the plugin
+        instance.params = instance.query_params();
+        instance.setup_ports()?;
+
+        // SAFETY: initialized and not yet active
+        instance.active = unsafe { ((*plugin).activate)(plugin, sample_rate as f64, 1, max_frames as u32) };
+        if !instance.active {
+            return Err(PluginError::Init(format!("{}: activate failed", id)));
+        }
+        Ok(instance)
+    }
+
+    fn extension<T>(&self, id: &CStr) -> Option<&T> {
+        // SAFETY: extensions are static tables owned by the plugin
+        let ext = unsafe { ((*self.plugin).get_extension)(self.plugin, id.as_ptr()) };
+        (!ext.is_null()).then(|| unsafe { &*(ext as *const T) })
+    }
+
+    fn query_params(&self) -> Vec<ParamInfo> {
+        let Some(params) = self.extension::<clap_plugin_params>(CLAP_EXT_PARAMS) else {
+            return Vec::new();
+        };
+        // SAFETY: main-thread calls on an initialized plugin
+        let count = unsafe { (params.count)(self.plugin) };
+        (0..count)
+            .filter_map(|index| {
+                let mut info = clap_param_info {
+                    id: CLAP_INVALID_ID,
+                    flags: 0,
+                    cookie: ptr::null_mut(),
+                    name: [0; CLAP_NAME_SIZE],
+                    module: [0; CLAP_PATH_SIZE],
+                    min_value: 0.0,
+                    max_value: 0.0,
+                    default_value: 0.0,
+                };
+                if !unsafe { (params.get_info)(self.plugin, index, &mut info) } {
+                    return None;
+                }
+                info.name[CLAP_NAME_SIZE - 1] = 0;
+                Some(ParamInfo {
+                    id: info.id,
+                    name: c_string(info.name.as_ptr()),
+                    min: info.min_value,
+                    max: info.max_value,
+                    default: info.default_value,
+                })
+            })
+            .collect()
+    }
+
+    /// Channel counts per port, main port first
+    fn port_channels(&self, is_input: bool) -> Vec<u32> {
+        let Some(ports) = self.extension::<clap_plugin_audio_ports>(CLAP_EXT_AUDIO_PORTS) else {
+            let instrument = self.info.kind == PluginKind::Instrument;
+            return if is_input && instrument { Vec::new() } else { vec![DEFAULT_CHANNELS] };
+        };
+        // SAFETY: main-thread calls on an initialized, inactive plugin
+        let count = unsafe { (ports.count)(self.plugin, is_input) };
+        (0..count)
+            .map(|index| {
+                let mut info = clap_audio_port_info {
+                    id: CLAP_INVALID_ID,
+                    name: [0; CLAP_NAME_SIZE],
+                    flags: 0,
+                    channel_count: 0,
+                    port_type: ptr::null(),
+                    in_place_pair: CLAP_INVALID_ID,
+                };
+                let ok = unsafe { (ports.get)(self.plugin, index, is_input, &mut info) };
+                if ok { info.channel_count } else { 0 }
+            })
+            .collect()
+    }
+
+    /// Point every port at a buffer: the main ports at the node's audio (set
+    /// per block), extra inputs at silence and extra outputs at scratch
+    fn setup_ports(&mut self) -> PluginResult<()> {
+        let inputs = self.port_channels(true);
+        let outputs = self.port_channels(false);
+        self.input_channels = inputs.first().copied().unwrap_or(0) as usize;
+        self.output_channels = outputs.first().copied().unwrap_or(0) as usize;
+        if self.input_channels > MAX_PLUGIN_CHANNELS || self.output_channels > MAX_PLUGIN_CHANNELS {
+            return Err(PluginError::Unsupported(format!(
+                "{}: more than {} channels on a main port",
+                self.info.id, MAX_PLUGIN_CHANNELS
+            )));
+        }
+
+        let extra_outputs: u32 = outputs.iter().skip(1).sum();
+        self.discard = vec![0.0; extra_outputs as usize * self.max_frames];
+        let silence = self.silence.as_mut_ptr();
+        self.input_ptrs = vec![silence; inputs.iter().sum::<u32>() as usize];
+        self.output_ptrs = (0..outputs.iter().sum::<u32>() as usize)
+            .map(|ch| match ch.checked_sub(self.output_channels) {
+                // SAFETY: within `discard`, sized for every extra channel
+                Some(extra) => unsafe { self.discard.as_mut_ptr().add(extra * self.max_frames) },
+                None => ptr::null_mut(),
+            })
+            .collect();
+
+        self.input_ports = port_buffers(&inputs, &mut self.input_ptrs);
+        self.output_ports = port_buffers(&outputs, &mut self.output_ptrs);
+        Ok(())
+    }
+}
+
+fn port_buffers(channels: &[u32], ptrs: &mut [*mut f32]) -> Vec<clap_audio_buffer> {
+    let mut offset = 0;
+    channels
+        .iter()
+        .map(|&count| {
+            // SAFETY: offsets stay within `ptrs`, which holds every port's channels
+            let data32 = unsafe { ptrs.as_mut_ptr().add(offset) };
+            offset += count as usize;
+            clap_audio_buffer { data32, data64: ptr::null_mut(), channel_count: count, latency: 0, constant_mask: 0 }
+        })
+        .collect()
+}
+
+impl PluginProcessor for ClapPlugin {
+    fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    fn params(&self) -> &[ParamInfo] {
+        &self.params
+    }
+
+    fn input_channels(&self) -> usize {
+        self.input_channels
+    }
+
+    fn output_channels(&self) -> usize {
+        self.output_channels
+    }
+
+    fn process(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], frames: usize, events: &[PluginEvent]) {
+        let frames = frames.min(self.max_frames);
+        if !self.processing {
+            // SAFETY: audio-thread call on an active plugin
+            self.processing = unsafe { ((*self.plugin).start_processing)(self.plugin) };
+        }
+        if !self.processing || inputs.len() < self.input_channels || outputs.len() < self.output_channels {
+            for channel in outputs.iter_mut() {
+                channel[..frames].fill(0.0);
+            }
+            return;
+        }
+
+        self.events.clear();
+        self.events.extend(events.iter().take(self.events.capacity()).map(ClapEvent::new));
+        for (ptr, input) in self.input_ptrs.iter_mut().zip(inputs.iter().take(self.input_channels)) {
+            // the plugin only reads inputs
+            *ptr = input.as_ptr() as *mut f32;
+        }
+        for (ptr, output) in self.output_ptrs.iter_mut().zip(outputs.iter_mut().take(self.output_channels)) {
+            *ptr = output.as_mut_ptr();
+        }
+
+        let in_events = clap_input_events {
+            ctx: &self.events as *const Vec<ClapEvent> as *mut c_void,
+            size: input_events_size,
+            get: input_events_get,
+        };
+        let out_events = clap_output_events { ctx: ptr::null_mut(), try_push: output_events_push };
+        let process = clap_process {
+            steady_time: self.steady_time,
+            frames_count: frames as u32,
+            transport: ptr::null(),
+            audio_inputs: self.input_ports.as_ptr(),
+            audio_outputs: self.output_ports.as_mut_ptr(),
+            audio_inputs_count: self.input_ports.len() as u32,
+            audio_outputs_count: self.output_ports.len() as u32,
+            in_events: &in_events,
+            out_events: &out_events,
+        };
+        // SAFETY: every channel pointer covers at least `frames` samples and
+        // stays valid for the call
+        let status = unsafe { ((*self.plugin).process)(self.plugin, &process) };
+        if status == CLAP_PROCESS_ERROR {
+            for channel in outputs.iter_mut() {
+                channel[..frames].fill(0.0);
+            }
+        }
+        self.steady_time += frames as i64;
+    }
+
+    fn reset(&mut self) {
+        // SAFETY: audio-thread call on an active plugin
+        unsafe { ((*self.plugin).reset)(self.plugin) }
+    }
+
+    fn save_state(&mut self) -> Option<Vec<u8>> {
+        let state_ext = self.extension::<clap_plugin_state>(CLAP_EXT_STATE)?;
+        let mut state = Vec::new();
+        let stream = clap_ostream { ctx: &mut state as *mut Vec<u8> as *mut c_void, write: ostream_write };
+        // SAFETY: main-thread call; the stream and its Vec outlive it
+        unsafe { (state_ext.save)(self.plugin, &stream) }.then_some(state)
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> PluginResult<()> {
+        let Some(state_ext) = self.extension::<clap_plugin_state>(CLAP_EXT_STATE) else {
+            return Err(PluginError::Unsupported(format!("{}: no clap.state extension", self.info.id)));
+        };
+        let mut rest = state;
+        let stream = clap_istream { ctx: &mut rest as *mut &[u8] as *mut c_void, read: istream_read };
+        // SAFETY: main-thread call; the stream and the slice outlive it
+        if unsafe { (state_ext.load)(self.plugin, &stream) } {
+            Ok(())
+        } else {
+            Err(PluginError::State(format!("{}: rejected by the plugin", self.info.id)))
+        }
+    }
+}
+
+impl Drop for ClapPlugin {
+    fn drop(&mut self) {
+        // SAFETY: tears down in reverse order of setup
+        unsafe {
+            if self.processing {
+                ((*self.plugin).stop_processing)(self.plugin);
+            }
+            if self.active {
+                ((*self.plugin).deactivate)(self.plugin);
+            }
+            ((*self.plugin).destroy)(self.plugin);
+        }
+    }
+}
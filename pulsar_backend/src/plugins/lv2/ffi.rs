@@ -0,0 +1,132 @@
+//! The subset of the LV2 C ABI the host uses, declared by hand from
+//! `lv2/core/lv2.h` and the urid, atom, options and state extension headers.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use std::ffi::{c_char, c_void};
+
+pub type LV2_Handle = *mut c_void;
+pub type LV2_URID = u32;
+
+#[repr(C)]
+pub struct LV2_Feature {
+    pub URI: *const c_char,
+    pub data: *mut c_void,
+}
+
+#[repr(C)]
+pub struct LV2_Descriptor {
+    pub URI: *const c_char,
+    pub instantiate: unsafe extern "C" fn(
+        descriptor: *const LV2_Descriptor,
+        sample_rate: f64,
+        bundle_path: *const c_char,
+        features: *const *const LV2_Feature,
+    ) -> LV2_Handle,
+    pub connect_port: unsafe extern "C" fn(instance: LV2_Handle, port: u32, data: *mut c_void),
+    pub activate: Option<unsafe extern "C" fn(instance: LV2_Handle)>,
+    pub run: unsafe extern "C" fn(instance: LV2_Handle, sample_count: u32),
+    pub deactivate: Option<unsafe extern "C" fn(instance: LV2_Handle)>,
+    pub cleanup: unsafe extern "C" fn(instance: LV2_Handle),
+    pub extension_data: Option<unsafe extern "C" fn(uri: *const c_char) -> *const c_void>,
+}
+
+/// Signature of the `lv2_descriptor` symbol every plugin binary exports
+pub type LV2_Descriptor_Function = unsafe extern "C" fn(index: u32) -> *const LV2_Descriptor;
+
+pub type LV2_URID_Map_Handle = *mut c_void;
+
+#[repr(C)]
+pub struct LV2_URID_Map {
+    pub handle: LV2_URID_Map_Handle,
+    pub map: unsafe extern "C" fn(handle: LV2_URID_Map_Handle, uri: *const c_char) -> LV2_URID,
+}
+
+#[repr(C)]
+pub struct LV2_URID_Unmap {
+    pub handle: LV2_URID_Map_Handle,
+    pub unmap: unsafe extern "C" fn(handle: LV2_URID_Map_Handle, urid: LV2_URID) -> *const c_char,
+}
+
+#[repr(C)]
+pub struct LV2_Atom {
+    pub size: u32,
+    pub type_: u32,
+}
+
+#[repr(C)]
+pub struct LV2_Atom_Sequence_Body {
+    pub unit: u32,
+    pub pad: u32,
+}
+
+#[repr(C)]
+pub struct LV2_Atom_Sequence {
+    pub atom: LV2_Atom,
+    pub body: LV2_Atom_Sequence_Body,
+}
+
+/// Event header in a sequence; `time` is in frames. The payload follows,
+/// padded to 8 bytes.
+#[repr(C)]
+pub struct LV2_Atom_Event {
+    pub time: i64,
+    pub body: LV2_Atom,
+}
+
+pub const LV2_OPTIONS_INSTANCE: u32 = 0;
+
+#[repr(C)]
+pub struct LV2_Options_Option {
+    pub context: u32,
+    pub subject: u32,
+    pub key: LV2_URID,
+    pub size: u32,
+    pub type_: LV2_URID,
+    pub value: *const c_void,
+}
+
+pub type LV2_State_Handle = *mut c_void;
+
+pub const LV2_STATE_SUCCESS: u32 = 0;
+pub const LV2_STATE_ERR_UNKNOWN: u32 = 1;
+pub const LV2_STATE_ERR_BAD_FLAGS: u32 = 4;
+/// Plain old data: the value can be copied byte for byte
+pub const LV2_STATE_IS_POD: u32 = 1;
+/// The value means the same on any machine
+pub const LV2_STATE_IS_PORTABLE: u32 = 2;
+
+pub type LV2_State_Store_Function = unsafe extern "C" fn(
+    handle: LV2_State_Handle,
+    key: LV2_URID,
+    value: *const c_void,
+    size: usize,
+    type_: LV2_URID,
+    flags: u32,
+) -> u32;
+
+pub type LV2_State_Retrieve_Function = unsafe extern "C" fn(
+    handle: LV2_State_Handle,
+    key: LV2_URID,
+    size: *mut usize,
+    type_: *mut LV2_URID,
+    flags: *mut u32,
+) -> *const c_void;
+
+#[repr(C)]
+pub struct LV2_State_Interface {
+    pub save: unsafe extern "C" fn(
+        instance: LV2_Handle,
+        store: LV2_State_Store_Function,
+        handle: LV2_State_Handle,
+        flags: u32,
+        features: *const *const LV2_Feature,
+    ) -> u32,
+    pub restore: unsafe extern "C" fn(
+        instance: LV2_Handle,
+        retrieve: LV2_State_Retrieve_Function,
+        handle: LV2_State_Handle,
+        flags: u32,
+        features: *const *const LV2_Feature,
+    ) -> u32,
+}
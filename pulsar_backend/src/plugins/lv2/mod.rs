@@ -0,0 +1,795 @@
+//! LV2 plugin hosting (Linux).
+//!
+//! Bundles are read with the small Turtle reader in `turtle` rather than
+//! lilv, so the host has no system dependencies. The host provides
+//! `urid:map`/`urid:unmap`, `options:options` (block lengths and sample rate)
+//! and `buf-size:boundedBlockLength`; plugins requiring anything else are
+//! reported as `PluginError::Unsupported`. Notes reach instruments as MIDI in
+//! their first atom input. Plugin state goes through the state interface,
+//! keeping plain-data values only, keyed by URI so it loads in any process.
+
+pub mod ffi;
+pub mod turtle;
+
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::slice;
+use std::sync::Mutex;
+
+use libloading::Library;
+use serde::{Deserialize, Serialize};
+
+use crate::jobs::{JobHandle, JobPriority, WorkerPool};
+use crate::rt_processing::notes::NoteEvent;
+
+use super::{
+    MAX_PLUGIN_CHANNELS, ParamInfo, PluginError, PluginEvent, PluginFormat, PluginInfo, PluginKind, PluginProcessor,
+    PluginResult,
+};
+use ffi::*;
+use turtle::{Graph, Term};
+
+const LV2: &str = "http://lv2plug.in/ns/lv2core#";
+const ATOM: &str = "http://lv2plug.in/ns/ext/atom#";
+const BUF_SIZE: &str = "http://lv2plug.in/ns/ext/buf-size#";
+const RDFS_SEE_ALSO: &str = "http://www.w3.org/2000/01/rdf-schema#seeAlso";
+const DOAP_NAME: &str = "http://usefulinc.com/ns/doap#name";
+const DOAP_MAINTAINER: &str = "http://usefulinc.com/ns/doap#maintainer";
+const FOAF_NAME: &str = "http://xmlns.com/foaf/0.1/name";
+const URID_MAP: &str = "http://lv2plug.in/ns/ext/urid#map";
+const URID_UNMAP: &str = "http://lv2plug.in/ns/ext/urid#unmap";
+const OPTIONS: &str = "http://lv2plug.in/ns/ext/options#options";
+const PARAM_SAMPLE_RATE: &str = "http://lv2plug.in/ns/ext/parameters#sampleRate";
+const MIDI_EVENT: &str = "http://lv2plug.in/ns/ext/midi#MidiEvent";
+const STATE_INTERFACE: &CStr = c"http://lv2plug.in/ns/ext/state#interface";
+
+/// Features a plugin may require: the ones passed to `instantiate`, plus
+/// promises the host keeps by construction (bounded, out-of-place blocks)
+const SUPPORTED_FEATURES: [&str; 7] = [
+    URID_MAP,
+    URID_UNMAP,
+    OPTIONS,
+    "http://lv2plug.in/ns/ext/buf-size#boundedBlockLength",
+    "http://lv2plug.in/ns/lv2core#hardRTCapable",
+    "http://lv2plug.in/ns/lv2core#inPlaceBroken",
+    "http://lv2plug.in/ns/lv2core#isLive",
+];
+
+/// Bytes per atom port buffer
+const ATOM_CAPACITY: usize = 8192;
+
+/// Directories searched for bundles: `LV2_PATH` if set, else the standard locations
+pub fn search_paths() -> Vec<PathBuf> {
+    if let Some(value) = std::env::var_os("LV2_PATH") {
+        return std::env::split_paths(&value).collect();
+    }
+    let mut paths = Vec::new();
+    if let Some(home) = std::env::var_os("HOME") {
+        paths.push(PathBuf::from(home).join(".lv2"));
+    }
+    paths.push(PathBuf::from("/usr/local/lib/lv2"));
+    paths.push(PathBuf::from("/usr/lib/lv2"));
+    paths
+}
+
+/// Plugins in the bundles directly under `dirs`. Bundles that fail to parse
+/// are skipped. Reads files only; no plugin code runs.
+pub fn scan(dirs: &[PathBuf]) -> Vec<PluginInfo> {
+    let mut plugins = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(dir) else { continue };
+        for entry in entries.flatten() {
+            if entry.path().join("manifest.ttl").is_file()
+                && let Ok(found) = scan_bundle(entry.path())
+            {
+                plugins.extend(found);
+            }
+        }
+    }
+    plugins
+}
+
+/// Scan on a background job
+pub fn scan_job(pool: &WorkerPool, dirs: Vec<PathBuf>) -> JobHandle<Vec<PluginInfo>> {
+    pool.spawn(JobPriority::Io, "LV2 scan", move |_| scan(&dirs))
+}
+
+/// Plugins described by one bundle directory
+pub fn scan_bundle(bundle: impl AsRef<Path>) -> PluginResult<Vec<PluginInfo>> {
+    let bundle = Bundle::read(bundle.as_ref())?;
+    Ok(bundle.plugins().into_iter().map(|plugin| bundle.info(plugin)).collect())
+}
+
+/// The bundle of the plugin with `uri`, searching `search_paths()`
+pub fn find(uri: &str) -> PluginResult<PluginInfo> {
+    scan(&search_paths())
+        .into_iter()
+        .find(|info| info.id == uri)
+        .ok_or_else(|| PluginError::NotFound(uri.to_string()))
+}
+
+fn lv2(name: &str) -> String {
+    format!("{}{}", LV2, name)
+}
+
+/// A bundle's manifest and every file it points to
+struct Bundle {
+    dir: PathBuf,
+    graph: Graph,
+}
+
+impl Bundle {
+    fn read(dir: &Path) -> PluginResult<Self> {
+        let mut graph = Graph::new();
+        let mut files = vec![file_uri(&dir.join("manifest.ttl"))];
+        let mut index = 0;
+        while let Some(uri) = files.get(index).cloned() {
+            index += 1;
+            let path = file_path(&uri);
+            // data files outside the bundle (shared presets, ...) aren't needed
+            if !path.starts_with(dir) {
+                continue;
+            }
+            let text = fs::read_to_string(&path).map_err(|e| PluginError::Load(format!("{}: {}", path.display(), e)))?;
+            graph.parse(&text, &uri).map_err(|e| PluginError::Load(format!("{}: {}", path.display(), e)))?;
+            let more: Vec<String> = graph
+                .instances(&lv2("Plugin"))
+                .flat_map(|plugin| graph.objects(plugin, RDFS_SEE_ALSO))
+                .filter_map(|file| file.as_iri())
+                .filter(|file| !files.iter().any(|f| f == file))
+                .map(str::to_string)
+                .collect();
+            files.extend(more);
+        }
+        Ok(Self { dir: dir.to_path_buf(), graph })
+    }
+
+    fn plugins(&self) -> Vec<&Term> {
+        let mut plugins = Vec::new();
+        for plugin in self.graph.instances(&lv2("Plugin")) {
+            if !plugins.contains(&plugin) {
+                plugins.push(plugin);
+            }
+        }
+        plugins
+    }
+
+    fn info(&self, plugin: &Term) -> PluginInfo {
+        let literal = |subject: &Term, predicate: &str| {
+            self.graph.object(subject, predicate).and_then(Term::as_literal).unwrap_or_default().to_string()
+        };
+        let vendor = self.graph.object(plugin, DOAP_MAINTAINER).map(|m| literal(m, FOAF_NAME)).unwrap_or_default();
+        let version = match (literal(plugin, &lv2("minorVersion")), literal(plugin, &lv2("microVersion"))) {
+            (minor, _) if minor.is_empty() => String::new(),
+            (minor, micro) => format!("{}.{}", minor, if micro.is_empty() { "0" } else { &micro }),
+        };
+        let instrument = Term::Iri(lv2("InstrumentPlugin"));
+        PluginInfo {
+            format: PluginFormat::Lv2,
+            id: plugin.as_iri().unwrap_or_default().to_string(),
+            name: literal(plugin, DOAP_NAME),
+            vendor,
+            version,
+            path: self.dir.clone(),
+            kind: if self.graph.has(plugin, turtle::RDF_TYPE, &instrument) {
+                PluginKind::Instrument
+            } else {
+                PluginKind::Effect
+            },
+        }
+    }
+
+    fn ports(&self, plugin: &Term) -> PluginResult<Vec<PortInfo>> {
+        let is = |port: &Term, class: &str| self.graph.has(port, turtle::RDF_TYPE, &Term::Iri(class.to_string()));
+        let mut ports: Vec<PortInfo> = self
+            .graph
+            .objects(plugin, &lv2("port"))
+            .map(|port| {
+                let number = |name: &str| self.graph.object(port, &lv2(name)).and_then(Term::as_f32);
+                let input = is(port, &lv2("InputPort"));
+                let kind = if is(port, &lv2("AudioPort")) {
+                    if input { PortKind::AudioIn } else { PortKind::AudioOut }
+                } else if is(port, &lv2("ControlPort")) {
+                    if input { PortKind::ControlIn } else { PortKind::ControlOut }
+                } else if is(port, &lv2("CVPort")) {
+                    PortKind::Cv
+                } else if is(port, &format!("{}AtomPort", ATOM)) {
+                    if input { PortKind::AtomIn } else { PortKind::AtomOut }
+                } else {
+                    PortKind::Unknown
+                };
+                let symbol = self.graph.object(port, &lv2("symbol")).and_then(Term::as_literal).unwrap_or_default();
+                let midi = Term::Iri(MIDI_EVENT.to_string());
+                PortInfo {
+                    index: number("index").map_or(u32::MAX, |i| i as u32),
+                    symbol: symbol.to_string(),
+                    kind,
+                    min: number("minimum"),
+                    max: number("maximum"),
+                    default: number("default"),
+                    optional: self.graph.has(
+                        port,
+                        &lv2("portProperty"),
+                        &Term::Iri(lv2("connectionOptional")),
+                    ),
+                    midi: self.graph.has(port, &format!("{}supports", ATOM), &midi),
+                }
+            })
+            .collect();
+        ports.sort_by_key(|port| port.index);
+        if ports.iter().enumerate().any(|(i, port)| port.index != i as u32) {
+            return Err(PluginError::Load("port indices are not contiguous".into()));
+        }
+        Ok(ports)
+    }
+
+    fn binary(&self, plugin: &Term) -> PluginResult<PathBuf> {
+        self.graph
+            .object(plugin, &lv2("binary"))
+            .and_then(Term::as_iri)
+            .map(file_path)
+            .ok_or_else(|| PluginError::Load(format!("{}: no lv2:binary", self.dir.display())))
+    }
+
+    fn required_features(&self, plugin: &Term) -> Vec<String> {
+        self.graph
+            .objects(plugin, &lv2("requiredFeature"))
+            .filter_map(Term::as_iri)
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+fn file_uri(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
+
+/// Path of a `file://` URI, undoing percent-encoding
+fn file_path(uri: &str) -> PathBuf {
+    let encoded = uri.strip_prefix("file://").unwrap_or(uri).as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let hex = encoded.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (encoded[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                bytes.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                bytes.push(byte);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum PortKind {
+    AudioIn,
+    AudioOut,
+    ControlIn,
+    ControlOut,
+    Cv,
+    AtomIn,
+    AtomOut,
+    Unknown,
+}
+
+#[derive(Clone, Debug)]
+struct PortInfo {
+    index: u32,
+    symbol: String,
+    kind: PortKind,
+    min: Option<f32>,
+    max: Option<f32>,
+    default: Option<f32>,
+    optional: bool,
+    midi: bool,
+}
+
+/// Process-wide URID table; ids are indices + 1 and are never reused, so
+/// plugins may cache them across instances
+static URIDS: Mutex<Vec<CString>> = Mutex::new(Vec::new());
+
+fn urid(uri: &CStr) -> LV2_URID {
+    let mut urids = URIDS.lock().unwrap_or_else(|e| e.into_inner());
+    match urids.iter().position(|u| u.as_c_str() == uri) {
+        Some(index) => index as LV2_URID + 1,
+        None => {
+            urids.push(uri.to_owned());
+            urids.len() as LV2_URID
+        }
+    }
+}
+
+fn urid_of(uri: &str) -> LV2_URID {
+    CString::new(uri).map_or(0, |uri| urid(&uri))
+}
+
+/// The URI `urid` was mapped from
+fn uri_of(urid: LV2_URID) -> Option<String> {
+    let urids = URIDS.lock().unwrap_or_else(|e| e.into_inner());
+    (urid as usize).checked_sub(1).and_then(|i| urids.get(i)).map(|uri| uri.to_string_lossy().into_owned())
+}
+
+unsafe extern "C" fn map_uri(_handle: LV2_URID_Map_Handle, uri: *const c_char) -> LV2_URID {
+    if uri.is_null() {
+        return 0;
+    }
+    // SAFETY: plugins pass null-terminated URIs
+    urid(unsafe { CStr::from_ptr(uri) })
+}
+
+unsafe extern "C" fn unmap_uri(_handle: LV2_URID_Map_Handle, id: LV2_URID) -> *const c_char {
+    let urids = URIDS.lock().unwrap_or_else(|e| e.into_inner());
+    // entries are never dropped, so the pointer outlives the lock
+    (id as usize).checked_sub(1).and_then(|i| urids.get(i)).map_or(ptr::null(), |uri| uri.as_ptr())
+}
+
+/// One value a plugin saved. Keys and types are URIs, since URIDs differ
+/// between processes.
+#[derive(Serialize, Deserialize)]
+struct StateValue {
+    key: String,
+    type_: String,
+    flags: u32,
+    value: Vec<u8>,
+}
+
+unsafe extern "C" fn state_store(
+    handle: LV2_State_Handle,
+    key: LV2_URID,
+    value: *const c_void,
+    size: usize,
+    type_: LV2_URID,
+    flags: u32,
+) -> u32 {
+    // anything else may point into the plugin's memory
+    if flags & LV2_STATE_IS_POD == 0 {
+        return LV2_STATE_ERR_BAD_FLAGS;
+    }
+    let (Some(key), Some(type_)) = (uri_of(key), uri_of(type_)) else { return LV2_STATE_ERR_UNKNOWN };
+    // SAFETY: handle is the Vec being saved into, alive for the save call;
+    // the plugin passes `size` readable bytes
+    let (values, value) = unsafe {
+        (&mut *(handle as *mut Vec<StateValue>), slice::from_raw_parts(value as *const u8, size).to_vec())
+    };
+    // a key stored twice keeps its last value
+    values.retain(|v| v.key != key);
+    values.push(StateValue { key, type_, flags, value });
+    LV2_STATE_SUCCESS
+}
+
+unsafe extern "C" fn state_retrieve(
+    handle: LV2_State_Handle,
+    key: LV2_URID,
+    size: *mut usize,
+    type_: *mut LV2_URID,
+    flags: *mut u32,
+) -> *const c_void {
+    // SAFETY: handle is the values being restored, with their keys mapped,
+    // alive for the restore call
+    let values = unsafe { &*(handle as *const Vec<(LV2_URID, StateValue)>) };
+    let Some((_, value)) = values.iter().find(|(k, _)| *k == key) else { return ptr::null() };
+    // SAFETY: out pointers from the plugin, skipped when null
+    unsafe {
+        if !size.is_null() {
+            *size = value.value.len();
+        }
+        if !type_.is_null() {
+            *type_ = urid_of(&value.type_);
+        }
+        if !flags.is_null() {
+            *flags = value.flags;
+        }
+    }
+    value.value.as_ptr() as *const c_void
+}
+
+/// Option values, boxed so `LV2_Options_Option::value` can point into them
+struct OptionValues {
+    max_block: i32,
+    min_block: i32,
+    nominal_block: i32,
+    sample_rate: f32,
+}
+
+/// Everything handed to `instantiate`; plugins may keep the pointers for
+/// their whole life, so nothing here moves or drops before `cleanup`
+struct HostFeatures {
+    _map: Box<LV2_URID_Map>,
+    _unmap: Box<LV2_URID_Unmap>,
+    _values: Box<OptionValues>,
+    _options: Vec<LV2_Options_Option>,
+    _features: Vec<LV2_Feature>,
+    /// Null-terminated
+    pointers: Vec<*const LV2_Feature>,
+}
+
+impl HostFeatures {
+    fn new(sample_rate: f32, max_frames: usize) -> Self {
+        let mut map = Box::new(LV2_URID_Map { handle: ptr::null_mut(), map: map_uri });
+        let mut unmap = Box::new(LV2_URID_Unmap { handle: ptr::null_mut(), unmap: unmap_uri });
+        let values = Box::new(OptionValues {
+            max_block: max_frames as i32,
+            min_block: 1,
+            nominal_block: max_frames as i32,
+            sample_rate,
+        });
+        let int_type = urid_of(&format!("{}Int", ATOM));
+        let option = |key: &str, type_: LV2_URID, value: *const c_void| LV2_Options_Option {
+            context: LV2_OPTIONS_INSTANCE,
+            subject: 0,
+            key: urid_of(key),
+            size: 4,
+            type_,
+            value,
+        };
+        let mut options = vec![
+            option(&format!("{}maxBlockLength", BUF_SIZE), int_type, &values.max_block as *const i32 as _),
+            option(&format!("{}minBlockLength", BUF_SIZE), int_type, &values.min_block as *const i32 as _),
+            option(&format!("{}nominalBlockLength", BUF_SIZE), int_type, &values.nominal_block as *const i32 as _),
+            option(PARAM_SAMPLE_RATE, urid_of(&format!("{}Float", ATOM)), &values.sample_rate as *const f32 as _),
+        ];
+        options.push(LV2_Options_Option { context: 0, subject: 0, key: 0, size: 0, type_: 0, value: ptr::null() });
+
+        let features = vec![
+            LV2_Feature { URI: c"http://lv2plug.in/ns/ext/urid#map".as_ptr(), data: &mut *map as *mut _ as *mut c_void },
+            LV2_Feature {
+                URI: c"http://lv2plug.in/ns/ext/urid#unmap".as_ptr(),
+                data: &mut *unmap as *mut _ as *mut c_void,
+            },
+            LV2_Feature {
+                URI: c"http://lv2plug.in/ns/ext/options#options".as_ptr(),
+                data: options.as_mut_ptr() as *mut c_void,
+            },
+            LV2_Feature { URI: c"http://lv2plug.in/ns/ext/buf-size#boundedBlockLength".as_ptr(), data: ptr::null_mut() },
+        ];
+        let mut pointers: Vec<*const LV2_Feature> = features.iter().map(|f| f as *const LV2_Feature).collect();
+        pointers.push(ptr::null());
+
+        Self { _map: map, _unmap: unmap, _values: values, _options: options, _features: features, pointers }
+    }
+}
+
+/// An atom port buffer, 8-byte aligned as atoms require
+struct AtomBuffer {
+    port: u32,
+    words: Vec<u64>,
+}
+
+impl AtomBuffer {
+    fn new(port: u32) -> Self {
+        Self { port, words: vec![0; ATOM_CAPACITY / 8] }
+    }
+
+    fn sequence(&mut self) -> &mut LV2_Atom_Sequence {
+        // SAFETY: the buffer is larger than and aligned for a sequence header
+        unsafe { &mut *(self.words.as_mut_ptr() as *mut LV2_Atom_Sequence) }
+    }
+
+    /// Input side: an empty sequence with frame timestamps
+    fn clear_sequence(&mut self, sequence_type: LV2_URID) {
+        let sequence = self.sequence();
+        sequence.atom = LV2_Atom { size: size_of::<LV2_Atom_Sequence_Body>() as u32, type_: sequence_type };
+        sequence.body = LV2_Atom_Sequence_Body { unit: 0, pad: 0 };
+    }
+
+    /// Append a MIDI message at frame 0; dropped if the buffer is full
+    fn push_midi(&mut self, midi_type: LV2_URID, message: [u8; 3]) {
+        let event_bytes = size_of::<LV2_Atom_Event>() + 8; // 3 bytes padded to 8
+        let offset = size_of::<LV2_Atom>() + self.sequence().atom.size as usize;
+        if offset + event_bytes > ATOM_CAPACITY {
+            return;
+        }
+        // SAFETY: offset + event_bytes is within the buffer and 8-byte aligned
+        unsafe {
+            let event = (self.words.as_mut_ptr() as *mut u8).add(offset);
+            (event as *mut LV2_Atom_Event).write(LV2_Atom_Event { time: 0, body: LV2_Atom { size: 3, type_: midi_type } });
+            let data = event.add(size_of::<LV2_Atom_Event>());
+            ptr::copy_nonoverlapping(message.as_ptr(), data, 3);
+            ptr::write_bytes(data.add(3), 0, 5);
+        }
+        self.sequence().atom.size += event_bytes as u32;
+    }
+
+    /// Output side: tell the plugin how much room it has
+    fn prepare_output(&mut self, chunk_type: LV2_URID) {
+        self.sequence().atom = LV2_Atom { size: (ATOM_CAPACITY - size_of::<LV2_Atom>()) as u32, type_: chunk_type };
+    }
+
+    fn as_ptr(&mut self) -> *mut c_void {
+        self.words.as_mut_ptr() as *mut c_void
+    }
+}
+
+/// An instantiated, activated LV2 plugin
+pub struct Lv2Plugin {
+    descriptor: *const LV2_Descriptor,
+    handle: LV2_Handle,
+    info: PluginInfo,
+    params: Vec<ParamInfo>,
+    max_frames: usize,
+    /// Control port values by port index; input and output controls point here
+    controls: Vec<f32>,
+    audio_inputs: Vec<u32>,
+    audio_outputs: Vec<u32>,
+    /// One block per CV port: silence in, scratch out
+    cv: Vec<f32>,
+    atom_inputs: Vec<AtomBuffer>,
+    atom_outputs: Vec<AtomBuffer>,
+    /// Index in `atom_inputs` that receives notes
+    midi_input: Option<usize>,
+    sequence_type: LV2_URID,
+    chunk_type: LV2_URID,
+    midi_type: LV2_URID,
+    active: bool,
+    // must outlive the instance
+    features: HostFeatures,
+    _library: Library,
+}
+
+// SAFETY: the instance is only touched through &mut self, from one thread at
+// a time; LV2 separates instantiation-class and audio-class calls but allows
+// them on different threads.
+unsafe impl Send for Lv2Plugin {}
+unsafe impl Sync for Lv2Plugin {}
+
+impl Lv2Plugin {
+    /// Instantiate and activate the plugin `uri` from the bundle directory `bundle`
+    pub fn load(bundle: impl AsRef<Path>, uri: &str, sample_rate: f32, max_frames: usize) -> PluginResult<Self> {
+        let bundle = Bundle::read(bundle.as_ref())?;
+        let plugin = bundle
+            .plugins()
+            .into_iter()
+            .find(|plugin| plugin.as_iri() == Some(uri))
+            .ok_or_else(|| PluginError::NotFound(uri.to_string()))?;
+        let info = bundle.info(plugin);
+        let ports = bundle.ports(plugin)?;
+        if let Some(feature) = bundle.required_features(plugin).iter().find(|f| !SUPPORTED_FEATURES.contains(&f.as_str())) {
+            return Err(PluginError::Unsupported(format!("{} requires {}", uri, feature)));
+        }
+        if let Some(port) = ports.iter().find(|port| port.kind == PortKind::Unknown && !port.optional) {
+            return Err(PluginError::Unsupported(format!("{}: unknown type of port {}", uri, port.symbol)));
+        }
+
+        let binary = bundle.binary(plugin)?;
+        let load_err = |e: libloading::Error| PluginError::Load(format!("{}: {}", binary.display(), e));
+        // SAFETY: loading runs the library's initializers; that is the point
+        // of hosting it
+        let library = unsafe { Library::new(&binary) }.map_err(load_err)?;
+        // SAFETY: the LV2 entry point has exactly this signature
+        let entry = unsafe { *library.get::<LV2_Descriptor_Function>(b"lv2_descriptor\0").map_err(load_err)? };
+        let descriptor = (0..)
+            // SAFETY: plugins return null past their last descriptor
+            .map(|index| unsafe { entry(index) })
+            .take_while(|descriptor| !descriptor.is_null())
+            .find(|&descriptor| c_str_eq(unsafe { (*descriptor).URI }, uri))
+            .ok_or_else(|| PluginError::NotFound(uri.to_string()))?;
+
+        let features = HostFeatures::new(sample_rate, max_frames);
+        let bundle_path = CString::new(format!("{}/", bundle.dir.display()))
+            .map_err(|_| PluginError::Load(format!("{}: invalid path", bundle.dir.display())))?;
+        // SAFETY: features stay alive (and in place) until cleanup
+        let handle = unsafe {
+            ((*descriptor).instantiate)(descriptor, sample_rate as f64, bundle_path.as_ptr(), features.pointers.as_ptr())
+        };
+        if handle.is_null() {
+            return Err(PluginError::Init(format!("{}: instantiate failed", uri)));
+        }
+
+        let mut instance = Self {
+            descriptor,
+            handle,
+            params: Vec::new(),
+            max_frames,
+            controls: ports.iter().map(|port| port.default.or(port.min).unwrap_or(0.0)).collect(),
+            audio_inputs: ports.iter().filter(|p| p.kind == PortKind::AudioIn).map(|p| p.index).collect(),
+            audio_outputs: ports.iter().filter(|p| p.kind == PortKind::AudioOut).map(|p| p.index).collect(),
+            cv: vec![0.0; ports.iter().filter(|p| p.kind == PortKind::Cv).count() * max_frames],
+            atom_inputs: ports.iter().filter(|p| p.kind == PortKind::AtomIn).map(|p| AtomBuffer::new(p.index)).collect(),
+            atom_outputs: ports.iter().filter(|p| p.kind == PortKind::AtomOut).map(|p| AtomBuffer::new(p.index)).collect(),
+            midi_input: None,
+            sequence_type: urid_of(&format!("{}Sequence", ATOM)),
+            chunk_type: urid_of(&format!("{}Chunk", ATOM)),
+            midi_type: urid_of(MIDI_EVENT),
+            active: false,
+            features,
+            _library: library,
+            info,
+        };
+        // from here on, Drop cleans the instance up
+        instance.params = ports
+            .iter()
+            .filter(|port| port.kind == PortKind::ControlIn)
+            .map(|port| {
+                let default = instance.controls[port.index as usize];
+                ParamInfo {
+                    id: port.index,
+                    name: port.symbol.clone(),
+                    min: port.min.unwrap_or(f32::MIN) as f64,
+                    max: port.max.unwrap_or(f32::MAX) as f64,
+                    default: default as f64,
+                }
+            })
+            .collect();
+        let midi_port = ports.iter().find(|p| p.kind == PortKind::AtomIn && p.midi).map(|p| p.index);
+        instance.midi_input = instance.atom_inputs.iter().position(|atom| Some(atom.port) == midi_port);
+        if instance.audio_inputs.len() > MAX_PLUGIN_CHANNELS || instance.audio_outputs.len() > MAX_PLUGIN_CHANNELS {
+            return Err(PluginError::Unsupported(format!("{}: more than {} audio ports", uri, MAX_PLUGIN_CHANNELS)));
+        }
+        instance.connect_fixed(&ports);
+
+        // SAFETY: instantiated and connected
+        unsafe {
+            if let Some(activate) = (*descriptor).activate {
+                activate(handle);
+            }
+        }
+        instance.active = true;
+        Ok(instance)
+    }
+
+    /// Connect every port whose buffer never moves: controls, CV and atoms
+    fn connect_fixed(&mut self, ports: &[PortInfo]) {
+        let (descriptor, handle) = (self.descriptor, self.handle);
+        let connect = |port: u32, data: *mut c_void| {
+            // SAFETY: buffers are owned by self and outlive the instance
+            unsafe { ((*descriptor).connect_port)(handle, port, data) }
+        };
+        let controls = self.controls.as_mut_ptr();
+        let mut cv = self.cv.chunks_mut(self.max_frames.max(1));
+        for port in ports {
+            match port.kind {
+                PortKind::ControlIn | PortKind::ControlOut => {
+                    // SAFETY: one slot per port index
+                    connect(port.index, unsafe { controls.add(port.index as usize) } as *mut c_void)
+                }
+                PortKind::Cv => {
+                    if let Some(block) = cv.next() {
+                        connect(port.index, block.as_mut_ptr() as *mut c_void);
+                    }
+                }
+                // optional ports of unknown type stay unconnected (null)
+                PortKind::Unknown => connect(port.index, ptr::null_mut()),
+                _ => {}
+            }
+        }
+        for atom in self.atom_inputs.iter_mut().chain(self.atom_outputs.iter_mut()) {
+            connect(atom.port, atom.as_ptr());
+        }
+    }
+
+    fn state_interface(&self) -> Option<&LV2_State_Interface> {
+        // SAFETY: the descriptor lives as long as the library
+        let extension_data = unsafe { (*self.descriptor).extension_data }?;
+        // SAFETY: extension data are static tables owned by the plugin
+        let ext = unsafe { extension_data(STATE_INTERFACE.as_ptr()) };
+        (!ext.is_null()).then(|| unsafe { &*(ext as *const LV2_State_Interface) })
+    }
+}
+
+fn c_str_eq(ptr: *const c_char, s: &str) -> bool {
+    // SAFETY: descriptor URIs are null-terminated
+    !ptr.is_null() && unsafe { CStr::from_ptr(ptr) }.to_bytes() == s.as_bytes()
+}
+
+impl PluginProcessor for Lv2Plugin {
+    fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    fn params(&self) -> &[ParamInfo] {
+        &self.params
+    }
+
+    fn input_channels(&self) -> usize {
+        self.audio_inputs.len()
+    }
+
+    fn output_channels(&self) -> usize {
+        self.audio_outputs.len()
+    }
+
+    fn process(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], frames: usize, events: &[PluginEvent]) {
+        let frames = frames.min(self.max_frames);
+        if inputs.len() < self.audio_inputs.len() || outputs.len() < self.audio_outputs.len() {
+            for channel in outputs.iter_mut() {
+                channel[..frames].fill(0.0);
+            }
+            return;
+        }
+
+        for atom in &mut self.atom_inputs {
+            atom.clear_sequence(self.sequence_type);
+        }
+        for atom in &mut self.atom_outputs {
+            atom.prepare_output(self.chunk_type);
+        }
+        for event in events {
+            match *event {
+                PluginEvent::Param { id, value } => {
+                    if let Some(param) = self.params.iter().find(|p| p.id == id) {
+                        self.controls[id as usize] = value.clamp(param.min, param.max) as f32;
+                    }
+                }
+                PluginEvent::Note(note) => {
+                    if let Some(atom) = self.midi_input.map(|i| &mut self.atom_inputs[i]) {
+                        let message = match note {
+                            NoteEvent::NoteOn { note, velocity } => {
+                                [0x90, note & 0x7f, (velocity.clamp(0.0, 1.0) * 127.0).round().max(1.0) as u8]
+                            }
+                            NoteEvent::NoteOff { note } => [0x80, note & 0x7f, 0],
+                        };
+                        atom.push_midi(self.midi_type, message);
+                    }
+                }
+            }
+        }
+
+        // SAFETY: audio buffers cover `frames` samples and stay valid for the
+        // run call; the plugin only reads inputs
+        unsafe {
+            let connect = (*self.descriptor).connect_port;
+            for (&port, input) in self.audio_inputs.iter().zip(inputs) {
+                connect(self.handle, port, input.as_ptr() as *mut c_void);
+            }
+            for (&port, output) in self.audio_outputs.iter().zip(outputs.iter_mut()) {
+                connect(self.handle, port, output.as_mut_ptr() as *mut c_void);
+            }
+            ((*self.descriptor).run)(self.handle, frames as u32);
+        }
+    }
+
+    fn save_state(&mut self) -> Option<Vec<u8>> {
+        let state = self.state_interface()?;
+        let mut values: Vec<StateValue> = Vec::new();
+        let handle = &mut values as *mut Vec<StateValue> as LV2_State_Handle;
+        let flags = LV2_STATE_IS_POD | LV2_STATE_IS_PORTABLE;
+        // SAFETY: instantiation-class call; `values` outlives it
+        let status = unsafe { (state.save)(self.handle, state_store, handle, flags, self.features.pointers.as_ptr()) };
+        if status != LV2_STATE_SUCCESS {
+            return None;
+        }
+        ron::to_string(&values).ok().map(String::into_bytes)
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> PluginResult<()> {
+        let Some(interface) = self.state_interface() else {
+            return Err(PluginError::Unsupported(format!("{}: no state interface", self.info.id)));
+        };
+        let values: Vec<StateValue> = std::str::from_utf8(state)
+            .ok()
+            .and_then(|text| ron::from_str(text).ok())
+            .ok_or_else(|| PluginError::State(format!("{}: not an LV2 state", self.info.id)))?;
+        let values: Vec<(LV2_URID, StateValue)> = values.into_iter().map(|v| (urid_of(&v.key), v)).collect();
+        let handle = &values as *const Vec<(LV2_URID, StateValue)> as LV2_State_Handle;
+        let flags = LV2_STATE_IS_POD | LV2_STATE_IS_PORTABLE;
+        // SAFETY: instantiation-class call; `values` outlives it
+        let status =
+            unsafe { (interface.restore)(self.handle, state_retrieve, handle, flags, self.features.pointers.as_ptr()) };
+        if status == LV2_STATE_SUCCESS {
+            Ok(())
+        } else {
+            Err(PluginError::State(format!("{}: rejected by the plugin (status {})", self.info.id, status)))
+        }
+    }
+}
+
+impl Drop for Lv2Plugin {
+    fn drop(&mut self) {
+        // SAFETY: tears down in reverse order of setup
+        unsafe {
+            if self.active
+                && let Some(deactivate) = (*self.descriptor).deactivate
+            {
+                deactivate(self.handle);
+            }
+            ((*self.descriptor).cleanup)(self.handle);
+        }
+    }
+}
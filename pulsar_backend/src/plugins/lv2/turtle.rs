@@ -0,0 +1,407 @@
+//! Minimal Turtle reader for LV2 bundle descriptions.
+//!
+//! Covers what bundles use in practice: prefixes and base, IRIs, prefixed
+//! names, `a`, predicate/object lists, blank node property lists,
+//! collections, and string/number/boolean literals (datatypes and language
+//! tags are dropped). Relative IRIs resolve against the file's URI.
+
+use std::fmt;
+
+const RDF_FIRST: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#first";
+const RDF_REST: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#rest";
+const RDF_NIL: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#nil";
+pub const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Term {
+    Iri(String),
+    Blank(usize),
+    /// Literal text, numbers included
+    Literal(String),
+}
+
+impl Term {
+    pub fn as_iri(&self) -> Option<&str> {
+        match self {
+            Term::Iri(iri) => Some(iri),
+            _ => None,
+        }
+    }
+
+    pub fn as_literal(&self) -> Option<&str> {
+        match self {
+            Term::Literal(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        self.as_literal()?.trim().parse().ok()
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Triples from any number of documents
+#[derive(Default)]
+pub struct Graph {
+    triples: Vec<(Term, String, Term)>,
+    blank_nodes: usize,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the triples in `text`, a document located at `base` (a `file://` URI)
+    pub fn parse(&mut self, text: &str, base: &str) -> Result<(), ParseError> {
+        Parser { graph: self, chars: text.chars().collect(), pos: 0, line: 1, base: base.to_string(), prefixes: Vec::new() }
+            .document()
+    }
+
+    pub fn objects<'a>(&'a self, subject: &'a Term, predicate: &'a str) -> impl Iterator<Item = &'a Term> + 'a {
+        self.triples.iter().filter(move |(s, p, _)| s == subject && p == predicate).map(|(_, _, o)| o)
+    }
+
+    pub fn object<'a>(&'a self, subject: &Term, predicate: &str) -> Option<&'a Term> {
+        self.triples.iter().find(|(s, p, _)| s == subject && p == predicate).map(|(_, _, o)| o)
+    }
+
+    pub fn has(&self, subject: &Term, predicate: &str, object: &Term) -> bool {
+        self.objects(subject, predicate).any(|o| o == object)
+    }
+
+    /// Every subject with `rdf:type` `class`
+    pub fn instances<'a>(&'a self, class: &str) -> impl Iterator<Item = &'a Term> + 'a {
+        let class = class.to_string();
+        self.triples
+            .iter()
+            .filter(move |(_, p, o)| p == RDF_TYPE && o.as_iri() == Some(class.as_str()))
+            .map(|(s, _, _)| s)
+    }
+
+    fn blank(&mut self) -> Term {
+        self.blank_nodes += 1;
+        Term::Blank(self.blank_nodes)
+    }
+}
+
+struct Parser<'g> {
+    graph: &'g mut Graph,
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    base: String,
+    prefixes: Vec<(String, String)>,
+}
+
+impl Parser<'_> {
+    fn document(&mut self) -> Result<(), ParseError> {
+        loop {
+            self.skip_space();
+            let Some(c) = self.peek() else { return Ok(()) };
+            if c == '@' {
+                self.pos += 1;
+                let keyword = self.word();
+                self.directive(&keyword)?;
+                self.expect('.')?;
+            } else if self.at_keyword("PREFIX") || self.at_keyword("BASE") {
+                let keyword = self.word().to_ascii_lowercase();
+                self.directive(&keyword)?;
+            } else {
+                let subject = self.subject()?;
+                self.skip_space();
+                // `[ ... ] .` on its own is a complete statement
+                if self.peek() != Some('.') {
+                    self.predicate_objects(&subject)?;
+                }
+                self.expect('.')?;
+            }
+        }
+    }
+
+    fn directive(&mut self, keyword: &str) -> Result<(), ParseError> {
+        self.skip_space();
+        match keyword {
+            "prefix" => {
+                let name = self.until(':');
+                self.pos += 1;
+                self.skip_space();
+                let iri = self.iri_ref()?;
+                self.prefixes.push((name, iri));
+                Ok(())
+            }
+            "base" => {
+                self.base = self.iri_ref()?;
+                Ok(())
+            }
+            _ => Err(self.error(format!("unknown directive @{}", keyword))),
+        }
+    }
+
+    fn subject(&mut self) -> Result<Term, ParseError> {
+        match self.peek() {
+            Some('[') => self.blank_node(),
+            Some('(') => self.collection(),
+            _ => self.iri().map(Term::Iri),
+        }
+    }
+
+    fn predicate_objects(&mut self, subject: &Term) -> Result<(), ParseError> {
+        loop {
+            self.skip_space();
+            let predicate = if self.peek() == Some('a') && self.peek_at(1).is_none_or(|c| c.is_whitespace()) {
+                self.pos += 1;
+                RDF_TYPE.to_string()
+            } else {
+                self.iri()?
+            };
+            loop {
+                let object = self.object()?;
+                self.graph.triples.push((subject.clone(), predicate.clone(), object));
+                self.skip_space();
+                if self.peek() != Some(',') {
+                    break;
+                }
+                self.pos += 1;
+            }
+            // `;` may repeat and may trail before `.` or `]`
+            let mut more = false;
+            while self.peek() == Some(';') {
+                self.pos += 1;
+                self.skip_space();
+                more = true;
+            }
+            if !more || matches!(self.peek(), Some('.' | ']') | None) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Term, ParseError> {
+        self.skip_space();
+        match self.peek() {
+            Some('[') => self.blank_node(),
+            Some('(') => self.collection(),
+            Some('"' | '\'') => self.string(),
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => Ok(Term::Literal(self.number())),
+            _ if self.at_keyword("true") || self.at_keyword("false") => Ok(Term::Literal(self.word())),
+            _ => self.iri().map(Term::Iri),
+        }
+    }
+
+    fn blank_node(&mut self) -> Result<Term, ParseError> {
+        self.expect('[')?;
+        let node = self.graph.blank();
+        self.skip_space();
+        if self.peek() != Some(']') {
+            self.predicate_objects(&node)?;
+        }
+        self.expect(']')?;
+        Ok(node)
+    }
+
+    fn collection(&mut self) -> Result<Term, ParseError> {
+        self.expect('(')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_space();
+            if self.peek() == Some(')') {
+                self.pos += 1;
+                break;
+            }
+            items.push(self.object()?);
+        }
+        let mut list = Term::Iri(RDF_NIL.to_string());
+        for item in items.into_iter().rev() {
+            let node = self.graph.blank();
+            self.graph.triples.push((node.clone(), RDF_FIRST.to_string(), item));
+            self.graph.triples.push((node.clone(), RDF_REST.to_string(), list));
+            list = node;
+        }
+        Ok(list)
+    }
+
+    fn string(&mut self) -> Result<Term, ParseError> {
+        let quote = self.peek().unwrap_or('"');
+        let long = self.peek_at(1) == Some(quote) && self.peek_at(2) == Some(quote);
+        self.pos += if long { 3 } else { 1 };
+
+        // reported where the string opens, not at the end of the file
+        let unterminated = ParseError { line: self.line, message: "unterminated string".into() };
+        let mut text = String::new();
+        loop {
+            let Some(c) = self.next() else { return Err(unterminated) };
+            if c == quote {
+                if !long {
+                    break;
+                }
+                if self.peek() == Some(quote) && self.peek_at(1) == Some(quote) {
+                    self.pos += 2;
+                    break;
+                }
+            }
+            if c == '\\' {
+                match self.next() {
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some('r') => text.push('\r'),
+                    Some(other) => text.push(other),
+                    None => return Err(unterminated),
+                }
+            } else {
+                text.push(c);
+            }
+        }
+
+        // language tag or datatype
+        if self.peek() == Some('@') {
+            self.pos += 1;
+            self.word();
+        } else if self.peek() == Some('^') && self.peek_at(1) == Some('^') {
+            self.pos += 2;
+            self.iri()?;
+        }
+        Ok(Term::Literal(text))
+    }
+
+    fn number(&mut self) -> String {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            let exponent_sign = (c == '-' || c == '+') && matches!(self.chars.get(self.pos - 1), Some('e' | 'E'));
+            let decimal_point = c == '.' && self.peek_at(1).is_some_and(|n| n.is_ascii_digit());
+            if c.is_ascii_digit() || c == 'e' || c == 'E' || decimal_point || exponent_sign || self.pos == start {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    /// An IRI reference or prefixed name, resolved to a full IRI
+    fn iri(&mut self) -> Result<String, ParseError> {
+        self.skip_space();
+        if self.peek() == Some('<') {
+            return self.iri_ref();
+        }
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            let dot_inside = c == '.' && self.peek_at(1).is_some_and(|n| n.is_alphanumeric() || n == '_');
+            if c.is_alphanumeric() || matches!(c, ':' | '_' | '-' | '%') || dot_inside {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let name: String = self.chars[start..self.pos].iter().collect();
+        let Some((prefix, local)) = name.split_once(':') else {
+            return Err(self.error(format!("expected an IRI, found {:?}", name)));
+        };
+        match self.prefixes.iter().rev().find(|(p, _)| p == prefix) {
+            Some((_, namespace)) => Ok(format!("{}{}", namespace, local)),
+            None => Err(self.error(format!("undefined prefix {:?}", prefix))),
+        }
+    }
+
+    fn iri_ref(&mut self) -> Result<String, ParseError> {
+        self.expect('<')?;
+        let iri = self.until('>');
+        self.expect('>')?;
+        Ok(resolve(&self.base, &iri))
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseError> {
+        self.skip_space();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", c)))
+        }
+    }
+
+    fn skip_space(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.pos += 1;
+                }
+            } else if c.is_whitespace() {
+                self.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        let end = self.pos + keyword.len();
+        end <= self.chars.len()
+            && self.chars[self.pos..end].iter().copied().eq(keyword.chars())
+            && self.chars.get(end).is_none_or(|c| !c.is_alphanumeric() && *c != ':')
+    }
+
+    fn word(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '-') {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn until(&mut self, end: char) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c != end) {
+            self.next();
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        ParseError { line: self.line, message }
+    }
+}
+
+/// Resolve `iri` against `base`; only the forms bundles use (absolute,
+/// empty, fragment, and paths relative to the base's directory)
+fn resolve(base: &str, iri: &str) -> String {
+    if iri.contains("://") || iri.starts_with("urn:") {
+        iri.to_string()
+    } else if iri.is_empty() {
+        base.to_string()
+    } else if iri.starts_with('#') {
+        format!("{}{}", base.split('#').next().unwrap_or(base), iri)
+    } else {
+        let dir = base.rfind('/').map_or(base, |end| &base[..=end]);
+        format!("{}{}", dir, iri)
+    }
+}
@@ -0,0 +1,173 @@
+pub mod bridge;
+pub mod clap;
+#[cfg(target_os = "linux")]
+pub mod lv2;
+pub mod node;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crossbeam::channel::{self, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+
+use crate::rt_processing::notes::NoteEvent;
+
+pub use bridge::{BridgeConfig, BridgeHandle, BridgedPlugin};
+pub use node::PluginNode;
+
+/// Events queued for a plugin between two blocks
+pub const EVENT_CAPACITY: usize = 512;
+/// Most audio channels a plugin node passes in or out
+pub const MAX_PLUGIN_CHANNELS: usize = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PluginFormat {
+    Clap,
+    Lv2,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PluginKind {
+    /// Processes the audio it is given
+    Effect,
+    /// Generates audio from note events
+    Instrument,
+}
+
+/// A plugin found by a scan
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PluginInfo {
+    pub format: PluginFormat,
+    /// Format-specific unique id (reverse-DNS for CLAP, the plugin URI for LV2)
+    pub id: String,
+    pub name: String,
+    pub vendor: String,
+    pub version: String,
+    /// The file the plugin is loaded from (the bundle directory for LV2)
+    pub path: PathBuf,
+    pub kind: PluginKind,
+}
+
+/// A plugin parameter. Values are in the plugin's own range; for LV2, `id`
+/// is the control port index and `name` its symbol.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ParamInfo {
+    pub id: u32,
+    pub name: String,
+    pub min: f64,
+    pub max: f64,
+    pub default: f64,
+}
+
+#[derive(Debug)]
+pub enum PluginError {
+    /// The library could not be opened or lacks the format's entry point
+    Load(String),
+    /// No plugin with this id in the library
+    NotFound(String),
+    /// The plugin refused to initialize or activate
+    Init(String),
+    /// The plugin needs something this host doesn't provide
+    Unsupported(String),
+    /// The plugin's state could not be saved or restored
+    State(String),
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Load(msg) => write!(f, "Cannot load plugin: {}", msg),
+            Self::NotFound(id) => write!(f, "Plugin not found: {}", id),
+            Self::Init(msg) => write!(f, "Plugin failed to start: {}", msg),
+            Self::Unsupported(msg) => write!(f, "Unsupported plugin: {}", msg),
+            Self::State(msg) => write!(f, "Plugin state not restored: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+pub type PluginResult<T> = Result<T, PluginError>;
+
+/// Event delivered to a plugin at the start of the next block
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PluginEvent {
+    Param { id: u32, value: f64 },
+    Note(NoteEvent),
+}
+
+/// A loaded, activated plugin instance, independent of its format.
+///
+/// Created on a control thread; `process` runs on the audio thread and must
+/// not block or allocate. Blocks are at most the `max_frames` the instance was
+/// activated with.
+pub trait PluginProcessor: Send + Sync {
+    fn info(&self) -> &PluginInfo;
+
+    fn params(&self) -> &[ParamInfo];
+
+    fn input_channels(&self) -> usize;
+
+    fn output_channels(&self) -> usize;
+
+    /// Render one block. `events` apply from the first frame.
+    fn process(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], frames: usize, events: &[PluginEvent]);
+
+    /// Clear internal state (tails, held notes)
+    fn reset(&mut self) {}
+
+    /// What the plugin keeps beyond its parameter values (loaded samples,
+    /// internal settings), as an opaque blob for `load_state`; `None` if it
+    /// has none. Not RT-safe: call it on a control thread, between blocks.
+    fn save_state(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Restore a blob from `save_state`. Not RT-safe, as above.
+    fn load_state(&mut self, _state: &[u8]) -> PluginResult<()> {
+        Err(PluginError::Unsupported(format!("{}: no plugin state", self.info().id)))
+    }
+}
+
+/// Load and activate a plugin in this process. `path` is the `.clap` file
+/// for CLAP and the bundle directory for LV2.
+pub fn load(
+    format: PluginFormat,
+    path: &Path,
+    id: &str,
+    sample_rate: f32,
+    max_frames: usize,
+) -> PluginResult<Box<dyn PluginProcessor>> {
+    match format {
+        PluginFormat::Clap => Ok(Box::new(clap::ClapPlugin::load(path, id, sample_rate, max_frames)?)),
+        #[cfg(target_os = "linux")]
+        PluginFormat::Lv2 => Ok(Box::new(lv2::Lv2Plugin::load(path, id, sample_rate, max_frames)?)),
+        #[cfg(not(target_os = "linux"))]
+        PluginFormat::Lv2 => Err(PluginError::Unsupported("LV2 is only hosted on Linux".into())),
+    }
+}
+
+/// Control-thread handle for feeding parameter changes and notes to a
+/// running plugin node. Never blocks; events beyond `EVENT_CAPACITY` per
+/// block are dropped.
+#[derive(Clone)]
+pub struct PluginControl {
+    sender: Sender<PluginEvent>,
+}
+
+impl PluginControl {
+    fn new() -> (Self, Receiver<PluginEvent>) {
+        let (sender, receiver) = channel::bounded(EVENT_CAPACITY);
+        (Self { sender }, receiver)
+    }
+
+    /// Returns `false` if the queue was full
+    pub fn set_param(&self, id: u32, value: f64) -> bool {
+        self.sender.try_send(PluginEvent::Param { id, value }).is_ok()
+    }
+
+    /// Returns `false` if the queue was full
+    pub fn send_note(&self, event: NoteEvent) -> bool {
+        self.sender.try_send(PluginEvent::Note(event)).is_ok()
+    }
+}
@@ -0,0 +1,116 @@
+use crossbeam::channel::Receiver;
+
+use crate::rt_processing::effects::Effect;
+use crate::rt_processing::routing::AudioSource;
+
+use super::{EVENT_CAPACITY, MAX_PLUGIN_CHANNELS, PluginControl, PluginEvent, PluginProcessor};
+
+/// Graph node around a plugin instance: an insert effect on a bus chain, or a
+/// source when the plugin is an instrument.
+///
+/// As an effect, engine channels beyond the plugin's inputs are dropped and
+/// plugin inputs beyond the engine's wrap around (a stereo plugin on a mono
+/// bus gets the same signal twice); outputs map back the same way. As a
+/// source, the plugin's inputs are silent.
+pub struct PluginNode {
+    plugin: Box<dyn PluginProcessor>,
+    control: PluginControl,
+    events: Receiver<PluginEvent>,
+    pending: Vec<PluginEvent>,
+    max_frames: usize,
+    inputs: Vec<f32>,
+    outputs: Vec<f32>,
+}
+
+impl PluginNode {
+    /// `max_frames` must match what the plugin was activated with
+    pub fn new(plugin: Box<dyn PluginProcessor>, max_frames: usize) -> Self {
+        let (control, events) = PluginControl::new();
+        let inputs = plugin.input_channels().min(MAX_PLUGIN_CHANNELS);
+        let outputs = plugin.output_channels().min(MAX_PLUGIN_CHANNELS);
+        Self {
+            plugin,
+            control,
+            events,
+            pending: Vec::with_capacity(EVENT_CAPACITY),
+            max_frames,
+            inputs: vec![0.0; inputs * max_frames],
+            outputs: vec![0.0; outputs * max_frames],
+        }
+    }
+
+    pub fn plugin(&self) -> &dyn PluginProcessor {
+        self.plugin.as_ref()
+    }
+
+    /// Handle for changing parameters and playing notes while the node runs
+    pub fn control(&self) -> PluginControl {
+        self.control.clone()
+    }
+
+    /// Run the plugin over `frames` of the scratch inputs into the scratch outputs
+    fn run(&mut self, frames: usize) {
+        self.pending.clear();
+        while self.pending.len() < EVENT_CAPACITY {
+            match self.events.try_recv() {
+                Ok(event) => self.pending.push(event),
+                Err(_) => break,
+            }
+        }
+
+        let mut inputs: [&[f32]; MAX_PLUGIN_CHANNELS] = Default::default();
+        let input_count = self.inputs.len() / self.max_frames.max(1);
+        for (view, channel) in inputs.iter_mut().zip(self.inputs.chunks(self.max_frames)) {
+            *view = &channel[..frames];
+        }
+        let mut outputs: [&mut [f32]; MAX_PLUGIN_CHANNELS] = Default::default();
+        let output_count = self.outputs.len() / self.max_frames.max(1);
+        for (view, channel) in outputs.iter_mut().zip(self.outputs.chunks_mut(self.max_frames)) {
+            *view = &mut channel[..frames];
+        }
+        self.plugin.process(&inputs[..input_count], &mut outputs[..output_count], frames, &self.pending);
+    }
+}
+
+impl Effect for PluginNode {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        if buffer.is_empty() || self.outputs.is_empty() {
+            return;
+        }
+        let frames = frames.min(self.max_frames);
+        for (ch, input) in self.inputs.chunks_mut(self.max_frames).enumerate() {
+            input[..frames].copy_from_slice(&buffer[ch % buffer.len()][..frames]);
+        }
+        self.run(frames);
+
+        let output_count = self.outputs.len() / self.max_frames;
+        for (ch, channel) in buffer.iter_mut().enumerate() {
+            let offset = (ch % output_count) * self.max_frames;
+            channel[..frames].copy_from_slice(&self.outputs[offset..offset + frames]);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.plugin.reset();
+    }
+}
+
+impl AudioSource for PluginNode {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        let frames = frames.min(self.max_frames);
+        if self.outputs.is_empty() {
+            for channel in output.iter_mut() {
+                channel[..frames].fill(0.0);
+            }
+            return;
+        }
+        self.inputs.fill(0.0);
+        self.run(frames);
+
+        let output_count = self.outputs.len() / self.max_frames;
+        for (ch, channel) in output.iter_mut().enumerate() {
+            let offset = (ch % output_count) * self.max_frames;
+            channel[..frames].copy_from_slice(&self.outputs[offset..offset + frames]);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Seeded parameter randomization ("dice") for sound-design exploration.
+//!
+//! Values are drawn in knob space through each `ParamSpec`'s taper, so a
+//! logarithmic frequency lands evenly across octaves rather than bunching up
+//! at the top of its range. With a `Scale`, pitches on source nodes (their
+//! frequencies and transpositions) land on notes of the scale. The same seed
+//! on the same project always rolls the same values.
+
+use crate::rt_processing::filters::Trim;
+use crate::rt_processing::notes::{Scale, note_to_frequency};
+use crate::rt_processing::waveform::noise::FastRng;
+
+use super::params::{ParamKind, ParamSpec, ParamUnit, Taper};
+use super::{NodeDescriptor, NodeRegistry, ProjectFile};
+
+/// Part of a project to randomize
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ParamGroup {
+    /// Parameters of source nodes (oscillators, noise, instruments)
+    Oscillator,
+    /// Cutoffs of the high- and low-pass trims already set on sources
+    Filter,
+    /// Parameters of bus and source insert effects
+    Fx,
+}
+
+impl ParamGroup {
+    pub const ALL: [ParamGroup; 3] = [ParamGroup::Oscillator, ParamGroup::Filter, ParamGroup::Fx];
+}
+
+/// Constrained randomizer for node parameters.
+///
+/// Only parameters described in the `NodeRegistry` are rolled, within their
+/// spec's range. Switches and text (paths, plugin ids, sandboxing) are left
+/// alone, as are optional parameters the node doesn't set, so a roll never
+/// changes what a node is, only how it sounds.
+#[derive(Clone, Debug)]
+pub struct Dice {
+    rng: FastRng,
+    amount: f32,
+    groups: Vec<ParamGroup>,
+    locked: Vec<String>,
+    scale: Option<Scale>,
+}
+
+impl Dice {
+    /// Every group, full range
+    pub fn new(seed: u32) -> Self {
+        Self {
+            rng: FastRng::new(seed),
+            amount: 1.0,
+            groups: ParamGroup::ALL.to_vec(),
+            locked: Vec::new(),
+            scale: None,
+        }
+    }
+
+    pub fn with_groups(mut self, groups: &[ParamGroup]) -> Self {
+        self.groups = groups.to_vec();
+        self
+    }
+
+    /// How far a roll may move each knob: 1.0 = anywhere in range, 0.1 = a
+    /// nudge of at most a tenth of the knob travel around the current value
+    pub fn with_amount(mut self, amount: f32) -> Self {
+        self.amount = amount.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Land source pitches on notes of `scale`: frequencies (Hz) on its notes,
+    /// transpositions (semitones) on its intervals above the root. A range
+    /// holding no note of the scale is rolled freely.
+    pub fn with_scale(mut self, scale: Scale) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    /// Never touch parameters with this name
+    pub fn with_locked(mut self, name: impl Into<String>) -> Self {
+        self.locked.push(name.into());
+        self
+    }
+
+    /// Roll the selected groups of `project`
+    pub fn roll(&mut self, project: &mut ProjectFile, registry: &NodeRegistry) {
+        if self.groups.contains(&ParamGroup::Oscillator) {
+            for source in &mut project.sources {
+                let specs = registry.params(&source.node.kind);
+                self.roll_params(&mut source.node, specs, self.scale);
+            }
+        }
+        if self.groups.contains(&ParamGroup::Filter) {
+            let high_pass = trim_spec("high_pass", 20.0, 2_000.0);
+            let low_pass = trim_spec("low_pass", 500.0, 20_000.0);
+            for source in &mut project.sources {
+                if let Some(trim) = &mut source.high_pass {
+                    self.roll_trim(trim, &high_pass);
+                }
+                if let Some(trim) = &mut source.low_pass {
+                    self.roll_trim(trim, &low_pass);
+                }
+            }
+        }
+        if self.groups.contains(&ParamGroup::Fx) {
+            let buses = project.buses.iter_mut().flat_map(|bus| &mut bus.effects);
+            let sources = project.sources.iter_mut().flat_map(|source| &mut source.effects);
+            for effect in buses.chain(sources) {
+                let specs = registry.params(&effect.kind);
+                self.roll_params(effect, specs, None);
+            }
+        }
+    }
+
+    /// Roll the parameters of one node described by `specs`, as a source:
+    /// pitches land on the scale, if any
+    pub fn roll_node(&mut self, node: &mut NodeDescriptor, specs: &[ParamSpec]) {
+        self.roll_params(node, specs, self.scale);
+    }
+
+    fn roll_params(&mut self, node: &mut NodeDescriptor, specs: &[ParamSpec], scale: Option<Scale>) {
+        for spec in specs {
+            if matches!(spec.kind, ParamKind::Bool | ParamKind::Text) || self.locked.contains(&spec.name) {
+                continue;
+            }
+            let current = match node.params.get(&spec.name) {
+                Some(value) => value,
+                None if spec.optional => continue,
+                None => &spec.default,
+            };
+            let Some(number) = spec.to_number(current) else { continue };
+            let mut value = self.roll_number(spec, number);
+            if let Some(scale) = scale {
+                value = on_scale(&scale, spec, value);
+            }
+            node.params.insert(spec.name.clone(), spec.from_number(value));
+        }
+    }
+
+    fn roll_trim(&mut self, trim: &mut Trim, spec: &ParamSpec) {
+        if !self.locked.contains(&spec.name) {
+            trim.cutoff = self.roll_number(spec, trim.cutoff);
+        }
+    }
+
+    /// New value between the current knob position and a random one
+    fn roll_number(&mut self, spec: &ParamSpec, value: f32) -> f32 {
+        let position = spec.to_normalized(value);
+        let target = self.rng.next_f32();
+        spec.from_normalized(position + (target - position) * self.amount)
+    }
+}
+
+/// `value` moved to the nearest note of `scale` in `spec`'s range, if it is a
+/// pitch and the range holds one
+fn on_scale(scale: &Scale, spec: &ParamSpec, value: f32) -> f32 {
+    match spec.unit {
+        ParamUnit::Hertz if spec.min > 0.0 => {
+            let note = |hz: f32| 69.0 + 12.0 * (hz / 440.0).log2();
+            nearest_note(scale, note(value), note(spec.min), note(spec.max)).map_or(value, note_to_frequency)
+        }
+        // transpositions count up from the root
+        ParamUnit::Semitones => {
+            let root = scale.root as f32;
+            nearest_note(scale, root + value, root + spec.min, root + spec.max).map_or(value, |note| note - root)
+        }
+        _ => value,
+    }
+}
+
+/// The note of `scale` nearest `note` within `low..=high`, in MIDI note numbers
+fn nearest_note(scale: &Scale, note: f32, low: f32, high: f32) -> Option<f32> {
+    (low.ceil() as i32..=high.floor() as i32)
+        .filter(|&n| scale.contains(n.rem_euclid(12) as u8))
+        .map(|n| n as f32)
+        .min_by(|a, b| (a - note).abs().total_cmp(&(b - note).abs()))
+}
+
+fn trim_spec(name: &str, min: f32, max: f32) -> ParamSpec {
+    ParamSpec::float(name, min, max, min)
+        .with_unit(ParamUnit::Hertz)
+        .with_taper(Taper::Logarithmic)
+}
@@ -0,0 +1,438 @@
+pub mod dice;
+pub mod params;
+pub mod registry;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{EngineConfig, EngineError};
+use crate::io::wav::WavError;
+use crate::jobs::PoolConfig;
+use crate::plugins::PluginError;
+use crate::rt_processing::effects::DEFAULT_TEMPO_BPM;
+use crate::rt_processing::filters::Trim;
+use crate::rt_processing::routing::PanLaw;
+use crate::rt_processing::transport::TimeSignature;
+
+pub use dice::{Dice, ParamGroup};
+pub use params::{ParamKind, ParamSpec, ParamUnit, Taper};
+pub use registry::{NodeContext, NodeRegistry};
+
+/// Format version written by this build
+pub const PROJECT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum ProjectError {
+    Io(std::io::Error),
+    /// The file is not a valid project document
+    Parse(String),
+    /// Written by a newer build
+    UnsupportedVersion(u32),
+    /// No factory registered for a node kind
+    UnknownKind(String),
+    /// A node parameter has the wrong type or an invalid value
+    InvalidParam { kind: String, param: String },
+    /// A source or effect refers to a bus the engine doesn't have
+    InvalidBus(usize),
+    /// No source at this index
+    InvalidSource(usize),
+    /// No effect at this index on the bus
+    InvalidEffect { bus: usize, index: usize },
+    Engine(EngineError),
+    /// A plugin node failed to load
+    Plugin(PluginError),
+    /// An audio file a node refers to failed to load
+    Wav(WavError),
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Project I/O error: {}", e),
+            Self::Parse(msg) => write!(f, "Invalid project file: {}", msg),
+            Self::UnsupportedVersion(v) => write!(f, "Unsupported project version: {}", v),
+            Self::UnknownKind(kind) => write!(f, "Unknown node kind: {}", kind),
+            Self::InvalidParam { kind, param } => write!(f, "Invalid parameter '{}' for {}", param, kind),
+            Self::InvalidBus(bus) => write!(f, "Invalid bus: {}", bus),
+            Self::InvalidSource(index) => write!(f, "Invalid source: {}", index),
+            Self::InvalidEffect { bus, index } => write!(f, "Invalid effect {} on bus {}", index, bus),
+            Self::Engine(e) => write!(f, "{}", e),
+            Self::Plugin(e) => write!(f, "{}", e),
+            Self::Wav(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProjectError {}
+
+impl From<std::io::Error> for ProjectError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<EngineError> for ProjectError {
+    fn from(e: EngineError) -> Self {
+        Self::Engine(e)
+    }
+}
+
+impl From<PluginError> for ProjectError {
+    fn from(e: PluginError) -> Self {
+        Self::Plugin(e)
+    }
+}
+
+impl From<WavError> for ProjectError {
+    fn from(e: WavError) -> Self {
+        Self::Wav(e)
+    }
+}
+
+pub type ProjectResult<T> = Result<T, ProjectError>;
+
+/// A node parameter value
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ParamValue {
+    Float(f32),
+    Int(i64),
+    Bool(bool),
+    Text(String),
+}
+
+impl From<f32> for ParamValue {
+    fn from(v: f32) -> Self {
+        Self::Float(v)
+    }
+}
+
+impl From<i64> for ParamValue {
+    fn from(v: i64) -> Self {
+        Self::Int(v)
+    }
+}
+
+impl From<bool> for ParamValue {
+    fn from(v: bool) -> Self {
+        Self::Bool(v)
+    }
+}
+
+impl From<&str> for ParamValue {
+    fn from(v: &str) -> Self {
+        Self::Text(v.to_string())
+    }
+}
+
+/// A source or effect: a registered kind plus its parameters
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeDescriptor {
+    pub kind: String,
+    #[serde(default)]
+    pub params: BTreeMap<String, ParamValue>,
+}
+
+impl NodeDescriptor {
+    pub fn new(kind: impl Into<String>) -> Self {
+        Self { kind: kind.into(), params: BTreeMap::new() }
+    }
+
+    pub fn with_param(mut self, name: impl Into<String>, value: impl Into<ParamValue>) -> Self {
+        self.params.insert(name.into(), value.into());
+        self
+    }
+
+    /// Float parameter, `default` when absent. Ints are accepted.
+    pub fn float(&self, name: &str, default: f32) -> ProjectResult<f32> {
+        match self.params.get(name) {
+            None => Ok(default),
+            Some(ParamValue::Float(v)) => Ok(*v),
+            Some(ParamValue::Int(v)) => Ok(*v as f32),
+            Some(_) => Err(self.invalid(name)),
+        }
+    }
+
+    pub fn int(&self, name: &str, default: i64) -> ProjectResult<i64> {
+        match self.params.get(name) {
+            None => Ok(default),
+            Some(ParamValue::Int(v)) => Ok(*v),
+            Some(_) => Err(self.invalid(name)),
+        }
+    }
+
+    pub fn bool(&self, name: &str, default: bool) -> ProjectResult<bool> {
+        match self.params.get(name) {
+            None => Ok(default),
+            Some(ParamValue::Bool(v)) => Ok(*v),
+            Some(_) => Err(self.invalid(name)),
+        }
+    }
+
+    pub fn text<'a>(&'a self, name: &str, default: &'a str) -> ProjectResult<&'a str> {
+        match self.params.get(name) {
+            None => Ok(default),
+            Some(ParamValue::Text(v)) => Ok(v),
+            Some(_) => Err(self.invalid(name)),
+        }
+    }
+
+    /// Error for a parameter that is present but unusable
+    pub fn invalid(&self, name: &str) -> ProjectError {
+        ProjectError::InvalidParam { kind: self.kind.clone(), param: name.to_string() }
+    }
+}
+
+/// Engine settings stored with the project. The worker pool size is a
+/// machine setting and is not part of the project.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EngineSettings {
+    pub sample_rate: f32,
+    pub channels: usize,
+    pub max_frames: usize,
+    pub num_buses: usize,
+}
+
+impl Default for EngineSettings {
+    fn default() -> Self {
+        EngineConfig::default().into()
+    }
+}
+
+impl From<EngineConfig> for EngineSettings {
+    fn from(config: EngineConfig) -> Self {
+        Self {
+            sample_rate: config.sample_rate,
+            channels: config.channels,
+            max_frames: config.max_frames,
+            num_buses: config.num_buses,
+        }
+    }
+}
+
+impl EngineSettings {
+    pub fn to_config(self, pool: PoolConfig) -> EngineConfig {
+        EngineConfig {
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            max_frames: self.max_frames,
+            num_buses: self.num_buses,
+            pool,
+            ..EngineConfig::default()
+        }
+    }
+}
+
+/// Preferred output device. Every field is optional; unset fields fall back to
+/// the host's defaults during negotiation.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DevicePreferences {
+    pub host: Option<String>,
+    pub device: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub buffer_size: Option<u32>,
+}
+
+/// Tempo and meter
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransportSettings {
+    pub tempo_bpm: f32,
+    pub beats_per_bar: u32,
+    pub beat_unit: u32,
+}
+
+impl Default for TransportSettings {
+    fn default() -> Self {
+        Self { tempo_bpm: DEFAULT_TEMPO_BPM, beats_per_bar: 4, beat_unit: 4 }
+    }
+}
+
+impl TransportSettings {
+    pub fn signature(&self) -> TimeSignature {
+        TimeSignature::new(self.beats_per_bar, self.beat_unit)
+    }
+}
+
+/// A routed source: the node plus its mixer strip
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SourceDescriptor {
+    pub node: NodeDescriptor,
+    pub gain: f32,
+    pub pan: f32,
+    pub pan_law: PanLaw,
+    pub bus: usize,
+    #[serde(default)]
+    pub high_pass: Option<Trim>,
+    #[serde(default)]
+    pub low_pass: Option<Trim>,
+    /// Insert chain, after the trims
+    #[serde(default)]
+    pub effects: Vec<NodeDescriptor>,
+    #[serde(default)]
+    pub sends: Vec<SendDescriptor>,
+}
+
+impl SourceDescriptor {
+    /// Unity gain, centered, on the master bus
+    pub fn new(node: NodeDescriptor) -> Self {
+        Self {
+            node,
+            gain: 1.0,
+            pan: 0.0,
+            pan_law: PanLaw::EqualPower,
+            bus: 0,
+            high_pass: None,
+            low_pass: None,
+            effects: Vec::new(),
+            sends: Vec::new(),
+        }
+    }
+
+    pub fn with_gain(mut self, gain: f32) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    pub fn with_pan(mut self, pan: f32, law: PanLaw) -> Self {
+        self.pan = pan.clamp(-1.0, 1.0);
+        self.pan_law = law;
+        self
+    }
+
+    pub fn with_bus(mut self, bus: usize) -> Self {
+        self.bus = bus;
+        self
+    }
+
+    pub fn with_trims(mut self, high_pass: Option<Trim>, low_pass: Option<Trim>) -> Self {
+        self.high_pass = high_pass;
+        self.low_pass = low_pass;
+        self
+    }
+
+    /// Append an insert effect
+    pub fn with_effect(mut self, effect: NodeDescriptor) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    /// Send to aux `bus` at `level`, post-fader
+    pub fn with_send(mut self, bus: usize, level: f32) -> Self {
+        self.sends.push(SendDescriptor { bus, level, pre_fader: false });
+        self
+    }
+}
+
+/// Send from a source to an aux bus
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SendDescriptor {
+    pub bus: usize,
+    pub level: f32,
+    #[serde(default)]
+    pub pre_fader: bool,
+}
+
+/// Insert chain of one bus (0 = master) and its return to the master mix
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BusDescriptor {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub effects: Vec<NodeDescriptor>,
+    /// Linear gain into the master mix; unused on the master bus
+    #[serde(default = "unity")]
+    pub return_gain: f32,
+}
+
+impl Default for BusDescriptor {
+    fn default() -> Self {
+        Self { name: String::new(), effects: Vec::new(), return_gain: 1.0 }
+    }
+}
+
+fn unity() -> f32 {
+    1.0
+}
+
+/// Breakpoint of an automation lane
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AutomationPoint {
+    /// Position in beats from the start of the project
+    pub beat: f64,
+    pub value: f32,
+}
+
+/// Automation of one parameter, addressed as e.g. `"bus.1.effect.0.depth"`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AutomationLane {
+    pub target: String,
+    pub points: Vec<AutomationPoint>,
+}
+
+/// A whole session: engine settings, device preferences, transport, buses
+/// with their effects, routed sources and automation.
+///
+/// Stored as RON. Nodes are described by kind and parameters and rebuilt
+/// through a `NodeRegistry`, so anything registered there can be saved.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProjectFile {
+    pub version: u32,
+    #[serde(default)]
+    pub engine: EngineSettings,
+    #[serde(default)]
+    pub device: DevicePreferences,
+    #[serde(default)]
+    pub transport: TransportSettings,
+    /// One entry per bus, index = bus number
+    #[serde(default)]
+    pub buses: Vec<BusDescriptor>,
+    #[serde(default)]
+    pub sources: Vec<SourceDescriptor>,
+    #[serde(default)]
+    pub automation: Vec<AutomationLane>,
+}
+
+impl Default for ProjectFile {
+    fn default() -> Self {
+        Self::new(EngineSettings::default())
+    }
+}
+
+impl ProjectFile {
+    /// Empty project with one bus descriptor per engine bus
+    pub fn new(engine: EngineSettings) -> Self {
+        Self {
+            version: PROJECT_VERSION,
+            engine,
+            device: DevicePreferences::default(),
+            transport: TransportSettings::default(),
+            buses: vec![BusDescriptor::default(); engine.num_buses.max(1)],
+            sources: Vec::new(),
+            automation: Vec::new(),
+        }
+    }
+
+    pub fn to_ron(&self) -> ProjectResult<String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| ProjectError::Parse(e.to_string()))
+    }
+
+    pub fn from_ron(text: &str) -> ProjectResult<Self> {
+        let project: Self = ron::from_str(text).map_err(|e| ProjectError::Parse(e.to_string()))?;
+        if project.version > PROJECT_VERSION {
+            return Err(ProjectError::UnsupportedVersion(project.version));
+        }
+        Ok(project)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> ProjectResult<()> {
+        fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> ProjectResult<Self> {
+        Self::from_ron(&fs::read_to_string(path)?)
+    }
+}
@@ -0,0 +1,315 @@
+//! Parameter metadata: units, ranges, knob taper and text formatting, so a
+//! UI or host can build controls for any node kind from its `ParamSpec`s.
+
+use serde::{Deserialize, Serialize};
+
+use crate::plugins::ParamInfo;
+
+use super::ParamValue;
+
+/// What a parameter value measures
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParamUnit {
+    None,
+    Hertz,
+    Decibels,
+    Milliseconds,
+    Seconds,
+    /// Stored as a fraction, shown ×100
+    Percent,
+    /// Compression ratio, shown as `4.0:1`
+    Ratio,
+    Semitones,
+    /// Quarter notes
+    Beats,
+}
+
+impl ParamUnit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            ParamUnit::None => "",
+            ParamUnit::Hertz => "Hz",
+            ParamUnit::Decibels => "dB",
+            ParamUnit::Milliseconds => "ms",
+            ParamUnit::Seconds => "s",
+            ParamUnit::Percent => "%",
+            ParamUnit::Ratio => ":1",
+            ParamUnit::Semitones => "st",
+            ParamUnit::Beats => "beats",
+        }
+    }
+}
+
+/// How a knob position (0..1) maps onto the range
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Taper {
+    Linear,
+    /// Equal ratios per equal travel; the range must be positive
+    Logarithmic,
+    /// `position = proportion^skew`: below 1 gives the low end more travel
+    Skew(f32),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ParamKind {
+    Float,
+    Int,
+    Bool,
+    /// One of the listed names, stored as text
+    Choice(Vec<String>),
+    Text,
+}
+
+/// Describes one node parameter
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ParamSpec {
+    pub name: String,
+    pub kind: ParamKind,
+    pub unit: ParamUnit,
+    pub min: f32,
+    pub max: f32,
+    pub default: ParamValue,
+    pub taper: Taper,
+    /// Only takes effect when set: leaving it out selects another behaviour
+    /// rather than `default` (e.g. `rate_beats` switches an LFO to tempo sync)
+    #[serde(default)]
+    pub optional: bool,
+}
+
+impl ParamSpec {
+    pub fn float(name: impl Into<String>, min: f32, max: f32, default: f32) -> Self {
+        Self {
+            name: name.into(),
+            kind: ParamKind::Float,
+            unit: ParamUnit::None,
+            min,
+            max,
+            default: ParamValue::Float(default),
+            taper: Taper::Linear,
+            optional: false,
+        }
+    }
+
+    pub fn int(name: impl Into<String>, min: i64, max: i64, default: i64) -> Self {
+        Self {
+            kind: ParamKind::Int,
+            default: ParamValue::Int(default),
+            ..Self::float(name, min as f32, max as f32, default as f32)
+        }
+    }
+
+    pub fn toggle(name: impl Into<String>, default: bool) -> Self {
+        Self {
+            kind: ParamKind::Bool,
+            default: ParamValue::Bool(default),
+            ..Self::float(name, 0.0, 1.0, 0.0)
+        }
+    }
+
+    /// `default` must be one of `choices`
+    pub fn choice(name: impl Into<String>, choices: &[&str], default: &str) -> Self {
+        Self {
+            kind: ParamKind::Choice(choices.iter().map(|c| c.to_string()).collect()),
+            default: ParamValue::from(default),
+            ..Self::float(name, 0.0, choices.len().saturating_sub(1) as f32, 0.0)
+        }
+    }
+
+    pub fn text(name: impl Into<String>, default: &str) -> Self {
+        Self {
+            kind: ParamKind::Text,
+            default: ParamValue::from(default),
+            ..Self::float(name, 0.0, 0.0, 0.0)
+        }
+    }
+
+    pub fn with_unit(mut self, unit: ParamUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    pub fn with_taper(mut self, taper: Taper) -> Self {
+        self.taper = taper;
+        self
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    /// Knob position (0..1) of `value`, following the taper
+    pub fn to_normalized(&self, value: f32) -> f32 {
+        if self.max <= self.min {
+            return 0.0;
+        }
+        let value = value.clamp(self.min, self.max);
+        match self.taper {
+            Taper::Logarithmic if self.min > 0.0 => {
+                (value / self.min).ln() / (self.max / self.min).ln()
+            }
+            Taper::Skew(skew) if skew > 0.0 => {
+                ((value - self.min) / (self.max - self.min)).powf(skew)
+            }
+            _ => (value - self.min) / (self.max - self.min),
+        }
+    }
+
+    /// Value at knob position `position` (0..1); ints and choices snap to whole steps
+    pub fn from_normalized(&self, position: f32) -> f32 {
+        let position = position.clamp(0.0, 1.0);
+        let value = match self.taper {
+            Taper::Logarithmic if self.min > 0.0 => self.min * (self.max / self.min).powf(position),
+            Taper::Skew(skew) if skew > 0.0 => {
+                self.min + (self.max - self.min) * position.powf(1.0 / skew)
+            }
+            _ => self.min + (self.max - self.min) * position,
+        };
+        match self.kind {
+            ParamKind::Float => value.clamp(self.min, self.max),
+            _ => value.round().clamp(self.min, self.max),
+        }
+    }
+
+    /// Numeric view of `value`: choices map to their index, bools to 0/1
+    pub fn to_number(&self, value: &ParamValue) -> Option<f32> {
+        match (value, &self.kind) {
+            (ParamValue::Float(v), _) => Some(*v),
+            (ParamValue::Int(v), _) => Some(*v as f32),
+            (ParamValue::Bool(v), _) => Some(*v as u8 as f32),
+            (ParamValue::Text(v), ParamKind::Choice(choices)) => {
+                choices.iter().position(|c| c == v).map(|i| i as f32)
+            }
+            (ParamValue::Text(_), _) => None,
+        }
+    }
+
+    /// Stored value for a number in range, the inverse of `to_number`
+    pub fn from_number(&self, value: f32) -> ParamValue {
+        let value = value.clamp(self.min, self.max);
+        match &self.kind {
+            ParamKind::Float => ParamValue::Float(value),
+            ParamKind::Int => ParamValue::Int(value.round() as i64),
+            ParamKind::Bool => ParamValue::Bool(value >= 0.5),
+            ParamKind::Choice(choices) => ParamValue::Text(
+                choices
+                    .get(value.round() as usize)
+                    .cloned()
+                    .unwrap_or_default(),
+            ),
+            ParamKind::Text => self.default.clone(),
+        }
+    }
+
+    /// Display text such as `1.20 kHz`, `-6.0 dB`, `12.5 ms` or `50 %`
+    pub fn format(&self, value: &ParamValue) -> String {
+        match (&self.kind, value) {
+            (_, ParamValue::Text(text)) => text.clone(),
+            (ParamKind::Bool, value) => if self.to_number(value).unwrap_or(0.0) >= 0.5 {
+                "On"
+            } else {
+                "Off"
+            }
+            .to_string(),
+            (ParamKind::Int, value) => {
+                let v = self.to_number(value).unwrap_or(0.0).round() as i64;
+                with_symbol(v.to_string(), self.unit)
+            }
+            (_, value) => self.format_number(self.to_number(value).unwrap_or(0.0)),
+        }
+    }
+
+    fn format_number(&self, v: f32) -> String {
+        match self.unit {
+            ParamUnit::Hertz if v.abs() >= 1000.0 => format!("{} kHz", decimals(v / 1000.0)),
+            ParamUnit::Decibels if v <= -144.0 => "-inf dB".to_string(),
+            ParamUnit::Decibels => format!("{v:.1} dB"),
+            ParamUnit::Seconds if v.abs() < 1.0 => format!("{} ms", decimals(v * 1000.0)),
+            ParamUnit::Percent => format!("{:.0} %", v * 100.0),
+            ParamUnit::Ratio => format!("{v:.1}:1"),
+            ParamUnit::Semitones => format!("{v:+.1} st"),
+            unit => with_symbol(decimals(v), unit),
+        }
+    }
+
+    /// Parse user text into a stored value, clamped to range. Numbers accept
+    /// their unit suffix and common alternatives (`1.2k`, `1.2 kHz`, `250ms`
+    /// for seconds, `0.5 s` for milliseconds, `4:1`, `-inf`).
+    pub fn parse(&self, text: &str) -> Option<ParamValue> {
+        let text = text.trim();
+        match &self.kind {
+            ParamKind::Text => return Some(ParamValue::Text(text.to_string())),
+            ParamKind::Choice(choices) => {
+                return choices
+                    .iter()
+                    .find(|c| c.eq_ignore_ascii_case(text))
+                    .map(|c| ParamValue::Text(c.clone()));
+            }
+            ParamKind::Bool => {
+                return match text.to_ascii_lowercase().as_str() {
+                    "on" | "true" | "yes" | "1" => Some(ParamValue::Bool(true)),
+                    "off" | "false" | "no" | "0" => Some(ParamValue::Bool(false)),
+                    _ => None,
+                };
+            }
+            ParamKind::Int | ParamKind::Float => {}
+        }
+
+        let lower = text.to_ascii_lowercase();
+        if lower.starts_with("-inf") {
+            return Some(self.from_number(self.min));
+        }
+        let split = lower
+            .char_indices()
+            .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && (c == '-' || c == '+'))))
+            .map_or(lower.len(), |(i, _)| i);
+        let number: f32 = lower[..split].parse().ok()?;
+        let suffix = lower[split..].trim();
+
+        let value = match (self.unit, suffix) {
+            (_, "") => number,
+            (ParamUnit::Hertz, "k" | "khz") => number * 1000.0,
+            (ParamUnit::Hertz, "hz") => number,
+            (ParamUnit::Seconds, "ms") => number / 1000.0,
+            (ParamUnit::Seconds, "s" | "sec") => number,
+            (ParamUnit::Milliseconds, "s" | "sec") => number * 1000.0,
+            (ParamUnit::Milliseconds, "ms") => number,
+            (ParamUnit::Ratio, ":1") => number,
+            (unit, suffix) if suffix == unit.symbol().to_ascii_lowercase() => number,
+            _ => return None,
+        };
+        let value = if self.unit == ParamUnit::Percent {
+            value / 100.0
+        } else {
+            value
+        };
+        Some(self.from_number(value))
+    }
+}
+
+impl From<&ParamInfo> for ParamSpec {
+    fn from(info: &ParamInfo) -> Self {
+        Self::float(
+            info.name.clone(),
+            info.min as f32,
+            info.max as f32,
+            info.default as f32,
+        )
+    }
+}
+
+fn with_symbol(text: String, unit: ParamUnit) -> String {
+    match unit.symbol() {
+        "" => text,
+        symbol => format!("{text} {symbol}"),
+    }
+}
+
+/// Three significant digits or so: `1.23`, `12.3`, `123`
+fn decimals(v: f32) -> String {
+    match v.abs() {
+        a if a >= 100.0 => format!("{v:.0}"),
+        a if a >= 10.0 => format!("{v:.1}"),
+        _ => format!("{v:.2}"),
+    }
+}
@@ -0,0 +1,600 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::plugins::clap::{self, ClapPlugin};
+#[cfg(target_os = "linux")]
+use crate::plugins::lv2::{self, Lv2Plugin};
+use crate::plugins::{BridgeConfig, BridgedPlugin, PluginFormat, PluginKind, PluginNode, PluginProcessor};
+use crate::remote::shm::{SharedInput, SharedOutput};
+use crate::rt_processing::effects::Effect;
+use crate::rt_processing::effects::LfoRate;
+use crate::rt_processing::effects::amp_sim::{AmpParams, AmpSim, MAX_AMP_STAGES};
+use crate::rt_processing::effects::auto_pan::AutoPan;
+use crate::rt_processing::effects::character::{Character, CharacterParams};
+use crate::rt_processing::effects::compressor::{Compressor, CompressorParams};
+use crate::rt_processing::effects::imager::StereoWidth;
+use crate::rt_processing::effects::input_strip::{InputStrip, InputStripParams, MAX_TRIM_DB, NoiseGateParams};
+use crate::rt_processing::effects::limiter::{Limiter, LimiterParams, MAX_LOOKAHEAD};
+use crate::rt_processing::effects::reverb::{MAX_PRE_DELAY, Reverb, ReverbParams};
+use crate::rt_processing::effects::tremolo::Tremolo;
+use crate::rt_processing::filters::{BUTTERWORTH_Q, Biquad, FilterType, Trim, TrimSlope};
+use crate::rt_processing::routing::AudioSource;
+use crate::rt_processing::voice_renderer::{SilenceSource, TestToneSource, routing_source};
+use crate::rt_processing::waveform::noise::{PinkNoise, WhiteNoise};
+#[cfg(not(feature = "fixed-point"))]
+use crate::rt_processing::waveform::oscillators::Oscillator;
+#[cfg(feature = "fixed-point")]
+use crate::rt_processing::fixed::FixedOscillator;
+use crate::rt_processing::waveform::oscillators::WavetableOscillator;
+use crate::rt_processing::waveform::tables::{DEFAULT_WAVETABLE_FRAME, Wavetable, WaveformType};
+
+use super::params::{ParamSpec, ParamUnit, Taper};
+use super::{NodeDescriptor, ParamValue, ProjectError, ProjectResult};
+
+/// What a factory gets to know about the graph it builds into
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NodeContext {
+    pub channels: usize,
+    pub sample_rate: f32,
+    pub max_frames: usize,
+    pub tempo_bpm: f32,
+}
+
+pub type SourceFactory =
+    Arc<dyn Fn(&NodeDescriptor, &NodeContext) -> ProjectResult<Box<dyn AudioSource>> + Send + Sync>;
+pub type EffectFactory = Arc<dyn Fn(&NodeDescriptor, &NodeContext) -> ProjectResult<Box<dyn Effect>> + Send + Sync>;
+
+/// Builds sources and effects from `NodeDescriptor`s by kind.
+///
+/// `with_builtins` registers the stock nodes; hosts add their own with
+/// `register_source` / `register_effect`, replacing a builtin of the same kind.
+/// `register_params` describes a kind's parameters for UIs and hosts.
+#[derive(Clone, Default)]
+pub struct NodeRegistry {
+    sources: HashMap<String, SourceFactory>,
+    effects: HashMap<String, EffectFactory>,
+    params: HashMap<String, Vec<ParamSpec>>,
+}
+
+impl NodeRegistry {
+    /// Empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry with the stock sources and effects:
+    /// - sources: `silence`, `test_tone`, `oscillator`, `wavetable`, `white_noise`, `pink_noise`,
+    ///   `shared_input`, `clap_instrument`, `lv2_instrument` (Linux)
+    /// - effects: `tremolo`, `auto_pan`, `biquad`, `compressor`, `stereo_width`, `character`,
+    ///   `amp_sim`, `input_strip`, `reverb`, `shared_output`, `clap_effect`, `lv2_effect` (Linux)
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+
+        registry.register_source("silence", |_, _| Ok(routing_source(SilenceSource)));
+        registry.register_source("test_tone", |node, _| {
+            Ok(routing_source(TestToneSource::new(node.float("frequency", 440.0)?, node.float("amplitude", 0.5)?)))
+        });
+        #[cfg(not(feature = "fixed-point"))]
+        registry.register_source("oscillator", |node, _| {
+            let waveform = waveform_param(node, "waveform")?;
+            let oscillator = Oscillator::new(waveform, node.float("frequency", 440.0)?)
+                .with_amplitude(node.float("amplitude", 0.5)?)
+                .with_antialiasing(node.bool("antialiasing", false)?);
+            Ok(routing_source(oscillator))
+        });
+        // reduced-precision builds render oscillators in Q15
+        #[cfg(feature = "fixed-point")]
+        registry.register_source("oscillator", |node, ctx| {
+            let waveform = waveform_param(node, "waveform")?;
+            let oscillator = FixedOscillator::new(waveform, node.float("frequency", 440.0)?, ctx.max_frames)
+                .with_amplitude(node.float("amplitude", 0.5)?);
+            Ok(Box::new(oscillator))
+        });
+        registry.register_source("wavetable", |node, _| {
+            // without a file, the four built-in shapes in a row
+            let table = match node.text("path", "")? {
+                "" => Wavetable::from_waveforms(
+                    &[WaveformType::Sine, WaveformType::Triangle, WaveformType::Sawtooth, WaveformType::Square],
+                    DEFAULT_WAVETABLE_FRAME,
+                ),
+                path => {
+                    let frame_len = node.int("frame_len", DEFAULT_WAVETABLE_FRAME as i64)?;
+                    let frame_len = usize::try_from(frame_len).map_err(|_| node.invalid("frame_len"))?;
+                    Wavetable::from_wav(path, frame_len)?
+                }
+            };
+            let oscillator = WavetableOscillator::new(table, node.float("frequency", 440.0)?)
+                .with_amplitude(node.float("amplitude", 0.5)?)
+                .with_position(node.float("position", 0.0)?)
+                .with_scan(
+                    waveform_param(node, "scan_waveform")?,
+                    node.float("scan_rate_hz", 0.5)?,
+                    node.float("scan_depth", 0.0)?,
+                );
+            Ok(routing_source(oscillator))
+        });
+        registry.register_source("white_noise", |node, _| {
+            Ok(routing_source(WhiteNoise::new().with_amplitude(node.float("amplitude", 0.5)?)))
+        });
+        registry.register_source("pink_noise", |node, _| {
+            Ok(routing_source(PinkNoise::new().with_amplitude(node.float("amplitude", 0.5)?)))
+        });
+        registry.register_source("shared_input", |node, _| {
+            Ok(Box::new(SharedInput::open(node.text("path", "")?)?))
+        });
+        registry.register_source("clap_instrument", |node, ctx| {
+            Ok(Box::new(clap_node(node, ctx, PluginKind::Instrument)?))
+        });
+        #[cfg(target_os = "linux")]
+        registry.register_source("lv2_instrument", |node, ctx| Ok(Box::new(lv2_node(node, ctx)?)));
+
+        registry.register_effect("tremolo", |node, ctx| {
+            let mut tremolo = Tremolo::new(waveform_param(node, "waveform")?, rate_param(node)?)
+                .with_depth(node.float("depth", 0.5)?);
+            tremolo.set_tempo(ctx.tempo_bpm);
+            Ok(Box::new(tremolo))
+        });
+        registry.register_effect("auto_pan", |node, ctx| {
+            let mut auto_pan = AutoPan::new(waveform_param(node, "waveform")?, rate_param(node)?)
+                .with_width(node.float("width", 1.0)?);
+            auto_pan.set_tempo(ctx.tempo_bpm);
+            Ok(Box::new(auto_pan))
+        });
+        registry.register_effect("biquad", |node, ctx| {
+            let filter_type = match node.text("type", "low_pass")? {
+                "low_pass" => FilterType::LowPass,
+                "high_pass" => FilterType::HighPass,
+                "band_pass" => FilterType::BandPass,
+                "notch" => FilterType::Notch,
+                "peaking" => FilterType::Peaking,
+                "low_shelf" => FilterType::LowShelf,
+                "high_shelf" => FilterType::HighShelf,
+                _ => return Err(node.invalid("type")),
+            };
+            let mut biquad = Biquad::new(filter_type, node.float("cutoff", 1000.0)?, ctx.channels)
+                .with_q(node.float("q", BUTTERWORTH_Q)?)
+                .with_gain_db(node.float("gain_db", 0.0)?)
+                .with_lfo(waveform_param(node, "lfo_waveform")?, rate_param(node)?, node.float("lfo_depth", 0.0)?);
+            biquad.set_tempo(ctx.tempo_bpm);
+            Ok(Box::new(biquad))
+        });
+        registry.register_effect("compressor", |node, _| {
+            let d = CompressorParams::default();
+            let params = CompressorParams {
+                threshold_db: node.float("threshold_db", d.threshold_db)?,
+                ratio: node.float("ratio", d.ratio)?,
+                knee_db: node.float("knee_db", d.knee_db)?,
+                attack: node.float("attack", d.attack)?,
+                release: node.float("release", d.release)?,
+                makeup_db: node.float("makeup_db", d.makeup_db)?,
+            };
+            Ok(Box::new(Compressor::new(params)))
+        });
+        registry.register_effect("limiter", |node, ctx| {
+            let d = LimiterParams::default();
+            let params = LimiterParams {
+                ceiling_db: node.float("ceiling_db", d.ceiling_db)?,
+                lookahead: node.float("lookahead", d.lookahead)?,
+                release: node.float("release", d.release)?,
+                true_peak: node.bool("true_peak", d.true_peak)?,
+            };
+            Ok(Box::new(Limiter::new(ctx.channels, ctx.sample_rate, params)))
+        });
+        registry.register_effect("stereo_width", |node, _| Ok(Box::new(StereoWidth::new(node.float("width", 1.0)?))));
+        registry.register_effect("character", |node, ctx| {
+            let d = match node.text("preset", "tape")? {
+                "tape" => CharacterParams::tape(),
+                "vinyl" => CharacterParams::vinyl(),
+                _ => return Err(node.invalid("preset")),
+            };
+            let params = CharacterParams {
+                intensity: node.float("intensity", d.intensity)?,
+                wow: node.float("wow", d.wow)?,
+                flutter: node.float("flutter", d.flutter)?,
+                saturation: node.float("saturation", d.saturation)?,
+                age: node.float("age", d.age)?,
+                hiss: node.float("hiss", d.hiss)?,
+                crackle: node.float("crackle", d.crackle)?,
+            };
+            let mut character = Character::new(ctx.channels, ctx.max_frames, params);
+            character.prepare(ctx.sample_rate);
+            Ok(Box::new(character))
+        });
+        registry.register_effect("input_strip", |node, ctx| {
+            // the filter and the gate are off unless their main knob is set
+            let high_pass = if node.params.contains_key("hpf_hz") {
+                Some(Trim::new(node.float("hpf_hz", 80.0)?, TrimSlope::Db12))
+            } else {
+                None
+            };
+            let gate = if node.params.contains_key("gate_threshold_db") {
+                let d = NoiseGateParams::default();
+                Some(NoiseGateParams {
+                    threshold_db: node.float("gate_threshold_db", d.threshold_db)?,
+                    attack: node.float("gate_attack", d.attack)?,
+                    release: node.float("gate_release", d.release)?,
+                    range_db: node.float("gate_range_db", d.range_db)?,
+                })
+            } else {
+                None
+            };
+            let params = InputStripParams {
+                trim_db: node.float("trim_db", 0.0)?,
+                invert: node.bool("invert", false)?,
+                high_pass,
+                gate,
+            };
+            Ok(Box::new(InputStrip::new(ctx.channels).with_params(params)))
+        });
+        registry.register_effect("amp_sim", |node, ctx| {
+            let d = AmpParams::default();
+            let stages = node.int("stages", d.stages as i64)?;
+            if stages < 1 {
+                return Err(node.invalid("stages"));
+            }
+            let params = AmpParams {
+                drive_db: node.float("drive_db", d.drive_db)?,
+                stages: stages as usize,
+                bias: node.float("bias", d.bias)?,
+                bass_db: node.float("bass_db", d.bass_db)?,
+                mid_db: node.float("mid_db", d.mid_db)?,
+                treble_db: node.float("treble_db", d.treble_db)?,
+                master_db: node.float("master_db", d.master_db)?,
+            };
+            Ok(Box::new(AmpSim::new(ctx.channels, params)))
+        });
+        registry.register_effect("reverb", |node, _| {
+            let d = ReverbParams::default();
+            let params = ReverbParams {
+                size: node.float("size", d.size)?,
+                decay: node.float("decay", d.decay)?,
+                damping: node.float("damping", d.damping)?,
+                pre_delay: node.float("pre_delay", d.pre_delay)?,
+                width: node.float("width", d.width)?,
+                mix: node.float("mix", d.mix)?,
+            };
+            Ok(Box::new(Reverb::new(params)))
+        });
+        registry.register_effect("shared_output", |node, ctx| {
+            Ok(Box::new(SharedOutput::open(node.text("path", "")?, ctx.max_frames)?))
+        });
+        registry.register_effect("clap_effect", |node, ctx| Ok(Box::new(clap_node(node, ctx, PluginKind::Effect)?)));
+        #[cfg(target_os = "linux")]
+        registry.register_effect("lv2_effect", |node, ctx| Ok(Box::new(lv2_node(node, ctx)?)));
+
+        register_builtin_params(&mut registry);
+        registry
+    }
+
+    pub fn register_source(
+        &mut self,
+        kind: impl Into<String>,
+        factory: impl Fn(&NodeDescriptor, &NodeContext) -> ProjectResult<Box<dyn AudioSource>> + Send + Sync + 'static,
+    ) {
+        self.sources.insert(kind.into(), Arc::new(factory));
+    }
+
+    pub fn register_effect(
+        &mut self,
+        kind: impl Into<String>,
+        factory: impl Fn(&NodeDescriptor, &NodeContext) -> ProjectResult<Box<dyn Effect>> + Send + Sync + 'static,
+    ) {
+        self.effects.insert(kind.into(), Arc::new(factory));
+    }
+
+    /// Describe the parameters of `kind`, replacing any earlier description
+    pub fn register_params(&mut self, kind: impl Into<String>, params: Vec<ParamSpec>) {
+        self.params.insert(kind.into(), params);
+    }
+
+    /// Parameters of `kind`, empty when it has none or wasn't described.
+    /// Plugin nodes list their parameters only once loaded, see `ParamSpec::from(&ParamInfo)`.
+    pub fn params(&self, kind: &str) -> &[ParamSpec] {
+        self.params.get(kind).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn param(&self, kind: &str, name: &str) -> Option<&ParamSpec> {
+        self.params(kind).iter().find(|p| p.name == name)
+    }
+
+    pub fn has_source(&self, kind: &str) -> bool {
+        self.sources.contains_key(kind)
+    }
+
+    pub fn has_effect(&self, kind: &str) -> bool {
+        self.effects.contains_key(kind)
+    }
+
+    pub fn build_source(&self, node: &NodeDescriptor, ctx: &NodeContext) -> ProjectResult<Box<dyn AudioSource>> {
+        match self.sources.get(&node.kind) {
+            Some(factory) => factory(node, ctx),
+            None => Err(ProjectError::UnknownKind(node.kind.clone())),
+        }
+    }
+
+    pub fn build_effect(&self, node: &NodeDescriptor, ctx: &NodeContext) -> ProjectResult<Box<dyn Effect>> {
+        match self.effects.get(&node.kind) {
+            Some(factory) => factory(node, ctx),
+            None => Err(ProjectError::UnknownKind(node.kind.clone())),
+        }
+    }
+}
+
+fn register_builtin_params(registry: &mut NodeRegistry) {
+    let waveform = || ParamSpec::choice("waveform", &["sine", "triangle", "sawtooth", "square"], "sine");
+    let frequency = || {
+        ParamSpec::float("frequency", 20.0, 20_000.0, 440.0)
+            .with_unit(ParamUnit::Hertz)
+            .with_taper(Taper::Logarithmic)
+    };
+    let amplitude = || ParamSpec::float("amplitude", 0.0, 1.0, 0.5);
+    let rates = || {
+        [
+            ParamSpec::float("rate_hz", 0.01, 20.0, 4.0)
+                .with_unit(ParamUnit::Hertz)
+                .with_taper(Taper::Logarithmic),
+            ParamSpec::float("rate_beats", 0.0625, 16.0, 1.0)
+                .with_unit(ParamUnit::Beats)
+                .with_taper(Taper::Logarithmic)
+                .optional(),
+        ]
+    };
+    let unit = |name: &str, default: f32| ParamSpec::float(name, 0.0, 1.0, default).with_unit(ParamUnit::Percent);
+    let decibels = |name: &str, min: f32, max: f32, default: f32| {
+        ParamSpec::float(name, min, max, default).with_unit(ParamUnit::Decibels)
+    };
+
+    registry.register_params("test_tone", vec![frequency(), amplitude()]);
+    registry.register_params(
+        "oscillator",
+        vec![waveform(), frequency(), amplitude(), ParamSpec::toggle("antialiasing", false)],
+    );
+    registry.register_params(
+        "wavetable",
+        vec![
+            ParamSpec::text("path", ""),
+            ParamSpec::int("frame_len", 1, 65_536, DEFAULT_WAVETABLE_FRAME as i64),
+            frequency(),
+            amplitude(),
+            unit("position", 0.0),
+            ParamSpec::choice("scan_waveform", &["sine", "triangle", "sawtooth", "square"], "sine"),
+            ParamSpec::float("scan_rate_hz", 0.01, 20.0, 0.5)
+                .with_unit(ParamUnit::Hertz)
+                .with_taper(Taper::Logarithmic),
+            unit("scan_depth", 0.0),
+        ],
+    );
+    registry.register_params("white_noise", vec![amplitude()]);
+    registry.register_params("pink_noise", vec![amplitude()]);
+    registry.register_params("shared_input", vec![ParamSpec::text("path", "")]);
+    registry.register_params("shared_output", vec![ParamSpec::text("path", "")]);
+
+    let [rate_hz, rate_beats] = rates();
+    registry.register_params("tremolo", vec![waveform(), rate_hz, rate_beats, unit("depth", 0.5)]);
+    let [rate_hz, rate_beats] = rates();
+    registry.register_params("auto_pan", vec![waveform(), rate_hz, rate_beats, unit("width", 1.0)]);
+
+    let [rate_hz, rate_beats] = rates();
+    registry.register_params(
+        "biquad",
+        vec![
+            ParamSpec::choice(
+                "type",
+                &["low_pass", "high_pass", "band_pass", "notch", "peaking", "low_shelf", "high_shelf"],
+                "low_pass",
+            ),
+            ParamSpec::float("cutoff", 20.0, 20_000.0, 1000.0)
+                .with_unit(ParamUnit::Hertz)
+                .with_taper(Taper::Logarithmic),
+            ParamSpec::float("q", 0.1, 20.0, BUTTERWORTH_Q).with_taper(Taper::Logarithmic),
+            decibels("gain_db", -24.0, 24.0, 0.0),
+            ParamSpec::choice("lfo_waveform", &["sine", "triangle", "sawtooth", "square"], "sine"),
+            rate_hz,
+            rate_beats,
+            // octaves either side of the cutoff
+            ParamSpec::float("lfo_depth", 0.0, 4.0, 0.0),
+        ],
+    );
+
+    let d = CompressorParams::default();
+    registry.register_params(
+        "compressor",
+        vec![
+            decibels("threshold_db", -60.0, 0.0, d.threshold_db),
+            ParamSpec::float("ratio", 1.0, 20.0, d.ratio)
+                .with_unit(ParamUnit::Ratio)
+                .with_taper(Taper::Skew(0.5)),
+            decibels("knee_db", 0.0, 24.0, d.knee_db),
+            ParamSpec::float("attack", 0.0001, 0.5, d.attack)
+                .with_unit(ParamUnit::Seconds)
+                .with_taper(Taper::Logarithmic),
+            ParamSpec::float("release", 0.005, 5.0, d.release)
+                .with_unit(ParamUnit::Seconds)
+                .with_taper(Taper::Logarithmic),
+            decibels("makeup_db", 0.0, 24.0, d.makeup_db),
+        ],
+    );
+
+    let d = LimiterParams::default();
+    registry.register_params(
+        "limiter",
+        vec![
+            decibels("ceiling_db", -24.0, 0.0, d.ceiling_db),
+            ParamSpec::float("lookahead", 0.0, MAX_LOOKAHEAD, d.lookahead).with_unit(ParamUnit::Seconds),
+            ParamSpec::float("release", 0.005, 5.0, d.release)
+                .with_unit(ParamUnit::Seconds)
+                .with_taper(Taper::Logarithmic),
+            ParamSpec::toggle("true_peak", d.true_peak),
+        ],
+    );
+    registry.register_params("stereo_width", vec![ParamSpec::float("width", 0.0, 2.0, 1.0).with_unit(ParamUnit::Percent)]);
+
+    let d = CharacterParams::tape();
+    registry.register_params(
+        "character",
+        vec![
+            ParamSpec::choice("preset", &["tape", "vinyl"], "tape"),
+            unit("intensity", d.intensity),
+            unit("wow", d.wow),
+            unit("flutter", d.flutter),
+            unit("saturation", d.saturation),
+            unit("age", d.age),
+            unit("hiss", d.hiss),
+            unit("crackle", d.crackle),
+        ],
+    );
+
+    let d = AmpParams::default();
+    registry.register_params(
+        "amp_sim",
+        vec![
+            decibels("drive_db", 0.0, 48.0, d.drive_db),
+            ParamSpec::int("stages", 1, MAX_AMP_STAGES as i64, d.stages as i64),
+            ParamSpec::float("bias", -0.5, 0.5, d.bias),
+            decibels("bass_db", -12.0, 12.0, d.bass_db),
+            decibels("mid_db", -12.0, 12.0, d.mid_db),
+            decibels("treble_db", -12.0, 12.0, d.treble_db),
+            decibels("master_db", -60.0, 12.0, d.master_db),
+        ],
+    );
+
+    let d = ReverbParams::default();
+    registry.register_params(
+        "reverb",
+        vec![
+            unit("size", d.size),
+            ParamSpec::float("decay", 0.1, 20.0, d.decay)
+                .with_unit(ParamUnit::Seconds)
+                .with_taper(Taper::Logarithmic),
+            unit("damping", d.damping),
+            ParamSpec::float("pre_delay", 0.0, MAX_PRE_DELAY, d.pre_delay).with_unit(ParamUnit::Seconds),
+            ParamSpec::float("width", 0.0, 2.0, d.width).with_unit(ParamUnit::Percent),
+            unit("mix", d.mix),
+        ],
+    );
+
+    let d = NoiseGateParams::default();
+    registry.register_params(
+        "input_strip",
+        vec![
+            decibels("trim_db", -MAX_TRIM_DB, MAX_TRIM_DB, 0.0),
+            ParamSpec::toggle("invert", false),
+            ParamSpec::float("hpf_hz", 20.0, 400.0, 80.0)
+                .with_unit(ParamUnit::Hertz)
+                .with_taper(Taper::Logarithmic)
+                .optional(),
+            decibels("gate_threshold_db", -90.0, 0.0, d.threshold_db).optional(),
+            ParamSpec::float("gate_attack", 0.0001, 0.1, d.attack)
+                .with_unit(ParamUnit::Seconds)
+                .with_taper(Taper::Logarithmic),
+            ParamSpec::float("gate_release", 0.005, 2.0, d.release)
+                .with_unit(ParamUnit::Seconds)
+                .with_taper(Taper::Logarithmic),
+            decibels("gate_range_db", -90.0, 0.0, d.range_db),
+        ],
+    );
+
+    for kind in ["clap_instrument", "clap_effect"] {
+        registry.register_params(
+            kind,
+            vec![ParamSpec::text("path", ""), ParamSpec::text("plugin_id", ""), ParamSpec::toggle("sandbox", false)],
+        );
+    }
+    #[cfg(target_os = "linux")]
+    for kind in ["lv2_instrument", "lv2_effect"] {
+        registry.register_params(
+            kind,
+            vec![ParamSpec::text("uri", ""), ParamSpec::text("bundle", ""), ParamSpec::toggle("sandbox", false)],
+        );
+    }
+}
+
+fn waveform_param(node: &NodeDescriptor, name: &str) -> ProjectResult<WaveformType> {
+    match node.text(name, "sine")? {
+        "sine" => Ok(WaveformType::Sine),
+        "triangle" => Ok(WaveformType::Triangle),
+        "sawtooth" => Ok(WaveformType::Sawtooth),
+        "square" => Ok(WaveformType::Square),
+        _ => Err(node.invalid(name)),
+    }
+}
+
+/// `rate_beats` (tempo-synced) takes precedence over `rate_hz`
+fn rate_param(node: &NodeDescriptor) -> ProjectResult<LfoRate> {
+    if node.params.contains_key("rate_beats") {
+        Ok(LfoRate::Beats(node.float("rate_beats", 1.0)?))
+    } else {
+        Ok(LfoRate::Hz(node.float("rate_hz", 4.0)?))
+    }
+}
+
+/// Load a CLAP plugin node: `path` is the `.clap` file and `plugin_id` picks
+/// the plugin in it (the first of `kind` when empty); `sandbox` runs it in a
+/// separate process. Every other parameter sets the plugin parameter of that
+/// name.
+fn clap_node(node: &NodeDescriptor, ctx: &NodeContext, kind: PluginKind) -> ProjectResult<PluginNode> {
+    let path = node.text("path", "")?;
+    let id = match node.text("plugin_id", "")? {
+        "" => clap::scan_file(path)?
+            .into_iter()
+            .find(|info| info.kind == kind)
+            .map(|info| info.id)
+            .ok_or_else(|| node.invalid("plugin_id"))?,
+        id => id.to_string(),
+    };
+    let plugin: Box<dyn PluginProcessor> = if node.bool("sandbox", false)? {
+        let config = BridgeConfig::new(PluginFormat::Clap, path, id);
+        Box::new(BridgedPlugin::load(config, ctx.sample_rate, ctx.max_frames)?)
+    } else {
+        Box::new(ClapPlugin::load(path, &id, ctx.sample_rate, ctx.max_frames)?)
+    };
+    plugin_node(node, plugin, ctx, &["path", "plugin_id", "sandbox"])
+}
+
+/// Load an LV2 plugin node: `uri` names the plugin and `bundle` is its bundle
+/// directory (looked up on the LV2 search path when empty); `sandbox` runs it
+/// in a separate process. Every other parameter sets the control port with
+/// that symbol.
+#[cfg(target_os = "linux")]
+fn lv2_node(node: &NodeDescriptor, ctx: &NodeContext) -> ProjectResult<PluginNode> {
+    let uri = node.text("uri", "")?;
+    let bundle = match node.text("bundle", "")? {
+        "" => lv2::find(uri)?.path,
+        bundle => bundle.into(),
+    };
+    let plugin: Box<dyn PluginProcessor> = if node.bool("sandbox", false)? {
+        let config = BridgeConfig::new(PluginFormat::Lv2, bundle, uri);
+        Box::new(BridgedPlugin::load(config, ctx.sample_rate, ctx.max_frames)?)
+    } else {
+        Box::new(Lv2Plugin::load(bundle, uri, ctx.sample_rate, ctx.max_frames)?)
+    };
+    plugin_node(node, plugin, ctx, &["uri", "bundle", "sandbox"])
+}
+
+/// Wrap a loaded plugin, queueing the node's parameters (except `reserved`)
+/// as initial values for the plugin parameters of the same name
+fn plugin_node(
+    node: &NodeDescriptor,
+    plugin: Box<dyn PluginProcessor>,
+    ctx: &NodeContext,
+    reserved: &[&str],
+) -> ProjectResult<PluginNode> {
+    let mut initial = Vec::new();
+    for (name, value) in node.params.iter().filter(|(name, _)| !reserved.contains(&name.as_str())) {
+        let param = plugin.params().iter().find(|p| &p.name == name).ok_or_else(|| node.invalid(name))?;
+        let value = match *value {
+            ParamValue::Float(v) => v as f64,
+            ParamValue::Int(v) => v as f64,
+            ParamValue::Bool(v) => v as u8 as f64,
+            ParamValue::Text(_) => return Err(node.invalid(name)),
+        };
+        initial.push((param.id, value.clamp(param.min, param.max)));
+    }
+
+    let plugin_node = PluginNode::new(plugin, ctx.max_frames);
+    let control = plugin_node.control();
+    for (id, value) in initial {
+        control.set_param(id, value);
+    }
+    Ok(plugin_node)
+}
@@ -0,0 +1,295 @@
+pub mod shm;
+
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pulsar_client::protocol::{
+    Command, Event, Meter, Node, PROTOCOL_VERSION, Reply, Request, ServerMessage, Target, Value, read_message,
+    write_message,
+};
+use pulsar_client::transport::{Connection, Listener};
+
+use crate::engine::{EditCommand, Engine, EngineAudioHandle, EngineConfig, NodeTarget};
+use crate::project::{NodeDescriptor, ParamValue, ProjectFile, ProjectResult, SourceDescriptor};
+use crate::rt_processing::routing::PanLaw;
+
+/// How often the accept loop checks for shutdown
+const ACCEPT_POLL: Duration = Duration::from_millis(50);
+/// Resolution of the meter stream
+const METER_TICK: Duration = Duration::from_millis(5);
+
+/// Device-callback side of a remote server. Follows the engine across
+/// reconfiguration, so the device stream can be opened once.
+#[derive(Clone, Default)]
+pub struct RemoteOutput {
+    handle: Arc<spin::Mutex<Option<EngineAudioHandle>>>,
+}
+
+impl RemoteOutput {
+    /// RT entry point: renders through the current engine handle, silence
+    /// while there is none or it is being replaced. Never blocks.
+    pub fn process(&self, output: &mut [f32]) -> bool {
+        match self.handle.try_lock() {
+            Some(guard) => match guard.as_ref() {
+                Some(handle) => handle.process(output),
+                None => {
+                    output.fill(0.0);
+                    false
+                }
+            },
+            None => {
+                output.fill(0.0);
+                false
+            }
+        }
+    }
+
+    fn refresh(&self, engine: &Engine) {
+        *self.handle.lock() = engine.audio_handle().ok();
+    }
+}
+
+/// A connected client
+struct Peer {
+    writer: Mutex<Connection>,
+    meter_interval_ms: AtomicU32,
+    next_meter: Mutex<Instant>,
+}
+
+impl Peer {
+    fn send(&self, message: &ServerMessage) -> io::Result<()> {
+        write_message(&mut *self.writer.lock().unwrap_or_else(|e| e.into_inner()), message)
+    }
+}
+
+/// Serves an `Engine` to `pulsar_client` clients in other processes.
+///
+/// Each client gets its own thread; commands are executed against the engine
+/// in arrival order under one lock. Engine transitions are pushed to every
+/// client as `Event::State`, and output meters to clients that asked for them.
+/// The audio device is driven through `output()`, which never waits on the
+/// control side.
+pub struct RemoteServer {
+    engine: Mutex<Engine>,
+    output: RemoteOutput,
+    peers: Mutex<Vec<Arc<Peer>>>,
+    shutdown: AtomicBool,
+}
+
+impl RemoteServer {
+    pub fn new(engine: Engine) -> Self {
+        let output = RemoteOutput::default();
+        output.refresh(&engine);
+        Self { engine: Mutex::new(engine), output, peers: Mutex::new(Vec::new()), shutdown: AtomicBool::new(false) }
+    }
+
+    /// Handle for the audio device callback
+    pub fn output(&self) -> RemoteOutput {
+        self.output.clone()
+    }
+
+    /// Run `f` on the engine, e.g. to set it up before serving
+    pub fn with_engine<R>(&self, f: impl FnOnce(&mut Engine) -> R) -> R {
+        let mut engine = self.engine();
+        let result = f(&mut engine);
+        self.output.refresh(&engine);
+        result
+    }
+
+    /// Make `serve` return: notifies and disconnects every client
+    pub fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::Release);
+        for peer in self.peers().iter() {
+            let _ = peer.send(&ServerMessage::Event(Event::Shutdown));
+            let _ = peer.writer.lock().unwrap_or_else(|e| e.into_inner()).shutdown();
+        }
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.shutdown.load(Ordering::Acquire)
+    }
+
+    /// Accept clients on `listener` until `shutdown` is called (locally or by a
+    /// client's `Command::Shutdown`). Returns once every client thread ended.
+    pub fn serve(&self, listener: Listener) -> io::Result<()> {
+        listener.set_nonblocking(true)?;
+        let events = self.engine().subscribe();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                while !self.is_shut_down() {
+                    if let Ok(event) = events.recv_timeout(ACCEPT_POLL) {
+                        let event = Event::State { from: event.from.to_string(), to: event.to.to_string() };
+                        self.broadcast(&ServerMessage::Event(event));
+                    }
+                }
+            });
+            scope.spawn(|| self.meter_loop());
+
+            let result = loop {
+                if self.is_shut_down() {
+                    break Ok(());
+                }
+                match listener.accept() {
+                    Ok(connection) => {
+                        scope.spawn(move || self.handle_client(connection));
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => break Err(e),
+                }
+            };
+            if result.is_err() {
+                self.shutdown();
+            }
+            result
+        })
+    }
+
+    fn handle_client(&self, mut reader: Connection) {
+        let Ok(writer) = reader.try_clone() else { return };
+        let peer = Arc::new(Peer {
+            writer: Mutex::new(writer),
+            meter_interval_ms: AtomicU32::new(0),
+            next_meter: Mutex::new(Instant::now()),
+        });
+        self.peers().push(Arc::clone(&peer));
+        // a shutdown that raced the registration would miss this client
+        if self.is_shut_down() {
+            let _ = reader.shutdown();
+        }
+
+        while let Ok(request) = read_message::<Request>(&mut reader) {
+            let shutdown = request.command == Command::Shutdown;
+            let result = self.execute(&peer, request.command).map_err(|e| e.to_string());
+            if peer.send(&ServerMessage::Response { id: request.id, result }).is_err() {
+                break;
+            }
+            if shutdown {
+                self.shutdown();
+                break;
+            }
+        }
+        self.peers().retain(|p| !Arc::ptr_eq(p, &peer));
+    }
+
+    fn execute(&self, peer: &Peer, command: Command) -> ProjectResult<Reply> {
+        let mut engine = self.engine();
+        let reply = match command {
+            Command::Hello { .. } => Reply::Hello { version: PROTOCOL_VERSION },
+            Command::Configure { sample_rate, channels, max_frames, num_buses } => {
+                // keep the settings the protocol doesn't carry
+                let current = engine.config().copied().unwrap_or_default();
+                engine.configure(EngineConfig { sample_rate, channels, max_frames, num_buses, ..current })?;
+                Reply::Ok
+            }
+            Command::Start => engine.start().map(|_| Reply::Ok)?,
+            Command::Suspend => engine.suspend().map(|_| Reply::Ok)?,
+            Command::Resume => engine.resume().map(|_| Reply::Ok)?,
+            Command::Stop => engine.stop().map(|_| Reply::Ok)?,
+            Command::State => Reply::State(engine.state().to_string()),
+            Command::AddSource { node, gain, pan, bus } => {
+                let source = SourceDescriptor::new(node_descriptor(node))
+                    .with_gain(gain)
+                    .with_pan(pan, PanLaw::EqualPower)
+                    .with_bus(bus);
+                engine.edit(EditCommand::AddSource(source)).map(|_| Reply::Ok)?
+            }
+            Command::RemoveSource(index) => engine.edit(EditCommand::RemoveSource(index)).map(|_| Reply::Ok)?,
+            Command::SetParams { target, params } => {
+                let target = match target {
+                    Target::Source(index) => NodeTarget::Source(index),
+                    Target::Effect { bus, index } => NodeTarget::BusEffect { bus, index },
+                };
+                let params = params.into_iter().map(|(name, value)| (name, param_value(value))).collect();
+                engine.edit(EditCommand::SetParams { target, params }).map(|_| Reply::Ok)?
+            }
+            Command::AddEffect { bus, node } => {
+                let node = node_descriptor(node);
+                engine.edit(EditCommand::AddBusEffect { bus, index: None, node }).map(|_| Reply::Ok)?
+            }
+            Command::RemoveEffect { bus, index } => {
+                engine.edit(EditCommand::RemoveBusEffect { bus, index }).map(|_| Reply::Ok)?
+            }
+            Command::Undo => Reply::Done(engine.undo()?),
+            Command::Redo => Reply::Done(engine.redo()?),
+            Command::LoadProject(text) => engine.restore(ProjectFile::from_ron(&text)?).map(|_| Reply::Ok)?,
+            Command::SaveProject => Reply::Project(engine.snapshot().to_ron()?),
+            Command::Meters { interval_ms } => {
+                peer.meter_interval_ms.store(interval_ms, Ordering::Relaxed);
+                *peer.next_meter.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+                Reply::Ok
+            }
+            // handled by the client loop once the reply is sent
+            Command::Shutdown => Reply::Ok,
+        };
+        self.output.refresh(&engine);
+        Ok(reply)
+    }
+
+    fn meter_loop(&self) {
+        let meter = self.engine().meter_handle();
+        while !self.is_shut_down() {
+            thread::sleep(METER_TICK);
+            let now = Instant::now();
+            let due: Vec<Arc<Peer>> = self
+                .peers()
+                .iter()
+                .filter(|peer| {
+                    let interval = peer.meter_interval_ms.load(Ordering::Relaxed);
+                    let mut next = peer.next_meter.lock().unwrap_or_else(|e| e.into_inner());
+                    if interval == 0 || now < *next {
+                        return false;
+                    }
+                    *next = now + Duration::from_millis(interval as u64);
+                    true
+                })
+                .cloned()
+                .collect();
+            if due.is_empty() {
+                continue;
+            }
+
+            let reading = meter.load();
+            let message = ServerMessage::Event(Event::Meter(Meter {
+                peak: reading.peak[..reading.channels].to_vec(),
+                rms: reading.rms[..reading.channels].to_vec(),
+                frames: self.engine().frame_count(),
+            }));
+            for peer in due {
+                let _ = peer.send(&message);
+            }
+        }
+    }
+
+    fn broadcast(&self, message: &ServerMessage) {
+        let peers: Vec<Arc<Peer>> = self.peers().clone();
+        for peer in peers {
+            let _ = peer.send(message);
+        }
+    }
+
+    fn engine(&self) -> MutexGuard<'_, Engine> {
+        self.engine.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn peers(&self) -> MutexGuard<'_, Vec<Arc<Peer>>> {
+        self.peers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn node_descriptor(node: Node) -> NodeDescriptor {
+    NodeDescriptor { kind: node.kind, params: node.params.into_iter().map(|(k, v)| (k, param_value(v))).collect() }
+}
+
+fn param_value(value: Value) -> ParamValue {
+    match value {
+        Value::Float(v) => ParamValue::Float(v),
+        Value::Int(v) => ParamValue::Int(v),
+        Value::Bool(v) => ParamValue::Bool(v),
+        Value::Text(v) => ParamValue::Text(v),
+    }
+}
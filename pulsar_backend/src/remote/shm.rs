@@ -0,0 +1,91 @@
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use pulsar_client::shm::SharedRing;
+
+use crate::rt_processing::effects::Effect;
+use crate::rt_processing::routing::AudioSource;
+
+/// Source playing audio another process writes into a `SharedRing`.
+///
+/// Reads one block per render; frames the producer hasn't delivered yet play
+/// as silence (counted as ring underruns). Engine channels beyond the ring's
+/// wrap around its channels, so a mono ring feeds every channel.
+pub struct SharedInput {
+    ring: Arc<SharedRing>,
+    scratch: Vec<f32>,
+}
+
+impl SharedInput {
+    /// Open the ring at `path`. Blocks are read through a buffer the size of
+    /// the ring, which is as much as a read can ever return.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let ring = SharedRing::open(path)?;
+        let scratch = vec![0.0; ring.capacity() * ring.channels()];
+        Ok(Self { ring: Arc::new(ring), scratch })
+    }
+
+    /// The ring, e.g. to watch its underrun count
+    pub fn ring(&self) -> Arc<SharedRing> {
+        Arc::clone(&self.ring)
+    }
+}
+
+impl AudioSource for SharedInput {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        let ring_channels = self.ring.channels();
+        let available = frames.min(self.scratch.len() / ring_channels);
+        let block = &mut self.scratch[..available * ring_channels];
+        let read = self.ring.read(block);
+        block[read * ring_channels..].fill(0.0);
+
+        // a block longer than the whole ring plays its tail as silence
+        for (ch, out) in output.iter_mut().enumerate() {
+            let source = ch % ring_channels;
+            for (i, sample) in out[..available].iter_mut().enumerate() {
+                *sample = block[i * ring_channels + source];
+            }
+            out[available..frames].fill(0.0);
+        }
+    }
+}
+
+/// Pass-through insert that copies the bus audio into a `SharedRing` for
+/// another process to consume.
+///
+/// Blocks the consumer hasn't made room for are dropped (counted as ring
+/// overruns) rather than waited on. Ring channels beyond the engine's are
+/// written as silence.
+pub struct SharedOutput {
+    ring: Arc<SharedRing>,
+    scratch: Vec<f32>,
+}
+
+impl SharedOutput {
+    /// Open the ring at `path`, sized for blocks of up to `max_frames`
+    pub fn open(path: impl AsRef<Path>, max_frames: usize) -> io::Result<Self> {
+        let ring = SharedRing::open(path)?;
+        let scratch = vec![0.0; max_frames * ring.channels()];
+        Ok(Self { ring: Arc::new(ring), scratch })
+    }
+
+    /// The ring, e.g. to watch its overrun count
+    pub fn ring(&self) -> Arc<SharedRing> {
+        Arc::clone(&self.ring)
+    }
+}
+
+impl Effect for SharedOutput {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        let ring_channels = self.ring.channels();
+        let frames = frames.min(self.scratch.len() / ring_channels);
+        let block = &mut self.scratch[..frames * ring_channels];
+        for (i, frame) in block.chunks_exact_mut(ring_channels).enumerate() {
+            for (ch, sample) in frame.iter_mut().enumerate() {
+                *sample = buffer.get(ch).map_or(0.0, |channel| channel[i]);
+            }
+        }
+        self.ring.write(block);
+    }
+}
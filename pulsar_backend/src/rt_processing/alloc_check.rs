@@ -0,0 +1,94 @@
+//! Debug check against heap allocation on the audio path.
+//!
+//! The router marks the thread while it mixes (`enter_audio_path`). A binary
+//! that installs `RtAllocCheck` as its global allocator then panics, in debug
+//! builds, on any allocation or free made while the mark is set, naming the
+//! size:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOC: pulsar_backend::rt_processing::alloc_check::RtAllocCheck =
+//!     pulsar_backend::rt_processing::alloc_check::RtAllocCheck;
+//! ```
+//!
+//! Release builds forward straight to the system allocator, and marking the
+//! thread compiles to nothing.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+    static IN_AUDIO_PATH: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks the current thread as on the audio path until dropped; nests
+pub struct AudioPathGuard {
+    #[cfg(debug_assertions)]
+    previous: bool,
+}
+
+#[inline]
+pub fn enter_audio_path() -> AudioPathGuard {
+    AudioPathGuard {
+        #[cfg(debug_assertions)]
+        previous: IN_AUDIO_PATH.try_with(|flag| flag.replace(true)).unwrap_or(false),
+    }
+}
+
+impl Drop for AudioPathGuard {
+    #[inline]
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        let _ = IN_AUDIO_PATH.try_with(|flag| flag.set(self.previous));
+    }
+}
+
+/// Run `f` with allocation allowed, e.g. for a one-off lazy init a node
+/// knowingly does on its first block
+pub fn allow_alloc<R>(f: impl FnOnce() -> R) -> R {
+    let previous = IN_AUDIO_PATH.try_with(|flag| flag.replace(false)).unwrap_or(false);
+    let result = f();
+    let _ = IN_AUDIO_PATH.try_with(|flag| flag.set(previous));
+    result
+}
+
+pub fn is_on_audio_path() -> bool {
+    IN_AUDIO_PATH.try_with(Cell::get).unwrap_or(false)
+}
+
+/// System allocator that panics on allocation or free inside the audio path
+/// (debug builds)
+pub struct RtAllocCheck;
+
+impl RtAllocCheck {
+    #[inline]
+    fn check(what: &str, size: usize) {
+        // clear the mark first: the panic itself allocates
+        if cfg!(debug_assertions) && IN_AUDIO_PATH.try_with(|flag| flag.replace(false)).unwrap_or(false) {
+            panic!("heap {} of {} bytes on the audio path", what, size);
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for RtAllocCheck {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::check("allocation", layout.size());
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        Self::check("allocation", layout.size());
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::check("allocation", new_size);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // free first, so the panic doesn't leak the block
+        unsafe { System.dealloc(ptr, layout) };
+        Self::check("deallocation", layout.size());
+    }
+}
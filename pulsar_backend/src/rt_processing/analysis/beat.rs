@@ -0,0 +1,278 @@
+use std::sync::Arc;
+
+use crossbeam::atomic::AtomicCell;
+
+use crate::rt_processing::events::{EngineEvent, EventPublisher};
+
+use super::Analyzer;
+use super::onset::{OnsetDetector, OnsetDetectorConfig, OnsetFrame};
+
+/// Current tempo estimate. `bpm == 0.0` until enough audio has been analyzed.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TempoReading {
+    pub bpm: f32,
+    /// Normalized autocorrelation peak (0.0 to 1.0)
+    pub confidence: f32,
+}
+
+/// Beat tracker configuration
+#[derive(Copy, Clone, Debug)]
+pub struct BeatTrackerConfig {
+    pub onset: OnsetDetectorConfig,
+    pub min_bpm: f32,
+    pub max_bpm: f32,
+    /// Tempo the estimator leans toward when peaks are ambiguous
+    pub preferred_bpm: f32,
+    /// Seconds of detection-function history used for tempo estimation
+    pub history_seconds: f32,
+    /// Seconds between tempo re-estimations
+    pub update_interval: f32,
+    /// Fraction of the beat period within which an onset pulls the beat phase
+    pub phase_tolerance: f32,
+}
+
+impl Default for BeatTrackerConfig {
+    fn default() -> Self {
+        Self {
+            onset: OnsetDetectorConfig::default(),
+            min_bpm: 60.0,
+            max_bpm: 180.0,
+            preferred_bpm: 120.0,
+            history_seconds: 6.0,
+            update_interval: 1.0,
+            phase_tolerance: 0.2,
+        }
+    }
+}
+
+/// Simple autocorrelation beat tracker.
+///
+/// Builds on `OnsetDetector`: the detection function is kept in a history window and
+/// periodically autocorrelated to estimate tempo; beat phase is predicted from the
+/// period and nudged toward onsets that land close to a predicted beat. Beats and
+/// tempo changes are published on the event bus, and the latest tempo is exposed
+/// through a shared handle for transport auto-sync.
+pub struct BeatTracker {
+    onsets: OnsetDetector,
+    state: BeatState,
+}
+
+/// Tempo/phase state, kept apart from the onset detector so both can be borrowed
+/// mutably while a block is analyzed.
+struct BeatState {
+    config: BeatTrackerConfig,
+    hop_size: usize,
+    publisher: Option<EventPublisher>,
+    odf: Vec<f32>,
+    odf_pos: usize,
+    odf_filled: usize,
+    acf: Vec<f32>,
+    sample_rate: f32,
+    hops_since_update: usize,
+    period_frames: f64,
+    next_beat: Option<f64>,
+    tempo: Arc<AtomicCell<TempoReading>>,
+}
+
+impl BeatTracker {
+    pub fn new(config: BeatTrackerConfig) -> Self {
+        let onsets = OnsetDetector::new(config.onset);
+        let hop_size = onsets.config().hop_size;
+        Self {
+            onsets,
+            state: BeatState::new(config, hop_size),
+        }
+    }
+
+    /// Publish onsets, beats and tempo changes on the given bus
+    pub fn with_publisher(mut self, publisher: EventPublisher) -> Self {
+        self.onsets.set_publisher(Some(publisher.clone()));
+        self.state.publisher = Some(publisher);
+        self
+    }
+
+    pub fn tempo_handle(&self) -> Arc<AtomicCell<TempoReading>> {
+        Arc::clone(&self.state.tempo)
+    }
+
+    pub fn tempo(&self) -> TempoReading {
+        self.state.tempo.load()
+    }
+
+    /// Size the history buffers for `sample_rate`. Called automatically when the
+    /// rate changes; call ahead of time to keep allocation off the audio thread.
+    pub fn prepare(&mut self, sample_rate: f32) {
+        self.state.prepare(sample_rate);
+    }
+}
+
+impl BeatState {
+    fn new(config: BeatTrackerConfig, hop_size: usize) -> Self {
+        Self {
+            config,
+            hop_size,
+            publisher: None,
+            odf: Vec::new(),
+            odf_pos: 0,
+            odf_filled: 0,
+            acf: Vec::new(),
+            sample_rate: 0.0,
+            hops_since_update: 0,
+            period_frames: 0.0,
+            next_beat: None,
+            tempo: Arc::new(AtomicCell::new(TempoReading::default())),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.odf.fill(0.0);
+        self.odf_pos = 0;
+        self.odf_filled = 0;
+        self.hops_since_update = 0;
+        self.period_frames = 0.0;
+        self.next_beat = None;
+        self.tempo.store(TempoReading::default());
+    }
+
+    fn prepare(&mut self, sample_rate: f32) {
+        let hop_rate = sample_rate / self.hop_size as f32;
+        let len = ((self.config.history_seconds * hop_rate) as usize).max(16);
+        self.odf = vec![0.0; len];
+        self.acf = vec![0.0; len / 2];
+        self.odf_pos = 0;
+        self.odf_filled = 0;
+        self.sample_rate = sample_rate;
+    }
+
+    fn hop_rate(&self) -> f32 {
+        self.sample_rate / self.hop_size as f32
+    }
+
+    fn on_hop(&mut self, hop: OnsetFrame) {
+        self.odf[self.odf_pos] = hop.odf;
+        self.odf_pos = (self.odf_pos + 1) % self.odf.len();
+        self.odf_filled = (self.odf_filled + 1).min(self.odf.len());
+
+        self.hops_since_update += 1;
+        let update_hops = (self.config.update_interval * self.hop_rate()).max(1.0) as usize;
+        if self.hops_since_update >= update_hops && self.odf_filled == self.odf.len() {
+            self.hops_since_update = 0;
+            self.estimate_tempo();
+        }
+
+        self.track_phase(hop);
+    }
+
+    fn estimate_tempo(&mut self) {
+        let hop_rate = self.hop_rate();
+        let n = self.odf.len();
+        let lag_min = ((60.0 * hop_rate / self.config.max_bpm) as usize).max(1);
+        let lag_max = ((60.0 * hop_rate / self.config.min_bpm) as usize).min(self.acf.len() - 1);
+        if lag_min + 1 >= lag_max {
+            return;
+        }
+
+        // remove the mean so sustained material doesn't dominate the correlation
+        let mean = self.odf.iter().sum::<f32>() / n as f32;
+        let at = |i: usize| self.odf[(self.odf_pos + i) % n] - mean;
+        let energy: f32 = (0..n).map(|i| at(i) * at(i)).sum();
+        if energy <= f32::EPSILON {
+            return;
+        }
+
+        for lag in lag_min..=lag_max {
+            let sum: f32 = (0..n - lag).map(|i| at(i) * at(i + lag)).sum();
+            self.acf[lag] = sum / energy;
+        }
+
+        // log-gaussian weighting around the preferred tempo resolves octave errors
+        let preferred_lag = 60.0 * hop_rate / self.config.preferred_bpm;
+        let weight = |lag: usize| {
+            let octaves = (lag as f32 / preferred_lag).log2();
+            (-0.5 * octaves * octaves / (0.9 * 0.9)).exp()
+        };
+        let Some(best) = (lag_min..=lag_max)
+            .max_by(|&a, &b| (self.acf[a] * weight(a)).total_cmp(&(self.acf[b] * weight(b))))
+        else {
+            return;
+        };
+        let confidence = self.acf[best].clamp(0.0, 1.0);
+        if confidence <= 0.0 {
+            return;
+        }
+
+        let refined = if best > lag_min && best < lag_max {
+            let (a, b, c) = (self.acf[best - 1], self.acf[best], self.acf[best + 1]);
+            let denom = a + c - 2.0 * b;
+            if denom.abs() > f32::EPSILON {
+                best as f32 + 0.5 * (a - c) / denom
+            } else {
+                best as f32
+            }
+        } else {
+            best as f32
+        };
+
+        let bpm = 60.0 * hop_rate / refined;
+        let previous = self.tempo.load();
+        self.tempo.store(TempoReading { bpm, confidence });
+        self.period_frames = (refined * self.hop_size as f32) as f64;
+
+        if (bpm - previous.bpm).abs() > 1.0
+            && let Some(publisher) = &self.publisher
+        {
+            publisher.publish(EngineEvent::TempoEstimate { bpm, confidence });
+        }
+    }
+
+    fn track_phase(&mut self, hop: OnsetFrame) {
+        if self.period_frames <= 0.0 {
+            return;
+        }
+        let now = hop.frame as f64;
+        let period = self.period_frames;
+
+        if let Some(strength) = hop.onset {
+            match self.next_beat {
+                None => self.next_beat = Some(now),
+                Some(predicted) => {
+                    // distance to the nearest predicted beat (previous or next)
+                    let error = [predicted - period, predicted]
+                        .into_iter()
+                        .map(|b| now - b)
+                        .min_by(|a, b| a.abs().total_cmp(&b.abs()))
+                        .unwrap_or(0.0);
+                    if error.abs() < period * self.config.phase_tolerance as f64 && strength > 0.0 {
+                        self.next_beat = Some(predicted + 0.5 * error);
+                    }
+                }
+            }
+        }
+
+        while let Some(beat) = self.next_beat {
+            if beat > now {
+                break;
+            }
+            let bpm = self.tempo.load().bpm;
+            if let Some(publisher) = &self.publisher {
+                publisher.publish(EngineEvent::Beat { frame: beat.max(0.0) as u64, bpm });
+            }
+            self.next_beat = Some(beat + period);
+        }
+    }
+}
+
+impl Analyzer for BeatTracker {
+    fn analyze(&mut self, input: &[&[f32]], frames: usize, sample_rate: f32) {
+        if self.state.sample_rate != sample_rate || self.state.odf.is_empty() {
+            self.state.prepare(sample_rate);
+        }
+        let state = &mut self.state;
+        self.onsets.analyze_mixed(input, frames, sample_rate, |hop| state.on_hop(hop));
+    }
+
+    fn reset(&mut self) {
+        self.onsets.reset();
+        self.state.reset();
+    }
+}
@@ -0,0 +1,122 @@
+use std::f64::consts::PI;
+
+use crate::rt_processing::filters::{BiquadCoefficients, BiquadState};
+
+/// Loudness reported for silence
+pub const SILENT_LUFS: f32 = -120.0;
+
+/// ITU-R BS.1770 K-weighting: a +4 dB high shelf (head diffraction) followed by
+/// a ~38 Hz high-pass (RLB curve). One instance filters one channel.
+#[derive(Copy, Clone, Debug)]
+pub struct KWeighting {
+    shelf: BiquadCoefficients,
+    high_pass: BiquadCoefficients,
+    states: [BiquadState; 2],
+}
+
+impl KWeighting {
+    /// Coefficients are re-derived for `sample_rate` from the analog prototype
+    /// that reproduces the 48 kHz values tabulated in the standard.
+    pub fn new(sample_rate: f32) -> Self {
+        let sample_rate = sample_rate as f64;
+
+        let k = (PI * 1_681.974_450_955_533 / sample_rate).tan();
+        let q = 0.707_175_236_955_419_6;
+        let vh = 10f64.powf(3.999_843_853_973_347 / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = BiquadCoefficients {
+            b0: ((vh + vb * k / q + k * k) / a0) as f32,
+            b1: (2.0 * (k * k - vh) / a0) as f32,
+            b2: ((vh - vb * k / q + k * k) / a0) as f32,
+            a1: (2.0 * (k * k - 1.0) / a0) as f32,
+            a2: ((1.0 - k / q + k * k) / a0) as f32,
+        };
+
+        let k = (PI * 38.135_470_876_024_44 / sample_rate).tan();
+        let q = 0.500_327_037_323_877_3;
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = BiquadCoefficients {
+            b0: 1.0,
+            b1: -2.0,
+            b2: 1.0,
+            a1: (2.0 * (k * k - 1.0) / a0) as f32,
+            a2: ((1.0 - k / q + k * k) / a0) as f32,
+        };
+
+        Self { shelf, high_pass, states: [BiquadState::default(); 2] }
+    }
+
+    #[inline(always)]
+    pub fn process(&mut self, x: f32) -> f32 {
+        let [shelf, high_pass] = &mut self.states;
+        high_pass.process(&self.high_pass, shelf.process(&self.shelf, x))
+    }
+
+    pub fn reset(&mut self) {
+        self.states.iter_mut().for_each(BiquadState::reset);
+    }
+}
+
+/// BS.1770 channel weight for channel `index` of a `channels`-wide signal.
+/// For 5.1 (L R C LFE Ls Rs) the LFE is excluded and the surrounds get +1.5 dB.
+pub fn channel_weight(index: usize, channels: usize) -> f32 {
+    match (channels, index) {
+        (6, 3) => 0.0,
+        (6, 4) | (6, 5) => 1.41,
+        _ => 1.0,
+    }
+}
+
+#[inline]
+fn power_to_lufs(power: f64) -> f32 {
+    if power > 0.0 { (-0.691 + 10.0 * power.log10()) as f32 } else { SILENT_LUFS }
+}
+
+/// Gated integrated loudness (BS.1770-4) of non-interleaved audio, in LUFS.
+///
+/// 400 ms blocks with 75% overlap; blocks below -70 LUFS are dropped, then blocks
+/// more than 10 LU below the remaining average. Offline helper: allocates.
+pub fn integrated_loudness(channels: &[Vec<f32>], sample_rate: f32) -> f32 {
+    let frames = channels.iter().map(Vec::len).min().unwrap_or(0);
+    let block = (0.4 * sample_rate) as usize;
+    let step = block / 4;
+    if frames < block || step == 0 {
+        return SILENT_LUFS;
+    }
+
+    // K-weighted power per 100 ms step, summed over weighted channels
+    let steps = frames / step;
+    let mut step_power = vec![0.0f64; steps];
+    for (index, samples) in channels.iter().enumerate() {
+        let weight = channel_weight(index, channels.len()) as f64;
+        if weight == 0.0 {
+            continue;
+        }
+        let mut filter = KWeighting::new(sample_rate);
+        for (power, chunk) in step_power.iter_mut().zip(samples.chunks_exact(step)) {
+            let energy: f64 = chunk.iter().map(|&s| (filter.process(s) as f64).powi(2)).sum();
+            *power += weight * energy;
+        }
+    }
+
+    // mean power of each 400 ms block (4 steps)
+    let blocks: Vec<f64> = step_power.windows(4).map(|w| w.iter().sum::<f64>() / block as f64).collect();
+
+    let absolute: Vec<f64> = blocks.into_iter().filter(|&p| power_to_lufs(p) > -70.0).collect();
+    if absolute.is_empty() {
+        return SILENT_LUFS;
+    }
+    let relative_gate = power_to_lufs(absolute.iter().sum::<f64>() / absolute.len() as f64) - 10.0;
+    let gated: Vec<f64> = absolute.into_iter().filter(|&p| power_to_lufs(p) > relative_gate).collect();
+    if gated.is_empty() {
+        return SILENT_LUFS;
+    }
+    power_to_lufs(gated.iter().sum::<f64>() / gated.len() as f64)
+}
+
+/// Sample peak over all channels, in dBFS
+pub fn sample_peak_db(channels: &[Vec<f32>]) -> f32 {
+    let peak = channels.iter().flatten().fold(0.0f32, |m, s| m.max(s.abs()));
+    if peak > 0.0 { 20.0 * peak.log10() } else { SILENT_LUFS }
+}
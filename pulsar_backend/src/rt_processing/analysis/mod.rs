@@ -0,0 +1,18 @@
+pub mod pitch;
+pub mod pitch_to_midi;
+pub mod onset;
+pub mod beat;
+pub mod spectrum;
+pub mod loudness;
+
+/// Block-rate analyzer fed from a capture (or any other) signal path.
+///
+/// Analyzers only observe audio; they never modify it. Results are published
+/// through lock-free handles so UI/control threads can poll them.
+pub trait Analyzer: Send {
+    /// Feed one block of non-interleaved `[channel][frame]` audio.
+    fn analyze(&mut self, input: &[&[f32]], frames: usize, sample_rate: f32);
+
+    /// Drop all accumulated history.
+    fn reset(&mut self);
+}
@@ -0,0 +1,192 @@
+use crate::rt_processing::events::{EngineEvent, EventPublisher};
+
+use super::Analyzer;
+
+/// Onset detector configuration
+#[derive(Copy, Clone, Debug)]
+pub struct OnsetDetectorConfig {
+    /// Samples per detection-function frame
+    pub hop_size: usize,
+    /// Detection-function frames used for the adaptive threshold
+    pub threshold_window: usize,
+    /// Multiplier over the local mean needed to fire (higher = less sensitive)
+    pub sensitivity: f32,
+    /// Absolute floor on the detection function (log-energy rise)
+    pub min_strength: f32,
+    /// Minimum time between onsets in seconds
+    pub min_interval: f32,
+}
+
+impl Default for OnsetDetectorConfig {
+    fn default() -> Self {
+        Self {
+            hop_size: 256,
+            threshold_window: 16,
+            sensitivity: 1.5,
+            min_strength: 0.5,
+            min_interval: 0.05,
+        }
+    }
+}
+
+/// One detection-function frame
+#[derive(Copy, Clone, Debug)]
+pub struct OnsetFrame {
+    /// Frame position (in samples) at the end of the hop
+    pub frame: u64,
+    /// Detection function value (positive log-energy flux)
+    pub odf: f32,
+    /// Onset strength if an onset fired on this hop
+    pub onset: Option<f32>,
+}
+
+/// Energy-flux onset detector.
+///
+/// The input is first-differenced (emphasizing transients over sustained low end),
+/// log energy is computed per hop and its positive rise forms the detection
+/// function. Onsets fire on peaks above an adaptive mean threshold and are
+/// published as `EngineEvent::Onset` when a publisher is attached.
+pub struct OnsetDetector {
+    config: OnsetDetectorConfig,
+    publisher: Option<EventPublisher>,
+    prev_input: f32,
+    hop_energy: f32,
+    hop_fill: usize,
+    prev_log_energy: f32,
+    odf_history: Vec<f32>,
+    history_pos: usize,
+    prev_odf: f32,
+    rising: bool,
+    frame: u64,
+    last_onset: Option<u64>,
+}
+
+impl OnsetDetector {
+    pub fn new(config: OnsetDetectorConfig) -> Self {
+        let config = OnsetDetectorConfig {
+            hop_size: config.hop_size.max(16),
+            threshold_window: config.threshold_window.max(1),
+            ..config
+        };
+        Self {
+            config,
+            publisher: None,
+            prev_input: 0.0,
+            hop_energy: 0.0,
+            hop_fill: 0,
+            prev_log_energy: f32::NEG_INFINITY,
+            odf_history: vec![0.0; config.threshold_window],
+            history_pos: 0,
+            prev_odf: 0.0,
+            rising: false,
+            frame: 0,
+            last_onset: None,
+        }
+    }
+
+    pub fn with_publisher(mut self, publisher: EventPublisher) -> Self {
+        self.publisher = Some(publisher);
+        self
+    }
+
+    pub fn set_publisher(&mut self, publisher: Option<EventPublisher>) {
+        self.publisher = publisher;
+    }
+
+    pub fn config(&self) -> &OnsetDetectorConfig {
+        &self.config
+    }
+
+    /// Frames analyzed so far
+    pub fn frame_position(&self) -> u64 {
+        self.frame
+    }
+
+    /// Push mono samples, calling `on_hop` for every completed detection frame
+    pub fn push_samples(&mut self, samples: &[f32], sample_rate: f32, mut on_hop: impl FnMut(OnsetFrame)) {
+        for &x in samples {
+            let diff = x - self.prev_input;
+            self.prev_input = x;
+            self.hop_energy += diff * diff;
+            self.hop_fill += 1;
+            self.frame += 1;
+
+            if self.hop_fill == self.config.hop_size {
+                let result = self.finish_hop(sample_rate);
+                on_hop(result);
+            }
+        }
+    }
+
+    fn finish_hop(&mut self, sample_rate: f32) -> OnsetFrame {
+        let log_energy = (self.hop_energy / self.config.hop_size as f32 + 1e-10).ln();
+        self.hop_energy = 0.0;
+        self.hop_fill = 0;
+
+        let odf = if self.prev_log_energy.is_finite() {
+            (log_energy - self.prev_log_energy).max(0.0)
+        } else {
+            0.0
+        };
+        self.prev_log_energy = log_energy;
+
+        let mean = self.odf_history.iter().sum::<f32>() / self.odf_history.len() as f32;
+        self.odf_history[self.history_pos] = odf;
+        self.history_pos = (self.history_pos + 1) % self.odf_history.len();
+
+        // fire on the first hop of a rise that clears the adaptive threshold
+        let threshold = (mean * self.config.sensitivity).max(self.config.min_strength);
+        let min_gap = (self.config.min_interval * sample_rate) as u64;
+        let spaced = self.last_onset.is_none_or(|last| self.frame - last >= min_gap);
+        let onset = (odf > threshold && !self.rising && spaced).then_some(odf);
+        self.rising = odf > self.prev_odf && odf > threshold;
+        self.prev_odf = odf;
+
+        if let Some(strength) = onset {
+            self.last_onset = Some(self.frame);
+            if let Some(publisher) = &self.publisher {
+                publisher.publish(EngineEvent::Onset { frame: self.frame, strength });
+            }
+        }
+
+        OnsetFrame { frame: self.frame, odf, onset }
+    }
+
+    /// Average the channels of a block into a mono detection input
+    pub(crate) fn analyze_mixed(
+        &mut self,
+        input: &[&[f32]],
+        frames: usize,
+        sample_rate: f32,
+        mut on_hop: impl FnMut(OnsetFrame),
+    ) {
+        let channels = input.len();
+        if channels == 0 {
+            return;
+        }
+        let scale = 1.0 / channels as f32;
+        for i in 0..frames {
+            let mono = input.iter().map(|ch| ch[i]).sum::<f32>() * scale;
+            self.push_samples(&[mono], sample_rate, &mut on_hop);
+        }
+    }
+}
+
+impl Analyzer for OnsetDetector {
+    fn analyze(&mut self, input: &[&[f32]], frames: usize, sample_rate: f32) {
+        self.analyze_mixed(input, frames, sample_rate, |_| {});
+    }
+
+    fn reset(&mut self) {
+        self.prev_input = 0.0;
+        self.hop_energy = 0.0;
+        self.hop_fill = 0;
+        self.prev_log_energy = f32::NEG_INFINITY;
+        self.odf_history.fill(0.0);
+        self.history_pos = 0;
+        self.prev_odf = 0.0;
+        self.rising = false;
+        self.frame = 0;
+        self.last_onset = None;
+    }
+}
@@ -0,0 +1,266 @@
+use std::sync::Arc;
+
+use crossbeam::atomic::AtomicCell;
+
+use super::Analyzer;
+use crate::rt_processing::fft::{Complex, Fft};
+
+/// Latest detected pitch.
+/// `frequency == 0.0` means the signal was unvoiced or too quiet.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PitchReading {
+    /// Fundamental frequency in Hz
+    pub frequency: f32,
+    /// Detection confidence (0.0 to 1.0)
+    pub confidence: f32,
+}
+
+impl PitchReading {
+    pub const UNVOICED: Self = Self { frequency: 0.0, confidence: 0.0 };
+
+    pub fn is_voiced(&self) -> bool {
+        self.frequency > 0.0
+    }
+
+    /// Fractional MIDI note number (A4 = 69 = 440 Hz)
+    pub fn midi_note(&self) -> Option<f32> {
+        self.is_voiced()
+            .then(|| 69.0 + 12.0 * (self.frequency / 440.0).log2())
+    }
+
+    /// Nearest MIDI note and the deviation from it in cents (tuner display)
+    pub fn nearest_note(&self) -> Option<(u8, f32)> {
+        let note = self.midi_note()?;
+        let nearest = note.round();
+        Some((nearest.clamp(0.0, 127.0) as u8, (note - nearest) * 100.0))
+    }
+}
+
+/// Pitch detector configuration
+#[derive(Copy, Clone, Debug)]
+pub struct PitchDetectorConfig {
+    /// Analysis window in samples (must cover at least two periods of `min_frequency`)
+    pub window_size: usize,
+    /// Samples between detections
+    pub hop_size: usize,
+    pub min_frequency: f32,
+    pub max_frequency: f32,
+    /// YIN absolute threshold (typically 0.1..0.2, lower = stricter)
+    pub threshold: f32,
+    /// RMS level below which the block is treated as silence
+    pub silence_rms: f32,
+}
+
+impl PitchDetectorConfig {
+    /// Settings suitable for a guitar/bass tuner
+    pub fn tuner() -> Self {
+        Self {
+            window_size: 4096,
+            hop_size: 1024,
+            min_frequency: 30.0,
+            max_frequency: 1500.0,
+            threshold: 0.1,
+            silence_rms: 0.005,
+        }
+    }
+
+    /// Smaller window and hop for lower latency voice/lead tracking
+    pub fn voice() -> Self {
+        Self {
+            window_size: 2048,
+            hop_size: 256,
+            min_frequency: 70.0,
+            max_frequency: 1200.0,
+            threshold: 0.15,
+            silence_rms: 0.01,
+        }
+    }
+}
+
+impl Default for PitchDetectorConfig {
+    fn default() -> Self {
+        Self::voice()
+    }
+}
+
+/// Monophonic YIN pitch detector.
+///
+/// Feed it blocks through `Analyzer::analyze` (channel 0 is analyzed); every
+/// `hop_size` samples a detection runs over the last `window_size` samples and the
+/// result is published to the shared `AtomicCell`. The difference function is
+/// worked out from an FFT cross-correlation, O(N log N) per detection, and all
+/// buffers are allocated up front, so the analyzer can run on the audio thread;
+/// on the capture path it goes on the input source (`InputSource::with_analyzer`).
+pub struct PitchDetector {
+    config: PitchDetectorConfig,
+    history: Vec<f32>,
+    write_pos: usize,
+    filled: usize,
+    since_last: usize,
+    window: Vec<f32>,
+    difference: Vec<f32>,
+    fft: Fft,
+    // spectra of the integration window and of the whole window
+    head: Vec<Complex>,
+    spectrum: Vec<Complex>,
+    reading: Arc<AtomicCell<PitchReading>>,
+}
+
+impl PitchDetector {
+    pub fn new(config: PitchDetectorConfig) -> Self {
+        let window_size = config.window_size.max(64);
+        let config = PitchDetectorConfig {
+            window_size,
+            hop_size: config.hop_size.clamp(1, window_size),
+            ..config
+        };
+
+        Self {
+            config,
+            history: vec![0.0; window_size],
+            write_pos: 0,
+            filled: 0,
+            since_last: 0,
+            window: vec![0.0; window_size],
+            difference: vec![0.0; window_size / 2 + 1],
+            // no wrap-around: lags stop at half the window, so the integration
+            // window shifted by any of them still ends inside it
+            fft: Fft::new(window_size),
+            head: vec![Complex::ZERO; window_size.next_power_of_two()],
+            spectrum: vec![Complex::ZERO; window_size.next_power_of_two()],
+            reading: Arc::new(AtomicCell::new(PitchReading::UNVOICED)),
+        }
+    }
+
+    /// Shared handle for polling the latest reading from other threads
+    pub fn reading_handle(&self) -> Arc<AtomicCell<PitchReading>> {
+        Arc::clone(&self.reading)
+    }
+
+    /// Latest published reading
+    pub fn reading(&self) -> PitchReading {
+        self.reading.load()
+    }
+
+    pub fn config(&self) -> &PitchDetectorConfig {
+        &self.config
+    }
+
+    /// Push mono samples. Returns the new reading if a detection ran during this call.
+    pub fn push_samples(&mut self, samples: &[f32], sample_rate: f32) -> Option<PitchReading> {
+        let mut latest = None;
+        for &s in samples {
+            self.history[self.write_pos] = s;
+            self.write_pos = (self.write_pos + 1) % self.config.window_size;
+            self.filled = (self.filled + 1).min(self.config.window_size);
+            self.since_last += 1;
+
+            if self.since_last >= self.config.hop_size && self.filled == self.config.window_size {
+                self.since_last = 0;
+                let reading = self.detect(sample_rate);
+                self.reading.store(reading);
+                latest = Some(reading);
+            }
+        }
+        latest
+    }
+
+    fn detect(&mut self, sample_rate: f32) -> PitchReading {
+        // unroll the ring buffer so the oldest sample is first
+        let n = self.config.window_size;
+        let (older, newer) = self.history.split_at(self.write_pos);
+        self.window[..newer.len()].copy_from_slice(newer);
+        self.window[newer.len()..n].copy_from_slice(older);
+
+        let rms = (self.window.iter().map(|s| s * s).sum::<f32>() / n as f32).sqrt();
+        if rms < self.config.silence_rms {
+            return PitchReading::UNVOICED;
+        }
+
+        let tau_min = ((sample_rate / self.config.max_frequency) as usize).max(2);
+        let tau_max = ((sample_rate / self.config.min_frequency) as usize).min(n / 2);
+        if tau_min + 2 >= tau_max {
+            return PitchReading::UNVOICED;
+        }
+        let integration = n - tau_max;
+
+        // difference function, expanded as d(tau) = e(0) + e(tau) - 2 r(tau):
+        // e(tau) is the energy of the integration window starting at tau, and
+        // the cross-correlation r(tau) comes from conj(FFT(head)) * FFT(window)
+        for (bin, &s) in self.head.iter_mut().zip(&self.window[..integration]) {
+            *bin = Complex::new(s, 0.0);
+        }
+        self.head[integration..].fill(Complex::ZERO);
+        for (bin, &s) in self.spectrum.iter_mut().zip(&self.window) {
+            *bin = Complex::new(s, 0.0);
+        }
+        self.spectrum[n..].fill(Complex::ZERO);
+        self.fft.forward(&mut self.head);
+        self.fft.forward(&mut self.spectrum);
+        for (bin, &head) in self.spectrum.iter_mut().zip(&self.head) {
+            *bin = head.conj() * *bin;
+        }
+        self.fft.inverse(&mut self.spectrum);
+
+        let energy = self.window[..integration].iter().map(|s| s * s).sum::<f32>();
+        let mut shifted = energy;
+        self.difference[0] = 0.0;
+        for tau in 1..=tau_max {
+            let (leaving, entering) = (self.window[tau - 1], self.window[tau - 1 + integration]);
+            shifted += entering * entering - leaving * leaving;
+            // rounding can take a near-perfect match just below zero
+            self.difference[tau] = (energy + shifted - 2.0 * self.spectrum[tau].re).max(0.0);
+        }
+
+        // cumulative mean normalized difference
+        self.difference[0] = 1.0;
+        let mut running = 0.0;
+        for tau in 1..=tau_max {
+            running += self.difference[tau];
+            self.difference[tau] = if running > 0.0 {
+                self.difference[tau] * tau as f32 / running
+            } else {
+                1.0
+            };
+        }
+
+        // absolute threshold, then walk down to the local minimum
+        let d = &self.difference;
+        let Some(mut tau) = (tau_min..tau_max).find(|&t| d[t] < self.config.threshold) else {
+            return PitchReading::UNVOICED;
+        };
+        while tau + 1 < tau_max && d[tau + 1] < d[tau] {
+            tau += 1;
+        }
+
+        // parabolic interpolation around the minimum
+        let (s0, s1, s2) = (d[tau - 1], d[tau], d[tau + 1]);
+        let denom = s0 + s2 - 2.0 * s1;
+        let refined = if denom.abs() > f32::EPSILON {
+            tau as f32 + 0.5 * (s0 - s2) / denom
+        } else {
+            tau as f32
+        };
+
+        PitchReading {
+            frequency: sample_rate / refined,
+            confidence: (1.0 - s1).clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Analyzer for PitchDetector {
+    fn analyze(&mut self, input: &[&[f32]], frames: usize, sample_rate: f32) {
+        if let Some(first) = input.first() {
+            self.push_samples(&first[..frames], sample_rate);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.write_pos = 0;
+        self.filled = 0;
+        self.since_last = 0;
+        self.reading.store(PitchReading::UNVOICED);
+    }
+}
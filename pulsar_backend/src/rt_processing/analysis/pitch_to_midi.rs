@@ -0,0 +1,195 @@
+use crate::rt_processing::notes::{NoteEvent, NoteSink};
+
+use super::Analyzer;
+use super::pitch::{PitchDetector, PitchDetectorConfig, PitchReading};
+
+/// Pitch-to-MIDI tracking parameters
+#[derive(Copy, Clone, Debug)]
+pub struct PitchToMidiConfig {
+    /// Minimum detector confidence for a reading to count as voiced
+    pub min_confidence: f32,
+    /// Extra cents beyond the half-semitone boundary before switching notes
+    pub hysteresis_cents: f32,
+    /// Consecutive readings on a new note before it is emitted
+    pub stable_readings: u32,
+    /// Consecutive unvoiced readings before the held note is released
+    pub release_readings: u32,
+    /// Block RMS rise (ratio over the tracked level) that counts as a new onset
+    pub onset_ratio: f32,
+    /// Block RMS mapped to full velocity
+    pub full_velocity_rms: f32,
+}
+
+impl Default for PitchToMidiConfig {
+    fn default() -> Self {
+        Self {
+            min_confidence: 0.8,
+            hysteresis_cents: 20.0,
+            stable_readings: 2,
+            release_readings: 3,
+            onset_ratio: 2.0,
+            full_velocity_rms: 0.3,
+        }
+    }
+}
+
+/// Converts detected input pitch and onsets into note events.
+///
+/// Wraps a `PitchDetector`; each detector reading is run through confidence gating,
+/// note hysteresis and a stability count so vibrato and glitches don't produce note
+/// spam. A sharp level rise while a note is held retriggers it (re-picked string,
+/// new syllable). Events go to any `NoteSink` (e.g. a channel into voice allocation).
+pub struct PitchToMidi<S: NoteSink> {
+    detector: PitchDetector,
+    config: PitchToMidiConfig,
+    sink: S,
+    held_note: Option<u8>,
+    candidate: Option<u8>,
+    candidate_count: u32,
+    unvoiced_count: u32,
+    level: f32,
+    block_rms: f32,
+    onset_pending: bool,
+}
+
+impl<S: NoteSink> PitchToMidi<S> {
+    pub fn new(detector_config: PitchDetectorConfig, config: PitchToMidiConfig, sink: S) -> Self {
+        Self {
+            detector: PitchDetector::new(detector_config),
+            config,
+            sink,
+            held_note: None,
+            candidate: None,
+            candidate_count: 0,
+            unvoiced_count: 0,
+            level: 0.0,
+            block_rms: 0.0,
+            onset_pending: false,
+        }
+    }
+
+    pub fn detector(&self) -> &PitchDetector {
+        &self.detector
+    }
+
+    pub fn held_note(&self) -> Option<u8> {
+        self.held_note
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    pub fn set_config(&mut self, config: PitchToMidiConfig) {
+        self.config = config;
+    }
+
+    /// Release the held note, if any
+    pub fn all_notes_off(&mut self) {
+        if let Some(note) = self.held_note.take() {
+            self.sink.send_note(NoteEvent::NoteOff { note });
+        }
+        self.candidate = None;
+        self.candidate_count = 0;
+    }
+
+    fn velocity(&self) -> f32 {
+        (self.block_rms / self.config.full_velocity_rms).clamp(0.05, 1.0)
+    }
+
+    fn track_level(&mut self, samples: &[f32]) {
+        if samples.is_empty() {
+            return;
+        }
+        let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+        if rms > self.level * self.config.onset_ratio && rms > self.detector.config().silence_rms {
+            self.onset_pending = true;
+        }
+        self.block_rms = rms;
+        // fast attack, slow release follower
+        let coeff = if rms > self.level { 0.5 } else { 0.05 };
+        self.level += coeff * (rms - self.level);
+    }
+
+    /// Whether `note_f` (fractional) has left the held note's hysteresis band
+    fn outside_held(&self, held: u8, note_f: f32) -> bool {
+        let limit = 0.5 + self.config.hysteresis_cents / 100.0;
+        (note_f - held as f32).abs() > limit
+    }
+
+    fn on_reading(&mut self, reading: PitchReading) {
+        let note_f = reading
+            .midi_note()
+            .filter(|_| reading.confidence >= self.config.min_confidence);
+
+        let Some(note_f) = note_f else {
+            self.unvoiced_count += 1;
+            self.candidate = None;
+            self.candidate_count = 0;
+            if self.unvoiced_count >= self.config.release_readings {
+                self.all_notes_off();
+            }
+            return;
+        };
+        self.unvoiced_count = 0;
+
+        let note = note_f.round().clamp(0.0, 127.0) as u8;
+
+        if let Some(held) = self.held_note
+            && !self.outside_held(held, note_f)
+        {
+            self.candidate = None;
+            self.candidate_count = 0;
+            if self.onset_pending {
+                self.onset_pending = false;
+                let velocity = self.velocity();
+                self.sink.send_note(NoteEvent::NoteOff { note: held });
+                self.sink.send_note(NoteEvent::NoteOn { note: held, velocity });
+            }
+            return;
+        }
+
+        if self.candidate == Some(note) {
+            self.candidate_count += 1;
+        } else {
+            self.candidate = Some(note);
+            self.candidate_count = 1;
+        }
+
+        if self.candidate_count >= self.config.stable_readings {
+            if let Some(held) = self.held_note.take() {
+                self.sink.send_note(NoteEvent::NoteOff { note: held });
+            }
+            let velocity = self.velocity();
+            self.sink.send_note(NoteEvent::NoteOn { note, velocity });
+            self.held_note = Some(note);
+            self.candidate = None;
+            self.candidate_count = 0;
+            self.onset_pending = false;
+        }
+    }
+}
+
+impl<S: NoteSink> Analyzer for PitchToMidi<S> {
+    fn analyze(&mut self, input: &[&[f32]], frames: usize, sample_rate: f32) {
+        let Some(first) = input.first() else { return };
+        let samples = &first[..frames];
+        self.track_level(samples);
+        if let Some(reading) = self.detector.push_samples(samples, sample_rate) {
+            self.on_reading(reading);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.all_notes_off();
+        self.detector.reset();
+        self.unvoiced_count = 0;
+        self.level = 0.0;
+        self.block_rms = 0.0;
+        self.onset_pending = false;
+    }
+}
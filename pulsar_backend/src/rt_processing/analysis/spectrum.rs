@@ -0,0 +1,188 @@
+use std::sync::Arc;
+
+use crossbeam::atomic::AtomicCell;
+
+use crate::rt_processing::filters::{BiquadCoefficients, BiquadState, OCTAVE_Q};
+
+use super::Analyzer;
+
+/// Number of octave bands in a `NoiseProfile`
+pub const OCTAVE_BANDS: usize = 10;
+
+/// Octave band centers in Hz (31.25 Hz .. 16 kHz)
+pub const OCTAVE_BAND_CENTERS: [f32; OCTAVE_BANDS] =
+    [31.25, 62.5, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0];
+
+/// Level reported for empty or unmeasured bands
+pub const SILENT_DB: f32 = -120.0;
+
+/// Measured spectral profile of a signal
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NoiseProfile {
+    /// RMS level per octave band in dBFS (`SILENT_DB` if above Nyquist or silent)
+    pub band_db: [f32; OCTAVE_BANDS],
+    /// Least-squares slope of the band levels in dB/octave
+    /// (white noise ≈ +3, pink ≈ 0, brown ≈ -3)
+    pub tilt_db_per_octave: f32,
+    /// Broadband RMS level in dBFS
+    pub overall_db: f32,
+}
+
+impl Default for NoiseProfile {
+    fn default() -> Self {
+        Self {
+            band_db: [SILENT_DB; OCTAVE_BANDS],
+            tilt_db_per_octave: 0.0,
+            overall_db: SILENT_DB,
+        }
+    }
+}
+
+impl NoiseProfile {
+    /// Linear RMS amplitude of a band
+    pub fn band_rms(&self, band: usize) -> f32 {
+        db_to_linear(self.band_db[band])
+    }
+}
+
+#[inline]
+fn linear_to_db(x: f32) -> f32 {
+    if x > 0.0 { (20.0 * x.log10()).max(SILENT_DB) } else { SILENT_DB }
+}
+
+#[inline]
+fn db_to_linear(db: f32) -> f32 {
+    if db <= SILENT_DB { 0.0 } else { 10f32.powf(db / 20.0) }
+}
+
+/// Octave-band spectral profile analyzer.
+///
+/// Runs the input through a bank of octave band-pass filters and integrates band
+/// energy over `integration_seconds`. After each integration period the profile
+/// (band levels, tilt, overall level) is published; `FilteredNoise` can then be
+/// configured from it to reproduce the measured noise floor.
+pub struct NoiseProfileAnalyzer {
+    integration_seconds: f32,
+    sample_rate: f32,
+    coeffs: [BiquadCoefficients; OCTAVE_BANDS],
+    states: [BiquadState; OCTAVE_BANDS],
+    band_energy: [f64; OCTAVE_BANDS],
+    total_energy: f64,
+    counted: usize,
+    profile: Arc<AtomicCell<NoiseProfile>>,
+}
+
+impl NoiseProfileAnalyzer {
+    pub fn new(integration_seconds: f32) -> Self {
+        Self {
+            integration_seconds: integration_seconds.max(0.05),
+            sample_rate: 0.0,
+            coeffs: [BiquadCoefficients::IDENTITY; OCTAVE_BANDS],
+            states: [BiquadState::default(); OCTAVE_BANDS],
+            band_energy: [0.0; OCTAVE_BANDS],
+            total_energy: 0.0,
+            counted: 0,
+            profile: Arc::new(AtomicCell::new(NoiseProfile::default())),
+        }
+    }
+
+    pub fn profile_handle(&self) -> Arc<AtomicCell<NoiseProfile>> {
+        Arc::clone(&self.profile)
+    }
+
+    /// Most recently published profile
+    pub fn profile(&self) -> NoiseProfile {
+        self.profile.load()
+    }
+
+    fn prepare(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        for (c, &center) in self.coeffs.iter_mut().zip(&OCTAVE_BAND_CENTERS) {
+            *c = BiquadCoefficients::band_pass(center, OCTAVE_Q, sample_rate);
+        }
+        self.clear_accumulators();
+    }
+
+    fn clear_accumulators(&mut self) {
+        self.states = [BiquadState::default(); OCTAVE_BANDS];
+        self.band_energy = [0.0; OCTAVE_BANDS];
+        self.total_energy = 0.0;
+        self.counted = 0;
+    }
+
+    /// Push mono samples, publishing a profile at the end of each integration period
+    pub fn push_samples(&mut self, samples: &[f32], sample_rate: f32) -> Option<NoiseProfile> {
+        if self.sample_rate != sample_rate {
+            self.prepare(sample_rate);
+        }
+        let period = (self.integration_seconds * sample_rate) as usize;
+        let nyquist_limit = sample_rate * 0.45;
+        let mut published = None;
+
+        for &x in samples {
+            self.total_energy += (x * x) as f64;
+            let bands = self.states.iter_mut().zip(&self.coeffs).zip(&mut self.band_energy);
+            for (((state, coeffs), energy), &center) in bands.zip(&OCTAVE_BAND_CENTERS) {
+                if center < nyquist_limit {
+                    let y = state.process(coeffs, x);
+                    *energy += (y * y) as f64;
+                }
+            }
+            self.counted += 1;
+
+            if self.counted >= period {
+                let profile = self.finish(nyquist_limit);
+                self.profile.store(profile);
+                published = Some(profile);
+                self.band_energy = [0.0; OCTAVE_BANDS];
+                self.total_energy = 0.0;
+                self.counted = 0;
+            }
+        }
+        published
+    }
+
+    fn finish(&self, nyquist_limit: f32) -> NoiseProfile {
+        let n = self.counted.max(1) as f64;
+        let mut profile = NoiseProfile {
+            overall_db: linear_to_db((self.total_energy / n).sqrt() as f32),
+            ..NoiseProfile::default()
+        };
+
+        // least-squares fit of level vs. octave index over measurable bands
+        let (mut sx, mut sy, mut sxx, mut sxy, mut count) = (0.0f32, 0.0f32, 0.0f32, 0.0f32, 0.0f32);
+        for (band, &center) in OCTAVE_BAND_CENTERS.iter().enumerate() {
+            if center >= nyquist_limit {
+                continue;
+            }
+            let db = linear_to_db((self.band_energy[band] / n).sqrt() as f32);
+            profile.band_db[band] = db;
+            if db > SILENT_DB {
+                let x = band as f32;
+                sx += x;
+                sy += db;
+                sxx += x * x;
+                sxy += x * db;
+                count += 1.0;
+            }
+        }
+        let denom = count * sxx - sx * sx;
+        if count >= 2.0 && denom.abs() > f32::EPSILON {
+            profile.tilt_db_per_octave = (count * sxy - sx * sy) / denom;
+        }
+        profile
+    }
+}
+
+impl Analyzer for NoiseProfileAnalyzer {
+    fn analyze(&mut self, input: &[&[f32]], frames: usize, sample_rate: f32) {
+        if let Some(first) = input.first() {
+            self.push_samples(&first[..frames], sample_rate);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.clear_accumulators();
+        self.profile.store(NoiseProfile::default());
+    }
+}
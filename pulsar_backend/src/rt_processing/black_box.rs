@@ -0,0 +1,356 @@
+//! "Black box" recorder: the latest engine events, and optionally the last
+//! seconds of master audio, always kept in memory and written to disk when
+//! something goes wrong.
+//!
+//! Every instrumentation point in `trace` (xruns, skipped callbacks, swaps,
+//! device events) lands here too, with or without the `tracing` feature.
+//! Recording is realtime-safe: events go into a fixed ring that drops the
+//! oldest entry when full, and audio into a preallocated ring. An xrun, a
+//! skipped callback or an error arms a dump; with a dump directory set
+//! (`set_dump_dir`), the global black box's thread writes it out, at most once
+//! every `MIN_DUMP_INTERVAL`, so a glitch that's hard to reproduce can be
+//! looked at after the fact.
+
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crossbeam::queue::ArrayQueue;
+use quanta::{Clock, Instant as QuantaInstant};
+use spin::Mutex;
+
+use crate::io::wav::{SampleFormat, WavResult, WavSpec, WavWriter};
+
+/// Events the global black box keeps
+pub const DEFAULT_BLACK_BOX_EVENTS: usize = 1024;
+/// Longest audio history, in seconds
+pub const MAX_BLACK_BOX_SECONDS: f32 = 60.0;
+/// Shortest time between two automatic dumps
+pub const MIN_DUMP_INTERVAL: Duration = Duration::from_secs(5);
+/// How often the global black box's thread checks for an armed dump
+const DUMP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What happened
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlackBoxEventKind {
+    /// An underrun (`overrun == false`) or overrun of `frames` frames (0 when
+    /// unknown) in the buffer `what` names
+    Xrun { what: &'static str, overrun: bool, frames: u64 },
+    /// The audio callback couldn't take the processor lock and played silence
+    CallbackSkipped { frames: usize },
+    ProcessorSwapped,
+    /// A device appeared, disappeared or became the default
+    Device { kind: &'static str },
+    Error(&'static str),
+    /// Anything else worth seeing next to the audio, from application code
+    Marker(&'static str),
+}
+
+impl BlackBoxEventKind {
+    /// Whether this arms a dump
+    pub fn is_failure(self) -> bool {
+        matches!(self, Self::Xrun { .. } | Self::CallbackSkipped { .. } | Self::Error(_))
+    }
+}
+
+impl fmt::Display for BlackBoxEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Xrun { what, overrun, frames } => {
+                let kind = if *overrun { "overrun" } else { "underrun" };
+                write!(f, "{} in {} ({} frames)", kind, what, frames)
+            }
+            Self::CallbackSkipped { frames } => write!(f, "callback skipped ({} frames)", frames),
+            Self::ProcessorSwapped => f.write_str("processor swapped"),
+            Self::Device { kind } => write!(f, "device {}", kind),
+            Self::Error(what) => write!(f, "error: {}", what),
+            Self::Marker(what) => write!(f, "marker: {}", what),
+        }
+    }
+}
+
+/// One recorded event
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BlackBoxEvent {
+    /// Since the black box was created
+    pub time: Duration,
+    /// Master audio frames captured before it, to line it up with the audio
+    pub frame: u64,
+    pub kind: BlackBoxEventKind,
+}
+
+/// What a dump wrote
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlackBoxDump {
+    pub dir: PathBuf,
+    /// `events.txt`, one line per event, oldest first
+    pub events_path: PathBuf,
+    pub events: usize,
+    /// `master.wav`, if audio was kept
+    pub audio_path: Option<PathBuf>,
+    pub audio_frames: usize,
+}
+
+/// Ring of the last master audio, interleaved
+struct AudioHistory {
+    samples: Vec<f32>,
+    channels: usize,
+    sample_rate: f32,
+    /// Next frame written
+    position: usize,
+    /// Frames holding audio, up to the ring's length
+    filled: usize,
+}
+
+impl AudioHistory {
+    fn new(seconds: f32, sample_rate: f32, channels: usize) -> Self {
+        let frames = (seconds * sample_rate).ceil().max(1.0) as usize;
+        Self { samples: vec![0.0; frames * channels], channels, sample_rate, position: 0, filled: 0 }
+    }
+
+    fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    fn write(&mut self, output: &[f32]) {
+        for frame in output.chunks_exact(self.channels) {
+            let at = self.position * self.channels;
+            self.samples[at..at + self.channels].copy_from_slice(frame);
+            self.position = (self.position + 1) % self.frames();
+        }
+        self.filled = (self.filled + output.len() / self.channels).min(self.frames());
+    }
+
+    /// Everything held, oldest first
+    fn ordered(&self) -> Vec<f32> {
+        let start = (self.position + self.frames() - self.filled) % self.frames() * self.channels;
+        let len = self.filled * self.channels;
+        self.samples.iter().cycle().skip(start).take(len).copied().collect()
+    }
+}
+
+/// The recorder. Cheap to feed from any thread, the audio thread included;
+/// reading it back (`take_events`, `dump`) empties it and is not realtime-safe.
+///
+/// Most code wants the process-wide one, `global()`, which every `trace`
+/// point feeds and the engine captures master audio into.
+pub struct BlackBox {
+    clock: Clock,
+    started: QuantaInstant,
+    events: ArrayQueue<BlackBoxEvent>,
+    audio: Mutex<Option<AudioHistory>>,
+    audio_on: AtomicBool,
+    /// Master frames offered to `capture`, kept or not
+    frames: AtomicU64,
+    /// Frames `capture` couldn't keep while a dump held the ring
+    missed_frames: AtomicU64,
+    armed: AtomicBool,
+    /// Dumps written, numbering their directories
+    dumps: AtomicU64,
+    dump_dir: Mutex<Option<PathBuf>>,
+    last_dump: Mutex<Option<Instant>>,
+}
+
+impl BlackBox {
+    /// Keeps the last `events` events; no audio until `set_audio`
+    pub fn new(events: usize) -> Self {
+        let clock = Clock::new();
+        Self {
+            started: clock.now(),
+            clock,
+            events: ArrayQueue::new(events.max(1)),
+            audio: Mutex::new(None),
+            audio_on: AtomicBool::new(false),
+            frames: AtomicU64::new(0),
+            missed_frames: AtomicU64::new(0),
+            armed: AtomicBool::new(false),
+            dumps: AtomicU64::new(0),
+            dump_dir: Mutex::new(None),
+            last_dump: Mutex::new(None),
+        }
+    }
+
+    /// Keep the last `seconds` of master audio (up to `MAX_BLACK_BOX_SECONDS`),
+    /// or none with 0. Allocates; call off the audio thread. Audio held so far
+    /// is dropped.
+    pub fn set_audio(&self, seconds: f32, sample_rate: f32, channels: usize) {
+        let seconds = seconds.clamp(0.0, MAX_BLACK_BOX_SECONDS);
+        // allocated before taking the lock `capture` tries
+        let history = (seconds > 0.0 && sample_rate > 0.0 && channels > 0)
+            .then(|| AudioHistory::new(seconds, sample_rate, channels));
+        self.audio_on.store(history.is_some(), Ordering::Relaxed);
+        let previous = std::mem::replace(&mut *self.audio.lock(), history);
+        // dropped here, outside the lock
+        drop(previous);
+    }
+
+    /// Seconds of audio kept, 0.0 when off
+    pub fn audio_seconds(&self) -> f32 {
+        self.audio.lock().as_ref().map_or(0.0, |history| history.frames() as f32 / history.sample_rate)
+    }
+
+    /// Where automatic dumps go; `None` (the default) only keeps them in memory
+    pub fn set_dump_dir(&self, dir: Option<PathBuf>) {
+        *self.dump_dir.lock() = dir;
+    }
+
+    pub fn dump_dir(&self) -> Option<PathBuf> {
+        self.dump_dir.lock().clone()
+    }
+
+    /// Record an event, dropping the oldest if full. Realtime-safe.
+    #[inline]
+    pub fn record(&self, kind: BlackBoxEventKind) {
+        let time = self.clock.now().saturating_duration_since(self.started);
+        let frame = self.frames.load(Ordering::Relaxed);
+        let _ = self.events.force_push(BlackBoxEvent { time, frame, kind });
+        if kind.is_failure() {
+            self.armed.store(true, Ordering::Release);
+        }
+    }
+
+    /// Keep a block of interleaved master audio with `channels` channels.
+    /// Realtime-safe: while a dump holds the ring, the block is skipped and
+    /// counted in `missed_frames`.
+    #[inline]
+    pub fn capture(&self, output: &[f32], channels: usize) {
+        let frames = (output.len() / channels.max(1)) as u64;
+        self.frames.fetch_add(frames, Ordering::Relaxed);
+        if !self.audio_on.load(Ordering::Relaxed) {
+            return;
+        }
+        let Some(mut audio) = self.audio.try_lock() else {
+            self.missed_frames.fetch_add(frames, Ordering::Relaxed);
+            return;
+        };
+        if let Some(history) = audio.as_mut()
+            && history.channels == channels
+        {
+            history.write(output);
+        }
+    }
+
+    /// Master frames offered to `capture` so far
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    pub fn missed_frames(&self) -> u64 {
+        self.missed_frames.load(Ordering::Relaxed)
+    }
+
+    /// Whether a failure was recorded since the last dump
+    pub fn is_armed(&self) -> bool {
+        self.armed.load(Ordering::Acquire)
+    }
+
+    /// The events held, oldest first, leaving the box empty
+    pub fn take_events(&self) -> Vec<BlackBoxEvent> {
+        let mut events = Vec::with_capacity(self.events.len());
+        while let Some(event) = self.events.pop() {
+            events.push(event);
+        }
+        events
+    }
+
+    /// Write the events and audio held to a new `blackbox-<unix ms>-<count>`
+    /// directory inside `dir`, emptying the box and disarming it. Not
+    /// realtime-safe.
+    pub fn dump(&self, dir: impl AsRef<Path>) -> WavResult<BlackBoxDump> {
+        self.armed.store(false, Ordering::Release);
+        *self.last_dump.lock() = Some(Instant::now());
+        let events = self.take_events();
+        let audio = self.take_audio();
+
+        let stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let count = self.dumps.fetch_add(1, Ordering::Relaxed);
+        let dir = dir.as_ref().join(format!("blackbox-{}-{}", stamp, count));
+        fs::create_dir_all(&dir)?;
+
+        let audio_frames = audio.as_ref().map_or(0, |(samples, channels, _)| samples.len() / channels);
+        let mut log = String::new();
+        let _ = writeln!(log, "# master.wav starts at frame {}", self.frames().saturating_sub(audio_frames as u64));
+        for event in &events {
+            let _ = writeln!(log, "{:>12.6}s frame {:>10} {}", event.time.as_secs_f64(), event.frame, event.kind);
+        }
+        let events_path = dir.join("events.txt");
+        fs::write(&events_path, log)?;
+
+        let audio_path = match audio {
+            Some((samples, channels, sample_rate)) => {
+                let path = dir.join("master.wav");
+                let spec = WavSpec { sample_rate: sample_rate as u32, format: SampleFormat::Float32, dither: false };
+                let mut writer = WavWriter::create(&path, channels, spec)?;
+                writer.write_interleaved(&samples)?;
+                writer.finish()?;
+                Some(path)
+            }
+            None => None,
+        };
+        Ok(BlackBoxDump { dir, events_path, events: events.len(), audio_path, audio_frames })
+    }
+
+    /// Dump into the dump directory if a failure armed the box, one is set and
+    /// the last dump was at least `MIN_DUMP_INTERVAL` ago; `None` if nothing
+    /// was written
+    pub fn dump_if_armed(&self) -> Option<WavResult<BlackBoxDump>> {
+        if !self.is_armed() {
+            return None;
+        }
+        let dir = self.dump_dir()?;
+        if self.last_dump.lock().is_some_and(|last| last.elapsed() < MIN_DUMP_INTERVAL) {
+            return None;
+        }
+        Some(self.dump(dir))
+    }
+
+    /// The audio ring in order, as `(samples, channels, sample rate)`; the
+    /// ring starts over empty
+    fn take_audio(&self) -> Option<(Vec<f32>, usize, f32)> {
+        if !self.audio_on.load(Ordering::Relaxed) {
+            return None;
+        }
+        // swap in an empty ring, so `capture` only misses the swap itself
+        let (seconds, sample_rate, channels) = {
+            let audio = self.audio.lock();
+            let history = audio.as_ref()?;
+            (history.frames() as f32 / history.sample_rate, history.sample_rate, history.channels)
+        };
+        let fresh = Some(AudioHistory::new(seconds, sample_rate, channels));
+        let full = std::mem::replace(&mut *self.audio.lock(), fresh)?;
+        (full.filled > 0).then(|| (full.ordered(), full.channels, full.sample_rate))
+    }
+}
+
+static GLOBAL: OnceLock<BlackBox> = OnceLock::new();
+
+/// The process-wide black box, with a background thread started on first use
+/// that writes out armed dumps (see `BlackBox::dump_if_armed`). The engine
+/// fetches it when configured, so the audio thread never starts it.
+pub fn global() -> &'static BlackBox {
+    GLOBAL.get_or_init(|| {
+        // without the thread, dumps only happen on request
+        let _ = thread::Builder::new().name("pulsar-black-box".into()).spawn(|| {
+            loop {
+                thread::sleep(DUMP_POLL_INTERVAL);
+                if let Some(black_box) = GLOBAL.get() {
+                    let _ = black_box.dump_if_armed();
+                }
+            }
+        });
+        BlackBox::new(DEFAULT_BLACK_BOX_EVENTS)
+    })
+}
+
+/// Record into the global black box if it has been created; never creates it,
+/// so this is safe on the audio thread
+#[inline]
+pub(crate) fn record(kind: BlackBoxEventKind) {
+    if let Some(black_box) = GLOBAL.get() {
+        black_box.record(kind);
+    }
+}
@@ -2,12 +2,19 @@
 //!
 //! Design goals:
 //! - Avoid OS mutex/syscall in the hot audio callback path.
-//! - Allow hot-swapping the processing engine from another thread.
+//! - Allow hot-swapping the processing engine from another thread, cut or crossfaded.
 //! - Never allocate inside the audio thread.
 //! - If processor is unavailable (locked), output silence to avoid glitches.
 
+use std::f32::consts::FRAC_PI_2;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+#[cfg(feature = "chaos")]
+use crate::rt_processing::chaos::Chaos;
+use crate::rt_processing::reclaim::{self, Reclaimer, Retired};
+use crate::rt_processing::trace;
 
 use spin::Mutex; // small, in-process spinning lock good for realtime callbacks
 
@@ -25,6 +32,55 @@ pub trait AudioCallback: Send + 'static {
     fn process(&mut self, output: &mut [f32], sample_rate: f32, channels: usize, frames: usize);
 }
 
+/// Frames the outgoing processor renders per call during a crossfade
+const FADE_CHUNK_FRAMES: usize = 256;
+
+/// How `CallbackSlot::swap_processor_with` replaces the running processor
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SwapMode {
+    /// Switch at the next callback; the output may jump
+    Cut,
+    /// Run both processors for this long, fading the old one out and the new
+    /// one in (equal power); 10–50 ms hides the seam
+    Crossfade(Duration),
+}
+
+/// An outgoing processor being faded out
+struct Fade {
+    outgoing: Box<dyn AudioCallback>,
+    // fade length and progress, in frames
+    total: usize,
+    done: usize,
+    // outgoing processor's output, FADE_CHUNK_FRAMES * channels
+    scratch: Vec<f32>,
+}
+
+impl Fade {
+    fn is_done(&self) -> bool {
+        self.done >= self.total
+    }
+
+    /// Blend the outgoing processor into `output`, which already holds the new one
+    fn mix(&mut self, output: &mut [f32], sample_rate: f32, channels: usize) {
+        for chunk in output.chunks_mut(FADE_CHUNK_FRAMES * channels) {
+            if self.is_done() {
+                break;
+            }
+            let frames = chunk.len() / channels;
+            let scratch = &mut self.scratch[..frames * channels];
+            self.outgoing.process(scratch, sample_rate, channels, frames);
+            for (frame, old) in chunk.chunks_exact_mut(channels).zip(scratch.chunks_exact(channels)) {
+                let t = (self.done as f32 / self.total as f32).min(1.0) * FRAC_PI_2;
+                let (fade_in, fade_out) = (t.sin(), t.cos());
+                for (s, o) in frame.iter_mut().zip(old) {
+                    *s = *s * fade_in + o * fade_out;
+                }
+                self.done += 1;
+            }
+        }
+    }
+}
+
 /// A wrapper that holds a processor and provides a realtime-safe `process` entrypoint.
 ///
 /// Internally it holds `Arc<spin::Mutex<Box<dyn AudioCallback>>>`. In the audio thread we
@@ -43,6 +99,16 @@ pub struct CallbackSlot {
     /// the audio thread side; updates to them should be done with `set_runtime_config`.
     sample_rate: f32,
     channels: usize,
+
+    /// Processor being faded out after a crossfade swap; boxed so the audio
+    /// thread can hand it to `reclaimer` once silent without allocating.
+    fade: Mutex<Option<Box<Fade>>>,
+    fading: AtomicBool,
+    reclaimer: Reclaimer,
+
+    /// Fault injector for soak tests; see `rt_processing::chaos`.
+    #[cfg(feature = "chaos")]
+    chaos: Option<Arc<Chaos>>,
 }
 
 impl CallbackSlot {
@@ -56,29 +122,77 @@ impl CallbackSlot {
             sample_clock: Arc::new(AtomicU64::new(0)),
             sample_rate,
             channels,
+            fade: Mutex::new(None),
+            fading: AtomicBool::new(false),
+            reclaimer: reclaim::global().clone(),
+            #[cfg(feature = "chaos")]
+            chaos: None,
         }
     }
 
+    /// Drop faded-out processors through `reclaimer` instead of the global one.
+    pub fn with_reclaimer(mut self, reclaimer: Reclaimer) -> Self {
+        self.reclaimer = reclaimer;
+        self
+    }
+
+    /// Inject faults from `chaos` into every `process_realtime` call.
+    #[cfg(feature = "chaos")]
+    pub fn with_chaos(mut self, chaos: Arc<Chaos>) -> Self {
+        self.chaos = Some(chaos);
+        self
+    }
+
+    /// The fault injector attached with `with_chaos`, if any.
+    #[cfg(feature = "chaos")]
+    pub fn chaos(&self) -> Option<&Arc<Chaos>> {
+        self.chaos.as_ref()
+    }
+
     /// Replaces the current processor with a new one.
     ///
     /// This attempts to acquire the lock and swap. If the lock is briefly contended,
     /// we spin until we can swap it — swapping is expected to be infrequent and fast.
     pub fn swap_processor(&self, new_processor: Box<dyn AudioCallback>) {
-        let mut guard = self.processor.lock();
-        *guard = new_processor;
-        // lock released on drop
+        self.swap_processor_with(new_processor, SwapMode::Cut);
     }
 
-    /// Try to mutate the processor in-place using a closure.
+    /// Replaces the current processor as `mode` says.
     ///
-    /// Useful to change parameters without replacing the whole boxed object.
-    /// This will block (spin) until the lock is acquired.
-    pub fn with_processor_mut<F, R>(&self, f: F) -> R
-    where
-        F: FnOnce(&mut Box<dyn AudioCallback>) -> R,
-    {
+    /// With `SwapMode::Crossfade` the old processor keeps running on the audio
+    /// thread until faded out, then goes to the reclaimer. Swapping again while
+    /// a fade runs drops the older processor outright and fades from the
+    /// current one. Call from a non-realtime thread: processors replaced
+    /// without a fade are dropped here.
+    pub fn swap_processor_with(&self, new_processor: Box<dyn AudioCallback>, mode: SwapMode) {
+        let total = match mode {
+            SwapMode::Cut => 0,
+            SwapMode::Crossfade(fade) => (fade.as_secs_f32() * self.sample_rate).round() as usize,
+        };
+        // allocated before taking the lock the audio thread waits on
+        let scratch = if total > 0 { vec![0.0; FADE_CHUNK_FRAMES * self.channels] } else { Vec::new() };
+
         let mut guard = self.processor.lock();
-        f(&mut guard)
+        let outgoing = std::mem::replace(&mut *guard, new_processor);
+        let mut fade = self.fade.lock();
+        let previous = fade.take();
+        let cut = if total > 0 {
+            *fade = Some(Box::new(Fade { outgoing, total, done: 0, scratch }));
+            None
+        } else {
+            Some(outgoing)
+        };
+        self.fading.store(total > 0, Ordering::Release);
+        drop(fade);
+        trace::processor_swapped();
+        drop(guard);
+        // replaced processors are dropped here, outside the locks
+        drop((previous, cut));
+    }
+
+    /// Whether a crossfade swap is still fading out the old processor
+    pub fn is_crossfading(&self) -> bool {
+        self.fading.load(Ordering::Acquire)
     }
 
     /// Realtime-safe process entry called from the audio I/O callback.
@@ -98,6 +212,15 @@ impl CallbackSlot {
         // We store frame count so playback_time is frames / sample_rate.
         self.sample_clock.fetch_add(frames as u64, Ordering::Relaxed);
 
+        #[cfg(feature = "chaos")]
+        if let Some(chaos) = &self.chaos {
+            return self.process_chaotic(chaos, output, frames);
+        }
+
+        self.process_locked(output, frames)
+    }
+
+    fn process_locked(&self, output: &mut [f32], frames: usize) -> bool {
         // Try to acquire the processor lock without blocking the OS.
         // spin::Mutex::try_lock() exists but isn't stable on all versions; we use lock() which spins briefly.
         // To be extra-safe against long blocking we can attempt a quick spin approach:
@@ -109,14 +232,51 @@ impl CallbackSlot {
             // Processor exists; call its process method.
             // Implementations MUST NOT block or allocate here.
             guard.process(output, self.sample_rate, self.channels, frames);
+            if self.fading.load(Ordering::Acquire)
+                && let Some(mut slot) = self.fade.try_lock()
+                && let Some(fade) = slot.as_mut()
+            {
+                fade.mix(output, self.sample_rate, self.channels);
+                if fade.is_done()
+                    && let Some(fade) = slot.take()
+                {
+                    self.fading.store(false, Ordering::Release);
+                    self.reclaimer.retire(Retired::Other(fade));
+                }
+            }
             true
         } else {
             // Could not lock quickly — output silence to avoid glitches.
             output.fill(0.0);
+            trace::callback_skipped(frames);
             false
         }
     }
 
+    /// `process_locked` with injected delays, lock failures and split buffers.
+    #[cfg(feature = "chaos")]
+    fn process_chaotic(&self, chaos: &Chaos, output: &mut [f32], frames: usize) -> bool {
+        chaos.maybe_delay();
+        if chaos.fail_lock() {
+            output.fill(0.0);
+            trace::callback_skipped(frames);
+            return false;
+        }
+        if !chaos.shrink() {
+            return self.process_locked(output, frames);
+        }
+
+        let mut ran = true;
+        let mut done = 0;
+        while done < frames {
+            let chunk = chaos.chunk_frames(frames - done);
+            let block = &mut output[done * self.channels..(done + chunk) * self.channels];
+            ran &= self.process_locked(block, chunk);
+            done += chunk;
+        }
+        ran
+    }
+
     /// Get current playback time in seconds (frames / sample_rate).
     pub fn playback_time(&self) -> f32 {
         let frames = self.sample_clock.load(Ordering::Relaxed);
@@ -128,6 +288,16 @@ impl CallbackSlot {
         self.sample_clock.load(Ordering::Relaxed)
     }
 
+    /// Sample rate the processor runs at.
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Interleaved channel count the processor fills.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
     /// Return a cloneable handle to the internal processor Arc. This allows other parts
     /// of the program to hold a reference if needed.
     pub fn processor_handle(&self) -> Arc<Mutex<Box<dyn AudioCallback>>> {
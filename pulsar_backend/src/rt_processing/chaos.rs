@@ -0,0 +1,180 @@
+//! Fault injection for soak tests, enabled with the `chaos` feature.
+//!
+//! A `Chaos` attached to a `CallbackSlot` (`CallbackSlot::with_chaos`, or
+//! `Engine::set_chaos` before configuring) makes the audio path misbehave on
+//! purpose: callbacks arrive late, the processor lock "fails", device buffers
+//! come in odd small pieces, and streams driven by a `StreamManager` report
+//! their device lost. Apps (and the engine's own recovery) can then be run for
+//! hours against the worst a driver does.
+//!
+//! Faults are drawn from a seeded generator, so a failing run can be replayed.
+//! Never enable the feature in a shipping build.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::rt_processing::waveform::noise::FastRng;
+
+/// Odds of each fault, per callback (0 = never, 1 = every time)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChaosConfig {
+    /// Callback delayed by up to `max_delay` before it runs
+    pub delay_chance: f32,
+    pub max_delay: Duration,
+    /// Processor lock treated as contended: the block plays silence
+    pub lock_failure_chance: f32,
+    /// Block split into random pieces of at least `min_chunk_frames`, as
+    /// drivers with varying buffer sizes do
+    pub shrink_chance: f32,
+    pub min_chunk_frames: usize,
+    /// Device reported lost (`StreamError::DeviceLost`)
+    pub disconnect_chance: f32,
+}
+
+impl Default for ChaosConfig {
+    /// Everything off
+    fn default() -> Self {
+        Self {
+            delay_chance: 0.0,
+            max_delay: Duration::ZERO,
+            lock_failure_chance: 0.0,
+            shrink_chance: 0.0,
+            min_chunk_frames: 1,
+            disconnect_chance: 0.0,
+        }
+    }
+}
+
+impl ChaosConfig {
+    /// Occasional trouble, like a busy desktop machine
+    pub fn mild() -> Self {
+        Self {
+            delay_chance: 0.01,
+            max_delay: Duration::from_millis(2),
+            lock_failure_chance: 0.001,
+            shrink_chance: 0.05,
+            min_chunk_frames: 16,
+            disconnect_chance: 0.0,
+        }
+    }
+
+    /// Constant trouble, including lost devices
+    pub fn severe() -> Self {
+        Self {
+            delay_chance: 0.1,
+            max_delay: Duration::from_millis(20),
+            lock_failure_chance: 0.02,
+            shrink_chance: 0.5,
+            min_chunk_frames: 1,
+            disconnect_chance: 0.0005,
+        }
+    }
+}
+
+/// Faults injected so far
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChaosStats {
+    pub delays: u64,
+    pub lock_failures: u64,
+    pub shrunk_blocks: u64,
+    pub disconnects: u64,
+}
+
+/// Shared fault injector; see the module docs
+pub struct Chaos {
+    config: ChaosConfig,
+    enabled: AtomicBool,
+    rng: AtomicU32,
+    delays: AtomicU64,
+    lock_failures: AtomicU64,
+    shrunk_blocks: AtomicU64,
+    disconnects: AtomicU64,
+}
+
+impl Chaos {
+    pub fn new(config: ChaosConfig, seed: u32) -> Self {
+        Self {
+            config,
+            enabled: AtomicBool::new(true),
+            rng: AtomicU32::new(FastRng::new(seed).state()),
+            delays: AtomicU64::new(0),
+            lock_failures: AtomicU64::new(0),
+            shrunk_blocks: AtomicU64::new(0),
+            disconnects: AtomicU64::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &ChaosConfig {
+        &self.config
+    }
+
+    /// Pause or resume injecting faults
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn stats(&self) -> ChaosStats {
+        ChaosStats {
+            delays: self.delays.load(Ordering::Relaxed),
+            lock_failures: self.lock_failures.load(Ordering::Relaxed),
+            shrunk_blocks: self.shrunk_blocks.load(Ordering::Relaxed),
+            disconnects: self.disconnects.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Uniform in [0, 1)
+    fn next(&self) -> f32 {
+        // a lost race between threads just repeats a value
+        let mut rng = FastRng::from_state(self.rng.load(Ordering::Relaxed));
+        let value = rng.next_f32();
+        self.rng.store(rng.state(), Ordering::Relaxed);
+        value
+    }
+
+    fn roll(&self, chance: f32) -> bool {
+        chance > 0.0 && self.is_enabled() && self.next() < chance
+    }
+
+    /// Maybe stall the calling (audio) thread
+    pub(crate) fn maybe_delay(&self) {
+        if self.roll(self.config.delay_chance) {
+            self.delays.fetch_add(1, Ordering::Relaxed);
+            std::thread::sleep(self.config.max_delay.mul_f32(self.next()));
+        }
+    }
+
+    pub(crate) fn fail_lock(&self) -> bool {
+        let fail = self.roll(self.config.lock_failure_chance);
+        if fail {
+            self.lock_failures.fetch_add(1, Ordering::Relaxed);
+        }
+        fail
+    }
+
+    /// Whether to split this block; counts it if so
+    pub(crate) fn shrink(&self) -> bool {
+        let shrink = self.roll(self.config.shrink_chance);
+        if shrink {
+            self.shrunk_blocks.fetch_add(1, Ordering::Relaxed);
+        }
+        shrink
+    }
+
+    /// Size of the next piece of a split block with `remaining` frames left
+    pub(crate) fn chunk_frames(&self, remaining: usize) -> usize {
+        let min = self.config.min_chunk_frames.clamp(1, remaining.max(1));
+        (min + ((remaining - min) as f32 * self.next()) as usize).min(remaining)
+    }
+
+    pub(crate) fn disconnect(&self) -> bool {
+        let lost = self.roll(self.config.disconnect_chance);
+        if lost {
+            self.disconnects.fetch_add(1, Ordering::Relaxed);
+        }
+        lost
+    }
+}
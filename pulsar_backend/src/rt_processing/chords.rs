@@ -0,0 +1,220 @@
+//! Chord memory and strumming: one played note standing for a whole chord.
+//!
+//! A `ChordShape` holds intervals from the played note, learnt from a held
+//! chord or given directly. A `Strum` spreads the chord's notes out in time,
+//! low to high or high to low, with a velocity change from note to note; the
+//! `Strummer` holds the notes still to come until they're due.
+
+/// Most notes in a chord
+pub const MAX_CHORD_NOTES: usize = 8;
+/// Strummed notes a `Strummer` holds before later ones play straight away
+pub const MAX_PENDING_NOTES: usize = 64;
+/// Longest delay between strummed notes, in seconds
+pub const MAX_STRUM_DELAY: f32 = 0.5;
+/// A note this many seconds early is due, so rounding doesn't hold it a frame
+const DUE_TOLERANCE: f64 = 1e-6;
+
+/// Notes of one chord, lowest first
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Voicing {
+    notes: [u8; MAX_CHORD_NOTES],
+    len: u8,
+}
+
+impl Voicing {
+    pub fn single(note: u8) -> Self {
+        let mut voicing = Self::default();
+        voicing.push(note);
+        voicing
+    }
+
+    pub fn notes(&self) -> &[u8] {
+        &self.notes[..self.len as usize]
+    }
+
+    /// Add `note` in order; ignored if already there or the voicing is full
+    fn push(&mut self, note: u8) {
+        let len = self.len as usize;
+        if len == MAX_CHORD_NOTES || self.notes().contains(&note) {
+            return;
+        }
+        let at = self.notes().iter().position(|&n| n > note).unwrap_or(len);
+        self.notes.copy_within(at..len, at + 1);
+        self.notes[at] = note;
+        self.len += 1;
+    }
+}
+
+/// Intervals of a chord from the note that plays it, in semitones
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChordShape {
+    intervals: [i8; MAX_CHORD_NOTES],
+    len: u8,
+}
+
+impl ChordShape {
+    /// Up to `MAX_CHORD_NOTES` intervals, within two octaves either way;
+    /// repeats are dropped. Include 0 for the played note to sound.
+    pub fn new(intervals: &[i8]) -> Self {
+        let mut shape = Self::default();
+        for &interval in intervals {
+            let interval = interval.clamp(-24, 24);
+            if shape.len as usize == MAX_CHORD_NOTES || shape.intervals().contains(&interval) {
+                continue;
+            }
+            shape.intervals[shape.len as usize] = interval;
+            shape.len += 1;
+        }
+        shape.intervals[..shape.len as usize].sort_unstable();
+        shape
+    }
+
+    /// The shape of a played chord, relative to its lowest note, as chord
+    /// memory learns it from the keys held down
+    pub fn from_notes(notes: &[u8]) -> Self {
+        let lowest = notes.iter().copied().min().unwrap_or(0);
+        let intervals: Vec<i8> = notes.iter().map(|&note| (note - lowest).min(24) as i8).collect();
+        Self::new(&intervals)
+    }
+
+    pub fn intervals(&self) -> &[i8] {
+        &self.intervals[..self.len as usize]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The chord on `root`, each note passed through `map` (a scale, so the
+    /// chord stays in key). Notes outside the MIDI range are left out.
+    pub fn voicing(&self, root: u8, map: impl Fn(u8) -> u8) -> Voicing {
+        let mut voicing = Voicing::default();
+        for &interval in self.intervals() {
+            if let Ok(note) = u8::try_from(root as i16 + interval as i16)
+                && note < 128
+            {
+                voicing.push(map(note));
+            }
+        }
+        voicing
+    }
+}
+
+/// Order a chord is strummed in
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum StrumDirection {
+    /// Lowest note first
+    #[default]
+    Up,
+    /// Highest note first
+    Down,
+}
+
+/// How a chord's notes are spread out
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Strum {
+    /// Seconds from one note to the next (0.0 plays them together)
+    pub delay: f32,
+    /// Velocity added to each note after the first, -1.0 to 1.0
+    pub velocity_step: f32,
+    pub direction: StrumDirection,
+}
+
+impl Default for Strum {
+    /// Every note at once, at the played velocity
+    fn default() -> Self {
+        Self { delay: 0.0, velocity_step: 0.0, direction: StrumDirection::Up }
+    }
+}
+
+impl Strum {
+    pub fn sanitize(self) -> Self {
+        Self {
+            delay: self.delay.clamp(0.0, MAX_STRUM_DELAY),
+            velocity_step: self.velocity_step.clamp(-1.0, 1.0),
+            ..self
+        }
+    }
+
+    /// `(note, velocity, delay in seconds)` for every note of `voicing`, in
+    /// strum order
+    pub fn spread(self, voicing: Voicing, velocity: f32) -> impl Iterator<Item = (u8, f32, f32)> {
+        let Strum { delay, velocity_step, direction } = self;
+        let len = voicing.notes().len();
+        (0..len).map(move |i| {
+            let note = match direction {
+                StrumDirection::Up => voicing.notes()[i],
+                StrumDirection::Down => voicing.notes()[len - 1 - i],
+            };
+            (note, (velocity + velocity_step * i as f32).clamp(0.0, 1.0), delay * i as f32)
+        })
+    }
+}
+
+/// A strummed note not yet played
+#[derive(Copy, Clone, Debug)]
+struct PendingNote {
+    /// Key that played the chord
+    key: u8,
+    note: u8,
+    velocity: f32,
+    /// Seconds until it plays
+    due: f64,
+}
+
+/// Notes of strummed chords waiting to play. Preallocated: scheduling
+/// doesn't allocate.
+#[derive(Clone, Debug)]
+pub struct Strummer {
+    pending: Vec<PendingNote>,
+}
+
+impl Default for Strummer {
+    fn default() -> Self {
+        Self { pending: Vec::with_capacity(MAX_PENDING_NOTES) }
+    }
+}
+
+impl Strummer {
+    /// Hold `note` of `key`'s chord for `delay` seconds. `false` when full,
+    /// and the note should play now.
+    pub fn schedule(&mut self, key: u8, note: u8, velocity: f32, delay: f32) -> bool {
+        if self.pending.len() == MAX_PENDING_NOTES {
+            return false;
+        }
+        self.pending.push(PendingNote { key, note, velocity, due: delay as f64 });
+        true
+    }
+
+    /// Drop the notes of `key`'s chord that haven't played yet
+    pub fn cancel(&mut self, key: u8) {
+        self.pending.retain(|pending| pending.key != key);
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Frames until the next note is due at `sample_rate`, rounded up
+    pub fn next_due(&self, sample_rate: f32) -> Option<usize> {
+        let due = self.pending.iter().map(|pending| pending.due).fold(f64::MAX, f64::min);
+        (due < f64::MAX).then(|| ((due - DUE_TOLERANCE) * sample_rate as f64).ceil().max(0.0) as usize)
+    }
+
+    /// Take a note that is due, as `(key, note, velocity)`, in the order
+    /// they were scheduled
+    pub fn pop_due(&mut self) -> Option<(u8, u8, f32)> {
+        let index = self.pending.iter().position(|pending| pending.due <= DUE_TOLERANCE)?;
+        let PendingNote { key, note, velocity, .. } = self.pending.remove(index);
+        Some((key, note, velocity))
+    }
+
+    pub fn advance(&mut self, frames: usize, sample_rate: f32) {
+        let seconds = frames as f64 / sample_rate as f64;
+        self.pending.iter_mut().for_each(|pending| pending.due -= seconds);
+    }
+}
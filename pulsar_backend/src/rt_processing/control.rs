@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crossbeam::atomic::AtomicCell;
+use spin::Mutex;
+
+use crate::rt_processing::effects::Effect;
+use crate::rt_processing::filters::EnvelopeFollower;
+use crate::rt_processing::modulation::ModulationMonitor;
+
+/// One named control-rate value ("CV" channel).
+///
+/// Writers store one value per block (the router's control grid, see
+/// `CONTROL_BLOCK_FRAMES`); readers see the latest value. A reader processed
+/// before the writer in the same block gets the previous block's value. Clones
+/// share the value.
+#[derive(Clone, Debug)]
+pub struct ControlBus {
+    name: Arc<str>,
+    value: Arc<AtomicCell<f32>>,
+}
+
+impl ControlBus {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get(&self) -> f32 {
+        self.value.load()
+    }
+
+    pub fn set(&self, value: f32) {
+        self.value.store(value);
+    }
+}
+
+/// Named control buses shared by the nodes of a graph, generalizing modulation
+/// beyond a node's own LFOs: any node can write a bus and any other read it.
+///
+/// Buses are created on first lookup, on the control thread; the handles
+/// themselves are lock-free.
+#[derive(Clone, Default)]
+pub struct ControlBuses {
+    buses: Arc<Mutex<HashMap<String, ControlBus>>>,
+}
+
+impl ControlBuses {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle to the bus called `name`, created at 0.0 if it doesn't exist yet
+    pub fn bus(&self, name: &str) -> ControlBus {
+        self.buses
+            .lock()
+            .entry(name.to_string())
+            .or_insert_with(|| ControlBus { name: name.into(), value: Arc::new(AtomicCell::new(0.0)) })
+            .clone()
+    }
+
+    pub fn get(&self, name: &str) -> Option<ControlBus> {
+        self.buses.lock().get(name).cloned()
+    }
+
+    /// Every bus with its current value, sorted by name
+    pub fn snapshot(&self) -> Vec<(String, f32)> {
+        let mut values: Vec<_> = self.buses.lock().iter().map(|(name, bus)| (name.clone(), bus.get())).collect();
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+        values
+    }
+}
+
+/// Pass-through effect writing the peak envelope of its input (all channels)
+/// to a control bus once per block, as `envelope * scale + offset`
+pub struct EnvelopeToControl {
+    bus: ControlBus,
+    attack: f32,
+    release: f32,
+    follower: EnvelopeFollower,
+    sample_rate: f32,
+    scale: f32,
+    offset: f32,
+}
+
+impl EnvelopeToControl {
+    pub fn new(bus: ControlBus, attack_seconds: f32, release_seconds: f32) -> Self {
+        Self {
+            bus,
+            attack: attack_seconds,
+            release: release_seconds,
+            follower: EnvelopeFollower::new(attack_seconds, release_seconds, 48000.0),
+            sample_rate: 0.0,
+            scale: 1.0,
+            offset: 0.0,
+        }
+    }
+
+    /// Map the envelope (0..1 for a full-scale signal) onto the target's range
+    pub fn with_range(mut self, scale: f32, offset: f32) -> Self {
+        self.scale = scale;
+        self.offset = offset;
+        self
+    }
+}
+
+impl Effect for EnvelopeToControl {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            self.follower.set_times(self.attack, self.release, sample_rate);
+        }
+        for i in 0..frames {
+            let peak = buffer.iter().fold(0.0f32, |peak, samples| peak.max(samples[i].abs()));
+            self.follower.process(peak);
+        }
+        self.bus.set(self.follower.value() * self.scale + self.offset);
+    }
+
+    fn reset(&mut self) {
+        self.follower.reset();
+        self.bus.set(self.offset);
+    }
+}
+
+/// Wraps an effect so a control bus drives one of its parameters: `apply`
+/// gets the bus value before every block, e.g.
+/// `ControlInput::new(tremolo, bus, |t, v| t.set_depth(v))`.
+pub struct ControlInput<E: Effect> {
+    effect: E,
+    bus: ControlBus,
+    apply: fn(&mut E, f32),
+}
+
+impl<E: Effect> ControlInput<E> {
+    pub fn new(effect: E, bus: ControlBus, apply: fn(&mut E, f32)) -> Self {
+        Self { effect, bus, apply }
+    }
+
+    pub fn effect(&self) -> &E {
+        &self.effect
+    }
+
+    pub fn effect_mut(&mut self) -> &mut E {
+        &mut self.effect
+    }
+}
+
+impl<E: Effect> Effect for ControlInput<E> {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        (self.apply)(&mut self.effect, self.bus.get());
+        self.effect.process(buffer, frames, sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.effect.reset();
+    }
+
+    fn modulation(&self) -> Option<ModulationMonitor> {
+        self.effect.modulation()
+    }
+}
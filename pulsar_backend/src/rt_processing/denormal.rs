@@ -0,0 +1,51 @@
+//! Keeping recursive DSP out of denormal (subnormal) floats.
+//!
+//! When the input falls silent, the feedback in filters, delays and reverbs
+//! decays towards zero and spends a long time in the subnormal range, where
+//! many CPUs are an order of magnitude slower. Flushing to zero needs the FPU
+//! mode set on every audio thread and isn't portable; instead a `NoiseFloor`
+//! adds white noise far below audibility to the input of effect chains, so
+//! their state settles on the noise rather than decaying.
+//!
+//! At the default level the noise is below the resolution of any signal above
+//! about -150 dBFS, so audible material passes bit for bit.
+
+use crate::rt_processing::waveform::noise::FastRng;
+
+/// Level of `NoiseFloor::default()`, in dBFS
+pub const DEFAULT_NOISE_FLOOR_DB: f32 = -300.0;
+
+/// Inaudible white noise generator, see the module docs
+#[derive(Clone, Debug)]
+pub struct NoiseFloor {
+    level_db: f32,
+    amplitude: f32,
+    rng: FastRng,
+}
+
+impl Default for NoiseFloor {
+    fn default() -> Self {
+        Self::new(DEFAULT_NOISE_FLOOR_DB)
+    }
+}
+
+impl NoiseFloor {
+    /// Noise peaking at `level_db` dBFS
+    pub fn new(level_db: f32) -> Self {
+        Self { level_db, amplitude: 10f32.powf(level_db / 20.0), rng: FastRng::new(1) }
+    }
+
+    pub fn level_db(&self) -> f32 {
+        self.level_db
+    }
+
+    /// Add noise to the first `frames` frames of every channel. RT-safe.
+    #[inline]
+    pub fn apply(&mut self, buffer: &mut [&mut [f32]], frames: usize) {
+        for channel in buffer.iter_mut() {
+            for sample in &mut channel[..frames] {
+                *sample += self.rng.next_bipolar() * self.amplitude;
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use crate::rt_processing::filters::{BUTTERWORTH_Q, BiquadCoefficients, BiquadState, OnePoleState};
+
+use super::Effect;
+use super::chain::EffectChain;
+use super::convolution::{Convolver, DEFAULT_PARTITION_SIZE, ImpulseResponse};
+
+/// Most gain stages an amp can cascade
+pub const MAX_AMP_STAGES: usize = 4;
+
+/// Amp parameters. Tone controls are in dB of boost/cut.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AmpParams {
+    /// Input drive in dB
+    pub drive_db: f32,
+    /// Number of cascaded gain stages (1..=MAX_AMP_STAGES)
+    pub stages: usize,
+    /// Stage bias; values away from 0 add even harmonics (-0.5 to 0.5)
+    pub bias: f32,
+    pub bass_db: f32,
+    pub mid_db: f32,
+    pub treble_db: f32,
+    /// Output level in dB
+    pub master_db: f32,
+}
+
+impl Default for AmpParams {
+    fn default() -> Self {
+        Self {
+            drive_db: 18.0,
+            stages: 2,
+            bias: 0.1,
+            bass_db: 0.0,
+            mid_db: 0.0,
+            treble_db: 0.0,
+            master_db: -12.0,
+        }
+    }
+}
+
+impl AmpParams {
+    /// Low-gain, open sound
+    pub fn clean() -> Self {
+        Self { drive_db: 6.0, stages: 1, bias: 0.05, master_db: -6.0, ..Self::default() }
+    }
+
+    /// Saturated lead tone with scooped mids
+    pub fn high_gain() -> Self {
+        Self {
+            drive_db: 30.0,
+            stages: 4,
+            bias: 0.2,
+            bass_db: 3.0,
+            mid_db: -4.0,
+            treble_db: 2.0,
+            master_db: -18.0,
+        }
+    }
+}
+
+/// Per-channel filter memory
+#[derive(Copy, Clone, Default)]
+struct AmpChannel {
+    input_high_pass: OnePoleState,
+    // [stage]: DC-blocking coupling and fizz-taming low-pass
+    coupling: [OnePoleState; MAX_AMP_STAGES],
+    smoothing: [OnePoleState; MAX_AMP_STAGES],
+    tone: [BiquadState; 3],
+}
+
+/// Guitar amp preamp model: drive into cascaded waveshaper stages followed by a
+/// bass/mid/treble tone stack.
+///
+/// Each stage is a biased `tanh` with the bias offset removed, AC-coupled to the
+/// next stage and gently low-passed to keep aliasing and fizz down. Pair it with
+/// a `Cabinet` (see `amp_sim_chain`) for a full amp + speaker chain.
+pub struct AmpSim {
+    params: AmpParams,
+    sample_rate: f32,
+    drive: f32,
+    master: f32,
+    input_high_pass: f32,
+    coupling: f32,
+    smoothing: f32,
+    tone: [BiquadCoefficients; 3],
+    states: Vec<AmpChannel>,
+}
+
+impl AmpSim {
+    pub fn new(channels: usize, params: AmpParams) -> Self {
+        Self {
+            params: Self::sanitize(params),
+            sample_rate: 0.0,
+            drive: 1.0,
+            master: 1.0,
+            input_high_pass: 1.0,
+            coupling: 1.0,
+            smoothing: 1.0,
+            tone: [BiquadCoefficients::IDENTITY; 3],
+            states: vec![AmpChannel::default(); channels],
+        }
+    }
+
+    pub fn params(&self) -> &AmpParams {
+        &self.params
+    }
+
+    pub fn set_params(&mut self, params: AmpParams) {
+        self.params = Self::sanitize(params);
+        self.sample_rate = 0.0;
+    }
+
+    pub fn set_tone(&mut self, bass_db: f32, mid_db: f32, treble_db: f32) {
+        self.set_params(AmpParams { bass_db, mid_db, treble_db, ..self.params });
+    }
+
+    fn sanitize(params: AmpParams) -> AmpParams {
+        AmpParams {
+            stages: params.stages.clamp(1, MAX_AMP_STAGES),
+            bias: params.bias.clamp(-0.5, 0.5),
+            bass_db: params.bass_db.clamp(-15.0, 15.0),
+            mid_db: params.mid_db.clamp(-15.0, 15.0),
+            treble_db: params.treble_db.clamp(-15.0, 15.0),
+            ..params
+        }
+    }
+
+    fn prepare(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        let p = self.params;
+        self.drive = 10f32.powf(p.drive_db / 20.0);
+        self.master = 10f32.powf(p.master_db / 20.0);
+        self.input_high_pass = OnePoleState::coefficient(90.0, sample_rate);
+        self.coupling = OnePoleState::coefficient(25.0, sample_rate);
+        self.smoothing = OnePoleState::coefficient(7000.0, sample_rate);
+        self.tone = [
+            BiquadCoefficients::low_shelf(120.0, BUTTERWORTH_Q, p.bass_db, sample_rate),
+            BiquadCoefficients::peaking(750.0, 0.7, p.mid_db, sample_rate),
+            BiquadCoefficients::high_shelf(3200.0, BUTTERWORTH_Q, p.treble_db, sample_rate),
+        ];
+    }
+}
+
+impl Effect for AmpSim {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if self.sample_rate != sample_rate {
+            self.prepare(sample_rate);
+        }
+        let stages = self.params.stages;
+        let bias = self.params.bias;
+        let (bias_offset, bias_cosh) = (bias.tanh(), bias.cosh());
+
+        for (samples, state) in buffer.iter_mut().zip(&mut self.states) {
+            for s in samples[..frames].iter_mut() {
+                let mut x = state.input_high_pass.high_pass(self.input_high_pass, *s * self.drive);
+                let stage_states = state.coupling.iter_mut().zip(state.smoothing.iter_mut());
+                for (coupling, smoothing) in stage_states.take(stages) {
+                    // tanh(x + b) - tanh(b) loses signals far below the bias to
+                    // rounding (the noise floor among them); near zero use the
+                    // exact sinh(x) / (cosh(x + b) cosh(b)) instead
+                    let shaped = if x.abs() < 0.5 {
+                        x.sinh() / ((x + bias).cosh() * bias_cosh)
+                    } else {
+                        (x + bias).tanh() - bias_offset
+                    };
+                    let coupled = coupling.high_pass(self.coupling, shaped);
+                    // inter-stage gain keeps later stages driven
+                    x = smoothing.low_pass(self.smoothing, coupled) * 3.0;
+                }
+                for (tone, c) in state.tone.iter_mut().zip(&self.tone) {
+                    x = tone.process(c, x);
+                }
+                *s = x * self.master;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.states.iter_mut().for_each(|s| *s = AmpChannel::default());
+    }
+}
+
+/// Speaker cabinet: convolution with a user IR, or a fixed band-limiting curve
+/// approximating a closed-back 12" cab when no IR is loaded.
+pub struct Cabinet {
+    convolver: Option<Convolver>,
+    sample_rate: f32,
+    curve: [BiquadCoefficients; 3],
+    // [channel][section]
+    states: Vec<[BiquadState; 3]>,
+}
+
+impl Cabinet {
+    /// Cabinet without an IR (fallback curve)
+    pub fn new(channels: usize) -> Self {
+        Self {
+            convolver: None,
+            sample_rate: 0.0,
+            curve: [BiquadCoefficients::IDENTITY; 3],
+            states: vec![[BiquadState::default(); 3]; channels],
+        }
+    }
+
+    /// Cabinet convolving with `ir` (energy-normalized, truncated to 500 ms)
+    pub fn with_impulse_response(channels: usize, ir: ImpulseResponse) -> Self {
+        let ir = ir.truncated(0.5).normalized();
+        Self {
+            convolver: Some(Convolver::new(&ir, channels, DEFAULT_PARTITION_SIZE)),
+            ..Self::new(channels)
+        }
+    }
+
+    /// Processing latency in samples (non-zero only with an IR)
+    pub fn latency(&self) -> usize {
+        self.convolver.as_ref().map_or(0, Convolver::latency)
+    }
+}
+
+impl Effect for Cabinet {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if let Some(convolver) = &mut self.convolver {
+            convolver.process(buffer, frames, sample_rate);
+            return;
+        }
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            self.curve = [
+                BiquadCoefficients::high_pass(80.0, BUTTERWORTH_Q, sample_rate),
+                BiquadCoefficients::peaking(2500.0, 1.2, 4.0, sample_rate),
+                BiquadCoefficients::low_pass(4500.0, 0.9, sample_rate),
+            ];
+        }
+        for (samples, states) in buffer.iter_mut().zip(&mut self.states) {
+            for s in samples[..frames].iter_mut() {
+                *s = states.iter_mut().zip(&self.curve).fold(*s, |x, (state, c)| state.process(c, x));
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        if let Some(convolver) = &mut self.convolver {
+            convolver.reset();
+        }
+        self.states.iter_mut().for_each(|s| s.iter_mut().for_each(BiquadState::reset));
+    }
+
+    fn latency(&self) -> usize {
+        Cabinet::latency(self)
+    }
+}
+
+/// Amp followed by cabinet, ready to insert on a live-input monitoring bus
+pub fn amp_sim_chain(channels: usize, params: AmpParams, cabinet_ir: Option<ImpulseResponse>) -> EffectChain {
+    let cabinet = match cabinet_ir {
+        Some(ir) => Cabinet::with_impulse_response(channels, ir),
+        None => Cabinet::new(channels),
+    };
+    EffectChain::new()
+        .with(Box::new(AmpSim::new(channels, params)))
+        .with(Box::new(cabinet))
+}
@@ -0,0 +1,114 @@
+use std::f32::consts::FRAC_PI_4;
+
+use crate::rt_processing::filters::OnePoleState;
+use crate::rt_processing::modulation::{ControlRamp, DEFAULT_CONTROL_INTERVAL, ModulationMonitor};
+use crate::rt_processing::waveform::oscillators::LFO;
+use crate::rt_processing::waveform::tables::WaveformType;
+
+use super::{DEFAULT_TEMPO_BPM, Effect, LfoRate};
+
+/// Corner of the pan smoother; keeps square/saw shapes from clicking
+const PAN_SMOOTHING_HZ: f32 = 150.0;
+
+/// Stereo position modulated by an LFO.
+///
+/// Sweeps the first two channels between left and right by `width` (1.0 = hard
+/// left to hard right). Gains follow a sin/cos law normalized to unity at the
+/// center, so a centered signal passes unchanged. Mono buses are left untouched.
+/// The LFO runs at control rate (see `ControlRamp`). The position is published
+/// as the `pan` modulation reading (base 0.0).
+pub struct AutoPan {
+    lfo: LFO,
+    rate: LfoRate,
+    width: f32,
+    tempo_bpm: f32,
+    smoother: OnePoleState,
+    control: ControlRamp,
+    modulation: ModulationMonitor,
+}
+
+impl AutoPan {
+    pub fn new(waveform: WaveformType, rate: LfoRate) -> Self {
+        Self {
+            lfo: LFO::new(waveform, rate.frequency(DEFAULT_TEMPO_BPM)),
+            rate,
+            width: 1.0,
+            tempo_bpm: DEFAULT_TEMPO_BPM,
+            smoother: OnePoleState::default(),
+            control: ControlRamp::new(DEFAULT_CONTROL_INTERVAL),
+            modulation: ModulationMonitor::new(&["pan"]),
+        }
+    }
+
+    /// Sweep width (0.0 = static center, 1.0 = full left/right)
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.set_width(width);
+        self
+    }
+
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.clamp(0.0, 1.0);
+    }
+
+    /// Samples between LFO evaluations (1 = every sample)
+    pub fn with_control_interval(mut self, interval: usize) -> Self {
+        self.control.set_interval(interval);
+        self
+    }
+
+    pub fn set_rate(&mut self, rate: LfoRate) {
+        self.rate = rate;
+        self.lfo.set_frequency(rate.frequency(self.tempo_bpm));
+    }
+
+    pub fn set_waveform(&mut self, waveform: WaveformType) {
+        self.lfo.set_waveform(waveform);
+    }
+
+    /// Tempo used by `LfoRate::Beats`
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.tempo_bpm = bpm;
+        self.lfo.set_frequency(self.rate.frequency(bpm));
+    }
+
+    /// Restart the LFO cycle, e.g. on a bar line
+    pub fn retrigger(&mut self) {
+        self.lfo.set_phase(0.0);
+    }
+
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    pub fn rate(&self) -> LfoRate {
+        self.rate
+    }
+}
+
+impl Effect for AutoPan {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        let [left, right, ..] = buffer else { return };
+        let a = OnePoleState::coefficient(PAN_SMOOTHING_HZ, sample_rate);
+        let mut pan = 0.0;
+        for (l, r) in left[..frames].iter_mut().zip(right[..frames].iter_mut()) {
+            let lfo = self.control.next(|n| self.lfo.advance(n, sample_rate));
+            pan = self.smoother.low_pass(a, lfo * self.width);
+            let angle = (pan + 1.0) * FRAC_PI_4;
+            let (sin, cos) = angle.sin_cos();
+            *l *= (cos * std::f32::consts::SQRT_2).min(1.0);
+            *r *= (sin * std::f32::consts::SQRT_2).min(1.0);
+        }
+        self.modulation.publish(0, 0.0, pan);
+    }
+
+    fn reset(&mut self) {
+        self.retrigger();
+        self.smoother.reset();
+        self.control.reset();
+        self.modulation.clear();
+    }
+
+    fn modulation(&self) -> Option<ModulationMonitor> {
+        Some(self.modulation.clone())
+    }
+}
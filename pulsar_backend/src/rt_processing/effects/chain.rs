@@ -0,0 +1,170 @@
+use std::ops::Range;
+
+use crate::rt_processing::prefault::Prefault;
+
+use super::Effect;
+
+/// Effects a prepared chain holds without reallocating, so effects can be
+/// spliced in on the audio thread (see `splice`)
+pub const CHAIN_CAPACITY: usize = 16;
+
+/// Ordered series of effects processed in place.
+///
+/// A chain is itself an `Effect`, so chains nest (e.g. one per multiband band).
+/// Build it on the control thread; `push` may allocate.
+///
+/// `set_mix` blends the processed signal with the chain's input (1.0 = all
+/// wet). Blending needs a copy of the input, so it only takes effect once
+/// `prepare` has sized that buffer; the router prepares every chain it is
+/// given. Mix changes glide over one block.
+pub struct EffectChain {
+    effects: Vec<Box<dyn Effect>>,
+    bypassed: bool,
+    mix: f32,
+    // mix heard at the end of the last block
+    applied_mix: f32,
+    // input copy for the dry signal, [channel][frame]
+    dry: Vec<Vec<f32>>,
+}
+
+impl Default for EffectChain {
+    fn default() -> Self {
+        Self { effects: Vec::new(), bypassed: false, mix: 1.0, applied_mix: 1.0, dry: Vec::new() }
+    }
+}
+
+impl EffectChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wet/dry balance (0.0 = input only, 1.0 = effects only)
+    pub fn with_mix(mut self, mix: f32) -> Self {
+        self.set_mix(mix);
+        self
+    }
+
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    pub fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Size the dry buffer for blocks of up to `max_frames` on `channels`,
+    /// and make room for `CHAIN_CAPACITY` effects; nothing to do if that's
+    /// done. Allocates; call off the audio thread.
+    pub fn prepare(&mut self, channels: usize, max_frames: usize) {
+        self.effects.reserve(CHAIN_CAPACITY.saturating_sub(self.effects.len()));
+        if self.dry.len() != channels || self.dry.first().is_some_and(|dry| dry.len() != max_frames) {
+            self.dry = vec![vec![0.0; max_frames]; channels];
+        }
+    }
+
+    /// Append an effect (builder form of `push`)
+    pub fn with(mut self, effect: Box<dyn Effect>) -> Self {
+        self.push(effect);
+        self
+    }
+
+    pub fn push(&mut self, effect: Box<dyn Effect>) {
+        self.effects.push(effect);
+    }
+
+    pub fn insert(&mut self, index: usize, effect: Box<dyn Effect>) {
+        self.effects.insert(index.min(self.effects.len()), effect);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Box<dyn Effect>> {
+        (index < self.effects.len()).then(|| self.effects.remove(index))
+    }
+
+    /// Put `effects` in place of the effects in `range` (clamped to the
+    /// chain), handing the replaced ones back in `effects`. Doesn't allocate
+    /// while `effects` has room for the replaced effects as well as its own
+    /// and the chain for the result.
+    pub fn splice(&mut self, range: Range<usize>, effects: &mut Vec<Box<dyn Effect>>) {
+        let end = range.end.min(self.effects.len());
+        let start = range.start.min(end);
+        let added = effects.len();
+        for _ in start..end {
+            effects.push(self.effects.remove(start));
+        }
+        for (offset, effect) in effects.drain(..added).enumerate() {
+            self.effects.insert(start + offset, effect);
+        }
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut (dyn Effect + 'static)> {
+        self.effects.get_mut(index).map(|e| e.as_mut())
+    }
+
+    pub fn clear(&mut self) {
+        self.effects.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.effects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    pub fn set_bypassed(&mut self, bypassed: bool) {
+        self.bypassed = bypassed;
+    }
+
+    pub fn is_bypassed(&self) -> bool {
+        self.bypassed
+    }
+}
+
+impl Effect for EffectChain {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if self.bypassed {
+            return;
+        }
+        let blend = (self.mix < 1.0 || self.applied_mix < 1.0)
+            && self.dry.len() >= buffer.len()
+            && self.dry.first().is_some_and(|dry| dry.len() >= frames);
+        if !blend {
+            self.applied_mix = self.mix;
+            for effect in &mut self.effects {
+                effect.process(buffer, frames, sample_rate);
+            }
+            return;
+        }
+
+        for (dry, samples) in self.dry.iter_mut().zip(buffer.iter()) {
+            dry[..frames].copy_from_slice(&samples[..frames]);
+        }
+        for effect in &mut self.effects {
+            effect.process(buffer, frames, sample_rate);
+        }
+        let (from, step) = (self.applied_mix, (self.mix - self.applied_mix) / frames.max(1) as f32);
+        for (dry, samples) in self.dry.iter().zip(buffer.iter_mut()) {
+            for (i, (wet, dry)) in samples[..frames].iter_mut().zip(&dry[..frames]).enumerate() {
+                let mix = from + step * (i + 1) as f32;
+                *wet = dry + (*wet - dry) * mix;
+            }
+        }
+        self.applied_mix = self.mix;
+    }
+
+    fn reset(&mut self) {
+        self.effects.iter_mut().for_each(|e| e.reset());
+        self.applied_mix = self.mix;
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        memory.touch_all(&mut self.dry);
+        self.effects.iter_mut().for_each(|e| e.prefault(memory));
+    }
+
+    /// The effects' latencies add up; the dry path of a partial mix isn't delayed
+    fn latency(&self) -> usize {
+        if self.bypassed { 0 } else { self.effects.iter().map(|e| e.latency()).sum() }
+    }
+}
@@ -0,0 +1,245 @@
+use crate::rt_processing::filters::OnePoleState;
+use crate::rt_processing::modulation::{ControlRamp, DEFAULT_CONTROL_INTERVAL};
+use crate::rt_processing::prefault::Prefault;
+use crate::rt_processing::voice_renderer::AudioSource;
+use crate::rt_processing::waveform::noise::{CrackleNoise, FilteredNoise};
+use crate::rt_processing::waveform::oscillators::LFO;
+use crate::rt_processing::waveform::tables::WaveformType;
+
+use super::Effect;
+
+/// Longest modulated delay in seconds (base delay + deepest wow excursion)
+const MAX_DELAY_SECONDS: f32 = 0.02;
+const MAX_SAMPLE_RATE: f32 = 192_000.0;
+/// Center of the wow/flutter delay sweep
+const BASE_DELAY_SECONDS: f32 = 0.006;
+
+/// Character parameters. Every amount is 0.0 to 1.0 and is scaled by `intensity`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CharacterParams {
+    /// Macro scaling all other amounts
+    pub intensity: f32,
+    /// Slow pitch drift (~0.5 Hz)
+    pub wow: f32,
+    /// Fast pitch jitter (~7 Hz)
+    pub flutter: f32,
+    /// Soft saturation drive
+    pub saturation: f32,
+    /// High-frequency loss
+    pub age: f32,
+    /// Tape hiss level
+    pub hiss: f32,
+    /// Vinyl crackle level
+    pub crackle: f32,
+}
+
+impl Default for CharacterParams {
+    fn default() -> Self {
+        Self::tape()
+    }
+}
+
+impl CharacterParams {
+    /// Cassette-style: wow, flutter, saturation and hiss
+    pub fn tape() -> Self {
+        Self {
+            intensity: 0.5,
+            wow: 0.5,
+            flutter: 0.4,
+            saturation: 0.5,
+            age: 0.3,
+            hiss: 0.3,
+            crackle: 0.0,
+        }
+    }
+
+    /// Record-style: slow wow, dull top end and crackle
+    pub fn vinyl() -> Self {
+        Self {
+            intensity: 0.5,
+            wow: 0.3,
+            flutter: 0.05,
+            saturation: 0.2,
+            age: 0.5,
+            hiss: 0.05,
+            crackle: 0.6,
+        }
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    fn sanitize(self) -> Self {
+        Self {
+            intensity: self.intensity.clamp(0.0, 1.0),
+            wow: self.wow.clamp(0.0, 1.0),
+            flutter: self.flutter.clamp(0.0, 1.0),
+            saturation: self.saturation.clamp(0.0, 1.0),
+            age: self.age.clamp(0.0, 1.0),
+            hiss: self.hiss.clamp(0.0, 1.0),
+            crackle: self.crackle.clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// Tape/vinyl "character" bus effect.
+///
+/// Wow and flutter modulate a short delay line (pitch wobble), followed by soft
+/// `tanh` saturation and a one-pole high cut, with hiss and crackle from the
+/// noise module mixed on top. `intensity` is the one-knob macro: 0.0 is
+/// transparent apart from the fixed base delay.
+///
+/// The hiss is shaped for one sample rate in `prepare`, which allocates and
+/// belongs off the audio thread; until then it stays silent. Parameter changes
+/// are RT-safe.
+pub struct Character {
+    params: CharacterParams,
+    sample_rate: f32,
+    wow: LFO,
+    flutter: LFO,
+    // wow + flutter delay offset, at control rate
+    wobble: ControlRamp,
+    // [channel]
+    delays: Vec<Vec<f32>>,
+    write_pos: usize,
+    tone: Vec<OnePoleState>,
+    tone_coeff: f32,
+    hiss: FilteredNoise,
+    crackle: CrackleNoise,
+    noise_buffer: Vec<f32>,
+    crackle_buffer: Vec<f32>,
+}
+
+impl Character {
+    pub fn new(channels: usize, max_frames: usize, params: CharacterParams) -> Self {
+        let delay_len = (MAX_DELAY_SECONDS * MAX_SAMPLE_RATE) as usize + 2;
+        let mut hiss = FilteredNoise::new();
+        // hiss rises toward the top octaves
+        hiss.set_tilt(-20.0, 2.0);
+        let mut character = Self {
+            params: params.sanitize(),
+            sample_rate: 0.0,
+            wow: LFO::new(WaveformType::Sine, 0.55),
+            flutter: LFO::new(WaveformType::Sine, 7.3),
+            wobble: ControlRamp::new(DEFAULT_CONTROL_INTERVAL),
+            delays: vec![vec![0.0; delay_len]; channels],
+            write_pos: 0,
+            tone: vec![OnePoleState::default(); channels],
+            tone_coeff: 1.0,
+            hiss,
+            crackle: CrackleNoise::new().with_amplitude(1.0),
+            noise_buffer: vec![0.0; max_frames],
+            crackle_buffer: vec![0.0; max_frames],
+        };
+        character.apply_params();
+        character
+    }
+
+    pub fn params(&self) -> &CharacterParams {
+        &self.params
+    }
+
+    pub fn set_params(&mut self, params: CharacterParams) {
+        self.params = params.sanitize();
+        self.apply_params();
+    }
+
+    /// The macro knob
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.set_params(self.params.with_intensity(intensity));
+    }
+
+    /// Shape the hiss and set the filters for `sample_rate`. Allocates.
+    pub fn prepare(&mut self, sample_rate: f32) {
+        self.hiss.prepare(sample_rate);
+        self.sample_rate = sample_rate;
+        self.update_tone();
+    }
+
+    fn apply_params(&mut self) {
+        let p = self.params;
+        self.hiss.set_amplitude((p.hiss * p.intensity).powi(2));
+        self.crackle.set_density(5.0 + 60.0 * p.crackle * p.intensity);
+        self.crackle.set_amplitude(0.5 * p.crackle * p.intensity);
+        self.update_tone();
+    }
+
+    fn update_tone(&mut self) {
+        if self.sample_rate > 0.0 {
+            let age = self.params.age * self.params.intensity;
+            // 20 kHz (new) down to ~5 kHz (worn)
+            self.tone_coeff = OnePoleState::coefficient(20000.0 * 0.25f32.powf(age), self.sample_rate);
+        }
+    }
+}
+
+impl Effect for Character {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        // an unprepared rate only gets its filters; the hiss waits for `prepare`
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            self.update_tone();
+        }
+        let frames = frames.min(self.noise_buffer.len());
+        let p = self.params;
+        let len = self.delays.first().map_or(1, Vec::len);
+
+        // noise beds are mono and shared by all channels
+        let hiss = &mut self.noise_buffer[..frames];
+        let crackle = &mut self.crackle_buffer[..frames];
+        if p.hiss * p.intensity > 0.0 {
+            self.hiss.fill_buffer(hiss, sample_rate, 1, frames);
+        } else {
+            hiss.fill(0.0);
+        }
+        if p.crackle * p.intensity > 0.0 {
+            self.crackle.fill_buffer(crackle, sample_rate, 1, frames);
+        } else {
+            crackle.fill(0.0);
+        }
+
+        let wow_depth = 0.004 * p.wow * p.intensity * sample_rate;
+        let flutter_depth = 0.0002 * p.flutter * p.intensity * sample_rate;
+        let base = BASE_DELAY_SECONDS * sample_rate;
+        let saturation = p.saturation * p.intensity;
+        let drive = 1.0 + 3.0 * saturation;
+
+        for i in 0..frames {
+            let wobble = self.wobble.next(|n| {
+                self.wow.advance(n, sample_rate) * wow_depth + self.flutter.advance(n, sample_rate) * flutter_depth
+            });
+            let delay = (base + wobble).clamp(1.0, (len - 2) as f32);
+            let noise = self.noise_buffer[i] + self.crackle_buffer[i];
+
+            for ((samples, line), tone) in buffer.iter_mut().zip(&mut self.delays).zip(&mut self.tone) {
+                line[self.write_pos] = samples[i];
+                let read = self.write_pos as f32 + len as f32 - delay;
+                let index = read as usize;
+                let frac = read - index as f32;
+                let a = line[index % len];
+                let b = line[(index + 1) % len];
+                let wobbled = a + (b - a) * frac;
+
+                // unity small-signal gain; peaks are rounded off
+                let saturated = if saturation > 0.0 { (wobbled * drive).tanh() / drive } else { wobbled };
+                samples[i] = tone.low_pass(self.tone_coeff, saturated) + noise;
+            }
+            self.write_pos = (self.write_pos + 1) % len;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.delays.iter_mut().for_each(|d| d.fill(0.0));
+        self.tone.iter_mut().for_each(OnePoleState::reset);
+        self.write_pos = 0;
+        self.wobble.reset();
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        memory.touch_all(&mut self.delays);
+        memory.touch(&mut self.noise_buffer);
+        memory.touch(&mut self.crackle_buffer);
+    }
+}
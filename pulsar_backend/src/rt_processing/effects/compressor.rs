@@ -0,0 +1,173 @@
+use std::sync::Arc;
+
+use crossbeam::atomic::AtomicCell;
+
+use crate::rt_processing::filters::EnvelopeFollower;
+
+use super::Effect;
+
+/// Compressor parameters
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CompressorParams {
+    /// Level above which gain reduction starts, in dBFS
+    pub threshold_db: f32,
+    /// Input:output ratio above the threshold (1.0 = off)
+    pub ratio: f32,
+    /// Soft-knee width in dB (0.0 = hard knee)
+    pub knee_db: f32,
+    /// Gain-reduction attack in seconds
+    pub attack: f32,
+    /// Gain-reduction release in seconds
+    pub release: f32,
+    /// Gain applied after compression, in dB
+    pub makeup_db: f32,
+}
+
+impl Default for CompressorParams {
+    fn default() -> Self {
+        Self {
+            threshold_db: -18.0,
+            ratio: 4.0,
+            knee_db: 6.0,
+            attack: 0.01,
+            release: 0.1,
+            makeup_db: 0.0,
+        }
+    }
+}
+
+impl CompressorParams {
+    /// Static gain computer: gain change in dB (<= 0) for an input level in dB
+    pub fn gain_db(&self, level_db: f32) -> f32 {
+        let over = level_db - self.threshold_db;
+        let slope = 1.0 / self.ratio - 1.0;
+        let half_knee = self.knee_db * 0.5;
+        if over <= -half_knee {
+            0.0
+        } else if over >= half_knee {
+            slope * over
+        } else {
+            // quadratic interpolation through the knee
+            let x = over + half_knee;
+            slope * x * x / (2.0 * self.knee_db)
+        }
+    }
+}
+
+/// Feed-forward peak compressor with stereo-linked detection.
+///
+/// The detector takes the loudest channel each frame, so the stereo image doesn't
+/// shift under gain reduction. The current gain reduction (positive dB) is
+/// published for metering.
+pub struct Compressor {
+    params: CompressorParams,
+    sample_rate: f32,
+    smoother: EnvelopeFollower,
+    makeup: f32,
+    gain_reduction: Arc<AtomicCell<f32>>,
+}
+
+impl Compressor {
+    pub fn new(params: CompressorParams) -> Self {
+        Self {
+            params: Self::sanitize(params),
+            sample_rate: 0.0,
+            smoother: EnvelopeFollower::new(0.0, 0.0, 1.0),
+            makeup: 1.0,
+            gain_reduction: Arc::new(AtomicCell::new(0.0)),
+        }
+    }
+
+    pub fn params(&self) -> &CompressorParams {
+        &self.params
+    }
+
+    pub fn set_params(&mut self, params: CompressorParams) {
+        self.params = Self::sanitize(params);
+        self.sample_rate = 0.0;
+    }
+
+    /// Shared handle to the gain reduction in dB (0.0 = none)
+    pub fn gain_reduction_handle(&self) -> Arc<AtomicCell<f32>> {
+        Arc::clone(&self.gain_reduction)
+    }
+
+    pub fn gain_reduction(&self) -> f32 {
+        self.gain_reduction.load()
+    }
+
+    fn sanitize(params: CompressorParams) -> CompressorParams {
+        CompressorParams {
+            ratio: params.ratio.max(1.0),
+            knee_db: params.knee_db.max(0.0),
+            attack: params.attack.max(0.0),
+            release: params.release.max(0.0),
+            ..params
+        }
+    }
+
+    fn prepare(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.smoother.set_times(self.params.attack, self.params.release, sample_rate);
+        self.makeup = 10f32.powf(self.params.makeup_db / 20.0);
+    }
+
+    /// Compress `buffer` using `key` for detection (sidechain); `key` may be the
+    /// buffer's own contents. Detection uses the loudest key channel.
+    pub fn process_keyed(&mut self, buffer: &mut [&mut [f32]], key: &[&[f32]], frames: usize, sample_rate: f32) {
+        if self.sample_rate != sample_rate {
+            self.prepare(sample_rate);
+        }
+        for i in 0..frames {
+            let peak = key.iter().fold(0.0f32, |m, ch| m.max(ch[i].abs()));
+            let gain = self.compute_gain(peak);
+            for samples in buffer.iter_mut() {
+                samples[i] *= gain;
+            }
+        }
+        self.gain_reduction.store(self.smoother.value());
+    }
+
+    /// Advance the detector by one frame with a detection `peak` and return the
+    /// linear gain to apply (makeup included). For building compound effects
+    /// that split the signal themselves.
+    #[inline]
+    pub fn next_gain(&mut self, peak: f32, sample_rate: f32) -> f32 {
+        if self.sample_rate != sample_rate {
+            self.prepare(sample_rate);
+        }
+        let gain = self.compute_gain(peak);
+        self.gain_reduction.store(self.smoother.value());
+        gain
+    }
+
+    /// Linear gain for a detector level; smooths gain reduction (in dB) with the
+    /// attack/release follower
+    #[inline]
+    fn compute_gain(&mut self, peak: f32) -> f32 {
+        let level_db = 20.0 * peak.max(1e-6).log10();
+        let reduction = self.smoother.process(-self.params.gain_db(level_db));
+        10f32.powf(-reduction / 20.0) * self.makeup
+    }
+}
+
+impl Effect for Compressor {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if self.sample_rate != sample_rate {
+            self.prepare(sample_rate);
+        }
+        for i in 0..frames {
+            let peak = buffer.iter().fold(0.0f32, |m, ch| m.max(ch[i].abs()));
+            let gain = self.compute_gain(peak);
+            for samples in buffer.iter_mut() {
+                samples[i] *= gain;
+            }
+        }
+        self.gain_reduction.store(self.smoother.value());
+    }
+
+    fn reset(&mut self) {
+        self.smoother.reset();
+        self.gain_reduction.store(0.0);
+    }
+}
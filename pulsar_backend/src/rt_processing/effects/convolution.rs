@@ -0,0 +1,291 @@
+use std::path::{Path, PathBuf};
+
+use crate::io::wav::{self, WavResult};
+use crate::jobs::{JobHandle, JobPriority, JobProgress, WorkerPool};
+use crate::rt_processing::fft::{Complex, Fft};
+use crate::rt_processing::prefault::Prefault;
+
+use super::Effect;
+
+/// Default partition size in samples (also the convolver's latency)
+pub const DEFAULT_PARTITION_SIZE: usize = 256;
+
+/// Impulse response data, one `Vec` per channel
+#[derive(Clone, Debug)]
+pub struct ImpulseResponse {
+    channels: Vec<Vec<f32>>,
+    sample_rate: f32,
+}
+
+impl ImpulseResponse {
+    /// Empty channels are dropped; an IR without any samples becomes a unit impulse
+    pub fn new(channels: Vec<Vec<f32>>, sample_rate: f32) -> Self {
+        let mut channels: Vec<Vec<f32>> = channels.into_iter().filter(|c| !c.is_empty()).collect();
+        if channels.is_empty() {
+            channels.push(vec![1.0]);
+        }
+        Self { channels, sample_rate }
+    }
+
+    pub fn mono(samples: Vec<f32>, sample_rate: f32) -> Self {
+        Self::new(vec![samples], sample_rate)
+    }
+
+    /// Load a user IR from a WAV file
+    pub fn from_wav(path: impl AsRef<Path>) -> WavResult<Self> {
+        let audio = wav::read_file(path)?;
+        Ok(Self::new(audio.channels, audio.sample_rate as f32))
+    }
+
+    /// Load a user IR on a background job
+    pub fn load_job(pool: &WorkerPool, path: impl Into<PathBuf>) -> JobHandle<WavResult<Self>> {
+        let path = path.into();
+        pool.spawn(JobPriority::Io, format!("IR load {}", path.display()), move |_| Self::from_wav(path))
+    }
+
+    /// Scale so the IR has unit energy (broadband level roughly preserved)
+    pub fn normalized(mut self) -> Self {
+        let energy = self.channels.iter().flatten().map(|s| s * s).sum::<f32>() / self.channels.len() as f32;
+        if energy > 0.0 {
+            let k = 1.0 / energy.sqrt();
+            self.channels.iter_mut().flatten().for_each(|s| *s *= k);
+        }
+        self
+    }
+
+    /// Cut the IR to at most `seconds`, with a short fade to avoid a hard edge
+    pub fn truncated(mut self, seconds: f32) -> Self {
+        let max = ((seconds * self.sample_rate) as usize).max(1);
+        let fade = (max / 16).max(1);
+        for channel in &mut self.channels {
+            if channel.len() > max {
+                channel.truncate(max);
+                for (i, s) in channel.iter_mut().rev().take(fade).enumerate() {
+                    *s *= i as f32 / fade as f32;
+                }
+            }
+        }
+        self
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Length in samples of the longest channel
+    pub fn len(&self) -> usize {
+        self.channels.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    pub fn channel(&self, index: usize) -> Option<&[f32]> {
+        self.channels.get(index).map(Vec::as_slice)
+    }
+}
+
+/// Per-channel streaming state
+struct ChannelState {
+    // previous + current input block (2 * partition)
+    input: Vec<f32>,
+    // output of the last completed block (partition)
+    output: Vec<f32>,
+    // frequency-domain delay line: spectra of the most recent input blocks
+    history: Vec<Vec<Complex>>,
+}
+
+/// Uniformly partitioned FFT convolution (overlap-save).
+///
+/// The IR is cut into `partition_size` blocks whose spectra are computed in
+/// `new`, on the calling (control) thread; `process` then only runs one FFT/IFFT
+/// pair per channel per partition and never allocates. Latency is one partition,
+/// and the dry signal is delayed to match. Buffer channel `n` uses IR channel `n`
+/// (or the last IR channel when the IR has fewer). The IR is used at its native
+/// rate; resample it beforehand if it differs from the engine rate.
+pub struct Convolver {
+    partition: usize,
+    fft: Fft,
+    // [ir channel][partition][bin]
+    filters: Vec<Vec<Vec<Complex>>>,
+    states: Vec<ChannelState>,
+    history_pos: usize,
+    fill: usize,
+    spectrum: Vec<Complex>,
+    accumulator: Vec<Complex>,
+    wet: f32,
+    dry: f32,
+}
+
+impl Convolver {
+    /// `partition_size` is rounded up to a power of two
+    pub fn new(ir: &ImpulseResponse, channels: usize, partition_size: usize) -> Self {
+        Self::new_with_progress(ir, channels, partition_size, &JobProgress::detached())
+    }
+
+    /// Prepare a convolver for a long IR on a background job
+    pub fn prepare_job(
+        pool: &WorkerPool,
+        ir: ImpulseResponse,
+        channels: usize,
+        partition_size: usize,
+    ) -> JobHandle<Self> {
+        pool.spawn(JobPriority::Compute, "convolver prepare", move |progress| {
+            Self::new_with_progress(&ir, channels, partition_size, progress)
+        })
+    }
+
+    /// `new`, reporting progress per IR partition. When cancelled, the remaining
+    /// partitions are left silent.
+    pub fn new_with_progress(
+        ir: &ImpulseResponse,
+        channels: usize,
+        partition_size: usize,
+        progress: &JobProgress,
+    ) -> Self {
+        let partition = partition_size.max(16).next_power_of_two();
+        let fft = Fft::new(partition * 2);
+        let partitions = ir.len().div_ceil(partition).max(1);
+        let total = (partitions * ir.channels.len()) as f32;
+
+        let mut filters = vec![vec![vec![Complex::ZERO; partition * 2]; partitions]; ir.channels.len()];
+        'channels: for (ch, (samples, filter)) in ir.channels.iter().zip(&mut filters).enumerate() {
+            for (p, spectrum) in filter.iter_mut().enumerate() {
+                if progress.is_cancelled() {
+                    break 'channels;
+                }
+                let start = (p * partition).min(samples.len());
+                let end = (start + partition).min(samples.len());
+                for (bin, &s) in spectrum.iter_mut().zip(&samples[start..end]) {
+                    bin.re = s;
+                }
+                fft.forward(spectrum);
+                progress.set((ch * partitions + p + 1) as f32 / total);
+            }
+        }
+
+        let states = (0..channels)
+            .map(|_| ChannelState {
+                input: vec![0.0; partition * 2],
+                output: vec![0.0; partition],
+                history: vec![vec![Complex::ZERO; partition * 2]; partitions],
+            })
+            .collect();
+
+        Self {
+            partition,
+            fft,
+            filters,
+            states,
+            history_pos: 0,
+            fill: 0,
+            spectrum: vec![Complex::ZERO; partition * 2],
+            accumulator: vec![Complex::ZERO; partition * 2],
+            wet: 1.0,
+            dry: 0.0,
+        }
+    }
+
+    pub fn with_mix(mut self, wet: f32, dry: f32) -> Self {
+        self.set_mix(wet, dry);
+        self
+    }
+
+    /// Linear wet and dry levels
+    pub fn set_mix(&mut self, wet: f32, dry: f32) {
+        self.wet = wet.max(0.0);
+        self.dry = dry.max(0.0);
+    }
+
+    /// Processing latency in samples
+    pub fn latency(&self) -> usize {
+        self.partition
+    }
+
+    /// Convolve the block of input collected in each channel's `input`
+    fn run_block(&mut self) {
+        let n = self.partition;
+        let partitions = self.filters[0].len();
+        let last_filter = self.filters.len() - 1;
+
+        for (ch, state) in self.states.iter_mut().enumerate() {
+            for (bin, &s) in self.spectrum.iter_mut().zip(&state.input) {
+                *bin = Complex::new(s, 0.0);
+            }
+            self.fft.forward(&mut self.spectrum);
+            state.history[self.history_pos].copy_from_slice(&self.spectrum);
+
+            // Y = sum over partitions of X[k - p] * H[p]
+            self.accumulator.fill(Complex::ZERO);
+            let filter = &self.filters[ch.min(last_filter)];
+            for (p, h) in filter.iter().enumerate() {
+                let x = &state.history[(self.history_pos + partitions - p) % partitions];
+                for ((acc, &a), &b) in self.accumulator.iter_mut().zip(x).zip(h) {
+                    *acc = acc.mul_add(a, b);
+                }
+            }
+            self.fft.inverse(&mut self.accumulator);
+
+            // overlap-save: the second half is the valid linear convolution
+            for (out, acc) in state.output.iter_mut().zip(&self.accumulator[n..]) {
+                *out = acc.re;
+            }
+            state.input.copy_within(n.., 0);
+        }
+        self.history_pos = (self.history_pos + 1) % partitions;
+    }
+}
+
+impl Effect for Convolver {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        let n = self.partition;
+        let channels = buffer.len().min(self.states.len());
+        for i in 0..frames {
+            let fill = self.fill;
+            for (samples, state) in buffer.iter_mut().zip(&mut self.states).take(channels) {
+                let x = samples[i];
+                // input[fill] still holds the sample from one partition ago
+                let delayed = state.input[fill];
+                state.input[n + fill] = x;
+                samples[i] = state.output[fill] * self.wet + delayed * self.dry;
+            }
+            self.fill += 1;
+            if self.fill == n {
+                self.fill = 0;
+                self.run_block();
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for state in &mut self.states {
+            state.input.fill(0.0);
+            state.output.fill(0.0);
+            state.history.iter_mut().for_each(|h| h.fill(Complex::ZERO));
+        }
+        self.history_pos = 0;
+        self.fill = 0;
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        for state in &mut self.states {
+            memory.touch(&mut state.input);
+            memory.touch(&mut state.output);
+            memory.touch_all(&mut state.history);
+        }
+        for channel in &mut self.filters {
+            memory.touch_all(channel);
+        }
+        memory.touch(&mut self.spectrum);
+        memory.touch(&mut self.accumulator);
+    }
+
+    fn latency(&self) -> usize {
+        Convolver::latency(self)
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use crossbeam::atomic::AtomicCell;
+
+use crate::rt_processing::filters::{CrossoverCoefficients, CrossoverState};
+
+use super::Effect;
+use super::compressor::{Compressor, CompressorParams};
+use super::multiband::MAX_SPLIT_CHANNELS;
+
+/// What the de-esser's gain reduction is applied to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DeEsserMode {
+    /// Only the band above the split frequency is turned down
+    SplitBand,
+    /// The whole signal is turned down while sibilance is detected
+    Wideband,
+    /// Output the sibilance band alone, for tuning the frequency
+    Listen,
+}
+
+/// De-esser parameters
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DeEsserParams {
+    /// Split frequency in Hz; sibilance is detected above it
+    pub frequency: f32,
+    /// Sibilance level where reduction starts, in dBFS
+    pub threshold_db: f32,
+    pub ratio: f32,
+    pub mode: DeEsserMode,
+}
+
+impl Default for DeEsserParams {
+    fn default() -> Self {
+        Self {
+            frequency: 6000.0,
+            threshold_db: -30.0,
+            ratio: 6.0,
+            mode: DeEsserMode::SplitBand,
+        }
+    }
+}
+
+impl DeEsserParams {
+    fn compressor(&self) -> CompressorParams {
+        CompressorParams {
+            threshold_db: self.threshold_db,
+            ratio: self.ratio,
+            knee_db: 3.0,
+            // sibilants are short: grab fast, let go before the next vowel
+            attack: 0.0005,
+            release: 0.04,
+            makeup_db: 0.0,
+        }
+    }
+}
+
+/// Frequency-selective compressor for taming sibilance on vocal input.
+///
+/// An LR4 crossover splits off the sibilance band, whose stereo-linked peak
+/// drives a fast `Compressor`. In split-band mode only the high band is reduced
+/// and recombined with the untouched low band, so the rest of the voice keeps
+/// its level.
+pub struct DeEsser {
+    params: DeEsserParams,
+    sample_rate: f32,
+    coeffs: CrossoverCoefficients,
+    states: [CrossoverState; MAX_SPLIT_CHANNELS],
+    compressor: Compressor,
+}
+
+impl DeEsser {
+    pub fn new(params: DeEsserParams) -> Self {
+        Self {
+            params,
+            sample_rate: 0.0,
+            coeffs: CrossoverCoefficients::new(params.frequency, 48000.0),
+            states: [CrossoverState::default(); MAX_SPLIT_CHANNELS],
+            compressor: Compressor::new(params.compressor()),
+        }
+    }
+
+    pub fn params(&self) -> &DeEsserParams {
+        &self.params
+    }
+
+    pub fn set_params(&mut self, params: DeEsserParams) {
+        self.params = params;
+        self.compressor.set_params(params.compressor());
+        self.sample_rate = 0.0;
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.set_params(DeEsserParams { frequency, ..self.params });
+    }
+
+    pub fn set_threshold(&mut self, threshold_db: f32) {
+        self.set_params(DeEsserParams { threshold_db, ..self.params });
+    }
+
+    pub fn set_mode(&mut self, mode: DeEsserMode) {
+        self.params.mode = mode;
+    }
+
+    /// Shared handle to the gain reduction in dB, for metering
+    pub fn gain_reduction_handle(&self) -> Arc<AtomicCell<f32>> {
+        self.compressor.gain_reduction_handle()
+    }
+}
+
+impl Effect for DeEsser {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            self.coeffs = CrossoverCoefficients::new(self.params.frequency, sample_rate);
+        }
+        let channels = buffer.len().min(MAX_SPLIT_CHANNELS);
+        let mode = self.params.mode;
+
+        for i in 0..frames {
+            let mut lows = [0.0; MAX_SPLIT_CHANNELS];
+            let mut highs = [0.0; MAX_SPLIT_CHANNELS];
+            let mut peak = 0.0f32;
+            let split = buffer.iter().zip(&mut self.states).zip(lows.iter_mut().zip(highs.iter_mut()));
+            for ((samples, state), (low, high)) in split.take(channels) {
+                (*low, *high) = state.split(&self.coeffs, samples[i]);
+                peak = peak.max(high.abs());
+            }
+
+            let gain = self.compressor.next_gain(peak, sample_rate);
+            for ((samples, low), high) in buffer.iter_mut().zip(&lows).zip(&highs).take(channels) {
+                samples[i] = match mode {
+                    DeEsserMode::SplitBand => low + high * gain,
+                    DeEsserMode::Wideband => (low + high) * gain,
+                    DeEsserMode::Listen => high * gain,
+                };
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.states.iter_mut().for_each(CrossoverState::reset);
+        self.compressor.reset();
+    }
+}
@@ -0,0 +1,174 @@
+use crate::rt_processing::filters::EnvelopeFollower;
+
+use super::{DEFAULT_TEMPO_BPM, Effect};
+
+/// Longest supported gate pattern
+pub const MAX_GATE_STEPS: usize = 32;
+
+/// Step pattern for `PatternGate`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GatePattern {
+    /// Level per step (0.0 = closed, 1.0 = open); only the first `length` are used
+    pub levels: [f32; MAX_GATE_STEPS],
+    /// Active steps (16 or 32)
+    pub length: usize,
+    /// Step duration in quarter notes (0.25 = 1/16)
+    pub step_beats: f32,
+}
+
+impl Default for GatePattern {
+    fn default() -> Self {
+        // classic on/off eighths in sixteenth steps
+        let mut levels = [0.0; MAX_GATE_STEPS];
+        for (i, level) in levels.iter_mut().enumerate() {
+            *level = if i % 2 == 0 { 1.0 } else { 0.0 };
+        }
+        Self {
+            levels,
+            length: 16,
+            step_beats: 0.25,
+        }
+    }
+}
+
+impl GatePattern {
+    /// Pattern from a slice of step levels; the length is rounded up to 16 or 32
+    pub fn from_levels(steps: &[f32]) -> Self {
+        let mut levels = [0.0; MAX_GATE_STEPS];
+        let used = steps.len().min(MAX_GATE_STEPS);
+        for (dst, &src) in levels.iter_mut().zip(&steps[..used]) {
+            *dst = src.clamp(0.0, 1.0);
+        }
+        Self {
+            levels,
+            length: if used > 16 { 32 } else { 16 },
+            ..Self::default()
+        }
+    }
+
+    pub fn with_step_beats(mut self, step_beats: f32) -> Self {
+        self.step_beats = step_beats.max(1.0 / 64.0);
+        self
+    }
+}
+
+/// Rhythmic (trance) gate.
+///
+/// Steps through a 16/32-step level pattern at the tempo given to `set_tempo`;
+/// `sync_to_beat` aligns the pattern with the song position. Level changes are
+/// smoothed with separate attack/release times so hard patterns don't click.
+pub struct PatternGate {
+    pattern: GatePattern,
+    tempo_bpm: f32,
+    // position within the pattern in beats
+    position: f64,
+    attack: f32,
+    release: f32,
+    sample_rate: f32,
+    smoother: EnvelopeFollower,
+    depth: f32,
+}
+
+impl PatternGate {
+    pub fn new(pattern: GatePattern) -> Self {
+        Self {
+            pattern: Self::sanitize(pattern),
+            tempo_bpm: DEFAULT_TEMPO_BPM,
+            position: 0.0,
+            attack: 0.002,
+            release: 0.01,
+            sample_rate: 0.0,
+            smoother: EnvelopeFollower::new(0.0, 0.0, 1.0),
+            depth: 1.0,
+        }
+    }
+
+    /// Edge smoothing in seconds
+    pub fn with_smoothing(mut self, attack: f32, release: f32) -> Self {
+        self.set_smoothing(attack, release);
+        self
+    }
+
+    pub fn set_smoothing(&mut self, attack: f32, release: f32) {
+        self.attack = attack.max(0.0);
+        self.release = release.max(0.0);
+        self.sample_rate = 0.0;
+    }
+
+    /// Blend between bypass (0.0) and the full pattern (1.0)
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    pub fn set_pattern(&mut self, pattern: GatePattern) {
+        self.pattern = Self::sanitize(pattern);
+    }
+
+    /// Set one step's level. Returns false if `step` is out of range.
+    pub fn set_step(&mut self, step: usize, level: f32) -> bool {
+        let Some(slot) = self.pattern.levels.get_mut(step) else {
+            return false;
+        };
+        *slot = level.clamp(0.0, 1.0);
+        true
+    }
+
+    pub fn pattern(&self) -> &GatePattern {
+        &self.pattern
+    }
+
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.tempo_bpm = bpm.max(1.0);
+    }
+
+    /// Align the pattern with a song position in beats (quarter notes)
+    pub fn sync_to_beat(&mut self, beat: f64) {
+        self.position = beat.rem_euclid(self.pattern_beats());
+    }
+
+    /// Step currently playing
+    pub fn current_step(&self) -> usize {
+        ((self.position / self.pattern.step_beats as f64) as usize).min(self.pattern.length - 1)
+    }
+
+    fn pattern_beats(&self) -> f64 {
+        self.pattern.length as f64 * self.pattern.step_beats as f64
+    }
+
+    fn sanitize(pattern: GatePattern) -> GatePattern {
+        GatePattern {
+            length: if pattern.length > 16 { 32 } else { 16 },
+            step_beats: pattern.step_beats.max(1.0 / 64.0),
+            ..pattern
+        }
+    }
+}
+
+impl Effect for PatternGate {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            self.smoother.set_times(self.attack, self.release, sample_rate);
+        }
+        let beats_per_sample = self.tempo_bpm as f64 / 60.0 / sample_rate as f64;
+        let pattern_beats = self.pattern_beats();
+
+        for i in 0..frames {
+            let level = self.pattern.levels[self.current_step()];
+            let gain = self.smoother.process(level);
+            let gain = 1.0 - self.depth * (1.0 - gain);
+            for samples in buffer.iter_mut() {
+                samples[i] *= gain;
+            }
+            self.position += beats_per_sample;
+            if self.position >= pattern_beats {
+                self.position -= pattern_beats;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.position = 0.0;
+        self.smoother.reset();
+    }
+}
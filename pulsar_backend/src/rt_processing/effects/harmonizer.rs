@@ -0,0 +1,279 @@
+use crate::rt_processing::analysis::Analyzer;
+use crate::rt_processing::analysis::pitch::{PitchDetector, PitchDetectorConfig};
+use crate::rt_processing::filters::EnvelopeFollower;
+use crate::rt_processing::notes::Scale;
+use crate::rt_processing::prefault::Prefault;
+
+use super::Effect;
+use super::pitch_shift::PitchShifter;
+
+/// Upper bound on harmony voices (state is preallocated for this many)
+pub const MAX_HARMONY_VOICES: usize = 4;
+
+/// How a harmony voice's pitch is derived from the input
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HarmonyInterval {
+    /// Fixed shift in semitones, independent of the input note
+    Semitones(f32),
+    /// Shift by scale steps relative to the detected input note
+    /// (e.g. +2 = a diatonic third above). Requires a scale on the harmonizer.
+    ScaleSteps(i32),
+}
+
+/// One harmony voice
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HarmonyVoice {
+    pub interval: HarmonyInterval,
+    /// Linear gain
+    pub level: f32,
+    /// Stereo position (-1.0 = left, 1.0 = right); ignored for non-stereo buses
+    pub pan: f32,
+}
+
+impl HarmonyVoice {
+    pub fn semitones(semitones: f32) -> Self {
+        Self {
+            interval: HarmonyInterval::Semitones(semitones),
+            level: 0.7,
+            pan: 0.0,
+        }
+    }
+
+    pub fn scale_steps(steps: i32) -> Self {
+        Self {
+            interval: HarmonyInterval::ScaleSteps(steps),
+            level: 0.7,
+            pan: 0.0,
+        }
+    }
+
+    pub fn with_level(mut self, level: f32) -> Self {
+        self.level = level.max(0.0);
+        self
+    }
+
+    pub fn with_pan(mut self, pan: f32) -> Self {
+        self.pan = pan.clamp(-1.0, 1.0);
+        self
+    }
+}
+
+/// Per-voice runtime state
+struct VoiceState {
+    voice: Option<HarmonyVoice>,
+    // smoothed shift, glides toward the target so scale-aware voices don't click
+    semitones: f32,
+    // smoothed gain, fades voices in and out
+    gain: f32,
+    // [channel]
+    shifters: Vec<PitchShifter>,
+}
+
+/// Real-time harmonizer.
+///
+/// Adds up to `MAX_HARMONY_VOICES` pitch-shifted copies of the bus signal. Fixed
+/// voices shift by a constant interval; scale-aware voices follow the input note
+/// (YIN on channel 0) and pick the interval that keeps the harmony inside the
+/// configured `Scale`. Scale-aware voices stay silent until a note has been
+/// detected and hold their last interval through unvoiced passages.
+pub struct Harmonizer {
+    channels: usize,
+    sample_rate: f32,
+    voices: [VoiceState; MAX_HARMONY_VOICES],
+    scale: Option<Scale>,
+    detector: PitchDetector,
+    input_note: Option<u8>,
+    dry_level: f32,
+    glide_seconds: f32,
+    glide_coeff: f32,
+}
+
+impl Harmonizer {
+    pub fn new(channels: usize) -> Self {
+        let voices = std::array::from_fn(|_| VoiceState {
+            voice: None,
+            semitones: 0.0,
+            gain: 0.0,
+            shifters: (0..channels).map(|_| PitchShifter::new(0.04)).collect(),
+        });
+        Self {
+            channels,
+            sample_rate: 0.0,
+            voices,
+            scale: None,
+            detector: PitchDetector::new(PitchDetectorConfig::voice()),
+            input_note: None,
+            dry_level: 1.0,
+            glide_seconds: 0.02,
+            glide_coeff: 1.0,
+        }
+    }
+
+    /// Builder form of `set_voice` for the first free slot
+    pub fn with_voice(mut self, voice: HarmonyVoice) -> Self {
+        if let Some(index) = self.voices.iter().position(|v| v.voice.is_none()) {
+            self.set_voice(index, Some(voice));
+        }
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Scale) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    /// Set or clear a voice slot. Returns false if `index` is out of range.
+    pub fn set_voice(&mut self, index: usize, voice: Option<HarmonyVoice>) -> bool {
+        let Some(state) = self.voices.get_mut(index) else {
+            return false;
+        };
+        if state.voice.is_none() {
+            // start a freshly enabled voice at its target instead of gliding from 0
+            state.semitones = voice
+                .and_then(|v| Self::target_semitones(v.interval, self.scale, self.input_note))
+                .unwrap_or(0.0);
+            state.shifters.iter_mut().for_each(PitchShifter::reset);
+        }
+        state.voice = voice;
+        true
+    }
+
+    pub fn voice(&self, index: usize) -> Option<HarmonyVoice> {
+        self.voices.get(index).and_then(|v| v.voice)
+    }
+
+    /// Scale used by `HarmonyInterval::ScaleSteps` voices
+    pub fn set_scale(&mut self, scale: Option<Scale>) {
+        self.scale = scale;
+    }
+
+    pub fn scale(&self) -> Option<Scale> {
+        self.scale
+    }
+
+    /// Level of the unprocessed input in the output (0.0 = harmonies only)
+    pub fn set_dry_level(&mut self, level: f32) {
+        self.dry_level = level.max(0.0);
+    }
+
+    /// Time taken by scale-aware voices to slide to a new interval
+    pub fn set_glide(&mut self, seconds: f32) {
+        self.glide_seconds = seconds.max(0.0);
+        self.sample_rate = 0.0;
+    }
+
+    /// Grain window of the pitch shifters (see `PitchShifter::new`)
+    pub fn set_window(&mut self, seconds: f32) {
+        for state in &mut self.voices {
+            state.shifters.iter_mut().for_each(|s| s.set_window_seconds(seconds));
+        }
+    }
+
+    /// Last detected input note, as used by scale-aware voices
+    pub fn input_note(&self) -> Option<u8> {
+        self.input_note
+    }
+
+    fn target_semitones(interval: HarmonyInterval, scale: Option<Scale>, note: Option<u8>) -> Option<f32> {
+        match interval {
+            HarmonyInterval::Semitones(semitones) => Some(semitones),
+            HarmonyInterval::ScaleSteps(steps) => {
+                let (scale, note) = (scale?, note?);
+                Some(scale.diatonic_shift(note, steps) as f32)
+            }
+        }
+    }
+
+    fn prepare(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.glide_coeff = EnvelopeFollower::time_coefficient(self.glide_seconds, sample_rate);
+    }
+}
+
+impl Effect for Harmonizer {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if self.sample_rate != sample_rate {
+            self.prepare(sample_rate);
+        }
+
+        let scale_aware = self
+            .voices
+            .iter()
+            .any(|v| matches!(v.voice, Some(HarmonyVoice { interval: HarmonyInterval::ScaleSteps(_), .. })));
+        if scale_aware
+            && let Some(first) = buffer.first()
+            && let Some(reading) = self.detector.push_samples(&first[..frames], sample_rate)
+            && let Some((note, _cents)) = reading.nearest_note()
+        {
+            self.input_note = Some(note);
+        }
+
+        let channels = buffer.len().min(self.channels);
+        let stereo = channels == 2;
+        let glide = self.glide_coeff;
+        // fades are short and fixed so enabling/disabling a voice never clicks
+        let fade = EnvelopeFollower::time_coefficient(0.005, sample_rate);
+        let dry = self.dry_level;
+
+        for i in 0..frames {
+            let mut out = [0.0f32; 2];
+            let mut wet_mono = 0.0;
+            let dry_frame = |ch: usize| buffer[ch][i];
+
+            for state in &mut self.voices {
+                let target = state
+                    .voice
+                    .and_then(|v| Self::target_semitones(v.interval, self.scale, self.input_note).map(|t| (v, t)));
+                let (level, pan) = match target {
+                    Some((voice, semitones)) => {
+                        state.semitones += glide * (semitones - state.semitones);
+                        (voice.level, voice.pan)
+                    }
+                    None => (0.0, 0.0),
+                };
+                state.gain += fade * (level - state.gain);
+                if state.gain < 1e-5 && level == 0.0 {
+                    continue;
+                }
+
+                let ratio = 2f32.powf(state.semitones / 12.0);
+                if stereo {
+                    // equal-power pan
+                    let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+                    let (right, left) = angle.sin_cos();
+                    out[0] += state.shifters[0].process(dry_frame(0), ratio, sample_rate) * state.gain * left;
+                    out[1] += state.shifters[1].process(dry_frame(1), ratio, sample_rate) * state.gain * right;
+                } else if channels > 0 {
+                    wet_mono += state.shifters[0].process(dry_frame(0), ratio, sample_rate) * state.gain;
+                }
+            }
+
+            if stereo {
+                for (ch, wet) in out.iter().enumerate() {
+                    buffer[ch][i] = buffer[ch][i] * dry + wet;
+                }
+            } else {
+                // mono or multichannel: harmonies follow channel 0, other channels keep only the dry signal
+                for (ch, samples) in buffer.iter_mut().take(channels).enumerate() {
+                    let wet = if ch == 0 { wet_mono } else { 0.0 };
+                    samples[i] = samples[i] * dry + wet;
+                }
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for state in &mut self.voices {
+            state.shifters.iter_mut().for_each(PitchShifter::reset);
+            state.gain = 0.0;
+        }
+        self.detector.reset();
+        self.input_note = None;
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        for voice in &mut self.voices {
+            voice.shifters.iter_mut().for_each(|shifter| shifter.prefault(memory));
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::sync::Arc;
+
+use crossbeam::atomic::AtomicCell;
+
+use crate::rt_processing::filters::OnePoleState;
+use crate::rt_processing::prefault::Prefault;
+
+use super::Effect;
+use super::multiband::{MAX_BANDS, MultibandSplitter};
+
+/// Longest Haas delay in seconds
+pub const MAX_HAAS_DELAY: f32 = 0.03;
+const MAX_SAMPLE_RATE: f32 = 192_000.0;
+
+/// Mid/side width on the first two channels. The width is read through a shared
+/// cell each block so it can be changed after the effect is boxed into a chain.
+pub struct StereoWidth {
+    width: Arc<AtomicCell<f32>>,
+}
+
+impl StereoWidth {
+    /// `width`: 0.0 = mono, 1.0 = unchanged, up to 2.0 = extra wide
+    pub fn new(width: f32) -> Self {
+        Self { width: Arc::new(AtomicCell::new(width.clamp(0.0, 2.0))) }
+    }
+
+    pub fn width_handle(&self) -> Arc<AtomicCell<f32>> {
+        Arc::clone(&self.width)
+    }
+
+    pub fn set_width(&self, width: f32) {
+        self.width.store(width.clamp(0.0, 2.0));
+    }
+}
+
+impl Effect for StereoWidth {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        let [left, right, ..] = buffer else { return };
+        let width = self.width.load();
+        if width == 1.0 {
+            return;
+        }
+        for (l, r) in left[..frames].iter_mut().zip(right[..frames].iter_mut()) {
+            let mid = (*l + *r) * 0.5;
+            let side = (*l - *r) * 0.5 * width;
+            *l = mid + side;
+            *r = mid - side;
+        }
+    }
+}
+
+/// Mono-compatibility reading
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StereoCorrelation {
+    /// Phase correlation (-1.0 = out of phase, 0.0 = uncorrelated, 1.0 = mono)
+    pub correlation: f32,
+    /// Level change in dB when folded to mono (0.0 = none, negative = cancellation)
+    pub mono_loss_db: f32,
+}
+
+impl Default for StereoCorrelation {
+    fn default() -> Self {
+        Self { correlation: 1.0, mono_loss_db: 0.0 }
+    }
+}
+
+/// Stereo imager: per-band width over an LR4 split, a Haas micro-delay on one
+/// side, and a correlation meter on the output for checking mono compatibility.
+///
+/// Widths are per band of the splitter (low band first). A common setup narrows
+/// the lows to mono and widens the top. The Haas delay (positive = delay the
+/// right channel, negative = the left) adds width at the cost of comb filtering
+/// in mono, which the meter makes visible.
+pub struct StereoImager {
+    splitter: MultibandSplitter,
+    widths: [Option<Arc<AtomicCell<f32>>>; MAX_BANDS],
+    haas_seconds: f32,
+    // delay line for the delayed side
+    delay: Vec<f32>,
+    write_pos: usize,
+    // exponentially averaged L*R, L², R², M²
+    averages: [OnePoleState; 4],
+    average_coeff: f32,
+    sample_rate: f32,
+    correlation: Arc<AtomicCell<StereoCorrelation>>,
+}
+
+impl StereoImager {
+    /// `crossovers`: 1-3 split frequencies; every band starts at width 1.0
+    pub fn new(max_frames: usize, crossovers: &[f32]) -> Self {
+        let mut splitter = MultibandSplitter::new(2, max_frames, crossovers);
+        let mut widths: [Option<Arc<AtomicCell<f32>>>; MAX_BANDS] = Default::default();
+        for (band, slot) in widths.iter_mut().enumerate().take(splitter.band_count()) {
+            let width = StereoWidth::new(1.0);
+            *slot = Some(width.width_handle());
+            if let Some(chain) = splitter.band_mut(band) {
+                chain.push(Box::new(width));
+            }
+        }
+        Self {
+            splitter,
+            widths,
+            haas_seconds: 0.0,
+            delay: vec![0.0; (MAX_HAAS_DELAY * MAX_SAMPLE_RATE) as usize + 1],
+            write_pos: 0,
+            averages: [OnePoleState::default(); 4],
+            average_coeff: 1.0,
+            sample_rate: 0.0,
+            correlation: Arc::new(AtomicCell::new(StereoCorrelation::default())),
+        }
+    }
+
+    /// Set the width of `band` (0.0 = mono .. 2.0). Returns false for an unknown band.
+    pub fn set_band_width(&self, band: usize, width: f32) -> bool {
+        match self.widths.get(band).and_then(Option::as_ref) {
+            Some(cell) => {
+                cell.store(width.clamp(0.0, 2.0));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn band_width(&self, band: usize) -> Option<f32> {
+        self.widths.get(band).and_then(Option::as_ref).map(|cell| cell.load())
+    }
+
+    /// Haas delay in seconds, clamped to ±`MAX_HAAS_DELAY`
+    pub fn set_haas_delay(&mut self, seconds: f32) {
+        let seconds = seconds.clamp(-MAX_HAAS_DELAY, MAX_HAAS_DELAY);
+        if seconds.signum() != self.haas_seconds.signum() {
+            // the other side is delayed now: don't replay its history
+            self.delay.fill(0.0);
+        }
+        self.haas_seconds = seconds;
+    }
+
+    pub fn correlation_handle(&self) -> Arc<AtomicCell<StereoCorrelation>> {
+        Arc::clone(&self.correlation)
+    }
+
+    pub fn correlation(&self) -> StereoCorrelation {
+        self.correlation.load()
+    }
+
+    fn prepare(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        // ~300 ms integration, like a hardware correlation meter
+        self.average_coeff = OnePoleState::coefficient(1.0 / (2.0 * std::f32::consts::PI * 0.3), sample_rate);
+    }
+}
+
+impl Effect for StereoImager {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if buffer.len() < 2 {
+            return;
+        }
+        if self.sample_rate != sample_rate {
+            self.prepare(sample_rate);
+        }
+        self.splitter.process(buffer, frames, sample_rate);
+
+        let [left, right, ..] = buffer else { return };
+        let delay = ((self.haas_seconds.abs() * sample_rate) as usize).min(self.delay.len() - 1);
+        let len = self.delay.len();
+        let delay_right = self.haas_seconds > 0.0;
+
+        for (l, r) in left[..frames].iter_mut().zip(right[..frames].iter_mut()) {
+            if delay > 0 {
+                let delayed = if delay_right { &mut *r } else { &mut *l };
+                self.delay[self.write_pos] = *delayed;
+                *delayed = self.delay[(self.write_pos + len - delay) % len];
+                self.write_pos = (self.write_pos + 1) % len;
+            }
+
+            let mid = (*l + *r) * 0.5;
+            let a = self.average_coeff;
+            let [lr, ll, rr, mm] = &mut self.averages;
+            lr.low_pass(a, *l * *r);
+            ll.low_pass(a, *l * *l);
+            rr.low_pass(a, *r * *r);
+            mm.low_pass(a, mid * mid);
+        }
+
+        let [lr, ll, rr, mm] = self.averages.map(|avg| avg.value());
+        let power = ll * rr;
+        let reading = if power > 1e-12 {
+            let stereo = (ll + rr) * 0.5;
+            StereoCorrelation {
+                correlation: (lr / power.sqrt()).clamp(-1.0, 1.0),
+                mono_loss_db: 10.0 * (mm.max(1e-12) / stereo).log10(),
+            }
+        } else {
+            StereoCorrelation::default()
+        };
+        self.correlation.store(reading);
+    }
+
+    fn reset(&mut self) {
+        self.splitter.reset();
+        self.delay.fill(0.0);
+        self.write_pos = 0;
+        self.averages.iter_mut().for_each(OnePoleState::reset);
+        self.correlation.store(StereoCorrelation::default());
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        memory.touch(&mut self.delay);
+        self.splitter.prefault(memory);
+    }
+}
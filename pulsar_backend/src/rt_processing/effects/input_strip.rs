@@ -0,0 +1,342 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crossbeam::atomic::AtomicCell;
+
+use crate::rt_processing::filters::{EnvelopeFollower, Trim, TrimFilter};
+
+use super::Effect;
+
+/// Largest trim, boost or cut, an input strip applies
+pub const MAX_TRIM_DB: f32 = 24.0;
+/// Sample level counted as a clip during calibration (about -0.01 dBFS)
+pub const CLIP_LEVEL: f32 = 0.999;
+/// Peaks below this are treated as no signal
+const SILENCE_DB: f32 = -90.0;
+
+/// Noise gate settings
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NoiseGateParams {
+    /// Level below which the gate closes, in dBFS
+    pub threshold_db: f32,
+    /// Opening time in seconds
+    pub attack: f32,
+    /// Closing time in seconds
+    pub release: f32,
+    /// Attenuation while closed, in dB (e.g. -80.0 for practically silent)
+    pub range_db: f32,
+}
+
+impl Default for NoiseGateParams {
+    fn default() -> Self {
+        Self { threshold_db: -50.0, attack: 0.001, release: 0.1, range_db: -80.0 }
+    }
+}
+
+/// Settings of one input channel's strip, in processing order
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InputStripParams {
+    /// Digital trim in dB
+    pub trim_db: f32,
+    /// Flip the polarity
+    pub invert: bool,
+    /// Rumble/handling-noise filter; `None` = off
+    pub high_pass: Option<Trim>,
+    /// `None` = no gate
+    pub gate: Option<NoiseGateParams>,
+}
+
+impl Default for InputStripParams {
+    /// Unity gain, everything off
+    fn default() -> Self {
+        Self { trim_db: 0.0, invert: false, high_pass: None, gate: None }
+    }
+}
+
+/// Detector envelope times of the gate; the audible opening/closing is
+/// shaped by the gate's own attack and release
+const GATE_DETECTOR_ATTACK: f32 = 0.0005;
+const GATE_DETECTOR_RELEASE: f32 = 0.02;
+
+struct ChannelStrip {
+    params: InputStripParams,
+    gain: f32,
+    high_pass: TrimFilter,
+    detector: EnvelopeFollower,
+    threshold: f32,
+    floor: f32,
+    open_coeff: f32,
+    close_coeff: f32,
+    /// Current gate gain, 0..1 between `floor` and unity
+    gate_gain: f32,
+}
+
+impl ChannelStrip {
+    fn new() -> Self {
+        Self {
+            params: InputStripParams::default(),
+            gain: 1.0,
+            high_pass: TrimFilter::high_pass(1),
+            detector: EnvelopeFollower::new(GATE_DETECTOR_ATTACK, GATE_DETECTOR_RELEASE, 48000.0),
+            threshold: 0.0,
+            floor: 0.0,
+            open_coeff: 1.0,
+            close_coeff: 1.0,
+            gate_gain: 1.0,
+        }
+    }
+
+    fn set(&mut self, params: InputStripParams) {
+        self.params = params;
+        let polarity = if params.invert { -1.0 } else { 1.0 };
+        self.gain = 10f32.powf(params.trim_db.clamp(-MAX_TRIM_DB, MAX_TRIM_DB) / 20.0) * polarity;
+        self.high_pass.set(params.high_pass);
+        if let Some(gate) = params.gate {
+            self.threshold = 10f32.powf(gate.threshold_db / 20.0);
+            self.floor = 10f32.powf(gate.range_db.min(0.0) / 20.0);
+        }
+    }
+
+    fn prepare(&mut self, sample_rate: f32) {
+        self.detector.set_times(GATE_DETECTOR_ATTACK, GATE_DETECTOR_RELEASE, sample_rate);
+        if let Some(gate) = self.params.gate {
+            self.open_coeff = EnvelopeFollower::time_coefficient(gate.attack, sample_rate);
+            self.close_coeff = EnvelopeFollower::time_coefficient(gate.release, sample_rate);
+        }
+    }
+
+    fn process(&mut self, samples: &mut [f32], sample_rate: f32) {
+        let gain = self.gain;
+        samples.iter_mut().for_each(|s| *s *= gain);
+        let frames = samples.len();
+        self.high_pass.process(std::slice::from_mut(&mut &mut *samples), frames, sample_rate);
+
+        if self.params.gate.is_none() {
+            return;
+        }
+        for s in samples.iter_mut() {
+            let open = self.detector.process(*s) >= self.threshold;
+            let (target, coeff) = if open { (1.0, self.open_coeff) } else { (0.0, self.close_coeff) };
+            self.gate_gain += coeff * (target - self.gate_gain);
+            *s *= self.floor + (1.0 - self.floor) * self.gate_gain;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.high_pass.reset();
+        self.detector.reset();
+        self.gate_gain = 1.0;
+    }
+}
+
+/// Per-channel input conditioning applied right after capture: trim, polarity,
+/// high-pass and an optional noise gate, each channel with its own settings
+/// (a mic on 1, a DI on 2...). Filter and gate state is allocated up front.
+pub struct InputStrip {
+    channels: Vec<ChannelStrip>,
+    sample_rate: f32,
+}
+
+impl InputStrip {
+    pub fn new(channels: usize) -> Self {
+        Self { channels: (0..channels).map(|_| ChannelStrip::new()).collect(), sample_rate: 0.0 }
+    }
+
+    /// Same settings on every channel
+    pub fn with_params(mut self, params: InputStripParams) -> Self {
+        for channel in 0..self.channels.len() {
+            self.set_channel(channel, params);
+        }
+        self
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn channel(&self, channel: usize) -> Option<&InputStripParams> {
+        self.channels.get(channel).map(|strip| &strip.params)
+    }
+
+    /// Configure one channel; out-of-range channels are ignored
+    pub fn set_channel(&mut self, channel: usize, params: InputStripParams) {
+        if let Some(strip) = self.channels.get_mut(channel) {
+            strip.set(params);
+            // refresh the gate's time constants on the next block
+            self.sample_rate = 0.0;
+        }
+    }
+}
+
+impl Effect for InputStrip {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            self.channels.iter_mut().for_each(|strip| strip.prepare(sample_rate));
+        }
+        for (strip, samples) in self.channels.iter_mut().zip(buffer.iter_mut()) {
+            strip.process(&mut samples[..frames], sample_rate);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.channels.iter_mut().for_each(ChannelStrip::reset);
+    }
+}
+
+/// What a calibration pass measured on one channel
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChannelCalibration {
+    /// Highest peak seen, in dBFS
+    pub peak_db: f32,
+    /// Trim that puts that peak at the target, within ±`MAX_TRIM_DB`; `None`
+    /// when the channel stayed silent
+    pub suggested_trim_db: Option<f32>,
+    /// Samples at or above `CLIP_LEVEL`
+    pub clipped_samples: usize,
+}
+
+impl ChannelCalibration {
+    pub fn clipped(&self) -> bool {
+        self.clipped_samples > 0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CalibrationReport {
+    pub channels: Vec<ChannelCalibration>,
+    /// Seconds of audio watched so far
+    pub elapsed: f32,
+    pub complete: bool,
+}
+
+struct CalibrationShared {
+    peaks: Vec<AtomicCell<f32>>,
+    clips: Vec<AtomicUsize>,
+    frames: AtomicUsize,
+    sample_rate: AtomicCell<f32>,
+    complete: AtomicBool,
+}
+
+/// Input auto-gain calibration.
+///
+/// `new` returns the calibration and its `CalibrationProbe`, a pass-through
+/// effect to insert on the raw capture (ahead of the `InputStrip`). The probe
+/// watches peaks and clipping for the given duration, then stops; meanwhile
+/// `report` gives the running result, and `apply` writes the suggested trims
+/// into a strip.
+pub struct InputCalibration {
+    shared: Arc<CalibrationShared>,
+    seconds: f32,
+    target_db: f32,
+}
+
+impl InputCalibration {
+    /// Watch `channels` for `seconds`, aiming peaks at `target_db` (e.g. -12.0)
+    pub fn new(channels: usize, seconds: f32, target_db: f32) -> (Self, CalibrationProbe) {
+        let shared = Arc::new(CalibrationShared {
+            peaks: (0..channels).map(|_| AtomicCell::new(0.0)).collect(),
+            clips: (0..channels).map(|_| AtomicUsize::new(0)).collect(),
+            frames: AtomicUsize::new(0),
+            sample_rate: AtomicCell::new(0.0),
+            complete: AtomicBool::new(false),
+        });
+        let probe = CalibrationProbe {
+            shared: Arc::clone(&shared),
+            seconds: seconds.max(0.0),
+            peaks: vec![0.0; channels],
+            clips: vec![0; channels],
+        };
+        (Self { shared, seconds: seconds.max(0.0), target_db }, probe)
+    }
+
+    pub fn target_db(&self) -> f32 {
+        self.target_db
+    }
+
+    /// 0..1 of the watch time done
+    pub fn progress(&self) -> f32 {
+        if self.is_complete() || self.seconds == 0.0 {
+            return 1.0;
+        }
+        (self.elapsed() / self.seconds).min(1.0)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.shared.complete.load(Ordering::Acquire)
+    }
+
+    fn elapsed(&self) -> f32 {
+        let sample_rate = self.shared.sample_rate.load();
+        if sample_rate > 0.0 { self.shared.frames.load(Ordering::Relaxed) as f32 / sample_rate } else { 0.0 }
+    }
+
+    /// Result so far; final once `is_complete`
+    pub fn report(&self) -> CalibrationReport {
+        let channels = self
+            .shared
+            .peaks
+            .iter()
+            .zip(&self.shared.clips)
+            .map(|(peak, clips)| {
+                let peak_db = 20.0 * peak.load().max(1e-9).log10();
+                ChannelCalibration {
+                    peak_db,
+                    suggested_trim_db: (peak_db > SILENCE_DB)
+                        .then(|| (self.target_db - peak_db).clamp(-MAX_TRIM_DB, MAX_TRIM_DB)),
+                    clipped_samples: clips.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        CalibrationReport { channels, elapsed: self.elapsed(), complete: self.is_complete() }
+    }
+
+    /// Set each channel's trim in `strip` to its suggestion; silent channels
+    /// keep their trim. Returns the channels changed.
+    pub fn apply(&self, strip: &mut InputStrip) -> usize {
+        let mut changed = 0;
+        for (channel, result) in self.report().channels.iter().enumerate() {
+            let (Some(trim_db), Some(&params)) = (result.suggested_trim_db, strip.channel(channel)) else { continue };
+            strip.set_channel(channel, InputStripParams { trim_db, ..params });
+            changed += 1;
+        }
+        changed
+    }
+}
+
+/// Audio-thread side of an `InputCalibration`; passes audio through untouched
+pub struct CalibrationProbe {
+    shared: Arc<CalibrationShared>,
+    seconds: f32,
+    peaks: Vec<f32>,
+    clips: Vec<usize>,
+}
+
+impl Effect for CalibrationProbe {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        let shared = &self.shared;
+        if shared.complete.load(Ordering::Relaxed) {
+            return;
+        }
+        shared.sample_rate.store(sample_rate);
+        let total = (self.seconds * sample_rate) as usize;
+        let watched = shared.frames.load(Ordering::Relaxed);
+        let frames = frames.min(total.saturating_sub(watched));
+
+        for (channel, samples) in buffer.iter().enumerate().take(self.peaks.len()) {
+            for &s in &samples[..frames] {
+                let level = s.abs();
+                self.peaks[channel] = self.peaks[channel].max(level);
+                if level >= CLIP_LEVEL {
+                    self.clips[channel] += 1;
+                }
+            }
+            shared.peaks[channel].store(self.peaks[channel]);
+            shared.clips[channel].store(self.clips[channel], Ordering::Relaxed);
+        }
+        shared.frames.store(watched + frames, Ordering::Relaxed);
+        if watched + frames >= total {
+            shared.complete.store(true, Ordering::Release);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use crossbeam::atomic::AtomicCell;
+
+use crate::rt_processing::prefault::Prefault;
+
+use super::Effect;
+
+/// Longest lookahead, in seconds
+pub const MAX_LOOKAHEAD: f32 = 0.02;
+const MAX_SAMPLE_RATE: f32 = 192_000.0;
+
+/// True-peak oversampling factor
+const OVERSAMPLING: usize = 4;
+/// Interpolator taps per oversampled phase; the estimate lags the input by half
+const PHASE_TAPS: usize = 8;
+const TRUE_PEAK_DELAY: usize = PHASE_TAPS / 2;
+
+/// Limiter parameters
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LimiterParams {
+    /// Highest output level, in dBFS
+    pub ceiling_db: f32,
+    /// How far ahead peaks are seen, in seconds (up to `MAX_LOOKAHEAD`); the
+    /// gain ramps down over this time
+    pub lookahead: f32,
+    /// Time for the gain to recover, in seconds
+    pub release: f32,
+    /// Also catch peaks between samples, estimated at 4x oversampling
+    pub true_peak: bool,
+}
+
+impl Default for LimiterParams {
+    fn default() -> Self {
+        Self { ceiling_db: -1.0, lookahead: 0.005, release: 0.1, true_peak: true }
+    }
+}
+
+/// Gain each frame needs, with its position, in the sliding minimum
+#[derive(Copy, Clone, Debug, Default)]
+struct Needed {
+    frame: u64,
+    gain: f32,
+}
+
+/// Lookahead brickwall limiter with stereo-linked detection.
+///
+/// The audio is delayed by the lookahead so the gain is already down when a
+/// peak comes out: the gain each peak needs is held for the lookahead, released
+/// exponentially, and averaged over the lookahead, which ramps it down without
+/// overshoot. With `true_peak`, a 4x interpolator estimates the peaks between
+/// samples too, which adds `PHASE_TAPS / 2` samples of latency.
+///
+/// Meant for the end of the master chain (the bus 0 chain of a `Router`).
+/// The gain reduction (positive dB) is published for metering.
+pub struct Limiter {
+    params: LimiterParams,
+    sample_rate: f32,
+    ceiling: f32,
+    release: f32,
+    /// Frames of lookahead, at least 1
+    lookahead: usize,
+    /// Samples of `Needed` window: lookahead plus the true-peak delay, plus one
+    window: usize,
+    /// Delay of the audio: lookahead plus the true-peak delay
+    delay: usize,
+    /// Interpolator coefficients by phase, newest input sample first
+    interpolator: [[f32; PHASE_TAPS]; OVERSAMPLING],
+    /// Per channel: the last inputs for the interpolator, newest first
+    history: Vec<[f32; PHASE_TAPS]>,
+    /// Per channel delay lines
+    lines: Vec<Vec<f32>>,
+    write: usize,
+    /// Monotonic queue of needed gains (rising from the front) over `window`
+    minimum: Vec<Needed>,
+    minimum_head: usize,
+    minimum_len: usize,
+    /// Released gain of the last `lookahead` frames, and their sum
+    released: Vec<f32>,
+    released_sum: f64,
+    released_pos: usize,
+    envelope: f32,
+    frame: u64,
+    gain_reduction: Arc<AtomicCell<f32>>,
+}
+
+impl Limiter {
+    /// Buffers for any lookahead up to `MAX_LOOKAHEAD` at up to 192 kHz are
+    /// allocated here. `sample_rate` sets the latency reported before the first
+    /// block.
+    pub fn new(channels: usize, sample_rate: f32, params: LimiterParams) -> Self {
+        let max_window = (MAX_LOOKAHEAD * MAX_SAMPLE_RATE) as usize + TRUE_PEAK_DELAY + 2;
+        let mut limiter = Self {
+            params: Self::sanitize(params),
+            sample_rate: 0.0,
+            ceiling: 1.0,
+            release: 0.0,
+            lookahead: 1,
+            window: 2,
+            delay: 1,
+            interpolator: Self::interpolator(),
+            history: vec![[0.0; PHASE_TAPS]; channels],
+            lines: vec![vec![0.0; max_window]; channels],
+            write: 0,
+            minimum: vec![Needed::default(); max_window],
+            minimum_head: 0,
+            minimum_len: 0,
+            released: vec![1.0; max_window],
+            released_sum: 0.0,
+            released_pos: 0,
+            envelope: 1.0,
+            frame: 0,
+            gain_reduction: Arc::new(AtomicCell::new(0.0)),
+        };
+        limiter.prepare(sample_rate);
+        limiter.reset();
+        limiter
+    }
+
+    pub fn params(&self) -> &LimiterParams {
+        &self.params
+    }
+
+    /// A new lookahead or true-peak setting changes the latency and restarts
+    /// the delay line
+    pub fn set_params(&mut self, params: LimiterParams) {
+        self.params = Self::sanitize(params);
+        self.sample_rate = 0.0;
+    }
+
+    /// Processing latency in samples
+    pub fn latency(&self) -> usize {
+        self.delay
+    }
+
+    /// Shared handle to the gain reduction in dB (0.0 = none)
+    pub fn gain_reduction_handle(&self) -> Arc<AtomicCell<f32>> {
+        Arc::clone(&self.gain_reduction)
+    }
+
+    pub fn gain_reduction(&self) -> f32 {
+        self.gain_reduction.load()
+    }
+
+    fn sanitize(params: LimiterParams) -> LimiterParams {
+        LimiterParams {
+            ceiling_db: params.ceiling_db.min(0.0),
+            lookahead: params.lookahead.clamp(0.0, MAX_LOOKAHEAD),
+            release: params.release.max(0.0),
+            ..params
+        }
+    }
+
+    /// Windowed-sinc 4x interpolator; phase 0 gives the input back,
+    /// `TRUE_PEAK_DELAY` samples late
+    fn interpolator() -> [[f32; PHASE_TAPS]; OVERSAMPLING] {
+        let centre = (TRUE_PEAK_DELAY * OVERSAMPLING) as f32;
+        let mut phases = [[0.0; PHASE_TAPS]; OVERSAMPLING];
+        for (phase, taps) in phases.iter_mut().enumerate() {
+            for (j, tap) in taps.iter_mut().enumerate() {
+                let x = ((j * OVERSAMPLING + phase) as f32 - centre) / OVERSAMPLING as f32;
+                let sinc = if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+                let window = 0.5 + 0.5 * (PI * x / (TRUE_PEAK_DELAY as f32 + 1.0)).cos();
+                *tap = sinc * window;
+            }
+            let sum: f32 = taps.iter().sum();
+            taps.iter_mut().for_each(|tap| *tap /= sum);
+        }
+        phases
+    }
+
+    fn prepare(&mut self, sample_rate: f32) {
+        let max_lookahead = self.released.len() - TRUE_PEAK_DELAY - 2;
+        let lookahead = ((self.params.lookahead * sample_rate).round() as usize).clamp(1, max_lookahead);
+        let delay = lookahead + if self.params.true_peak { TRUE_PEAK_DELAY } else { 0 };
+        self.sample_rate = sample_rate;
+        self.ceiling = 10f32.powf(self.params.ceiling_db / 20.0);
+        self.release =
+            if self.params.release > 0.0 { (-1.0 / (self.params.release * sample_rate)).exp() } else { 0.0 };
+        if (lookahead, delay) != (self.lookahead, self.delay) {
+            self.lookahead = lookahead;
+            self.delay = delay;
+            self.window = delay + 1;
+            self.reset();
+        }
+    }
+
+    /// Highest level of the current frame across channels, true peak or sample
+    #[inline]
+    fn detect(&mut self, buffer: &[&mut [f32]], i: usize) -> f32 {
+        let mut peak = 0.0f32;
+        for (history, samples) in self.history.iter_mut().zip(buffer.iter()) {
+            let x = samples[i];
+            peak = peak.max(x.abs());
+            if self.params.true_peak {
+                history.copy_within(..PHASE_TAPS - 1, 1);
+                history[0] = x;
+                for taps in &self.interpolator[1..] {
+                    let y: f32 = taps.iter().zip(history.iter()).map(|(t, h)| t * h).sum();
+                    peak = peak.max(y.abs());
+                }
+            }
+        }
+        peak
+    }
+
+    /// Gain for the frame leaving the delay line, after `needed` joined the window
+    #[inline]
+    fn next_gain(&mut self, needed: f32) -> f32 {
+        let capacity = self.minimum.len();
+        // drop gains at least as low as the new one's from the back, expired ones from the front
+        while self.minimum_len > 0 {
+            let back = (self.minimum_head + self.minimum_len - 1) % capacity;
+            if self.minimum[back].gain < needed {
+                break;
+            }
+            self.minimum_len -= 1;
+        }
+        self.minimum[(self.minimum_head + self.minimum_len) % capacity] = Needed { frame: self.frame, gain: needed };
+        self.minimum_len += 1;
+        if self.frame - self.minimum[self.minimum_head].frame >= self.window as u64 {
+            self.minimum_head = (self.minimum_head + 1) % capacity;
+            self.minimum_len -= 1;
+        }
+        let held = self.minimum[self.minimum_head].gain;
+        self.frame += 1;
+
+        self.envelope = if held < self.envelope { held } else { held + (self.envelope - held) * self.release };
+
+        self.released_sum += (self.envelope - self.released[self.released_pos]) as f64;
+        self.released[self.released_pos] = self.envelope;
+        self.released_pos = (self.released_pos + 1) % self.lookahead;
+        (self.released_sum / self.lookahead as f64) as f32
+    }
+}
+
+impl Effect for Limiter {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if self.sample_rate != sample_rate {
+            self.prepare(sample_rate);
+        }
+        let channels = buffer.len().min(self.lines.len());
+        let line_len = self.lines.first().map_or(1, Vec::len);
+        let mut lowest = 1.0f32;
+        for i in 0..frames {
+            let peak = self.detect(&buffer[..channels], i);
+            let needed = if peak > self.ceiling { self.ceiling / peak } else { 1.0 };
+            // the ramp averages gains that are all at or under each peak's
+            let gain = self.next_gain(needed).min(1.0);
+            lowest = lowest.min(gain);
+
+            let read = (self.write + line_len - self.delay) % line_len;
+            for (line, samples) in self.lines.iter_mut().zip(buffer.iter_mut()) {
+                line[self.write] = samples[i];
+                samples[i] = line[read] * gain;
+            }
+            self.write = (self.write + 1) % line_len;
+        }
+        self.gain_reduction.store(-20.0 * lowest.max(1e-6).log10());
+    }
+
+    fn reset(&mut self) {
+        for history in &mut self.history {
+            *history = [0.0; PHASE_TAPS];
+        }
+        for line in &mut self.lines {
+            line.fill(0.0);
+        }
+        self.write = 0;
+        self.minimum_head = 0;
+        self.minimum_len = 0;
+        self.released.fill(1.0);
+        self.released_sum = self.lookahead as f64;
+        self.released_pos = 0;
+        self.envelope = 1.0;
+        self.frame = 0;
+        self.gain_reduction.store(0.0);
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        for line in &mut self.lines {
+            memory.touch(line);
+        }
+        memory.touch(&mut self.minimum);
+        memory.touch(&mut self.released);
+    }
+
+    fn latency(&self) -> usize {
+        Limiter::latency(self)
+    }
+}
@@ -0,0 +1,70 @@
+pub mod amp_sim;
+pub mod auto_pan;
+pub mod chain;
+pub mod character;
+pub mod compressor;
+pub mod convolution;
+pub mod de_esser;
+pub mod gate;
+pub mod harmonizer;
+pub mod imager;
+pub mod input_strip;
+pub mod limiter;
+pub mod multiband;
+pub mod pitch_shift;
+pub mod reverb;
+pub mod spectral;
+pub mod tremolo;
+pub mod vocoder;
+
+use crate::rt_processing::modulation::ModulationMonitor;
+use crate::rt_processing::prefault::Prefault;
+
+/// Trait for in-place audio effects.
+/// Non-interleaved, [channel][frame]
+///
+/// `process` runs on the audio thread: implementations must not block and should
+/// allocate everything they need up front.
+pub trait Effect: Send + Sync {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32);
+
+    /// Clear internal state (delay lines, envelopes, filter memory)
+    fn reset(&mut self) {}
+
+    /// Readings of the parameters this effect modulates internally, if any
+    fn modulation(&self) -> Option<ModulationMonitor> {
+        None
+    }
+
+    /// Touch the buffers used while processing (see `rt_processing::prefault`).
+    /// Runs off the audio thread before playback; may allocate.
+    fn prefault(&mut self, _memory: &mut Prefault) {}
+
+    /// Delay the effect adds to its input, in samples (block-based effects
+    /// like convolution and spectral processing)
+    fn latency(&self) -> usize {
+        0
+    }
+}
+
+/// Tempo assumed by tempo-synced effects until one is set
+pub const DEFAULT_TEMPO_BPM: f32 = 120.0;
+
+/// Modulation rate: free-running or locked to the tempo
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LfoRate {
+    /// Free-running rate in Hz
+    Hz(f32),
+    /// One cycle every `beats` quarter notes (0.25 = 1/16, 4.0 = one 4/4 bar)
+    Beats(f32),
+}
+
+impl LfoRate {
+    /// Rate in Hz at `bpm`
+    pub fn frequency(self, bpm: f32) -> f32 {
+        match self {
+            LfoRate::Hz(hz) => hz.max(0.0),
+            LfoRate::Beats(beats) => bpm.max(1.0) / 60.0 / beats.max(1.0 / 64.0),
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::sync::Arc;
+
+use crossbeam::atomic::AtomicCell;
+
+use crate::rt_processing::filters::{BiquadState, CrossoverCoefficients, CrossoverState};
+use crate::rt_processing::prefault::Prefault;
+
+use super::Effect;
+use super::chain::EffectChain;
+use super::compressor::{Compressor, CompressorParams};
+
+/// Most bands a splitter can produce
+pub const MAX_BANDS: usize = 4;
+
+/// Most channels a splitter processes; extra channels are passed through
+pub const MAX_SPLIT_CHANNELS: usize = 8;
+
+const MAX_CROSSOVERS: usize = MAX_BANDS - 1;
+
+/// Filter memory for one channel of the split tree
+#[derive(Copy, Clone, Default)]
+struct ChannelSplitState {
+    crossovers: [CrossoverState; MAX_CROSSOVERS],
+    // [band][crossover]: phase compensation for crossovers above the band
+    all_pass: [[BiquadState; MAX_CROSSOVERS]; MAX_BANDS],
+}
+
+impl ChannelSplitState {
+    #[inline]
+    fn split(&mut self, coeffs: &[CrossoverCoefficients], x: f32, out: &mut [f32; MAX_BANDS]) {
+        let crossovers = coeffs.len();
+        let mut rest = x;
+        for ((c, state), band) in coeffs.iter().zip(&mut self.crossovers).zip(out.iter_mut()) {
+            let (low, high) = state.split(c, rest);
+            *band = low;
+            rest = high;
+        }
+        out[crossovers] = rest;
+
+        // band k was split off before crossovers k+1.. ; run it through their
+        // all-pass sums so every band carries the same phase response
+        for (band, sample) in out.iter_mut().enumerate().take(crossovers) {
+            for (j, c) in coeffs.iter().enumerate().skip(band + 1) {
+                *sample = self.all_pass[band][j].process(&c.all_pass, *sample);
+            }
+        }
+    }
+}
+
+/// Linkwitz-Riley (LR4) band splitter.
+///
+/// Splits a bus into 2-4 bands with a tree of LR4 crossovers, runs each band
+/// through its own `EffectChain`, and sums the bands back. Lower bands are
+/// phase-compensated with the all-pass response of the crossovers above them, so
+/// with empty chains the output is a flat-magnitude all-pass of the input.
+pub struct MultibandSplitter {
+    channels: usize,
+    max_frames: usize,
+    crossovers: [f32; MAX_CROSSOVERS],
+    band_count: usize,
+    sample_rate: f32,
+    coeffs: [CrossoverCoefficients; MAX_CROSSOVERS],
+    states: Vec<ChannelSplitState>,
+    bands: [EffectChain; MAX_BANDS],
+    band_gains: [f32; MAX_BANDS],
+    // [band][channel][frame], flattened
+    band_buffers: Vec<f32>,
+}
+
+impl MultibandSplitter {
+    /// `crossovers` holds 1-3 split frequencies in Hz (2-4 bands)
+    pub fn new(channels: usize, max_frames: usize, crossovers: &[f32]) -> Self {
+        let channels = channels.min(MAX_SPLIT_CHANNELS);
+        let mut splitter = Self {
+            channels,
+            max_frames,
+            crossovers: [0.0; MAX_CROSSOVERS],
+            band_count: 2,
+            sample_rate: 0.0,
+            coeffs: [CrossoverCoefficients::new(1000.0, 48000.0); MAX_CROSSOVERS],
+            states: vec![ChannelSplitState::default(); channels],
+            bands: std::array::from_fn(|_| EffectChain::new()),
+            band_gains: [1.0; MAX_BANDS],
+            band_buffers: vec![0.0; MAX_BANDS * channels * max_frames],
+        };
+        splitter.set_crossovers(crossovers);
+        splitter
+    }
+
+    /// Add an effect to the end of a band's chain (builder form)
+    pub fn with_band_effect(mut self, band: usize, effect: Box<dyn Effect>) -> Self {
+        if let Some(chain) = self.band_mut(band) {
+            chain.push(effect);
+        }
+        self
+    }
+
+    /// Replace the split frequencies. The band count follows the number of
+    /// frequencies (clamped to 2-4 bands); frequencies are sorted ascending.
+    pub fn set_crossovers(&mut self, crossovers: &[f32]) {
+        let count = crossovers.len().clamp(1, MAX_CROSSOVERS);
+        let mut sorted = [0.0; MAX_CROSSOVERS];
+        for (dst, &src) in sorted.iter_mut().zip(crossovers.iter().chain(std::iter::repeat(&1000.0))).take(count) {
+            *dst = src.max(20.0);
+        }
+        sorted[..count].sort_by(f32::total_cmp);
+
+        if count + 1 != self.band_count {
+            self.states.iter_mut().for_each(|s| *s = ChannelSplitState::default());
+        }
+        self.crossovers = sorted;
+        self.band_count = count + 1;
+        self.sample_rate = 0.0;
+    }
+
+    pub fn crossovers(&self) -> &[f32] {
+        &self.crossovers[..self.band_count - 1]
+    }
+
+    pub fn band_count(&self) -> usize {
+        self.band_count
+    }
+
+    /// Effect chain of `band` (0 = lowest)
+    pub fn band_mut(&mut self, band: usize) -> Option<&mut EffectChain> {
+        self.bands[..self.band_count].get_mut(band)
+    }
+
+    /// Linear output gain of a band (0.0 mutes it)
+    pub fn set_band_gain(&mut self, band: usize, gain: f32) {
+        if let Some(g) = self.band_gains.get_mut(band) {
+            *g = gain.max(0.0);
+        }
+    }
+
+    fn prepare(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        for (c, &frequency) in self.coeffs.iter_mut().zip(&self.crossovers).take(self.band_count - 1) {
+            *c = CrossoverCoefficients::new(frequency, sample_rate);
+        }
+    }
+}
+
+impl Effect for MultibandSplitter {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if self.sample_rate != sample_rate {
+            self.prepare(sample_rate);
+        }
+        let frames = frames.min(self.max_frames);
+        let channels = buffer.len().min(self.channels);
+        let stride = self.channels * self.max_frames;
+        let crossovers = self.band_count - 1;
+        let coeffs = &self.coeffs[..crossovers];
+
+        // split
+        for (ch, (samples, state)) in buffer.iter().zip(&mut self.states).take(channels).enumerate() {
+            let offset = ch * self.max_frames;
+            for (i, &x) in samples[..frames].iter().enumerate() {
+                let mut out = [0.0; MAX_BANDS];
+                state.split(coeffs, x, &mut out);
+                for (band, &y) in out.iter().enumerate().take(self.band_count) {
+                    self.band_buffers[band * stride + offset + i] = y;
+                }
+            }
+        }
+
+        // process each band through its chain
+        let band_data = self.band_buffers.chunks_mut(stride);
+        for (chain, data) in self.bands.iter_mut().zip(band_data).take(self.band_count) {
+            let mut views: [&mut [f32]; MAX_SPLIT_CHANNELS] = Default::default();
+            for (view, channel) in views.iter_mut().zip(data.chunks_mut(self.max_frames)) {
+                *view = &mut channel[..frames];
+            }
+            chain.process(&mut views[..channels], frames, sample_rate);
+        }
+
+        // recombine
+        for (ch, samples) in buffer.iter_mut().take(channels).enumerate() {
+            let offset = ch * self.max_frames;
+            for (i, s) in samples[..frames].iter_mut().enumerate() {
+                *s = (0..self.band_count)
+                    .map(|band| self.band_buffers[band * stride + offset + i] * self.band_gains[band])
+                    .sum();
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.states.iter_mut().for_each(|s| *s = ChannelSplitState::default());
+        self.bands.iter_mut().for_each(|b| b.reset());
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        memory.touch(&mut self.band_buffers);
+        self.bands.iter_mut().for_each(|b| b.prefault(memory));
+    }
+}
+
+/// Multiband compressor: an LR4 splitter with one `Compressor` per band
+pub struct MultibandCompressor {
+    splitter: MultibandSplitter,
+    gain_reduction: [Option<Arc<AtomicCell<f32>>>; MAX_BANDS],
+}
+
+impl MultibandCompressor {
+    /// One `CompressorParams` per band; missing entries use the defaults
+    pub fn new(channels: usize, max_frames: usize, crossovers: &[f32], bands: &[CompressorParams]) -> Self {
+        let mut splitter = MultibandSplitter::new(channels, max_frames, crossovers);
+        let mut gain_reduction: [Option<Arc<AtomicCell<f32>>>; MAX_BANDS] = Default::default();
+        for (band, meter) in gain_reduction.iter_mut().enumerate().take(splitter.band_count()) {
+            let params = bands.get(band).copied().unwrap_or_default();
+            let compressor = Compressor::new(params);
+            *meter = Some(compressor.gain_reduction_handle());
+            if let Some(chain) = splitter.band_mut(band) {
+                chain.push(Box::new(compressor));
+            }
+        }
+        Self { splitter, gain_reduction }
+    }
+
+    /// Three-band mastering-style preset (crossovers at 200 Hz and 3 kHz):
+    /// slow and firm lows, gentle mids, fast highs
+    pub fn three_band(channels: usize, max_frames: usize) -> Self {
+        let low = CompressorParams {
+            threshold_db: -20.0,
+            ratio: 3.0,
+            attack: 0.03,
+            release: 0.25,
+            makeup_db: 2.0,
+            ..CompressorParams::default()
+        };
+        let mid = CompressorParams {
+            threshold_db: -18.0,
+            ratio: 2.0,
+            attack: 0.015,
+            release: 0.15,
+            makeup_db: 1.5,
+            ..CompressorParams::default()
+        };
+        let high = CompressorParams {
+            threshold_db: -22.0,
+            ratio: 2.5,
+            attack: 0.005,
+            release: 0.08,
+            makeup_db: 1.5,
+            ..CompressorParams::default()
+        };
+        Self::new(channels, max_frames, &[200.0, 3000.0], &[low, mid, high])
+    }
+
+    /// Gain reduction meter of a band, in dB
+    pub fn gain_reduction_handle(&self, band: usize) -> Option<Arc<AtomicCell<f32>>> {
+        self.gain_reduction.get(band).and_then(Clone::clone)
+    }
+
+    /// Underlying splitter, e.g. to add effects after a band's compressor
+    pub fn splitter_mut(&mut self) -> &mut MultibandSplitter {
+        &mut self.splitter
+    }
+}
+
+impl Effect for MultibandCompressor {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        self.splitter.process(buffer, frames, sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.splitter.reset();
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        self.splitter.prefault(memory);
+    }
+}
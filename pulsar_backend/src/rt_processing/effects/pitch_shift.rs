@@ -0,0 +1,104 @@
+use std::f32::consts::PI;
+
+use crate::rt_processing::prefault::Prefault;
+
+/// Longest supported grain window in seconds
+pub const MAX_PITCH_SHIFT_WINDOW: f32 = 0.1;
+
+/// Highest sample rate the delay line is sized for
+const MAX_SAMPLE_RATE: f32 = 192_000.0;
+
+/// Guard distance (in samples) between the write head and the closest read
+const MIN_DELAY: f32 = 2.0;
+
+/// Single-channel delay-line pitch shifter.
+///
+/// Two read taps sweep across a grain window at a rate set by the pitch ratio,
+/// half a window apart, and are crossfaded with complementary sin² windows so the
+/// jump when a tap wraps is never heard. Latency is at most one window. This is
+/// the pitch-shift half of the engine; the ratio can change every sample without
+/// discontinuities, which is what the harmonizer relies on for glides.
+pub struct PitchShifter {
+    delay: Vec<f32>,
+    write_pos: usize,
+    window_seconds: f32,
+    sample_rate: f32,
+    window: f32,
+    phase: f32,
+}
+
+impl PitchShifter {
+    /// `window_seconds` is clamped to 5 ms ..= `MAX_PITCH_SHIFT_WINDOW`. Longer
+    /// windows smear transients less audibly on sustained material, shorter ones
+    /// track percussive input better.
+    pub fn new(window_seconds: f32) -> Self {
+        let len = (MAX_PITCH_SHIFT_WINDOW * MAX_SAMPLE_RATE) as usize + MIN_DELAY as usize + 2;
+        Self {
+            delay: vec![0.0; len],
+            write_pos: 0,
+            window_seconds: window_seconds.clamp(0.005, MAX_PITCH_SHIFT_WINDOW),
+            sample_rate: 0.0,
+            window: 0.0,
+            phase: 0.0,
+        }
+    }
+
+    pub fn window_seconds(&self) -> f32 {
+        self.window_seconds
+    }
+
+    pub fn set_window_seconds(&mut self, window_seconds: f32) {
+        self.window_seconds = window_seconds.clamp(0.005, MAX_PITCH_SHIFT_WINDOW);
+        self.sample_rate = 0.0;
+    }
+
+    /// Update the window length in samples for `sample_rate`
+    #[inline]
+    pub fn prepare(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        let max = (self.delay.len() - MIN_DELAY as usize - 2) as f32;
+        self.window = (self.window_seconds * sample_rate).clamp(16.0, max);
+    }
+
+    /// Process one sample shifted by `ratio` (2.0 = one octave up)
+    #[inline]
+    pub fn process(&mut self, x: f32, ratio: f32, sample_rate: f32) -> f32 {
+        if self.sample_rate != sample_rate {
+            self.prepare(sample_rate);
+        }
+        let len = self.delay.len();
+        self.delay[self.write_pos] = x;
+
+        // phase runs backwards when shifting up: the taps close in on the write head
+        self.phase = (self.phase + (1.0 - ratio) / self.window).rem_euclid(1.0);
+        let other = (self.phase + 0.5).fract();
+
+        let y = self.tap(self.phase) + self.tap(other);
+        self.write_pos = (self.write_pos + 1) % len;
+        y
+    }
+
+    #[inline(always)]
+    fn tap(&self, phase: f32) -> f32 {
+        let len = self.delay.len();
+        let delay = MIN_DELAY + phase * self.window;
+        let read = self.write_pos as f32 + len as f32 - delay;
+        let index = read as usize;
+        let frac = read - index as f32;
+        let a = self.delay[index % len];
+        let b = self.delay[(index + 1) % len];
+        let gain = (PI * phase).sin();
+        (a + (b - a) * frac) * gain * gain
+    }
+
+    pub fn reset(&mut self) {
+        self.delay.fill(0.0);
+        self.write_pos = 0;
+        self.phase = 0.0;
+    }
+
+    /// Touch the delay line (see `rt_processing::prefault`)
+    pub fn prefault(&mut self, memory: &mut Prefault) {
+        memory.touch(&mut self.delay);
+    }
+}
@@ -0,0 +1,228 @@
+use crate::rt_processing::filters::OnePoleState;
+use crate::rt_processing::prefault::Prefault;
+
+use super::Effect;
+
+/// Longest pre-delay in seconds
+pub const MAX_PRE_DELAY: f32 = 0.5;
+const MAX_SAMPLE_RATE: f32 = 192_000.0;
+/// Delay lines in the network
+const LINES: usize = 8;
+/// Line lengths at size 1.0, in seconds; mutually prime at common rates so
+/// the echoes don't pile up on one another
+const LINE_SECONDS: [f32; LINES] = [0.0297, 0.0371, 0.0411, 0.0437, 0.0479, 0.0533, 0.0599, 0.0671];
+/// Line lengths scale from this (size 0.0) to 1.0 (size 1.0)
+const MIN_SIZE_SCALE: f32 = 0.25;
+/// Damping cutoff at damping 0.0; each step of 0.2 takes an octave off
+const DAMPING_MAX_HZ: f32 = 20_000.0;
+
+/// Reverb parameters
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ReverbParams {
+    /// Room size, 0.0 to 1.0: scales the delay lines, and so the echo density
+    pub size: f32,
+    /// Time for the tail to fall by 60 dB, in seconds
+    pub decay: f32,
+    /// High-frequency absorption, 0.0 (bright) to 1.0 (dark)
+    pub damping: f32,
+    /// Delay before the reverb starts, in seconds (up to `MAX_PRE_DELAY`)
+    pub pre_delay: f32,
+    /// Width of the tail: 0.0 = mono, 1.0 = natural, up to 2.0 = extra wide
+    pub width: f32,
+    /// 0.0 = dry only, 1.0 = reverb only (for an aux bus fed by sends)
+    pub mix: f32,
+}
+
+impl Default for ReverbParams {
+    fn default() -> Self {
+        Self { size: 0.5, decay: 1.5, damping: 0.4, pre_delay: 0.01, width: 1.0, mix: 1.0 }
+    }
+}
+
+/// Feedback-delay-network reverb on the first two channels (a mono buffer is
+/// reverberated in mono).
+///
+/// Eight delay lines feed back into one another through a Hadamard matrix,
+/// each with a one-pole low-pass for damping and a gain set so the tail
+/// decays by 60 dB in `decay` seconds at any size. The left input drives
+/// half the lines and the right the other half, and the outputs are tapped
+/// the same way, so the tail keeps the input's stereo image.
+///
+/// With the default mix of 1.0 the output is reverb only, as an aux bus that
+/// several sources send to wants; lower the mix to use it as an insert.
+/// Changing the size moves the taps, which can click on a sounding tail.
+pub struct Reverb {
+    params: ReverbParams,
+    sample_rate: f32,
+    lines: [Vec<f32>; LINES],
+    lengths: [usize; LINES],
+    gains: [f32; LINES],
+    damping: [OnePoleState; LINES],
+    damping_coeff: f32,
+    // left and right
+    pre_delay: [Vec<f32>; 2],
+    pre_delay_frames: usize,
+    write_pos: usize,
+    pre_write_pos: usize,
+}
+
+impl Default for Reverb {
+    fn default() -> Self {
+        Self::new(ReverbParams::default())
+    }
+}
+
+impl Reverb {
+    pub fn new(params: ReverbParams) -> Self {
+        let line_len = (LINE_SECONDS[LINES - 1] * MAX_SAMPLE_RATE) as usize + 1;
+        let pre_len = (MAX_PRE_DELAY * MAX_SAMPLE_RATE) as usize + 1;
+        Self {
+            params: Self::sanitize(params),
+            sample_rate: 0.0,
+            lines: std::array::from_fn(|_| vec![0.0; line_len]),
+            lengths: [1; LINES],
+            gains: [0.0; LINES],
+            damping: [OnePoleState::default(); LINES],
+            damping_coeff: 1.0,
+            pre_delay: std::array::from_fn(|_| vec![0.0; pre_len]),
+            pre_delay_frames: 0,
+            write_pos: 0,
+            pre_write_pos: 0,
+        }
+    }
+
+    pub fn params(&self) -> &ReverbParams {
+        &self.params
+    }
+
+    pub fn set_params(&mut self, params: ReverbParams) {
+        self.params = Self::sanitize(params);
+        self.sample_rate = 0.0;
+    }
+
+    fn sanitize(params: ReverbParams) -> ReverbParams {
+        ReverbParams {
+            size: params.size.clamp(0.0, 1.0),
+            decay: params.decay.max(0.01),
+            damping: params.damping.clamp(0.0, 1.0),
+            pre_delay: params.pre_delay.clamp(0.0, MAX_PRE_DELAY),
+            width: params.width.clamp(0.0, 2.0),
+            mix: params.mix.clamp(0.0, 1.0),
+        }
+    }
+
+    fn prepare(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        let scale = MIN_SIZE_SCALE + (1.0 - MIN_SIZE_SCALE) * self.params.size;
+        for (i, seconds) in LINE_SECONDS.iter().enumerate() {
+            let length = ((seconds * scale * sample_rate) as usize).clamp(1, self.lines[i].len() - 1);
+            self.lengths[i] = length;
+            // -60 dB over `decay` seconds, whatever the line's length
+            self.gains[i] = 10f32.powf(-3.0 * length as f32 / (self.params.decay * sample_rate));
+        }
+        let cutoff = DAMPING_MAX_HZ * 2f32.powf(-5.0 * self.params.damping);
+        self.damping_coeff = if self.params.damping > 0.0 {
+            OnePoleState::coefficient(cutoff.min(sample_rate * 0.45), sample_rate)
+        } else {
+            1.0
+        };
+        self.pre_delay_frames = ((self.params.pre_delay * sample_rate) as usize).min(self.pre_delay[0].len() - 1);
+    }
+
+    /// One frame through the network: the reverb of `(left, right)`
+    #[inline]
+    fn tick(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let (left, right) = self.pre_delay(left, right);
+        let len = self.lines[0].len();
+        let mut taps = [0.0; LINES];
+        for (i, tap) in taps.iter_mut().enumerate() {
+            let delayed = self.lines[i][(self.write_pos + len - self.lengths[i]) % len];
+            *tap = self.damping[i].low_pass(self.damping_coeff, delayed) * self.gains[i];
+        }
+        let (wet_left, wet_right) = taps.iter().enumerate().fold((0.0, 0.0), |(l, r), (i, &tap)| {
+            // alternate signs decorrelate the two sides further
+            let sign = if i & 2 == 0 { 1.0 } else { -1.0 };
+            if i < LINES / 2 { (l + sign * tap, r) } else { (l, r + sign * tap) }
+        });
+        hadamard(&mut taps);
+        for (i, tap) in taps.iter().enumerate() {
+            let input = if i < LINES / 2 { left } else { right };
+            self.lines[i][self.write_pos] = tap + input;
+        }
+        self.write_pos = (self.write_pos + 1) % len;
+        (wet_left * 0.5, wet_right * 0.5)
+    }
+
+    #[inline]
+    fn pre_delay(&mut self, left: f32, right: f32) -> (f32, f32) {
+        if self.pre_delay_frames == 0 {
+            return (left, right);
+        }
+        let len = self.pre_delay[0].len();
+        let read = (self.pre_write_pos + len - self.pre_delay_frames) % len;
+        let [pre_left, pre_right] = &mut self.pre_delay;
+        pre_left[self.pre_write_pos] = left;
+        pre_right[self.pre_write_pos] = right;
+        self.pre_write_pos = (self.pre_write_pos + 1) % len;
+        (pre_left[read], pre_right[read])
+    }
+}
+
+/// In-place normalized fast Walsh-Hadamard transform: an orthogonal mix of
+/// every line into every other, so feedback neither grows nor fades by itself
+#[inline]
+fn hadamard(values: &mut [f32; LINES]) {
+    let mut half = 1;
+    while half < LINES {
+        for start in (0..LINES).step_by(half * 2) {
+            for i in start..start + half {
+                let (a, b) = (values[i], values[i + half]);
+                values[i] = a + b;
+                values[i + half] = a - b;
+            }
+        }
+        half *= 2;
+    }
+    let norm = 1.0 / (LINES as f32).sqrt();
+    values.iter_mut().for_each(|v| *v *= norm);
+}
+
+impl Effect for Reverb {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if self.sample_rate != sample_rate {
+            self.prepare(sample_rate);
+        }
+        let ReverbParams { width, mix, .. } = self.params;
+        match buffer {
+            [left, right, ..] => {
+                for (l, r) in left[..frames].iter_mut().zip(right[..frames].iter_mut()) {
+                    let (wet_left, wet_right) = self.tick(*l, *r);
+                    let mid = (wet_left + wet_right) * 0.5;
+                    let side = (wet_left - wet_right) * 0.5 * width;
+                    *l += (mid + side - *l) * mix;
+                    *r += (mid - side - *r) * mix;
+                }
+            }
+            [mono] => {
+                for sample in mono[..frames].iter_mut() {
+                    let (wet_left, wet_right) = self.tick(*sample, *sample);
+                    *sample += ((wet_left + wet_right) * 0.5 - *sample) * mix;
+                }
+            }
+            [] => {}
+        }
+    }
+
+    fn reset(&mut self) {
+        self.lines.iter_mut().for_each(|line| line.fill(0.0));
+        self.pre_delay.iter_mut().for_each(|line| line.fill(0.0));
+        self.damping.iter_mut().for_each(OnePoleState::reset);
+        self.write_pos = 0;
+        self.pre_write_pos = 0;
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        memory.touch_all(&mut self.lines);
+        memory.touch_all(&mut self.pre_delay);
+    }
+}
@@ -0,0 +1,337 @@
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::rt_processing::fft::{Complex, Fft, Window};
+use crate::rt_processing::prefault::Prefault;
+use crate::rt_processing::waveform::noise::FastRng;
+
+use super::Effect;
+
+/// FFT size of `StftConfig::default()`
+pub const DEFAULT_STFT_SIZE: usize = 1024;
+/// Frames overlapping each sample in `StftConfig::default()`
+pub const DEFAULT_STFT_OVERLAP: usize = 4;
+
+/// Frame layout of an `StftEffect`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StftConfig {
+    /// Frame length, rounded up to a power of two
+    pub fft_size: usize,
+    /// Frames covering each sample; the hop is `fft_size / overlap`
+    pub overlap: usize,
+    /// Applied on analysis and again on resynthesis. `Rectangular` only
+    /// suits an overlap of 1.
+    pub window: Window,
+}
+
+impl Default for StftConfig {
+    fn default() -> Self {
+        Self { fft_size: DEFAULT_STFT_SIZE, overlap: DEFAULT_STFT_OVERLAP, window: Window::Hann }
+    }
+}
+
+impl StftConfig {
+    /// Bins in each spectrum a processor gets, DC to Nyquist
+    pub fn bins(&self) -> usize {
+        self.fft_size.max(2).next_power_of_two() / 2 + 1
+    }
+}
+
+/// Where a spectrum handed to a `SpectralProcessor` comes from
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpectralContext {
+    pub channel: usize,
+    pub sample_rate: f32,
+    pub fft_size: usize,
+    pub hop: usize,
+    /// Frames processed before this one, for all channels alike
+    pub frame: u64,
+}
+
+impl SpectralContext {
+    /// Centre frequency of `bin` in Hz
+    pub fn bin_frequency(&self, bin: usize) -> f32 {
+        bin as f32 * self.sample_rate / self.fft_size as f32
+    }
+}
+
+/// What a spectral effect does to each frame.
+///
+/// Closures taking `(&mut [Complex], &SpectralContext)` are processors, and
+/// `PerBin` turns a per-bin closure into one.
+pub trait SpectralProcessor: Send + Sync {
+    /// Change one channel's spectrum in place: `fft_size / 2 + 1` bins, DC to
+    /// Nyquist (the mirrored half is rebuilt from them). Runs on the audio
+    /// thread; must not block or allocate.
+    fn process_spectrum(&mut self, bins: &mut [Complex], context: &SpectralContext);
+
+    /// Forget anything kept between frames
+    fn reset(&mut self) {}
+}
+
+impl<F> SpectralProcessor for F
+where
+    F: FnMut(&mut [Complex], &SpectralContext) + Send + Sync,
+{
+    fn process_spectrum(&mut self, bins: &mut [Complex], context: &SpectralContext) {
+        self(bins, context)
+    }
+}
+
+/// Processor calling `F(bin value, bin index, context)` for every bin and
+/// keeping what it returns
+pub struct PerBin<F>(pub F);
+
+impl<F> SpectralProcessor for PerBin<F>
+where
+    F: FnMut(Complex, usize, &SpectralContext) -> Complex + Send + Sync,
+{
+    fn process_spectrum(&mut self, bins: &mut [Complex], context: &SpectralContext) {
+        for (index, bin) in bins.iter_mut().enumerate() {
+            *bin = (self.0)(*bin, index, context);
+        }
+    }
+}
+
+/// Per-channel frames
+struct StftChannel {
+    /// The last `fft_size` input samples
+    input: Vec<f32>,
+    /// Overlap-added output, from the start of the current frame
+    accumulator: Vec<f32>,
+    /// Finished samples played until the next frame, one hop
+    output: Vec<f32>,
+}
+
+/// Short-time Fourier transform effect: every `hop` samples the last
+/// `fft_size` samples of each channel are windowed and transformed, the
+/// `SpectralProcessor` changes the spectrum, and the inverse is windowed again
+/// and overlap-added into the output.
+///
+/// The output is normalized by the summed squared windows, so a processor
+/// that leaves the bins alone reproduces the input exactly, `latency()`
+/// samples late. Buffers are allocated in `new`; channels past the ones it
+/// was built for pass through undelayed.
+pub struct StftEffect<P> {
+    processor: P,
+    fft: Fft,
+    hop: usize,
+    window: Vec<f32>,
+    /// Gain per position in a hop, undoing the summed squared windows
+    normalize: Vec<f32>,
+    channels: Vec<StftChannel>,
+    spectrum: Vec<Complex>,
+    /// Where the next input sample goes in `input`
+    fill: usize,
+    /// Next sample to play from `output`
+    read: usize,
+    frames: u64,
+}
+
+impl<P: SpectralProcessor> StftEffect<P> {
+    pub fn new(channels: usize, config: StftConfig, processor: P) -> Self {
+        let fft = Fft::new(config.fft_size);
+        let size = fft.size();
+        let hop = (size / config.overlap.clamp(1, size)).max(1);
+        let window = config.window.coefficients(size);
+        let normalize = (0..hop)
+            .map(|m| {
+                let sum: f32 = window[m..].iter().step_by(hop).map(|w| w * w).sum();
+                if sum > 1e-6 { 1.0 / sum } else { 0.0 }
+            })
+            .collect();
+        let channels = (0..channels)
+            .map(|_| StftChannel { input: vec![0.0; size], accumulator: vec![0.0; size], output: vec![0.0; hop] })
+            .collect();
+        Self {
+            processor,
+            spectrum: vec![Complex::ZERO; size],
+            fft,
+            hop,
+            window,
+            normalize,
+            channels,
+            fill: size - hop,
+            read: 0,
+            frames: 0,
+        }
+    }
+
+    pub fn fft_size(&self) -> usize {
+        self.fft.size()
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// Processing latency in samples
+    pub fn latency(&self) -> usize {
+        self.fft.size() - 1
+    }
+
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+
+    pub fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    /// Transform, process and resynthesize the frame in each channel's `input`
+    fn run_frame(&mut self, channels: usize, sample_rate: f32) {
+        let size = self.fft.size();
+        let (hop, half) = (self.hop, size / 2);
+        let spectrum = &mut self.spectrum;
+        for (channel, state) in self.channels.iter_mut().enumerate().take(channels) {
+            for ((bin, &x), &w) in spectrum.iter_mut().zip(&state.input).zip(&self.window) {
+                *bin = Complex::new(x * w, 0.0);
+            }
+            self.fft.forward(spectrum);
+            let context = SpectralContext { channel, sample_rate, fft_size: size, hop, frame: self.frames };
+            self.processor.process_spectrum(&mut spectrum[..=half], &context);
+            // keep the signal real
+            spectrum[0].im = 0.0;
+            spectrum[half].im = 0.0;
+            for k in 1..half {
+                spectrum[size - k] = spectrum[k].conj();
+            }
+            self.fft.inverse(spectrum);
+
+            for ((sum, bin), &w) in state.accumulator.iter_mut().zip(spectrum.iter()).zip(&self.window) {
+                *sum += bin.re * w;
+            }
+            for ((out, &sum), &gain) in state.output.iter_mut().zip(&state.accumulator).zip(&self.normalize) {
+                *out = sum * gain;
+            }
+            state.accumulator.copy_within(hop..size, 0);
+            state.accumulator[size - hop..].fill(0.0);
+            state.input.copy_within(hop..size, 0);
+        }
+        self.frames += 1;
+    }
+}
+
+impl<P: SpectralProcessor> Effect for StftEffect<P> {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        let channels = buffer.len().min(self.channels.len());
+        let size = self.fft.size();
+        for i in 0..frames {
+            for (state, samples) in self.channels.iter_mut().zip(buffer.iter()) {
+                state.input[self.fill] = samples[i];
+            }
+            self.fill += 1;
+            if self.fill == size {
+                self.run_frame(channels, sample_rate);
+                self.fill = size - self.hop;
+                self.read = 0;
+            }
+            for (state, samples) in self.channels.iter().zip(buffer.iter_mut()) {
+                samples[i] = state.output[self.read];
+            }
+            self.read += 1;
+        }
+    }
+
+    fn reset(&mut self) {
+        for state in &mut self.channels {
+            state.input.fill(0.0);
+            state.accumulator.fill(0.0);
+            state.output.fill(0.0);
+        }
+        self.fill = self.fft.size() - self.hop;
+        self.read = 0;
+        self.frames = 0;
+        self.processor.reset();
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        for state in &mut self.channels {
+            memory.touch(&mut state.input);
+            memory.touch(&mut state.accumulator);
+            memory.touch(&mut state.output);
+        }
+        memory.touch(&mut self.spectrum);
+    }
+
+    fn latency(&self) -> usize {
+        StftEffect::latency(self)
+    }
+}
+
+/// Spectral freeze: while the gate is open, every frame plays the magnitudes
+/// captured as it opened with fresh random phases, sustaining the sound as a
+/// steady wash; while it's closed, audio passes through. Overlap-add
+/// crossfades both ways over a frame.
+///
+/// Each channel draws its own phases, which also widens the held sound. The
+/// gate is shared (`gate_handle`), so it can be opened from any thread after
+/// the effect is in a chain.
+pub struct SpectralFreeze {
+    gate: Arc<AtomicBool>,
+    /// Per channel: the captured magnitudes, and whether they belong to the
+    /// current opening of the gate
+    magnitudes: Vec<Vec<f32>>,
+    captured: Vec<bool>,
+    rng: FastRng,
+}
+
+impl SpectralFreeze {
+    /// Room for `channels` spectra of `config`'s size; use with that config
+    pub fn new(channels: usize, config: &StftConfig) -> Self {
+        Self {
+            gate: Arc::new(AtomicBool::new(false)),
+            magnitudes: vec![vec![0.0; config.bins()]; channels],
+            captured: vec![false; channels],
+            rng: FastRng::new(1),
+        }
+    }
+
+    /// The freeze as an effect
+    pub fn effect(channels: usize, config: StftConfig) -> StftEffect<Self> {
+        StftEffect::new(channels, config, Self::new(channels, &config))
+    }
+
+    /// Open (freeze) or close the gate; takes effect from the next frame
+    pub fn set_frozen(&self, frozen: bool) {
+        self.gate.store(frozen, Ordering::Relaxed);
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.gate.load(Ordering::Relaxed)
+    }
+
+    /// Shared gate: `true` freezes
+    pub fn gate_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.gate)
+    }
+}
+
+impl SpectralProcessor for SpectralFreeze {
+    fn process_spectrum(&mut self, bins: &mut [Complex], context: &SpectralContext) {
+        let channel = context.channel;
+        let (Some(magnitudes), Some(captured)) = (self.magnitudes.get_mut(channel), self.captured.get_mut(channel))
+        else {
+            return;
+        };
+        if !self.gate.load(Ordering::Relaxed) {
+            *captured = false;
+            return;
+        }
+        if !*captured {
+            for (magnitude, bin) in magnitudes.iter_mut().zip(bins.iter()) {
+                *magnitude = bin.norm();
+            }
+            *captured = true;
+        }
+        for (bin, &magnitude) in bins.iter_mut().zip(magnitudes.iter()) {
+            let (sin, cos) = (TAU * self.rng.next_f32()).sin_cos();
+            *bin = Complex::new(magnitude * cos, magnitude * sin);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.captured.fill(false);
+    }
+}
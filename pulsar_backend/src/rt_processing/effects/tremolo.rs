@@ -0,0 +1,114 @@
+use crate::rt_processing::filters::OnePoleState;
+use crate::rt_processing::modulation::{ControlRamp, DEFAULT_CONTROL_INTERVAL, ModulationMonitor};
+use crate::rt_processing::waveform::oscillators::LFO;
+use crate::rt_processing::waveform::tables::WaveformType;
+
+use super::{DEFAULT_TEMPO_BPM, Effect, LfoRate};
+
+/// Corner of the gain smoother; keeps square/saw shapes from clicking
+const GAIN_SMOOTHING_HZ: f32 = 150.0;
+
+/// Amplitude modulation by an LFO.
+///
+/// Gain swings between 1.0 and `1.0 - depth` following the selected waveform.
+/// With `LfoRate::Beats` the rate follows the tempo given to `set_tempo`.
+/// The LFO runs at control rate (see `ControlRamp`). The gain is published as
+/// the `gain` modulation reading (base 1.0).
+pub struct Tremolo {
+    lfo: LFO,
+    rate: LfoRate,
+    depth: f32,
+    tempo_bpm: f32,
+    smoother: OnePoleState,
+    control: ControlRamp,
+    modulation: ModulationMonitor,
+}
+
+impl Tremolo {
+    pub fn new(waveform: WaveformType, rate: LfoRate) -> Self {
+        let mut lfo = LFO::new(waveform, rate.frequency(DEFAULT_TEMPO_BPM));
+        // start at full gain
+        lfo.set_phase(0.25);
+        Self {
+            lfo,
+            rate,
+            depth: 0.5,
+            tempo_bpm: DEFAULT_TEMPO_BPM,
+            smoother: OnePoleState::default(),
+            control: ControlRamp::new(DEFAULT_CONTROL_INTERVAL),
+            modulation: ModulationMonitor::new(&["gain"]),
+        }
+    }
+
+    /// Modulation depth (0.0 = no effect, 1.0 = full gate)
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.set_depth(depth);
+        self
+    }
+
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// Samples between LFO evaluations (1 = every sample)
+    pub fn with_control_interval(mut self, interval: usize) -> Self {
+        self.control.set_interval(interval);
+        self
+    }
+
+    pub fn set_rate(&mut self, rate: LfoRate) {
+        self.rate = rate;
+        self.lfo.set_frequency(rate.frequency(self.tempo_bpm));
+    }
+
+    pub fn set_waveform(&mut self, waveform: WaveformType) {
+        self.lfo.set_waveform(waveform);
+    }
+
+    /// Tempo used by `LfoRate::Beats`
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.tempo_bpm = bpm;
+        self.lfo.set_frequency(self.rate.frequency(bpm));
+    }
+
+    /// Restart the LFO cycle, e.g. on a bar line
+    pub fn retrigger(&mut self) {
+        self.lfo.set_phase(0.25);
+    }
+
+    pub fn depth(&self) -> f32 {
+        self.depth
+    }
+
+    pub fn rate(&self) -> LfoRate {
+        self.rate
+    }
+}
+
+impl Effect for Tremolo {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        let a = OnePoleState::coefficient(GAIN_SMOOTHING_HZ, sample_rate);
+        let mut gain = 1.0;
+        for i in 0..frames {
+            // LFO in [-1, 1] -> attenuation in [0, depth]
+            let lfo = self.control.next(|n| self.lfo.advance(n, sample_rate));
+            let target = 1.0 - self.depth * (1.0 - lfo) * 0.5;
+            gain = self.smoother.low_pass(a, target);
+            for samples in buffer.iter_mut() {
+                samples[i] *= gain;
+            }
+        }
+        self.modulation.publish(0, 1.0, gain - 1.0);
+    }
+
+    fn reset(&mut self) {
+        self.retrigger();
+        self.smoother.reset();
+        self.control.reset();
+        self.modulation.clear();
+    }
+
+    fn modulation(&self) -> Option<ModulationMonitor> {
+        Some(self.modulation.clone())
+    }
+}
@@ -0,0 +1,237 @@
+use crate::rt_processing::filters::{BiquadCoefficients, BiquadState, EnvelopeFollower};
+use crate::rt_processing::prefault::Prefault;
+use crate::rt_processing::routing::AudioSource;
+
+use super::Effect;
+
+/// Upper bound on vocoder bands (state is preallocated for this many)
+pub const MAX_VOCODER_BANDS: usize = 32;
+
+/// One band-pass stage pair (two cascaded biquads = 4th order)
+#[derive(Copy, Clone, Default)]
+struct BandFilter {
+    stages: [BiquadState; 2],
+}
+
+impl BandFilter {
+    #[inline(always)]
+    fn process(&mut self, c: &BiquadCoefficients, x: f32) -> f32 {
+        let y = self.stages[0].process(c, x);
+        self.stages[1].process(c, y)
+    }
+
+    fn reset(&mut self) {
+        self.stages.iter_mut().for_each(BiquadState::reset);
+    }
+}
+
+/// Vocoder parameters
+#[derive(Copy, Clone, Debug)]
+pub struct VocoderParams {
+    /// Number of analysis/synthesis bands (clamped to 4..=MAX_VOCODER_BANDS)
+    pub band_count: usize,
+    /// Lowest band center in Hz
+    pub low_frequency: f32,
+    /// Highest band center in Hz
+    pub high_frequency: f32,
+    /// Carrier band shift relative to the modulator bands, in semitones
+    pub formant_shift: f32,
+    /// Envelope follower attack in seconds
+    pub attack: f32,
+    /// Envelope follower release in seconds
+    pub release: f32,
+    /// Output makeup gain
+    pub output_gain: f32,
+    /// Dry carrier blended into the output (0.0 to 1.0)
+    pub dry_mix: f32,
+}
+
+impl Default for VocoderParams {
+    fn default() -> Self {
+        Self {
+            band_count: 16,
+            low_frequency: 100.0,
+            high_frequency: 8000.0,
+            formant_shift: 0.0,
+            attack: 0.002,
+            release: 0.03,
+            output_gain: 4.0,
+            dry_mix: 0.0,
+        }
+    }
+}
+
+/// Classic channel vocoder.
+///
+/// The modulator (typically input capture) is split into log-spaced bands whose
+/// envelopes are followed and imposed on the matching bands of the carrier (the
+/// bus this effect is inserted on, typically a synth). Formant shift moves the
+/// carrier bands relative to the modulator bands.
+///
+/// The modulator is either rendered from an attached routing `AudioSource` each
+/// block, or passed explicitly via `process_with_modulator`.
+pub struct Vocoder {
+    params: VocoderParams,
+    channels: usize,
+    sample_rate: f32,
+    analysis_coeffs: [BiquadCoefficients; MAX_VOCODER_BANDS],
+    synthesis_coeffs: [BiquadCoefficients; MAX_VOCODER_BANDS],
+    analysis: [BandFilter; MAX_VOCODER_BANDS],
+    followers: [EnvelopeFollower; MAX_VOCODER_BANDS],
+    // [channel][band]
+    synthesis: Vec<[BandFilter; MAX_VOCODER_BANDS]>,
+    modulator: Option<Box<dyn AudioSource + 'static>>,
+    // mono render target for the modulator source
+    modulator_buffer: Vec<f32>,
+}
+
+impl Vocoder {
+    pub fn new(channels: usize, max_frames: usize, params: VocoderParams) -> Self {
+        Self {
+            params: Self::sanitize(params),
+            channels,
+            sample_rate: 0.0,
+            analysis_coeffs: [BiquadCoefficients::IDENTITY; MAX_VOCODER_BANDS],
+            synthesis_coeffs: [BiquadCoefficients::IDENTITY; MAX_VOCODER_BANDS],
+            analysis: [BandFilter::default(); MAX_VOCODER_BANDS],
+            followers: [EnvelopeFollower::new(0.0, 0.0, 1.0); MAX_VOCODER_BANDS],
+            synthesis: vec![[BandFilter::default(); MAX_VOCODER_BANDS]; channels],
+            modulator: None,
+            modulator_buffer: vec![0.0; max_frames],
+        }
+    }
+
+    /// Attach the modulator source (e.g. the input capture source)
+    pub fn with_modulator(mut self, modulator: Box<dyn AudioSource + 'static>) -> Self {
+        self.modulator = Some(modulator);
+        self
+    }
+
+    pub fn set_modulator(&mut self, modulator: Option<Box<dyn AudioSource + 'static>>) {
+        self.modulator = modulator;
+    }
+
+    pub fn params(&self) -> &VocoderParams {
+        &self.params
+    }
+
+    /// Update parameters; filter coefficients are rebuilt on the next block
+    pub fn set_params(&mut self, params: VocoderParams) {
+        self.params = Self::sanitize(params);
+        self.sample_rate = 0.0;
+    }
+
+    pub fn set_formant_shift(&mut self, semitones: f32) {
+        self.set_params(VocoderParams { formant_shift: semitones, ..self.params });
+    }
+
+    pub fn set_band_count(&mut self, band_count: usize) {
+        self.set_params(VocoderParams { band_count, ..self.params });
+    }
+
+    fn sanitize(params: VocoderParams) -> VocoderParams {
+        let low = params.low_frequency.max(20.0);
+        VocoderParams {
+            band_count: params.band_count.clamp(4, MAX_VOCODER_BANDS),
+            low_frequency: low,
+            high_frequency: params.high_frequency.max(low * 2.0),
+            formant_shift: params.formant_shift.clamp(-24.0, 24.0),
+            dry_mix: params.dry_mix.clamp(0.0, 1.0),
+            ..params
+        }
+    }
+
+    fn prepare(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        let p = self.params;
+        let n = p.band_count;
+
+        // log-spaced centers; Q sized so adjacent bands cross near -3 dB
+        let ratio = (p.high_frequency / p.low_frequency).powf(1.0 / (n - 1) as f32);
+        let q = ratio.sqrt() / (ratio - 1.0);
+        let shift = 2f32.powf(p.formant_shift / 12.0);
+
+        for band in 0..n {
+            let center = p.low_frequency * ratio.powi(band as i32);
+            self.analysis_coeffs[band] = BiquadCoefficients::band_pass(center, q, sample_rate);
+            self.synthesis_coeffs[band] = BiquadCoefficients::band_pass(center * shift, q, sample_rate);
+            self.followers[band].set_times(p.attack, p.release, sample_rate);
+        }
+    }
+
+    /// Vocode `carrier` in place using the mono `modulator` signal
+    pub fn process_with_modulator(
+        &mut self,
+        carrier: &mut [&mut [f32]],
+        modulator: &[f32],
+        frames: usize,
+        sample_rate: f32,
+    ) {
+        if self.sample_rate != sample_rate {
+            self.prepare(sample_rate);
+        }
+        let n = self.params.band_count;
+        let dry = self.params.dry_mix;
+        let gain = self.params.output_gain;
+        let channels = carrier.len().min(self.channels);
+
+        for i in 0..frames {
+            // analysis: one envelope per band, shared by all carrier channels
+            let m = modulator[i];
+            let mut envelopes = [0.0f32; MAX_VOCODER_BANDS];
+            let analysis = self.analysis.iter_mut().zip(&self.analysis_coeffs).zip(&mut self.followers);
+            for (((filter, coeffs), follower), env) in analysis.zip(envelopes.iter_mut()).take(n) {
+                *env = follower.process(filter.process(coeffs, m));
+            }
+
+            for (samples, bands) in carrier.iter_mut().take(channels).zip(self.synthesis.iter_mut()) {
+                let x = samples[i];
+                let wet: f32 = bands
+                    .iter_mut()
+                    .zip(&self.synthesis_coeffs)
+                    .zip(&envelopes)
+                    .take(n)
+                    .map(|((filter, coeffs), env)| filter.process(coeffs, x) * env)
+                    .sum();
+                samples[i] = wet * gain + x * dry;
+            }
+        }
+    }
+}
+
+impl Effect for Vocoder {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        let Some(mut modulator) = self.modulator.take() else {
+            // no modulator: nothing opens the bands
+            for samples in buffer.iter_mut() {
+                for s in samples[..frames].iter_mut() {
+                    *s *= self.params.dry_mix;
+                }
+            }
+            return;
+        };
+
+        // render the modulator as a single channel
+        let frames = frames.min(self.modulator_buffer.len());
+        let mut mono = std::mem::take(&mut self.modulator_buffer);
+        modulator.render(&mut [&mut mono[..frames]], frames, sample_rate);
+        self.process_with_modulator(buffer, &mono[..frames], frames, sample_rate);
+        self.modulator_buffer = mono;
+        self.modulator = Some(modulator);
+    }
+
+    fn reset(&mut self) {
+        self.analysis.iter_mut().for_each(BandFilter::reset);
+        self.followers.iter_mut().for_each(EnvelopeFollower::reset);
+        for bands in &mut self.synthesis {
+            bands.iter_mut().for_each(BandFilter::reset);
+        }
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        memory.touch(&mut self.modulator_buffer);
+        if let Some(modulator) = &mut self.modulator {
+            modulator.prefault(memory);
+        }
+    }
+}
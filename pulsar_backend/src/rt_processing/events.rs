@@ -0,0 +1,90 @@
+use crossbeam::channel::{self, Receiver, Sender, TrySendError};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Events published by the engine for UI, visuals and control logic.
+///
+/// Frame positions are counted from the moment the publishing analyzer started.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EngineEvent {
+    /// A transient/onset was detected
+    Onset { frame: u64, strength: f32 },
+    /// A tracked beat occurred
+    Beat { frame: u64, bpm: f32 },
+    /// Tempo estimate changed noticeably
+    TempoEstimate { bpm: f32, confidence: f32 },
+    /// A router bus with an auto-trim has stayed above its headroom target;
+    /// `frame` is the router's position, `sources` those playing into the bus
+    HeadroomExceeded { frame: u64, bus: usize, level_db: f32, sources: usize },
+}
+
+/// Bounded, lock-free event bus.
+///
+/// Publishing is real-time safe (`try_send` only); if the bus is full the event is
+/// dropped and counted instead of blocking the audio thread.
+pub struct EventBus {
+    sender: Sender<EngineEvent>,
+    receiver: Receiver<EngineEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, receiver) = channel::bounded(capacity.max(1));
+        Self {
+            sender,
+            receiver,
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Cloneable handle for producers (audio thread or otherwise)
+    pub fn publisher(&self) -> EventPublisher {
+        EventPublisher {
+            sender: self.sender.clone(),
+            dropped: Arc::clone(&self.dropped),
+        }
+    }
+
+    /// Receiver for consumers. Each event is delivered to exactly one receiver.
+    pub fn subscriber(&self) -> Receiver<EngineEvent> {
+        self.receiver.clone()
+    }
+
+    /// Drain all pending events (non-RT)
+    pub fn drain(&self) -> Vec<EngineEvent> {
+        self.receiver.try_iter().collect()
+    }
+
+    /// Number of events dropped because the bus was full
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+/// Producer side of the `EventBus`
+#[derive(Clone)]
+pub struct EventPublisher {
+    sender: Sender<EngineEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl EventPublisher {
+    /// Publish without blocking. Returns `false` if the event was dropped.
+    #[inline]
+    pub fn publish(&self, event: EngineEvent) -> bool {
+        match self.sender.try_send(event) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::f32::consts::PI;
+use std::ops::{Add, Mul, Sub};
+
+/// Minimal complex number for spectral processing
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub const ZERO: Self = Self { re: 0.0, im: 0.0 };
+
+    #[inline(always)]
+    pub fn new(re: f32, im: f32) -> Self {
+        Self { re, im }
+    }
+
+    #[inline(always)]
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    #[inline(always)]
+    pub fn norm_sqr(self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    #[inline(always)]
+    pub fn norm(self) -> f32 {
+        self.norm_sqr().sqrt()
+    }
+
+    #[inline(always)]
+    pub fn scale(self, k: f32) -> Self {
+        Self::new(self.re * k, self.im * k)
+    }
+
+    /// `self + a * b`
+    #[inline(always)]
+    pub fn mul_add(self, a: Self, b: Self) -> Self {
+        Self::new(self.re + a.re * b.re - a.im * b.im, self.im + a.re * b.im + a.im * b.re)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+    #[inline(always)]
+    fn add(self, o: Self) -> Self {
+        Self::new(self.re + o.re, self.im + o.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+    #[inline(always)]
+    fn sub(self, o: Self) -> Self {
+        Self::new(self.re - o.re, self.im - o.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+    #[inline(always)]
+    fn mul(self, o: Self) -> Self {
+        Self::new(self.re * o.re - self.im * o.im, self.re * o.im + self.im * o.re)
+    }
+}
+
+/// Window applied to each frame before a transform
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Window {
+    Rectangular,
+    #[default]
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl Window {
+    /// `size` coefficients of the periodic form, which tiles seamlessly when
+    /// frames overlap
+    pub fn coefficients(self, size: usize) -> Vec<f32> {
+        (0..size)
+            .map(|i| {
+                let phase = 2.0 * PI * i as f32 / size as f32;
+                match self {
+                    Window::Rectangular => 1.0,
+                    Window::Hann => 0.5 - 0.5 * phase.cos(),
+                    Window::Hamming => 0.54 - 0.46 * phase.cos(),
+                    Window::Blackman => 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Iterative radix-2 FFT for a fixed power-of-two size.
+///
+/// Twiddles and the bit-reversal table are computed in `new`, so `forward` and
+/// `inverse` run in place without allocating and are safe on the audio thread.
+pub struct Fft {
+    size: usize,
+    twiddles: Vec<Complex>,
+    bit_reverse: Vec<u32>,
+}
+
+impl Fft {
+    /// `size` is rounded up to a power of two (minimum 2)
+    pub fn new(size: usize) -> Self {
+        let size = size.max(2).next_power_of_two();
+        let bits = size.trailing_zeros();
+        let twiddles = (0..size / 2)
+            .map(|k| {
+                let (sin, cos) = (-2.0 * PI * k as f32 / size as f32).sin_cos();
+                Complex::new(cos, sin)
+            })
+            .collect();
+        let bit_reverse = (0..size as u32).map(|i| i.reverse_bits() >> (32 - bits)).collect();
+        Self { size, twiddles, bit_reverse }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Forward transform (unscaled)
+    pub fn forward(&self, data: &mut [Complex]) {
+        self.transform(data, false);
+    }
+
+    /// Inverse transform, scaled by `1 / size` so `inverse(forward(x)) == x`
+    pub fn inverse(&self, data: &mut [Complex]) {
+        self.transform(data, true);
+        let k = 1.0 / self.size as f32;
+        data[..self.size].iter_mut().for_each(|c| *c = c.scale(k));
+    }
+
+    fn transform(&self, data: &mut [Complex], inverse: bool) {
+        let n = self.size;
+        let data = &mut data[..n];
+
+        for (i, &j) in self.bit_reverse.iter().enumerate() {
+            let j = j as usize;
+            if i < j {
+                data.swap(i, j);
+            }
+        }
+
+        let mut len = 2;
+        while len <= n {
+            let half = len / 2;
+            let stride = n / len;
+            for chunk in data.chunks_exact_mut(len) {
+                let (lo, hi) = chunk.split_at_mut(half);
+                for (k, (a, b)) in lo.iter_mut().zip(hi.iter_mut()).enumerate() {
+                    let w = self.twiddles[k * stride];
+                    let w = if inverse { w.conj() } else { w };
+                    let t = *b * w;
+                    *b = *a - t;
+                    *a = *a + t;
+                }
+            }
+            len *= 2;
+        }
+    }
+}
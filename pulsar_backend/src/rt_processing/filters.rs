@@ -0,0 +1,1009 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::rt_processing::effects::{DEFAULT_TEMPO_BPM, Effect, LfoRate};
+use crate::rt_processing::modulation::{DEFAULT_CONTROL_INTERVAL, ModulationMonitor};
+use crate::rt_processing::prefault::Prefault;
+use crate::rt_processing::routing::AudioSource;
+use crate::rt_processing::waveform::oscillators::LFO;
+use crate::rt_processing::waveform::tables::WaveformType;
+
+/// Butterworth Q for a single 2nd-order section
+pub const BUTTERWORTH_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Q of a band-pass spanning one octave
+pub const OCTAVE_Q: f32 = std::f32::consts::SQRT_2;
+
+/// Normalized biquad coefficients (a0 == 1)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BiquadCoefficients {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+    pub a1: f32,
+    pub a2: f32,
+}
+
+impl BiquadCoefficients {
+    /// Pass-through (identity) coefficients
+    pub const IDENTITY: Self = Self { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0 };
+
+    /// RBJ cookbook low-pass
+    pub fn low_pass(cutoff: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prewarp(cutoff, q, sample_rate);
+        let b1 = 1.0 - cos_w;
+        Self::normalize(b1 * 0.5, b1, b1 * 0.5, 1.0 + alpha, -2.0 * cos_w, 1.0 - alpha)
+    }
+
+    /// RBJ cookbook high-pass
+    pub fn high_pass(cutoff: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prewarp(cutoff, q, sample_rate);
+        let b1 = -(1.0 + cos_w);
+        Self::normalize(-b1 * 0.5, b1, -b1 * 0.5, 1.0 + alpha, -2.0 * cos_w, 1.0 - alpha)
+    }
+
+    /// RBJ cookbook band-pass with constant 0 dB peak gain
+    pub fn band_pass(center: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prewarp(center, q, sample_rate);
+        Self::normalize(alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos_w, 1.0 - alpha)
+    }
+
+    /// RBJ cookbook notch (band-reject) with unity gain away from `center`
+    pub fn notch(center: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prewarp(center, q, sample_rate);
+        Self::normalize(1.0, -2.0 * cos_w, 1.0, 1.0 + alpha, -2.0 * cos_w, 1.0 - alpha)
+    }
+
+    /// RBJ cookbook peaking EQ
+    pub fn peaking(center: f32, q: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prewarp(center, q, sample_rate);
+        let a = 10f32.powf(gain_db / 40.0);
+        Self::normalize(1.0 + alpha * a, -2.0 * cos_w, 1.0 - alpha * a, 1.0 + alpha / a, -2.0 * cos_w, 1.0 - alpha / a)
+    }
+
+    /// RBJ cookbook low shelf (`q = BUTTERWORTH_Q` gives the steepest monotonic slope)
+    pub fn low_shelf(cutoff: f32, q: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prewarp(cutoff, q, sample_rate);
+        let a = 10f32.powf(gain_db / 40.0);
+        let k = 2.0 * a.sqrt() * alpha;
+        Self::normalize(
+            a * ((a + 1.0) - (a - 1.0) * cos_w + k),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos_w),
+            a * ((a + 1.0) - (a - 1.0) * cos_w - k),
+            (a + 1.0) + (a - 1.0) * cos_w + k,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos_w),
+            (a + 1.0) + (a - 1.0) * cos_w - k,
+        )
+    }
+
+    /// RBJ cookbook high shelf
+    pub fn high_shelf(cutoff: f32, q: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prewarp(cutoff, q, sample_rate);
+        let a = 10f32.powf(gain_db / 40.0);
+        let k = 2.0 * a.sqrt() * alpha;
+        Self::normalize(
+            a * ((a + 1.0) + (a - 1.0) * cos_w + k),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w),
+            a * ((a + 1.0) + (a - 1.0) * cos_w - k),
+            (a + 1.0) - (a - 1.0) * cos_w + k,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos_w),
+            (a + 1.0) - (a - 1.0) * cos_w - k,
+        )
+    }
+
+    /// RBJ cookbook all-pass (unity magnitude, phase turns through 180° at `center`)
+    pub fn all_pass(center: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prewarp(center, q, sample_rate);
+        Self::normalize(1.0 - alpha, -2.0 * cos_w, 1.0 + alpha, 1.0 + alpha, -2.0 * cos_w, 1.0 - alpha)
+    }
+
+    /// Squared magnitude response at `freq` Hz
+    pub fn magnitude_squared(&self, freq: f32, sample_rate: f32) -> f32 {
+        let w = 2.0 * PI * freq / sample_rate;
+        let (s1, c1) = w.sin_cos();
+        let (s2, c2) = (2.0 * w).sin_cos();
+        // H(e^-jw) numerator and denominator as complex numbers
+        let num_re = self.b0 + self.b1 * c1 + self.b2 * c2;
+        let num_im = -(self.b1 * s1 + self.b2 * s2);
+        let den_re = 1.0 + self.a1 * c1 + self.a2 * c2;
+        let den_im = -(self.a1 * s1 + self.a2 * s2);
+        (num_re * num_re + num_im * num_im) / (den_re * den_re + den_im * den_im).max(f32::MIN_POSITIVE)
+    }
+
+    #[inline]
+    fn prewarp(cutoff: f32, q: f32, sample_rate: f32) -> (f32, f32) {
+        // keep the cutoff safely below Nyquist so the section stays stable
+        let cutoff = cutoff.clamp(1.0, sample_rate * 0.49);
+        let w = 2.0 * PI * cutoff / sample_rate;
+        let (sin_w, cos_w) = w.sin_cos();
+        (cos_w, sin_w / (2.0 * q.max(1e-3)))
+    }
+
+    #[inline]
+    fn normalize(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        let inv = 1.0 / a0;
+        Self {
+            b0: b0 * inv,
+            b1: b1 * inv,
+            b2: b2 * inv,
+            a1: a1 * inv,
+            a2: a2 * inv,
+        }
+    }
+}
+
+/// Response of a `Biquad`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterType {
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+    Peaking,
+    LowShelf,
+    HighShelf,
+}
+
+impl FilterType {
+    /// Coefficients for this response; `gain_db` only affects peaking and shelves
+    pub fn coefficients(self, cutoff: f32, q: f32, gain_db: f32, sample_rate: f32) -> BiquadCoefficients {
+        match self {
+            FilterType::LowPass => BiquadCoefficients::low_pass(cutoff, q, sample_rate),
+            FilterType::HighPass => BiquadCoefficients::high_pass(cutoff, q, sample_rate),
+            FilterType::BandPass => BiquadCoefficients::band_pass(cutoff, q, sample_rate),
+            FilterType::Notch => BiquadCoefficients::notch(cutoff, q, sample_rate),
+            FilterType::Peaking => BiquadCoefficients::peaking(cutoff, q, gain_db, sample_rate),
+            FilterType::LowShelf => BiquadCoefficients::low_shelf(cutoff, q, gain_db, sample_rate),
+            FilterType::HighShelf => BiquadCoefficients::high_shelf(cutoff, q, gain_db, sample_rate),
+        }
+    }
+}
+
+/// Per-channel biquad state (transposed direct form II)
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BiquadState {
+    z1: f32,
+    z2: f32,
+}
+
+impl BiquadState {
+    #[inline(always)]
+    pub fn process(&mut self, c: &BiquadCoefficients, x: f32) -> f32 {
+        let y = c.b0 * x + self.z1;
+        self.z1 = c.b1 * x - c.a1 * y + self.z2;
+        self.z2 = c.b2 * x - c.a2 * y;
+        y
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+/// Arithmetic inside a filter that offers a double-precision path. Audio
+/// stays f32 at the node edges either way.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Precision {
+    #[default]
+    Single,
+    /// Coefficients and state in f64: for low cutoffs at high sample rates,
+    /// where f32 poles sit so close to the unit circle that rounding shifts
+    /// the response and leaves a DC offset in the feedback path
+    Double,
+}
+
+/// Double-precision counterpart of `BiquadCoefficients`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BiquadCoefficients64 {
+    pub b0: f64,
+    pub b1: f64,
+    pub b2: f64,
+    pub a1: f64,
+    pub a2: f64,
+}
+
+impl BiquadCoefficients64 {
+    pub const IDENTITY: Self = Self { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0 };
+
+    /// RBJ cookbook low-pass
+    pub fn low_pass(cutoff: f64, q: f64, sample_rate: f64) -> Self {
+        let (cos_w, alpha) = Self::prewarp(cutoff, q, sample_rate);
+        let b1 = 1.0 - cos_w;
+        Self::normalize(b1 * 0.5, b1, b1 * 0.5, 1.0 + alpha, -2.0 * cos_w, 1.0 - alpha)
+    }
+
+    /// RBJ cookbook high-pass
+    pub fn high_pass(cutoff: f64, q: f64, sample_rate: f64) -> Self {
+        let (cos_w, alpha) = Self::prewarp(cutoff, q, sample_rate);
+        let b1 = -(1.0 + cos_w);
+        Self::normalize(-b1 * 0.5, b1, -b1 * 0.5, 1.0 + alpha, -2.0 * cos_w, 1.0 - alpha)
+    }
+
+    #[inline]
+    fn prewarp(cutoff: f64, q: f64, sample_rate: f64) -> (f64, f64) {
+        let cutoff = cutoff.clamp(1.0, sample_rate * 0.49);
+        let w = 2.0 * std::f64::consts::PI * cutoff / sample_rate;
+        let (sin_w, cos_w) = w.sin_cos();
+        (cos_w, sin_w / (2.0 * q.max(1e-3)))
+    }
+
+    #[inline]
+    fn normalize(b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) -> Self {
+        let inv = 1.0 / a0;
+        Self {
+            b0: b0 * inv,
+            b1: b1 * inv,
+            b2: b2 * inv,
+            a1: a1 * inv,
+            a2: a2 * inv,
+        }
+    }
+}
+
+/// Double-precision biquad state; samples are converted on the way in and out
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BiquadState64 {
+    z1: f64,
+    z2: f64,
+}
+
+impl BiquadState64 {
+    #[inline(always)]
+    pub fn process(&mut self, c: &BiquadCoefficients64, x: f32) -> f32 {
+        let x = x as f64;
+        let y = c.b0 * x + self.z1;
+        self.z1 = c.b1 * x - c.a1 * y + self.z2;
+        self.z2 = c.b2 * x - c.a2 * y;
+        y as f32
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+/// Coefficients for one Linkwitz-Riley 4th-order (LR4) crossover point
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CrossoverCoefficients {
+    pub low: BiquadCoefficients,
+    pub high: BiquadCoefficients,
+    /// The LP + HP sum of an LR4 pair, used to phase-align bands split elsewhere
+    pub all_pass: BiquadCoefficients,
+}
+
+impl CrossoverCoefficients {
+    pub fn new(frequency: f32, sample_rate: f32) -> Self {
+        Self {
+            low: BiquadCoefficients::low_pass(frequency, BUTTERWORTH_Q, sample_rate),
+            high: BiquadCoefficients::high_pass(frequency, BUTTERWORTH_Q, sample_rate),
+            all_pass: BiquadCoefficients::all_pass(frequency, BUTTERWORTH_Q, sample_rate),
+        }
+    }
+}
+
+/// Per-channel LR4 crossover state (two cascaded Butterworth sections per side).
+/// `low + high` reconstructs the input with a flat magnitude response.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CrossoverState {
+    low: [BiquadState; 2],
+    high: [BiquadState; 2],
+}
+
+impl CrossoverState {
+    /// Split one sample into `(low, high)`
+    #[inline(always)]
+    pub fn split(&mut self, c: &CrossoverCoefficients, x: f32) -> (f32, f32) {
+        let [l0, l1] = &mut self.low;
+        let [h0, h1] = &mut self.high;
+        (l1.process(&c.low, l0.process(&c.low, x)), h1.process(&c.high, h0.process(&c.high, x)))
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Per-channel one-pole low-pass state.
+/// The high-pass response is derived as `x - lp(x)`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OnePoleState {
+    y: f32,
+}
+
+impl OnePoleState {
+    /// Smoothing coefficient for a given cutoff
+    #[inline]
+    pub fn coefficient(cutoff: f32, sample_rate: f32) -> f32 {
+        let cutoff = cutoff.clamp(1.0, sample_rate * 0.49);
+        1.0 - (-2.0 * PI * cutoff / sample_rate).exp()
+    }
+
+    #[inline(always)]
+    pub fn low_pass(&mut self, a: f32, x: f32) -> f32 {
+        self.y += a * (x - self.y);
+        self.y
+    }
+
+    #[inline(always)]
+    pub fn high_pass(&mut self, a: f32, x: f32) -> f32 {
+        x - self.low_pass(a, x)
+    }
+
+    /// Current low-pass output
+    pub fn value(&self) -> f32 {
+        self.y
+    }
+
+    pub fn reset(&mut self) {
+        self.y = 0.0;
+    }
+}
+
+/// Double-precision counterpart of `OnePoleState`
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OnePoleState64 {
+    y: f64,
+}
+
+impl OnePoleState64 {
+    #[inline]
+    pub fn coefficient(cutoff: f64, sample_rate: f64) -> f64 {
+        let cutoff = cutoff.clamp(1.0, sample_rate * 0.49);
+        1.0 - (-2.0 * std::f64::consts::PI * cutoff / sample_rate).exp()
+    }
+
+    #[inline(always)]
+    pub fn low_pass(&mut self, a: f64, x: f32) -> f32 {
+        self.y += a * (x as f64 - self.y);
+        self.y as f32
+    }
+
+    #[inline(always)]
+    pub fn high_pass(&mut self, a: f64, x: f32) -> f32 {
+        self.y += a * (x as f64 - self.y);
+        (x as f64 - self.y) as f32
+    }
+
+    pub fn reset(&mut self) {
+        self.y = 0.0;
+    }
+}
+
+/// Curve of a `SmoothedParam` ramp
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RampShape {
+    /// Constant rate, arriving exactly after the ramp time
+    Linear,
+    /// One-pole glide, within -60 dB of the target after the ramp time
+    Exponential,
+}
+
+/// Difference at which an exponential ramp snaps to its target
+const RAMP_SNAP: f32 = 1e-5;
+
+/// A control value that glides to new targets instead of jumping, so gain and
+/// pan changes don't cause zipper noise. Advance it once per sample with
+/// `next_value`, or a block at a time with `fill`; while settled both are a copy.
+#[derive(Copy, Clone, Debug)]
+pub struct SmoothedParam {
+    value: f32,
+    target: f32,
+    ramp_ms: f32,
+    shape: RampShape,
+    sample_rate: f32,
+    // linear: per-sample step and samples left
+    step: f32,
+    remaining: usize,
+    // exponential: one-pole coefficient
+    coeff: f32,
+}
+
+impl SmoothedParam {
+    pub fn new(value: f32, ramp_ms: f32, shape: RampShape) -> Self {
+        Self {
+            value,
+            target: value,
+            ramp_ms: ramp_ms.max(0.0),
+            shape,
+            sample_rate: 0.0,
+            step: 0.0,
+            remaining: 0,
+            coeff: 1.0,
+        }
+    }
+
+    /// Takes effect from the next target
+    pub fn set_ramp(&mut self, ramp_ms: f32, shape: RampShape) {
+        self.ramp_ms = ramp_ms.max(0.0);
+        self.shape = shape;
+        // recompute the coefficient in `prepare`
+        self.sample_rate = 0.0;
+    }
+
+    pub fn ramp_ms(&self) -> f32 {
+        self.ramp_ms
+    }
+
+    pub fn shape(&self) -> RampShape {
+        self.shape
+    }
+
+    /// Call before setting targets or advancing; cheap when the rate is unchanged
+    #[inline]
+    pub fn prepare(&mut self, sample_rate: f32) {
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            // time constant that decays 60 dB over the ramp
+            let seconds = self.ramp_ms * 0.001 / 1000f32.ln();
+            self.coeff = EnvelopeFollower::time_coefficient(seconds, sample_rate);
+        }
+    }
+
+    /// Start gliding from the current value to `target`
+    #[inline]
+    pub fn set_target(&mut self, target: f32) {
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+        let samples = (self.ramp_ms * 0.001 * self.sample_rate).round() as usize;
+        if samples == 0 {
+            self.set_immediate(target);
+        } else if self.shape == RampShape::Linear {
+            self.remaining = samples;
+            self.step = (target - self.value) / samples as f32;
+        }
+    }
+
+    /// Jump straight to `value`, cancelling any ramp
+    pub fn set_immediate(&mut self, value: f32) {
+        self.value = value;
+        self.target = value;
+        self.remaining = 0;
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    pub fn is_smoothing(&self) -> bool {
+        self.value != self.target
+    }
+
+    /// Value for the next sample
+    #[inline(always)]
+    pub fn next_value(&mut self) -> f32 {
+        if self.value != self.target {
+            match self.shape {
+                RampShape::Linear => {
+                    self.remaining = self.remaining.saturating_sub(1);
+                    self.value = if self.remaining == 0 { self.target } else { self.value + self.step };
+                }
+                RampShape::Exponential => {
+                    self.value += self.coeff * (self.target - self.value);
+                    if (self.target - self.value).abs() < RAMP_SNAP {
+                        self.value = self.target;
+                    }
+                }
+            }
+        }
+        self.value
+    }
+
+    /// Values for the next `output.len()` samples
+    #[inline]
+    pub fn fill(&mut self, output: &mut [f32]) {
+        if self.is_smoothing() {
+            output.iter_mut().for_each(|out| *out = self.next_value());
+        } else {
+            output.fill(self.value);
+        }
+    }
+}
+
+/// Trim filter response
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TrimKind {
+    HighPass,
+    LowPass,
+}
+
+/// Trim filter slope
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrimSlope {
+    /// One-pole, 6 dB/octave
+    Db6,
+    /// Butterworth biquad, 12 dB/octave
+    Db12,
+}
+
+/// Trim settings: a single cutoff knob plus slope
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Trim {
+    pub cutoff: f32,
+    pub slope: TrimSlope,
+    #[serde(default)]
+    pub precision: Precision,
+}
+
+impl Trim {
+    pub fn new(cutoff: f32, slope: TrimSlope) -> Self {
+        Self { cutoff, slope, precision: Precision::Single }
+    }
+
+    /// Filter in f64, e.g. for a low high-pass cutoff at 96 kHz and up
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+}
+
+/// Lightweight one-knob HP or LP filter.
+///
+/// Bypassed while no `Trim` is set. Coefficients are recomputed only when the
+/// cutoff, slope or sample rate changes; per-channel state is allocated up front
+/// so `process` never allocates.
+pub struct TrimFilter {
+    kind: TrimKind,
+    trim: Option<Trim>,
+    sample_rate: f32,
+    one_pole_coeff: f32,
+    biquad_coeffs: BiquadCoefficients,
+    one_pole: Vec<OnePoleState>,
+    biquad: Vec<BiquadState>,
+    one_pole_coeff_64: f64,
+    biquad_coeffs_64: BiquadCoefficients64,
+    one_pole_64: Vec<OnePoleState64>,
+    biquad_64: Vec<BiquadState64>,
+}
+
+impl TrimFilter {
+    pub fn new(kind: TrimKind, channels: usize) -> Self {
+        Self {
+            kind,
+            trim: None,
+            sample_rate: 0.0,
+            one_pole_coeff: 1.0,
+            biquad_coeffs: BiquadCoefficients::IDENTITY,
+            one_pole: vec![OnePoleState::default(); channels],
+            biquad: vec![BiquadState::default(); channels],
+            one_pole_coeff_64: 1.0,
+            biquad_coeffs_64: BiquadCoefficients64::IDENTITY,
+            one_pole_64: vec![OnePoleState64::default(); channels],
+            biquad_64: vec![BiquadState64::default(); channels],
+        }
+    }
+
+    pub fn high_pass(channels: usize) -> Self {
+        Self::new(TrimKind::HighPass, channels)
+    }
+
+    pub fn low_pass(channels: usize) -> Self {
+        Self::new(TrimKind::LowPass, channels)
+    }
+
+    /// Set or clear (`None` = bypass) the trim
+    pub fn set(&mut self, trim: Option<Trim>) {
+        let topology = |t: Trim| (t.slope, t.precision);
+        let topology_changed = self.trim.map(topology) != trim.map(topology);
+        self.trim = trim;
+        // force a coefficient refresh on the next block
+        self.sample_rate = 0.0;
+        if topology_changed {
+            self.reset();
+        }
+    }
+
+    pub fn trim(&self) -> Option<Trim> {
+        self.trim
+    }
+
+    pub fn kind(&self) -> TrimKind {
+        self.kind
+    }
+
+    pub fn is_bypassed(&self) -> bool {
+        self.trim.is_none()
+    }
+
+    pub fn reset(&mut self) {
+        self.one_pole.iter_mut().for_each(OnePoleState::reset);
+        self.biquad.iter_mut().for_each(BiquadState::reset);
+        self.one_pole_64.iter_mut().for_each(OnePoleState64::reset);
+        self.biquad_64.iter_mut().for_each(BiquadState64::reset);
+    }
+
+    fn update_coefficients(&mut self, trim: Trim, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        let (cutoff, rate, q) = (trim.cutoff as f64, sample_rate as f64, std::f64::consts::FRAC_1_SQRT_2);
+        match (trim.slope, trim.precision) {
+            (TrimSlope::Db6, Precision::Single) => {
+                self.one_pole_coeff = OnePoleState::coefficient(trim.cutoff, sample_rate);
+            }
+            (TrimSlope::Db6, Precision::Double) => {
+                self.one_pole_coeff_64 = OnePoleState64::coefficient(cutoff, rate);
+            }
+            (TrimSlope::Db12, Precision::Single) => {
+                self.biquad_coeffs = match self.kind {
+                    TrimKind::HighPass => BiquadCoefficients::high_pass(trim.cutoff, BUTTERWORTH_Q, sample_rate),
+                    TrimKind::LowPass => BiquadCoefficients::low_pass(trim.cutoff, BUTTERWORTH_Q, sample_rate),
+                };
+            }
+            (TrimSlope::Db12, Precision::Double) => {
+                self.biquad_coeffs_64 = match self.kind {
+                    TrimKind::HighPass => BiquadCoefficients64::high_pass(cutoff, q, rate),
+                    TrimKind::LowPass => BiquadCoefficients64::low_pass(cutoff, q, rate),
+                };
+            }
+        }
+    }
+
+    /// Filter non-interleaved `[channel][frame]` audio in place
+    pub fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        let Some(trim) = self.trim else { return };
+        if self.sample_rate != sample_rate {
+            self.update_coefficients(trim, sample_rate);
+        }
+
+        let channels = buffer.len().min(self.one_pole.len());
+        for (ch, samples) in buffer.iter_mut().take(channels).enumerate() {
+            let samples = &mut samples[..frames];
+            match (trim.slope, trim.precision, self.kind) {
+                (TrimSlope::Db6, Precision::Single, TrimKind::HighPass) => {
+                    let (a, state) = (self.one_pole_coeff, &mut self.one_pole[ch]);
+                    samples.iter_mut().for_each(|s| *s = state.high_pass(a, *s));
+                }
+                (TrimSlope::Db6, Precision::Single, TrimKind::LowPass) => {
+                    let (a, state) = (self.one_pole_coeff, &mut self.one_pole[ch]);
+                    samples.iter_mut().for_each(|s| *s = state.low_pass(a, *s));
+                }
+                (TrimSlope::Db6, Precision::Double, TrimKind::HighPass) => {
+                    let (a, state) = (self.one_pole_coeff_64, &mut self.one_pole_64[ch]);
+                    samples.iter_mut().for_each(|s| *s = state.high_pass(a, *s));
+                }
+                (TrimSlope::Db6, Precision::Double, TrimKind::LowPass) => {
+                    let (a, state) = (self.one_pole_coeff_64, &mut self.one_pole_64[ch]);
+                    samples.iter_mut().for_each(|s| *s = state.low_pass(a, *s));
+                }
+                (TrimSlope::Db12, Precision::Single, _) => {
+                    let (c, state) = (&self.biquad_coeffs, &mut self.biquad[ch]);
+                    samples.iter_mut().for_each(|s| *s = state.process(c, *s));
+                }
+                (TrimSlope::Db12, Precision::Double, _) => {
+                    let (c, state) = (&self.biquad_coeffs_64, &mut self.biquad_64[ch]);
+                    samples.iter_mut().for_each(|s| *s = state.process(c, *s));
+                }
+            }
+        }
+    }
+}
+
+/// High-pass + low-pass trim pair carried by every routed source
+pub struct SourceTrim {
+    pub high_pass: TrimFilter,
+    pub low_pass: TrimFilter,
+}
+
+impl SourceTrim {
+    pub fn new(channels: usize) -> Self {
+        Self {
+            high_pass: TrimFilter::high_pass(channels),
+            low_pass: TrimFilter::low_pass(channels),
+        }
+    }
+
+    pub fn is_bypassed(&self) -> bool {
+        self.high_pass.is_bypassed() && self.low_pass.is_bypassed()
+    }
+
+    pub fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        self.high_pass.process(buffer, frames, sample_rate);
+        self.low_pass.process(buffer, frames, sample_rate);
+    }
+}
+
+/// Peak envelope follower with separate attack and release times
+#[derive(Copy, Clone, Debug)]
+pub struct EnvelopeFollower {
+    attack_coeff: f32,
+    release_coeff: f32,
+    envelope: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn new(attack_seconds: f32, release_seconds: f32, sample_rate: f32) -> Self {
+        let mut follower = Self {
+            attack_coeff: 1.0,
+            release_coeff: 1.0,
+            envelope: 0.0,
+        };
+        follower.set_times(attack_seconds, release_seconds, sample_rate);
+        follower
+    }
+
+    /// One-pole coefficient reaching ~63% of a step in `seconds`
+    #[inline]
+    pub fn time_coefficient(seconds: f32, sample_rate: f32) -> f32 {
+        if seconds <= 0.0 {
+            1.0
+        } else {
+            1.0 - (-1.0 / (seconds * sample_rate)).exp()
+        }
+    }
+
+    pub fn set_times(&mut self, attack_seconds: f32, release_seconds: f32, sample_rate: f32) {
+        self.attack_coeff = Self::time_coefficient(attack_seconds, sample_rate);
+        self.release_coeff = Self::time_coefficient(release_seconds, sample_rate);
+    }
+
+    #[inline(always)]
+    pub fn process(&mut self, x: f32) -> f32 {
+        let level = x.abs();
+        let coeff = if level > self.envelope { self.attack_coeff } else { self.release_coeff };
+        self.envelope += coeff * (level - self.envelope);
+        // decaying from a gain computer's exact zero, the step rounds to nothing
+        // before the envelope does, leaving it stuck in the subnormals
+        if self.envelope < f32::MIN_POSITIVE {
+            self.envelope = 0.0;
+        }
+        self.envelope
+    }
+
+    pub fn value(&self) -> f32 {
+        self.envelope
+    }
+
+    pub fn reset(&mut self) {
+        self.envelope = 0.0;
+    }
+}
+
+/// Multi-channel RBJ biquad filter with a modulatable cutoff, for use as a
+/// bus insert (`Effect`) or around a single source (`FilteredSource`).
+///
+/// The cutoff heard is the set cutoff moved by an offset from an external
+/// modulation source (`set_cutoff_modulation`) and an optional LFO, both in
+/// octaves. It is re-evaluated every control interval (see
+/// `DEFAULT_CONTROL_INTERVAL`), the coefficients stepping with it, and
+/// published as the `cutoff` modulation reading in Hz. With no modulation the
+/// coefficients are only recomputed when a setting or the sample rate changes.
+pub struct Biquad {
+    filter_type: FilterType,
+    cutoff: f32,
+    q: f32,
+    gain_db: f32,
+    cutoff_offset: f32,
+    lfo: LFO,
+    lfo_rate: LfoRate,
+    lfo_depth: f32,
+    tempo_bpm: f32,
+    interval: usize,
+    countdown: usize,
+    // cutoff and sample rate the coefficients were computed for
+    current: (f32, f32),
+    coefficients: BiquadCoefficients,
+    states: Vec<BiquadState>,
+    modulation: ModulationMonitor,
+}
+
+impl Biquad {
+    pub fn new(filter_type: FilterType, cutoff: f32, channels: usize) -> Self {
+        let lfo_rate = LfoRate::Hz(1.0);
+        Self {
+            filter_type,
+            cutoff: cutoff.max(1.0),
+            q: BUTTERWORTH_Q,
+            gain_db: 0.0,
+            cutoff_offset: 0.0,
+            lfo: LFO::new(WaveformType::Sine, lfo_rate.frequency(DEFAULT_TEMPO_BPM)),
+            lfo_rate,
+            lfo_depth: 0.0,
+            tempo_bpm: DEFAULT_TEMPO_BPM,
+            interval: DEFAULT_CONTROL_INTERVAL,
+            countdown: 0,
+            current: (0.0, 0.0),
+            coefficients: BiquadCoefficients::IDENTITY,
+            states: vec![BiquadState::default(); channels],
+            modulation: ModulationMonitor::new(&["cutoff"]),
+        }
+    }
+
+    pub fn with_q(mut self, q: f32) -> Self {
+        self.set_q(q);
+        self
+    }
+
+    /// Boost or cut of the peaking and shelf types
+    pub fn with_gain_db(mut self, gain_db: f32) -> Self {
+        self.set_gain_db(gain_db);
+        self
+    }
+
+    /// Sweep the cutoff with an LFO, `depth` octaves either side (0.0 = off)
+    pub fn with_lfo(mut self, waveform: WaveformType, rate: LfoRate, depth: f32) -> Self {
+        self.lfo.set_waveform(waveform);
+        self.set_lfo_rate(rate);
+        self.set_lfo_depth(depth);
+        self
+    }
+
+    /// Samples between cutoff updates while modulated (1 = every sample)
+    pub fn with_control_interval(mut self, interval: usize) -> Self {
+        self.interval = interval.max(1);
+        self
+    }
+
+    pub fn set_filter_type(&mut self, filter_type: FilterType) {
+        self.filter_type = filter_type;
+        self.invalidate();
+    }
+
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        self.cutoff = cutoff.max(1.0);
+        self.invalidate();
+    }
+
+    pub fn set_q(&mut self, q: f32) {
+        self.q = q.max(0.01);
+        self.invalidate();
+    }
+
+    pub fn set_gain_db(&mut self, gain_db: f32) {
+        self.gain_db = gain_db;
+        self.invalidate();
+    }
+
+    /// Octaves added to the cutoff by an external source (envelope,
+    /// automation, key tracking)
+    pub fn set_cutoff_modulation(&mut self, octaves: f32) {
+        self.cutoff_offset = octaves;
+        self.countdown = 0;
+    }
+
+    pub fn set_lfo_rate(&mut self, rate: LfoRate) {
+        self.lfo_rate = rate;
+        self.lfo.set_frequency(rate.frequency(self.tempo_bpm));
+    }
+
+    pub fn set_lfo_waveform(&mut self, waveform: WaveformType) {
+        self.lfo.set_waveform(waveform);
+    }
+
+    pub fn set_lfo_depth(&mut self, octaves: f32) {
+        self.lfo_depth = octaves.clamp(0.0, 10.0);
+        self.countdown = 0;
+    }
+
+    /// Tempo used by `LfoRate::Beats`
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.tempo_bpm = bpm;
+        self.lfo.set_frequency(self.lfo_rate.frequency(bpm));
+    }
+
+    pub fn filter_type(&self) -> FilterType {
+        self.filter_type
+    }
+
+    pub fn cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    pub fn q(&self) -> f32 {
+        self.q
+    }
+
+    pub fn gain_db(&self) -> f32 {
+        self.gain_db
+    }
+
+    pub fn lfo_depth(&self) -> f32 {
+        self.lfo_depth
+    }
+
+    pub fn coefficients(&self) -> &BiquadCoefficients {
+        &self.coefficients
+    }
+
+    /// Force a coefficient refresh on the next sample
+    fn invalidate(&mut self) {
+        self.current = (0.0, 0.0);
+        self.countdown = 0;
+    }
+
+    fn update(&mut self, sample_rate: f32) {
+        let octaves = self.cutoff_offset + self.lfo.advance(self.interval, sample_rate) * self.lfo_depth;
+        let cutoff = self.cutoff * octaves.exp2();
+        if (cutoff, sample_rate) != self.current {
+            self.current = (cutoff, sample_rate);
+            self.coefficients = self.filter_type.coefficients(cutoff, self.q, self.gain_db, sample_rate);
+        }
+        self.countdown = self.interval;
+    }
+}
+
+impl Effect for Biquad {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if sample_rate != self.current.1 {
+            self.countdown = 0;
+        }
+        let channels = buffer.len().min(self.states.len());
+        let mut start = 0;
+        while start < frames {
+            if self.countdown == 0 {
+                self.update(sample_rate);
+            }
+            let end = frames.min(start.saturating_add(self.countdown));
+            let c = &self.coefficients;
+            for (samples, state) in buffer.iter_mut().take(channels).zip(self.states.iter_mut()) {
+                samples[start..end].iter_mut().for_each(|s| *s = state.process(c, *s));
+            }
+            // an unmodulated filter only needs updating when a setting changes
+            let modulated = self.lfo_depth > 0.0;
+            self.countdown = if modulated { self.countdown - (end - start) } else { usize::MAX };
+            start = end;
+        }
+        self.modulation.publish(0, self.cutoff, self.current.0 - self.cutoff);
+    }
+
+    fn reset(&mut self) {
+        self.states.iter_mut().for_each(BiquadState::reset);
+        self.lfo.set_phase(0.0);
+        self.invalidate();
+        self.modulation.clear();
+    }
+
+    fn modulation(&self) -> Option<ModulationMonitor> {
+        Some(self.modulation.clone())
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        memory.touch(&mut self.states);
+    }
+}
+
+/// A routed source played through a `Biquad`
+pub struct FilteredSource {
+    source: Box<dyn AudioSource>,
+    filter: Biquad,
+}
+
+impl FilteredSource {
+    pub fn new(source: Box<dyn AudioSource>, filter: Biquad) -> Self {
+        Self { source, filter }
+    }
+
+    pub fn filter(&self) -> &Biquad {
+        &self.filter
+    }
+
+    pub fn filter_mut(&mut self) -> &mut Biquad {
+        &mut self.filter
+    }
+
+    pub fn source_mut(&mut self) -> &mut dyn AudioSource {
+        self.source.as_mut()
+    }
+}
+
+impl AudioSource for FilteredSource {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        self.source.render(output, frames, sample_rate);
+        self.filter.process(output, frames, sample_rate);
+    }
+
+    fn is_active(&self) -> bool {
+        self.source.is_active()
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        self.source.prefault(memory);
+        self.filter.prefault(memory);
+    }
+}
@@ -0,0 +1,265 @@
+//! Reduced-precision (Q15) render path for low-power targets, enabled with the
+//! `fixed-point` feature.
+//!
+//! Samples are `i16` in Q15 (-1.0..1.0 maps to -32768..32767); gains are Q15
+//! too, so unity is just below 1.0. Oscillators run an integer phase
+//! accumulator over i16 tables, and the router mixes sources into its buses
+//! in Q15, saturating instead of clipping later (see `FixedMix`). Conversion
+//! to f32 happens once per bus, ahead of its inserts.
+
+use std::sync::OnceLock;
+
+use crate::rt_processing::metering::{BlockLevel, LevelMeter};
+use crate::rt_processing::routing::AudioSource;
+use crate::rt_processing::waveform::tables::WaveformType;
+
+/// Log2 of the Q15 table length
+const TABLE_BITS: u32 = 11;
+const TABLE_SIZE: usize = 1 << TABLE_BITS;
+/// Phase bits below the table index, used for interpolation
+const FRACTION_BITS: u32 = 32 - TABLE_BITS;
+
+#[inline]
+pub fn to_q15(sample: f32) -> i16 {
+    (sample * 32768.0).round().clamp(-32768.0, 32767.0) as i16
+}
+
+#[inline]
+pub fn from_q15(sample: i16) -> f32 {
+    sample as f32 / 32768.0
+}
+
+/// Q15 multiply, rounded
+#[inline]
+pub fn mul_q15(a: i16, b: i16) -> i16 {
+    ((a as i32 * b as i32 + (1 << 14)) >> 15).clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+/// Add `source * gains` into `dest` sample by sample, saturating at full
+/// scale
+#[inline]
+pub fn mix_q15(dest: &mut [i16], source: &[i16], gains: &[i16]) {
+    for ((d, &s), &gain) in dest.iter_mut().zip(source).zip(gains) {
+        *d = d.saturating_add(mul_q15(s, gain));
+    }
+}
+
+/// Mono to stereo with Q15 gains per side (e.g. from a pan law)
+#[inline]
+pub fn mix_q15_stereo(left: &mut [i16], right: &mut [i16], source: &[i16], gains: (&[i16], &[i16])) {
+    mix_q15(left, source, gains.0);
+    mix_q15(right, source, gains.1);
+}
+
+pub fn block_to_f32(source: &[i16], dest: &mut [f32]) {
+    for (d, &s) in dest.iter_mut().zip(source) {
+        *d = from_q15(s);
+    }
+}
+
+pub fn block_to_q15(source: &[f32], dest: &mut [i16]) {
+    for (d, &s) in dest.iter_mut().zip(source) {
+        *d = to_q15(s);
+    }
+}
+
+/// Q15 router mix, see `Router::set_fixed_point_mix`: sources are added
+/// into an i16 accumulator per bus channel, which is converted and added to
+/// the f32 bus once all sources are in.
+pub(crate) struct FixedMix {
+    // one channel of the source being mixed, and its per-sample gains (per
+    // side when panning mono to stereo)
+    source: Vec<i16>,
+    gains: [Vec<i16>; 2],
+    // [bus][channel][frame]
+    buses: Vec<Vec<Vec<i16>>>,
+}
+
+impl FixedMix {
+    pub(crate) fn new(channels: usize, num_buses: usize, max_frames: usize) -> Self {
+        Self {
+            source: vec![0; max_frames],
+            gains: [vec![0; max_frames], vec![0; max_frames]],
+            buses: vec![vec![vec![0; max_frames]; channels]; num_buses],
+        }
+    }
+
+    /// Mix `views` into `bus` at the per-sample `gains`; with two channels
+    /// the source is mono and `pans` gives each side's gain. Records the
+    /// levels heard on `meter`.
+    pub(crate) fn mix(
+        &mut self,
+        bus: usize,
+        views: &[&mut [f32]],
+        gains: &[f32],
+        pans: (&[f32], &[f32]),
+        meter: &LevelMeter,
+    ) {
+        let frames = gains.len();
+        let source = &mut self.source[..frames];
+        let [first, second] = &mut self.gains;
+        let (first, second) = (&mut first[..frames], &mut second[..frames]);
+        let channels = &mut self.buses[bus];
+        if let [left, right] = &mut channels[..] {
+            let (mut left_level, mut right_level) = (BlockLevel::default(), BlockLevel::default());
+            for i in 0..frames {
+                let (l, r) = (gains[i] * pans.0[i], gains[i] * pans.1[i]);
+                first[i] = to_q15(l);
+                second[i] = to_q15(r);
+                left_level.add(views[0][i] * l);
+                right_level.add(views[0][i] * r);
+            }
+            block_to_q15(&views[0][..frames], source);
+            mix_q15_stereo(&mut left[..frames], &mut right[..frames], source, (first, second));
+            meter.record(0, left_level, frames);
+            meter.record(1, right_level, frames);
+        } else {
+            block_to_q15(gains, first);
+            for (ch, (accumulator, view)) in channels.iter_mut().zip(views).enumerate() {
+                let mut level = BlockLevel::default();
+                view[..frames].iter().zip(gains).for_each(|(s, gain)| level.add(s * gain));
+                block_to_q15(&view[..frames], source);
+                mix_q15(&mut accumulator[..frames], source, first);
+                meter.record(ch, level, frames);
+            }
+        }
+    }
+
+    /// Add what was mixed into each bus to `buses` ([bus][channel][frame])
+    /// and start over
+    pub(crate) fn finish(&mut self, buses: &mut [Vec<Vec<f32>>], frames: usize) {
+        for (bus, accumulators) in buses.iter_mut().zip(&mut self.buses) {
+            for (channel, accumulator) in bus.iter_mut().zip(accumulators.iter_mut()) {
+                for (sample, q15) in channel[..frames].iter_mut().zip(&mut accumulator[..frames]) {
+                    *sample += from_q15(std::mem::take(q15));
+                }
+            }
+        }
+    }
+}
+
+type Tables = [Vec<i16>; 4];
+
+static TABLES: OnceLock<Tables> = OnceLock::new();
+
+/// Q15 copies of the waveform tables, built from the f32 ones by the first
+/// `FixedOscillator::new`
+fn tables() -> &'static Tables {
+    TABLES.get_or_init(|| {
+        let build = |waveform: WaveformType| {
+            (0..TABLE_SIZE).map(|i| to_q15(waveform.interpolated_sample(i as f32 / TABLE_SIZE as f32))).collect()
+        };
+        [
+            build(WaveformType::Sine),
+            build(WaveformType::Triangle),
+            build(WaveformType::Sawtooth),
+            build(WaveformType::Square),
+        ]
+    })
+}
+
+/// Integer counterpart of `Oscillator`: a 32-bit phase accumulator that wraps
+/// for free, a Q15 table lookup with linear interpolation, and a Q15 amplitude.
+/// The phase increment is only recomputed when the frequency or sample rate
+/// changes, so the per-sample path is integer-only.
+pub struct FixedOscillator {
+    waveform: WaveformType,
+    frequency: f32,
+    amplitude: i16,
+    phase: u32,
+    increment: u32,
+    sample_rate: f32,
+    tables: &'static Tables,
+    // scratch for `render`, `max_frames` long
+    block: Vec<i16>,
+}
+
+impl FixedOscillator {
+    /// `max_frames` sizes the scratch `render` converts through; longer
+    /// blocks are rendered in pieces
+    pub fn new(waveform: WaveformType, frequency: f32, max_frames: usize) -> Self {
+        Self {
+            waveform,
+            frequency,
+            amplitude: to_q15(0.5),
+            phase: 0,
+            increment: 0,
+            sample_rate: 0.0,
+            // built here, so rendering never does
+            tables: tables(),
+            block: vec![0; max_frames.max(1)],
+        }
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.set_amplitude(amplitude);
+        self
+    }
+
+    pub fn set_amplitude(&mut self, amplitude: f32) {
+        self.amplitude = to_q15(amplitude.clamp(0.0, 1.0));
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency;
+        // recompute the increment on the next sample
+        self.sample_rate = 0.0;
+    }
+
+    pub fn set_waveform(&mut self, waveform: WaveformType) {
+        self.waveform = waveform;
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    pub fn waveform(&self) -> WaveformType {
+        self.waveform
+    }
+
+    #[inline]
+    fn prepare(&mut self, sample_rate: f32) {
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            let cycles = (self.frequency / sample_rate).clamp(0.0, 0.5) as f64;
+            self.increment = (cycles * 4_294_967_296.0) as u32;
+        }
+    }
+
+    /// Fill `output` with Q15 samples
+    pub fn render_q15(&mut self, output: &mut [i16], sample_rate: f32) {
+        self.prepare(sample_rate);
+        let table = match self.waveform {
+            WaveformType::Sine => &self.tables[0],
+            WaveformType::Triangle => &self.tables[1],
+            WaveformType::Sawtooth => &self.tables[2],
+            WaveformType::Square => &self.tables[3],
+        };
+        for out in output.iter_mut() {
+            let index = (self.phase >> FRACTION_BITS) as usize;
+            let fraction = ((self.phase >> (FRACTION_BITS - 15)) & 0x7FFF) as i32;
+            let a = table[index] as i32;
+            let b = table[(index + 1) & (TABLE_SIZE - 1)] as i32;
+            let sample = (a + (((b - a) * fraction) >> 15)) as i16;
+            *out = mul_q15(sample, self.amplitude);
+            self.phase = self.phase.wrapping_add(self.increment);
+        }
+    }
+}
+
+impl AudioSource for FixedOscillator {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        let mut block = std::mem::take(&mut self.block);
+        let mut done = 0;
+        while done < frames {
+            let len = block.len().min(frames - done);
+            self.render_q15(&mut block[..len], sample_rate);
+            for out in output.iter_mut() {
+                block_to_f32(&block[..len], &mut out[done..done + len]);
+            }
+            done += len;
+        }
+        self.block = block;
+    }
+}
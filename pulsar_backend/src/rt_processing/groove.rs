@@ -0,0 +1,124 @@
+//! Swing and groove templates: per-step timing and velocity offsets applied
+//! to sequenced events.
+//!
+//! A groove repeats every `len` steps, counted from the transport's first
+//! step. Swing is the simplest groove: every second step played late.
+//! Templates can be extracted from a played part (`Groove::from_hits`) and
+//! stored alongside a project, as they serialize.
+
+use serde::{Deserialize, Serialize};
+
+/// Swing that leaves the timing straight, in percent
+pub const STRAIGHT_SWING: f32 = 50.0;
+/// Heaviest swing: the off-beat moved halfway to the next step
+pub const MAX_SWING: f32 = 75.0;
+/// Furthest a step moves, in steps, so steps never swap places
+pub const MAX_OFFSET: f32 = 0.5;
+
+/// Timing and velocity offsets per step
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Groove {
+    /// Offset of each step in steps, positive late
+    offsets: Vec<f32>,
+    /// Velocity scale of each step; empty leaves velocities alone
+    #[serde(default)]
+    velocities: Vec<f32>,
+    /// How much of the template applies, 0.0 (straight) to 1.0
+    #[serde(default = "full")]
+    amount: f32,
+}
+
+fn full() -> f32 {
+    1.0
+}
+
+impl Default for Groove {
+    /// Straight
+    fn default() -> Self {
+        Self { offsets: Vec::new(), velocities: Vec::new(), amount: 1.0 }
+    }
+}
+
+impl Groove {
+    /// Template from per-step offsets (in steps, within `MAX_OFFSET`) and
+    /// velocity scales; the two may differ in length, and each repeats on
+    /// its own
+    pub fn new(offsets: Vec<f32>, velocities: Vec<f32>) -> Self {
+        Self {
+            offsets: offsets.into_iter().map(|offset| offset.clamp(-MAX_OFFSET, MAX_OFFSET)).collect(),
+            velocities: velocities.into_iter().map(|scale| scale.max(0.0)).collect(),
+            amount: 1.0,
+        }
+    }
+
+    /// Swing as a percentage: how much of each pair of steps the first one
+    /// takes. 50% is straight, 66.7% a triplet feel, up to `MAX_SWING`.
+    pub fn swing(percent: f32) -> Self {
+        let percent = percent.clamp(STRAIGHT_SWING, MAX_SWING);
+        Self::new(vec![0.0, percent / STRAIGHT_SWING - 1.0], Vec::new())
+    }
+
+    /// Extract a template from a played part: `(position, velocity)` hits,
+    /// positions in steps from the start. Each hit is heard as its nearest
+    /// step; the template holds the average offset of every step of a
+    /// `steps`-long cycle, and velocities relative to the loudest step.
+    /// Steps nobody played stay straight.
+    pub fn from_hits(hits: &[(f64, f32)], steps: usize) -> Self {
+        let steps = steps.max(1);
+        let mut offsets = vec![(0.0, 0.0, 0usize); steps];
+        for &(position, velocity) in hits {
+            let nearest = position.round();
+            let (offset, level, count) = &mut offsets[nearest.rem_euclid(steps as f64) as usize];
+            *offset += position - nearest;
+            *level += velocity.max(0.0) as f64;
+            *count += 1;
+        }
+        let averages: Vec<(f64, f64)> = offsets
+            .iter()
+            .map(|&(offset, level, count)| if count == 0 { (0.0, 0.0) } else { (offset / count as f64, level / count as f64) })
+            .collect();
+        let loudest = averages.iter().map(|&(_, level)| level).fold(0.0, f64::max);
+        let velocities = if loudest > 0.0 {
+            averages.iter().map(|&(_, level)| if level > 0.0 { (level / loudest) as f32 } else { 1.0 }).collect()
+        } else {
+            Vec::new()
+        };
+        Self::new(averages.iter().map(|&(offset, _)| offset as f32).collect(), velocities)
+    }
+
+    pub fn with_amount(mut self, amount: f32) -> Self {
+        self.set_amount(amount);
+        self
+    }
+
+    pub fn set_amount(&mut self, amount: f32) {
+        self.amount = amount.clamp(0.0, 1.0);
+    }
+
+    pub fn amount(&self) -> f32 {
+        self.amount
+    }
+
+    /// Steps before the template repeats
+    pub fn len(&self) -> usize {
+        self.offsets.len().max(self.velocities.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Offset of step `step` in steps, positive late
+    pub fn offset(&self, step: u64) -> f32 {
+        cycle(&self.offsets, step).map_or(0.0, |offset| offset.clamp(-MAX_OFFSET, MAX_OFFSET) * self.amount)
+    }
+
+    /// Velocity scale of step `step`
+    pub fn velocity(&self, step: u64) -> f32 {
+        cycle(&self.velocities, step).map_or(1.0, |scale| 1.0 + (scale.max(0.0) - 1.0) * self.amount)
+    }
+}
+
+fn cycle(values: &[f32], step: u64) -> Option<f32> {
+    (!values.is_empty()).then(|| values[(step % values.len() as u64) as usize])
+}
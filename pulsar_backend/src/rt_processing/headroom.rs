@@ -0,0 +1,142 @@
+//! Headroom protection for summing buses ("too many sources").
+//!
+//! Generative patches can spawn voices faster than anyone rides the faders,
+//! and the sum creeps into clipping. An auto-trim on a bus follows its summed
+//! peak level slowly, so a hit or a fill doesn't move it, and when the level
+//! stays above the headroom target it either turns the bus down to match or
+//! only warns with `EngineEvent::HeadroomExceeded`.
+//!
+//! The trim also counts the sources playing into the bus: voices add up
+//! roughly by the square root of their number, so when more join, the level
+//! estimate is raised by that much at once rather than waiting for the
+//! detector to see them.
+
+use crate::rt_processing::events::EngineEvent;
+
+/// Fastest the trim turns a bus down, in dB per second
+const CUT_DB_PER_SECOND: f32 = 6.0;
+/// Fastest it gives the level back
+const RECOVER_DB_PER_SECOND: f32 = 2.0;
+/// How far under the target the level must fall before the next warning
+const WARNING_HYSTERESIS_DB: f32 = 1.0;
+
+/// What an auto-trim does when a bus runs out of headroom
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum AutoTrimMode {
+    /// Turn the bus down, and warn
+    #[default]
+    Trim,
+    /// Only warn
+    Warn,
+}
+
+/// Auto-trim settings of one bus, see `Router::set_auto_trim`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AutoTrimConfig {
+    /// Highest summed peak level the bus should run at, in dBFS
+    pub headroom_db: f32,
+    /// Time constant of the level detector in seconds: how long the bus must
+    /// stay over before the trim has fully caught up
+    pub response: f32,
+    /// Most the trim turns the bus down, in dB
+    pub max_trim_db: f32,
+    pub mode: AutoTrimMode,
+}
+
+impl Default for AutoTrimConfig {
+    fn default() -> Self {
+        Self { headroom_db: -6.0, response: 2.0, max_trim_db: 24.0, mode: AutoTrimMode::Trim }
+    }
+}
+
+/// Audio-thread state of one bus's auto-trim
+pub(crate) struct AutoTrim {
+    config: AutoTrimConfig,
+    bus: usize,
+    /// Slow peak level of the sum, before the trim
+    envelope: f32,
+    /// Sources that played into the bus last block
+    sources: usize,
+    trim_db: f32,
+    /// Warned, and not back under the target since
+    over: bool,
+}
+
+impl AutoTrim {
+    pub(crate) fn new(bus: usize, config: AutoTrimConfig) -> Self {
+        Self { config, bus, envelope: 0.0, sources: 0, trim_db: 0.0, over: false }
+    }
+
+    pub(crate) fn config(&self) -> AutoTrimConfig {
+        self.config
+    }
+
+    /// Takes effect from the next block, from the current trim
+    pub(crate) fn set_config(&mut self, config: AutoTrimConfig) {
+        self.config = config;
+    }
+
+    /// Gain applied now, in dB (0 or below)
+    pub(crate) fn trim_db(&self) -> f32 {
+        self.trim_db
+    }
+
+    /// Measure and trim the first `frames` frames of the bus, summed from
+    /// `sources` sources. Returns the warning to publish when the bus has just
+    /// gone over its target. RT-safe.
+    pub(crate) fn process(
+        &mut self,
+        buffers: &mut [Vec<f32>],
+        frames: usize,
+        sources: usize,
+        sample_rate: f32,
+        frame: u64,
+    ) -> Option<EngineEvent> {
+        if frames == 0 {
+            return None;
+        }
+        let peak = buffers.iter().flat_map(|samples| &samples[..frames]).fold(0.0f32, |peak, s| peak.max(s.abs()));
+        if sources > self.sources && self.sources > 0 {
+            self.envelope *= (sources as f32 / self.sources as f32).sqrt();
+        }
+        self.sources = sources;
+
+        let seconds = frames as f32 / sample_rate;
+        let coeff = 1.0 - (-seconds / self.config.response.max(1e-3)).exp();
+        self.envelope += coeff * (peak - self.envelope);
+        if self.envelope < f32::MIN_POSITIVE {
+            self.envelope = 0.0;
+        }
+        let level_db = 20.0 * self.envelope.max(1e-6).log10();
+        let excess = level_db - self.config.headroom_db;
+
+        let mut warning = None;
+        if excess > 0.0 && !self.over {
+            self.over = true;
+            warning = Some(EngineEvent::HeadroomExceeded { frame, bus: self.bus, level_db, sources });
+        } else if excess < -WARNING_HYSTERESIS_DB {
+            self.over = false;
+        }
+
+        let target = match self.config.mode {
+            AutoTrimMode::Trim => (-excess).clamp(-self.config.max_trim_db.max(0.0), 0.0),
+            AutoTrimMode::Warn => 0.0,
+        };
+        let from = self.trim_db;
+        self.trim_db = if target < from {
+            (from - CUT_DB_PER_SECOND * seconds).max(target)
+        } else {
+            (from + RECOVER_DB_PER_SECOND * seconds).min(target)
+        };
+        if from != 0.0 || self.trim_db != 0.0 {
+            let (from, to) = (10f32.powf(from / 20.0), 10f32.powf(self.trim_db / 20.0));
+            let step = (to - from) / frames as f32;
+            for samples in buffers.iter_mut() {
+                for (i, s) in samples[..frames].iter_mut().enumerate() {
+                    *s *= from + step * (i + 1) as f32;
+                }
+            }
+        }
+        warning
+    }
+}
@@ -0,0 +1,297 @@
+//! EBU R128 loudness of the router output (ITU-R BS.1770 K-weighting and
+//! gating).
+//!
+//! The audio thread K-weights the master output as it is metered, leaving the
+//! output itself alone, and queues the energy of every 100 ms. A
+//! `LoudnessMeter` on the control side keeps the history and works out the
+//! momentary (400 ms), short-term (3 s) and gated integrated loudness, so the
+//! audio thread only runs two biquads per channel.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crossbeam::queue::ArrayQueue;
+
+use crate::offline::downmix::ChannelLayout;
+use crate::rt_processing::filters::{BiquadCoefficients, BiquadState};
+use crate::rt_processing::metering::Meters;
+use crate::rt_processing::routing::MAX_ROUTER_CHANNELS;
+
+/// Blocks below this never count towards the integrated loudness
+pub const ABSOLUTE_GATE_LUFS: f32 = -70.0;
+/// Blocks this far below the ungated loudness don't count either
+pub const RELATIVE_GATE_LU: f32 = -10.0;
+
+/// Measuring step: the 400 ms blocks overlap by 75%
+const STEP_SECONDS: f32 = 0.1;
+const MOMENTARY_STEPS: usize = 4;
+const SHORT_TERM_STEPS: usize = 30;
+/// Steps waiting for the meter: a minute
+const LOUDNESS_QUEUE_CAPACITY: usize = 600;
+
+/// Loudness in LUFS of a weighted mean square
+fn lufs(mean_square: f64) -> f32 {
+    if mean_square > 0.0 { (-0.691 + 10.0 * mean_square.log10()) as f32 } else { f32::NEG_INFINITY }
+}
+
+/// Mean square with loudness `lufs`
+fn mean_square(lufs: f32) -> f64 {
+    10f64.powf((lufs as f64 + 0.691) / 10.0)
+}
+
+/// The two BS.1770 K-weighting stages, a high shelf modelling the head and
+/// the RLB high-pass, at any sample rate
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct KWeighting {
+    shelf: BiquadCoefficients,
+    high_pass: BiquadCoefficients,
+}
+
+impl KWeighting {
+    pub(crate) fn new(sample_rate: f32) -> Self {
+        let sample_rate = sample_rate as f64;
+
+        let k = (std::f64::consts::PI * 1681.974450955533 / sample_rate).tan();
+        let q = 0.7071752369554196;
+        let vh = 10f64.powf(3.999843853973347 / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = BiquadCoefficients {
+            b0: ((vh + vb * k / q + k * k) / a0) as f32,
+            b1: (2.0 * (k * k - vh) / a0) as f32,
+            b2: ((vh - vb * k / q + k * k) / a0) as f32,
+            a1: (2.0 * (k * k - 1.0) / a0) as f32,
+            a2: ((1.0 - k / q + k * k) / a0) as f32,
+        };
+
+        let k = (std::f64::consts::PI * 38.13547087602444 / sample_rate).tan();
+        let q = 0.5003270373238773;
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = BiquadCoefficients {
+            b0: 1.0,
+            b1: -2.0,
+            b2: 1.0,
+            a1: (2.0 * (k * k - 1.0) / a0) as f32,
+            a2: ((1.0 - k / q + k * k) / a0) as f32,
+        };
+        Self { shelf, high_pass }
+    }
+}
+
+/// K-weighted energy per channel over one step
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct LoudnessStep {
+    energy: [f64; MAX_ROUTER_CHANNELS],
+    frames: u32,
+}
+
+pub(crate) type LoudnessQueue = ArrayQueue<LoudnessStep>;
+
+pub(crate) fn loudness_queue() -> Arc<LoudnessQueue> {
+    Arc::new(ArrayQueue::new(LOUDNESS_QUEUE_CAPACITY))
+}
+
+/// Audio-thread side: K-weights blocks and queues finished steps
+pub(crate) struct LoudnessProbe {
+    weighting: KWeighting,
+    filters: Vec<[BiquadState; 2]>,
+    step: LoudnessStep,
+    step_frames: u32,
+    queue: Arc<LoudnessQueue>,
+}
+
+impl LoudnessProbe {
+    pub(crate) fn new(channels: usize, sample_rate: f32, queue: Arc<LoudnessQueue>) -> Self {
+        Self {
+            weighting: KWeighting::new(sample_rate),
+            filters: vec![[BiquadState::default(); 2]; channels.min(MAX_ROUTER_CHANNELS)],
+            step: LoudnessStep::default(),
+            step_frames: ((sample_rate * STEP_SECONDS).round() as u32).max(1),
+            queue,
+        }
+    }
+
+    /// Add the first `frames` frames of planar `buffers`. RT-safe.
+    pub(crate) fn measure(&mut self, buffers: &[Vec<f32>], frames: usize) {
+        let KWeighting { shelf, high_pass } = self.weighting;
+        let mut start = 0;
+        while start < frames {
+            let end = frames.min(start + (self.step_frames - self.step.frames) as usize);
+            for ((buffer, filters), energy) in buffers.iter().zip(&mut self.filters).zip(&mut self.step.energy) {
+                let [first, second] = filters;
+                *energy += buffer[start..end]
+                    .iter()
+                    .map(|&x| {
+                        let y = second.process(&high_pass, first.process(&shelf, x));
+                        (y * y) as f64
+                    })
+                    .sum::<f64>();
+            }
+            self.step.frames += (end - start) as u32;
+            start = end;
+            if self.step.frames == self.step_frames {
+                // a meter this far behind loses the step
+                let _ = self.queue.push(self.step);
+                self.step = LoudnessStep::default();
+            }
+        }
+    }
+}
+
+/// Loudness readings in LUFS; `f32::NEG_INFINITY` until there's something
+/// to measure
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LoudnessSnapshot {
+    /// Last 400 ms
+    pub momentary: f32,
+    /// Last 3 s
+    pub short_term: f32,
+    /// Everything since the meter started or was reset, gated
+    pub integrated: f32,
+    /// Loudest full 400 ms block
+    pub max_momentary: f32,
+    /// Loudest full 3 s window
+    pub max_short_term: f32,
+}
+
+impl Default for LoudnessSnapshot {
+    fn default() -> Self {
+        Self {
+            momentary: f32::NEG_INFINITY,
+            short_term: f32::NEG_INFINITY,
+            integrated: f32::NEG_INFINITY,
+            max_momentary: f32::NEG_INFINITY,
+            max_short_term: f32::NEG_INFINITY,
+        }
+    }
+}
+
+/// Control side of the loudness measurement of a router's output. Reading
+/// takes the queued steps: one meter per router.
+///
+/// The integrated loudness keeps one value per 100 ms measured until `reset`.
+pub struct LoudnessMeter {
+    queue: Arc<LoudnessQueue>,
+    weights: Vec<f32>,
+    /// Weighted energy and frames of the latest steps, oldest first
+    recent: VecDeque<(f64, u32)>,
+    /// Mean square of every 400 ms block, for the integrated loudness
+    blocks: Vec<f64>,
+    snapshot: LoudnessSnapshot,
+}
+
+impl LoudnessMeter {
+    /// Meter the output of the router `meters` belongs to, from now on
+    pub fn new(meters: &Meters) -> Self {
+        let mut meter = Self {
+            queue: Arc::clone(&meters.loudness),
+            weights: default_weights(meters.channels),
+            recent: VecDeque::with_capacity(SHORT_TERM_STEPS),
+            blocks: Vec::new(),
+            snapshot: LoudnessSnapshot::default(),
+        };
+        meter.reset();
+        meter
+    }
+
+    /// Weight of each output channel. The default is BS.1770's for 5.1 (L R
+    /// C LFE Ls Rs: LFE left out, surrounds +1.5 dB) and 1 for every channel
+    /// of other layouts.
+    pub fn with_channel_weights(mut self, weights: Vec<f32>) -> Self {
+        self.set_channel_weights(weights);
+        self
+    }
+
+    /// Applies to what is measured from the next `update` on
+    pub fn set_channel_weights(&mut self, weights: Vec<f32>) {
+        self.weights = weights;
+    }
+
+    pub fn channel_weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    /// Take in everything measured since the last update
+    pub fn update(&mut self) {
+        let mut measured = false;
+        while let Some(step) = self.queue.pop() {
+            let energy = step.energy.iter().zip(&self.weights).map(|(&e, &w)| e * w as f64).sum();
+            if self.recent.len() == SHORT_TERM_STEPS {
+                self.recent.pop_front();
+            }
+            self.recent.push_back((energy, step.frames));
+            measured = true;
+
+            if self.recent.len() >= MOMENTARY_STEPS {
+                let block = self.mean_square(MOMENTARY_STEPS);
+                self.blocks.push(block);
+                self.snapshot.max_momentary = self.snapshot.max_momentary.max(lufs(block));
+            }
+            if self.recent.len() == SHORT_TERM_STEPS {
+                let window = lufs(self.mean_square(SHORT_TERM_STEPS));
+                self.snapshot.max_short_term = self.snapshot.max_short_term.max(window);
+            }
+        }
+        if measured {
+            self.snapshot.momentary = lufs(self.mean_square(MOMENTARY_STEPS));
+            self.snapshot.short_term = lufs(self.mean_square(SHORT_TERM_STEPS));
+            self.snapshot.integrated = self.integrated();
+        }
+    }
+
+    /// Readings as of the last `update`
+    pub fn snapshot(&self) -> LoudnessSnapshot {
+        self.snapshot
+    }
+
+    /// `update`, then the readings
+    pub fn read(&mut self) -> LoudnessSnapshot {
+        self.update();
+        self.snapshot()
+    }
+
+    /// Start over: drop the history, including what's queued
+    pub fn reset(&mut self) {
+        while self.queue.pop().is_some() {}
+        self.recent.clear();
+        self.blocks.clear();
+        self.snapshot = LoudnessSnapshot::default();
+    }
+
+    /// Mean square over the latest `steps` steps, or as many as there are
+    fn mean_square(&self, steps: usize) -> f64 {
+        let (energy, frames) = self
+            .recent
+            .iter()
+            .rev()
+            .take(steps)
+            .fold((0.0, 0u64), |(energy, frames), &(e, f)| (energy + e, frames + f as u64));
+        if frames == 0 { 0.0 } else { energy / frames as f64 }
+    }
+
+    /// Mean of the blocks passing the absolute gate and the relative gate
+    /// that follows from them
+    fn integrated(&self) -> f32 {
+        let gated_mean = |gate: f64| {
+            let (sum, count) = self
+                .blocks
+                .iter()
+                .filter(|&&block| block > gate)
+                .fold((0.0, 0usize), |(sum, count), &block| (sum + block, count + 1));
+            (count > 0).then(|| sum / count as f64)
+        };
+        let absolute = mean_square(ABSOLUTE_GATE_LUFS);
+        let Some(ungated) = gated_mean(absolute) else {
+            return f32::NEG_INFINITY;
+        };
+        let relative = mean_square(lufs(ungated) + RELATIVE_GATE_LU);
+        gated_mean(absolute.max(relative)).map_or(f32::NEG_INFINITY, lufs)
+    }
+}
+
+fn default_weights(channels: usize) -> Vec<f32> {
+    match ChannelLayout::from_channels(channels) {
+        Some(ChannelLayout::Surround51) => vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41],
+        _ => vec![1.0; channels],
+    }
+}
@@ -0,0 +1,285 @@
+//! Peak and RMS meters for the router's sources, buses and master output.
+//!
+//! The router adds every block to lock-free per-channel accumulators: the peak
+//! is the highest since the last read, the RMS covers every frame since the
+//! last read, so a GUI polling at 30 Hz misses nothing between polls. The read
+//! side (`MeterReader`) takes the accumulators and applies the ballistics, so
+//! the audio thread never does more than a few atomic updates per channel.
+//!
+//! Reading resets the accumulators: one reader per router.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use spin::Mutex;
+
+use crate::rt_processing::loudness::{self, LoudnessQueue};
+use crate::rt_processing::routing::SourceId;
+
+/// Peak and energy of one channel over a block, before it's added to a meter
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct BlockLevel {
+    peak: f32,
+    energy: f32,
+}
+
+impl BlockLevel {
+    #[inline]
+    pub(crate) fn add(&mut self, sample: f32) {
+        self.peak = self.peak.max(sample.abs());
+        self.energy += sample * sample;
+    }
+}
+
+/// Accumulators of one channel
+#[derive(Default)]
+struct ChannelLevel {
+    /// f32 bits; non-negative floats order like their bits, so `fetch_max` works
+    peak: AtomicU32,
+    /// Sum of squares (f32 bits, high half) and frames (low half) since the last read
+    energy: AtomicU64,
+}
+
+/// Levels of one meter point, written by the audio thread
+pub struct LevelMeter {
+    channels: Vec<ChannelLevel>,
+}
+
+impl LevelMeter {
+    pub fn new(channels: usize) -> Self {
+        Self { channels: (0..channels).map(|_| ChannelLevel::default()).collect() }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Add one channel's block. RT-safe.
+    pub(crate) fn record(&self, channel: usize, level: BlockLevel, frames: usize) {
+        let Some(meter) = self.channels.get(channel) else {
+            return;
+        };
+        // a NaN would hold the peak forever
+        if !level.peak.is_nan() {
+            meter.peak.fetch_max(level.peak.to_bits(), Ordering::Relaxed);
+        }
+        let _ = meter.energy.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |packed| {
+            let energy = f32::from_bits((packed >> 32) as u32) + level.energy;
+            let counted = (packed as u32).saturating_add(frames as u32);
+            Some((energy.to_bits() as u64) << 32 | counted as u64)
+        });
+    }
+
+    /// Add the first `frames` frames of planar `buffers`. RT-safe.
+    pub fn measure(&self, buffers: &[Vec<f32>], frames: usize) {
+        for (channel, buffer) in buffers.iter().take(self.channels.len()).enumerate() {
+            let mut level = BlockLevel::default();
+            buffer[..frames].iter().for_each(|&s| level.add(s));
+            self.record(channel, level, frames);
+        }
+    }
+
+    /// Peak and RMS since the last call, linear; silence when nothing was added
+    pub fn take(&self) -> MeterLevels {
+        let channels = self.channels();
+        let mut levels = MeterLevels { peak: Vec::with_capacity(channels), rms: Vec::with_capacity(channels) };
+        for meter in &self.channels {
+            levels.peak.push(f32::from_bits(meter.peak.swap(0, Ordering::Relaxed)));
+            let packed = meter.energy.swap(0, Ordering::Relaxed);
+            let (energy, frames) = (f32::from_bits((packed >> 32) as u32), packed as u32);
+            levels.rms.push(if frames == 0 { 0.0 } else { (energy / frames as f32).sqrt() });
+        }
+        levels
+    }
+}
+
+/// Per-channel levels of one meter point, linear
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeterLevels {
+    pub peak: Vec<f32>,
+    pub rms: Vec<f32>,
+}
+
+impl MeterLevels {
+    /// Highest peak over the channels
+    pub fn max_peak(&self) -> f32 {
+        self.peak.iter().fold(0.0, |max, &p| max.max(p))
+    }
+
+    /// Highest RMS over the channels
+    pub fn max_rms(&self) -> f32 {
+        self.rms.iter().fold(0.0, |max, &r| max.max(r))
+    }
+
+    /// Move towards `target` as the ballistics allow over `elapsed`
+    fn follow(&mut self, target: &MeterLevels, ballistics: MeterBallistics, elapsed: Duration) {
+        let (attack, release) = (ballistics.coefficient(true, elapsed), ballistics.coefficient(false, elapsed));
+        for (shown, levels) in [(&mut self.peak, &target.peak), (&mut self.rms, &target.rms)] {
+            shown.resize(levels.len(), 0.0);
+            for (value, &target) in shown.iter_mut().zip(levels) {
+                let coefficient = if target > *value { attack } else { release };
+                *value += (target - *value) * coefficient;
+            }
+        }
+    }
+}
+
+/// Levels of every meter point at one read
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeterSnapshot {
+    /// The router output, after the master inserts
+    pub master: MeterLevels,
+    /// Each bus after its inserts and fade, before its return gain; bus 0
+    /// holds the sources routed straight to master
+    pub buses: Vec<MeterLevels>,
+    /// Each source after gain and pan, as it enters its bus; silent while
+    /// muted or soloed out
+    pub sources: Vec<(SourceId, MeterLevels)>,
+}
+
+impl MeterSnapshot {
+    pub fn source(&self, id: SourceId) -> Option<&MeterLevels> {
+        self.sources.iter().find(|(source, _)| *source == id).map(|(_, levels)| levels)
+    }
+}
+
+/// How fast the shown levels follow the measured ones: time constants in ms
+/// for rising (attack) and falling (release). 0 follows instantly.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeterBallistics {
+    pub attack_ms: f32,
+    pub release_ms: f32,
+}
+
+impl Default for MeterBallistics {
+    /// Peaks show at once and fall back over about a second
+    fn default() -> Self {
+        Self { attack_ms: 0.0, release_ms: 300.0 }
+    }
+}
+
+impl MeterBallistics {
+    /// No smoothing: every read shows what was measured since the last one
+    pub const INSTANT: Self = Self { attack_ms: 0.0, release_ms: 0.0 };
+
+    fn coefficient(self, rising: bool, elapsed: Duration) -> f32 {
+        let time_ms = if rising { self.attack_ms } else { self.release_ms };
+        if time_ms <= 0.0 {
+            return 1.0;
+        }
+        1.0 - (-(elapsed.as_secs_f32() * 1000.0) / time_ms).exp()
+    }
+}
+
+/// Source meters by id, as listed for reading
+type SourceMeters = Vec<(SourceId, Arc<LevelMeter>)>;
+
+/// Every meter point of a router. Cheap to clone; see `Router::meters`.
+#[derive(Clone)]
+pub struct Meters {
+    pub(crate) master: Arc<LevelMeter>,
+    pub(crate) buses: Arc<Vec<LevelMeter>>,
+    // control side only: filled as sources are added, pruned as they go
+    sources: Arc<Mutex<SourceMeters>>,
+    // K-weighted output energy, see `LoudnessMeter`
+    pub(crate) loudness: Arc<LoudnessQueue>,
+    pub(crate) channels: usize,
+}
+
+impl Meters {
+    pub(crate) fn new(channels: usize, num_buses: usize) -> Self {
+        Self {
+            master: Arc::new(LevelMeter::new(channels)),
+            buses: Arc::new((0..num_buses).map(|_| LevelMeter::new(channels)).collect()),
+            sources: Arc::new(Mutex::new(Vec::new())),
+            loudness: loudness::loudness_queue(),
+            channels,
+        }
+    }
+
+    /// Meter for a new source, listed until the router drops it. Not RT-safe.
+    pub(crate) fn add_source(&self, id: SourceId) -> Arc<LevelMeter> {
+        let meter = Arc::new(LevelMeter::new(self.channels));
+        self.sources.lock().push((id, Arc::clone(&meter)));
+        meter
+    }
+
+    /// Raw levels since the last take, without ballistics
+    pub fn take(&self) -> MeterSnapshot {
+        let mut sources = self.sources.lock();
+        // the router holds the other reference while the source is playing
+        sources.retain(|(_, meter)| Arc::strong_count(meter) > 1);
+        MeterSnapshot {
+            master: self.master.take(),
+            buses: self.buses.iter().map(LevelMeter::take).collect(),
+            sources: sources.iter().map(|(id, meter)| (*id, meter.take())).collect(),
+        }
+    }
+}
+
+/// GUI side of the meters: reads them and applies the ballistics
+pub struct MeterReader {
+    meters: Meters,
+    ballistics: MeterBallistics,
+    shown: Option<MeterSnapshot>,
+    last_read: Option<Instant>,
+}
+
+impl MeterReader {
+    pub fn new(meters: Meters) -> Self {
+        Self { meters, ballistics: MeterBallistics::default(), shown: None, last_read: None }
+    }
+
+    pub fn with_ballistics(mut self, ballistics: MeterBallistics) -> Self {
+        self.set_ballistics(ballistics);
+        self
+    }
+
+    pub fn set_ballistics(&mut self, ballistics: MeterBallistics) {
+        self.ballistics = ballistics;
+    }
+
+    pub fn ballistics(&self) -> MeterBallistics {
+        self.ballistics
+    }
+
+    /// Levels to show now
+    pub fn read(&mut self) -> MeterSnapshot {
+        let now = Instant::now();
+        let elapsed = self.last_read.map_or(Duration::ZERO, |last| now - last);
+        self.last_read = Some(now);
+        self.read_after(elapsed)
+    }
+
+    /// `read`, with the time since the previous read given (a UI on a fixed
+    /// frame clock)
+    pub fn read_after(&mut self, elapsed: Duration) -> MeterSnapshot {
+        let measured = self.meters.take();
+        let Some(mut shown) = self.shown.take() else {
+            self.shown = Some(measured.clone());
+            return measured;
+        };
+        shown.master.follow(&measured.master, self.ballistics, elapsed);
+        shown.buses.resize(measured.buses.len(), MeterLevels::default());
+        for (bus, target) in shown.buses.iter_mut().zip(&measured.buses) {
+            bus.follow(target, self.ballistics, elapsed);
+        }
+        // sources that left are dropped; new ones start from their level
+        let sources = measured
+            .sources
+            .into_iter()
+            .map(|(id, target)| match shown.source(id) {
+                Some(previous) => {
+                    let mut levels = previous.clone();
+                    levels.follow(&target, self.ballistics, elapsed);
+                    (id, levels)
+                }
+                None => (id, target),
+            })
+            .collect();
+        shown.sources = sources;
+        self.shown = Some(shown.clone());
+        shown
+    }
+}
@@ -2,4 +2,34 @@ pub mod waveform;
 pub mod voice_renderer;
 pub mod callback;
 pub mod routing;
-pub mod performance;
\ No newline at end of file
+pub mod performance;
+pub mod filters;
+pub mod analysis;
+pub mod notes;
+pub mod chords;
+pub mod events;
+pub mod effects;
+pub mod fft;
+pub mod modulation;
+pub mod voices;
+pub mod sequencer;
+pub mod groove;
+pub mod turing;
+pub mod voice_bank;
+pub mod control;
+pub mod alloc_check;
+pub mod reclaim;
+pub mod prefault;
+pub mod denormal;
+pub mod black_box;
+pub(crate) mod trace;
+#[cfg(feature = "fixed-point")]
+pub mod fixed;
+pub mod resampler;
+pub mod metering;
+pub mod loudness;
+pub mod headroom;
+pub mod scheduler;
+pub mod transport;
+#[cfg(feature = "chaos")]
+pub mod chaos;
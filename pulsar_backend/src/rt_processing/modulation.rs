@@ -0,0 +1,295 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crossbeam::atomic::AtomicCell;
+use crossbeam::queue::ArrayQueue;
+
+use crate::rt_processing::routing::AudioSource;
+use crate::rt_processing::turing::TuringModulator;
+use crate::rt_processing::waveform::envelopes::ADSREnvelope;
+use crate::rt_processing::waveform::oscillators::LFO;
+
+/// Samples between modulator updates at control rate
+pub const DEFAULT_CONTROL_INTERVAL: usize = 32;
+
+/// Control-rate tier for modulators (LFOs, mod envelopes).
+///
+/// The modulator is evaluated once every `interval` samples and the consumer
+/// gets a per-sample linear ramp towards each new value, so modulation stays
+/// smooth at a fraction of the cost. The ramp runs one interval behind the
+/// modulator. An interval of 1 is plain audio rate.
+#[derive(Copy, Clone, Debug)]
+pub struct ControlRamp {
+    interval: usize,
+    remaining: usize,
+    value: f32,
+    step: f32,
+    primed: bool,
+}
+
+impl ControlRamp {
+    pub fn new(interval: usize) -> Self {
+        Self { interval: interval.max(1), remaining: 0, value: 0.0, step: 0.0, primed: false }
+    }
+
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    /// Takes effect at the next update
+    pub fn set_interval(&mut self, interval: usize) {
+        self.interval = interval.max(1);
+    }
+
+    /// Next interpolated value. When an update is due, `update` is called with
+    /// the number of samples the modulator should advance and returns its new
+    /// value.
+    #[inline]
+    pub fn next(&mut self, update: impl FnOnce(usize) -> f32) -> f32 {
+        if self.remaining == 0 {
+            let target = update(self.interval);
+            if !self.primed {
+                // nothing to ramp from yet
+                self.value = target;
+                self.primed = true;
+            }
+            self.step = (target - self.value) / self.interval as f32;
+            self.remaining = self.interval;
+        }
+        self.remaining -= 1;
+        self.value += self.step;
+        self.value
+    }
+
+    /// Last value handed out
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// Forget the ramp; the next call jumps straight to the modulator's value
+    pub fn reset(&mut self) {
+        self.remaining = 0;
+        self.step = 0.0;
+        self.primed = false;
+    }
+}
+
+/// A modulated parameter at the end of the last block
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ModulationReading {
+    /// Value set by the user, automation or the project
+    pub base: f32,
+    /// Sum of every modulation source applied on top of `base`
+    pub offset: f32,
+}
+
+impl ModulationReading {
+    /// The value the node actually used
+    pub fn value(&self) -> f32 {
+        self.base + self.offset
+    }
+}
+
+/// Block-rate view of a node's modulated parameters, for drawing modulation
+/// rings around knobs.
+///
+/// The node publishes one reading per parameter at the end of every block;
+/// clones share the same slots, so a UI keeps a clone and polls `snapshot`.
+/// Publishing is a plain atomic store and never blocks the audio thread.
+#[derive(Clone)]
+pub struct ModulationMonitor {
+    names: Arc<[&'static str]>,
+    readings: Arc<[AtomicCell<ModulationReading>]>,
+}
+
+impl ModulationMonitor {
+    /// One slot per name, in order; the index is what `publish` takes
+    pub fn new(names: &[&'static str]) -> Self {
+        Self {
+            names: names.into(),
+            readings: names.iter().map(|_| AtomicCell::new(ModulationReading::default())).collect(),
+        }
+    }
+
+    /// Store the reading for the parameter at `index`; out-of-range indices are ignored
+    pub fn publish(&self, index: usize, base: f32, offset: f32) {
+        if let Some(reading) = self.readings.get(index) {
+            reading.store(ModulationReading { base, offset });
+        }
+    }
+
+    pub fn names(&self) -> &[&'static str] {
+        &self.names
+    }
+
+    pub fn reading(&self, name: &str) -> Option<ModulationReading> {
+        let index = self.names.iter().position(|n| *n == name)?;
+        Some(self.readings[index].load())
+    }
+
+    /// Parameter `name` as a modulation source, for a `ModulationProbe`
+    pub fn param(&self, name: &str) -> Option<MonitoredParam> {
+        let index = self.names.iter().position(|n| *n == name)?;
+        Some(MonitoredParam { monitor: self.clone(), index })
+    }
+
+    /// Every parameter with its latest reading
+    pub fn snapshot(&self) -> Vec<(&'static str, ModulationReading)> {
+        self.names.iter().zip(self.readings.iter()).map(|(name, reading)| (*name, reading.load())).collect()
+    }
+
+    /// Back to zero readings, e.g. after the node was reset
+    pub fn clear(&self) {
+        for reading in self.readings.iter() {
+            reading.store(ModulationReading::default());
+        }
+    }
+}
+
+/// Anything producing a modulation signal at control rate (see `ControlRamp`)
+pub trait ModulationSource: Send + Sync {
+    /// The current value, then move `frames` samples ahead
+    fn advance(&mut self, frames: usize, sample_rate: f32) -> f32;
+}
+
+impl ModulationSource for LFO {
+    fn advance(&mut self, frames: usize, sample_rate: f32) -> f32 {
+        LFO::advance(self, frames, sample_rate)
+    }
+}
+
+impl ModulationSource for TuringModulator {
+    fn advance(&mut self, frames: usize, sample_rate: f32) -> f32 {
+        TuringModulator::advance(self, frames, sample_rate)
+    }
+}
+
+impl ModulationSource for ADSREnvelope {
+    fn advance(&mut self, frames: usize, sample_rate: f32) -> f32 {
+        let value = self.get_value(sample_rate);
+        for _ in 1..frames {
+            self.get_value(sample_rate);
+        }
+        value
+    }
+}
+
+/// The modulation a node applied to one of its parameters, as published to
+/// its `ModulationMonitor`: the offset on top of the base value, held for a
+/// block
+#[derive(Clone)]
+pub struct MonitoredParam {
+    monitor: ModulationMonitor,
+    index: usize,
+}
+
+impl MonitoredParam {
+    pub fn name(&self) -> &'static str {
+        self.monitor.names[self.index]
+    }
+}
+
+impl ModulationSource for MonitoredParam {
+    fn advance(&mut self, _frames: usize, _sample_rate: f32) -> f32 {
+        self.monitor.readings[self.index].load().offset
+    }
+}
+
+/// Recent modulation values for drawing, one per control update.
+///
+/// Clones share the same ring, so the audio side keeps one (in a
+/// `ModulationProbe`) and a UI another. When the UI falls behind, the oldest
+/// values are overwritten and counted.
+#[derive(Clone)]
+pub struct ScopeTap {
+    values: Arc<ArrayQueue<f32>>,
+    overwritten: Arc<AtomicU64>,
+}
+
+impl ScopeTap {
+    pub fn new(capacity: usize) -> Self {
+        Self { values: Arc::new(ArrayQueue::new(capacity.max(1))), overwritten: Arc::new(AtomicU64::new(0)) }
+    }
+
+    /// Realtime-safe
+    #[inline]
+    pub fn push(&self, value: f32) {
+        if self.values.force_push(value).is_some() {
+            self.overwritten.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Move every value held into `out`, oldest first; returns how many
+    pub fn drain_into(&self, out: &mut Vec<f32>) -> usize {
+        let before = out.len();
+        while let Some(value) = self.values.pop() {
+            out.push(value);
+        }
+        out.len() - before
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Values lost because nobody drained them in time
+    pub fn overwritten(&self) -> u64 {
+        self.overwritten.load(Ordering::Relaxed)
+    }
+}
+
+/// Debug source that plays a modulation signal as audio, on every channel,
+/// so it can be heard or seen on any bus while building a patch. The signal
+/// is ramped exactly as a node would see it, and each control update also
+/// goes to the `ScopeTap`, if any.
+///
+/// Modulation is mostly slow or DC: keep the source's gain low on a bus you
+/// listen to. To only watch it, send it to a bus whose return is 0.
+pub struct ModulationProbe {
+    source: Box<dyn ModulationSource>,
+    control: ControlRamp,
+    scope: Option<ScopeTap>,
+}
+
+impl ModulationProbe {
+    pub fn new(source: impl ModulationSource + 'static) -> Self {
+        Self { source: Box::new(source), control: ControlRamp::new(DEFAULT_CONTROL_INTERVAL), scope: None }
+    }
+
+    /// Samples between updates, as the node using the source would have it
+    pub fn with_interval(mut self, interval: usize) -> Self {
+        self.control.set_interval(interval);
+        self
+    }
+
+    pub fn with_scope(mut self, scope: ScopeTap) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    pub fn scope(&self) -> Option<&ScopeTap> {
+        self.scope.as_ref()
+    }
+}
+
+impl AudioSource for ModulationProbe {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        let Self { source, control, scope } = self;
+        for i in 0..frames {
+            let value = control.next(|n| {
+                let value = source.advance(n, sample_rate);
+                if let Some(scope) = scope {
+                    scope.push(value);
+                }
+                value
+            });
+            for channel in output.iter_mut() {
+                channel[i] = value;
+            }
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use crossbeam::channel::Sender;
+use crossbeam::queue::ArrayQueue;
+
+/// Note event consumed by voice allocation.
+/// `note` is a MIDI note number, `velocity` is normalized (0.0 to 1.0).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NoteEvent {
+    NoteOn { note: u8, velocity: f32 },
+    NoteOff { note: u8 },
+}
+
+impl NoteEvent {
+    pub fn note(&self) -> u8 {
+        match *self {
+            NoteEvent::NoteOn { note, .. } | NoteEvent::NoteOff { note } => note,
+        }
+    }
+}
+
+/// Destination for note events produced on the audio thread.
+/// Implementations must not block.
+pub trait NoteSink: Send {
+    fn send_note(&mut self, event: NoteEvent);
+}
+
+impl NoteSink for Sender<NoteEvent> {
+    fn send_note(&mut self, event: NoteEvent) {
+        // drop the event rather than block if the consumer falls behind
+        let _ = self.try_send(event);
+    }
+}
+
+impl NoteSink for Vec<NoteEvent> {
+    fn send_note(&mut self, event: NoteEvent) {
+        self.push(event);
+    }
+}
+
+/// Host-assigned identity of one note, so later events address that note
+/// rather than its key. A host must not reuse an id while the note sounds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NoteId(pub u32);
+
+/// Per-note expression dimensions
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NoteExpression {
+    /// Semitones (fractional) from the note's key, -120.0 to 120.0
+    PitchOffset,
+    /// 0.0 to 1.0
+    Pressure,
+    /// 0.0 to 1.0
+    Brightness,
+}
+
+impl NoteExpression {
+    pub const COUNT: usize = 3;
+    pub const ALL: [NoteExpression; Self::COUNT] =
+        [NoteExpression::PitchOffset, NoteExpression::Pressure, NoteExpression::Brightness];
+
+    /// Value a note starts with
+    pub fn neutral(self) -> f32 {
+        match self {
+            NoteExpression::PitchOffset | NoteExpression::Pressure => 0.0,
+            NoteExpression::Brightness => 0.5,
+        }
+    }
+
+    /// `value` limited to this dimension's range; NaN gives the neutral value
+    pub fn clamp(self, value: f32) -> f32 {
+        if value.is_nan() {
+            return self.neutral();
+        }
+        match self {
+            NoteExpression::PitchOffset => value.clamp(-120.0, 120.0),
+            NoteExpression::Pressure | NoteExpression::Brightness => value.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Note event addressed by `NoteId`, independent of MIDI framing: any number
+/// of notes may share a key, and each carries its own expression.
+/// `velocity` is normalized (0.0 to 1.0).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ExpressiveNoteEvent {
+    NoteOn { id: NoteId, note: u8, velocity: f32 },
+    NoteOff { id: NoteId },
+    Expression { id: NoteId, expression: NoteExpression, value: f32 },
+}
+
+impl ExpressiveNoteEvent {
+    pub fn id(&self) -> NoteId {
+        match *self {
+            ExpressiveNoteEvent::NoteOn { id, .. }
+            | ExpressiveNoteEvent::NoteOff { id }
+            | ExpressiveNoteEvent::Expression { id, .. } => id,
+        }
+    }
+}
+
+/// Lock-free queue carrying `ExpressiveNoteEvent`s from control threads to
+/// the audio thread.
+///
+/// Clones share the queue. When it is full, new events are dropped and
+/// counted rather than blocking the sender.
+#[derive(Clone)]
+pub struct NoteQueue {
+    events: Arc<ArrayQueue<ExpressiveNoteEvent>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl NoteQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self { events: Arc::new(ArrayQueue::new(capacity.max(1))), dropped: Arc::new(AtomicU64::new(0)) }
+    }
+
+    /// Queue `event`; false if the queue was full and it was dropped
+    pub fn send(&self, event: ExpressiveNoteEvent) -> bool {
+        if self.events.push(event).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    pub fn note_on(&self, id: NoteId, note: u8, velocity: f32) -> bool {
+        self.send(ExpressiveNoteEvent::NoteOn { id, note, velocity })
+    }
+
+    pub fn note_off(&self, id: NoteId) -> bool {
+        self.send(ExpressiveNoteEvent::NoteOff { id })
+    }
+
+    pub fn expression(&self, id: NoteId, expression: NoteExpression, value: f32) -> bool {
+        self.send(ExpressiveNoteEvent::Expression { id, expression, value })
+    }
+
+    /// Oldest pending event. Never blocks; safe on the audio thread.
+    pub fn pop(&self) -> Option<ExpressiveNoteEvent> {
+        self.events.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Events dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Convert a MIDI note number to frequency in Hz (A4 = 69 = 440 Hz)
+#[inline]
+pub fn note_to_frequency(note: f32) -> f32 {
+    440.0 * 2f32.powf((note - 69.0) / 12.0)
+}
+
+/// Scale modes, as semitone offsets from the root
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScaleMode {
+    Major,
+    NaturalMinor,
+    HarmonicMinor,
+    Dorian,
+    Mixolydian,
+    MajorPentatonic,
+    MinorPentatonic,
+    Chromatic,
+}
+
+impl ScaleMode {
+    pub const ALL: [ScaleMode; 8] = [
+        ScaleMode::Major,
+        ScaleMode::NaturalMinor,
+        ScaleMode::HarmonicMinor,
+        ScaleMode::Dorian,
+        ScaleMode::Mixolydian,
+        ScaleMode::MajorPentatonic,
+        ScaleMode::MinorPentatonic,
+        ScaleMode::Chromatic,
+    ];
+
+    pub fn intervals(self) -> &'static [u8] {
+        match self {
+            ScaleMode::Major => &[0, 2, 4, 5, 7, 9, 11],
+            ScaleMode::NaturalMinor => &[0, 2, 3, 5, 7, 8, 10],
+            ScaleMode::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            ScaleMode::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            ScaleMode::Mixolydian => &[0, 2, 4, 5, 7, 9, 10],
+            ScaleMode::MajorPentatonic => &[0, 2, 4, 7, 9],
+            ScaleMode::MinorPentatonic => &[0, 3, 5, 7, 10],
+            ScaleMode::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+        }
+    }
+}
+
+/// Key + mode. `root` is a pitch class (0 = C .. 11 = B).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Scale {
+    pub root: u8,
+    pub mode: ScaleMode,
+}
+
+impl Scale {
+    pub fn new(root: u8, mode: ScaleMode) -> Self {
+        Self { root: root % 12, mode }
+    }
+
+    /// Scale degree index of the scale note at or just below `note`, with its octave
+    fn degree_of(&self, note: i32) -> (i32, usize) {
+        let intervals = self.mode.intervals();
+        let relative = note - self.root as i32;
+        let octave = relative.div_euclid(12);
+        let pc = relative.rem_euclid(12) as u8;
+        let degree = intervals.iter().rposition(|&i| i <= pc).unwrap_or(0);
+        (octave, degree)
+    }
+
+    /// Semitone offset that moves `note` by `degrees` scale steps (e.g. +2 = a third
+    /// above in a diatonic scale). Out-of-scale notes are treated as the scale note
+    /// just below them, and the offset keeps their distance from it.
+    pub fn diatonic_shift(&self, note: u8, degrees: i32) -> i32 {
+        let intervals = self.mode.intervals();
+        let len = intervals.len() as i32;
+        let (octave, degree) = self.degree_of(note as i32);
+        let base = self.root as i32 + octave * 12 + intervals[degree] as i32;
+        let target_index = degree as i32 + degrees;
+        let target = self.root as i32
+            + (octave + target_index.div_euclid(len)) * 12
+            + intervals[target_index.rem_euclid(len) as usize] as i32;
+        target - base
+    }
+
+    /// Whether `note`'s pitch class is in the scale
+    pub fn contains(&self, note: u8) -> bool {
+        self.contains_pitch(note as i32)
+    }
+
+    fn contains_pitch(&self, note: i32) -> bool {
+        let pc = (note - self.root as i32).rem_euclid(12) as u8;
+        self.mode.intervals().contains(&pc)
+    }
+
+    /// The scale note nearest `note`, the lower one on a tie. Stays within
+    /// the MIDI note range.
+    pub fn quantize(&self, note: u8) -> u8 {
+        let note = note.min(127) as i32;
+        (0..12)
+            .flat_map(|distance| [note - distance, note + distance])
+            .find(|&n| (0..128).contains(&n) && self.contains_pitch(n))
+            .unwrap_or(note) as u8
+    }
+
+    /// The scale note nearest a fractional pitch (in MIDI note numbers), the
+    /// lower one on a tie
+    pub fn quantize_pitch(&self, pitch: f32) -> f32 {
+        if !pitch.is_finite() {
+            return pitch;
+        }
+        let floor = pitch.floor() as i32;
+        (floor - 11..=floor + 12)
+            .filter(|&n| self.contains_pitch(n))
+            .map(|n| n as f32)
+            .min_by(|a, b| (a - pitch).abs().total_cmp(&(b - pitch).abs()))
+            .unwrap_or(pitch)
+    }
+}
+
+// bit set in a `SharedScale` when a scale is chosen
+const SCALE_ON: u32 = 1 << 16;
+
+/// A scale that generators share and the UI changes while they run.
+///
+/// Clones share the setting. Reading and setting are single atomic
+/// operations, so both are safe on the audio thread; `None` leaves pitches
+/// as they are.
+#[derive(Clone, Debug, Default)]
+pub struct SharedScale {
+    // root in bits 0-3, mode index in bits 8-15, SCALE_ON when set
+    packed: Arc<AtomicU32>,
+}
+
+impl SharedScale {
+    pub fn new(scale: Option<Scale>) -> Self {
+        let shared = Self::default();
+        shared.set(scale);
+        shared
+    }
+
+    pub fn set(&self, scale: Option<Scale>) {
+        let packed = scale.map_or(0, |scale| {
+            let mode = ScaleMode::ALL.iter().position(|&m| m == scale.mode).unwrap_or(0) as u32;
+            SCALE_ON | mode << 8 | (scale.root % 12) as u32
+        });
+        self.packed.store(packed, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> Option<Scale> {
+        let packed = self.packed.load(Ordering::Relaxed);
+        if packed & SCALE_ON == 0 {
+            return None;
+        }
+        let mode = ScaleMode::ALL.get((packed >> 8 & 0xFF) as usize).copied().unwrap_or(ScaleMode::Chromatic);
+        Some(Scale::new((packed & 0x0F) as u8, mode))
+    }
+
+    /// `note` constrained to the current scale, if any
+    pub fn quantize(&self, note: u8) -> u8 {
+        self.get().map_or(note, |scale| scale.quantize(note))
+    }
+
+    /// `pitch` constrained to the current scale, if any
+    pub fn quantize_pitch(&self, pitch: f32) -> f32 {
+        self.get().map_or(pitch, |scale| scale.quantize_pitch(pitch))
+    }
+}
@@ -1,7 +1,97 @@
+use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
+use spin::Mutex;
 use quanta::{Clock, Instant as QuantaInstant};
 
+use crate::rt_processing::trace;
+
+/// EMA alpha for monitors the engine creates itself (one per stream)
+pub const DEFAULT_EMA_ALPHA: f64 = 0.1;
+/// Default length of the windowed statistics, in seconds
+pub const DEFAULT_WINDOW_SECONDS: usize = 10;
+/// Longest window a monitor keeps
+pub const MAX_WINDOW_SECONDS: usize = 300;
+/// Warm-up of monitors the engine creates itself (one per stream)
+pub const DEFAULT_WARMUP: Duration = Duration::from_millis(250);
+
+/// Callback timing over one second of wall-clock time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SecondStats {
+    pub callbacks: u64,
+    pub avg_callback_nanos: f64,
+    pub max_callback_nanos: u64,
+    pub avg_load_percent: f64,
+    pub max_load_percent: f64,
+}
+
+/// Callback timing over the last `seconds` seconds, including the current one
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowStats {
+    pub seconds: usize,
+    pub callbacks: u64,
+    pub avg_callback_nanos: f64,
+    pub max_callback_nanos: u64,
+    pub avg_load_percent: f64,
+    pub max_load_percent: f64,
+    /// One entry per second, oldest first; seconds without callbacks are zero
+    pub per_second: Vec<SecondStats>,
+}
+
+/// Callbacks of the warm-up window, kept out of every other statistic
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WarmupStats {
+    /// Whether the window is still open
+    pub active: bool,
+    pub callbacks: u64,
+    pub avg_callback_nanos: f64,
+    pub max_callback_nanos: u64,
+    pub underruns: u64,
+    pub overruns: u64,
+}
+
+/// One second of callback timings, reused round-robin
+struct SecondBucket {
+    /// Second (since monitor creation) the bucket holds, +1; 0 = never used
+    second: AtomicU64,
+    callbacks: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl SecondBucket {
+    fn new() -> Self {
+        Self {
+            second: AtomicU64::new(0),
+            callbacks: AtomicU64::new(0),
+            total_nanos: AtomicU64::new(0),
+            max_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Add a callback duration for `second`, recycling the bucket if it held an older one
+    #[inline(always)]
+    fn record(&self, second: u64, nanos: u64) {
+        let tag = second + 1;
+        if self.second.load(Ordering::Relaxed) != tag {
+            self.callbacks.store(0, Ordering::Relaxed);
+            self.total_nanos.store(0, Ordering::Relaxed);
+            self.max_nanos.store(0, Ordering::Relaxed);
+            self.second.store(tag, Ordering::Release);
+        }
+        self.callbacks.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        self.second.store(0, Ordering::Relaxed);
+        self.callbacks.store(0, Ordering::Relaxed);
+        self.total_nanos.store(0, Ordering::Relaxed);
+        self.max_nanos.store(0, Ordering::Relaxed);
+    }
+}
+
 /// Snapshot of metrics suitable for logging/telemetry (non-RT).
 #[derive(Debug, Clone)]
 pub struct PerformanceSnapshot {
@@ -22,7 +112,12 @@ pub struct PerformanceSnapshot {
     /// Time when snapshot was taken.
     pub timestamp: Instant,
     pub expected_callback_nanos: f64,
+    /// Load from the EMA: `ema_callback_nanos / expected_callback_nanos`
     pub avg_load_percent: f64,
+    /// Fixed-window statistics, which show spikes the EMA smooths away
+    pub window: WindowStats,
+    /// The callbacks right after the stream started, which none of the above include
+    pub warmup: WarmupStats,
 }
 
 /// Real-time-safe performance monitor.
@@ -32,6 +127,18 @@ pub struct PerformanceSnapshot {
 ///
 /// Snapshotting (via `snapshot`) reads atomics and computes a `PerformanceSnapshot`
 /// on the non-real-time thread; calling `snapshot` is not real-time safe.
+///
+/// The first callbacks after a stream starts are slow for reasons that say
+/// nothing about the graph (page faults, lazy initialization in drivers and
+/// plugins). With a warm-up window (`with_warmup`), callbacks and xruns inside
+/// it are reported apart, in `PerformanceSnapshot::warmup`, and leave the counts
+/// of xruns, min/max, EMA and windowed statistics untouched. Anything that
+/// reacts to load should hold off while `is_warming_up`. `restart_warmup`
+/// reopens the window when the stream starts again.
+///
+/// Besides the EMA, callback timings are kept in per-second buckets for the
+/// last `window_seconds` seconds (see `with_window_seconds`), so a snapshot
+/// also reports plain averages and maxima over a fixed window.
 pub struct PerformanceMonitor {
     // high-resolution clock used on RT path (quanta)
     clock: Clock,
@@ -54,6 +161,20 @@ pub struct PerformanceMonitor {
     /// EMA alpha used for updating exponential moving average on RT thread.
     ema_alpha: f64,
 
+    // windowed stats: `window_seconds` + 1 buckets, so the current second
+    // never overwrites the oldest one still reported
+    started: QuantaInstant,
+    window_seconds: usize,
+    buckets: Box<[SecondBucket]>,
+
+    // warm-up: callbacks in the window, those still to come, and their stats
+    warmup_callbacks: u64,
+    warmup_remaining: AtomicU64,
+    warmup_count: AtomicU64,
+    warmup_total_nanos: AtomicU64,
+    warmup_max_nanos: AtomicU64,
+    warmup_underruns: AtomicU64,
+    warmup_overruns: AtomicU64,
 }
 
 impl PerformanceMonitor {
@@ -63,8 +184,10 @@ impl PerformanceMonitor {
     /// callback timing. Typical small values around 0.05..0.2 work well.
     pub fn new(frame_size: usize, sample_rate: f32, ema_alpha: f64) -> Self {
         assert!(ema_alpha > 0.0 && ema_alpha <= 1.0);
+        let clock = Clock::new();
         Self {
-            clock: Clock::new(),
+            started: clock.now(),
+            clock,
             frame_size,
             sample_rate,
             frames_processed: AtomicU64::new(0),
@@ -75,9 +198,81 @@ impl PerformanceMonitor {
             max_callback_nanos: AtomicU64::new(0),
             ema_callback_bits: AtomicU64::new(0u64),
             ema_alpha,
+            window_seconds: DEFAULT_WINDOW_SECONDS,
+            buckets: (0..=DEFAULT_WINDOW_SECONDS).map(|_| SecondBucket::new()).collect(),
+            warmup_callbacks: 0,
+            warmup_remaining: AtomicU64::new(0),
+            warmup_count: AtomicU64::new(0),
+            warmup_total_nanos: AtomicU64::new(0),
+            warmup_max_nanos: AtomicU64::new(0),
+            warmup_underruns: AtomicU64::new(0),
+            warmup_overruns: AtomicU64::new(0),
         }
     }
 
+    /// Report windowed statistics over the last `seconds` seconds (1..=`MAX_WINDOW_SECONDS`)
+    pub fn with_window_seconds(mut self, seconds: usize) -> Self {
+        self.set_window_seconds(seconds);
+        self
+    }
+
+    /// Change the window length; clears the windowed statistics (non-RT)
+    pub fn set_window_seconds(&mut self, seconds: usize) {
+        self.window_seconds = seconds.clamp(1, MAX_WINDOW_SECONDS);
+        self.buckets = (0..=self.window_seconds).map(|_| SecondBucket::new()).collect();
+    }
+
+    pub fn window_seconds(&self) -> usize {
+        self.window_seconds
+    }
+
+    /// Change how fast the EMA follows new timings (0 < alpha <= 1) (non-RT)
+    pub fn set_ema_alpha(&mut self, ema_alpha: f64) {
+        assert!(ema_alpha > 0.0 && ema_alpha <= 1.0);
+        self.ema_alpha = ema_alpha;
+    }
+
+    pub fn ema_alpha(&self) -> f64 {
+        self.ema_alpha
+    }
+
+    /// Keep the callbacks of the first `warmup` of audio out of the statistics
+    /// (see the type docs); zero turns warm-up off
+    pub fn with_warmup(mut self, warmup: Duration) -> Self {
+        self.set_warmup(warmup);
+        self
+    }
+
+    /// Change the warm-up length and reopen the window (non-RT)
+    pub fn set_warmup(&mut self, warmup: Duration) {
+        let period = self.frame_size as f64 / self.sample_rate as f64;
+        self.warmup_callbacks = if period > 0.0 { (warmup.as_secs_f64() / period).ceil() as u64 } else { 0 };
+        self.restart_warmup();
+    }
+
+    /// Callbacks in the warm-up window
+    pub fn warmup_callbacks(&self) -> u64 {
+        self.warmup_callbacks
+    }
+
+    /// Whether the callbacks seen now are still excluded as warm-up
+    #[inline(always)]
+    pub fn is_warming_up(&self) -> bool {
+        self.warmup_remaining.load(Ordering::Relaxed) > 0
+    }
+
+    /// Open the warm-up window again, e.g. when the stream restarts.
+    /// Real-time safe.
+    #[inline(always)]
+    pub fn restart_warmup(&self) {
+        self.warmup_remaining.store(self.warmup_callbacks, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    fn second_at(&self, now: QuantaInstant) -> u64 {
+        now.saturating_duration_since(self.started).as_secs()
+    }
+
     // ---------------------------
     // RT-safe small operations
     // ---------------------------
@@ -100,21 +295,44 @@ impl PerformanceMonitor {
     /// Real-time safe.
     #[inline(always)]
     pub fn increment_underrun_count(&self) {
-        self.underrun_count.fetch_add(1, Ordering::Relaxed);
+        let count = if self.is_warming_up() { &self.warmup_underruns } else { &self.underrun_count };
+        count.fetch_add(1, Ordering::Relaxed);
+        trace::xrun("output", false, 0);
     }
 
     /// Increment overrun count by 1 (report overrun).
     /// Real-time safe.
     #[inline(always)]
     pub fn increment_overrun_count(&self) {
-        self.overrun_count.fetch_add(1, Ordering::Relaxed);
+        let count = if self.is_warming_up() { &self.warmup_overruns } else { &self.overrun_count };
+        count.fetch_add(1, Ordering::Relaxed);
+        trace::xrun("output", true, 0);
     }
 
     /// Record a callback duration in nanoseconds.
     ///
-    /// Real-time safe — uses atomics only. Updates min, max, and EMA.
+    /// Real-time safe — uses atomics only. Updates min, max, EMA and the current
+    /// second's bucket, or only the warm-up stats while warming up.
     #[inline(always)]
     pub fn record_callback_duration_nanos(&self, nanos: u64) {
+        self.record_at(nanos, self.clock.now());
+    }
+
+    #[inline(always)]
+    fn record_at(&self, nanos: u64, now: QuantaInstant) {
+        // one audio thread per monitor, so a plain load/store is enough
+        let remaining = self.warmup_remaining.load(Ordering::Relaxed);
+        if remaining > 0 {
+            self.warmup_remaining.store(remaining - 1, Ordering::Relaxed);
+            self.warmup_count.fetch_add(1, Ordering::Relaxed);
+            self.warmup_total_nanos.fetch_add(nanos, Ordering::Relaxed);
+            self.warmup_max_nanos.fetch_max(nanos, Ordering::Relaxed);
+            return;
+        }
+
+        let second = self.second_at(now);
+        self.buckets[second as usize % self.buckets.len()].record(second, nanos);
+
         // update min (atomic min loop)
         let mut prev_min = self.min_callback_nanos.load(Ordering::Relaxed);
         while nanos < prev_min {
@@ -198,7 +416,7 @@ impl PerformanceMonitor {
     /// are collected from zero.
     ///
     /// This function is NOT real-time safe and should be called from a non-RT thread.
-    pub fn snapshot(&mut self, reset_peaks: bool) -> PerformanceSnapshot {
+    pub fn snapshot(&self, reset_peaks: bool) -> PerformanceSnapshot {
         // read counters
         let frames_processed = self.frames_processed.load(Ordering::Relaxed);
         let callback_count = self.callback_count.load(Ordering::Relaxed);
@@ -232,6 +450,20 @@ impl PerformanceMonitor {
             self.ema_callback_bits.store(0u64, Ordering::Relaxed);
         }
 
+        let window = self.window_stats(expected_callback_nanos);
+        let warmup_count = self.warmup_count.load(Ordering::Relaxed);
+        let warmup = WarmupStats {
+            active: self.is_warming_up(),
+            callbacks: warmup_count,
+            avg_callback_nanos: self.warmup_total_nanos.load(Ordering::Relaxed) as f64 / warmup_count.max(1) as f64,
+            max_callback_nanos: self.warmup_max_nanos.load(Ordering::Relaxed),
+            underruns: self.warmup_underruns.load(Ordering::Relaxed),
+            overruns: self.warmup_overruns.load(Ordering::Relaxed),
+        };
+        if reset_peaks {
+            self.buckets.iter().for_each(SecondBucket::reset);
+        }
+
         PerformanceSnapshot {
             frames_processed,
             callback_count,
@@ -242,11 +474,60 @@ impl PerformanceMonitor {
             ema_callback_nanos: ema_f,
             expected_callback_nanos,
             avg_load_percent,
+            window,
+            warmup,
             timestamp: Instant::now(),
         }
     }
 
-    /// Reset *all* counters (non-RT). Useful when starting a new session or test.
+    fn window_stats(&self, expected_callback_nanos: f64) -> WindowStats {
+        let load =
+            |nanos: f64| if expected_callback_nanos > 0.0 { nanos / expected_callback_nanos * 100.0 } else { 0.0 };
+        let current = self.second_at(self.clock.now());
+        let first = (current + 1).saturating_sub(self.window_seconds as u64);
+
+        let per_second: Vec<SecondStats> = (first..=current)
+            .map(|second| {
+                let bucket = &self.buckets[second as usize % self.buckets.len()];
+                if bucket.second.load(Ordering::Acquire) != second + 1 {
+                    return SecondStats {
+                        callbacks: 0,
+                        avg_callback_nanos: 0.0,
+                        max_callback_nanos: 0,
+                        avg_load_percent: 0.0,
+                        max_load_percent: 0.0,
+                    };
+                }
+                let callbacks = bucket.callbacks.load(Ordering::Relaxed);
+                let max = bucket.max_nanos.load(Ordering::Relaxed);
+                let avg = bucket.total_nanos.load(Ordering::Relaxed) as f64 / callbacks.max(1) as f64;
+                SecondStats {
+                    callbacks,
+                    avg_callback_nanos: avg,
+                    max_callback_nanos: max,
+                    avg_load_percent: load(avg),
+                    max_load_percent: load(max as f64),
+                }
+            })
+            .collect();
+
+        let callbacks: u64 = per_second.iter().map(|s| s.callbacks).sum();
+        let total: f64 = per_second.iter().map(|s| s.avg_callback_nanos * s.callbacks as f64).sum();
+        let max = per_second.iter().map(|s| s.max_callback_nanos).max().unwrap_or(0);
+        let avg = if callbacks > 0 { total / callbacks as f64 } else { 0.0 };
+        WindowStats {
+            seconds: self.window_seconds,
+            callbacks,
+            avg_callback_nanos: avg,
+            max_callback_nanos: max,
+            avg_load_percent: load(avg),
+            max_load_percent: load(max as f64),
+            per_second,
+        }
+    }
+
+    /// Reset *all* counters (non-RT) and reopen the warm-up window. Useful when
+    /// starting a new session or test.
     pub fn reset_all(&mut self) {
         self.frames_processed.store(0, Ordering::Relaxed);
         self.callback_count.store(0, Ordering::Relaxed);
@@ -255,6 +536,17 @@ impl PerformanceMonitor {
         self.min_callback_nanos.store(u64::MAX, Ordering::Relaxed);
         self.max_callback_nanos.store(0, Ordering::Relaxed);
         self.ema_callback_bits.store(0u64, Ordering::Relaxed);
+        self.buckets.iter().for_each(SecondBucket::reset);
+        for stat in [
+            &self.warmup_count,
+            &self.warmup_total_nanos,
+            &self.warmup_max_nanos,
+            &self.warmup_underruns,
+            &self.warmup_overruns,
+        ] {
+            stat.store(0, Ordering::Relaxed);
+        }
+        self.restart_warmup();
     }
 }
 
@@ -277,6 +569,110 @@ impl<'a> Drop for RealtimeGuard<'a> {
         } else {
             elapsed_ns_u128 as u64
         };
-        self.monitor.record_callback_duration_nanos(elapsed);
+        self.monitor.record_at(elapsed, now);
+    }
+}
+
+/// Identifies a stream's monitor within a `PerformanceAggregator`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StreamId(pub u64);
+
+/// One stream's part of an `AggregateReport`
+#[derive(Debug, Clone)]
+pub struct StreamReport {
+    pub id: StreamId,
+    /// Name given at registration, e.g. the device name
+    pub label: String,
+    pub snapshot: PerformanceSnapshot,
+}
+
+/// Combined performance of every registered stream
+#[derive(Debug, Clone)]
+pub struct AggregateReport {
+    pub streams: Vec<StreamReport>,
+    pub frames_processed: u64,
+    pub callback_count: u64,
+    pub underrun_count: u64,
+    pub overrun_count: u64,
+    /// Highest EMA load of any stream
+    pub max_load_percent: f64,
+    /// Highest single-callback load over the window, of any stream
+    pub peak_load_percent: f64,
+    /// Stream with the highest EMA load
+    pub busiest: Option<StreamId>,
+    /// Some stream is still in its warm-up window, so the loads above don't
+    /// yet reflect it
+    pub warming_up: bool,
+    pub timestamp: Instant,
+}
+
+/// Collects the monitors of several streams (one per device) into one report.
+///
+/// Each stream keeps its own `PerformanceMonitor`, updated from its own
+/// callback; the aggregator only holds `Arc`s to them and reads them in
+/// `report`, so the audio threads never touch it. Registration and reports
+/// are non-RT.
+#[derive(Default)]
+pub struct PerformanceAggregator {
+    monitors: Mutex<Vec<(StreamId, String, Arc<PerformanceMonitor>)>>,
+    next_id: AtomicU64,
+}
+
+impl PerformanceAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start including `monitor` in reports, under `label`
+    pub fn register(&self, label: impl Into<String>, monitor: Arc<PerformanceMonitor>) -> StreamId {
+        let id = StreamId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.monitors.lock().push((id, label.into(), monitor));
+        id
+    }
+
+    /// Returns `false` if `id` isn't registered
+    pub fn unregister(&self, id: StreamId) -> bool {
+        let mut monitors = self.monitors.lock();
+        let before = monitors.len();
+        monitors.retain(|(stream, _, _)| *stream != id);
+        monitors.len() != before
+    }
+
+    pub fn monitor(&self, id: StreamId) -> Option<Arc<PerformanceMonitor>> {
+        self.monitors.lock().iter().find(|(stream, _, _)| *stream == id).map(|(_, _, m)| Arc::clone(m))
+    }
+
+    pub fn len(&self) -> usize {
+        self.monitors.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Snapshot every stream (see `PerformanceMonitor::snapshot`) and combine them
+    pub fn report(&self, reset_peaks: bool) -> AggregateReport {
+        let monitors: Vec<_> = self.monitors.lock().clone();
+        let streams: Vec<StreamReport> = monitors
+            .into_iter()
+            .map(|(id, label, monitor)| StreamReport { id, label, snapshot: monitor.snapshot(reset_peaks) })
+            .collect();
+
+        let busiest = streams
+            .iter()
+            .max_by(|a, b| a.snapshot.avg_load_percent.total_cmp(&b.snapshot.avg_load_percent))
+            .map(|s| s.id);
+        AggregateReport {
+            frames_processed: streams.iter().map(|s| s.snapshot.frames_processed).sum(),
+            callback_count: streams.iter().map(|s| s.snapshot.callback_count).sum(),
+            underrun_count: streams.iter().map(|s| s.snapshot.underrun_count).sum(),
+            overrun_count: streams.iter().map(|s| s.snapshot.overrun_count).sum(),
+            max_load_percent: streams.iter().map(|s| s.snapshot.avg_load_percent).fold(0.0, f64::max),
+            peak_load_percent: streams.iter().map(|s| s.snapshot.window.max_load_percent).fold(0.0, f64::max),
+            busiest,
+            warming_up: streams.iter().any(|s| s.snapshot.warmup.active),
+            streams,
+            timestamp: Instant::now(),
+        }
     }
 }
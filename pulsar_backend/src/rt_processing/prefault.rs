@@ -0,0 +1,117 @@
+//! Pre-touching (and optionally locking) of memory the audio path uses.
+//!
+//! A fresh allocation is mostly address space: the OS maps each page on its
+//! first write, so buffers sized ahead of time still page-fault the first few
+//! times the audio thread uses them. `Engine::start` walks the graph with a
+//! `Prefault` before playback (`EngineConfig::prefault`). The router, sources
+//! and effects pass their pre-allocated buffers to `touch`, which writes every
+//! page, and with `PrefaultMode::Lock` also `mlock`s them so they can't be
+//! swapped out. Sources and effects opt in by overriding `prefault` on their
+//! trait.
+//!
+//! Lock-free queues (`ArrayQueue`) write every slot when created, so they are
+//! resident already. Locked pages stay locked until the allocator returns them
+//! to the OS, even after the buffer is freed.
+
+use std::hint::black_box;
+use std::mem::size_of;
+
+/// Smallest page size of the supported platforms; touching more often is harmless
+const PAGE_SIZE: usize = 4096;
+
+/// What `Engine::start` does with the graph's memory
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PrefaultMode {
+    Off,
+    /// Write every page
+    #[default]
+    Touch,
+    /// Write and `mlock` every page. Needs a large enough `RLIMIT_MEMLOCK`
+    /// (or the privilege to raise it); failures are reported, not fatal.
+    Lock,
+}
+
+/// Outcome of a prefault pass
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PrefaultReport {
+    pub buffers: usize,
+    pub bytes: usize,
+    pub locked_bytes: usize,
+    /// First error `mlock` returned; the buffers after it are still touched
+    pub lock_error: Option<String>,
+}
+
+/// Visitor handed to everything that owns audio-path memory; see the module docs
+pub struct Prefault {
+    lock: bool,
+    channels: usize,
+    max_frames: usize,
+    report: PrefaultReport,
+}
+
+impl Prefault {
+    /// `channels` and `max_frames` are the engine's, for sources and effects
+    /// that size buffers lazily and can allocate them here instead
+    pub fn new(mode: PrefaultMode, channels: usize, max_frames: usize) -> Self {
+        Self { lock: mode == PrefaultMode::Lock, channels, max_frames, report: PrefaultReport::default() }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn max_frames(&self) -> usize {
+        self.max_frames
+    }
+
+    /// Write every page of `buffer` (its contents are unchanged), and lock them
+    /// if asked to
+    pub fn touch<T: Copy>(&mut self, buffer: &mut [T]) {
+        let bytes = std::mem::size_of_val(buffer);
+        if bytes == 0 {
+            return;
+        }
+        let step = (PAGE_SIZE / size_of::<T>().max(1)).max(1);
+        // one write per page-sized stride, plus the last element for the tail page
+        for i in (0..buffer.len()).step_by(step).chain([buffer.len() - 1]) {
+            buffer[i] = black_box(buffer[i]);
+        }
+        self.report.buffers += 1;
+        self.report.bytes += bytes;
+        if self.lock {
+            self.lock(buffer.as_ptr().cast(), bytes);
+        }
+    }
+
+    /// `touch` each buffer
+    pub fn touch_all<T: Copy>(&mut self, buffers: &mut [Vec<T>]) {
+        for buffer in buffers {
+            self.touch(buffer);
+        }
+    }
+
+    pub fn report(&self) -> &PrefaultReport {
+        &self.report
+    }
+
+    pub fn finish(self) -> PrefaultReport {
+        self.report
+    }
+
+    #[cfg(unix)]
+    fn lock(&mut self, start: *const u8, bytes: usize) {
+        // SAFETY: the range is one live allocation; mlock only pins its pages
+        if unsafe { libc::mlock(start.cast(), bytes) } == 0 {
+            self.report.locked_bytes += bytes;
+        } else if self.report.lock_error.is_none() {
+            self.report.lock_error = Some(std::io::Error::last_os_error().to_string());
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn lock(&mut self, _start: *const u8, _bytes: usize) {
+        if self.report.lock_error.is_none() {
+            self.report.lock_error = Some("memory locking is not supported on this platform".into());
+        }
+    }
+}
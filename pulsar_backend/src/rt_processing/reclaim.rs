@@ -0,0 +1,100 @@
+//! Deferred deallocation for the audio thread.
+//!
+//! Dropping a processor or source frees its memory, and the allocator may
+//! lock or make a syscall. Whatever the audio thread is done with goes to a
+//! `Reclaimer` instead, a fixed-size lock-free queue that a background thread
+//! empties and drops from.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam::queue::ArrayQueue;
+
+use crate::rt_processing::callback::AudioCallback;
+use crate::rt_processing::routing::RoutedSource;
+use crate::rt_processing::trace;
+
+/// Objects the global reclaimer holds before retiring more drops them in place
+pub const RECLAIM_CAPACITY: usize = 1024;
+/// How often the global reclaimer's thread empties the queue
+const RECLAIM_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Something the audio thread is done with
+pub enum Retired {
+    Processor(Box<dyn AudioCallback>),
+    Source(Box<RoutedSource>),
+    /// Anything else that's already boxed
+    Other(Box<dyn Send>),
+}
+
+/// Queue of retired objects, dropped by whoever calls `collect`. Cheap to
+/// clone; clones share the queue.
+#[derive(Clone)]
+pub struct Reclaimer {
+    queue: Arc<ArrayQueue<Retired>>,
+    overflows: Arc<AtomicU64>,
+}
+
+impl Reclaimer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            queue: Arc::new(ArrayQueue::new(capacity.max(1))),
+            overflows: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Hand `item` over to be dropped elsewhere. Realtime-safe, except when
+    /// the queue is full: then `item` is dropped right here and counted in
+    /// `overflows`.
+    #[inline]
+    pub fn retire(&self, item: Retired) {
+        if let Err(item) = self.queue.push(item) {
+            self.overflows.fetch_add(1, Ordering::Relaxed);
+            trace::xrun("reclaim", true, 0);
+            drop(item);
+        }
+    }
+
+    /// Drop everything retired so far; returns how many objects that was.
+    /// Call off the audio thread.
+    pub fn collect(&self) -> usize {
+        let mut count = 0;
+        while let Some(item) = self.queue.pop() {
+            drop(item);
+            count += 1;
+        }
+        count
+    }
+
+    /// Objects waiting to be dropped
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Objects dropped by `retire` because the queue was full
+    pub fn overflows(&self) -> u64 {
+        self.overflows.load(Ordering::Relaxed)
+    }
+}
+
+static GLOBAL: OnceLock<Reclaimer> = OnceLock::new();
+
+/// The process-wide reclaimer, emptied every 20 ms by a background thread
+/// started on first use. Routers and callback slots fetch it when they're
+/// created, so the audio thread never starts it.
+pub fn global() -> &'static Reclaimer {
+    GLOBAL.get_or_init(|| {
+        let reclaimer = Reclaimer::new(RECLAIM_CAPACITY);
+        let collector = reclaimer.clone();
+        // without the thread, retired objects wait for an explicit `collect`
+        let _ = thread::Builder::new().name("pulsar-reclaim".into()).spawn(move || {
+            loop {
+                collector.collect();
+                thread::sleep(RECLAIM_INTERVAL);
+            }
+        });
+        reclaimer
+    })
+}
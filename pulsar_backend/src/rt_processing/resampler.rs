@@ -0,0 +1,307 @@
+//! Streaming sample rate conversion for the realtime path.
+//!
+//! `offline::resample` converts whole buffers at a time; this converts an
+//! endless interleaved stream block by block, with every buffer allocated up
+//! front. It's what lets the engine keep running at its own rate when a device
+//! only offers another one, and what lets the router play sources that render
+//! at their own rate (`ResampledSource`).
+
+use std::f64::consts::PI;
+
+use crate::rt_processing::prefault::Prefault;
+use crate::rt_processing::routing::{AudioSource, channel_views};
+
+/// Kernel taps per output sample (zero crossings on each side = TAPS / 2)
+const TAPS: usize = 16;
+const HALF_TAPS: usize = TAPS / 2;
+/// Kernel phases stored; lookups interpolate between neighbours
+const PHASES: usize = 256;
+/// Input frames pulled per refill by default
+pub const DEFAULT_RESAMPLE_BLOCK: usize = 256;
+
+/// Windowed-sinc polyphase resampler for interleaved audio.
+///
+/// Two ways to drive it, depending on which side owns the clock:
+/// - `pull`: fill an output block, asking a source for input as needed
+///   (playback: the device wants N frames, the engine renders at its rate);
+/// - `push`: feed whatever input arrived, receive whatever output it makes
+///   (capture: the device hands over N frames at its rate).
+///
+/// The cutoff follows the lower of the two rates, so downsampling doesn't
+/// alias. Latency is `HALF_TAPS` input frames plus, in pull mode, up to one
+/// block.
+pub struct Resampler {
+    channels: usize,
+    from_rate: u32,
+    to_rate: u32,
+    /// Input frames per output frame
+    step: f64,
+    /// (PHASES + 1) rows of TAPS coefficients
+    kernel: Vec<f32>,
+    /// Interleaved input frames, oldest first
+    buffer: Vec<f32>,
+    frames: usize,
+    /// Position of the next output frame, in input frames from the buffer start
+    position: f64,
+    block: usize,
+    /// Push mode output, handed to the sink in pieces
+    scratch: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(channels: usize, from_rate: u32, to_rate: u32) -> Self {
+        Self::with_block(channels, from_rate, to_rate, DEFAULT_RESAMPLE_BLOCK)
+    }
+
+    /// `block` is the input frames pulled per refill, or the largest piece
+    /// `push` takes in at once
+    pub fn with_block(channels: usize, from_rate: u32, to_rate: u32, block: usize) -> Self {
+        let channels = channels.max(1);
+        let block = block.max(1);
+        let ratio = to_rate.max(1) as f64 / from_rate.max(1) as f64;
+        let out_block = (block as f64 * ratio).ceil() as usize + 1;
+        let mut resampler = Self {
+            channels,
+            from_rate,
+            to_rate,
+            step: 1.0 / ratio,
+            kernel: build_kernel(ratio.min(1.0) * 0.95),
+            buffer: vec![0.0; (TAPS + block) * channels],
+            frames: 0,
+            position: 0.0,
+            block,
+            scratch: vec![0.0; out_block * channels],
+        };
+        resampler.reset();
+        resampler
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn from_rate(&self) -> u32 {
+        self.from_rate
+    }
+
+    pub fn to_rate(&self) -> u32 {
+        self.to_rate
+    }
+
+    /// Input frames of delay through the kernel
+    pub fn latency(&self) -> usize {
+        HALF_TAPS
+    }
+
+    /// Forget all input; the next output starts from silence
+    pub fn reset(&mut self) {
+        // history of silence, so the first output centers on the first input frame
+        self.frames = HALF_TAPS - 1;
+        self.buffer[..self.frames * self.channels].fill(0.0);
+        self.position = (HALF_TAPS - 1) as f64;
+    }
+
+    /// Fill `output` (interleaved, at `to_rate`), calling `source` to render
+    /// blocks of input at `from_rate` whenever more is needed
+    pub fn pull(&mut self, output: &mut [f32], mut source: impl FnMut(&mut [f32])) {
+        let channels = self.channels;
+        for frame in output.chunks_exact_mut(channels) {
+            while self.position as usize + HALF_TAPS >= self.frames {
+                self.compact();
+                let start = self.frames * channels;
+                source(&mut self.buffer[start..start + self.block * channels]);
+                self.frames += self.block;
+            }
+            self.render_frame(frame);
+        }
+    }
+
+    /// Feed interleaved `input` at `from_rate`; `sink` receives the output
+    /// made from it, possibly in several pieces
+    pub fn push(&mut self, input: &[f32], mut sink: impl FnMut(&[f32])) {
+        let channels = self.channels;
+        for chunk in input.chunks(self.block * channels) {
+            self.compact();
+            let start = self.frames * channels;
+            let len = chunk.len() - chunk.len() % channels;
+            self.buffer[start..start + len].copy_from_slice(&chunk[..len]);
+            self.frames += len / channels;
+
+            let mut made = 0;
+            let mut scratch = std::mem::take(&mut self.scratch);
+            while (self.position as usize + HALF_TAPS) < self.frames && made + channels <= scratch.len() {
+                self.render_frame(&mut scratch[made..made + channels]);
+                made += channels;
+            }
+            if made > 0 {
+                sink(&scratch[..made]);
+            }
+            self.scratch = scratch;
+        }
+    }
+
+    /// Drop input frames no output needs anymore
+    fn compact(&mut self) {
+        let first = (self.position as usize + 1).saturating_sub(HALF_TAPS);
+        if first == 0 {
+            return;
+        }
+        let first = first.min(self.frames);
+        self.buffer.copy_within(first * self.channels..self.frames * self.channels, 0);
+        self.frames -= first;
+        self.position -= first as f64;
+    }
+
+    #[inline]
+    fn render_frame(&mut self, frame: &mut [f32]) {
+        let index = self.position as usize;
+        let phase = (self.position - index as f64) * PHASES as f64;
+        let row = phase as usize;
+        let blend = (phase - row as f64) as f32;
+        let a = &self.kernel[row * TAPS..(row + 1) * TAPS];
+        let b = &self.kernel[(row + 1) * TAPS..(row + 2) * TAPS];
+
+        let first = index + 1 - HALF_TAPS;
+        frame.fill(0.0);
+        for k in 0..TAPS {
+            let coeff = a[k] + (b[k] - a[k]) * blend;
+            let input = &self.buffer[(first + k) * self.channels..(first + k + 1) * self.channels];
+            for (out, &x) in frame.iter_mut().zip(input) {
+                *out += x * coeff;
+            }
+        }
+        self.position += self.step;
+    }
+}
+
+/// Routing source rendering at `native_rate`, played at the router's rate.
+///
+/// The router wraps sources declaring another `native_sample_rate` in one of
+/// these; build one directly to pick the block size. The inner source is
+/// pulled `block` frames at a time, so a smaller block lowers the latency
+/// (`Resampler::latency` plus up to one block) at some cost per frame.
+pub struct ResampledSource {
+    source: Box<dyn AudioSource>,
+    resampler: Resampler,
+    /// What the inner source renders into, [channel][frame], one block long
+    input: Vec<Vec<f32>>,
+    /// Interleaved output at the router rate
+    output: Vec<f32>,
+}
+
+impl ResampledSource {
+    /// Every buffer is allocated here; `max_frames` is the most the router
+    /// asks for at once
+    pub fn new(
+        source: Box<dyn AudioSource>,
+        channels: usize,
+        native_rate: u32,
+        sample_rate: u32,
+        max_frames: usize,
+    ) -> Self {
+        Self::with_block(source, channels, native_rate, sample_rate, max_frames, DEFAULT_RESAMPLE_BLOCK)
+    }
+
+    pub fn with_block(
+        source: Box<dyn AudioSource>,
+        channels: usize,
+        native_rate: u32,
+        sample_rate: u32,
+        max_frames: usize,
+        block: usize,
+    ) -> Self {
+        let resampler = Resampler::with_block(channels, native_rate, sample_rate, block);
+        let channels = resampler.channels();
+        Self {
+            source,
+            input: vec![vec![0.0; block.max(1)]; channels],
+            output: vec![0.0; max_frames * channels],
+            resampler,
+        }
+    }
+
+    pub fn native_rate(&self) -> u32 {
+        self.resampler.from_rate()
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.resampler.to_rate()
+    }
+
+    pub fn source(&self) -> &dyn AudioSource {
+        self.source.as_ref()
+    }
+
+    /// Forget what's buffered, say after the source jumps
+    pub fn reset(&mut self) {
+        self.resampler.reset();
+    }
+}
+
+impl AudioSource for ResampledSource {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        let channels = self.resampler.channels();
+        let frames = frames.min(self.output.len() / channels);
+        let native_rate = self.resampler.from_rate() as f32;
+        let (source, input) = (&mut self.source, &mut self.input);
+        self.resampler.pull(&mut self.output[..frames * channels], |block| {
+            let len = block.len() / channels;
+            let mut views = channel_views(input, len);
+            let views = &mut views[..channels];
+            views.iter_mut().for_each(|v| v.fill(0.0));
+            source.render(views, len, native_rate);
+            for (i, frame) in block.chunks_exact_mut(channels).enumerate() {
+                for (sample, view) in frame.iter_mut().zip(views.iter()) {
+                    *sample = view[i];
+                }
+            }
+        });
+        for (ch, out) in output.iter_mut().enumerate().take(channels) {
+            for (sample, frame) in out[..frames].iter_mut().zip(self.output.chunks_exact(channels)) {
+                *sample = frame[ch];
+            }
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.source.is_active()
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        memory.touch_all(&mut self.input);
+        memory.touch(&mut self.output);
+        self.source.prefault(memory);
+    }
+}
+
+/// Blackman-windowed sinc rows for fractional offsets 0..=1, each normalized to unity gain
+fn build_kernel(cutoff: f64) -> Vec<f32> {
+    let mut kernel = Vec::with_capacity((PHASES + 1) * TAPS);
+    for row in 0..=PHASES {
+        let fraction = row as f64 / PHASES as f64;
+        let taps: Vec<f64> = (0..TAPS)
+            .map(|k| {
+                let t = k as f64 - (HALF_TAPS - 1) as f64 - fraction;
+                cutoff * sinc(cutoff * t) * blackman(t / HALF_TAPS as f64)
+            })
+            .collect();
+        let sum: f64 = taps.iter().sum();
+        kernel.extend(taps.iter().map(|&c| (c / sum) as f32));
+    }
+    kernel
+}
+
+#[inline]
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 { 1.0 } else { (PI * x).sin() / (PI * x) }
+}
+
+/// Blackman window over -1..1
+#[inline]
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        return 0.0;
+    }
+    let phase = PI * (x + 1.0);
+    0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos()
+}
@@ -1,16 +1,75 @@
+use std::ops::Range;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use crossbeam::atomic::AtomicCell;
+use crossbeam::queue::ArrayQueue;
+use serde::{Deserialize, Serialize};
 use spin::RwLock;
 
+use crate::rt_processing::effects::{DEFAULT_TEMPO_BPM, Effect};
+use crate::rt_processing::denormal::NoiseFloor;
+use crate::rt_processing::events::EventPublisher;
+use crate::rt_processing::headroom::{AutoTrim, AutoTrimConfig};
+use crate::rt_processing::effects::chain::EffectChain;
+#[cfg(feature = "fixed-point")]
+use crate::rt_processing::fixed::FixedMix;
+use crate::rt_processing::filters::{EnvelopeFollower, OnePoleState, RampShape, SmoothedParam, SourceTrim, Trim};
+use crate::rt_processing::alloc_check::enter_audio_path;
+use crate::rt_processing::loudness::LoudnessProbe;
+use crate::rt_processing::metering::{BlockLevel, LevelMeter, Meters};
 use crate::rt_processing::performance::PerformanceMonitor;
+use crate::rt_processing::prefault::{Prefault, PrefaultMode};
+use crate::rt_processing::reclaim::{self, Reclaimer, Retired};
+use crate::rt_processing::resampler::ResampledSource;
+use crate::rt_processing::trace;
+use crate::rt_processing::transport::{TimeSignature, Transport};
+
+/// Sub-block length every `Router` processes in by default, in frames.
+///
+/// Nodes update their block-rate state (modulation, envelopes, smoothing
+/// targets) once per call, so rendering with the device's buffer size would
+/// make the output depend on it. The router instead cuts every buffer on a
+/// fixed grid counted from its first frame, so nodes see the same sequence of
+/// calls, and produce the same output, whatever the device block size.
+pub const CONTROL_BLOCK_FRAMES: usize = 64;
+
+/// Commands a `RouterCommands` handle can queue before the audio thread drains them
+pub const ROUTER_COMMAND_CAPACITY: usize = 256;
+/// Sources a router holds before adding one reallocates on the audio thread
+const INITIAL_SOURCE_CAPACITY: usize = 64;
+
+// boxed so reordering moves pointers, and a removed source can be retired as is
+type SourceList = Vec<Box<RoutedSource>>;
+/// Most channels a `Router` mixes
+pub const MAX_ROUTER_CHANNELS: usize = 32;
+/// Most aux sends one source has; room for them is made when it's created
+pub const MAX_SENDS: usize = 8;
 
 /// Trait for any renderable audio source.
 /// Non-interleaved, [channel][frame]
 pub trait AudioSource: Send + Sync {
     fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32);
+
+    /// `false` once the source has nothing more to play (a one-shot that ran
+    /// out); the router then removes it after the current block
+    fn is_active(&self) -> bool {
+        true
+    }
+
+    /// Touch the buffers used while rendering (see `rt_processing::prefault`).
+    /// Runs off the audio thread before playback; may allocate.
+    fn prefault(&mut self, _memory: &mut Prefault) {}
+
+    /// Rate the source renders at, when it isn't the router's (a 44.1 kHz
+    /// recording on a 48 kHz device). The router then plays it through a
+    /// `ResampledSource`, and `render` is called with this rate.
+    fn native_sample_rate(&self) -> Option<u32> {
+        None
+    }
 }
 
 /// Pan law
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PanLaw {
     Linear,
     EqualPower,
@@ -40,111 +99,1820 @@ impl Pan {
     }
 }
 
+/// Where a listened source is tapped for the listen bus
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ListenMode {
+    /// Pre-fader listen: the source as it renders, ahead of its trims,
+    /// inserts, gain and pan, centered
+    Pfl,
+    /// After-fader listen: after gain and pan, as it sits in the mix
+    Afl,
+}
+
+/// Duck and fade-in time of the talkback path when keyed or released
+const TALKBACK_RAMP_SECONDS: f32 = 0.01;
+
+/// Fade time of a bus being disabled or re-enabled
+const BUS_RAMP_SECONDS: f32 = 0.01;
+
+/// Glide time of source gain and pan changes by default
+pub const DEFAULT_PARAM_RAMP_MS: f32 = 20.0;
+
+/// Fade-out time of a source whose `Lifetime` is up
+pub const EXPIRY_FADE_SECONDS: f32 = 0.02;
+
+/// Control-thread handle of the router's talkback path. Cheap to clone; every
+/// setter is a single atomic store.
+#[derive(Clone)]
+pub struct TalkbackControl {
+    keyed: Arc<AtomicBool>,
+    duck_db: Arc<AtomicCell<f32>>,
+    gain: Arc<AtomicCell<f32>>,
+}
+
+impl TalkbackControl {
+    fn new() -> Self {
+        Self {
+            keyed: Arc::new(AtomicBool::new(false)),
+            duck_db: Arc::new(AtomicCell::new(-20.0)),
+            gain: Arc::new(AtomicCell::new(1.0)),
+        }
+    }
+
+    /// Press (`true`) or release the talkback key
+    pub fn set_keyed(&self, keyed: bool) {
+        self.keyed.store(keyed, Ordering::Release);
+    }
+
+    pub fn is_keyed(&self) -> bool {
+        self.keyed.load(Ordering::Acquire)
+    }
+
+    /// Level change of the cue signal while keyed (default -20 dB; -inf mutes it)
+    pub fn set_duck_db(&self, duck_db: f32) {
+        self.duck_db.store(duck_db.min(0.0));
+    }
+
+    pub fn duck_db(&self) -> f32 {
+        self.duck_db.load()
+    }
+
+    /// Linear gain of the talkback signal (default 1.0)
+    pub fn set_gain(&self, gain: f32) {
+        self.gain.store(gain.max(0.0));
+    }
+
+    pub fn gain(&self) -> f32 {
+        self.gain.load()
+    }
+}
+
+/// Talkback input: one channel of a source, mixed onto the listen bus when keyed
+struct Talkback {
+    source: Box<dyn AudioSource + 'static>,
+    channel: usize,
+    control: TalkbackControl,
+    // [channels][frames]
+    buffer: Vec<Vec<f32>>,
+    // 0 = released, 1 = keyed
+    ramp: OnePoleState,
+}
+
+/// Send from a source to an aux bus, for shared effects such as a reverb
+/// return. Sends are tapped after the source's inserts and mixed into the bus
+/// next to the sources routed there.
+#[derive(Copy, Clone, Debug)]
+pub struct AuxSend {
+    /// Aux bus fed (1..num_buses)
+    pub bus: usize,
+    /// Linear send level
+    pub level: f32,
+    /// Tap before the source's gain, so the fader doesn't change the send; pan
+    /// still applies. Post-fader sends follow the gain.
+    pub pre_fader: bool,
+    // `level` as heard; a new send fades in from silence
+    ramp: SmoothedParam,
+}
+
+impl AuxSend {
+    fn new(bus: usize, level: f32, pre_fader: bool, ramp_ms: f32, shape: RampShape) -> Self {
+        Self { bus, level, pre_fader, ramp: SmoothedParam::new(0.0, ramp_ms, shape) }
+    }
+}
+
+/// How long a source plays before the router fades it out and removes it,
+/// counted from when it joined the mix (it doesn't age while its bus is
+/// disabled). Bars follow the tempo and meter of
+/// the router's transport (4/4 at `DEFAULT_TEMPO_BPM` without one) and keep
+/// counting while it is stopped.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Lifetime {
+    Seconds(f64),
+    Bars(f64),
+}
+
+/// Stable identity of a routed source. Unlike its index, it doesn't change
+/// when other sources are removed, and is never reused by the router.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SourceId(pub u64);
+
 /// Represents a routed audio source.
 /// Note: we store a 'static trait object so it's straightforward to push
 /// Boxed adapters created from local types.
 pub struct RoutedSource {
+    pub id: SourceId,
     pub source: Box<dyn AudioSource + 'static>,
     pub gain: f32,
     pub pan: Pan,
     pub bus: usize, // 0 = master, >0 = aux bus
+    pub trim: SourceTrim,
+    /// Insert chain, run after the trims and before gain, pan and the bus sum
+    pub effects: EffectChain,
+    /// Sends to aux buses, at most `MAX_SENDS`; see `Router::set_send`
+    pub sends: Vec<AuxSend>,
+    /// Sent to the listen bus when set; never changes the main mix
+    pub listen: Option<ListenMode>,
+    /// Left out of the main mix (still rendered, so it stays in time)
+    pub mute: bool,
+    /// While any source is soloed, only soloed ones reach the main mix
+    pub solo: bool,
+    /// Labels for changing related sources together, e.g. "drums"; see
+    /// `Router::set_tags`
+    pub tags: Vec<String>,
+    /// Fade out and leave once this is up; see `Lifetime`
+    pub lifetime: Option<Lifetime>,
+    // time mixed so far, for `lifetime`
+    age_seconds: f64,
+    age_beats: f64,
+    // 1 until the lifetime is up, then falling to 0 over `EXPIRY_FADE_SECONDS`
+    fade: f32,
+    // cleared when the router drops the source, for `SourceHandle::is_alive`
+    alive: Arc<AtomicBool>,
+    // post-fader levels, listed in the router's `Meters`
+    meter: Arc<LevelMeter>,
+    // `gain` and the pan law's (left, right) gains as heard, gliding to the fields
+    gain_ramp: SmoothedParam,
+    pan_ramps: [SmoothedParam; 2],
+}
+
+impl RoutedSource {
+    fn new(
+        id: SourceId,
+        source: Box<dyn AudioSource + 'static>,
+        gain: f32,
+        pan: Pan,
+        bus: usize,
+        channels: usize,
+        meter: Arc<LevelMeter>,
+    ) -> Self {
+        let (left, right) = pan.gains();
+        let ramp = |value| SmoothedParam::new(value, DEFAULT_PARAM_RAMP_MS, RampShape::Linear);
+        Self {
+            id,
+            source,
+            gain,
+            pan,
+            bus,
+            trim: SourceTrim::new(channels),
+            effects: EffectChain::new(),
+            sends: Vec::with_capacity(MAX_SENDS),
+            listen: None,
+            mute: false,
+            solo: false,
+            tags: Vec::new(),
+            lifetime: None,
+            age_seconds: 0.0,
+            age_beats: 0.0,
+            fade: 1.0,
+            alive: Arc::new(AtomicBool::new(true)),
+            meter,
+            gain_ramp: ramp(gain),
+            pan_ramps: [ramp(left), ramp(right)],
+        }
+    }
+
+    fn set_ramp(&mut self, ramp_ms: f32, shape: RampShape) {
+        self.gain_ramp.set_ramp(ramp_ms, shape);
+        self.pan_ramps.iter_mut().for_each(|ramp| ramp.set_ramp(ramp_ms, shape));
+        self.sends.iter_mut().for_each(|send| send.ramp.set_ramp(ramp_ms, shape));
+    }
+
+    /// Add or update the send to `bus`; `false` when all `MAX_SENDS` are taken
+    fn set_send(&mut self, bus: usize, level: f32, pre_fader: bool) -> bool {
+        if let Some(send) = self.sends.iter_mut().find(|send| send.bus == bus) {
+            send.level = level;
+            send.pre_fader = pre_fader;
+            return true;
+        }
+        if self.sends.len() == MAX_SENDS {
+            return false;
+        }
+        let (ramp_ms, shape) = (self.gain_ramp.ramp_ms(), self.gain_ramp.shape());
+        self.sends.push(AuxSend::new(bus, level, pre_fader, ramp_ms, shape));
+        true
+    }
+
+    fn remove_send(&mut self, bus: usize) -> bool {
+        let before = self.sends.len();
+        self.sends.retain(|send| send.bus != bus);
+        self.sends.len() != before
+    }
+
+    /// Whether the router is done with the source: it ran out, or its
+    /// lifetime is up and it has faded out
+    fn is_done(&self) -> bool {
+        !self.source.is_active() || self.fade == 0.0
+    }
+
+    /// Fade the rendered `views` from the frame the lifetime is up, then count
+    /// `frames` towards it. `bar_beats` is the length of a bar in quarter notes.
+    fn age(&mut self, views: &mut [&mut [f32]], frames: usize, sample_rate: f32, beats_per_frame: f64, bar_beats: f64) {
+        // frames left to live
+        let left = match self.lifetime {
+            Some(Lifetime::Seconds(seconds)) => (seconds - self.age_seconds) * sample_rate as f64,
+            Some(Lifetime::Bars(bars)) => (bars * bar_beats - self.age_beats) / beats_per_frame,
+            None => f64::INFINITY,
+        };
+        let start = left.max(0.0).round();
+        if start < frames as f64 {
+            let step = 1.0 / (EXPIRY_FADE_SECONDS * sample_rate).max(1.0);
+            let from = self.fade;
+            for view in views.iter_mut() {
+                let mut fade = from;
+                for sample in view[start as usize..frames].iter_mut() {
+                    fade = (fade - step).max(0.0);
+                    *sample *= fade;
+                }
+                self.fade = fade;
+            }
+        }
+        self.age_seconds += frames as f64 / sample_rate as f64;
+        self.age_beats += frames as f64 * beats_per_frame;
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own == tag)
+    }
+
+    /// Aim the ramps at the current `gain` and `pan`
+    fn update_ramps(&mut self, sample_rate: f32) {
+        let (left, right) = self.pan.gains();
+        let [left_ramp, right_ramp] = &mut self.pan_ramps;
+        for (ramp, target) in [&mut self.gain_ramp, left_ramp, right_ramp].into_iter().zip([self.gain, left, right]) {
+            ramp.prepare(sample_rate);
+            ramp.set_target(target);
+        }
+    }
+}
+
+/// `source` as the router plays it: converted to `sample_rate` when it renders
+/// at another rate, and prefaulted, so buffers it sizes lazily exist before
+/// its first block. Not RT-safe.
+fn prepared(source: Box<dyn AudioSource>, channels: usize, sample_rate: f32, max_frames: usize) -> Box<dyn AudioSource> {
+    let rate = sample_rate.round() as u32;
+    let mut source = match source.native_sample_rate() {
+        Some(native) if native != rate => Box::new(ResampledSource::new(source, channels, native, rate, max_frames)),
+        _ => source,
+    };
+    source.prefault(&mut Prefault::new(PrefaultMode::Touch, channels, max_frames));
+    source
+}
+
+impl Drop for RoutedSource {
+    fn drop(&mut self) {
+        self.alive.store(false, Ordering::Release);
+    }
+}
+
+/// Live change to a router, queued by a `RouterCommands` handle and applied
+/// at the start of the next `Router::process`. Commands naming a source that
+/// is gone do nothing.
+pub enum RouterCommand {
+    AddSource(Box<RoutedSource>),
+    RemoveSource(SourceId),
+    /// Swap the node the source renders, keeping its mix settings, trims,
+    /// inserts and sends; the old node is dropped off the audio thread
+    ReplaceSource { id: SourceId, source: Box<Box<dyn AudioSource>> },
+    SetGain { id: SourceId, gain: f32 },
+    SetPan { id: SourceId, pan: Pan },
+    SetBus { id: SourceId, bus: usize },
+    SetMute { id: SourceId, mute: bool },
+    SetSolo { id: SourceId, solo: bool },
+    SetTrims { id: SourceId, high_pass: Option<Trim>, low_pass: Option<Trim> },
+    SetHighPassTrim { id: SourceId, trim: Option<Trim> },
+    SetLowPassTrim { id: SourceId, trim: Option<Trim> },
+    /// Send the source to the listen bus, or stop with `None`
+    SetListen { id: SourceId, mode: Option<ListenMode> },
+    /// Replace the source's insert chain; the old one is dropped off the audio thread
+    SetEffects { id: SourceId, chain: Box<EffectChain> },
+    /// Put `effects` in place of the source's inserts in `range` (see
+    /// `EffectChain::splice`); the replaced ones are dropped off the audio thread
+    SpliceEffects { id: SourceId, range: Range<usize>, effects: Box<Vec<Box<dyn Effect>>> },
+    SetEffectsMix { id: SourceId, mix: f32 },
+    /// Add or update the source's send to aux `bus`
+    SetSend { id: SourceId, bus: usize, level: f32, pre_fader: bool },
+    RemoveSend { id: SourceId, bus: usize },
+    /// Replace the source's tags; the old ones are dropped off the audio thread
+    SetTags { id: SourceId, tags: Box<Vec<String>> },
+    SetLifetime { id: SourceId, lifetime: Option<Lifetime> },
+    /// Change every source carrying `tag`
+    Tagged { tag: Box<String>, change: TagChange },
+    /// Replace the insert chain of `bus`; the old one is dropped off the audio thread
+    SetBusEffects { bus: usize, chain: Box<EffectChain> },
+    /// `SpliceEffects` on the insert chain of `bus`
+    SpliceBusEffects { bus: usize, range: Range<usize>, effects: Box<Vec<Box<dyn Effect>>> },
+    SetBusReturn { bus: usize, gain: f32 },
+    /// Applied together, in order, within one block; see `RouterCommands::send_batch`
+    Batch(Box<Vec<RouterCommand>>),
+}
+
+/// What `RouterCommand::Tagged` does to each source with the tag
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TagChange {
+    Mute(bool),
+    Solo(bool),
+    Gain(f32),
+    Remove,
+}
+
+/// Control-thread handle queueing `RouterCommand`s for the audio thread.
+///
+/// Sending never takes a lock, so the UI can't stall the callback the way the
+/// router's `&self` mutators can when they contend with it. Cheap to clone.
+/// Commands are applied in order; when the queue is full, `send` hands the
+/// command back.
+#[derive(Clone)]
+pub struct RouterCommands {
+    queue: Arc<ArrayQueue<RouterCommand>>,
+    next_id: Arc<AtomicU64>,
+    channels: usize,
+    sample_rate: f32,
+    max_frames: usize,
+    num_buses: usize,
+    meters: Meters,
+}
+
+impl RouterCommands {
+    pub fn send(&self, command: RouterCommand) -> Result<(), RouterCommand> {
+        self.queue.push(command)
+    }
+
+    /// Queue a new source; builds its trim state (and its resampler, see
+    /// `AudioSource::native_sample_rate`) here, off the audio thread. `None`
+    /// when the queue is full.
+    pub fn add_source(
+        &self,
+        source: Box<dyn AudioSource + 'static>,
+        gain: f32,
+        pan: Pan,
+        bus: usize,
+    ) -> Option<SourceHandle> {
+        let (handle, command) = self.source_command(source, gain, pan, bus);
+        self.send(command).ok().map(|_| handle)
+    }
+
+    /// `AddSource` for a new source, built as `add_source` builds it, to
+    /// send in a batch along with commands for its id
+    pub fn source_command(
+        &self,
+        source: Box<dyn AudioSource + 'static>,
+        gain: f32,
+        pan: Pan,
+        bus: usize,
+    ) -> (SourceHandle, RouterCommand) {
+        let id = SourceId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let source = prepared(source, self.channels, self.sample_rate, self.max_frames);
+        let routed = RoutedSource::new(id, source, gain, pan, bus, self.channels, self.meters.add_source(id));
+        let handle = SourceHandle { id, alive: Arc::clone(&routed.alive), commands: self.clone() };
+        (handle, RouterCommand::AddSource(Box::new(routed)))
+    }
+
+    /// Swap the node source `id` renders for `source`, keeping everything
+    /// else about it; resamples and prefaults `source` here, as `add_source` does
+    pub fn replace_source(&self, id: SourceId, source: Box<dyn AudioSource + 'static>) -> bool {
+        self.send(self.replace_source_command(id, source)).is_ok()
+    }
+
+    /// `ReplaceSource` as `replace_source` queues it, for a batch
+    pub fn replace_source_command(&self, id: SourceId, source: Box<dyn AudioSource + 'static>) -> RouterCommand {
+        let source = prepared(source, self.channels, self.sample_rate, self.max_frames);
+        RouterCommand::ReplaceSource { id, source: Box::new(source) }
+    }
+
+    pub fn remove_source(&self, id: SourceId) -> bool {
+        self.send(RouterCommand::RemoveSource(id)).is_ok()
+    }
+
+    pub fn set_gain(&self, id: SourceId, gain: f32) -> bool {
+        self.send(RouterCommand::SetGain { id, gain }).is_ok()
+    }
+
+    pub fn set_pan(&self, id: SourceId, pan: Pan) -> bool {
+        self.send(RouterCommand::SetPan { id, pan }).is_ok()
+    }
+
+    pub fn set_bus(&self, id: SourceId, bus: usize) -> bool {
+        self.send(RouterCommand::SetBus { id, bus }).is_ok()
+    }
+
+    pub fn set_mute(&self, id: SourceId, mute: bool) -> bool {
+        self.send(RouterCommand::SetMute { id, mute }).is_ok()
+    }
+
+    pub fn set_solo(&self, id: SourceId, solo: bool) -> bool {
+        self.send(RouterCommand::SetSolo { id, solo }).is_ok()
+    }
+
+    /// Replace the insert chain of source `id`; prepares the chain here, off
+    /// the audio thread
+    pub fn set_effects(&self, id: SourceId, mut chain: EffectChain) -> bool {
+        chain.prepare(self.channels, self.max_frames);
+        self.send(RouterCommand::SetEffects { id, chain: Box::new(chain) }).is_ok()
+    }
+
+    /// Put `effects` in place of the inserts in `range` on source `id`; see
+    /// `EffectChain::splice`. Doesn't allocate on the audio thread while the
+    /// chain was prepared and stays within `CHAIN_CAPACITY`.
+    pub fn splice_effects(&self, id: SourceId, range: Range<usize>, mut effects: Vec<Box<dyn Effect>>) -> bool {
+        effects.reserve(range.len());
+        self.send(RouterCommand::SpliceEffects { id, range, effects: Box::new(effects) }).is_ok()
+    }
+
+    /// Wet/dry balance of the insert chain of source `id`; see `EffectChain::set_mix`
+    pub fn set_effects_mix(&self, id: SourceId, mix: f32) -> bool {
+        self.send(RouterCommand::SetEffectsMix { id, mix }).is_ok()
+    }
+
+    /// Send source `id` to aux `bus` at `level`, or change an existing send.
+    /// Ignored on the audio thread when the source already has `MAX_SENDS`
+    /// sends; `false` for the master bus, a bus that doesn't exist or a full
+    /// queue.
+    pub fn set_send(&self, id: SourceId, bus: usize, level: f32, pre_fader: bool) -> bool {
+        (1..self.num_buses).contains(&bus) && self.send(RouterCommand::SetSend { id, bus, level, pre_fader }).is_ok()
+    }
+
+    pub fn remove_send(&self, id: SourceId, bus: usize) -> bool {
+        self.send(RouterCommand::RemoveSend { id, bus }).is_ok()
+    }
+
+    /// Set (or clear with `None`) both trims of source `id`
+    pub fn set_trims(&self, id: SourceId, high_pass: Option<Trim>, low_pass: Option<Trim>) -> bool {
+        self.send(RouterCommand::SetTrims { id, high_pass, low_pass }).is_ok()
+    }
+
+    pub fn set_high_pass_trim(&self, id: SourceId, trim: Option<Trim>) -> bool {
+        self.send(RouterCommand::SetHighPassTrim { id, trim }).is_ok()
+    }
+
+    pub fn set_low_pass_trim(&self, id: SourceId, trim: Option<Trim>) -> bool {
+        self.send(RouterCommand::SetLowPassTrim { id, trim }).is_ok()
+    }
+
+    /// See `Router::set_listen`
+    pub fn set_listen(&self, id: SourceId, mode: Option<ListenMode>) -> bool {
+        self.send(RouterCommand::SetListen { id, mode }).is_ok()
+    }
+
+    /// Replace the insert chain of `bus`; prepares the chain here
+    pub fn set_bus_effects(&self, bus: usize, mut chain: EffectChain) -> bool {
+        chain.prepare(self.channels, self.max_frames);
+        self.send(RouterCommand::SetBusEffects { bus, chain: Box::new(chain) }).is_ok()
+    }
+
+    /// `splice_effects` on the insert chain of `bus`
+    pub fn splice_bus_effects(&self, bus: usize, range: Range<usize>, mut effects: Vec<Box<dyn Effect>>) -> bool {
+        effects.reserve(range.len());
+        self.send(RouterCommand::SpliceBusEffects { bus, range, effects: Box::new(effects) }).is_ok()
+    }
+
+    /// See `Router::set_bus_return`
+    pub fn set_bus_return(&self, bus: usize, gain: f32) -> bool {
+        self.send(RouterCommand::SetBusReturn { bus, gain }).is_ok()
+    }
+
+    /// Give source `id` a lifetime, counted from when it was added, or
+    /// none with `None`. Sent right after `add_source`, it lands in the same
+    /// block.
+    pub fn set_lifetime(&self, id: SourceId, lifetime: Option<Lifetime>) -> bool {
+        self.send(RouterCommand::SetLifetime { id, lifetime }).is_ok()
+    }
+
+    pub fn set_tags(&self, id: SourceId, tags: &[&str]) -> bool {
+        let tags = tags.iter().map(|tag| tag.to_string()).collect();
+        self.send(RouterCommand::SetTags { id, tags: Box::new(tags) }).is_ok()
+    }
+
+    /// Apply `change` to every source tagged `tag` when the command lands,
+    /// including sources queued before it
+    pub fn change_tagged(&self, tag: &str, change: TagChange) -> bool {
+        self.send(RouterCommand::Tagged { tag: Box::new(tag.to_string()), change }).is_ok()
+    }
+
+    pub fn set_mute_tagged(&self, tag: &str, mute: bool) -> bool {
+        self.change_tagged(tag, TagChange::Mute(mute))
+    }
+
+    pub fn set_solo_tagged(&self, tag: &str, solo: bool) -> bool {
+        self.change_tagged(tag, TagChange::Solo(solo))
+    }
+
+    pub fn set_gain_tagged(&self, tag: &str, gain: f32) -> bool {
+        self.change_tagged(tag, TagChange::Gain(gain))
+    }
+
+    pub fn remove_tagged(&self, tag: &str) -> bool {
+        self.change_tagged(tag, TagChange::Remove)
+    }
+
+    /// Queue `commands` to be applied at the same block boundary, e.g. a
+    /// preset's gains and pans, so no block plays half of them. Commands sent
+    /// one by one can straddle a block when the audio thread drains the queue
+    /// mid-way. Takes one queue slot; the batch is handed back if it's full.
+    pub fn send_batch(&self, commands: Vec<RouterCommand>) -> Result<(), Vec<RouterCommand>> {
+        match self.send(RouterCommand::Batch(Box::new(commands))) {
+            Ok(()) => Ok(()),
+            Err(RouterCommand::Batch(commands)) => Err(*commands),
+            Err(_) => unreachable!("send hands back the command it was given"),
+        }
+    }
+
+    /// Commands waiting for the audio thread
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+/// Control-thread handle of one routed source.
+///
+/// Changes go through the router's command queue (see `RouterCommands`), so
+/// they land at the next block and never contend with the audio thread; each
+/// setter returns `false` if the queue was full. Dropping the handle leaves the
+/// source playing. `is_alive` turns false once the router drops the source,
+/// whether through `remove` or because it stopped being active.
+#[derive(Clone)]
+pub struct SourceHandle {
+    id: SourceId,
+    alive: Arc<AtomicBool>,
+    commands: RouterCommands,
+}
+
+impl SourceHandle {
+    pub fn id(&self) -> SourceId {
+        self.id
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::Acquire)
+    }
+
+    pub fn remove(&self) -> bool {
+        self.commands.remove_source(self.id)
+    }
+
+    pub fn set_gain(&self, gain: f32) -> bool {
+        self.commands.set_gain(self.id, gain)
+    }
+
+    pub fn set_pan(&self, pan: Pan) -> bool {
+        self.commands.set_pan(self.id, pan)
+    }
+
+    pub fn set_bus(&self, bus: usize) -> bool {
+        self.commands.set_bus(self.id, bus)
+    }
+
+    pub fn set_mute(&self, mute: bool) -> bool {
+        self.commands.set_mute(self.id, mute)
+    }
+
+    pub fn set_solo(&self, solo: bool) -> bool {
+        self.commands.set_solo(self.id, solo)
+    }
+
+    pub fn set_effects(&self, chain: EffectChain) -> bool {
+        self.commands.set_effects(self.id, chain)
+    }
+
+    pub fn set_effects_mix(&self, mix: f32) -> bool {
+        self.commands.set_effects_mix(self.id, mix)
+    }
+
+    pub fn set_send(&self, bus: usize, level: f32, pre_fader: bool) -> bool {
+        self.commands.set_send(self.id, bus, level, pre_fader)
+    }
+
+    pub fn remove_send(&self, bus: usize) -> bool {
+        self.commands.remove_send(self.id, bus)
+    }
+
+    pub fn set_trims(&self, high_pass: Option<Trim>, low_pass: Option<Trim>) -> bool {
+        self.commands.set_trims(self.id, high_pass, low_pass)
+    }
+
+    pub fn set_high_pass_trim(&self, trim: Option<Trim>) -> bool {
+        self.commands.set_high_pass_trim(self.id, trim)
+    }
+
+    pub fn set_low_pass_trim(&self, trim: Option<Trim>) -> bool {
+        self.commands.set_low_pass_trim(self.id, trim)
+    }
+
+    pub fn set_listen(&self, mode: Option<ListenMode>) -> bool {
+        self.commands.set_listen(self.id, mode)
+    }
+
+    pub fn set_tags(&self, tags: &[&str]) -> bool {
+        self.commands.set_tags(self.id, tags)
+    }
+
+    pub fn set_lifetime(&self, lifetime: Option<Lifetime>) -> bool {
+        self.commands.set_lifetime(self.id, lifetime)
+    }
 }
 
 /// The main router/mixer
 pub struct Router {
-    sources: Arc<RwLock<Vec<RoutedSource>>>,
+    // boxed, so a removed source can be handed to the reclaimer without allocating
+    #[allow(clippy::vec_box)]
+    sources: Arc<RwLock<SourceList>>,
     channels: usize,
     sample_rate: f32,
     // Scratch buffer: [channels][frames]
     scratch: Vec<Vec<f32>>,
+    // render target of one source at a time: [channels][frames]
+    source_buffer: Vec<Vec<f32>>,
+    // per-bus mix: [bus][channels][frames]
+    bus_buffers: Vec<Vec<Vec<f32>>>,
+    // per-bus gain target and whether the bus runs this block
+    bus_targets: Vec<f32>,
+    bus_active: Vec<bool>,
+    // listen bus: [channels][frames]
+    listen: Vec<Vec<f32>>,
+    talkback: Option<Talkback>,
     num_buses: usize,
+    // insert chain per bus; bus 0's chain runs on the final master mix
+    bus_effects: Arc<RwLock<Vec<EffectChain>>>,
+    bus_enabled: Arc<Vec<AtomicBool>>,
+    // output gain per bus, ramping towards 0 or 1 after a toggle
+    bus_gains: Vec<f32>,
+    // return gain per bus as set, and as applied (gliding across a block)
+    bus_returns: Arc<Vec<AtomicCell<f32>>>,
+    return_gains: Vec<f32>,
+    // per-sample source gain and (left, right) pan gains of the current block
+    gain_ramp: Vec<f32>,
+    // per-sample level of one send
+    send_ramp: Vec<f32>,
+    pan_ramps: [Vec<f32>; 2],
+    param_ramp_ms: f32,
+    param_ramp_shape: RampShape,
+    // sub-block grid; 0 = process whole buffers
+    control_block: usize,
+    // frames processed since creation, places buffers on the grid
+    position: u64,
+    // live changes from `RouterCommands` handles
+    commands: Arc<ArrayQueue<RouterCommand>>,
+    // shared with `RouterCommands`, so ids are unique however a source was added
+    next_id: Arc<AtomicU64>,
+    // sources leaving during `process` are dropped through this
+    reclaimer: Reclaimer,
+    // levels of the sources, buses and output, see `meters`
+    meters: Meters,
+    // K-weights the output for `LoudnessMeter`
+    loudness: LoudnessProbe,
+    // added ahead of effect chains, see `set_noise_floor`
+    noise_floor: Option<NoiseFloor>,
+    // per bus, see `set_auto_trim`, and the sources heard on each this block
+    auto_trims: Vec<Option<AutoTrim>>,
+    bus_sources: Vec<usize>,
+    events: Option<EventPublisher>,
+    // per bus, see `set_bus_tags`; never read on the audio thread
+    bus_tags: Vec<Vec<String>>,
+    // moved on by every sub-block, see `set_transport`
+    transport: Option<Transport>,
+    // see `set_fixed_point_mix`
+    #[cfg(feature = "fixed-point")]
+    fixed_mix: Option<FixedMix>,
 }
 
 impl Router {
+    /// Every buffer the mix needs is allocated here, sized by `max_frames`, so
+    /// `process` doesn't allocate. Panics past `MAX_ROUTER_CHANNELS` channels.
     pub fn new(channels: usize, sample_rate: f32, num_buses: usize, max_frames: usize) -> Self {
+        assert!(channels <= MAX_ROUTER_CHANNELS, "a router mixes at most {} channels", MAX_ROUTER_CHANNELS);
         let mut scratch = Vec::with_capacity(channels);
         for _ in 0..channels {
             scratch.push(vec![0.0; max_frames]);
         }
+        let listen = scratch.clone();
+        let meters = Meters::new(channels, num_buses.max(1));
 
         Self {
-            sources: Arc::new(RwLock::new(Vec::new())),
+            sources: Arc::new(RwLock::new(Vec::with_capacity(INITIAL_SOURCE_CAPACITY))),
             channels,
             sample_rate,
+            source_buffer: scratch.clone(),
+            bus_buffers: vec![scratch.clone(); num_buses.max(1)],
+            bus_targets: vec![1.0; num_buses.max(1)],
+            bus_active: vec![true; num_buses.max(1)],
             scratch,
+            listen,
+            talkback: None,
             num_buses: num_buses.max(1),
+            bus_effects: Arc::new(RwLock::new(
+                (0..num_buses.max(1))
+                    .map(|_| {
+                        let mut chain = EffectChain::new();
+                        chain.prepare(channels, max_frames);
+                        chain
+                    })
+                    .collect(),
+            )),
+            bus_enabled: Arc::new((0..num_buses.max(1)).map(|_| AtomicBool::new(true)).collect()),
+            bus_gains: vec![1.0; num_buses.max(1)],
+            bus_returns: Arc::new((0..num_buses.max(1)).map(|_| AtomicCell::new(1.0)).collect()),
+            return_gains: vec![1.0; num_buses.max(1)],
+            gain_ramp: vec![0.0; max_frames],
+            send_ramp: vec![0.0; max_frames],
+            pan_ramps: [vec![0.0; max_frames], vec![0.0; max_frames]],
+            param_ramp_ms: DEFAULT_PARAM_RAMP_MS,
+            param_ramp_shape: RampShape::Linear,
+            control_block: CONTROL_BLOCK_FRAMES,
+            position: 0,
+            commands: Arc::new(ArrayQueue::new(ROUTER_COMMAND_CAPACITY)),
+            next_id: Arc::new(AtomicU64::new(0)),
+            reclaimer: reclaim::global().clone(),
+            loudness: LoudnessProbe::new(channels, sample_rate, Arc::clone(&meters.loudness)),
+            meters,
+            noise_floor: None,
+            auto_trims: (0..num_buses.max(1)).map(|_| None).collect(),
+            bus_sources: vec![0; num_buses.max(1)],
+            events: None,
+            bus_tags: vec![Vec::new(); num_buses.max(1)],
+            transport: None,
+            #[cfg(feature = "fixed-point")]
+            fixed_mix: None,
+        }
+    }
+
+    /// Drop sources removed during `process` through `reclaimer` instead of
+    /// the global one
+    pub fn set_reclaimer(&mut self, reclaimer: Reclaimer) {
+        self.reclaimer = reclaimer;
+    }
+
+    /// Peak and RMS of every source, bus and the output, measured each block,
+    /// and the loudness of the output; read them through a `MeterReader` and
+    /// a `LoudnessMeter`
+    pub fn meters(&self) -> Meters {
+        self.meters.clone()
+    }
+
+    /// Handle for changing sources from other threads without contending
+    /// with `process`; see `RouterCommand`
+    pub fn commands(&self) -> RouterCommands {
+        RouterCommands {
+            queue: Arc::clone(&self.commands),
+            next_id: Arc::clone(&self.next_id),
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            max_frames: self.max_frames(),
+            num_buses: self.num_buses,
+            meters: self.meters.clone(),
+        }
+    }
+
+    /// Apply queued commands now rather than at the next `process`, e.g. while
+    /// nothing is processing; commands naming a missing source are dropped
+    pub fn apply_commands(&mut self) {
+        if self.commands.is_empty() {
+            return;
+        }
+        let mut sources = self.sources.write();
+        while let Some(command) = self.commands.pop() {
+            self.apply_command(&mut sources, command);
+        }
+    }
+
+    fn apply_command(&self, sources: &mut SourceList, command: RouterCommand) {
+        match command {
+            RouterCommand::AddSource(mut routed) => {
+                routed.set_ramp(self.param_ramp_ms, self.param_ramp_shape);
+                sources.push(routed);
+            }
+            RouterCommand::RemoveSource(id) => {
+                if let Some(index) = sources.iter().position(|routed| routed.id == id) {
+                    self.reclaimer.retire(Retired::Source(sources.remove(index)));
+                }
+            }
+            RouterCommand::ReplaceSource { id, mut source } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    std::mem::swap(&mut routed.source, &mut *source);
+                }
+                self.reclaimer.retire(Retired::Other(source));
+            }
+            RouterCommand::SetGain { id, gain } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    routed.gain = gain;
+                }
+            }
+            RouterCommand::SetPan { id, pan } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    routed.pan = pan;
+                }
+            }
+            RouterCommand::SetBus { id, bus } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    routed.bus = bus;
+                }
+            }
+            RouterCommand::SetMute { id, mute } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    routed.mute = mute;
+                }
+            }
+            RouterCommand::SetSolo { id, solo } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    routed.solo = solo;
+                }
+            }
+            RouterCommand::SetTrims { id, high_pass, low_pass } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    routed.trim.high_pass.set(high_pass);
+                    routed.trim.low_pass.set(low_pass);
+                }
+            }
+            RouterCommand::SetHighPassTrim { id, trim } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    routed.trim.high_pass.set(trim);
+                }
+            }
+            RouterCommand::SetLowPassTrim { id, trim } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    routed.trim.low_pass.set(trim);
+                }
+            }
+            RouterCommand::SetListen { id, mode } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    routed.listen = mode;
+                }
+            }
+            RouterCommand::SetEffects { id, mut chain } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    // the command's box carries the old chain back out, so
+                    // nothing is allocated or freed on the audio thread
+                    std::mem::swap(&mut routed.effects, &mut *chain);
+                }
+                self.reclaimer.retire(Retired::Other(chain));
+            }
+            RouterCommand::SpliceEffects { id, range, mut effects } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    routed.effects.splice(range, &mut effects);
+                }
+                self.reclaimer.retire(Retired::Other(effects));
+            }
+            RouterCommand::SetEffectsMix { id, mix } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    routed.effects.set_mix(mix);
+                }
+            }
+            RouterCommand::SetSend { id, bus, level, pre_fader } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    routed.set_send(bus, level, pre_fader);
+                }
+            }
+            RouterCommand::RemoveSend { id, bus } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    routed.remove_send(bus);
+                }
+            }
+            RouterCommand::SetLifetime { id, lifetime } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    routed.lifetime = lifetime;
+                }
+            }
+            RouterCommand::SetTags { id, mut tags } => {
+                if let Some(routed) = source_mut(sources, id) {
+                    std::mem::swap(&mut routed.tags, &mut tags);
+                }
+                self.reclaimer.retire(Retired::Other(tags));
+            }
+            RouterCommand::Tagged { tag, change } => {
+                match change {
+                    TagChange::Remove => {
+                        while let Some(index) = sources.iter().position(|routed| routed.has_tag(&tag)) {
+                            self.reclaimer.retire(Retired::Source(sources.remove(index)));
+                        }
+                    }
+                    _ => {
+                        for routed in sources.iter_mut().filter(|routed| routed.has_tag(&tag)) {
+                            match change {
+                                TagChange::Mute(mute) => routed.mute = mute,
+                                TagChange::Solo(solo) => routed.solo = solo,
+                                TagChange::Gain(gain) => routed.gain = gain,
+                                TagChange::Remove => {}
+                            }
+                        }
+                    }
+                }
+                self.reclaimer.retire(Retired::Other(tag));
+            }
+            RouterCommand::SetBusEffects { bus, mut chain } => {
+                if let Some(slot) = self.bus_effects.write().get_mut(bus) {
+                    std::mem::swap(slot, &mut *chain);
+                }
+                self.reclaimer.retire(Retired::Other(chain));
+            }
+            RouterCommand::SpliceBusEffects { bus, range, mut effects } => {
+                if let Some(chain) = self.bus_effects.write().get_mut(bus) {
+                    chain.splice(range, &mut effects);
+                }
+                self.reclaimer.retire(Retired::Other(effects));
+            }
+            RouterCommand::SetBusReturn { bus, gain } => {
+                self.set_bus_return(bus, gain);
+            }
+            RouterCommand::Batch(mut commands) => {
+                for command in commands.drain(..) {
+                    self.apply_command(sources, command);
+                }
+                // the emptied list is freed off the audio thread
+                self.reclaimer.retire(Retired::Other(commands));
+            }
         }
     }
 
     /// Accept a 'static boxed routing AudioSource.
     /// We take &self because we mutate the internal RwLock, not `self` itself.
-    pub fn add_source(&self, source: Box<dyn AudioSource + 'static>, gain: f32, pan: Pan, bus: usize) {
-        let mut guard = self.sources.write();
-        guard.push(RoutedSource { source, gain, pan, bus });
+    /// Sources with another native rate are resampled to the router's.
+    pub fn add_source(&self, source: Box<dyn AudioSource + 'static>, gain: f32, pan: Pan, bus: usize) -> SourceId {
+        let id = SourceId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let source = prepared(source, self.channels, self.sample_rate, self.max_frames());
+        let mut routed = RoutedSource::new(id, source, gain, pan, bus, self.channels, self.meters.add_source(id));
+        routed.set_ramp(self.param_ramp_ms, self.param_ramp_shape);
+        self.sources.write().push(Box::new(routed));
+        id
+    }
+
+    /// How source gain and pan changes glide, for existing and future sources;
+    /// 0 ms makes changes instant
+    pub fn set_param_ramp(&mut self, ramp_ms: f32, shape: RampShape) {
+        self.param_ramp_ms = ramp_ms.max(0.0);
+        self.param_ramp_shape = shape;
+        for routed in self.sources.write().iter_mut() {
+            routed.set_ramp(ramp_ms, shape);
+        }
+    }
+
+    /// Add noise at `level_db` dBFS to the input of every source, bus and
+    /// master effect chain, keeping their feedback out of denormals (see
+    /// `rt_processing::denormal`); `None` (the default) adds nothing
+    pub fn set_noise_floor(&mut self, level_db: Option<f32>) {
+        self.noise_floor = level_db.map(NoiseFloor::new);
+    }
+
+    pub fn noise_floor(&self) -> Option<f32> {
+        self.noise_floor.as_ref().map(NoiseFloor::level_db)
+    }
+
+    /// Protect `bus` against piling up sources (see `rt_processing::headroom`):
+    /// its sum, before its inserts, is trimmed down slowly while it stays above
+    /// the headroom target. Bus 0's trim works on the whole master mix, ahead
+    /// of the master inserts. A new config keeps the current trim; `None`
+    /// removes it. Returns `false` if there is no such bus.
+    pub fn set_auto_trim(&mut self, bus: usize, config: Option<AutoTrimConfig>) -> bool {
+        let Some(slot) = self.auto_trims.get_mut(bus) else {
+            return false;
+        };
+        match (slot.as_mut(), config) {
+            (Some(trim), Some(config)) => trim.set_config(config),
+            (_, config) => *slot = config.map(|config| AutoTrim::new(bus, config)),
+        }
+        true
+    }
+
+    pub fn auto_trim(&self, bus: usize) -> Option<AutoTrimConfig> {
+        self.auto_trims.get(bus)?.as_ref().map(AutoTrim::config)
+    }
+
+    /// Gain the auto-trim of `bus` applies now, in dB (0 or below)
+    pub fn auto_trim_db(&self, bus: usize) -> Option<f32> {
+        self.auto_trims.get(bus)?.as_ref().map(AutoTrim::trim_db)
+    }
+
+    /// Where auto-trims publish `EngineEvent::HeadroomExceeded`
+    pub fn set_event_publisher(&mut self, publisher: Option<EventPublisher>) {
+        self.events = publisher;
+    }
+
+    /// Touch every buffer the mix uses, and those of the sources, bus effects
+    /// and talkback (see `rt_processing::prefault`). Not RT-safe.
+    pub fn prefault(&mut self, memory: &mut Prefault) {
+        // commands queued so far add sources that should be covered too
+        self.apply_commands();
+        memory.touch_all(&mut self.scratch);
+        memory.touch_all(&mut self.source_buffer);
+        for bus in &mut self.bus_buffers {
+            memory.touch_all(bus);
+        }
+        memory.touch_all(&mut self.listen);
+        memory.touch(&mut self.gain_ramp);
+        memory.touch(&mut self.send_ramp);
+        memory.touch_all(&mut self.pan_ramps);
+        memory.touch(&mut self.bus_targets);
+        memory.touch(&mut self.bus_active);
+        memory.touch(&mut self.bus_gains);
+        memory.touch(&mut self.return_gains);
+        for routed in self.sources.write().iter_mut() {
+            routed.source.prefault(memory);
+            routed.effects.prefault(memory);
+        }
+        for chain in self.bus_effects.write().iter_mut() {
+            chain.prefault(memory);
+        }
+        if let Some(talkback) = &mut self.talkback {
+            memory.touch_all(&mut talkback.buffer);
+            talkback.source.prefault(memory);
+        }
+    }
+
+    /// Glide time in ms and curve of source gain and pan changes
+    pub fn param_ramp(&self) -> (f32, RampShape) {
+        (self.param_ramp_ms, self.param_ramp_shape)
+    }
+
+    /// Control handle of source `id`, `None` if there is no such source
+    pub fn handle(&self, id: SourceId) -> Option<SourceHandle> {
+        let sources = self.sources.read();
+        let routed = sources.iter().find(|routed| routed.id == id)?;
+        Some(SourceHandle { id, alive: Arc::clone(&routed.alive), commands: self.commands() })
+    }
+
+    /// Current position of source `id` in the source list
+    pub fn index_of(&self, id: SourceId) -> Option<usize> {
+        self.sources.read().iter().position(|routed| routed.id == id)
+    }
+
+    /// Drop source `id`. Returns `false` if there is no such source.
+    pub fn remove(&self, id: SourceId) -> bool {
+        let mut sources = self.sources.write();
+        let before = sources.len();
+        sources.retain(|routed| routed.id != id);
+        sources.len() != before
+    }
+
+    /// Set the gain of source `id`, which glides there over the param ramp.
+    /// Returns `false` if there is no such source.
+    pub fn set_gain(&self, id: SourceId, gain: f32) -> bool {
+        source_mut(&mut self.sources.write(), id).map(|routed| routed.gain = gain).is_some()
+    }
+
+    pub fn set_pan(&self, id: SourceId, pan: Pan) -> bool {
+        source_mut(&mut self.sources.write(), id).map(|routed| routed.pan = pan).is_some()
+    }
+
+    /// Move source `id` to `bus` (0 = master). Returns `false` if there is no
+    /// such source.
+    pub fn set_bus(&self, id: SourceId, bus: usize) -> bool {
+        source_mut(&mut self.sources.write(), id).map(|routed| routed.bus = bus).is_some()
+    }
+
+    /// Swap the node source `id` renders for `source`, keeping its mix
+    /// settings, trims, inserts and sends. Returns `false` if there is no such
+    /// source.
+    pub fn replace_source(&self, id: SourceId, source: Box<dyn AudioSource + 'static>) -> bool {
+        let mut source = prepared(source, self.channels, self.sample_rate, self.max_frames());
+        match source_mut(&mut self.sources.write(), id) {
+            Some(routed) => {
+                std::mem::swap(&mut routed.source, &mut source);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Keep source `id` out of the main mix. Returns `false` if there is no such source.
+    pub fn set_mute(&self, id: SourceId, mute: bool) -> bool {
+        source_mut(&mut self.sources.write(), id).map(|routed| routed.mute = mute).is_some()
+    }
+
+    /// While any source is soloed, only soloed sources reach the main mix.
+    /// Returns `false` if there is no such source.
+    pub fn set_solo(&self, id: SourceId, solo: bool) -> bool {
+        source_mut(&mut self.sources.write(), id).map(|routed| routed.solo = solo).is_some()
+    }
+
+    pub fn is_muted(&self, id: SourceId) -> bool {
+        self.sources.read().iter().any(|routed| routed.id == id && routed.mute)
+    }
+
+    pub fn is_soloed(&self, id: SourceId) -> bool {
+        self.sources.read().iter().any(|routed| routed.id == id && routed.solo)
+    }
+
+    /// Fade out and drop source `id` once `lifetime` is up, counted from when
+    /// it was added; `None` keeps it until removed. A source whose lifetime
+    /// has already run out goes at the next block. Returns `false` if there is
+    /// no such source.
+    pub fn set_lifetime(&self, id: SourceId, lifetime: Option<Lifetime>) -> bool {
+        source_mut(&mut self.sources.write(), id).map(|routed| routed.lifetime = lifetime).is_some()
+    }
+
+    pub fn lifetime(&self, id: SourceId) -> Option<Lifetime> {
+        self.sources.read().iter().find(|routed| routed.id == id).and_then(|routed| routed.lifetime)
+    }
+
+    /// Replace the tags of source `id`, which the `*_tagged` methods select
+    /// by. A source can carry any number of tags. Returns `false` if there is
+    /// no such source.
+    pub fn set_tags(&self, id: SourceId, tags: &[&str]) -> bool {
+        let tags = tags.iter().map(|tag| tag.to_string()).collect();
+        source_mut(&mut self.sources.write(), id).map(|routed| routed.tags = tags).is_some()
+    }
+
+    /// Tags of source `id`, empty if there is no such source
+    pub fn tags(&self, id: SourceId) -> Vec<String> {
+        self.sources.read().iter().find(|routed| routed.id == id).map(|routed| routed.tags.clone()).unwrap_or_default()
+    }
+
+    /// Sources tagged `tag`, in mixing order
+    pub fn tagged(&self, tag: &str) -> Vec<SourceId> {
+        self.sources.read().iter().filter(|routed| routed.has_tag(tag)).map(|routed| routed.id).collect()
+    }
+
+    /// Apply `f` to every source tagged `tag` under one lock, so the change
+    /// lands in a single block; returns how many there were
+    fn for_tagged(&self, tag: &str, mut f: impl FnMut(&mut RoutedSource)) -> usize {
+        let mut sources = self.sources.write();
+        sources.iter_mut().filter(|routed| routed.has_tag(tag)).map(|routed| f(routed)).count()
+    }
+
+    /// Mute or unmute every source tagged `tag`; returns how many there were
+    pub fn set_mute_tagged(&self, tag: &str, mute: bool) -> usize {
+        self.for_tagged(tag, |routed| routed.mute = mute)
+    }
+
+    pub fn set_solo_tagged(&self, tag: &str, solo: bool) -> usize {
+        self.for_tagged(tag, |routed| routed.solo = solo)
+    }
+
+    /// Set the gain of every source tagged `tag`; each glides there as with
+    /// `set_gain`. Returns how many there were.
+    pub fn set_gain_tagged(&self, tag: &str, gain: f32) -> usize {
+        self.for_tagged(tag, |routed| routed.gain = gain)
+    }
+
+    /// Drop every source tagged `tag`; returns how many there were
+    pub fn remove_tagged(&self, tag: &str) -> usize {
+        let mut sources = self.sources.write();
+        let before = sources.len();
+        sources.retain(|routed| !routed.has_tag(tag));
+        before - sources.len()
+    }
+
+    /// Send (or stop sending with `None`) source `id` to the listen bus.
+    /// Returns `false` if there is no such source.
+    pub fn set_listen(&self, id: SourceId, mode: Option<ListenMode>) -> bool {
+        source_mut(&mut self.sources.write(), id).map(|routed| routed.listen = mode).is_some()
+    }
+
+    pub fn listen(&self, id: SourceId) -> Option<ListenMode> {
+        self.sources.read().iter().find(|routed| routed.id == id).and_then(|routed| routed.listen)
+    }
+
+    /// Use `channel` of `source` (e.g. an input capture) as the talkback
+    /// microphone. While keyed it ducks the listen bus and is mixed onto it,
+    /// leaving the main output alone. The source is only rendered when the
+    /// listen bus is, see `process_with_listen`. Replaces any earlier talkback.
+    pub fn set_talkback(&mut self, source: Box<dyn AudioSource + 'static>, channel: usize) -> TalkbackControl {
+        let control = TalkbackControl::new();
+        self.talkback = Some(Talkback {
+            source,
+            channel: channel.min(self.channels.saturating_sub(1)),
+            control: control.clone(),
+            buffer: self.scratch.clone(),
+            ramp: OnePoleState::default(),
+        });
+        control
+    }
+
+    pub fn clear_talkback(&mut self) {
+        self.talkback = None;
+    }
+
+    /// Whether any source is sent to the listen bus
+    pub fn is_listening(&self) -> bool {
+        self.sources.read().iter().any(|routed| routed.listen.is_some())
+    }
+
+    /// Set (or clear with `None`) the high-pass trim of source `id`.
+    /// Returns `false` if there is no such source.
+    pub fn set_high_pass_trim(&self, id: SourceId, trim: Option<Trim>) -> bool {
+        source_mut(&mut self.sources.write(), id).map(|routed| routed.trim.high_pass.set(trim)).is_some()
+    }
+
+    /// Set (or clear with `None`) the low-pass trim of source `id`.
+    /// Returns `false` if there is no such source.
+    pub fn set_low_pass_trim(&self, id: SourceId, trim: Option<Trim>) -> bool {
+        source_mut(&mut self.sources.write(), id).map(|routed| routed.trim.low_pass.set(trim)).is_some()
     }
 
     pub fn clear_sources(&self) {
         self.sources.write().clear();
     }
 
+    pub fn num_sources(&self) -> usize {
+        self.sources.read().len()
+    }
+
+    /// Append an insert effect to source `id`. Returns `false` if there is no
+    /// such source.
+    pub fn add_source_effect(&self, id: SourceId, effect: Box<dyn Effect>) -> bool {
+        let (channels, max_frames) = (self.channels, self.max_frames());
+        source_mut(&mut self.sources.write(), id)
+            .map(|routed| {
+                routed.effects.prepare(channels, max_frames);
+                routed.effects.push(effect);
+            })
+            .is_some()
+    }
+
+    /// Replace the whole insert chain of source `id`. Returns `false` if there
+    /// is no such source.
+    pub fn set_source_effects(&self, id: SourceId, mut chain: EffectChain) -> bool {
+        chain.prepare(self.channels, self.max_frames());
+        source_mut(&mut self.sources.write(), id).map(|routed| routed.effects = chain).is_some()
+    }
+
+    /// Put `effects` in place of the inserts in `range` on source `id`,
+    /// leaving the rest of its chain (and their state) alone; see
+    /// `EffectChain::splice`. Returns `false` if there is no such source.
+    pub fn splice_source_effects(&self, id: SourceId, range: Range<usize>, mut effects: Vec<Box<dyn Effect>>) -> bool {
+        let (channels, max_frames) = (self.channels, self.max_frames());
+        source_mut(&mut self.sources.write(), id)
+            .map(|routed| {
+                routed.effects.prepare(channels, max_frames);
+                routed.effects.splice(range, &mut effects);
+            })
+            .is_some()
+    }
+
+    /// Wet/dry balance of the insert chain of source `id`. Returns `false` if
+    /// there is no such source.
+    pub fn set_source_effects_mix(&self, id: SourceId, mix: f32) -> bool {
+        source_mut(&mut self.sources.write(), id).map(|routed| routed.effects.set_mix(mix)).is_some()
+    }
+
+    /// Send source `id` to aux `bus` at `level` (linear), or change the level
+    /// of an existing send. A pre-fader send ignores the source's gain. The
+    /// send is skipped while its bus is disabled or the source is muted or
+    /// solo'd out. Returns `false` for the master bus, a bus that doesn't
+    /// exist, a missing source or one with `MAX_SENDS` sends already.
+    pub fn set_send(&self, id: SourceId, bus: usize, level: f32, pre_fader: bool) -> bool {
+        if !(1..self.num_buses).contains(&bus) {
+            return false;
+        }
+        source_mut(&mut self.sources.write(), id).is_some_and(|routed| routed.set_send(bus, level, pre_fader))
+    }
+
+    /// Stop sending source `id` to `bus`. Returns `false` if there was no such send.
+    pub fn remove_send(&self, id: SourceId, bus: usize) -> bool {
+        source_mut(&mut self.sources.write(), id).is_some_and(|routed| routed.remove_send(bus))
+    }
+
+    /// Level of the send from source `id` to `bus`, `None` if there is none
+    pub fn send_level(&self, id: SourceId, bus: usize) -> Option<f32> {
+        let sources = self.sources.read();
+        let routed = sources.iter().find(|routed| routed.id == id)?;
+        routed.sends.iter().find(|send| send.bus == bus).map(|send| send.level)
+    }
+
+    /// Linear gain of aux `bus` where it returns to the master mix (default
+    /// 1.0), after its inserts; changes glide across one block. Returns
+    /// `false` for the master bus (0) or a bus that doesn't exist.
+    pub fn set_bus_return(&self, bus: usize, gain: f32) -> bool {
+        match self.bus_returns.get(bus) {
+            Some(cell) if bus > 0 => {
+                cell.store(gain.max(0.0));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn bus_return(&self, bus: usize) -> Option<f32> {
+        self.bus_returns.get(bus).map(AtomicCell::load)
+    }
+
+    /// Append an insert effect to `bus` (0 = master).
+    /// Returns `false` if there is no such bus.
+    pub fn add_bus_effect(&self, bus: usize, effect: Box<dyn Effect>) -> bool {
+        match self.bus_effects.write().get_mut(bus) {
+            Some(chain) => {
+                chain.push(effect);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace the whole insert chain of `bus`.
+    /// Returns `false` if there is no such bus.
+    pub fn set_bus_effects(&self, bus: usize, mut chain: EffectChain) -> bool {
+        chain.prepare(self.channels, self.max_frames());
+        match self.bus_effects.write().get_mut(bus) {
+            Some(slot) => {
+                *slot = chain;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Put `effects` in place of the inserts in `range` on `bus`, leaving the
+    /// rest of its chain (and their state) alone; see `EffectChain::splice`.
+    /// Returns `false` if there is no such bus.
+    pub fn splice_bus_effects(&self, bus: usize, range: Range<usize>, mut effects: Vec<Box<dyn Effect>>) -> bool {
+        self.bus_effects.write().get_mut(bus).map(|chain| chain.splice(range, &mut effects)).is_some()
+    }
+
+    /// Wet/dry balance of the insert chain of `bus`. Returns `false` if there
+    /// is no such bus.
+    pub fn set_bus_effects_mix(&self, bus: usize, mix: f32) -> bool {
+        self.bus_effects.write().get_mut(bus).map(|chain| chain.set_mix(mix)).is_some()
+    }
+
+    pub fn clear_bus_effects(&self, bus: usize) {
+        if let Some(chain) = self.bus_effects.write().get_mut(bus) {
+            chain.clear();
+        }
+    }
+
+    /// Enable or disable aux `bus`. A disabled bus is skipped entirely: its
+    /// sources aren't rendered and its inserts don't run, so their state
+    /// (phases, delay lines, envelopes) picks up where it left off. The bus
+    /// fades out before it stops and fades back in when re-enabled. Returns
+    /// `false` for the master bus (0) or a bus that doesn't exist.
+    pub fn set_bus_enabled(&self, bus: usize, enabled: bool) -> bool {
+        match self.bus_enabled.get(bus) {
+            Some(flag) if bus > 0 => {
+                flag.store(enabled, Ordering::Release);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_bus_enabled(&self, bus: usize) -> bool {
+        self.bus_enabled.get(bus).is_some_and(|flag| flag.load(Ordering::Acquire))
+    }
+
+    /// Replace the tags of `bus`, which `tagged_buses` and the `*_tagged_buses`
+    /// methods select by. Returns `false` if there is no such bus.
+    pub fn set_bus_tags(&mut self, bus: usize, tags: &[&str]) -> bool {
+        match self.bus_tags.get_mut(bus) {
+            Some(own) => {
+                *own = tags.iter().map(|tag| tag.to_string()).collect();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn bus_tags(&self, bus: usize) -> &[String] {
+        self.bus_tags.get(bus).map_or(&[], Vec::as_slice)
+    }
+
+    /// Buses tagged `tag`, in order
+    pub fn tagged_buses(&self, tag: &str) -> Vec<usize> {
+        let tagged = |tags: &Vec<String>| tags.iter().any(|own| own == tag);
+        self.bus_tags.iter().enumerate().filter(|(_, tags)| tagged(tags)).map(|(bus, _)| bus).collect()
+    }
+
+    /// `set_bus_enabled` on every bus tagged `tag`; returns how many were
+    /// changed, leaving out the master bus
+    pub fn set_bus_enabled_tagged(&self, tag: &str, enabled: bool) -> usize {
+        self.tagged_buses(tag).into_iter().filter(|&bus| self.set_bus_enabled(bus, enabled)).count()
+    }
+
+    /// `set_bus_return` on every bus tagged `tag`; returns how many were
+    /// changed, leaving out the master bus
+    pub fn set_bus_return_tagged(&self, tag: &str, gain: f32) -> usize {
+        self.tagged_buses(tag).into_iter().filter(|&bus| self.set_bus_return(bus, gain)).count()
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    pub fn num_buses(&self) -> usize {
+        self.num_buses
+    }
+
+    /// Largest block `process` accepts, in frames
+    pub fn max_frames(&self) -> usize {
+        self.scratch.first().map_or(0, Vec::len)
+    }
+
+    /// Sub-block length (see `CONTROL_BLOCK_FRAMES`); 0 processes each buffer
+    /// in one go, trading block-size invariance for fewer node calls
+    pub fn set_control_block(&mut self, frames: usize) {
+        self.control_block = frames;
+    }
+
+    pub fn control_block(&self) -> usize {
+        self.control_block
+    }
+
+    /// Mix sources into their buses in Q15 (see `rt_processing::fixed`)
+    /// instead of f32. Gains and pans are capped just below unity and a bus
+    /// saturates at full scale; sends, buses and the master stay f32. On by
+    /// default for routers built by `VoiceProcessor`.
+    #[cfg(feature = "fixed-point")]
+    pub fn set_fixed_point_mix(&mut self, enabled: bool) {
+        self.fixed_mix = enabled.then(|| FixedMix::new(self.channels, self.num_buses, self.max_frames()));
+    }
+
+    #[cfg(feature = "fixed-point")]
+    pub fn fixed_point_mix(&self) -> bool {
+        self.fixed_mix.is_some()
+    }
+
+    /// Drive `transport` from this router: it moves on by every sub-block
+    /// mixed, so sources reading its `state` while rendering see where their
+    /// block starts. `None` detaches it where it stands.
+    pub fn set_transport(&mut self, transport: Option<Transport>) {
+        self.transport = transport;
+    }
+
+    pub fn transport(&self) -> Option<&Transport> {
+        self.transport.as_ref()
+    }
+
+    /// Frames processed since the router was created
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
     /// Process all sources → mix into interleaved output buffer
     pub fn process(&mut self, output: &mut [f32], perf_monitor: Option<&PerformanceMonitor>) {
+        self.process_internal(output, None, None, perf_monitor);
+    }
+
+    /// Like `process`, additionally copying each bus (after its inserts, before
+    /// the master inserts) into `stems[bus]`, interleaved. Bus 0's stem holds the
+    /// sources routed straight to master. Each stem must hold `output.len()`
+    /// samples. Used for stem bounces.
+    pub fn process_with_stems(
+        &mut self,
+        output: &mut [f32],
+        stems: &mut [&mut [f32]],
+        perf_monitor: Option<&PerformanceMonitor>,
+    ) {
+        self.process_internal(output, Some(stems), None, perf_monitor);
+    }
+
+    /// Like `process`, additionally writing the listen bus into `listen`
+    /// (interleaved, `output.len()` samples) for a monitor or headphone output.
+    /// The listen bus carries the sum of the listened sources, or the master mix
+    /// while no source is listened to, like a console's monitor section.
+    pub fn process_with_listen(
+        &mut self,
+        output: &mut [f32],
+        listen: &mut [f32],
+        perf_monitor: Option<&PerformanceMonitor>,
+    ) {
+        self.process_internal(output, None, Some(listen), perf_monitor);
+    }
+
+    fn process_internal(
+        &mut self,
+        output: &mut [f32],
+        mut stems: Option<&mut [&mut [f32]]>,
+        mut listen: Option<&mut [f32]>,
+        perf_monitor: Option<&PerformanceMonitor>,
+    ) {
+        let frames = output.len() / self.channels;
+        let _audio_path = enter_audio_path();
+        self.apply_commands();
+        let _span = trace::block_span(frames);
+
+        // split on the control grid so sub-blocks don't depend on the buffer size
+        let mut done = 0;
+        while done < frames {
+            let len = match self.control_block {
+                0 => frames - done,
+                grid => (grid - (self.position % grid as u64) as usize).min(frames - done),
+            };
+            let range = done * self.channels..(done + len) * self.channels;
+            let sub_listen = listen.as_deref_mut().map(|l| &mut l[range.clone()]);
+            let sub_stems = stems.as_deref_mut().map(|stems| (stems, range.start));
+            if let Some(transport) = &self.transport {
+                transport.advance(len);
+            }
+            self.process_block(&mut output[range], sub_stems, sub_listen);
+            self.position += len as u64;
+            done += len;
+        }
+
+        let _guard = perf_monitor.map(|p| p.scoped_callback());
+
+        if let Some(monitor) = perf_monitor {
+            monitor.add_frames_processed(frames as u64);
+        }
+    }
+
+    /// Mix one sub-block. `stems` are whole buffers, written from `offset` on.
+    fn process_block(
+        &mut self,
+        output: &mut [f32],
+        stems: Option<(&mut [&mut [f32]], usize)>,
+        listen: Option<&mut [f32]>,
+    ) {
         let frames = output.len() / self.channels;
+        let channels = self.channels;
 
         // zero master scratch
         for ch in 0..self.channels {
             self.scratch[ch][..frames].fill(0.0);
         }
+        let mut listening = false;
+        if listen.is_some() {
+            self.listen.iter_mut().for_each(|ch| ch[..frames].fill(0.0));
+        }
 
-        // allocate + zero bus buffers: [bus][channel][frame]
-        let mut bus_buffers: Vec<Vec<Vec<f32>>> =
-            (0..self.num_buses)
-                .map(|_| (0..self.channels).map(|_| vec![0.0; frames]).collect())
-                .collect();
+        // zero bus buffers: [bus][channel][frame]
+        for bus in &mut self.bus_buffers {
+            bus.iter_mut().for_each(|ch| ch[..frames].fill(0.0));
+        }
+        self.bus_sources.fill(0);
 
         // mix all sources into their assigned bus
+        // a disabled bus keeps running until its fade-out is done
+        for ((target, active), (flag, &gain)) in
+            self.bus_targets.iter_mut().zip(&mut self.bus_active).zip(self.bus_enabled.iter().zip(&self.bus_gains))
+        {
+            *target = if flag.load(Ordering::Acquire) { 1.0 } else { 0.0 };
+            *active = *target > 0.0 || gain > 0.0;
+        }
+
+        // lifetimes in bars count at the transport's tempo and meter
+        let (beats_per_frame, bar_beats) = match &self.transport {
+            Some(transport) => {
+                let state = transport.state();
+                (state.beats_per_frame(), state.signature.bar_length())
+            }
+            None => (DEFAULT_TEMPO_BPM as f64 / 60.0 / self.sample_rate as f64, TimeSignature::default().bar_length()),
+        };
+
         let mut guard = self.sources.write();
+        let soloing = guard.iter().any(|routed| routed.solo);
+        let mut finished = false;
         for routed in guard.iter_mut() {
-            // temporary buffer for this source [channel][frame]
-            let mut temp: Vec<Vec<f32>> = (0..self.channels)
-                .map(|_| vec![0.0; frames])
-                .collect();
+            let bus = routed.bus.min(self.num_buses - 1);
+            if !self.bus_active[bus] {
+                continue;
+            }
 
-            let mut views: Vec<&mut [f32]> =
-                temp.iter_mut().map(|c| &mut c[..]).collect();
+            // render target for this source [channel][frame]
+            let mut views = channel_views(&mut self.source_buffer, frames);
+            let views = &mut views[..channels];
+            views.iter_mut().for_each(|v| v.fill(0.0));
 
-            routed.source.render(&mut views, frames, self.sample_rate);
+            routed.source.render(views, frames, self.sample_rate);
+            if routed.listen == Some(ListenMode::Pfl) && listen.is_some() {
+                listening = true;
+                if let [left, right] = &mut self.listen[..] {
+                    for ((l, r), s) in left[..frames].iter_mut().zip(&mut right[..frames]).zip(&views[0][..frames]) {
+                        *l += s;
+                        *r += s;
+                    }
+                } else {
+                    for (bus_ch, source_ch) in self.listen.iter_mut().zip(views.iter()) {
+                        bus_ch[..frames].iter_mut().zip(source_ch.iter()).for_each(|(l, s)| *l += s);
+                    }
+                }
+            }
+            routed.trim.process(views, frames, self.sample_rate);
+            if !routed.effects.is_empty() {
+                if let Some(noise) = &mut self.noise_floor {
+                    noise.apply(views, frames);
+                }
+                routed.effects.process(views, frames, self.sample_rate);
+            }
+            routed.age(views, frames, self.sample_rate, beats_per_frame, bar_beats);
+            finished |= routed.is_done();
 
-            let bus = routed.bus.min(self.num_buses - 1);
+            // gain and pan glide sample by sample towards the latest values
+            routed.update_ramps(self.sample_rate);
+            let gains = &mut self.gain_ramp[..frames];
+            let [lefts, rights] = &mut self.pan_ramps;
+            let (lefts, rights) = (&mut lefts[..frames], &mut rights[..frames]);
+            routed.gain_ramp.fill(gains);
+            routed.pan_ramps[0].fill(lefts);
+            routed.pan_ramps[1].fill(rights);
 
-            if self.channels == 2 {
+            // muted or solo'd out: kept out of the main mix, still listenable below
+            let audible = !routed.mute && (routed.solo || !soloing);
+            if audible {
+                self.bus_sources[bus] += 1;
+            }
+            #[cfg(feature = "fixed-point")]
+            let fixed = match &mut self.fixed_mix {
+                Some(fixed) if audible => {
+                    fixed.mix(bus, views, gains, (lefts, rights), &routed.meter);
+                    true
+                }
+                _ => false,
+            };
+            #[cfg(not(feature = "fixed-point"))]
+            let fixed = false;
+            let bus_buffer = &mut self.bus_buffers[bus];
+            if fixed {
+                // mixed in Q15 above
+            } else if audible && self.channels == 2 {
                 // stereo panning for mono → stereo
-                let (lg, rg) = routed.pan.gains();
+                let (mut left, mut right) = (BlockLevel::default(), BlockLevel::default());
                 for i in 0..frames {
                     // assume source filled views[0] as mono
-                    let s = views[0][i] * routed.gain;
-                    bus_buffers[bus][0][i] += s * lg;
-                    bus_buffers[bus][1][i] += s * rg;
+                    let s = views[0][i] * gains[i];
+                    let (l, r) = (s * lefts[i], s * rights[i]);
+                    bus_buffer[0][i] += l;
+                    bus_buffer[1][i] += r;
+                    left.add(l);
+                    right.add(r);
                 }
-            } else {
+                routed.meter.record(0, left, frames);
+                routed.meter.record(1, right, frames);
+            } else if audible {
                 // generic n-channel, apply gain only
                 for ch in 0..self.channels {
+                    let mut level = BlockLevel::default();
                     for i in 0..frames {
-                        bus_buffers[bus][ch][i] += views[ch][i] * routed.gain;
+                        let s = views[ch][i] * gains[i];
+                        bus_buffer[ch][i] += s;
+                        level.add(s);
+                    }
+                    routed.meter.record(ch, level, frames);
+                }
+            }
+
+            // sends glide even while unheard, so unmuting doesn't jump
+            for send in routed.sends.iter_mut() {
+                send.ramp.prepare(self.sample_rate);
+                send.ramp.set_target(send.level);
+                let levels = &mut self.send_ramp[..frames];
+                send.ramp.fill(levels);
+                let bus = send.bus.min(self.num_buses - 1);
+                if !audible || bus == 0 || !self.bus_active[bus] {
+                    continue;
+                }
+                if !send.pre_fader {
+                    levels.iter_mut().zip(gains.iter()).for_each(|(level, gain)| *level *= gain);
+                }
+                let bus_buffer = &mut self.bus_buffers[bus];
+                if self.channels == 2 {
+                    for i in 0..frames {
+                        let s = views[0][i] * levels[i];
+                        bus_buffer[0][i] += s * lefts[i];
+                        bus_buffer[1][i] += s * rights[i];
+                    }
+                } else {
+                    for (bus_ch, source_ch) in bus_buffer.iter_mut().zip(views.iter()) {
+                        for ((b, s), level) in bus_ch[..frames].iter_mut().zip(source_ch.iter()).zip(levels.iter()) {
+                            *b += s * level;
+                        }
+                    }
+                }
+            }
+
+            if routed.listen == Some(ListenMode::Afl) && listen.is_some() {
+                listening = true;
+                if let [left, right] = &mut self.listen[..] {
+                    for (i, (l, r)) in left[..frames].iter_mut().zip(&mut right[..frames]).enumerate() {
+                        let s = views[0][i] * gains[i];
+                        *l += s * lefts[i];
+                        *r += s * rights[i];
+                    }
+                } else {
+                    for (bus_ch, source_ch) in self.listen.iter_mut().zip(views.iter()) {
+                        for (i, (l, s)) in bus_ch[..frames].iter_mut().zip(source_ch.iter()).enumerate() {
+                            *l += s * gains[i];
+                        }
                     }
                 }
             }
         }
 
-        // finally mix all buses into master (bus 0 is master)
-        for bus in 0..self.num_buses {
-            for ch in 0..self.channels {
-                for i in 0..frames {
-                    self.scratch[ch][i] += bus_buffers[bus][ch][i];
+        // one-shots that ran out, and sources that lived out their lifetime,
+        // leave after their last block
+        if finished {
+            let mut index = 0;
+            while index < guard.len() {
+                if !guard[index].is_done() {
+                    index += 1;
+                } else {
+                    self.reclaimer.retire(Retired::Source(guard.remove(index)));
+                }
+            }
+        }
+        drop(guard);
+        #[cfg(feature = "fixed-point")]
+        if let Some(fixed) = &mut self.fixed_mix {
+            fixed.finish(&mut self.bus_buffers, frames);
+        }
+
+        // aux bus auto-trims, then inserts
+        let buses = self.bus_buffers.iter_mut().zip(self.bus_sources.iter().zip(&self.bus_active));
+        for (trim, (buffers, (&sources, &active))) in self.auto_trims.iter_mut().zip(buses).skip(1) {
+            if let Some(trim) = trim
+                && active
+                && let Some(event) = trim.process(buffers, frames, sources, self.sample_rate, self.position)
+                && let Some(events) = &self.events
+            {
+                events.publish(event);
+            }
+        }
+        let mut effects = self.bus_effects.write();
+        for (bus, (chain, &active)) in self.bus_buffers.iter_mut().zip(effects.iter_mut().zip(&self.bus_active)).skip(1)
+        {
+            if active && !chain.is_empty() {
+                let mut views = channel_views(bus, frames);
+                if let Some(noise) = &mut self.noise_floor {
+                    noise.apply(&mut views[..channels], frames);
+                }
+                chain.process(&mut views[..channels], frames, self.sample_rate);
+            }
+        }
+
+        // fade toggled buses
+        let step = 1.0 / (BUS_RAMP_SECONDS * self.sample_rate).max(1.0);
+        for ((bus, gain), &target) in self.bus_buffers.iter_mut().zip(&mut self.bus_gains).zip(&self.bus_targets) {
+            if *gain == target {
+                continue;
+            }
+            for i in 0..frames {
+                *gain = if target > *gain { (*gain + step).min(target) } else { (*gain - step).max(target) };
+                for samples in bus.iter_mut() {
+                    samples[i] *= *gain;
+                }
+            }
+        }
+
+        for ((meter, bus), &active) in self.meters.buses.iter().zip(&self.bus_buffers).zip(&self.bus_active) {
+            if active {
+                meter.measure(bus, frames);
+            }
+        }
+
+        if let Some((stems, offset)) = stems {
+            for (stem, bus) in stems.iter_mut().zip(&self.bus_buffers) {
+                for (ch, samples) in bus.iter().enumerate() {
+                    for (i, &s) in samples[..frames].iter().enumerate() {
+                        stem[offset + i * self.channels + ch] = s;
+                    }
+                }
+            }
+        }
+
+        // finally mix all buses into master (bus 0 is master) at their return gains
+        for ((bus, applied), target) in self.bus_buffers.iter().zip(&mut self.return_gains).zip(self.bus_returns.iter())
+        {
+            let (from, to) = (*applied, target.load());
+            *applied = to;
+            let step = (to - from) / frames as f32;
+            for (master, bus_ch) in self.scratch.iter_mut().zip(bus) {
+                for (i, (m, b)) in master[..frames].iter_mut().zip(&bus_ch[..frames]).enumerate() {
+                    *m += *b * if from == to { to } else { from + step * (i + 1) as f32 };
                 }
             }
         }
 
+        // the master trim and inserts run on the full mix
+        if let Some(trim) = &mut self.auto_trims[0] {
+            let sources = self.bus_sources.iter().sum();
+            if let Some(event) = trim.process(&mut self.scratch, frames, sources, self.sample_rate, self.position)
+                && let Some(events) = &self.events
+            {
+                events.publish(event);
+            }
+        }
+        if let Some(master) = effects.first_mut()
+            && !master.is_empty()
+        {
+            let mut views = channel_views(&mut self.scratch, frames);
+            if let Some(noise) = &mut self.noise_floor {
+                noise.apply(&mut views[..channels], frames);
+            }
+            master.process(&mut views[..channels], frames, self.sample_rate);
+        }
+        drop(effects);
+        self.meters.master.measure(&self.scratch, frames);
+        self.loudness.measure(&self.scratch, frames);
+
         // write interleaved
         for i in 0..frames {
             for ch in 0..self.channels {
@@ -152,10 +1920,59 @@ impl Router {
             }
         }
 
-        let _guard = perf_monitor.map(|p| p.scoped_callback());
+        if let Some(listen) = listen {
+            if listening {
+                for i in 0..frames {
+                    for ch in 0..self.channels {
+                        listen[i * self.channels + ch] = self.listen[ch][i];
+                    }
+                }
+            } else {
+                listen.copy_from_slice(output);
+            }
+            if let Some(talkback) = &mut self.talkback {
+                talkback.process(listen, self.channels, frames, self.sample_rate);
+            }
+        }
+    }
+}
 
-        if let Some(monitor) = perf_monitor {
-            monitor.add_frames_processed(frames as u64);
+fn source_mut(sources: &mut [Box<RoutedSource>], id: SourceId) -> Option<&mut RoutedSource> {
+    sources.iter_mut().find(|routed| routed.id == id).map(|routed| &mut **routed)
+}
+
+/// `[channel][frame]` views of the first `frames` frames of each buffer, without
+/// allocating; entries past `buffers.len()` are empty
+pub(crate) fn channel_views(buffers: &mut [Vec<f32>], frames: usize) -> [&mut [f32]; MAX_ROUTER_CHANNELS] {
+    let mut views: [&mut [f32]; MAX_ROUTER_CHANNELS] = Default::default();
+    for (view, buffer) in views.iter_mut().zip(buffers.iter_mut()) {
+        *view = &mut buffer[..frames];
+    }
+    views
+}
+
+impl Talkback {
+    /// Duck the interleaved `listen` block and mix the talkback channel onto it
+    fn process(&mut self, listen: &mut [f32], channels: usize, frames: usize, sample_rate: f32) {
+        let mut views = channel_views(&mut self.buffer, frames);
+        let views = &mut views[..channels];
+        views.iter_mut().for_each(|v| v.fill(0.0));
+        self.source.render(views, frames, sample_rate);
+
+        let target = if self.control.is_keyed() { 1.0 } else { 0.0 };
+        if target == 0.0 && self.ramp.value() < 1e-4 {
+            self.ramp.reset();
+            return;
+        }
+        let a = EnvelopeFollower::time_coefficient(TALKBACK_RAMP_SECONDS, sample_rate);
+        let duck = 10f32.powf(self.control.duck_db() / 20.0);
+        let gain = self.control.gain();
+        for (frame, talk) in listen.chunks_exact_mut(channels).zip(&self.buffer[self.channel][..frames]) {
+            let keyed = self.ramp.low_pass(a, target);
+            let cue_gain = 1.0 + (duck - 1.0) * keyed;
+            for s in frame {
+                *s = *s * cue_gain + talk * gain * keyed;
+            }
         }
     }
 }
@@ -0,0 +1,245 @@
+//! Sample-accurate event scheduling.
+//!
+//! Queues like `NoteQueue` are drained at the start of each block, so what
+//! they carry lands on block boundaries and moves with the device buffer
+//! size. An `EventScheduler` takes events stamped with the frame they belong
+//! at instead, and hands each one over with its offset into the block that
+//! contains it; a consumer that splits its rendering there (as
+//! `ScheduledPlayer` does) plays sequenced material exactly on time.
+
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crossbeam::queue::ArrayQueue;
+
+use crate::rt_processing::notes::{ExpressiveNoteEvent, NoteEvent};
+use crate::rt_processing::prefault::Prefault;
+use crate::rt_processing::routing::{AudioSource, MAX_ROUTER_CHANNELS};
+use crate::rt_processing::voices::VoicePool;
+
+/// Something an `EventScheduler` delivers at its frame
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScheduledEvent {
+    Note(NoteEvent),
+    Expressive(ExpressiveNoteEvent),
+    /// Bend every voice by `semitones`
+    PitchBend { semitones: f32 },
+    /// A MIDI controller; `value` is normalized
+    ControlChange { controller: u8, value: f32 },
+    /// Parameter `param` of the receiver changed; voices get it as an NRPN.
+    /// `value` is normalized.
+    Param { param: u16, value: f32 },
+}
+
+/// An event and the frame it belongs at, on the scheduler's timeline
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimedEvent {
+    pub frame: u64,
+    pub event: ScheduledEvent,
+}
+
+/// Control-thread side of an `EventScheduler`. Clones share the queue; when
+/// it is full, new events are dropped and counted rather than blocking.
+#[derive(Clone)]
+pub struct EventSender {
+    queue: Arc<ArrayQueue<TimedEvent>>,
+    position: Arc<AtomicU64>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl EventSender {
+    /// Deliver `event` at `frame`; frames already played are delivered at the
+    /// start of the next block. False if it was dropped.
+    pub fn schedule(&self, frame: u64, event: ScheduledEvent) -> bool {
+        if self.queue.push(TimedEvent { frame, event }).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    pub fn note_on(&self, frame: u64, note: u8, velocity: f32) -> bool {
+        self.schedule(frame, ScheduledEvent::Note(NoteEvent::NoteOn { note, velocity }))
+    }
+
+    pub fn note_off(&self, frame: u64, note: u8) -> bool {
+        self.schedule(frame, ScheduledEvent::Note(NoteEvent::NoteOff { note }))
+    }
+
+    pub fn param(&self, frame: u64, param: u16, value: f32) -> bool {
+        self.schedule(frame, ScheduledEvent::Param { param, value })
+    }
+
+    /// First frame of the next block the scheduler dispatches; schedule
+    /// ahead of it, by at least a device buffer, to be on time
+    pub fn position(&self) -> u64 {
+        self.position.load(Ordering::Acquire)
+    }
+}
+
+/// Audio-thread side: keeps received events in time order and dispatches
+/// those due in each block with their offset into it.
+///
+/// The timeline counts the frames dispatched since the scheduler was made.
+/// Events wait in storage allocated in `new`; nothing on the audio path
+/// allocates, and events beyond `capacity` are dropped and counted.
+pub struct EventScheduler {
+    queue: Arc<ArrayQueue<TimedEvent>>,
+    shared_position: Arc<AtomicU64>,
+    dropped: Arc<AtomicU64>,
+    /// Received events, by frame, then in arrival order
+    pending: Vec<TimedEvent>,
+    position: u64,
+    late: u64,
+}
+
+impl EventScheduler {
+    /// Room for `capacity` events in flight and as many waiting for their frame
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            queue: Arc::new(ArrayQueue::new(capacity)),
+            shared_position: Arc::new(AtomicU64::new(0)),
+            dropped: Arc::new(AtomicU64::new(0)),
+            pending: Vec::with_capacity(capacity),
+            position: 0,
+            late: 0,
+        }
+    }
+
+    pub fn sender(&self) -> EventSender {
+        EventSender {
+            queue: Arc::clone(&self.queue),
+            position: Arc::clone(&self.shared_position),
+            dropped: Arc::clone(&self.dropped),
+        }
+    }
+
+    /// First frame of the next block
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Events received and not yet due
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Events dropped because the queue or the storage was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Events that arrived after their frame was played
+    pub fn late(&self) -> u64 {
+        self.late
+    }
+
+    /// Take in what the senders queued. Also done by `dispatch`. RT-safe.
+    pub fn receive(&mut self) {
+        while let Some(event) = self.queue.pop() {
+            if self.pending.len() == self.pending.capacity() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            let index = self.pending.partition_point(|waiting| waiting.frame <= event.frame);
+            self.pending.insert(index, event);
+        }
+    }
+
+    /// Call `dispatch` with every event due in the next `frames` frames and
+    /// its offset into them, in time order, then move on by `frames`. RT-safe.
+    pub fn dispatch(&mut self, frames: usize, mut dispatch: impl FnMut(usize, ScheduledEvent)) {
+        self.receive();
+        let end = self.position + frames as u64;
+        let due = self.pending.partition_point(|waiting| waiting.frame < end);
+        for TimedEvent { frame, event } in self.pending.drain(..due) {
+            if frame < self.position {
+                self.late += 1;
+            }
+            dispatch(frame.saturating_sub(self.position) as usize, event);
+        }
+        self.position = end;
+        self.shared_position.store(end, Ordering::Release);
+    }
+
+    /// Drop everything queued and waiting, e.g. when the transport stops
+    pub fn clear(&mut self) {
+        while self.queue.pop().is_some() {}
+        self.pending.clear();
+    }
+}
+
+/// Plays a `VoicePool` from an `EventScheduler`, as a routed source: each
+/// block is rendered in stretches between the events due in it, so notes
+/// start and stop, and parameters change, on their exact frame.
+pub struct ScheduledPlayer {
+    scheduler: EventScheduler,
+    pool: VoicePool,
+}
+
+impl ScheduledPlayer {
+    pub fn new(scheduler: EventScheduler, pool: VoicePool) -> Self {
+        Self { scheduler, pool }
+    }
+
+    pub fn sender(&self) -> EventSender {
+        self.scheduler.sender()
+    }
+
+    pub fn scheduler(&self) -> &EventScheduler {
+        &self.scheduler
+    }
+
+    pub fn scheduler_mut(&mut self) -> &mut EventScheduler {
+        &mut self.scheduler
+    }
+
+    pub fn pool(&self) -> &VoicePool {
+        &self.pool
+    }
+
+    pub fn pool_mut(&mut self) -> &mut VoicePool {
+        &mut self.pool
+    }
+}
+
+fn apply(pool: &mut VoicePool, event: ScheduledEvent) {
+    match event {
+        ScheduledEvent::Note(event) => pool.handle(event),
+        ScheduledEvent::Expressive(event) => pool.handle_expressive(event),
+        ScheduledEvent::PitchBend { semitones } => pool.pitch_bend(semitones),
+        ScheduledEvent::ControlChange { controller, value } => pool.control_change(controller, value),
+        ScheduledEvent::Param { param, value } => pool.nrpn(param, value),
+    }
+}
+
+/// Render `range` of `output` from `pool`
+fn render_range(pool: &mut VoicePool, output: &mut [&mut [f32]], range: Range<usize>, sample_rate: f32) {
+    let channels = output.len().min(MAX_ROUTER_CHANNELS);
+    let mut views: [&mut [f32]; MAX_ROUTER_CHANNELS] = Default::default();
+    for (view, samples) in views.iter_mut().zip(output.iter_mut()) {
+        *view = &mut samples[range.clone()];
+    }
+    pool.render(&mut views[..channels], range.len(), sample_rate);
+}
+
+impl AudioSource for ScheduledPlayer {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        let pool = &mut self.pool;
+        let mut done = 0;
+        self.scheduler.dispatch(frames, |offset, event| {
+            if offset > done {
+                render_range(pool, output, done..offset, sample_rate);
+                done = offset;
+            }
+            apply(pool, event);
+        });
+        render_range(pool, output, done..frames, sample_rate);
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        self.pool.prefault(memory);
+    }
+}
@@ -0,0 +1,497 @@
+//! Step sequencer: lanes of steps clocked from the tempo, playing notes on a
+//! `VoicePool` with sample accuracy.
+//!
+//! Each lane plays one note and has its own length, so lanes of different
+//! lengths drift against each other (polymeter). A lane's pattern can be
+//! drawn step by step or generated as a Euclidean rhythm; every step has a
+//! probability of playing and a ratchet count that repeats it within the
+//! step. A `Groove` shifts steps off the grid, for swing or a feel taken from
+//! a played part. A lane can take its pitch from a `TuringMachine` instead of
+//! its fixed note, for a melody that loops and slowly mutates.
+
+use crate::rt_processing::effects::DEFAULT_TEMPO_BPM;
+use crate::rt_processing::groove::Groove;
+use crate::rt_processing::notes::SharedScale;
+use crate::rt_processing::prefault::Prefault;
+use crate::rt_processing::routing::AudioSource;
+use crate::rt_processing::turing::TuringMachine;
+use crate::rt_processing::voices::{MAX_VOICE_CHANNELS, VoicePool};
+use crate::rt_processing::waveform::noise::FastRng;
+
+/// Most repeats of one step
+pub const MAX_RATCHET: u8 = 8;
+/// Longest lane, in steps
+pub const MAX_STEPS: usize = 64;
+
+/// `hits` onsets spread as evenly as possible over `steps`, rotated later by
+/// `rotation` steps (negative rotates earlier). The first step is a hit
+/// before rotation; `hits` is capped at `steps`.
+pub fn euclidean(hits: usize, steps: usize, rotation: isize) -> Vec<bool> {
+    let hits = hits.min(steps);
+    (0..steps)
+        .map(|i| {
+            // Bresenham: a hit wherever the running total wraps
+            let i = (i as isize - rotation).rem_euclid(steps as isize) as usize;
+            (i * hits) % steps < hits
+        })
+        .collect()
+}
+
+/// One step of a lane
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Step {
+    pub active: bool,
+    /// Normalized (0.0 to 1.0)
+    pub velocity: f32,
+    /// Chance of playing each time the step comes round (0.0 to 1.0)
+    pub probability: f32,
+    /// Hits within the step, evenly spaced (1 = a single hit)
+    pub ratchet: u8,
+}
+
+impl Default for Step {
+    /// Inactive; full velocity, always plays and a single hit once activated
+    fn default() -> Self {
+        Self { active: false, velocity: 1.0, probability: 1.0, ratchet: 1 }
+    }
+}
+
+impl Step {
+    pub fn hit(velocity: f32) -> Self {
+        Self { active: true, velocity, ..Self::default() }
+    }
+
+    fn sanitize(self) -> Self {
+        Self {
+            velocity: self.velocity.clamp(0.0, 1.0),
+            probability: self.probability.clamp(0.0, 1.0),
+            ratchet: self.ratchet.clamp(1, MAX_RATCHET),
+            ..self
+        }
+    }
+}
+
+/// Pitch of a lane driven by a `TuringMachine`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TuringPitch {
+    pub machine: TuringMachine,
+    /// Semitones above the lane's note that the machine's value spans
+    pub range: u8,
+}
+
+/// A row of steps playing one note, or notes from a `TuringMachine`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequencerLane {
+    pub note: u8,
+    steps: Vec<Step>,
+    /// Part of each hit the note is held for (0.0 to 1.0)
+    gate: f32,
+    turing: Option<TuringPitch>,
+}
+
+impl SequencerLane {
+    /// `steps` inactive steps (1 to `MAX_STEPS`)
+    pub fn new(note: u8, steps: usize) -> Self {
+        Self {
+            note: note.min(127),
+            steps: vec![Step::default(); steps.clamp(1, MAX_STEPS)],
+            gate: 0.5,
+            turing: None,
+        }
+    }
+
+    /// Lane holding a Euclidean rhythm; see `set_euclidean`
+    pub fn euclidean(note: u8, hits: usize, steps: usize, rotation: isize) -> Self {
+        let mut lane = Self::new(note, steps);
+        lane.set_euclidean(hits, steps, rotation);
+        lane
+    }
+
+    pub fn with_gate(mut self, gate: f32) -> Self {
+        self.set_gate(gate);
+        self
+    }
+
+    pub fn set_gate(&mut self, gate: f32) {
+        self.gate = gate.clamp(0.01, 1.0);
+    }
+
+    pub fn gate(&self) -> f32 {
+        self.gate
+    }
+
+    /// Play from `note` up to `range` semitones above it as `machine` says.
+    /// The machine steps each time a step plays, not on every ratchet; with a
+    /// scale set on the sequencer, the notes are kept in it.
+    pub fn with_turing(mut self, machine: TuringMachine, range: u8) -> Self {
+        self.turing = Some(TuringPitch { machine, range: range.min(127) });
+        self
+    }
+
+    pub fn turing(&self) -> Option<&TuringPitch> {
+        self.turing.as_ref()
+    }
+
+    /// Note of the next step played, stepping the machine if there is one
+    fn next_note(&mut self, scale: &SharedScale) -> u8 {
+        match &mut self.turing {
+            Some(TuringPitch { machine, range }) => {
+                let offset = (machine.step() * *range as f32).round() as u8;
+                scale.quantize(self.note.saturating_add(offset).min(127))
+            }
+            None => self.note,
+        }
+    }
+
+    /// Resize the lane to `steps` and activate the steps of a Euclidean
+    /// rhythm (`euclidean`). Steps keep their velocity, probability and
+    /// ratchet.
+    pub fn set_euclidean(&mut self, hits: usize, steps: usize, rotation: isize) {
+        let steps = steps.clamp(1, MAX_STEPS);
+        self.steps.resize(steps, Step::default());
+        for (step, active) in self.steps.iter_mut().zip(euclidean(hits, steps, rotation)) {
+            step.active = active;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    pub fn step(&self, index: usize) -> Option<&Step> {
+        self.steps.get(index)
+    }
+
+    /// Replace step `index`; false if the lane is shorter
+    pub fn set_step(&mut self, index: usize, step: Step) -> bool {
+        match self.steps.get_mut(index) {
+            Some(slot) => {
+                *slot = step.sanitize();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set the probability of every step
+    pub fn set_probability(&mut self, probability: f32) {
+        for step in &mut self.steps {
+            *step = Step { probability, ..*step }.sanitize();
+        }
+    }
+}
+
+/// Playback state of one lane
+#[derive(Copy, Clone, Default)]
+struct LaneState {
+    /// Hits of the current step still to come
+    hits_left: u8,
+    /// Note of the current step's hits, and of the hit sounding
+    note: u8,
+    sounding: u8,
+    velocity: f32,
+    /// Frames between the current step's hits
+    hit_frames: f64,
+    until_hit: f64,
+    /// Frames until the sounding hit is released
+    until_off: Option<f64>,
+}
+
+/// Plays `SequencerLane`s on a `VoicePool`, as a routed source.
+///
+/// Steps are `step_beats` long at the tempo (a 16th note by default). Hits
+/// and releases land on the exact frame within a block: the pool is
+/// rendered in pieces between them. Probability is rolled from a seeded
+/// generator, so a pattern plays back the same way each time from `reset`.
+///
+/// The groove moves each step's hits by its offset, early or late, and scales
+/// their velocity; ratchets and gates follow the step's swung length.
+pub struct Sequencer {
+    pool: VoicePool,
+    lanes: Vec<SequencerLane>,
+    states: Vec<LaneState>,
+    tempo_bpm: f32,
+    step_beats: f32,
+    groove: Groove,
+    scale: SharedScale,
+    playing: bool,
+    /// Steps started since `reset`
+    step: u64,
+    until_step: f64,
+    /// Frames the current step started ahead of the grid, for an early hit
+    lead: f64,
+    seed: u32,
+    rng: FastRng,
+}
+
+impl Sequencer {
+    pub fn new(pool: VoicePool) -> Self {
+        Self {
+            pool,
+            lanes: Vec::new(),
+            states: Vec::new(),
+            tempo_bpm: DEFAULT_TEMPO_BPM,
+            step_beats: 0.25,
+            groove: Groove::default(),
+            scale: SharedScale::default(),
+            playing: true,
+            step: 0,
+            until_step: 0.0,
+            lead: 0.0,
+            seed: 1,
+            rng: FastRng::new(1),
+        }
+    }
+
+    pub fn with_lane(mut self, lane: SequencerLane) -> Self {
+        self.add_lane(lane);
+        self
+    }
+
+    /// Step length in quarter notes (0.25 = 16th)
+    pub fn with_step_beats(mut self, beats: f32) -> Self {
+        self.set_step_beats(beats);
+        self
+    }
+
+    pub fn with_groove(mut self, groove: Groove) -> Self {
+        self.set_groove(groove);
+        self
+    }
+
+    /// Scale for the notes of lanes driven by a `TuringMachine`
+    pub fn with_scale(mut self, scale: SharedScale) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Seed for the probability rolls
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self.reset();
+        self
+    }
+
+    /// Add a lane; it joins at the current step. Allocates.
+    pub fn add_lane(&mut self, lane: SequencerLane) -> usize {
+        self.lanes.push(lane);
+        self.states.push(LaneState::default());
+        self.lanes.len() - 1
+    }
+
+    /// Replace lane `index`; false if there is no such lane
+    pub fn set_lane(&mut self, index: usize, lane: SequencerLane) -> bool {
+        let Some(slot) = self.lanes.get_mut(index) else { return false };
+        *slot = lane;
+        true
+    }
+
+    pub fn lanes(&self) -> &[SequencerLane] {
+        &self.lanes
+    }
+
+    /// Turing machine of lane `index`, to change its lock and length while
+    /// it plays
+    pub fn turing_mut(&mut self, index: usize) -> Option<&mut TuringMachine> {
+        Some(&mut self.lanes.get_mut(index)?.turing.as_mut()?.machine)
+    }
+
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.tempo_bpm = bpm.max(1.0);
+    }
+
+    pub fn tempo(&self) -> f32 {
+        self.tempo_bpm
+    }
+
+    pub fn set_step_beats(&mut self, beats: f32) {
+        self.step_beats = beats.max(1.0 / 64.0);
+    }
+
+    pub fn step_beats(&self) -> f32 {
+        self.step_beats
+    }
+
+    /// Swing or groove template, counted from the first step; takes effect
+    /// from the next step
+    pub fn set_groove(&mut self, groove: Groove) {
+        self.groove = groove;
+    }
+
+    pub fn groove(&self) -> &Groove {
+        &self.groove
+    }
+
+    pub fn set_scale(&mut self, scale: SharedScale) {
+        self.scale = scale;
+    }
+
+    pub fn scale(&self) -> &SharedScale {
+        &self.scale
+    }
+
+    /// Stop or resume stepping; stopping releases every sounding hit
+    pub fn set_playing(&mut self, playing: bool) {
+        if !playing {
+            for lane in 0..self.lanes.len() {
+                self.release(lane);
+            }
+        }
+        self.playing = playing;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Steps started since the last `reset`
+    pub fn position(&self) -> u64 {
+        self.step
+    }
+
+    /// Back to the first step, with the probability rolls and Turing
+    /// machines restarted
+    pub fn reset(&mut self) {
+        for lane in 0..self.lanes.len() {
+            self.release(lane);
+            if let Some(turing) = &mut self.lanes[lane].turing {
+                turing.machine.reset();
+            }
+        }
+        self.states.iter_mut().for_each(|state| *state = LaneState::default());
+        self.step = 0;
+        self.until_step = 0.0;
+        self.lead = 0.0;
+        self.rng = FastRng::new(self.seed);
+    }
+
+    pub fn pool(&self) -> &VoicePool {
+        &self.pool
+    }
+
+    pub fn pool_mut(&mut self) -> &mut VoicePool {
+        &mut self.pool
+    }
+
+    fn step_frames(&self, sample_rate: f32) -> f64 {
+        60.0 / self.tempo_bpm as f64 * self.step_beats as f64 * sample_rate as f64
+    }
+
+    fn release(&mut self, lane: usize) {
+        if self.states[lane].until_off.take().is_some() {
+            self.pool.note_off(self.states[lane].sounding);
+        }
+    }
+
+    fn hit(&mut self, lane: usize) {
+        self.release(lane);
+        let state = &mut self.states[lane];
+        state.hits_left -= 1;
+        state.until_hit += state.hit_frames;
+        state.until_off = Some(state.hit_frames * self.lanes[lane].gate as f64);
+        state.sounding = state.note;
+        self.pool.note_on(state.note, state.velocity);
+    }
+
+    /// Start the next step on every lane
+    fn start_step(&mut self, step_frames: f64) {
+        let offset = self.groove.offset(self.step) as f64 * step_frames;
+        let next_offset = self.groove.offset(self.step + 1) as f64 * step_frames;
+        // from this step's first hit to the next step's
+        let length = step_frames + next_offset - offset;
+        let scale = self.groove.velocity(self.step);
+        // counted from now, which may be a fraction of a frame past the start
+        let first_hit = self.until_step + self.lead + offset;
+        for lane in 0..self.lanes.len() {
+            let index = (self.step % self.lanes[lane].len() as u64) as usize;
+            let step = self.lanes[lane].steps[index];
+            // roll every step, so changing one step's odds leaves the others' rolls alone
+            let roll = self.rng.next_f32();
+            let state = &mut self.states[lane];
+            state.hits_left = 0;
+            if step.active && roll < step.probability {
+                state.note = self.lanes[lane].next_note(&self.scale);
+                state.hits_left = step.ratchet.max(1);
+                state.velocity = (step.velocity * scale).clamp(0.0, 1.0);
+                state.hit_frames = length / state.hits_left as f64;
+                state.until_hit = first_hit;
+            }
+        }
+        // a step played early starts ahead of the grid
+        let next_lead = (-next_offset).max(0.0);
+        self.until_step += step_frames + self.lead - next_lead;
+        self.lead = next_lead;
+        self.step += 1;
+    }
+
+    /// Apply every hit and release due by now (counters at or below zero)
+    fn fire_due(&mut self, step_frames: f64) {
+        for lane in 0..self.lanes.len() {
+            if self.states[lane].until_off.is_some_and(|off| off <= 0.0) {
+                self.release(lane);
+            }
+        }
+        if self.until_step <= 0.0 {
+            self.start_step(step_frames);
+        }
+        for lane in 0..self.lanes.len() {
+            if self.states[lane].hits_left > 0 && self.states[lane].until_hit <= 0.0 {
+                self.hit(lane);
+            }
+        }
+    }
+
+    /// Frames until the next hit, release or step, from now
+    fn next_event(&self) -> f64 {
+        self.states.iter().fold(self.until_step, |next, state| {
+            let hit = if state.hits_left > 0 { state.until_hit } else { f64::MAX };
+            next.min(hit).min(state.until_off.unwrap_or(f64::MAX))
+        })
+    }
+
+    fn advance(&mut self, frames: f64) {
+        self.until_step -= frames;
+        for state in &mut self.states {
+            state.until_hit -= frames;
+            if let Some(off) = &mut state.until_off {
+                *off -= frames;
+            }
+        }
+    }
+}
+
+impl AudioSource for Sequencer {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if !self.playing || self.lanes.is_empty() {
+            self.pool.render(output, frames, sample_rate);
+            return;
+        }
+        let step_frames = self.step_frames(sample_rate);
+        let mut done = 0;
+        while done < frames {
+            self.fire_due(step_frames);
+            // events land on the first frame at or after their exact time
+            let until = (self.next_event().ceil().max(1.0) as usize).min(frames - done);
+            let mut views: [&mut [f32]; MAX_VOICE_CHANNELS] = Default::default();
+            let channels = output.len().min(MAX_VOICE_CHANNELS);
+            for (view, samples) in views.iter_mut().zip(output.iter_mut()) {
+                *view = &mut samples[done..done + until];
+            }
+            self.pool.render(&mut views[..channels], until, sample_rate);
+            self.advance(until as f64);
+            done += until;
+        }
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        self.pool.prefault(memory);
+    }
+}
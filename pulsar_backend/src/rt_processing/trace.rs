@@ -0,0 +1,73 @@
+//! Instrumentation points, emitted through `tracing` with the `tracing`
+//! feature and compiled out without it.
+//!
+//! Every span and event comes from a `tracing` macro, so its callsite is a
+//! static registered once; with the feature on but the level filtered out,
+//! each point is one relaxed load. Whatever is enabled runs on the thread that
+//! hits it, the audio thread included, so pair this with a subscriber that
+//! writes off-thread (a non-blocking writer) and keep per-block spans at
+//! `trace` level in production.
+//!
+//! Swaps, xruns, skipped callbacks and device events also go to the black box
+//! (`black_box::global`), feature or not.
+
+use crate::rt_processing::black_box::{self, BlackBoxEventKind};
+
+#[cfg(feature = "tracing")]
+pub(crate) struct BlockSpan(#[allow(dead_code)] tracing::span::EnteredSpan);
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct BlockSpan;
+
+/// Span around one router block, closed when the guard drops
+#[inline(always)]
+pub(crate) fn block_span(frames: usize) -> BlockSpan {
+    #[cfg(feature = "tracing")]
+    {
+        BlockSpan(tracing::trace_span!("pulsar.block", frames).entered())
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = frames;
+        BlockSpan
+    }
+}
+
+/// The processor in a `CallbackSlot` was replaced
+#[inline(always)]
+pub(crate) fn processor_swapped() {
+    black_box::record(BlackBoxEventKind::ProcessorSwapped);
+    #[cfg(feature = "tracing")]
+    tracing::debug!(target: "pulsar.swap", "processor swapped");
+}
+
+/// The audio callback couldn't take the processor lock and played silence
+#[inline(always)]
+pub(crate) fn callback_skipped(frames: usize) {
+    black_box::record(BlackBoxEventKind::CallbackSkipped { frames });
+    #[cfg(feature = "tracing")]
+    tracing::warn!(target: "pulsar.xrun", frames, "processor busy, block skipped");
+}
+
+/// An underrun (`overrun == false`) or overrun of `frames` frames (0 when
+/// unknown); `what` names the buffer, e.g. "output" or "input_ring"
+#[inline(always)]
+pub(crate) fn xrun(what: &'static str, overrun: bool, frames: u64) {
+    black_box::record(BlackBoxEventKind::Xrun { what, overrun, frames });
+    #[cfg(feature = "tracing")]
+    tracing::warn!(target: "pulsar.xrun", what, overrun, frames, "xrun");
+}
+
+/// A device appeared, disappeared, became the default, or failed; `detail`
+/// is only formatted when the event is recorded
+#[inline(always)]
+pub(crate) fn device_event(kind: &'static str, detail: &dyn std::fmt::Display) {
+    black_box::record(match kind {
+        "error" => BlackBoxEventKind::Error("device"),
+        kind => BlackBoxEventKind::Device { kind },
+    });
+    #[cfg(feature = "tracing")]
+    tracing::info!(target: "pulsar.device", kind, detail = %detail, "device event");
+    #[cfg(not(feature = "tracing"))]
+    let _ = detail;
+}
@@ -0,0 +1,285 @@
+//! Transport and tempo clock.
+//!
+//! Tempo-synced effects take a tempo in `set_tempo` and keep their own phase,
+//! which is enough for a free-running LFO but not for anything that has to
+//! line up with bars. A `Transport` is the shared playhead: the app thread
+//! plays, stops, locates and changes tempo and meter through it, the router
+//! moves it on by every block it mixes, and sources read where the block
+//! starts in musical time, without locks, to place LFO cycles and delay taps
+//! on the beat.
+//!
+//! Beats are quarter notes throughout, as in `LfoRate::Beats`; the time
+//! signature only decides how they group into bars.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crossbeam::atomic::AtomicCell;
+use crossbeam::queue::ArrayQueue;
+
+use crate::rt_processing::effects::DEFAULT_TEMPO_BPM;
+
+/// Changes a `Transport` can have queued for the next block
+pub const TRANSPORT_COMMAND_CAPACITY: usize = 64;
+
+/// Meter: `beats_per_bar` beats of 1/`beat_unit` notes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimeSignature {
+    pub beats_per_bar: u32,
+    pub beat_unit: u32,
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self { beats_per_bar: 4, beat_unit: 4 }
+    }
+}
+
+impl TimeSignature {
+    pub fn new(beats_per_bar: u32, beat_unit: u32) -> Self {
+        Self { beats_per_bar: beats_per_bar.max(1), beat_unit: beat_unit.max(1) }
+    }
+
+    /// Length of one of the signature's beats in quarter notes (0.5 in 6/8)
+    pub fn beat_length(self) -> f64 {
+        4.0 / self.beat_unit.max(1) as f64
+    }
+
+    /// Length of a bar in quarter notes
+    pub fn bar_length(self) -> f64 {
+        self.beats_per_bar.max(1) as f64 * self.beat_length()
+    }
+
+    /// Bar, beat and fraction of the position `beats` quarter notes in
+    pub fn musical_time(self, beats: f64) -> MusicalTime {
+        let beats = beats.max(0.0);
+        let bar = (beats / self.bar_length()).floor();
+        let in_bar = (beats - bar * self.bar_length()) / self.beat_length();
+        // rounding can leave `in_bar` a hair short of a full bar
+        let beat = (in_bar.floor() as u32).min(self.beats_per_bar.max(1) - 1);
+        MusicalTime { bar: bar as u64, beat, fraction: (in_bar - beat as f64).clamp(0.0, 1.0) }
+    }
+
+    /// Quarter notes from the start to `time`
+    pub fn beats(self, time: MusicalTime) -> f64 {
+        time.bar as f64 * self.bar_length() + (time.beat as f64 + time.fraction) * self.beat_length()
+    }
+}
+
+/// A position in bars and beats, counted from 0; `beat` is in the time
+/// signature's unit and `fraction` is how far into it, from 0 to 1
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MusicalTime {
+    pub bar: u64,
+    pub beat: u32,
+    pub fraction: f64,
+}
+
+/// Where a `Transport` is at the start of a block, and how fast it moves
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TransportState {
+    pub playing: bool,
+    /// Playhead on the sample timeline
+    pub frame: u64,
+    /// Playhead in quarter notes. Follows tempo changes, so it only matches
+    /// `frame` at the current tempo after a locate.
+    pub beats: f64,
+    /// Quarter notes per minute
+    pub tempo_bpm: f32,
+    pub signature: TimeSignature,
+    pub sample_rate: f32,
+}
+
+impl TransportState {
+    /// How far one frame moves the playhead, in quarter notes
+    pub fn beats_per_frame(&self) -> f64 {
+        self.tempo_bpm as f64 / 60.0 / self.sample_rate.max(1.0) as f64
+    }
+
+    /// Quarter notes lasting `frames` frames at the tempo
+    pub fn frames_to_beats(&self, frames: f64) -> f64 {
+        frames * self.beats_per_frame()
+    }
+
+    /// Frames lasting `beats` quarter notes at the tempo, e.g. a synced
+    /// delay time
+    pub fn beats_to_frames(&self, beats: f64) -> f64 {
+        beats / self.beats_per_frame()
+    }
+
+    /// Playhead `offset` frames into the block; stays put while stopped
+    pub fn beats_at(&self, offset: usize) -> f64 {
+        match self.playing {
+            true => self.beats + self.frames_to_beats(offset as f64),
+            false => self.beats,
+        }
+    }
+
+    /// Position of sample `frame` of the timeline, in quarter notes, at the
+    /// tempo; earlier frames give negative positions
+    pub fn beats_at_frame(&self, frame: u64) -> f64 {
+        self.beats + self.frames_to_beats(frame as f64 - self.frame as f64)
+    }
+
+    /// Sample of the timeline where the playhead reaches `beats`, at the tempo
+    pub fn frame_at_beats(&self, beats: f64) -> f64 {
+        self.frame as f64 + self.beats_to_frames(beats - self.beats)
+    }
+
+    /// Bars and beats at the start of the block
+    pub fn musical_time(&self) -> MusicalTime {
+        self.signature.musical_time(self.beats)
+    }
+
+    /// Bars and beats at sample `frame` of the timeline
+    pub fn musical_time_at_frame(&self, frame: u64) -> MusicalTime {
+        self.signature.musical_time(self.beats_at_frame(frame))
+    }
+
+    /// Phase from 0 to 1 of a cycle `cycle_beats` quarter notes long, `offset`
+    /// frames into the block; cycles start on multiples of their length, so a
+    /// synced LFO restarts on the bar
+    pub fn phase(&self, cycle_beats: f64, offset: usize) -> f64 {
+        (self.beats_at(offset) / cycle_beats.max(1.0 / 64.0)).rem_euclid(1.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum TransportCommand {
+    Play,
+    Stop,
+    Locate(u64),
+    LocateBeats(f64),
+    SetTempo(f32),
+    SetSignature(TimeSignature),
+}
+
+struct Shared {
+    state: AtomicCell<TransportState>,
+    commands: ArrayQueue<TransportCommand>,
+    /// Frames the playhead moves on by at the next `advance`
+    moving: AtomicU64,
+}
+
+/// Shared playhead with tempo and meter. Cheap to clone; clones are the
+/// same transport.
+///
+/// The app thread's changes are queued and land together at the start of
+/// the next block the router mixes, so a source never sees half of a
+/// locate-and-play. Each returns `false` when the queue is full. `state`
+/// is RT-safe and what sources read while rendering.
+#[derive(Clone)]
+pub struct Transport {
+    shared: Arc<Shared>,
+}
+
+impl Transport {
+    /// A stopped transport at the start, 4/4 at `DEFAULT_TEMPO_BPM`.
+    /// `sample_rate` should be the router's.
+    pub fn new(sample_rate: f32) -> Self {
+        let state = TransportState {
+            playing: false,
+            frame: 0,
+            beats: 0.0,
+            tempo_bpm: DEFAULT_TEMPO_BPM,
+            signature: TimeSignature::default(),
+            sample_rate,
+        };
+        let shared = Shared {
+            state: AtomicCell::new(state),
+            commands: ArrayQueue::new(TRANSPORT_COMMAND_CAPACITY),
+            moving: AtomicU64::new(0),
+        };
+        Self { shared: Arc::new(shared) }
+    }
+
+    pub fn with_tempo(self, bpm: f32) -> Self {
+        let state = self.state();
+        self.shared.state.store(TransportState { tempo_bpm: bpm.max(1.0), ..state });
+        self
+    }
+
+    pub fn with_signature(self, signature: TimeSignature) -> Self {
+        let state = self.state();
+        self.shared.state.store(TransportState { signature, ..state });
+        self
+    }
+
+    /// The playhead at the start of the block being mixed, or the last one
+    /// mixed. RT-safe.
+    pub fn state(&self) -> TransportState {
+        self.shared.state.load()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.state().playing
+    }
+
+    pub fn play(&self) -> bool {
+        self.send(TransportCommand::Play)
+    }
+
+    /// Stop where the playhead is; `play` carries on from there
+    pub fn stop(&self) -> bool {
+        self.send(TransportCommand::Stop)
+    }
+
+    /// Move the playhead to sample `frame` of the timeline; its position in
+    /// beats is taken at the tempo in effect then
+    pub fn locate(&self, frame: u64) -> bool {
+        self.send(TransportCommand::Locate(frame))
+    }
+
+    /// Move the playhead to `beats` quarter notes in
+    pub fn locate_beats(&self, beats: f64) -> bool {
+        self.send(TransportCommand::LocateBeats(beats.max(0.0)))
+    }
+
+    /// Move the playhead to bar and beat `time` of the current signature
+    pub fn locate_musical(&self, time: MusicalTime) -> bool {
+        self.locate_beats(self.state().signature.beats(time))
+    }
+
+    /// Quarter notes per minute; the playhead keeps its position in beats
+    pub fn set_tempo(&self, bpm: f32) -> bool {
+        self.send(TransportCommand::SetTempo(bpm.max(1.0)))
+    }
+
+    pub fn set_signature(&self, signature: TimeSignature) -> bool {
+        self.send(TransportCommand::SetSignature(signature))
+    }
+
+    fn send(&self, command: TransportCommand) -> bool {
+        self.shared.commands.push(command).is_ok()
+    }
+
+    /// Start a block of `frames`: move the playhead on by the previous
+    /// block, apply the queued changes and publish the result as `state`.
+    /// Called by the router for every block it mixes; only one thread may
+    /// drive a transport. RT-safe.
+    pub fn advance(&self, frames: usize) {
+        let shared = &*self.shared;
+        let mut state = shared.state.load();
+        let moved = shared.moving.load(Ordering::Relaxed);
+        state.frame += moved;
+        state.beats += state.frames_to_beats(moved as f64);
+        while let Some(command) = shared.commands.pop() {
+            match command {
+                TransportCommand::Play => state.playing = true,
+                TransportCommand::Stop => state.playing = false,
+                TransportCommand::Locate(frame) => {
+                    state.frame = frame;
+                    state.beats = state.frames_to_beats(frame as f64);
+                }
+                TransportCommand::LocateBeats(beats) => {
+                    state.beats = beats;
+                    state.frame = state.beats_to_frames(beats).round() as u64;
+                }
+                TransportCommand::SetTempo(bpm) => state.tempo_bpm = bpm,
+                TransportCommand::SetSignature(signature) => state.signature = signature,
+            }
+        }
+        shared.moving.store(if state.playing { frames as u64 } else { 0 }, Ordering::Relaxed);
+        shared.state.store(state);
+    }
+}
@@ -0,0 +1,166 @@
+//! Turing-machine style generator: a looping shift register that random bits
+//! leak into.
+//!
+//! Each step the register rotates by one, and the bit coming round is kept
+//! with the lock probability or replaced by a random one otherwise. Fully
+//! locked, the last `length` bits repeat forever; fully unlocked, every step
+//! is new; in between, a loop that slowly mutates. The newest eight bits read as
+//! a value from 0.0 to 1.0.
+//!
+//! `TuringMachine` drives a sequencer lane's pitch (see
+//! `SequencerLane::with_turing`); `TuringModulator` clocks one from a rate
+//! and reads it as a modulation source, like an LFO.
+
+use crate::rt_processing::effects::{DEFAULT_TEMPO_BPM, LfoRate};
+use crate::rt_processing::waveform::noise::FastRng;
+
+/// Longest loop, in steps
+pub const MAX_TURING_LENGTH: usize = 16;
+
+/// The shift register and its dice
+#[derive(Clone, Debug, PartialEq)]
+pub struct TuringMachine {
+    register: u16,
+    length: usize,
+    lock: f32,
+    seed: u32,
+    rng: FastRng,
+}
+
+impl Default for TuringMachine {
+    /// 8 steps, fully locked, seed 1
+    fn default() -> Self {
+        Self::new(8, 1.0)
+    }
+}
+
+impl TuringMachine {
+    /// A loop of `length` steps (1 to `MAX_TURING_LENGTH`) kept with
+    /// probability `lock` each time round, filled from the default seed
+    pub fn new(length: usize, lock: f32) -> Self {
+        let mut machine = Self { register: 0, length: 8, lock: 1.0, seed: 1, rng: FastRng::new(1) };
+        machine.set_length(length);
+        machine.set_lock(lock);
+        machine.reset();
+        machine
+    }
+
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self.reset();
+        self
+    }
+
+    /// Steps before the pattern comes round
+    pub fn set_length(&mut self, length: usize) {
+        self.length = length.clamp(1, MAX_TURING_LENGTH);
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Chance that the bit coming round is kept, 0.0 (random) to 1.0 (locked)
+    pub fn set_lock(&mut self, lock: f32) {
+        self.lock = lock.clamp(0.0, 1.0);
+    }
+
+    pub fn lock(&self) -> f32 {
+        self.lock
+    }
+
+    /// The register, newest bit lowest
+    pub fn register(&self) -> u16 {
+        self.register
+    }
+
+    /// Back to the register the seed fills, with the dice restarted
+    pub fn reset(&mut self) {
+        self.rng = FastRng::new(self.seed);
+        self.register = (self.rng.next_u32() >> 16) as u16;
+    }
+
+    /// Rotate by one step and return the new value
+    pub fn step(&mut self) -> f32 {
+        let wrapped = self.register >> (self.length - 1) & 1;
+        // roll every step, so changing the lock doesn't shift later rolls
+        let roll = self.rng.next_f32();
+        let random = self.rng.next_u32() & 1;
+        let bit = if roll < self.lock { wrapped } else { random as u16 };
+        self.register = self.register << 1 | bit;
+        self.value()
+    }
+
+    /// The newest eight bits, 0.0 to 1.0
+    pub fn value(&self) -> f32 {
+        (self.register & 0xFF) as f32 / 255.0
+    }
+}
+
+/// A `TuringMachine` stepped at a rate, as a modulation source.
+///
+/// Holds each value until the next step, -1.0 to 1.0 scaled by the depth.
+/// Call `advance` at control rate (see `ControlRamp`), as an LFO's; with
+/// `LfoRate::Beats` it steps in time with the tempo given to `set_tempo`.
+#[derive(Clone, Debug)]
+pub struct TuringModulator {
+    machine: TuringMachine,
+    rate: LfoRate,
+    tempo_bpm: f32,
+    depth: f32,
+    // 0.0 to 1.0 through the current step
+    phase: f64,
+    value: f32,
+}
+
+impl TuringModulator {
+    pub fn new(machine: TuringMachine, rate: LfoRate) -> Self {
+        let value = machine.value();
+        Self { machine, rate, tempo_bpm: DEFAULT_TEMPO_BPM, depth: 1.0, phase: 0.0, value }
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.set_depth(depth);
+        self
+    }
+
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    pub fn set_rate(&mut self, rate: LfoRate) {
+        self.rate = rate;
+    }
+
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.tempo_bpm = bpm;
+    }
+
+    pub fn machine(&self) -> &TuringMachine {
+        &self.machine
+    }
+
+    /// For changing the lock and length while it runs
+    pub fn machine_mut(&mut self) -> &mut TuringMachine {
+        &mut self.machine
+    }
+
+    /// The current value, then move `frames` samples ahead, stepping the
+    /// machine as many times as the rate says
+    pub fn advance(&mut self, frames: usize, sample_rate: f32) -> f32 {
+        let output = (self.value * 2.0 - 1.0) * self.depth;
+        self.phase += self.rate.frequency(self.tempo_bpm) as f64 * frames as f64 / sample_rate as f64;
+        while self.phase >= 1.0 {
+            self.phase -= 1.0;
+            self.value = self.machine.step();
+        }
+        output
+    }
+
+    /// Back to the seed's register and the start of a step
+    pub fn reset(&mut self) {
+        self.machine.reset();
+        self.phase = 0.0;
+        self.value = self.machine.value();
+    }
+}
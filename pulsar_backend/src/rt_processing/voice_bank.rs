@@ -0,0 +1,243 @@
+use crate::rt_processing::filters::EnvelopeFollower;
+use crate::rt_processing::notes::{NoteEvent, note_to_frequency};
+use crate::rt_processing::prefault::Prefault;
+use crate::rt_processing::routing::AudioSource;
+use crate::rt_processing::waveform::tables::{WaveformType, init_tables};
+
+/// Voices rendered together; storage is padded to a multiple of this
+pub const VOICE_LANES: usize = 8;
+/// Envelope level below which a released voice is free again
+const SILENT_LEVEL: f32 = 1e-4;
+const NO_NOTE: u8 = u8::MAX;
+
+/// Polyphonic oscillator voices stored as structure-of-arrays.
+///
+/// `VoicePool` keeps one boxed `Voice` per slot, which suits voices that differ
+/// but costs a virtual call and a pointer chase per voice per block. When all
+/// voices are the same oscillator, the bank keeps each field in its own
+/// contiguous array (phases, increments, levels...) and renders `VOICE_LANES`
+/// voices at a time from fixed-size lane arrays, which the compiler can keep
+/// in vector registers. Lanes with nothing sounding are skipped.
+///
+/// Each voice has a one-pole attack/release envelope. A note-on takes a free
+/// voice or steals the oldest; the stolen voice's envelope glides from where
+/// it was to the new level, so steals don't click.
+pub struct OscillatorBank {
+    waveform: WaveformType,
+    voices: usize,
+    phases: Vec<f32>,
+    /// Cycles per sample
+    increments: Vec<f32>,
+    frequencies: Vec<f32>,
+    levels: Vec<f32>,
+    targets: Vec<f32>,
+    /// Envelope coefficient: attack while `target` > 0, release otherwise
+    coeffs: Vec<f32>,
+    notes: Vec<u8>,
+    /// Note-on order, used to steal the oldest voice
+    started: Vec<u64>,
+    next_start: u64,
+    amplitude: f32,
+    attack: f32,
+    release: f32,
+    sample_rate: f32,
+    // mono mix, copied to every output channel
+    mix: Vec<f32>,
+}
+
+impl OscillatorBank {
+    pub fn new(waveform: WaveformType, voices: usize, max_frames: usize) -> Self {
+        init_tables();
+        let padded = voices.div_ceil(VOICE_LANES) * VOICE_LANES;
+        Self {
+            waveform,
+            voices,
+            phases: vec![0.0; padded],
+            increments: vec![0.0; padded],
+            frequencies: vec![0.0; padded],
+            levels: vec![0.0; padded],
+            targets: vec![0.0; padded],
+            coeffs: vec![1.0; padded],
+            notes: vec![NO_NOTE; padded],
+            started: vec![0; padded],
+            next_start: 0,
+            amplitude: 0.2,
+            attack: 0.005,
+            release: 0.2,
+            sample_rate: 0.0,
+            mix: vec![0.0; max_frames],
+        }
+    }
+
+    /// Level of a full-velocity note
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Attack and release times in seconds
+    pub fn with_envelope(mut self, attack: f32, release: f32) -> Self {
+        self.set_envelope(attack, release);
+        self
+    }
+
+    pub fn set_envelope(&mut self, attack: f32, release: f32) {
+        self.attack = attack.max(0.0);
+        self.release = release.max(0.0);
+        // recompute coefficients on the next block
+        self.sample_rate = 0.0;
+    }
+
+    pub fn set_waveform(&mut self, waveform: WaveformType) {
+        self.waveform = waveform;
+    }
+
+    pub fn waveform(&self) -> WaveformType {
+        self.waveform
+    }
+
+    pub fn polyphony(&self) -> usize {
+        self.voices
+    }
+
+    /// Voices currently sounding, including release tails
+    pub fn active_voices(&self) -> usize {
+        (0..self.voices).filter(|&v| self.is_sounding(v)).count()
+    }
+
+    #[inline]
+    fn is_sounding(&self, voice: usize) -> bool {
+        self.targets[voice] > 0.0 || self.levels[voice] > SILENT_LEVEL
+    }
+
+    pub fn handle(&mut self, event: NoteEvent) {
+        match event {
+            NoteEvent::NoteOn { note, velocity } => self.note_on(note, velocity),
+            NoteEvent::NoteOff { note } => self.note_off(note),
+        }
+    }
+
+    pub fn note_on(&mut self, note: u8, velocity: f32) {
+        let voice = (0..self.voices)
+            .find(|&v| !self.is_sounding(v))
+            .or_else(|| (0..self.voices).min_by_key(|&v| self.started[v]));
+        let Some(voice) = voice else { return };
+
+        let frequency = note_to_frequency(note as f32);
+        self.notes[voice] = note;
+        self.frequencies[voice] = frequency;
+        if self.sample_rate > 0.0 {
+            self.increments[voice] = frequency / self.sample_rate;
+        }
+        if !self.is_sounding(voice) {
+            self.phases[voice] = 0.0;
+        }
+        self.targets[voice] = velocity.clamp(0.0, 1.0) * self.amplitude;
+        self.coeffs[voice] = self.attack_coeff();
+        self.started[voice] = self.next_start;
+        self.next_start += 1;
+    }
+
+    pub fn note_off(&mut self, note: u8) {
+        let release = self.release_coeff();
+        for voice in 0..self.voices {
+            if self.notes[voice] == note && self.targets[voice] > 0.0 {
+                self.targets[voice] = 0.0;
+                self.coeffs[voice] = release;
+            }
+        }
+    }
+
+    /// Cut every voice at once
+    pub fn reset(&mut self) {
+        self.levels.fill(0.0);
+        self.targets.fill(0.0);
+        self.phases.fill(0.0);
+        self.notes.fill(NO_NOTE);
+    }
+
+    fn attack_coeff(&self) -> f32 {
+        EnvelopeFollower::time_coefficient(self.attack, self.sample_rate.max(1.0))
+    }
+
+    fn release_coeff(&self) -> f32 {
+        EnvelopeFollower::time_coefficient(self.release, self.sample_rate.max(1.0))
+    }
+
+    fn prepare(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        let (attack, release) = (self.attack_coeff(), self.release_coeff());
+        for voice in 0..self.voices {
+            self.increments[voice] = self.frequencies[voice] / sample_rate;
+            self.coeffs[voice] = if self.targets[voice] > 0.0 { attack } else { release };
+        }
+    }
+
+    /// Render one lane group into the mix
+    fn render_lanes(&mut self, first: usize, frames: usize) {
+        let lane = |values: &[f32]| -> [f32; VOICE_LANES] { std::array::from_fn(|i| values[first + i]) };
+        let mut phase = lane(&self.phases);
+        let mut level = lane(&self.levels);
+        let increment = lane(&self.increments);
+        let target = lane(&self.targets);
+        let coeff = lane(&self.coeffs);
+        // table lengths are powers of two
+        let table = self.waveform.table();
+        let mask = table.len() - 1;
+
+        for out in &mut self.mix[..frames] {
+            let mut sum = 0.0;
+            for lane in 0..VOICE_LANES {
+                let index = (phase[lane] * table.len() as f32) as usize & mask;
+                sum += table[index] * level[lane];
+                phase[lane] += increment[lane];
+                phase[lane] -= phase[lane].floor();
+                level[lane] += (target[lane] - level[lane]) * coeff[lane];
+            }
+            *out += sum;
+        }
+
+        self.phases[first..first + VOICE_LANES].copy_from_slice(&phase);
+        self.levels[first..first + VOICE_LANES].copy_from_slice(&level);
+    }
+}
+
+impl AudioSource for OscillatorBank {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        if self.sample_rate != sample_rate {
+            self.prepare(sample_rate);
+        }
+        let frames = frames.min(self.mix.len());
+        self.mix[..frames].fill(0.0);
+
+        for first in (0..self.voices).step_by(VOICE_LANES) {
+            let end = (first + VOICE_LANES).min(self.voices);
+            if (first..end).any(|v| self.is_sounding(v)) {
+                self.render_lanes(first, frames);
+            }
+        }
+        // released voices that went quiet are free again
+        for voice in 0..self.voices {
+            if self.targets[voice] == 0.0 && self.levels[voice] <= SILENT_LEVEL {
+                self.levels[voice] = 0.0;
+                self.notes[voice] = NO_NOTE;
+            }
+        }
+
+        for out in output.iter_mut() {
+            out[..frames].copy_from_slice(&self.mix[..frames]);
+        }
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        memory.touch(&mut self.phases);
+        memory.touch(&mut self.increments);
+        memory.touch(&mut self.frequencies);
+        memory.touch(&mut self.levels);
+        memory.touch(&mut self.targets);
+        memory.touch(&mut self.coeffs);
+        memory.touch(&mut self.notes);
+        memory.touch(&mut self.started);
+        memory.touch(&mut self.mix);
+    }
+}
@@ -1,5 +1,6 @@
-use crate::rt_processing::routing::{AudioSource as RoutingAudioSource, Router, Pan, PanLaw};
+use crate::rt_processing::routing::{AudioSource as RoutingAudioSource, Router, Pan, PanLaw, SourceHandle, SourceId};
 use crate::rt_processing::callback::AudioCallback;
+use crate::rt_processing::prefault::Prefault;
 
 /// Trait for waveform generators that produce audio samples
 /// This is our internal waveform interface - simpler than the routing interface
@@ -43,29 +44,50 @@ impl<T: AudioSource> RoutingAudioSource for WaveformAdapter<T> {
         self.source.fill_buffer(&mut self.temp_buffer[..needed_size], sample_rate, channels, frames);
 
         // De-interleave into non-interleaved output for routing system
-        for frame in 0..frames {
-            for ch in 0..channels {
-                output[ch][frame] = self.temp_buffer[frame * channels + ch];
+        for (ch, out) in output.iter_mut().enumerate() {
+            for (frame, sample) in out[..frames].iter_mut().enumerate() {
+                *sample = self.temp_buffer[frame * channels + ch];
             }
         }
     }
+
+    fn is_active(&self) -> bool {
+        self.source.is_active()
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        // size the temp buffer now rather than on the first render
+        let needed_size = memory.max_frames() * memory.channels();
+        if self.temp_buffer.len() < needed_size {
+            self.temp_buffer.resize(needed_size, 0.0);
+        }
+        memory.touch(&mut self.temp_buffer);
+    }
+}
+
+/// Wrap a waveform source for use wherever the router expects a routing source
+pub fn routing_source<T: AudioSource + 'static>(source: T) -> Box<dyn RoutingAudioSource + 'static> {
+    Box::new(WaveformAdapter::new(source))
 }
 
 /// Voice processor that integrates with the real-time callback system
 pub struct VoiceProcessor {
     router: Router,
     _temp_interleaved: Vec<f32>,
-    next_source_id: usize,
 }
 
 impl VoiceProcessor {
     /// Create a new voice processor
     pub fn new(channels: usize, sample_rate: f32, max_frames: usize, num_buses: usize) -> Self {
-        Self {
-            router: Router::new(channels, sample_rate, num_buses.max(1), max_frames),
-            _temp_interleaved: Vec::with_capacity(max_frames * channels),
-            next_source_id: 0,
-        }
+        let router = Router::new(channels, sample_rate, num_buses.max(1), max_frames);
+        // reduced-precision builds mix in Q15
+        #[cfg(feature = "fixed-point")]
+        let router = {
+            let mut router = router;
+            router.set_fixed_point_mix(true);
+            router
+        };
+        Self { router, _temp_interleaved: Vec::with_capacity(max_frames * channels) }
     }
 
     /// Create a basic stereo voice processor with 4 buses
@@ -73,26 +95,21 @@ impl VoiceProcessor {
         Self::new(2, sample_rate, max_frames, 4)
     }
 
-    /// Add a waveform audio source to the processor
+    /// Add a waveform audio source to the processor. The source is removed
+    /// automatically once it reports `is_active() == false`.
     pub fn add_waveform_source<T: AudioSource + 'static>(
         &mut self,
         source: T,
         gain: f32,
         pan: f32,
         bus: usize
-    ) -> usize {
+    ) -> SourceId {
         let pan_control = Pan {
             value: pan.clamp(-1.0, 1.0),
             law: PanLaw::EqualPower,
         };
 
-        let adapter = WaveformAdapter::new(source);
-        // Coerce into the routing trait object (requires 'static; we bound T with 'static)
-        self.router.add_source(Box::new(adapter), gain, pan_control, bus);
-
-        let id = self.next_source_id;
-        self.next_source_id += 1;
-        id
+        self.router.add_source(routing_source(source), gain, pan_control, bus)
     }
 
     /// Add a routing audio source directly (for advanced use)
@@ -102,12 +119,26 @@ impl VoiceProcessor {
         gain: f32,
         pan: Pan,
         bus: usize
-    ) -> usize {
-        self.router.add_source(source, gain, pan, bus);
+    ) -> SourceId {
+        self.router.add_source(source, gain, pan, bus)
+    }
+
+    /// Handle for changing source `id` from another thread
+    pub fn source_handle(&self, id: SourceId) -> Option<SourceHandle> {
+        self.router.handle(id)
+    }
 
-        let id = self.next_source_id;
-        self.next_source_id += 1;
-        id
+    /// Remove source `id`; `false` if it's already gone
+    pub fn remove_source(&mut self, id: SourceId) -> bool {
+        self.router.remove(id)
+    }
+
+    pub fn set_mute(&mut self, id: SourceId, mute: bool) -> bool {
+        self.router.set_mute(id, mute)
+    }
+
+    pub fn set_solo(&mut self, id: SourceId, solo: bool) -> bool {
+        self.router.set_solo(id, solo)
     }
 
     /// Clear all sources
@@ -115,6 +146,12 @@ impl VoiceProcessor {
         self.router.clear_sources();
     }
 
+    /// Touch (and with `PrefaultMode::Lock`, lock) the router's and graph's
+    /// buffers; see `prefault`
+    pub fn prefault(&mut self, memory: &mut Prefault) {
+        self.router.prefault(memory);
+    }
+
     /// Get access to the internal router for advanced operations
     pub fn router(&self) -> &Router {
         &self.router
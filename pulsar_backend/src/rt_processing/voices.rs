@@ -0,0 +1,486 @@
+use crate::rt_processing::notes::{ExpressiveNoteEvent, NoteEvent, NoteExpression, NoteId, NoteQueue};
+use crate::rt_processing::prefault::Prefault;
+use crate::rt_processing::routing::AudioSource;
+use crate::rt_processing::waveform::noise::FastRng;
+
+/// Default fade applied to a stolen voice before it is reused
+pub const DEFAULT_STEAL_FADE_SECONDS: f32 = 0.005;
+/// Most output channels a voice pool renders
+pub const MAX_VOICE_CHANNELS: usize = 8;
+
+/// Per-trigger variation ranges, against the "machine-gun" effect of a note
+/// repeated with identical samples
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Humanize {
+    /// Largest random start offset into the sound, in seconds
+    pub start_offset: f32,
+    /// Level varies randomly by up to ± this many dB
+    pub level_db: f32,
+    /// Pitch varies randomly by up to ± this many cents
+    pub pitch_cents: f32,
+    /// Alternate sounds cycled through on each repeat of a note (1 = off)
+    pub round_robin: usize,
+}
+
+impl Default for Humanize {
+    /// No variation
+    fn default() -> Self {
+        Self { start_offset: 0.0, level_db: 0.0, pitch_cents: 0.0, round_robin: 1 }
+    }
+}
+
+/// How one trigger deviates from the plain note, drawn from `Humanize`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TriggerVariation {
+    /// Seconds to skip at the start of the sound
+    pub start_offset: f32,
+    /// Linear level, applied by the pool
+    pub gain: f32,
+    pub detune_cents: f32,
+    /// Alternate to play, `0..Humanize::round_robin`
+    pub round_robin: usize,
+}
+
+impl Default for TriggerVariation {
+    fn default() -> Self {
+        Self { start_offset: 0.0, gain: 1.0, detune_cents: 0.0, round_robin: 0 }
+    }
+}
+
+/// One voice of a `VoicePool`.
+///
+/// Runs on the audio thread: implementations must not block or allocate.
+pub trait Voice: Send + Sync {
+    fn note_on(&mut self, note: u8, velocity: f32);
+
+    /// Variation for the next `note_on`, called right before it.
+    /// `SamplerVoice` and `OscillatorVoice` apply the start offset, detune and
+    /// alternate; voices that can't ignore it. The level is applied by the
+    /// pool either way.
+    fn vary(&mut self, _variation: &TriggerVariation) {}
+
+    /// Enter the release stage
+    fn note_off(&mut self);
+
+    /// Bend the pitch by `semitones` (fractional) from the notes played, now
+    /// and for later notes until the next bend
+    fn pitch_bend(&mut self, _semitones: f32) {}
+
+    /// A MIDI controller moved; `value` is normalized (0.0 to 1.0), at 14-bit
+    /// resolution for paired controllers
+    fn control_change(&mut self, _controller: u8, _value: f32) {}
+
+    /// A MIDI non-registered parameter (NRPN) changed; `value` is normalized
+    fn nrpn(&mut self, _parameter: u16, _value: f32) {}
+
+    /// Per-note expression of the note this voice plays, already clamped to
+    /// the dimension's range. Every dimension is set right before each
+    /// `note_on`, so a voice never inherits the previous note's expression.
+    fn expression(&mut self, _expression: NoteExpression, _value: f32) {}
+
+    /// Overwrite non-interleaved `[channel][frame]` output
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32);
+
+    /// Still sounding, including its release tail
+    fn is_active(&self) -> bool;
+
+    /// Silence immediately and clear internal state
+    fn reset(&mut self);
+}
+
+/// A note waiting for a stolen voice to fade out
+#[derive(Copy, Clone)]
+struct PendingNote {
+    note: u8,
+    velocity: f32,
+    variation: TriggerVariation,
+    id: Option<NoteId>,
+    /// Expression received while waiting, by `NoteExpression::index`
+    expression: [f32; NoteExpression::COUNT],
+}
+
+struct VoiceSlot {
+    voice: Box<dyn Voice>,
+    /// Note held or releasing in this slot
+    note: Option<u8>,
+    /// Id of that note, if it was started with one
+    id: Option<NoteId>,
+    /// Note-on order, used to steal the oldest voice
+    started: u64,
+    /// Fade-out gain while stolen; `None` when playing normally
+    fade: Option<f32>,
+    /// Note that takes over once the fade-out is done
+    pending: Option<PendingNote>,
+    /// Level of the current trigger
+    gain: f32,
+}
+
+/// Fixed-size polyphonic voice allocator, playable as a routed source.
+///
+/// A note-on takes a free voice, or steals the oldest one when all are busy.
+/// A stolen voice isn't cut: it fades out over `steal_fade` (a few ms), then
+/// restarts with the new note at the next block, so steals never click.
+/// Voices and mix buffers are allocated up front; nothing on the audio path
+/// allocates.
+///
+/// `with_humanize` varies each trigger (start offset, level, pitch, round-robin
+/// alternate) from a seeded generator, so renders stay reproducible.
+///
+/// Notes started with a `NoteId` (`start_note`, or `handle_expressive`) can
+/// be released and given per-note expression by id, whatever their key;
+/// notes started by key answer to `note_off` only.
+pub struct VoicePool {
+    slots: Vec<VoiceSlot>,
+    // per-voice render target, [channel][frame]
+    scratch: Vec<Vec<f32>>,
+    steal_fade: f32,
+    next_start: u64,
+    humanize: Humanize,
+    rng: FastRng,
+    // next round-robin alternate per MIDI note
+    round_robin: [u16; 128],
+}
+
+impl VoicePool {
+    /// `voices` sets the polyphony; blocks are at most `max_frames` long and
+    /// channels beyond `MAX_VOICE_CHANNELS` stay silent
+    pub fn new(voices: Vec<Box<dyn Voice>>, channels: usize, max_frames: usize) -> Self {
+        Self {
+            slots: voices
+                .into_iter()
+                .map(|voice| VoiceSlot { voice, note: None, id: None, started: 0, fade: None, pending: None, gain: 1.0 })
+                .collect(),
+            scratch: vec![vec![0.0; max_frames]; channels.min(MAX_VOICE_CHANNELS)],
+            steal_fade: DEFAULT_STEAL_FADE_SECONDS,
+            next_start: 0,
+            humanize: Humanize::default(),
+            rng: FastRng::new(1),
+            round_robin: [0; 128],
+        }
+    }
+
+    /// Vary every trigger within `humanize`; `seed` makes the variation repeatable
+    pub fn with_humanize(mut self, humanize: Humanize, seed: u32) -> Self {
+        self.set_humanize(humanize, seed);
+        self
+    }
+
+    pub fn set_humanize(&mut self, humanize: Humanize, seed: u32) {
+        self.humanize = Humanize { round_robin: humanize.round_robin.max(1), ..humanize };
+        self.rng = FastRng::new(seed);
+        self.round_robin = [0; 128];
+    }
+
+    pub fn humanize(&self) -> Humanize {
+        self.humanize
+    }
+
+    /// Fade time for stolen voices, in seconds (0 = hard cut)
+    pub fn with_steal_fade(mut self, seconds: f32) -> Self {
+        self.set_steal_fade(seconds);
+        self
+    }
+
+    pub fn set_steal_fade(&mut self, seconds: f32) {
+        self.steal_fade = seconds.max(0.0);
+    }
+
+    pub fn steal_fade(&self) -> f32 {
+        self.steal_fade
+    }
+
+    pub fn polyphony(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Voices currently sounding, including fading ones
+    pub fn active_voices(&self) -> usize {
+        self.slots.iter().filter(|s| s.voice.is_active() || s.fade.is_some()).count()
+    }
+
+    pub fn handle(&mut self, event: NoteEvent) {
+        match event {
+            NoteEvent::NoteOn { note, velocity } => self.note_on(note, velocity),
+            NoteEvent::NoteOff { note } => self.note_off(note),
+        }
+    }
+
+    pub fn handle_expressive(&mut self, event: ExpressiveNoteEvent) {
+        match event {
+            ExpressiveNoteEvent::NoteOn { id, note, velocity } => self.start_note(id, note, velocity),
+            ExpressiveNoteEvent::NoteOff { id } => self.release_note(id),
+            ExpressiveNoteEvent::Expression { id, expression, value } => self.note_expression(id, expression, value),
+        }
+    }
+
+    pub fn note_on(&mut self, note: u8, velocity: f32) {
+        self.trigger(note, velocity, None);
+    }
+
+    /// Start a note addressed by `id` from now on. A note still holding the
+    /// same id is released first.
+    pub fn start_note(&mut self, id: NoteId, note: u8, velocity: f32) {
+        self.release_note(id);
+        self.trigger(note, velocity, Some(id));
+    }
+
+    fn trigger(&mut self, note: u8, velocity: f32, id: Option<NoteId>) {
+        let start = self.next_start;
+        self.next_start += 1;
+        let pending = PendingNote {
+            note,
+            velocity,
+            variation: self.next_variation(note),
+            id,
+            expression: NoteExpression::ALL.map(NoteExpression::neutral),
+        };
+
+        if let Some(slot) = self.slots.iter_mut().find(|s| !s.voice.is_active() && s.fade.is_none()) {
+            slot.start(&pending);
+            slot.started = start;
+            return;
+        }
+
+        // steal the oldest voice; one already fading just gets a new successor
+        let Some(slot) = self.slots.iter_mut().min_by_key(|s| (s.fade.is_none(), s.started)) else { return };
+        if self.steal_fade == 0.0 {
+            slot.voice.reset();
+            slot.start(&pending);
+            slot.started = start;
+            slot.fade = None;
+            slot.pending = None;
+        } else {
+            slot.fade = Some(slot.fade.unwrap_or(1.0));
+            slot.pending = Some(pending);
+            slot.started = start;
+        }
+    }
+
+    fn next_variation(&mut self, note: u8) -> TriggerVariation {
+        let h = self.humanize;
+        let alternate = &mut self.round_robin[note as usize & 127];
+        let round_robin = *alternate as usize % h.round_robin;
+        *alternate = ((round_robin + 1) % h.round_robin) as u16;
+        TriggerVariation {
+            start_offset: h.start_offset * self.rng.next_f32(),
+            gain: 10f32.powf(h.level_db * self.rng.next_bipolar() / 20.0),
+            detune_cents: h.pitch_cents * self.rng.next_bipolar(),
+            round_robin,
+        }
+    }
+
+    pub fn note_off(&mut self, note: u8) {
+        for slot in &mut self.slots {
+            if slot.pending.is_some_and(|pending| pending.note == note) {
+                // released before it could start: let the fade finish and free the voice
+                slot.pending = None;
+            } else if slot.fade.is_none() && slot.note == Some(note) {
+                slot.voice.note_off();
+            }
+        }
+    }
+
+    /// Release the note started with `id`; its voice keeps taking expression
+    /// through the release
+    pub fn release_note(&mut self, id: NoteId) {
+        for slot in &mut self.slots {
+            if slot.pending.is_some_and(|pending| pending.id == Some(id)) {
+                slot.pending = None;
+            } else if slot.fade.is_none() && slot.id == Some(id) {
+                slot.voice.note_off();
+            }
+        }
+    }
+
+    /// Set one expression dimension of the note started with `id`. Unknown
+    /// ids are ignored.
+    pub fn note_expression(&mut self, id: NoteId, expression: NoteExpression, value: f32) {
+        let value = expression.clamp(value);
+        for slot in &mut self.slots {
+            match &mut slot.pending {
+                Some(pending) if pending.id == Some(id) => pending.expression[expression.index()] = value,
+                _ if slot.fade.is_none() && slot.id == Some(id) => slot.voice.expression(expression, value),
+                _ => {}
+            }
+        }
+    }
+
+    /// Set one expression dimension of every note playing `note`, for
+    /// key-addressed sources such as MIDI poly aftertouch
+    pub fn key_expression(&mut self, note: u8, expression: NoteExpression, value: f32) {
+        let value = expression.clamp(value);
+        for slot in &mut self.slots {
+            match &mut slot.pending {
+                Some(pending) if pending.note == note => pending.expression[expression.index()] = value,
+                _ if slot.fade.is_none() && slot.note == Some(note) => slot.voice.expression(expression, value),
+                _ => {}
+            }
+        }
+    }
+
+    /// Release every note
+    pub fn all_notes_off(&mut self) {
+        for slot in &mut self.slots {
+            slot.pending = None;
+            if slot.fade.is_none() && slot.note.is_some() {
+                slot.voice.note_off();
+            }
+        }
+    }
+
+    /// Bend every voice; see `Voice::pitch_bend`
+    pub fn pitch_bend(&mut self, semitones: f32) {
+        for slot in &mut self.slots {
+            slot.voice.pitch_bend(semitones);
+        }
+    }
+
+    /// Pass a controller to every voice; see `Voice::control_change`
+    pub fn control_change(&mut self, controller: u8, value: f32) {
+        for slot in &mut self.slots {
+            slot.voice.control_change(controller, value);
+        }
+    }
+
+    /// Pass an NRPN change to every voice; see `Voice::nrpn`
+    pub fn nrpn(&mut self, parameter: u16, value: f32) {
+        for slot in &mut self.slots {
+            slot.voice.nrpn(parameter, value);
+        }
+    }
+
+    /// Cut every voice at once
+    pub fn reset(&mut self) {
+        for slot in &mut self.slots {
+            slot.voice.reset();
+            slot.note = None;
+            slot.id = None;
+            slot.fade = None;
+            slot.pending = None;
+        }
+    }
+
+    /// Add every voice's next `frames` into `output` from `offset` on;
+    /// `frames` fits in `scratch`
+    fn render_chunk(&mut self, output: &mut [&mut [f32]], offset: usize, frames: usize, sample_rate: f32) {
+        let channels = self.scratch.len();
+        let step = 1.0 / (self.steal_fade * sample_rate).max(1.0);
+
+        for slot in &mut self.slots {
+            if !slot.voice.is_active() && slot.fade.is_none() {
+                continue;
+            }
+            let mut views: [&mut [f32]; MAX_VOICE_CHANNELS] = Default::default();
+            for (view, samples) in views.iter_mut().zip(self.scratch.iter_mut()) {
+                *view = &mut samples[..frames];
+            }
+            let views = &mut views[..channels];
+            slot.voice.render(views, frames, sample_rate);
+
+            match slot.fade {
+                None => {
+                    let gain = slot.gain;
+                    for (out, voice) in output.iter_mut().zip(views.iter()) {
+                        out[offset..offset + frames].iter_mut().zip(voice.iter()).for_each(|(o, v)| *o += v * gain);
+                    }
+                }
+                Some(mut gain) => {
+                    for i in 0..frames {
+                        gain = (gain - step).max(0.0);
+                        for (out, voice) in output.iter_mut().zip(views.iter()) {
+                            out[offset + i] += voice[i] * gain * slot.gain;
+                        }
+                    }
+                    slot.fade = Some(gain);
+                    if gain == 0.0 {
+                        slot.voice.reset();
+                        slot.fade = None;
+                        slot.note = None;
+                        slot.id = None;
+                        if let Some(pending) = slot.pending.take() {
+                            slot.start(&pending);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl VoiceSlot {
+    fn start(&mut self, pending: &PendingNote) {
+        self.voice.vary(&pending.variation);
+        for expression in NoteExpression::ALL {
+            self.voice.expression(expression, pending.expression[expression.index()]);
+        }
+        self.voice.note_on(pending.note, pending.velocity);
+        self.note = Some(pending.note);
+        self.id = pending.id;
+        self.gain = pending.variation.gain;
+    }
+}
+
+impl AudioSource for VoicePool {
+    /// Blocks longer than `max_frames` are rendered in `max_frames` pieces
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        for out in output.iter_mut() {
+            out[..frames].fill(0.0);
+        }
+        let chunk = self.scratch.first().map_or(0, Vec::len);
+        if chunk == 0 {
+            return;
+        }
+        let mut offset = 0;
+        while offset < frames {
+            let len = chunk.min(frames - offset);
+            self.render_chunk(output, offset, len, sample_rate);
+            offset += len;
+        }
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        memory.touch_all(&mut self.scratch);
+    }
+}
+
+/// Plays `ExpressiveNoteEvent`s from a `NoteQueue` on a `VoicePool`, as a
+/// routed source: the host-level counterpart of `midi::MidiVoiceAllocator`
+/// for sources that address notes by id (CLAP note expressions, MPE-style
+/// controllers, sequencers). The queue is drained at the start of each
+/// block, so events take effect at block boundaries; `ScheduledPlayer` plays
+/// timestamped events on their exact frame.
+pub struct NotePlayer {
+    queue: NoteQueue,
+    pool: VoicePool,
+}
+
+impl NotePlayer {
+    pub fn new(queue: NoteQueue, pool: VoicePool) -> Self {
+        Self { queue, pool }
+    }
+
+    pub fn queue(&self) -> &NoteQueue {
+        &self.queue
+    }
+
+    pub fn pool(&self) -> &VoicePool {
+        &self.pool
+    }
+
+    pub fn pool_mut(&mut self) -> &mut VoicePool {
+        &mut self.pool
+    }
+}
+
+impl AudioSource for NotePlayer {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        while let Some(event) = self.queue.pop() {
+            self.pool.handle_expressive(event);
+        }
+        self.pool.render(output, frames, sample_rate);
+    }
+
+    fn prefault(&mut self, memory: &mut Prefault) {
+        self.pool.prefault(memory);
+    }
+}
@@ -23,6 +23,8 @@ pub struct ADSREnvelope {
     // Current state
     state: EnvelopeState,
     current_value: f32,
+    // level when the release started; the release ramps from here to zero
+    release_level: f32,
     sample_rate: f32,
     
     // Internal counters (in samples)
@@ -46,6 +48,7 @@ impl ADSREnvelope {
             release_time,
             state: EnvelopeState::Idle,
             current_value: 0.0,
+            release_level: 0.0,
             sample_rate: 44100.0, // Default, will be updated on first use
             attack_samples: 0,
             decay_samples: 0,
@@ -85,6 +88,7 @@ impl ADSREnvelope {
         if self.note_on && !self.note_off_triggered {
             self.note_on = false;
             self.note_off_triggered = true;
+            self.release_level = self.current_value;
             self.state = EnvelopeState::Release;
             self.current_sample = 0;
         }
@@ -207,14 +211,8 @@ impl ADSREnvelope {
                     self.current_value = 0.0;
                     self.state = EnvelopeState::Finished;
                 } else {
-                    let start_level = if self.note_off_triggered {
-                        self.current_value // Start release from current level
-                    } else {
-                        self.sustain_level
-                    };
-                    
                     let progress = self.current_sample as f32 / self.release_samples as f32;
-                    self.current_value = start_level * (1.0 - progress);
+                    self.current_value = self.release_level * (1.0 - progress);
                     self.current_sample += 1;
                     
                     if self.current_sample >= self.release_samples {
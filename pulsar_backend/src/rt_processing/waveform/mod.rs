@@ -2,6 +2,7 @@ pub mod tables;
 pub mod oscillators;
 pub mod envelopes;
 pub mod noise;
+pub mod sampler;
 
 use crate::rt_processing::routing::AudioSource as RoutingAudioSource;
 
@@ -24,9 +25,9 @@ impl<T: crate::rt_processing::voice_renderer::AudioSource> RoutingAudioSource fo
         self.source.fill_buffer(&mut self.temp_buffer[..needed_size], sample_rate, channels, frames);
         
         // De-interleave into output
-        for frame in 0..frames {
-            for ch in 0..channels {
-                output[ch][frame] = self.temp_buffer[frame * channels + ch];
+        for (ch, out) in output.iter_mut().enumerate() {
+            for (frame, sample) in out[..frames].iter_mut().enumerate() {
+                *sample = self.temp_buffer[frame * channels + ch];
             }
         }
     }
@@ -1,33 +1,47 @@
+use crate::rt_processing::analysis::spectrum::{NoiseProfile, OCTAVE_BANDS, OCTAVE_BAND_CENTERS};
+use crate::rt_processing::filters::{BiquadCoefficients, BiquadState, OCTAVE_Q};
 use crate::rt_processing::voice_renderer::AudioSource;
 
-/// Fast pseudo-random number generator for audio applications
-/// Uses a linear congruential generator (LCG) for deterministic, fast noise
-struct FastRng {
+/// Fast seeded pseudo-random numbers (xorshift32), for noise and for anything
+/// else that must roll the same values from the same seed. RT-safe.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FastRng {
     state: u32,
 }
 
 impl FastRng {
-    fn new(seed: u32) -> Self {
-        Self {
-            state: if seed == 0 { 1 } else { seed }, // Avoid zero seed
-        }
+    pub fn new(seed: u32) -> Self {
+        // spreads nearby seeds apart; xorshift has a fixed point at zero
+        Self { state: seed.wrapping_mul(0x9E37_79B9) | 1 }
     }
-    
+
+    /// Carry on from a `state` saved earlier, e.g. in an atomic
+    pub fn from_state(state: u32) -> Self {
+        Self { state: state.max(1) }
+    }
+
+    pub fn state(&self) -> u32 {
+        self.state
+    }
+
     #[inline]
-    fn next_u32(&mut self) -> u32 {
-        self.state = self.state.wrapping_mul(1664525).wrapping_add(1013904223);
+    pub fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
         self.state
     }
-    
+
+    /// Uniform in [0, 1)
     #[inline]
-    fn next_f32(&mut self) -> f32 {
-        (self.next_u32() as f32) * (1.0 / 4294967296.0) // [0.0, 1.0)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
     }
-    
+
+    /// Uniform in [-1, 1)
     #[inline]
-    fn next_bipolar(&mut self) -> f32 {
-        // Convert to [-1.0, 1.0] range
-        (self.next_f32() - 0.5) * 2.0
+    pub fn next_bipolar(&mut self) -> f32 {
+        self.next_f32() * 2.0 - 1.0
     }
 }
 
@@ -81,6 +95,12 @@ impl WhiteNoise {
     }
 }
 
+impl Default for WhiteNoise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AudioSource for WhiteNoise {
     fn fill_buffer(&mut self, output: &mut [f32], _sample_rate: f32, channels: usize, frame_count: usize) {
         if !self.active {
@@ -134,9 +154,9 @@ impl PinkNoise {
         
         // Coefficients for pink noise approximation
         let coefficients = [
-            0.049922035, 0.990566037, 0.115926437,
-            0.923311349, 0.972852432, 0.063612432,
-            0.999981195,
+            0.049_922_03, 0.990_566, 0.115_926_44,
+            0.923_311_3, 0.972_852_4, 0.063_612_43,
+            0.999_981_2,
         ];
         
         Self {
@@ -175,6 +195,12 @@ impl PinkNoise {
     }
 }
 
+impl Default for PinkNoise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AudioSource for PinkNoise {
     fn fill_buffer(&mut self, output: &mut [f32], sample_rate: f32, channels: usize, frame_count: usize) {
         if !self.active {
@@ -266,6 +292,12 @@ impl BrownNoise {
     }
 }
 
+impl Default for BrownNoise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AudioSource for BrownNoise {
     fn fill_buffer(&mut self, output: &mut [f32], _sample_rate: f32, channels: usize, frame_count: usize) {
         if !self.active {
@@ -358,6 +390,12 @@ impl BurstNoise {
     }
 }
 
+impl Default for BurstNoise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AudioSource for BurstNoise {
     fn fill_buffer(&mut self, output: &mut [f32], _sample_rate: f32, channels: usize, frame_count: usize) {
         if !self.active {
@@ -400,4 +438,312 @@ impl AudioSource for BurstNoise {
         self.burst_counter = 0;
         self.active = true;
     }
-}
\ No newline at end of file
+}
+
+/// Vinyl crackle generator - sparse, randomly sized clicks with a fast decay
+pub struct CrackleNoise {
+    rng: FastRng,
+    density: f32, // Average clicks per second
+    click: f32,   // Current click level (decays toward zero)
+    decay: f32,   // Per-sample decay factor of the current click
+    amplitude: f32,
+    active: bool,
+}
+
+impl CrackleNoise {
+    pub fn new() -> Self {
+        Self {
+            rng: FastRng::new(8642),
+            density: 20.0,
+            click: 0.0,
+            decay: 0.0,
+            amplitude: 0.2,
+            active: true,
+        }
+    }
+
+    pub fn with_density(mut self, clicks_per_second: f32) -> Self {
+        self.density = clicks_per_second.max(0.0);
+        self
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn set_density(&mut self, clicks_per_second: f32) {
+        self.density = clicks_per_second.max(0.0);
+    }
+
+    pub fn set_amplitude(&mut self, amplitude: f32) {
+        self.amplitude = amplitude.clamp(0.0, 1.0);
+    }
+
+    pub fn start(&mut self) {
+        self.active = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.active = false;
+    }
+
+    pub fn amplitude(&self) -> f32 {
+        self.amplitude
+    }
+
+    #[inline]
+    fn next_sample(&mut self, sample_rate: f32) -> f32 {
+        if self.rng.next_f32() < self.density / sample_rate {
+            // mostly tiny ticks, occasionally a louder pop
+            let size = self.rng.next_f32().powi(3);
+            let polarity = if self.rng.next_u32() & 1 == 0 { 1.0 } else { -1.0 };
+            self.click = polarity * size;
+            // 0.05 .. 0.5 ms decay
+            let seconds = 0.000_05 + 0.000_45 * self.rng.next_f32();
+            self.decay = (-1.0 / (seconds * sample_rate)).exp();
+        }
+        let sample = self.click;
+        self.click *= self.decay;
+        sample * self.amplitude
+    }
+}
+
+impl Default for CrackleNoise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioSource for CrackleNoise {
+    fn fill_buffer(&mut self, output: &mut [f32], sample_rate: f32, channels: usize, frame_count: usize) {
+        if !self.active {
+            output.fill(0.0);
+            return;
+        }
+
+        for frame_idx in 0..frame_count {
+            let sample = self.next_sample(sample_rate);
+            let start = frame_idx * channels;
+            let end = start + channels;
+            for out in &mut output[start..end] {
+                *out = sample;
+            }
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn reset(&mut self) {
+        self.rng = FastRng::new(8642);
+        self.click = 0.0;
+        self.active = true;
+    }
+}
+
+/// Filtered noise generator - white noise shaped by an octave filter bank
+///
+/// Each octave band carries a target RMS level, so the generator can be configured
+/// directly from a measured `NoiseProfile` (room noise, hiss, hum beds) or from a
+/// simple level + tilt description. Every band filters its own independent noise
+/// stream so band powers add without cross terms.
+///
+/// Silent until `prepare`d for the rate it renders at. `prepare` allocates and
+/// belongs off the audio thread; changing levels afterwards is RT-safe.
+pub struct FilteredNoise {
+    rngs: [FastRng; OCTAVE_BANDS],
+    seed: u32,
+    band_rms: [f32; OCTAVE_BANDS],
+    band_gains: [f32; OCTAVE_BANDS],
+    coeffs: [BiquadCoefficients; OCTAVE_BANDS],
+    states: [BiquadState; OCTAVE_BANDS],
+    // band-to-band power transfer of the bands below Nyquist, see `prepare`
+    transfer: [[f32; OCTAVE_BANDS]; OCTAVE_BANDS],
+    bands: usize,
+    sample_rate: f32,
+    amplitude: f32,
+    active: bool,
+}
+
+impl FilteredNoise {
+    /// Pink-like noise (equal level per octave) at -30 dBFS per band
+    pub fn new() -> Self {
+        let mut noise = Self {
+            rngs: Self::band_rngs(2468),
+            seed: 2468,
+            band_rms: [0.0; OCTAVE_BANDS],
+            band_gains: [0.0; OCTAVE_BANDS],
+            coeffs: [BiquadCoefficients::IDENTITY; OCTAVE_BANDS],
+            states: [BiquadState::default(); OCTAVE_BANDS],
+            transfer: [[0.0; OCTAVE_BANDS]; OCTAVE_BANDS],
+            bands: 0,
+            sample_rate: 0.0,
+            amplitude: 1.0,
+            active: true,
+        };
+        noise.set_tilt(-30.0, 0.0);
+        noise
+    }
+
+    /// Noise matching a measured profile
+    pub fn from_profile(profile: &NoiseProfile) -> Self {
+        let mut noise = Self::new();
+        noise.apply_profile(profile);
+        noise
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Match the band levels of a measured profile
+    pub fn apply_profile(&mut self, profile: &NoiseProfile) {
+        for band in 0..OCTAVE_BANDS {
+            self.band_rms[band] = profile.band_rms(band);
+        }
+        self.solve_gains();
+    }
+
+    /// Set band levels from the 1 kHz band level and a tilt in dB/octave
+    pub fn set_tilt(&mut self, level_db_at_1k: f32, tilt_db_per_octave: f32) {
+        let reference = OCTAVE_BAND_CENTERS.iter().position(|&f| f == 1000.0).unwrap_or(0);
+        for band in 0..OCTAVE_BANDS {
+            let db = level_db_at_1k + tilt_db_per_octave * (band as f32 - reference as f32);
+            self.band_rms[band] = 10f32.powf(db / 20.0);
+        }
+        self.solve_gains();
+    }
+
+    /// Set target RMS level per octave band in dBFS
+    pub fn set_band_levels_db(&mut self, levels: &[f32; OCTAVE_BANDS]) {
+        for (rms, &db) in self.band_rms.iter_mut().zip(levels) {
+            *rms = 10f32.powf(db / 20.0);
+        }
+        self.solve_gains();
+    }
+
+    pub fn set_amplitude(&mut self, amplitude: f32) {
+        self.amplitude = amplitude.clamp(0.0, 1.0);
+    }
+
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed = seed;
+        self.rngs = Self::band_rngs(seed);
+    }
+
+    pub fn start(&mut self) {
+        self.active = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.active = false;
+    }
+
+    pub fn amplitude(&self) -> f32 {
+        self.amplitude
+    }
+
+    fn band_rngs(seed: u32) -> [FastRng; OCTAVE_BANDS] {
+        std::array::from_fn(|band| FastRng::new(seed.wrapping_add(band as u32).wrapping_mul(2_654_435_761)))
+    }
+
+    /// Precompute band filters and gains for `sample_rate`. Allocates; call
+    /// off the audio thread before rendering.
+    pub fn prepare(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        // band centres rise, so the bands below Nyquist come first
+        self.bands = OCTAVE_BAND_CENTERS.iter().take_while(|&&center| center < sample_rate * 0.45).count();
+        for (coeffs, &center) in self.coeffs.iter_mut().zip(&OCTAVE_BAND_CENTERS).take(self.bands) {
+            *coeffs = BiquadCoefficients::band_pass(center, OCTAVE_Q, sample_rate);
+        }
+
+        // The bands overlap, so a band's measured level also picks up its neighbours'
+        // skirts. Build the band-to-band power transfer matrix for uniform bipolar
+        // noise (variance 1/3 spread evenly up to Nyquist); `solve_gains` inverts it.
+        const GRID: usize = 2048;
+        let n = self.bands;
+        let mut response = vec![0.0f32; n * GRID];
+        for band in 0..n {
+            for g in 0..GRID {
+                let freq = (g as f32 + 0.5) * sample_rate * 0.5 / GRID as f32;
+                response[band * GRID + g] = self.coeffs[band].magnitude_squared(freq, sample_rate);
+            }
+        }
+        let density = (1.0 / 3.0) / GRID as f32;
+        self.transfer = [[0.0; OCTAVE_BANDS]; OCTAVE_BANDS];
+        for k in 0..n {
+            for j in 0..n {
+                let overlap: f32 = (0..GRID).map(|g| response[k * GRID + g] * response[j * GRID + g]).sum();
+                self.transfer[k][j] = overlap * density;
+            }
+        }
+        self.solve_gains();
+    }
+
+    /// Per-band gains reaching the target levels through the prepared transfer
+    /// matrix, with a few multiplicative (non-negative) refinement passes.
+    /// RT-safe; nothing to do before `prepare`.
+    fn solve_gains(&mut self) {
+        let n = self.bands;
+        let target: [f32; OCTAVE_BANDS] = std::array::from_fn(|band| self.band_rms[band].powi(2));
+        let mut power_gain: [f32; OCTAVE_BANDS] =
+            std::array::from_fn(|k| if k < n { target[k] / self.transfer[k][k].max(f32::MIN_POSITIVE) } else { 0.0 });
+        for _ in 0..50 {
+            for k in 0..n {
+                let predicted: f32 = (0..n).map(|j| self.transfer[k][j] * power_gain[j]).sum();
+                if predicted > f32::MIN_POSITIVE {
+                    power_gain[k] *= target[k] / predicted;
+                }
+            }
+        }
+        for (gain, power) in self.band_gains.iter_mut().zip(power_gain) {
+            *gain = power.max(0.0).sqrt();
+        }
+    }
+}
+
+impl Default for FilteredNoise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioSource for FilteredNoise {
+    fn fill_buffer(&mut self, output: &mut [f32], sample_rate: f32, channels: usize, frame_count: usize) {
+        // unprepared for this rate: the filters and gains would be wrong
+        if !self.active || self.sample_rate != sample_rate {
+            output.fill(0.0);
+            return;
+        }
+
+        for frame_idx in 0..frame_count {
+            let mut sample = 0.0;
+            for band in 0..OCTAVE_BANDS {
+                if self.band_gains[band] > 0.0 {
+                    let white = self.rngs[band].next_bipolar();
+                    sample += self.states[band].process(&self.coeffs[band], white) * self.band_gains[band];
+                }
+            }
+            let sample = sample * self.amplitude;
+
+            let start = frame_idx * channels;
+            let end = start + channels;
+            for out in &mut output[start..end] {
+                *out = sample;
+            }
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn reset(&mut self) {
+        self.rngs = Self::band_rngs(self.seed);
+        self.states = [BiquadState::default(); OCTAVE_BANDS];
+        self.active = true;
+    }
+}
@@ -1,5 +1,9 @@
+use crate::rt_processing::modulation::{ControlRamp, DEFAULT_CONTROL_INTERVAL, ModulationMonitor};
+use crate::rt_processing::notes::note_to_frequency;
 use crate::rt_processing::voice_renderer::AudioSource;
-use super::tables::{WaveformType, normalize_phase, phase_increment, init_tables};
+use crate::rt_processing::voices::{TriggerVariation, Voice};
+use super::envelopes::ADSREnvelope;
+use super::tables::{Wavetable, WaveformType, normalize_phase, phase_increment, init_tables};
 use crossbeam::atomic::AtomicCell;
 
 /// A versatile oscillator that can generate multiple waveform types
@@ -10,6 +14,7 @@ pub struct Oscillator {
     phase: AtomicCell<f32>,
     active: bool,
     use_interpolation: bool,
+    antialiasing: bool,
 }
 
 impl Oscillator {
@@ -25,17 +30,14 @@ impl Oscillator {
             phase: AtomicCell::new(0.0),
             active: true,
             use_interpolation: true, // High quality by default
+            antialiasing: false,
         }
     }
 
     pub fn next_sample(&mut self, sample_rate: f32) -> f32 {
         let phase_inc = phase_increment(self.frequency, sample_rate);
         let mut current_phase = self.phase.load();
-        let sample = if self.use_interpolation {
-            self.waveform.interpolated_sample(current_phase)
-        } else {
-            self.waveform.fast_sample(current_phase)
-        } * self.amplitude;
+        let sample = self.sample_at(current_phase, phase_inc) * self.amplitude;
         current_phase += phase_inc;
         self.phase.store(normalize_phase(current_phase));
         sample
@@ -73,6 +75,14 @@ impl Oscillator {
         self
     }
     
+    /// Render saw, square and triangle band-limited (polyBLEP), so they don't
+    /// alias at high frequencies; costs a few operations per sample. Takes
+    /// precedence over the interpolation setting for those waveforms.
+    pub fn with_antialiasing(mut self, antialiasing: bool) -> Self {
+        self.antialiasing = antialiasing;
+        self
+    }
+    
     /// Set starting phase (0.0 to 1.0)
     pub fn with_phase(self, phase: f32) -> Self {
         self.phase.store(normalize_phase(phase));
@@ -101,6 +111,10 @@ impl Oscillator {
         self.use_interpolation = use_interpolation;
     }
     
+    pub fn set_antialiasing(&mut self, antialiasing: bool) {
+        self.antialiasing = antialiasing;
+    }
+    
     // Getters
     
     pub fn waveform(&self) -> WaveformType {
@@ -119,6 +133,10 @@ impl Oscillator {
         self.phase.load()
     }
     
+    pub fn antialiasing(&self) -> bool {
+        self.antialiasing
+    }
+    
     // Control methods
     
     pub fn start(&mut self) {
@@ -132,6 +150,17 @@ impl Oscillator {
     pub fn toggle(&mut self) {
         self.active = !self.active;
     }
+    
+    #[inline]
+    fn sample_at(&self, phase: f32, phase_inc: f32) -> f32 {
+        if self.antialiasing && self.waveform != WaveformType::Sine {
+            self.waveform.band_limited_sample(phase, phase_inc)
+        } else if self.use_interpolation {
+            self.waveform.interpolated_sample(phase)
+        } else {
+            self.waveform.fast_sample(phase)
+        }
+    }
 }
 
 impl AudioSource for Oscillator {
@@ -146,11 +175,7 @@ impl AudioSource for Oscillator {
         
         for frame_idx in 0..frame_count {
             // Generate sample based on waveform type and quality setting
-            let sample = if self.use_interpolation {
-                self.waveform.interpolated_sample(current_phase)
-            } else {
-                self.waveform.fast_sample(current_phase)
-            } * self.amplitude;
+            let sample = self.sample_at(current_phase, phase_inc) * self.amplitude;
             
             // Fill all channels for this frame with the same sample
             let start = frame_idx * channels;
@@ -159,11 +184,11 @@ impl AudioSource for Oscillator {
                 *out = sample;
             }
             
-            current_phase += phase_inc;
+            // wrapped every sample, so the phase doesn't depend on how the
+            // render is split into calls
+            current_phase = normalize_phase(current_phase + phase_inc);
         }
         
-        // Normalize phase to prevent accumulation errors
-        current_phase = normalize_phase(current_phase);
         self.phase.store(current_phase);
     }
     
@@ -264,6 +289,179 @@ impl AudioSource for SineOscillator {
     }
 }
 
+/// Plays a `Wavetable`, morphing between its frames.
+///
+/// The position (0.0 = first frame, 1.0 = last) is the sum of the value set
+/// with `set_position`, an offset from an external modulation source
+/// (`set_position_modulation`) and an optional scan LFO. It is evaluated at
+/// control rate (see `ControlRamp`), so jumps glide over one interval instead
+/// of clicking, and published as the `position` modulation reading. Tables
+/// are not band-limited: bright frames alias when played high.
+pub struct WavetableOscillator {
+    table: Wavetable,
+    frequency: f32,
+    amplitude: f32,
+    phase: f32,
+    position: f32,
+    position_offset: f32,
+    scan: LFO,
+    scan_depth: f32,
+    control: ControlRamp,
+    modulation: ModulationMonitor,
+    active: bool,
+}
+
+impl WavetableOscillator {
+    pub fn new(table: Wavetable, frequency: f32) -> Self {
+        Self {
+            table,
+            frequency,
+            amplitude: 0.5,
+            phase: 0.0,
+            position: 0.0,
+            position_offset: 0.0,
+            scan: LFO::new(WaveformType::Triangle, 0.5),
+            scan_depth: 0.0,
+            control: ControlRamp::new(DEFAULT_CONTROL_INTERVAL),
+            modulation: ModulationMonitor::new(&["position"]),
+            active: true,
+        }
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.set_amplitude(amplitude);
+        self
+    }
+
+    /// Frame position (0.0 to 1.0)
+    pub fn with_position(mut self, position: f32) -> Self {
+        self.set_position(position);
+        self
+    }
+
+    /// Sweep the position with an LFO; `depth` is the swing either side of
+    /// the set position (0.0 = off, 1.0 = the whole table)
+    pub fn with_scan(mut self, waveform: WaveformType, rate_hz: f32, depth: f32) -> Self {
+        self.scan.set_waveform(waveform);
+        self.scan.set_frequency(rate_hz);
+        self.set_scan_depth(depth);
+        self
+    }
+
+    /// Samples between position updates (1 = every sample)
+    pub fn with_control_interval(mut self, interval: usize) -> Self {
+        self.control.set_interval(interval);
+        self
+    }
+
+    /// Swap the table, e.g. one loaded with `Wavetable::load_job`. Keeps the
+    /// phase and position, so the change is heard from the next sample.
+    pub fn set_table(&mut self, table: Wavetable) {
+        self.table = table;
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency;
+    }
+
+    pub fn set_amplitude(&mut self, amplitude: f32) {
+        self.amplitude = amplitude.clamp(0.0, 1.0);
+    }
+
+    pub fn set_position(&mut self, position: f32) {
+        self.position = position.clamp(0.0, 1.0);
+    }
+
+    /// Offset added to the position by an external source (envelope,
+    /// automation); the sum is clamped to the table
+    pub fn set_position_modulation(&mut self, offset: f32) {
+        self.position_offset = offset;
+    }
+
+    pub fn set_scan_rate(&mut self, rate_hz: f32) {
+        self.scan.set_frequency(rate_hz);
+    }
+
+    pub fn set_scan_waveform(&mut self, waveform: WaveformType) {
+        self.scan.set_waveform(waveform);
+    }
+
+    pub fn set_scan_depth(&mut self, depth: f32) {
+        self.scan_depth = depth.clamp(0.0, 1.0);
+    }
+
+    pub fn table(&self) -> &Wavetable {
+        &self.table
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    pub fn amplitude(&self) -> f32 {
+        self.amplitude
+    }
+
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+
+    pub fn scan_depth(&self) -> f32 {
+        self.scan_depth
+    }
+
+    /// Live view of the modulated position, for the UI
+    pub fn modulation(&self) -> ModulationMonitor {
+        self.modulation.clone()
+    }
+
+    pub fn start(&mut self) {
+        self.active = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.active = false;
+    }
+}
+
+impl AudioSource for WavetableOscillator {
+    fn fill_buffer(&mut self, output: &mut [f32], sample_rate: f32, channels: usize, frame_count: usize) {
+        if !self.active {
+            output.fill(0.0);
+            return;
+        }
+
+        let phase_inc = phase_increment(self.frequency, sample_rate);
+        let base = self.position + self.position_offset;
+        let mut position = base;
+        for frame_idx in 0..frame_count {
+            let (scan, depth) = (&mut self.scan, self.scan_depth);
+            position = self.control.next(|n| (base + scan.advance(n, sample_rate) * depth).clamp(0.0, 1.0));
+            let sample = self.table.sample(self.phase, position) * self.amplitude;
+
+            let start = frame_idx * channels;
+            for out in &mut output[start..start + channels] {
+                *out = sample;
+            }
+
+            self.phase = normalize_phase(self.phase + phase_inc);
+        }
+        self.modulation.publish(0, self.position, position - self.position);
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn reset(&mut self) {
+        self.phase = 0.0;
+        self.scan.set_phase(0.0);
+        self.control.reset();
+        self.modulation.clear();
+        self.active = true;
+    }
+}
+
 /// An LFO (Low Frequency Oscillator) for modulation purposes
 /// Typically used for vibrato, tremolo, filter sweeps, etc.
 pub struct LFO {
@@ -299,6 +497,12 @@ impl LFO {
         (self.oscillator.next_sample(sample_rate) * self.depth) + self.offset
     }
 
+    /// Get the current LFO value, then move `frames` samples ahead at once
+    /// (control-rate evaluation)
+    pub fn advance(&mut self, frames: usize, sample_rate: f32) -> f32 {
+        self.get_value(sample_rate / frames.max(1) as f32)
+    }
+
     
     pub fn set_frequency(&mut self, frequency: f32) {
         self.oscillator.set_frequency(frequency);
@@ -311,6 +515,23 @@ impl LFO {
     pub fn set_offset(&mut self, offset: f32) {
         self.offset = offset.clamp(-1.0, 1.0);
     }
+
+    pub fn set_waveform(&mut self, waveform: WaveformType) {
+        self.oscillator.set_waveform(waveform);
+    }
+
+    /// Set the LFO phase (0.0 to 1.0), e.g. to restart it on a beat
+    pub fn set_phase(&mut self, phase: f32) {
+        self.oscillator.set_phase(phase);
+    }
+
+    pub fn waveform(&self) -> WaveformType {
+        self.oscillator.waveform()
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.oscillator.frequency()
+    }
     
     pub fn start(&mut self) {
         self.oscillator.start();
@@ -319,4 +540,106 @@ impl LFO {
     pub fn stop(&mut self) {
         self.oscillator.stop();
     }
-}
\ No newline at end of file
+}
+
+/// One `VoicePool` voice: an oscillator under an ADSR envelope, playing each
+/// note at its MIDI pitch.
+///
+/// Takes the pool's trigger variation: the detune shifts the note, the start
+/// offset starts the wave that far into its cycle, and the round-robin
+/// alternate picks one of the waveforms given to `with_alternates`.
+pub struct OscillatorVoice {
+    oscillator: Oscillator,
+    envelope: ADSREnvelope,
+    /// Waveform per round-robin alternate; empty keeps the oscillator's own
+    alternates: Vec<WaveformType>,
+    variation: TriggerVariation,
+    note: u8,
+    /// Semitones
+    bend: f32,
+    velocity: f32,
+}
+
+impl OscillatorVoice {
+    pub fn new(waveform: WaveformType, envelope: ADSREnvelope) -> Self {
+        let mut envelope = envelope;
+        envelope.reset();
+        Self {
+            oscillator: Oscillator::new(waveform, note_to_frequency(69.0)),
+            envelope,
+            alternates: Vec::new(),
+            variation: TriggerVariation::default(),
+            note: 69,
+            bend: 0.0,
+            velocity: 0.0,
+        }
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.oscillator.set_amplitude(amplitude);
+        self
+    }
+
+    /// Waveforms cycled through by the pool's round robin, alternate `n`
+    /// playing `waveforms[n % len]`
+    pub fn with_alternates(mut self, waveforms: Vec<WaveformType>) -> Self {
+        self.alternates = waveforms;
+        self
+    }
+
+    pub fn oscillator(&self) -> &Oscillator {
+        &self.oscillator
+    }
+
+    fn retune(&mut self) {
+        let semitones = self.note as f32 + self.bend + self.variation.detune_cents / 100.0;
+        self.oscillator.set_frequency(note_to_frequency(semitones));
+    }
+}
+
+impl Voice for OscillatorVoice {
+    fn note_on(&mut self, note: u8, velocity: f32) {
+        self.note = note;
+        self.velocity = velocity.clamp(0.0, 1.0);
+        if !self.alternates.is_empty() {
+            self.oscillator.set_waveform(self.alternates[self.variation.round_robin % self.alternates.len()]);
+        }
+        self.retune();
+        // a periodic wave skips whole cycles for free, so only the phase counts
+        self.oscillator.set_phase(self.variation.start_offset * self.oscillator.frequency());
+        self.oscillator.start();
+        self.envelope.note_on();
+    }
+
+    fn vary(&mut self, variation: &TriggerVariation) {
+        self.variation = *variation;
+    }
+
+    fn note_off(&mut self) {
+        self.envelope.note_off();
+    }
+
+    fn pitch_bend(&mut self, semitones: f32) {
+        self.bend = semitones;
+        self.retune();
+    }
+
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        for i in 0..frames {
+            let level = self.envelope.get_value(sample_rate) * self.velocity;
+            let sample = self.oscillator.next_sample(sample_rate) * level;
+            for channel in output.iter_mut() {
+                channel[i] = sample;
+            }
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.envelope.is_active()
+    }
+
+    fn reset(&mut self) {
+        self.envelope.reset();
+        self.oscillator.reset();
+    }
+}
@@ -0,0 +1,651 @@
+//! Sample playback: recorded audio played back as a waveform source.
+//!
+//! A `SamplePlayer` plays a decoded `SampleBuffer` (loaded on its own or
+//! shared through a `SampleCache`) at any pitch, resampling by linear
+//! interpolation, so a sample recorded at 44.1 kHz plays at its own pitch in
+//! a 48 kHz graph. Only WAV files load for now. A `SamplerVoice` plays
+//! samples as notes in a `VoicePool`.
+//!
+//! Files too long to hold in memory (backing tracks) play through a
+//! `StreamingSamplePlayer` instead, which a worker thread keeps fed from disk.
+
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam::queue::ArrayQueue;
+use spin::Mutex;
+
+use crate::io::sample_cache::SampleBuffer;
+use crate::io::wav::{WavError, WavResult, WavStream};
+use crate::rt_processing::resampler::Resampler;
+use crate::rt_processing::voice_renderer::AudioSource;
+use crate::rt_processing::voices::{MAX_VOICE_CHANNELS, TriggerVariation, Voice};
+
+use super::envelopes::ADSREnvelope;
+
+/// Furthest the pitch goes either way, in semitones
+pub const MAX_SAMPLE_PITCH: f32 = 48.0;
+/// Audio a `StreamingSamplePlayer` keeps read ahead, in seconds
+pub const DEFAULT_READ_AHEAD_SECONDS: f32 = 2.0;
+/// Most channels a streamed file may have
+pub const MAX_STREAM_CHANNELS: usize = 8;
+/// Frames the worker reads from disk at a time
+const STREAM_CHUNK_FRAMES: usize = 4096;
+/// How long the worker sleeps once the ring is full
+const STREAM_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Plays a sample from a start frame, optionally looping a region of it.
+///
+/// A new player is playing. When it reaches the end (or `stop` is called) it
+/// goes silent; a one-shot player also reports itself inactive then, so the
+/// router drops it, while any other waits for `start`. Output channels take
+/// the sample's channels in turn, so a mono sample plays on every channel.
+pub struct SamplePlayer {
+    sample: Arc<SampleBuffer>,
+    /// Semitones
+    pitch: f32,
+    gain: f32,
+    /// Frame `start` plays from
+    start: usize,
+    loop_range: Option<Range<usize>>,
+    one_shot: bool,
+    playing: bool,
+    /// Frames into the sample
+    position: f64,
+}
+
+impl SamplePlayer {
+    pub fn new(sample: Arc<SampleBuffer>) -> Self {
+        Self {
+            sample,
+            pitch: 0.0,
+            gain: 1.0,
+            start: 0,
+            loop_range: None,
+            one_shot: false,
+            playing: true,
+            position: 0.0,
+        }
+    }
+
+    /// Load a WAV file and play it. Not RT-safe.
+    pub fn load(path: impl AsRef<Path>) -> WavResult<Self> {
+        Ok(Self::new(Arc::new(SampleBuffer::load(path)?)))
+    }
+
+    pub fn with_pitch(mut self, semitones: f32) -> Self {
+        self.set_pitch(semitones);
+        self
+    }
+
+    pub fn with_gain(mut self, gain: f32) -> Self {
+        self.set_gain(gain);
+        self
+    }
+
+    /// Play from `frame`, now and on every `start`
+    pub fn with_start(mut self, frame: usize) -> Self {
+        self.set_start(frame);
+        self.position = self.start as f64;
+        self
+    }
+
+    pub fn with_loop(mut self, range: Option<Range<usize>>) -> Self {
+        self.set_loop(range);
+        self
+    }
+
+    pub fn with_one_shot(mut self, one_shot: bool) -> Self {
+        self.one_shot = one_shot;
+        self
+    }
+
+    /// Playback rate as a pitch shift, in semitones
+    pub fn set_pitch(&mut self, semitones: f32) {
+        self.pitch = semitones.clamp(-MAX_SAMPLE_PITCH, MAX_SAMPLE_PITCH);
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain.max(0.0);
+    }
+
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Frame `start` plays from; takes effect at the next `start`
+    pub fn set_start(&mut self, frame: usize) {
+        self.start = frame.min(self.sample.frames());
+    }
+
+    pub fn start_frame(&self) -> usize {
+        self.start
+    }
+
+    /// Frames to repeat once playback reaches the end of the range; `None`,
+    /// or an empty range once clamped to the sample, plays straight through.
+    /// Playback starting after the range never loops.
+    pub fn set_loop(&mut self, range: Option<Range<usize>>) {
+        let frames = self.sample.frames();
+        self.loop_range =
+            range.map(|range| range.start.min(frames)..range.end.min(frames)).filter(|range| !range.is_empty());
+    }
+
+    pub fn loop_range(&self) -> Option<Range<usize>> {
+        self.loop_range.clone()
+    }
+
+    /// Drop out of the router once finished
+    pub fn set_one_shot(&mut self, one_shot: bool) {
+        self.one_shot = one_shot;
+    }
+
+    pub fn is_one_shot(&self) -> bool {
+        self.one_shot
+    }
+
+    /// Play from the start frame, from wherever it was
+    pub fn start(&mut self) {
+        self.position = self.start as f64;
+        self.playing = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Frames into the sample, fractional when pitched
+    pub fn position(&self) -> f64 {
+        self.position
+    }
+
+    pub fn sample(&self) -> &Arc<SampleBuffer> {
+        &self.sample
+    }
+
+    /// Sample frames per output frame at `sample_rate`
+    fn step(&self, sample_rate: f32) -> f64 {
+        (self.pitch as f64 / 12.0).exp2() * self.sample.sample_rate() as f64 / sample_rate as f64
+    }
+
+    /// Channel `channel` at the current position, interpolated
+    fn read(&self, channel: &[f32]) -> f32 {
+        let index = self.position as usize;
+        let frac = (self.position - index as f64) as f32;
+        let next = match &self.loop_range {
+            // the loop's end joins back to its start
+            Some(range) if index + 1 == range.end => range.start,
+            _ => index + 1,
+        };
+        let a = channel.get(index).copied().unwrap_or(0.0);
+        let b = channel.get(next).copied().unwrap_or(0.0);
+        a + (b - a) * frac
+    }
+}
+
+impl AudioSource for SamplePlayer {
+    fn fill_buffer(&mut self, output: &mut [f32], sample_rate: f32, channels: usize, frame_count: usize) {
+        output.fill(0.0);
+        let sources = self.sample.channel_count();
+        if !self.playing || sources == 0 || channels == 0 {
+            return;
+        }
+        let step = self.step(sample_rate);
+        let frames = self.sample.frames() as f64;
+        for frame in output.chunks_exact_mut(channels).take(frame_count) {
+            if self.position >= frames {
+                self.playing = false;
+                break;
+            }
+            for (ch, out) in frame.iter_mut().enumerate() {
+                *out = self.read(&self.sample.channels()[ch % sources]) * self.gain;
+            }
+            let before = self.position;
+            self.position += step;
+            if let Some(range) = &self.loop_range
+                && before < range.end as f64
+                && self.position >= range.end as f64
+            {
+                let length = range.len() as f64;
+                self.position = range.start as f64 + (self.position - range.end as f64) % length;
+            }
+        }
+        if self.position >= frames {
+            self.playing = false;
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.playing || !self.one_shot
+    }
+
+    /// Back to playing from the start frame
+    fn reset(&mut self) {
+        self.start();
+    }
+}
+
+/// One `VoicePool` voice: a sample played under an ADSR envelope, pitched by
+/// the note's distance from the sample's root note.
+///
+/// Takes the pool's trigger variation: the start offset skips that far into
+/// the sample, the detune shifts the pitch, and the round-robin alternate picks
+/// one of the samples given to `with_alternates`. A note that plays its sample
+/// out ends there, released or not.
+pub struct SamplerVoice {
+    /// One player per round-robin alternate
+    players: Vec<SamplePlayer>,
+    /// Player of the current note
+    current: usize,
+    root: u8,
+    envelope: ADSREnvelope,
+    variation: TriggerVariation,
+    note: u8,
+    /// Semitones
+    bend: f32,
+    velocity: f32,
+    /// Interleaved, `max_frames` of `MAX_VOICE_CHANNELS`
+    scratch: Vec<f32>,
+}
+
+impl SamplerVoice {
+    /// Play `sample` at its own pitch on `root`. `max_frames` is the longest
+    /// block the voice is rendered in; longer ones are rendered in pieces.
+    pub fn new(sample: Arc<SampleBuffer>, root: u8, envelope: ADSREnvelope, max_frames: usize) -> Self {
+        let mut envelope = envelope;
+        envelope.reset();
+        Self {
+            players: vec![Self::player(sample)],
+            current: 0,
+            root,
+            envelope,
+            variation: TriggerVariation::default(),
+            note: root,
+            bend: 0.0,
+            velocity: 0.0,
+            scratch: vec![0.0; max_frames.max(1) * MAX_VOICE_CHANNELS],
+        }
+    }
+
+    /// Samples cycled through by the pool's round robin, alternate `n` playing
+    /// `samples[n % len]`; they replace the sample given to `new`
+    pub fn with_alternates(mut self, samples: Vec<Arc<SampleBuffer>>) -> Self {
+        if !samples.is_empty() {
+            self.players = samples.into_iter().map(Self::player).collect();
+        }
+        self
+    }
+
+    /// The sample the current (or last) note plays
+    pub fn sample(&self) -> &Arc<SampleBuffer> {
+        self.players[self.current].sample()
+    }
+
+    fn player(sample: Arc<SampleBuffer>) -> SamplePlayer {
+        let mut player = SamplePlayer::new(sample);
+        player.stop();
+        player
+    }
+
+    fn retune(&mut self) {
+        let semitones = self.note as f32 - self.root as f32 + self.bend + self.variation.detune_cents / 100.0;
+        self.players[self.current].set_pitch(semitones);
+    }
+}
+
+impl Voice for SamplerVoice {
+    fn note_on(&mut self, note: u8, velocity: f32) {
+        self.players[self.current].stop();
+        self.current = self.variation.round_robin % self.players.len();
+        self.note = note;
+        self.velocity = velocity.clamp(0.0, 1.0);
+        self.retune();
+        let player = &mut self.players[self.current];
+        let offset = self.variation.start_offset * player.sample().sample_rate() as f32;
+        player.set_start(offset as usize);
+        player.start();
+        self.envelope.note_on();
+    }
+
+    fn vary(&mut self, variation: &TriggerVariation) {
+        self.variation = *variation;
+    }
+
+    fn note_off(&mut self) {
+        self.envelope.note_off();
+    }
+
+    fn pitch_bend(&mut self, semitones: f32) {
+        self.bend = semitones;
+        self.retune();
+    }
+
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        let channels = output.len().min(MAX_VOICE_CHANNELS);
+        if channels == 0 {
+            return;
+        }
+        let piece = self.scratch.len() / channels;
+        let player = &mut self.players[self.current];
+        let mut done = 0;
+        while done < frames {
+            let n = (frames - done).min(piece);
+            let scratch = &mut self.scratch[..n * channels];
+            player.fill_buffer(scratch, sample_rate, channels, n);
+            for (i, frame) in scratch.chunks_exact(channels).enumerate() {
+                let level = self.envelope.get_value(sample_rate) * self.velocity;
+                for (out, sample) in output.iter_mut().zip(frame) {
+                    out[done + i] = sample * level;
+                }
+            }
+            done += n;
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.envelope.is_active() && self.players[self.current].is_playing()
+    }
+
+    fn reset(&mut self) {
+        self.envelope.reset();
+        self.players[self.current].stop();
+    }
+}
+
+/// State shared by a streaming player and its worker
+struct StreamShared {
+    /// Interleaved at the output rate, whole frames only
+    queue: ArrayQueue<f32>,
+    channels: usize,
+    looping: AtomicBool,
+    restart: AtomicBool,
+    /// The worker has queued the last frame
+    finished: AtomicBool,
+    /// The player is gone; the worker stops
+    closed: AtomicBool,
+    /// Frames played as silence because the worker fell behind
+    underruns: AtomicU64,
+    error: Mutex<Option<String>>,
+}
+
+/// Disk side of a streaming player: reads the file in chunks, converts it to
+/// the output rate and queues it
+struct StreamWorker {
+    shared: Arc<StreamShared>,
+    stream: WavStream,
+    resampler: Option<Resampler>,
+    /// Next file frame to read
+    position: usize,
+    /// Queue space one chunk can take, in samples
+    chunk_samples: usize,
+}
+
+impl StreamWorker {
+    fn run(mut self) {
+        while !self.shared.closed.load(Ordering::Acquire) {
+            // what's queued still plays: only the audio thread pops
+            if self.shared.restart.swap(false, Ordering::AcqRel) {
+                self.position = 0;
+                if let Some(resampler) = &mut self.resampler {
+                    resampler.reset();
+                }
+                self.shared.finished.store(false, Ordering::Release);
+            }
+            if !self.fill_chunk() {
+                thread::sleep(STREAM_POLL_INTERVAL);
+            }
+        }
+    }
+
+    /// Queue until the ring is full or the file ends
+    fn fill(&mut self) {
+        while self.fill_chunk() {}
+    }
+
+    /// Queue one chunk; false if there was no room or nothing left to read
+    fn fill_chunk(&mut self) -> bool {
+        let shared = &self.shared;
+        if shared.finished.load(Ordering::Acquire) {
+            // looping switched on after the end: carry on from the start
+            if !shared.looping.load(Ordering::Relaxed) || shared.error.lock().is_some() {
+                return false;
+            }
+            self.position = 0;
+            shared.finished.store(false, Ordering::Release);
+        }
+        if shared.queue.capacity() - shared.queue.len() < self.chunk_samples {
+            return false;
+        }
+        let audio = match self.stream.read(self.position, STREAM_CHUNK_FRAMES) {
+            Ok(audio) => audio,
+            Err(e) => {
+                *shared.error.lock() = Some(e.to_string());
+                shared.finished.store(true, Ordering::Release);
+                return false;
+            }
+        };
+        let frames = audio.frames();
+        if frames == 0 {
+            if shared.looping.load(Ordering::Relaxed) && self.position > 0 {
+                self.position = 0;
+                return true;
+            }
+            // what the resampler still holds comes out with a tail of silence
+            if let Some(resampler) = &mut self.resampler {
+                let tail = vec![0.0; resampler.latency() * shared.channels];
+                resampler.push(&tail, |converted| push_all(&shared.queue, converted));
+            }
+            shared.finished.store(true, Ordering::Release);
+            return false;
+        }
+        self.position += frames;
+        let interleaved: Vec<f32> =
+            (0..frames).flat_map(|i| audio.channels.iter().map(move |channel| channel[i])).collect();
+        match &mut self.resampler {
+            Some(resampler) => resampler.push(&interleaved, |converted| push_all(&shared.queue, converted)),
+            None => push_all(&shared.queue, &interleaved),
+        }
+        true
+    }
+}
+
+fn push_all(queue: &ArrayQueue<f32>, samples: &[f32]) {
+    for &sample in samples {
+        // room for the whole chunk was checked, and only the worker pushes
+        let _ = queue.push(sample);
+    }
+}
+
+/// Plays a long WAV file from disk without loading it.
+///
+/// A worker thread reads ahead into a ring (`DEFAULT_READ_AHEAD_SECONDS` by
+/// default) and converts the file to the output rate; the audio thread only
+/// takes frames out of the ring and never blocks. Should the worker fall
+/// behind, the missing frames play as silence and are counted in
+/// `underruns`. Looping and restarting take effect on the worker's side, so
+/// up to the read-ahead of audio already queued plays first.
+///
+/// Output channels take the file's channels in turn. The player stays active
+/// until the file has played out (never, while looping), then the router
+/// drops it and the worker stops.
+pub struct StreamingSamplePlayer {
+    shared: Arc<StreamShared>,
+    gain: f32,
+    playing: bool,
+    /// Output rate the worker converts to
+    sample_rate: u32,
+    frame: [f32; MAX_STREAM_CHANNELS],
+}
+
+impl StreamingSamplePlayer {
+    /// Open `path` for playback at `sample_rate`, with the first read-ahead
+    /// queued before this returns. Not RT-safe.
+    pub fn open(path: impl AsRef<Path>, sample_rate: u32) -> WavResult<Self> {
+        Self::with_read_ahead(path, sample_rate, DEFAULT_READ_AHEAD_SECONDS)
+    }
+
+    /// `open` with the read-ahead in seconds
+    pub fn with_read_ahead(path: impl AsRef<Path>, sample_rate: u32, seconds: f32) -> WavResult<Self> {
+        let stream = WavStream::open(path)?;
+        let channels = stream.channels();
+        if channels > MAX_STREAM_CHANNELS {
+            return Err(WavError::Unsupported(format!("cannot stream {} channels", channels)));
+        }
+        let ratio = sample_rate as f64 / stream.sample_rate().max(1) as f64;
+        let resampler = (stream.sample_rate() != sample_rate)
+            .then(|| Resampler::with_block(channels, stream.sample_rate(), sample_rate, STREAM_CHUNK_FRAMES));
+        let chunk_samples = ((STREAM_CHUNK_FRAMES as f64 * ratio).ceil() as usize + 1) * channels;
+        let frames = (seconds.max(0.0) * sample_rate as f32) as usize;
+        let shared = Arc::new(StreamShared {
+            queue: ArrayQueue::new((frames * channels).max(2 * chunk_samples)),
+            channels,
+            looping: AtomicBool::new(false),
+            restart: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            underruns: AtomicU64::new(0),
+            error: Mutex::new(None),
+        });
+        let mut worker = StreamWorker { shared: Arc::clone(&shared), stream, resampler, position: 0, chunk_samples };
+        worker.fill();
+        thread::Builder::new().name("pulsar-stream".into()).spawn(move || worker.run())?;
+        Ok(Self { shared, gain: 1.0, playing: true, sample_rate, frame: [0.0; MAX_STREAM_CHANNELS] })
+    }
+
+    pub fn with_gain(mut self, gain: f32) -> Self {
+        self.set_gain(gain);
+        self
+    }
+
+    pub fn with_looping(self, looping: bool) -> Self {
+        self.set_looping(looping);
+        self
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain.max(0.0);
+    }
+
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Go back to the start at the end of the file instead of finishing
+    pub fn set_looping(&self, looping: bool) {
+        self.shared.looping.store(looping, Ordering::Relaxed);
+        // a file already read to the end goes round again
+        if looping && self.shared.error.lock().is_none() {
+            self.shared.finished.store(false, Ordering::Release);
+        }
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.shared.looping.load(Ordering::Relaxed)
+    }
+
+    /// Continue from where it was paused
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    /// Hold the position; the ring stays full
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Have the worker start again from the beginning of the file
+    pub fn restart(&self) {
+        self.shared.restart.store(true, Ordering::Release);
+    }
+
+    /// File channels
+    pub fn channels(&self) -> usize {
+        self.shared.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Frames queued and ready to play
+    pub fn buffered_frames(&self) -> usize {
+        self.shared.queue.len() / self.shared.channels.max(1)
+    }
+
+    /// Whether the rest of the file is queued, so nothing more is coming
+    pub fn is_fully_buffered(&self) -> bool {
+        self.shared.finished.load(Ordering::Acquire)
+    }
+
+    /// Whether the whole file has played (and isn't looping)
+    pub fn is_finished(&self) -> bool {
+        self.shared.finished.load(Ordering::Acquire) && self.shared.queue.len() < self.shared.channels.max(1)
+    }
+
+    pub fn underruns(&self) -> u64 {
+        self.shared.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Why reading stopped early, if it did
+    pub fn error(&self) -> Option<String> {
+        self.shared.error.lock().clone()
+    }
+}
+
+impl Drop for StreamingSamplePlayer {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+    }
+}
+
+impl AudioSource for StreamingSamplePlayer {
+    fn fill_buffer(&mut self, output: &mut [f32], _sample_rate: f32, channels: usize, frame_count: usize) {
+        output.fill(0.0);
+        let sources = self.shared.channels;
+        if !self.playing || sources == 0 || channels == 0 {
+            return;
+        }
+        let gain = self.gain;
+        for (i, frame) in output.chunks_exact_mut(channels).take(frame_count).enumerate() {
+            // only this side pops, so a whole frame queued stays there
+            if self.shared.queue.len() < sources {
+                if !self.shared.finished.load(Ordering::Acquire) {
+                    self.shared.underruns.fetch_add((frame_count - i) as u64, Ordering::Relaxed);
+                }
+                return;
+            }
+            for sample in &mut self.frame[..sources] {
+                *sample = self.shared.queue.pop().unwrap_or(0.0);
+            }
+            for (ch, out) in frame.iter_mut().enumerate() {
+                *out = self.frame[ch % sources] * gain;
+            }
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        !self.is_finished()
+    }
+
+    /// Play from the start of the file
+    fn reset(&mut self) {
+        self.restart();
+        self.playing = true;
+    }
+}
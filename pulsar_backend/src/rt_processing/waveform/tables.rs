@@ -1,5 +1,9 @@
 use std::f32::consts::PI;
-use std::sync::OnceLock;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use crate::io::wav::{self, WavResult};
+use crate::jobs::{JobHandle, JobPriority, WorkerPool};
 
 // Optimized sine table configuration
 const SINE_TABLE_SIZE: usize = 8192; // Power of 2 for fast masking
@@ -152,6 +156,36 @@ pub fn phase_increment(frequency: f32, sample_rate: f32) -> f32 {
     frequency / sample_rate
 }
 
+/// polyBLEP residual of a rising step of 2 at phase 0, for a phase advancing
+/// `phase_inc` per sample. Non-zero only within one sample of the step.
+#[inline]
+pub fn poly_blep(phase: f32, phase_inc: f32) -> f32 {
+    if phase < phase_inc {
+        let x = phase / phase_inc;
+        2.0 * x - x * x - 1.0
+    } else if phase > 1.0 - phase_inc {
+        let x = (phase - 1.0) / phase_inc;
+        x * x + 2.0 * x + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// polyBLAMP residual of a corner at phase 0 whose slope rises by 1 per
+/// sample; the integral of `poly_blep`
+#[inline]
+pub fn poly_blamp(phase: f32, phase_inc: f32) -> f32 {
+    let x = if phase < phase_inc {
+        phase / phase_inc
+    } else if phase > 1.0 - phase_inc {
+        (1.0 - phase) / phase_inc
+    } else {
+        return 0.0;
+    };
+    let r = 1.0 - x;
+    r * r * r / 6.0
+}
+
 /// Waveform type enumeration for dynamic waveform selection
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum WaveformType {
@@ -182,6 +216,34 @@ impl WaveformType {
         }
     }
     
+    /// Get an anti-aliased sample: the exact waveform with its steps (saw,
+    /// square) and corners (triangle) smoothed by polyBLEP / polyBLAMP, for a
+    /// phase advancing `phase_inc` per sample. The sine is the interpolated table.
+    pub fn band_limited_sample(self, phase: f32, phase_inc: f32) -> f32 {
+        // past Nyquist the corrections overlap; nothing is band-limited there anyway
+        let dt = phase_inc.abs().min(0.5);
+        match self {
+            WaveformType::Sine => interpolated_sine(phase),
+            WaveformType::Triangle => {
+                let naive = if phase < 0.25 {
+                    4.0 * phase
+                } else if phase < 0.75 {
+                    2.0 - 4.0 * phase
+                } else {
+                    4.0 * phase - 4.0
+                };
+                // slope turns by -8 per cycle at the peak, +8 at the trough
+                naive - 8.0 * dt * poly_blamp(normalize_phase(phase + 0.75), dt)
+                    + 8.0 * dt * poly_blamp(normalize_phase(phase + 0.25), dt)
+            }
+            WaveformType::Sawtooth => 2.0 * phase - 1.0 - poly_blep(phase, dt),
+            WaveformType::Square => {
+                let naive = if phase < 0.5 { 1.0 } else { -1.0 };
+                naive + poly_blep(phase, dt) - poly_blep(normalize_phase(phase + 0.5), dt)
+            }
+        }
+    }
+
     /// Get the lookup table for this waveform type
     pub fn table(self) -> &'static [f32] {
         match self {
@@ -191,4 +253,123 @@ impl WaveformType {
             WaveformType::Square => get_square_table(),
         }
     }
-}
\ No newline at end of file
+}
+
+/// Frame length of the common wavetable file layout
+pub const DEFAULT_WAVETABLE_FRAME: usize = 2048;
+
+/// Single-cycle frames for `WavetableOscillator`, all of the same length and
+/// stored back to back. Clones share the samples.
+#[derive(Clone, Debug)]
+pub struct Wavetable {
+    samples: Arc<[f32]>,
+    frame_len: usize,
+}
+
+impl Wavetable {
+    /// Frames shorter than the longest are resampled to its length; empty
+    /// frames are dropped, and a table without any becomes one sine cycle
+    pub fn new(frames: Vec<Vec<f32>>) -> Self {
+        let frames: Vec<Vec<f32>> = frames.into_iter().filter(|f| !f.is_empty()).collect();
+        let Some(frame_len) = frames.iter().map(Vec::len).max() else {
+            return Self::from_waveforms(&[WaveformType::Sine], DEFAULT_WAVETABLE_FRAME);
+        };
+        let mut samples = Vec::with_capacity(frames.len() * frame_len);
+        for frame in &frames {
+            if frame.len() == frame_len {
+                samples.extend_from_slice(frame);
+            } else {
+                samples.extend((0..frame_len).map(|i| cyclic_lookup(frame, i as f32 / frame_len as f32)));
+            }
+        }
+        Self { samples: samples.into(), frame_len }
+    }
+
+    /// Split `samples` into frames of `frame_len`, as wavetable files store
+    /// them; a trailing partial frame is dropped. A `frame_len` of 0, or one
+    /// longer than `samples`, takes the whole slice as one frame.
+    pub fn from_slice(samples: &[f32], frame_len: usize) -> Self {
+        if frame_len == 0 || frame_len > samples.len() {
+            return Self::new(vec![samples.to_vec()]);
+        }
+        Self::new(samples.chunks_exact(frame_len).map(<[f32]>::to_vec).collect())
+    }
+
+    /// One frame per built-in waveform, `frame_len` samples each
+    pub fn from_waveforms(waveforms: &[WaveformType], frame_len: usize) -> Self {
+        init_tables();
+        let frame_len = frame_len.max(1);
+        let frames = waveforms
+            .iter()
+            .map(|w| (0..frame_len).map(|i| w.interpolated_sample(i as f32 / frame_len as f32)).collect())
+            .collect();
+        Self::new(frames)
+    }
+
+    /// Load a user wavetable from a WAV file of frames of `frame_len` back to
+    /// back; multichannel files are mixed to mono
+    pub fn from_wav(path: impl AsRef<Path>, frame_len: usize) -> WavResult<Self> {
+        let audio = wav::read_file(path)?;
+        let scale = 1.0 / audio.channels.len().max(1) as f32;
+        let mono: Vec<f32> = (0..audio.frames())
+            .map(|i| audio.channels.iter().map(|c| c[i]).sum::<f32>() * scale)
+            .collect();
+        Ok(Self::from_slice(&mono, frame_len))
+    }
+
+    /// Load a user wavetable on a background job
+    pub fn load_job(pool: &WorkerPool, path: impl Into<PathBuf>, frame_len: usize) -> JobHandle<WavResult<Self>> {
+        let path = path.into();
+        pool.spawn(JobPriority::Io, format!("wavetable load {}", path.display()), move |_| {
+            Self::from_wav(path, frame_len)
+        })
+    }
+
+    /// Scale so the loudest sample of the table peaks at 1.0
+    pub fn normalized(self) -> Self {
+        let peak = self.samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        if peak <= 0.0 {
+            return self;
+        }
+        Self { samples: self.samples.iter().map(|s| s / peak).collect(), frame_len: self.frame_len }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / self.frame_len
+    }
+
+    /// Samples per frame
+    pub fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+
+    pub fn frame(&self, index: usize) -> Option<&[f32]> {
+        self.samples.get(index * self.frame_len..(index + 1) * self.frame_len)
+    }
+
+    /// Sample at `phase` (0.0 to 1.0) of the cycle at `position` (0.0 = first
+    /// frame, 1.0 = last), interpolated within and between frames
+    #[inline]
+    pub fn sample(&self, phase: f32, position: f32) -> f32 {
+        let last = self.frame_count() - 1;
+        let scaled = position.clamp(0.0, 1.0) * last as f32;
+        let index = (scaled as usize).min(last);
+        let a = cyclic_lookup(&self.samples[index * self.frame_len..(index + 1) * self.frame_len], phase);
+        let frac = scaled - index as f32;
+        if frac <= 0.0 || index == last {
+            return a;
+        }
+        let b = cyclic_lookup(&self.samples[(index + 1) * self.frame_len..(index + 2) * self.frame_len], phase);
+        a + frac * (b - a)
+    }
+}
+
+/// Linear lookup into a single cycle of any length, wrapping at the end
+#[inline]
+fn cyclic_lookup(cycle: &[f32], phase: f32) -> f32 {
+    let scaled = normalize_phase(phase) * cycle.len() as f32;
+    let index = (scaled as usize).min(cycle.len() - 1);
+    let frac = scaled - index as f32;
+    let next = if index + 1 == cycle.len() { 0 } else { index + 1 };
+    cycle[index] + frac * (cycle[next] - cycle[index])
+}
@@ -0,0 +1,119 @@
+//! Bus auto-trim: slow compensating gain when the sum of many sources stays
+//! above the headroom target, warnings on the event bus, and the source count
+//! feeding the level estimate.
+
+mod common;
+
+use pulsar_backend::rt_processing::events::{EngineEvent, EventBus};
+use pulsar_backend::rt_processing::headroom::{AutoTrimConfig, AutoTrimMode};
+use pulsar_backend::rt_processing::routing::Router;
+
+use common::{CENTRE, Square, router};
+
+const FRAMES: usize = 256;
+const SAMPLE_RATE: f32 = 48_000.0;
+
+/// Run for `seconds`; the peak of the last block
+fn run(router: &mut Router, seconds: f32) -> f32 {
+    let mut output = vec![0.0; FRAMES * 2];
+    let mut peak = 0.0f32;
+    for _ in 0..(seconds * SAMPLE_RATE) as usize / FRAMES {
+        router.process(&mut output, None);
+        peak = output.iter().fold(0.0, |peak, s| peak.max(s.abs()));
+    }
+    peak
+}
+
+fn db(gain: f32) -> f32 {
+    20.0 * gain.log10()
+}
+
+#[test]
+fn piled_up_sources_are_trimmed_to_the_headroom_target() {
+    let mut router = router(2, FRAMES);
+    let bus = EventBus::new(16);
+    router.set_event_publisher(Some(bus.publisher()));
+    assert!(router.set_auto_trim(0, Some(AutoTrimConfig::default())));
+    assert!(!router.set_auto_trim(2, Some(AutoTrimConfig::default())));
+    // eight sources at 0.125 per side sum to full scale, 6 dB over
+    let ids: Vec<_> = (0..8).map(|_| router.add_source(Box::new(Square(0.25)), 1.0, CENTRE, 0)).collect();
+
+    // slow: barely moved after a tenth of a second
+    let early = run(&mut router, 0.1);
+    assert!(db(early) > -0.5, "{}", db(early));
+    let settled = run(&mut router, 12.0);
+    assert!((db(settled) + 6.0).abs() < 0.2, "{}", db(settled));
+    assert!((router.auto_trim_db(0).unwrap() + 6.0).abs() < 0.2);
+
+    // one warning for the whole stretch over the target
+    let events = bus.drain();
+    assert_eq!(events.len(), 1, "{events:?}");
+    let EngineEvent::HeadroomExceeded { bus: 0, sources: 8, level_db, .. } = events[0] else {
+        panic!("{:?}", events[0]);
+    };
+    assert!(level_db > -6.0 && level_db < -5.0, "{level_db}");
+
+    // back under the target, the level comes back
+    for id in &ids[3..] {
+        router.remove(*id);
+    }
+    let recovered = run(&mut router, 12.0);
+    assert_eq!(router.auto_trim_db(0), Some(0.0));
+    assert!((recovered - 0.375).abs() < 1e-6, "{recovered}");
+}
+
+#[test]
+fn short_peaks_leave_the_trim_alone() {
+    let mut router = router(2, FRAMES);
+    router.set_auto_trim(0, Some(AutoTrimConfig::default()));
+    let id = router.add_source(Box::new(Square(2.0)), 1.0, CENTRE, 0);
+    run(&mut router, 0.05);
+    router.remove(id);
+    run(&mut router, 1.0);
+    assert!(router.auto_trim_db(0).unwrap() > -0.5, "{:?}", router.auto_trim_db(0));
+}
+
+#[test]
+fn warn_mode_only_publishes() {
+    let mut router = router(2, FRAMES);
+    let bus = EventBus::new(16);
+    router.set_event_publisher(Some(bus.publisher()));
+    let config = AutoTrimConfig { mode: AutoTrimMode::Warn, ..AutoTrimConfig::default() };
+    router.set_auto_trim(1, Some(config));
+    assert_eq!(router.auto_trim(1), Some(config));
+    for _ in 0..8 {
+        router.add_source(Box::new(Square(0.25)), 1.0, CENTRE, 1);
+    }
+
+    assert!((run(&mut router, 6.0) - 1.0).abs() < 1e-6);
+    assert_eq!(router.auto_trim_db(1), Some(0.0));
+    assert!(matches!(bus.drain()[..], [EngineEvent::HeadroomExceeded { bus: 1, sources: 8, .. }]));
+
+    router.set_auto_trim(1, None);
+    assert_eq!(router.auto_trim(1), None);
+}
+
+#[test]
+fn new_sources_raise_the_estimate_at_once() {
+    let mut router = router(2, FRAMES);
+    let bus = EventBus::new(16);
+    router.set_event_publisher(Some(bus.publisher()));
+    router.set_auto_trim(0, Some(AutoTrimConfig::default()));
+    // two sources sit at -10.5 dBFS, well under the target
+    for _ in 0..2 {
+        router.add_source(Box::new(Square(0.3)), 1.0, CENTRE, 0);
+    }
+    run(&mut router, 12.0);
+    assert!(bus.drain().is_empty());
+
+    // four times the sources: the estimate doubles straight away, -4.4 dBFS
+    for _ in 0..6 {
+        router.add_source(Box::new(Square(0.3)), 1.0, CENTRE, 0);
+    }
+    run(&mut router, 0.01);
+    let events = bus.drain();
+    let [EngineEvent::HeadroomExceeded { sources: 8, level_db, .. }] = events[..] else {
+        panic!("{events:?}");
+    };
+    assert!((level_db - db(0.6)).abs() < 0.1, "{level_db}");
+}
@@ -0,0 +1,108 @@
+//! Aux sends and bus returns: sources feeding a shared bus at their own
+//! levels, pre- and post-fader, and the bus coming back into the master mix.
+
+mod common;
+
+use pulsar_backend::engine::{EditCommand, Engine, EngineConfig};
+use pulsar_backend::project::{NodeDescriptor, ProjectError, SendDescriptor, SourceDescriptor};
+use pulsar_backend::rt_processing::routing::MAX_SENDS;
+
+use common::{CENTRE, Dc, Gain, block, router};
+
+const FRAMES: usize = 64;
+
+#[test]
+fn sends_feed_a_shared_bus_pre_and_post_fader() {
+    let mut router = router(3, FRAMES);
+    let (left, _) = CENTRE.gains();
+    // the return bus is wet only, so what it adds is easy to tell apart
+    assert!(router.add_bus_effect(2, Box::new(Gain(10.0))));
+    let a = router.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 0);
+    let b = router.add_source(Box::new(Dc(1.0)), 0.5, CENTRE, 0);
+    assert!((block(&mut router) - 1.5 * left).abs() < 1e-5);
+
+    assert!(router.set_send(a, 2, 0.25, false));
+    assert!(router.set_send(b, 2, 0.5, false));
+    // dry 1.5, plus (0.25 + 0.5 * 0.5) through the x10 return
+    assert!((block(&mut router) - 6.5 * left).abs() < 1e-5);
+    assert_eq!(router.send_level(b, 2), Some(0.5));
+
+    // a pre-fader send ignores the source's gain
+    assert!(router.set_send(b, 2, 0.5, true));
+    assert!((block(&mut router) - 9.0 * left).abs() < 1e-5);
+    router.set_mute(b, true);
+    assert!((block(&mut router) - 3.5 * left).abs() < 1e-5);
+    router.set_mute(b, false);
+
+    assert!(router.remove_send(a, 2));
+    assert!(!router.remove_send(a, 2));
+    assert_eq!(router.send_level(a, 2), None);
+    assert!((block(&mut router) - 6.5 * left).abs() < 1e-5);
+
+    // only aux buses that exist take sends, up to MAX_SENDS per source
+    assert!(!router.set_send(a, 0, 1.0, false));
+    assert!(!router.set_send(a, 3, 1.0, false));
+    let mut router = common::router(MAX_SENDS + 2, FRAMES);
+    let id = router.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 0);
+    assert!((1..=MAX_SENDS).all(|bus| router.set_send(id, bus, 0.1, false)));
+    assert!(!router.set_send(id, MAX_SENDS + 1, 0.1, false));
+    assert!(router.set_send(id, 1, 0.2, false));
+    block(&mut router);
+}
+
+#[test]
+fn return_gains_scale_buses_into_master() {
+    let mut router = router(3, FRAMES);
+    let (left, _) = CENTRE.gains();
+    let commands = router.commands();
+    let handle = commands.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 1).unwrap();
+    assert!(handle.set_send(2, 1.0, false));
+    assert!((block(&mut router) - 2.0 * left).abs() < 1e-5);
+
+    assert!(router.set_bus_return(2, 0.5));
+    assert_eq!(router.bus_return(2), Some(0.5));
+    // the change glides across one block, then holds
+    let mut output = vec![0.0; FRAMES * 2];
+    router.process(&mut output, None);
+    assert!(output[0] > 1.5 * left && output[0] < 2.0 * left);
+    assert!((output[(FRAMES - 1) * 2] - 1.5 * left).abs() < 1e-5);
+    assert!(router.set_bus_return(1, 0.0));
+    block(&mut router);
+    assert!((block(&mut router) - 0.5 * left).abs() < 1e-5);
+    assert!(!router.set_bus_return(0, 0.5));
+    assert!(!router.set_bus_return(3, 0.5));
+
+    // sends to a disabled bus go quiet with it
+    assert!(router.set_bus_return(1, 1.0));
+    assert!(router.set_bus_enabled(2, false));
+    for _ in 0..10 {
+        block(&mut router);
+    }
+    assert!((block(&mut router) - left).abs() < 1e-5);
+    assert!(!handle.set_send(0, 1.0, false));
+    assert!(handle.remove_send(2));
+}
+
+#[test]
+fn project_sends_and_returns_survive_undo() {
+    let mut engine = Engine::new();
+    engine.configure(EngineConfig::default()).unwrap();
+    engine.start().unwrap();
+    let source = SourceDescriptor::new(NodeDescriptor::new("oscillator")).with_send(1, 0.3);
+    engine.add_source_node(source).unwrap();
+    assert_eq!(engine.snapshot().sources[0].sends[0].level, 0.3);
+
+    let sends = vec![SendDescriptor { bus: 2, level: 0.6, pre_fader: true }];
+    engine.edit(EditCommand::SetSourceSends { source: 0, sends }).unwrap();
+    engine.edit(EditCommand::SetBusReturn { bus: 2, gain: 0.5 }).unwrap();
+    assert_eq!(engine.snapshot().sources[0].sends[0].bus, 2);
+    assert_eq!(engine.with_processor(|p| p.router().bus_return(2)).unwrap(), Some(0.5));
+
+    let master = vec![SendDescriptor { bus: 0, level: 1.0, pre_fader: false }];
+    let master = EditCommand::SetSourceSends { source: 0, sends: master };
+    assert!(matches!(engine.edit(master), Err(ProjectError::InvalidBus(0))));
+    assert!(engine.undo().unwrap());
+    assert_eq!(engine.with_processor(|p| p.router().bus_return(2)).unwrap(), Some(1.0));
+    assert!(engine.undo().unwrap());
+    assert_eq!(engine.snapshot().sources[0].sends[0].bus, 1);
+}
@@ -0,0 +1,90 @@
+//! Black box recorder: the event ring, the audio history and what a dump
+//! writes to disk.
+
+use std::fs;
+use std::path::PathBuf;
+
+use pulsar_backend::engine::{Engine, EngineConfig};
+use pulsar_backend::io::wav;
+use pulsar_backend::rt_processing::black_box::{self, BlackBox, BlackBoxEventKind};
+
+fn temp_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("pulsar-black-box-{}-{}", name, std::process::id()))
+}
+
+#[test]
+fn the_event_ring_keeps_the_latest_and_failures_arm_a_dump() {
+    const NAMES: [&str; 6] = ["a", "b", "c", "d", "e", "f"];
+    let black_box = BlackBox::new(4);
+    for name in NAMES {
+        black_box.record(BlackBoxEventKind::Marker(name));
+    }
+    assert!(!black_box.is_armed());
+    let events = black_box.take_events();
+    let kinds: Vec<_> = events.iter().map(|event| event.kind).collect();
+    assert_eq!(kinds, NAMES[2..].iter().map(|&name| BlackBoxEventKind::Marker(name)).collect::<Vec<_>>());
+    assert!(events.windows(2).all(|pair| pair[0].time <= pair[1].time));
+    assert!(black_box.take_events().is_empty());
+
+    black_box.record(BlackBoxEventKind::ProcessorSwapped);
+    assert!(!black_box.is_armed());
+    black_box.record(BlackBoxEventKind::Xrun { what: "output", overrun: false, frames: 0 });
+    assert!(black_box.is_armed());
+    // nowhere to write to: the dump stays armed
+    assert!(black_box.dump_if_armed().is_none());
+    assert!(black_box.is_armed());
+}
+
+#[test]
+fn dumps_write_the_events_and_the_last_seconds_of_audio() {
+    let dir = temp_dir("dump");
+    let black_box = BlackBox::new(16);
+    // 10 frames of stereo history
+    black_box.set_audio(0.01, 1000.0, 2);
+    assert!((black_box.audio_seconds() - 0.01).abs() < 1e-6);
+    let ramp: Vec<f32> = (0..15).flat_map(|i| [i as f32, -(i as f32)]).collect();
+    black_box.capture(&ramp[..12], 2);
+    black_box.record(BlackBoxEventKind::Marker("glitch"));
+    black_box.capture(&ramp[12..], 2);
+    assert_eq!(black_box.frames(), 15);
+
+    let dump = black_box.dump(&dir).unwrap();
+    assert_eq!((dump.events, dump.audio_frames), (1, 10));
+    let log = fs::read_to_string(&dump.events_path).unwrap();
+    assert!(log.starts_with("# master.wav starts at frame 5\n"));
+    assert!(log.contains("frame          6 marker: glitch"), "{log}");
+    let audio = wav::read_file(dump.audio_path.as_ref().unwrap()).unwrap();
+    assert_eq!(audio.sample_rate, 1000);
+    assert_eq!(audio.channels[0], (5..15).map(|i| i as f32).collect::<Vec<_>>());
+    assert_eq!(audio.channels[1], (5..15).map(|i| -(i as f32)).collect::<Vec<_>>());
+
+    // the box starts over after a dump
+    let empty = black_box.dump(&dir).unwrap();
+    assert_ne!(empty.dir, dump.dir);
+    assert_eq!((empty.events, empty.audio_frames, empty.audio_path), (0, 0, None));
+
+    // automatic dumps wait for a failure, a directory and the interval
+    black_box.set_dump_dir(Some(dir.clone()));
+    assert!(black_box.dump_if_armed().is_none());
+    black_box.record(BlackBoxEventKind::Error("test"));
+    assert!(black_box.dump_if_armed().is_none());
+    black_box.set_audio(0.0, 1000.0, 2);
+    assert_eq!(black_box.audio_seconds(), 0.0);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn engines_feed_the_global_black_box() {
+    let mut engine = Engine::new();
+    let config = EngineConfig { black_box_seconds: 0.5, ..EngineConfig::default() };
+    engine.configure(config).unwrap();
+    engine.start().unwrap();
+    let global = black_box::global();
+    assert_eq!(global.audio_seconds(), 0.5);
+
+    let before = global.frames();
+    let handle = engine.audio_handle().unwrap();
+    let mut output = vec![0.0; 256 * config.channels];
+    assert!(handle.process(&mut output));
+    assert_eq!(global.frames() - before, 256);
+}
@@ -0,0 +1,71 @@
+//! Block-size invariance: a graph full of modulation renders the same at
+//! every device block size, because its block-rate state moves on the
+//! router's control grid, not on the buffers it is handed.
+
+use pulsar_backend::offline::invariance::check_block_sizes;
+use pulsar_backend::rt_processing::effects::LfoRate;
+use pulsar_backend::rt_processing::effects::auto_pan::AutoPan;
+use pulsar_backend::rt_processing::effects::chain::EffectChain;
+use pulsar_backend::rt_processing::effects::tremolo::Tremolo;
+use pulsar_backend::rt_processing::filters::{Biquad, FilterType, RampShape};
+use pulsar_backend::rt_processing::routing::{AudioSource, Pan, PanLaw, Router};
+use pulsar_backend::rt_processing::voice_renderer::routing_source;
+use pulsar_backend::rt_processing::waveform::oscillators::Oscillator;
+use pulsar_backend::rt_processing::waveform::tables::WaveformType;
+
+const MAX_FRAMES: usize = 1024;
+const FRAMES: usize = 48_000;
+const BLOCK_SIZES: [usize; 4] = [64, 100, 512, 1023];
+
+/// Steps its level on every call: block-rate state off the control grid
+struct PerCall(f32);
+
+impl AudioSource for PerCall {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        self.0 = (self.0 + 0.01) % 1.0;
+        for channel in output.iter_mut() {
+            channel[..frames].fill(self.0);
+        }
+    }
+}
+
+/// A tremolo'd saw through a swept filter, and a sine auto-panned on a bus
+fn modulated() -> Router {
+    let mut router = Router::new(2, 48_000.0, 2, MAX_FRAMES);
+    router.set_param_ramp(20.0, RampShape::Linear);
+    let centre = Pan { value: 0.0, law: PanLaw::EqualPower };
+
+    let saw = router.add_source(routing_source(Oscillator::sawtooth(110.0)), 0.5, centre, 0);
+    let sweep = Biquad::new(FilterType::LowPass, 800.0, 2).with_lfo(WaveformType::Sine, LfoRate::Hz(0.7), 0.8);
+    let inserts = EffectChain::new()
+        .with(Box::new(Tremolo::new(WaveformType::Triangle, LfoRate::Hz(6.0)).with_depth(0.7)))
+        .with(Box::new(sweep));
+    router.set_source_effects(saw, inserts);
+
+    router.add_source(routing_source(Oscillator::sine(330.0)), 0.4, centre, 1);
+    router.add_bus_effect(1, Box::new(AutoPan::new(WaveformType::Sine, LfoRate::Hz(2.0))));
+    router
+}
+
+#[test]
+fn modulated_graphs_render_the_same_at_any_block_size() {
+    let report = check_block_sizes(modulated, FRAMES, &BLOCK_SIZES, 0.0);
+    assert_eq!(report.reference_block_size, 64);
+    assert!(report.is_invariant(), "{report:?}");
+    assert_eq!(report.max_difference, 0.0);
+}
+
+#[test]
+fn nodes_stepping_per_call_are_caught() {
+    let stepping = || {
+        let router = modulated();
+        router.add_source(Box::new(PerCall(0.0)), 0.5, Pan { value: 0.0, law: PanLaw::Linear }, 0);
+        router
+    };
+    let report = check_block_sizes(stepping, FRAMES, &BLOCK_SIZES, 1e-4);
+    assert!(!report.is_invariant());
+    assert!(report.max_difference > 1e-4);
+    // buffers that end off the 64-frame grid add a call inside a grid cell
+    assert!(report.worst_block_size.is_some_and(|size| size % 64 != 0));
+    assert!(report.first_divergent_frame.is_some_and(|frame| frame % 64 != 0));
+}
@@ -0,0 +1,67 @@
+//! Fixtures shared by the integration tests: a constant source, a fixed gain,
+//! a router whose parameter changes land at once, and reading a block back.
+//! Each test binary uses some of them.
+#![allow(dead_code)]
+
+use pulsar_backend::rt_processing::effects::Effect;
+use pulsar_backend::rt_processing::filters::RampShape;
+use pulsar_backend::rt_processing::routing::{AudioSource, Pan, PanLaw, Router};
+
+pub const SAMPLE_RATE: f32 = 48_000.0;
+/// Linear pan at the centre: 0.5 on each side
+pub const CENTRE: Pan = Pan { value: 0.0, law: PanLaw::Linear };
+
+/// Constant level on every channel
+pub struct Dc(pub f32);
+
+impl AudioSource for Dc {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        for channel in output.iter_mut() {
+            channel[..frames].fill(self.0);
+        }
+    }
+}
+
+/// Alternating +level / -level, so the peak and RMS are both `level`
+pub struct Square(pub f32);
+
+impl AudioSource for Square {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        for channel in output.iter_mut() {
+            for (i, sample) in channel[..frames].iter_mut().enumerate() {
+                *sample = if i % 2 == 0 { self.0 } else { -self.0 };
+            }
+        }
+    }
+}
+
+/// Fixed gain, standing in for any effect
+pub struct Gain(pub f32);
+
+impl Effect for Gain {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        for channel in buffer.iter_mut() {
+            channel[..frames].iter_mut().for_each(|s| *s *= self.0);
+        }
+    }
+}
+
+/// Stereo router at `SAMPLE_RATE` with `buses` buses and blocks of up to
+/// `frames`, without parameter ramps
+pub fn router(buses: usize, frames: usize) -> Router {
+    let mut router = Router::new(2, SAMPLE_RATE, buses, frames);
+    router.set_param_ramp(0.0, RampShape::Linear);
+    router
+}
+
+/// Left channel of the last frame of one full block
+pub fn block(router: &mut Router) -> f32 {
+    let frames = router.max_frames();
+    let mut output = vec![0.0; frames * 2];
+    router.process(&mut output, None);
+    output[(frames - 1) * 2]
+}
+
+pub fn close(a: f32, b: f32, tolerance: f32) -> bool {
+    (a - b).abs() < tolerance
+}
@@ -0,0 +1,181 @@
+//! Denormals: with the noise floor on, effects fed a burst and then silence
+//! never decay into subnormal floats, and (in the ignored timing test, run
+//! nightly) their silent tails cost no more than live audio.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use pulsar_backend::engine::{Engine, EngineConfig};
+use pulsar_backend::rt_processing::denormal::{DEFAULT_NOISE_FLOOR_DB, NoiseFloor};
+use pulsar_backend::rt_processing::effects::Effect;
+use pulsar_backend::rt_processing::effects::LfoRate;
+use pulsar_backend::rt_processing::effects::amp_sim::{AmpParams, AmpSim, Cabinet};
+use pulsar_backend::rt_processing::effects::auto_pan::AutoPan;
+use pulsar_backend::rt_processing::effects::chain::EffectChain;
+use pulsar_backend::rt_processing::effects::character::{Character, CharacterParams};
+use pulsar_backend::rt_processing::effects::compressor::{Compressor, CompressorParams};
+use pulsar_backend::rt_processing::effects::convolution::{Convolver, ImpulseResponse};
+use pulsar_backend::rt_processing::effects::de_esser::{DeEsser, DeEsserParams};
+use pulsar_backend::rt_processing::effects::gate::{GatePattern, PatternGate};
+use pulsar_backend::rt_processing::effects::harmonizer::Harmonizer;
+use pulsar_backend::rt_processing::effects::imager::{StereoImager, StereoWidth};
+use pulsar_backend::rt_processing::effects::input_strip::InputStrip;
+use pulsar_backend::rt_processing::effects::limiter::{Limiter, LimiterParams};
+use pulsar_backend::rt_processing::effects::multiband::MultibandCompressor;
+use pulsar_backend::rt_processing::effects::reverb::{Reverb, ReverbParams};
+use pulsar_backend::rt_processing::effects::spectral::SpectralFreeze;
+use pulsar_backend::rt_processing::effects::spectral::StftConfig;
+use pulsar_backend::rt_processing::effects::tremolo::Tremolo;
+use pulsar_backend::rt_processing::effects::vocoder::{Vocoder, VocoderParams};
+use pulsar_backend::rt_processing::filters::{Biquad, FilterType};
+use pulsar_backend::rt_processing::routing::{AudioSource, Pan, PanLaw, Router};
+use pulsar_backend::rt_processing::waveform::tables::WaveformType;
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const FRAMES: usize = 256;
+/// Long enough for every tail to reach denormals without the floor
+const TAIL_SECONDS: f32 = 5.0;
+
+fn character() -> Character {
+    let mut character = Character::new(2, FRAMES, CharacterParams::tape());
+    character.prepare(SAMPLE_RATE);
+    character
+}
+
+/// Every effect the crate provides, in a typical setting
+fn effects() -> Vec<(&'static str, Box<dyn Effect>)> {
+    let ir: Vec<f32> = (0..4_800).map(|i| (-(i as f32) / 600.0).exp() * if i % 3 == 0 { 0.5 } else { -0.3 }).collect();
+    let ir = ImpulseResponse::new(vec![ir.clone(), ir], SAMPLE_RATE);
+    vec![
+        ("amp_sim", Box::new(AmpSim::new(2, AmpParams::default()))),
+        ("cabinet", Box::new(Cabinet::new(2))),
+        ("auto_pan", Box::new(AutoPan::new(WaveformType::Sine, LfoRate::Hz(1.0)))),
+        ("biquad", Box::new(Biquad::new(FilterType::LowPass, 800.0, 2))),
+        ("character", Box::new(character())),
+        ("compressor", Box::new(Compressor::new(CompressorParams::default()))),
+        ("convolver", Box::new(Convolver::new(&ir, 2, FRAMES))),
+        ("de_esser", Box::new(DeEsser::new(DeEsserParams::default()))),
+        ("gate", Box::new(PatternGate::new(GatePattern::default()))),
+        ("harmonizer", Box::new(Harmonizer::new(2))),
+        ("stereo_width", Box::new(StereoWidth::new(1.5))),
+        ("stereo_imager", Box::new(StereoImager::new(FRAMES, &[300.0, 3_000.0]))),
+        ("input_strip", Box::new(InputStrip::new(2))),
+        ("limiter", Box::new(Limiter::new(2, SAMPLE_RATE, LimiterParams::default()))),
+        ("multiband", Box::new(MultibandCompressor::new(2, FRAMES, &[200.0, 2_000.0], &[]))),
+        ("reverb", Box::new(Reverb::new(ReverbParams::default()))),
+        ("spectral_freeze", Box::new(SpectralFreeze::effect(2, StftConfig::default()))),
+        ("tremolo", Box::new(Tremolo::new(WaveformType::Sine, LfoRate::Hz(4.0)))),
+        ("vocoder", Box::new(Vocoder::new(2, FRAMES, VocoderParams::default()))),
+    ]
+}
+
+/// Noise for a quarter of a second, then silence
+struct Burst {
+    remaining: usize,
+    rng: u32,
+}
+
+impl Burst {
+    fn new() -> Self {
+        Self { remaining: SAMPLE_RATE as usize / 4, rng: 1 }
+    }
+}
+
+impl AudioSource for Burst {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        for i in 0..frames {
+            let sample = if self.remaining > 0 {
+                self.remaining -= 1;
+                self.rng = self.rng.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (self.rng >> 8) as f32 / (1 << 23) as f32 - 1.0
+            } else {
+                0.0
+            };
+            for channel in output.iter_mut() {
+                channel[i] = sample * 0.5;
+            }
+        }
+    }
+}
+
+/// Router playing a burst into `effect` on the master chain
+fn router(effect: Box<dyn Effect>, floor_db: Option<f32>) -> Router {
+    let mut router = common::router(1, FRAMES);
+    router.set_noise_floor(floor_db);
+    router.add_source(Box::new(Burst::new()), 2.0, Pan { value: 0.0, law: PanLaw::Linear }, 0);
+    assert!(router.set_bus_effects(0, EffectChain::new().with(effect)));
+    router
+}
+
+/// Subnormal output samples over `seconds`
+fn subnormals(router: &mut Router, seconds: f32) -> usize {
+    let mut output = vec![0.0; FRAMES * 2];
+    let blocks = (seconds * SAMPLE_RATE / FRAMES as f32) as usize;
+    (0..blocks)
+        .map(|_| {
+            router.process(&mut output, None);
+            output.iter().filter(|s| s.is_subnormal()).count()
+        })
+        .sum()
+}
+
+#[test]
+fn decaying_effects_stay_out_of_denormals() {
+    // the check catches them: a plain filter's tail goes subnormal
+    let mut plain = router(Box::new(Biquad::new(FilterType::LowPass, 800.0, 2)), None);
+    assert_eq!(plain.noise_floor(), None);
+    assert!(subnormals(&mut plain, TAIL_SECONDS) > 0);
+
+    for (name, effect) in effects() {
+        let mut router = router(effect, Some(DEFAULT_NOISE_FLOOR_DB));
+        assert_eq!(subnormals(&mut router, TAIL_SECONDS), 0, "{name}");
+    }
+}
+
+#[test]
+fn the_floor_is_inaudible() {
+    let mut floor = NoiseFloor::default();
+    assert_eq!(floor.level_db(), DEFAULT_NOISE_FLOOR_DB);
+    let (mut silence, mut audio) = (vec![0.0; FRAMES], vec![0.25; FRAMES]);
+    floor.apply(&mut [&mut silence, &mut audio], FRAMES);
+    let limit = 10f32.powf(DEFAULT_NOISE_FLOOR_DB / 20.0);
+    assert!(silence.iter().all(|&s| s != 0.0 && s.abs() <= limit));
+    // far below the resolution of real signals
+    assert!(audio.iter().all(|&s| s == 0.25));
+}
+
+#[test]
+fn engines_take_the_floor_from_their_config() {
+    let mut engine = Engine::new();
+    engine.configure(EngineConfig::default()).unwrap();
+    assert_eq!(engine.with_processor(|p| p.router().noise_floor()).unwrap(), None);
+    engine.configure(EngineConfig { denormal_floor_db: Some(-280.0), ..EngineConfig::default() }).unwrap();
+    assert_eq!(engine.with_processor(|p| p.router().noise_floor()).unwrap(), Some(-280.0));
+}
+
+/// Time per block over `blocks` blocks
+fn time_blocks(router: &mut Router, blocks: usize) -> Duration {
+    let mut output = vec![0.0; FRAMES * 2];
+    let start = Instant::now();
+    for _ in 0..blocks {
+        router.process(&mut output, None);
+    }
+    start.elapsed() / blocks as u32
+}
+
+/// Timing depends on the machine and the build; run nightly, in release:
+/// `cargo test --release --test denormals -- --ignored`
+#[test]
+#[ignore = "timing; run nightly in release"]
+fn silent_tails_cost_no_more_than_audio() {
+    let burst_blocks = SAMPLE_RATE as usize / 4 / FRAMES;
+    for (name, effect) in effects() {
+        let mut router = router(effect, Some(DEFAULT_NOISE_FLOOR_DB));
+        let live = time_blocks(&mut router, burst_blocks);
+        // let the tail decay, then time it
+        time_blocks(&mut router, (20.0 * SAMPLE_RATE) as usize / FRAMES);
+        let tail = time_blocks(&mut router, burst_blocks);
+        assert!(tail <= live * 2 + Duration::from_micros(5), "{name}: {tail:?} in the tail, {live:?} live");
+    }
+}
@@ -0,0 +1,116 @@
+//! Parameter dice: rolls stay within each parameter's range, leave switches,
+//! text and locked parameters alone, repeat for a seed, and with a scale put
+//! source pitches on its notes.
+
+use pulsar_backend::project::{
+    Dice, NodeDescriptor, NodeRegistry, ParamGroup, ParamSpec, ParamUnit, ParamValue, ProjectFile, SourceDescriptor,
+};
+use pulsar_backend::rt_processing::filters::{Trim, TrimSlope};
+use pulsar_backend::rt_processing::notes::{Scale, ScaleMode};
+
+const ROLLS: u32 = 32;
+
+fn project() -> ProjectFile {
+    let mut project = ProjectFile { buses: vec![Default::default()], ..Default::default() };
+    project.buses[0].effects.push(NodeDescriptor::new("biquad"));
+    for frequency in [110.0, 220.0, 440.0] {
+        let node = NodeDescriptor::new("oscillator")
+            .with_param("frequency", frequency)
+            .with_param("waveform", "sawtooth");
+        let trim = Some(Trim::new(5_000.0, TrimSlope::Db12));
+        project.sources.push(SourceDescriptor::new(node).with_trims(None, trim));
+    }
+    project
+}
+
+fn float(node: &NodeDescriptor, name: &str) -> f32 {
+    node.float(name, f32::NAN).unwrap()
+}
+
+/// Source frequencies after a roll
+fn frequencies(project: &ProjectFile) -> Vec<f32> {
+    project.sources.iter().map(|source| float(&source.node, "frequency")).collect()
+}
+
+#[test]
+fn rolls_stay_in_range_and_repeat_for_a_seed() {
+    let registry = NodeRegistry::with_builtins();
+    for seed in 0..ROLLS {
+        let (mut a, mut b) = (project(), project());
+        Dice::new(seed).roll(&mut a, &registry);
+        Dice::new(seed).roll(&mut b, &registry);
+        assert_eq!(frequencies(&a), frequencies(&b));
+
+        assert!(frequencies(&a).iter().all(|hz| (20.0..=20_000.0).contains(hz)));
+        let cutoff = a.sources[0].low_pass.unwrap().cutoff;
+        assert!((500.0..=20_000.0).contains(&cutoff));
+        let q = float(&a.buses[0].effects[0], "q");
+        assert!((0.1..=20.0).contains(&q));
+        // choices are rolled among their names
+        assert!(matches!(a.sources[0].node.params.get("waveform"), Some(ParamValue::Text(_))));
+    }
+    let (mut a, mut b) = (project(), project());
+    Dice::new(1).roll(&mut a, &registry);
+    Dice::new(2).roll(&mut b, &registry);
+    assert_ne!(frequencies(&a), frequencies(&b));
+}
+
+#[test]
+fn groups_locks_and_amount_limit_the_roll() {
+    let registry = NodeRegistry::with_builtins();
+    let mut rolled = project();
+    Dice::new(3).with_groups(&[ParamGroup::Filter]).roll(&mut rolled, &registry);
+    assert_eq!(frequencies(&rolled), [110.0, 220.0, 440.0]);
+    assert_eq!(rolled.buses[0].effects[0].params.get("q"), None);
+    assert_ne!(rolled.sources[0].low_pass.unwrap().cutoff, 5_000.0);
+
+    let mut rolled = project();
+    Dice::new(3).with_locked("frequency").with_locked("low_pass").roll(&mut rolled, &registry);
+    assert_eq!(frequencies(&rolled), [110.0, 220.0, 440.0]);
+    assert_eq!(rolled.sources[0].low_pass.unwrap().cutoff, 5_000.0);
+
+    // a tenth of the knob travel on a 20 Hz - 20 kHz log taper, about an octave
+    let mut rolled = project();
+    Dice::new(3).with_amount(0.1).with_groups(&[ParamGroup::Oscillator]).roll(&mut rolled, &registry);
+    for (hz, before) in frequencies(&rolled).into_iter().zip([110.0f32, 220.0, 440.0]) {
+        assert!((hz / before).log2().abs() <= 0.1 * 1000f32.log2() + 1e-3, "{before} -> {hz}");
+    }
+}
+
+#[test]
+fn a_scale_puts_source_pitches_on_its_notes() {
+    let registry = NodeRegistry::with_builtins();
+    let c_major = Scale::new(0, ScaleMode::Major);
+    for seed in 0..ROLLS {
+        let mut rolled = project();
+        Dice::new(seed).with_scale(c_major).roll(&mut rolled, &registry);
+        for hz in frequencies(&rolled) {
+            let note = 69.0 + 12.0 * (hz / 440.0).log2();
+            assert!((note - note.round()).abs() < 1e-3, "{hz} Hz is between notes");
+            assert!(c_major.contains(note.round() as u8), "{hz} Hz is off the scale");
+        }
+        // effects aren't pitched
+        let cutoff = float(&rolled.buses[0].effects[0], "cutoff");
+        assert!((20.0..=20_000.0).contains(&cutoff));
+    }
+}
+
+#[test]
+fn a_scale_puts_transpositions_on_its_intervals() {
+    let mut registry = NodeRegistry::with_builtins();
+    let transpose = ParamSpec::float("transpose", -12.0, 12.0, 0.0).with_unit(ParamUnit::Semitones);
+    registry.register_params("pitched", vec![transpose]);
+    // D minor pentatonic: D F G A C
+    let scale = Scale::new(2, ScaleMode::MinorPentatonic);
+    let mut seen = Vec::new();
+    for seed in 0..ROLLS {
+        let mut node = NodeDescriptor::new("pitched");
+        Dice::new(seed).with_scale(scale).roll_node(&mut node, registry.params("pitched"));
+        let semitones = float(&node, "transpose");
+        assert_eq!(semitones, semitones.round());
+        assert!([0, 3, 5, 7, 10].contains(&(semitones as i32).rem_euclid(12)), "{semitones}");
+        seen.push(semitones);
+    }
+    seen.dedup();
+    assert!(seen.len() > 1);
+}
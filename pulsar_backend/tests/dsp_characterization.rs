@@ -0,0 +1,447 @@
+//! Golden characterization of the DSP building blocks: distortion and noise of
+//! the oscillators, aliasing of the band-limited paths, filter magnitude
+//! responses, envelope timing and reverb decay. Thresholds sit a few dB (or samples) outside
+//! what the current code measures, so a change that degrades quality fails
+//! here instead of being heard later; each failure reports the value measured.
+
+use std::f32::consts::PI;
+
+use pulsar_backend::rt_processing::fft::{Complex, Fft};
+use pulsar_backend::rt_processing::effects::reverb::{Reverb, ReverbParams};
+use pulsar_backend::rt_processing::effects::{Effect, LfoRate};
+use pulsar_backend::rt_processing::filters::{
+    BUTTERWORTH_Q, Biquad, BiquadCoefficients, BiquadState, CrossoverCoefficients, CrossoverState,
+    EnvelopeFollower, FilterType, RampShape, SmoothedParam,
+};
+use pulsar_backend::rt_processing::resampler::Resampler;
+use pulsar_backend::rt_processing::waveform::envelopes::ADSREnvelope;
+use pulsar_backend::io::wav::{self, SampleFormat, WavSpec};
+use pulsar_backend::rt_processing::voice_renderer::AudioSource;
+use pulsar_backend::rt_processing::waveform::oscillators::{Oscillator, WavetableOscillator};
+use pulsar_backend::rt_processing::waveform::tables::{Wavetable, WaveformType};
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const FFT_SIZE: usize = 16_384;
+/// Bins either side of a tone that belong to it under the window
+const TONE_BINS: usize = 6;
+
+fn db(power_ratio: f64) -> f32 {
+    (10.0 * power_ratio.max(1e-30).log10()) as f32
+}
+
+/// Power spectrum (bins 0..=N/2) of `signal[..FFT_SIZE]` under a
+/// 4-term Blackman-Harris window
+fn power_spectrum(signal: &[f32]) -> Vec<f64> {
+    let fft = Fft::new(FFT_SIZE);
+    let mut data: Vec<Complex> = signal[..FFT_SIZE]
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| {
+            let t = 2.0 * PI * i as f32 / FFT_SIZE as f32;
+            let w = 0.35875 - 0.48829 * t.cos() + 0.14128 * (2.0 * t).cos() - 0.01168 * (3.0 * t).cos();
+            Complex::new(x * w, 0.0)
+        })
+        .collect();
+    fft.forward(&mut data);
+    data[..=FFT_SIZE / 2].iter().map(|c| c.norm_sqr() as f64).collect()
+}
+
+fn bin_of(frequency: f32, sample_rate: f32) -> usize {
+    (frequency * FFT_SIZE as f32 / sample_rate).round() as usize
+}
+
+fn is_near(bin: usize, tone: usize) -> bool {
+    bin.abs_diff(tone) <= TONE_BINS
+}
+
+/// Power within `TONE_BINS` of `tone`
+fn tone_power(spectrum: &[f64], tone: usize) -> f64 {
+    spectrum.iter().enumerate().filter(|&(bin, _)| is_near(bin, tone)).map(|(_, p)| p).sum()
+}
+
+/// THD+N in dB: everything but DC and the fundamental, against the fundamental
+fn thd_n_db(signal: &[f32], frequency: f32, sample_rate: f32) -> f32 {
+    let spectrum = power_spectrum(signal);
+    let tone = bin_of(frequency, sample_rate);
+    let rest: f64 =
+        spectrum.iter().enumerate().filter(|&(bin, _)| !is_near(bin, 0) && !is_near(bin, tone)).map(|(_, p)| p).sum();
+    db(rest / tone_power(&spectrum, tone))
+}
+
+/// Power in bins that are not harmonics of `frequency`, against the harmonics, in dB
+fn alias_db(signal: &[f32], frequency: f32, sample_rate: f32) -> f32 {
+    let spectrum = power_spectrum(signal);
+    let harmonics: Vec<usize> = (1..)
+        .map(|k| k as f32 * frequency)
+        .take_while(|&f| f < sample_rate / 2.0)
+        .map(|f| bin_of(f, sample_rate))
+        .collect();
+    let (mut tonal, mut other) = (0.0, 0.0);
+    for (bin, &power) in spectrum.iter().enumerate().filter(|&(bin, _)| !is_near(bin, 0)) {
+        if harmonics.iter().any(|&h| is_near(bin, h)) {
+            tonal += power;
+        } else {
+            other += power;
+        }
+    }
+    db(other / tonal)
+}
+
+fn oscillator_output(mut oscillator: Oscillator, frames: usize) -> Vec<f32> {
+    (0..frames).map(|_| oscillator.next_sample(SAMPLE_RATE)).collect()
+}
+
+/// Gain in dB of `process` at `frequency`, from the steady-state output of a
+/// sine, correlated over whole cycles
+fn response_db(frequency: f32, mut process: impl FnMut(f32) -> f32) -> f32 {
+    let settle = SAMPLE_RATE as usize / 2;
+    let cycles = (frequency * 0.5).ceil().max(10.0);
+    let frames = (cycles * SAMPLE_RATE / frequency).round() as usize;
+    let (mut re, mut im) = (0.0f64, 0.0f64);
+    for n in 0..settle + frames {
+        let phase = 2.0 * std::f64::consts::PI * frequency as f64 * n as f64 / SAMPLE_RATE as f64;
+        let y = process(phase.sin() as f32) as f64;
+        if n >= settle {
+            re += y * phase.sin();
+            im += y * phase.cos();
+        }
+    }
+    let amplitude = 2.0 * (re * re + im * im).sqrt() / frames as f64;
+    20.0 * amplitude.max(1e-15).log10() as f32
+}
+
+#[test]
+fn sine_oscillator_thd_n() {
+    for frequency in [100.0, 1_000.0, 10_000.0] {
+        let signal = oscillator_output(Oscillator::sine(frequency).with_amplitude(0.5), FFT_SIZE);
+        let thd_n = thd_n_db(&signal, frequency, SAMPLE_RATE);
+        assert!(thd_n < -85.0, "sine {} Hz THD+N {:.1} dB", frequency, thd_n);
+    }
+}
+
+#[test]
+fn sine_oscillator_without_interpolation_thd_n() {
+    let oscillator = Oscillator::sine(1_000.0).with_amplitude(0.5).with_interpolation(false);
+    let thd_n = thd_n_db(&oscillator_output(oscillator, FFT_SIZE), 1_000.0, SAMPLE_RATE);
+    assert!(thd_n < -70.0, "THD+N {:.1} dB", thd_n);
+}
+
+/// The table sawtooth is not band-limited; this pins its aliasing so it can
+/// only get better
+#[test]
+fn sawtooth_aliasing_baseline() {
+    for (frequency, limit) in [(220.0, -20.0), (2_000.0, -18.0)] {
+        let oscillator = Oscillator::new(WaveformType::Sawtooth, frequency).with_amplitude(0.5);
+        let alias = alias_db(&oscillator_output(oscillator, FFT_SIZE), frequency, SAMPLE_RATE);
+        assert!(alias < limit, "sawtooth {} Hz aliasing {:.1} dB", frequency, alias);
+    }
+}
+
+/// The polyBLEP paths against the plain tables at the same settings: aliasing
+/// must sit well below both an absolute limit and the table's own
+#[test]
+fn antialiased_oscillator_suppresses_aliasing() {
+    // periods that aren't whole samples, so aliases fall between the harmonics
+    let cases = [
+        (WaveformType::Sawtooth, 1_760.0, -25.0),
+        (WaveformType::Sawtooth, 4_186.0, -20.0),
+        (WaveformType::Square, 1_760.0, -28.0),
+        (WaveformType::Square, 4_186.0, -25.0),
+        (WaveformType::Triangle, 4_186.0, -42.0),
+    ];
+    for (waveform, frequency, limit) in cases {
+        let oscillator = || Oscillator::new(waveform, frequency).with_amplitude(0.5);
+        let naive = alias_db(&oscillator_output(oscillator(), FFT_SIZE), frequency, SAMPLE_RATE);
+        let blep =
+            alias_db(&oscillator_output(oscillator().with_antialiasing(true), FFT_SIZE), frequency, SAMPLE_RATE);
+        assert!(blep < limit, "{:?} {} Hz aliasing {:.1} dB", waveform, frequency, blep);
+        assert!(blep < naive - 12.0, "{:?} {} Hz: {:.1} dB vs {:.1} dB plain", waveform, frequency, blep, naive);
+    }
+}
+
+/// Resample a mono tone from `from` to `to` and return its output
+fn wavetable_output(mut oscillator: WavetableOscillator, frames: usize) -> Vec<f32> {
+    let mut output = vec![0.0; frames];
+    oscillator.fill_buffer(&mut output, SAMPLE_RATE, 1, frames);
+    output
+}
+
+#[test]
+fn wavetable_sine_frame_thd_n() {
+    let table = Wavetable::from_waveforms(&[WaveformType::Sine], 2048);
+    let signal = wavetable_output(WavetableOscillator::new(table, 1_000.0).with_amplitude(0.5), FFT_SIZE);
+    let thd_n = thd_n_db(&signal, 1_000.0, SAMPLE_RATE);
+    assert!(thd_n < -85.0, "THD+N {:.1} dB", thd_n);
+}
+
+#[test]
+fn wavetable_morphs_between_frames() {
+    // a sine frame and a quiet saw of another length, loaded from a file
+    let frame = 600;
+    let mut samples: Vec<f32> = (0..frame).map(|i| (2.0 * PI * i as f32 / frame as f32).sin()).collect();
+    samples.extend((0..frame).map(|i| 0.5 * (2.0 * i as f32 / frame as f32 - 1.0)));
+    let path = std::env::temp_dir().join(format!("pulsar-wavetable-{}.wav", std::process::id()));
+    let spec = WavSpec { sample_rate: 48_000, format: SampleFormat::Float32, dither: false };
+    wav::write_file(&path, &[samples], spec).unwrap();
+    let table = Wavetable::from_wav(&path, frame).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!((table.frame_count(), table.frame_len()), (2, frame));
+
+    let frequency = SAMPLE_RATE / 240.0;
+    let render = |position: f32| {
+        let oscillator = WavetableOscillator::new(table.clone(), frequency).with_amplitude(1.0);
+        wavetable_output(oscillator.with_position(position), 240)
+    };
+    let (sine, saw, middle) = (render(0.0), render(1.0), render(0.5));
+    for i in 0..240 {
+        let phase = i as f32 / 240.0;
+        assert!((sine[i] - (2.0 * PI * phase).sin()).abs() < 1e-3, "sine at {}: {}", i, sine[i]);
+        assert!((saw[i] - 0.5 * (2.0 * phase - 1.0)).abs() < 1e-3, "saw at {}: {}", i, saw[i]);
+        assert!((middle[i] - 0.5 * (sine[i] + saw[i])).abs() < 1e-5, "middle at {}", i);
+    }
+
+    // a full-depth scan sweeps the whole table
+    let oscillator = WavetableOscillator::new(table, frequency).with_scan(WaveformType::Triangle, 4.0, 1.0);
+    let monitor = oscillator.modulation();
+    let mut oscillator = oscillator.with_control_interval(1);
+    let (mut lowest, mut highest) = (f32::MAX, f32::MIN);
+    let mut block = [0.0; 64];
+    for _ in 0..SAMPLE_RATE as usize / 4 / 64 {
+        oscillator.fill_buffer(&mut block, SAMPLE_RATE, 1, 64);
+        let position = monitor.reading("position").unwrap().value();
+        lowest = lowest.min(position);
+        highest = highest.max(position);
+    }
+    assert!(lowest < 0.05 && highest > 0.95, "scan covered {} to {}", lowest, highest);
+}
+
+fn resample_tone(frequency: f32, from: u32, to: u32, frames: usize) -> Vec<f32> {
+    let mut resampler = Resampler::new(1, from, to);
+    let mut output = vec![0.0; frames];
+    let mut n = 0usize;
+    resampler.pull(&mut output, |block| {
+        for sample in block {
+            *sample = 0.5 * (2.0 * PI * frequency * n as f32 / from as f32).sin();
+            n += 1;
+        }
+    });
+    output
+}
+
+#[test]
+fn resampler_passband_and_image_rejection() {
+    let (from, to) = (48_000, 44_100);
+    let settle = 1_024;
+
+    // a passband tone comes through at unity
+    let tone = bin_of(1_000.0, to as f32);
+    let reference: Vec<f32> = (0..FFT_SIZE).map(|n| 0.5 * (2.0 * PI * 1_000.0 * n as f32 / to as f32).sin()).collect();
+    let reference = tone_power(&power_spectrum(&reference), tone);
+    let output = resample_tone(1_000.0, from, to, settle + FFT_SIZE);
+    let gain = db(tone_power(&power_spectrum(&output[settle..]), tone) / reference);
+    let thd_n = thd_n_db(&output[settle..], 1_000.0, to as f32);
+    assert!(gain.abs() < 0.1, "passband gain {:.2} dB", gain);
+    assert!(thd_n < -70.0, "passband THD+N {:.1} dB", thd_n);
+
+    // a tone in the stopband (past the 16-tap kernel's transition band) must
+    // not fold back into the output
+    let output = resample_tone(40_000.0, 96_000, 48_000, settle + FFT_SIZE);
+    let folded = db(power_spectrum(&output[settle..]).iter().sum::<f64>() / reference);
+    assert!(folded < -55.0, "40 kHz tone leaks at {:.1} dB", folded);
+}
+
+#[test]
+fn biquad_magnitude_responses() {
+    let cutoff = 1_000.0;
+    let low_pass = BiquadCoefficients::low_pass(cutoff, BUTTERWORTH_Q, SAMPLE_RATE);
+    let high_pass = BiquadCoefficients::high_pass(cutoff, BUTTERWORTH_Q, SAMPLE_RATE);
+
+    for coefficients in [low_pass, high_pass] {
+        for frequency in [50.0, 250.0, 1_000.0, 4_000.0, 12_000.0] {
+            let mut state = BiquadState::default();
+            let measured = response_db(frequency, |x| state.process(&coefficients, x));
+            let expected = db(coefficients.magnitude_squared(frequency, SAMPLE_RATE) as f64);
+            assert!(
+                (measured - expected).abs() < 0.05,
+                "{} Hz: measured {:.3} dB, expected {:.3} dB",
+                frequency,
+                measured,
+                expected
+            );
+        }
+    }
+
+    // Butterworth: -3 dB at the cutoff, 12 dB/oct beyond it
+    let mut state = BiquadState::default();
+    let at_cutoff = response_db(cutoff, |x| state.process(&low_pass, x));
+    let mut state = BiquadState::default();
+    let three_octaves = response_db(cutoff * 8.0, |x| state.process(&low_pass, x));
+    let mut state = BiquadState::default();
+    let passband = response_db(cutoff / 10.0, |x| state.process(&low_pass, x));
+    assert!((at_cutoff + 3.01).abs() < 0.05, "cutoff gain {:.2} dB", at_cutoff);
+    assert!(passband.abs() < 0.01, "passband gain {:.3} dB", passband);
+    assert!(three_octaves < -35.0, "stopband gain {:.1} dB", three_octaves);
+}
+
+/// Gain in dB of a one-channel `Biquad` at `frequency`
+fn biquad_response_db(filter: &mut Biquad, frequency: f32) -> f32 {
+    filter.reset();
+    response_db(frequency, |x| {
+        let mut sample = [x];
+        filter.process(&mut [&mut sample[..]], 1, SAMPLE_RATE);
+        sample[0]
+    })
+}
+
+#[test]
+fn biquad_node_types_and_cutoff_modulation() {
+    let mut notch = Biquad::new(FilterType::Notch, 1_000.0, 1).with_q(2.0);
+    let (rejected, passed) = (biquad_response_db(&mut notch, 1_000.0), biquad_response_db(&mut notch, 100.0));
+    assert!(rejected < -40.0, "notch depth {:.1} dB", rejected);
+    assert!(passed.abs() < 0.1, "notch passband {:.2} dB", passed);
+
+    let mut band_pass = Biquad::new(FilterType::BandPass, 1_000.0, 1).with_q(4.0);
+    assert!(biquad_response_db(&mut band_pass, 1_000.0).abs() < 0.05);
+    assert!(biquad_response_db(&mut band_pass, 4_000.0) < -20.0);
+    let mut peaking = Biquad::new(FilterType::Peaking, 1_000.0, 1).with_gain_db(6.0);
+    assert!((biquad_response_db(&mut peaking, 1_000.0) - 6.0).abs() < 0.05);
+    let mut shelf = Biquad::new(FilterType::HighShelf, 1_000.0, 1).with_gain_db(-12.0);
+    assert!((biquad_response_db(&mut shelf, 16_000.0) + 12.0).abs() < 0.5);
+
+    // one octave of external modulation puts the -3 dB point at 2 kHz
+    let mut low_pass = Biquad::new(FilterType::LowPass, 1_000.0, 1);
+    let monitor = low_pass.modulation().unwrap();
+    low_pass.set_cutoff_modulation(1.0);
+    let at_cutoff = biquad_response_db(&mut low_pass, 2_000.0);
+    assert!((at_cutoff + 3.01).abs() < 0.05, "modulated cutoff gain {:.2} dB", at_cutoff);
+    let reading = monitor.reading("cutoff").unwrap();
+    assert_eq!((reading.base, reading.value()), (1_000.0, 2_000.0));
+
+    // an LFO sweeps the cutoff over its depth and no further
+    let mut swept = Biquad::new(FilterType::LowPass, 1_000.0, 1).with_lfo(WaveformType::Sine, LfoRate::Hz(5.0), 1.0);
+    let monitor = swept.modulation().unwrap();
+    let (mut lowest, mut highest) = (f32::MAX, 0.0f32);
+    let mut block = [0.0; 64];
+    for _ in 0..(SAMPLE_RATE as usize / 64) {
+        swept.process(&mut [&mut block[..]], 64, SAMPLE_RATE);
+        let cutoff = monitor.reading("cutoff").unwrap().value();
+        (lowest, highest) = (lowest.min(cutoff), highest.max(cutoff));
+    }
+    assert!((499.0..550.0).contains(&lowest), "lowest cutoff {:.0} Hz", lowest);
+    assert!((1_900.0..=2_001.0).contains(&highest), "highest cutoff {:.0} Hz", highest);
+}
+
+#[test]
+fn crossover_sums_flat() {
+    let coefficients = CrossoverCoefficients::new(500.0, SAMPLE_RATE);
+    for frequency in [50.0, 250.0, 500.0, 1_000.0, 5_000.0, 15_000.0] {
+        let mut state = CrossoverState::default();
+        let sum = response_db(frequency, |x| {
+            let (low, high) = state.split(&coefficients, x);
+            low + high
+        });
+        assert!(sum.abs() < 0.01, "{} Hz: crossover sum {:.3} dB", frequency, sum);
+    }
+}
+
+#[test]
+fn adsr_envelope_timing() {
+    let mut envelope = ADSREnvelope::new(0.01, 0.05, 0.5, 0.1);
+    envelope.note_on();
+    let attack = (1usize..).find(|_| envelope.get_value(SAMPLE_RATE) >= 1.0).unwrap();
+    let decay = (1usize..).find(|_| envelope.get_value(SAMPLE_RATE) <= 0.5).unwrap();
+    envelope.note_off();
+    let release = (1usize..).find(|_| envelope.is_finished() || envelope.get_value(SAMPLE_RATE) <= 0.0).unwrap();
+    assert!(attack.abs_diff(480) <= 1, "attack took {} samples", attack);
+    assert!(decay.abs_diff(2_400) <= 1, "decay took {} samples", decay);
+    assert!(release.abs_diff(4_800) <= 1, "release took {} samples", release);
+}
+
+#[test]
+fn envelope_follower_time_constant() {
+    let mut follower = EnvelopeFollower::new(0.005, 0.05, SAMPLE_RATE);
+    let rise = (1usize..).find(|_| follower.process(1.0) >= 1.0 - (-1.0f32).exp()).unwrap();
+    (0..SAMPLE_RATE as usize).for_each(|_| {
+        follower.process(1.0);
+    });
+    let fall = (1usize..).find(|_| follower.process(0.0) <= (-1.0f32).exp()).unwrap();
+    assert!(rise.abs_diff(240) <= 1, "attack reached 63% after {} samples", rise);
+    assert!(fall.abs_diff(2_400) <= 2, "release reached 37% after {} samples", fall);
+}
+
+#[test]
+fn smoothed_param_ramp_timing() {
+    let samples = (0.02 * SAMPLE_RATE) as usize;
+
+    let mut linear = SmoothedParam::new(0.0, 20.0, RampShape::Linear);
+    linear.prepare(SAMPLE_RATE);
+    linear.set_target(1.0);
+    let arrived = (1usize..).find(|_| linear.next_value() == 1.0).unwrap();
+    assert_eq!(arrived, samples, "linear ramp arrived after {} samples", arrived);
+
+    let mut exponential = SmoothedParam::new(0.0, 20.0, RampShape::Exponential);
+    exponential.prepare(SAMPLE_RATE);
+    exponential.set_target(1.0);
+    let values: Vec<f32> = (0..samples).map(|_| exponential.next_value()).collect();
+    let residual = db((1.0 - values[samples - 1] as f64).powi(2));
+    assert!((-61.0..-59.0).contains(&residual), "residual {:.1} dB", residual);
+    assert!(values.windows(2).all(|w| w[1] >= w[0]), "exponential ramp is not monotonic");
+}
+
+/// Stereo impulse response of `reverb`, `seconds` long, in 512-frame blocks
+fn reverb_response(reverb: &mut Reverb, seconds: f32) -> [Vec<f32>; 2] {
+    let frames = (seconds * SAMPLE_RATE) as usize;
+    let mut response = [vec![0.0; frames], vec![0.0; frames]];
+    response[0][0] = 1.0;
+    response[1][0] = 1.0;
+    let [left, right] = &mut response;
+    for (l, r) in left.chunks_mut(512).zip(right.chunks_mut(512)) {
+        let len = l.len();
+        reverb.process(&mut [l, r], len, SAMPLE_RATE);
+    }
+    response
+}
+
+/// Seconds for the energy of `signal` (in 10 ms windows) to fall from -5 dB to
+/// -35 dB below its loudest window, extrapolated to 60 dB (T30)
+fn t30(signal: &[f32]) -> f32 {
+    let window = (0.01 * SAMPLE_RATE) as usize;
+    let energy: Vec<f32> =
+        signal.chunks(window).map(|chunk| db(chunk.iter().map(|&s| (s * s) as f64).sum::<f64>())).collect();
+    let peak = energy.iter().copied().fold(f32::MIN, f32::max);
+    let crossing = |below: f32| energy.iter().rposition(|&e| e >= peak - below).unwrap_or(0) as f32;
+    (crossing(35.0) - crossing(5.0)) * 0.01 * 2.0
+}
+
+#[test]
+fn reverb_decay_pre_delay_and_width() {
+    let params = ReverbParams { decay: 1.5, damping: 0.0, pre_delay: 0.05, ..ReverbParams::default() };
+    let [left, right] = reverb_response(&mut Reverb::new(params), 4.0);
+    let undamped = t30(&left);
+    assert!((1.2..1.8).contains(&undamped), "T30 {:.2} s for a 1.5 s decay", undamped);
+    // nothing before the pre-delay, and the two sides differ
+    let onset = left.iter().position(|s| s.abs() > 1e-6).unwrap();
+    assert!(onset >= (0.05 * SAMPLE_RATE) as usize, "tail starts after {} samples", onset);
+    assert!(left.iter().zip(&right).any(|(l, r)| (l - r).abs() > 1e-3));
+
+    // damping shortens the tail; width 0 folds it to mono
+    let damped = ReverbParams { damping: 0.8, width: 0.0, ..params };
+    let [left, right] = reverb_response(&mut Reverb::new(damped), 4.0);
+    let shortened = t30(&left);
+    assert!(shortened < undamped - 0.1, "damped T30 {:.2} s vs {:.2} s undamped", shortened, undamped);
+    assert!(left.iter().zip(&right).all(|(l, r)| (l - r).abs() < 1e-6));
+
+    // no reverb at mix 0; a long decay stays bounded
+    let [left, _] = reverb_response(&mut Reverb::new(ReverbParams { mix: 0.0, ..params }), 0.5);
+    assert_eq!(left[0], 1.0);
+    assert!(left[1..].iter().all(|&s| s == 0.0));
+    let mut reverb = Reverb::new(ReverbParams { decay: 20.0, size: 1.0, ..params });
+    let mut peak = 0.0f32;
+    for block in 0..1000 {
+        let mut l = [if block < 100 { 0.5 } else { 0.0 }; 512];
+        let mut r = l;
+        reverb.process(&mut [&mut l[..], &mut r[..]], 512, SAMPLE_RATE);
+        peak = l.iter().chain(&r).fold(peak, |m, s| m.max(s.abs()));
+    }
+    assert!(peak.is_finite() && peak < 20.0, "peak {:.2} for 100 blocks of DC into a 20 s decay", peak);
+}
@@ -0,0 +1,158 @@
+//! Engine edits change the running graph in place: mix settings reach the
+//! live sources, and only nodes whose descriptor changed are rebuilt, so
+//! everything else keeps its identity and state.
+
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use pulsar_backend::engine::{EditCommand, Engine, EngineConfig, NodeTarget};
+use pulsar_backend::project::{NodeDescriptor, ParamValue, SendDescriptor, SourceDescriptor};
+use pulsar_backend::rt_processing::filters::{Trim, TrimSlope};
+use pulsar_backend::rt_processing::routing::{PanLaw, SourceId};
+
+use common::{Dc, Gain, close};
+
+/// A running engine with a `dc` source and a `gain` effect that count how
+/// often they are built
+fn engine() -> (Engine, Arc<AtomicUsize>, Arc<AtomicUsize>) {
+    let (sources, effects) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    let mut engine = Engine::new();
+    let built = Arc::clone(&sources);
+    engine.registry_mut().register_source("dc", move |node, _| {
+        built.fetch_add(1, Ordering::Relaxed);
+        Ok(Box::new(Dc(node.float("level", 1.0)?)))
+    });
+    let built = Arc::clone(&effects);
+    engine.registry_mut().register_effect("gain", move |node, _| {
+        built.fetch_add(1, Ordering::Relaxed);
+        Ok(Box::new(Gain(node.float("gain", 1.0)?)))
+    });
+    engine.configure(EngineConfig::default()).unwrap();
+    engine.start().unwrap();
+    (engine, sources, effects)
+}
+
+fn dc(level: f32) -> SourceDescriptor {
+    SourceDescriptor::new(NodeDescriptor::new("dc").with_param("level", level)).with_pan(0.0, PanLaw::Linear)
+}
+
+fn gain(gain: f32) -> NodeDescriptor {
+    NodeDescriptor::new("gain").with_param("gain", gain)
+}
+
+/// Router ids of the live sources, in the order they were added
+fn source_ids(engine: &Engine) -> Vec<SourceId> {
+    engine.meters().unwrap().take().sources.into_iter().map(|(id, _)| id).collect()
+}
+
+fn is_live(engine: &Engine, id: SourceId) -> bool {
+    engine.with_processor(|p| p.router().index_of(id).is_some()).unwrap()
+}
+
+/// Left channel of the last frame, once gain changes have settled
+fn settled(engine: &Engine) -> f32 {
+    let handle = engine.audio_handle().unwrap();
+    let mut output = vec![0.0; 1024 * 2];
+    for _ in 0..4 {
+        handle.process(&mut output);
+    }
+    output[output.len() - 2]
+}
+
+#[test]
+fn mix_edits_reach_the_live_source() {
+    let (mut engine, sources, _) = engine();
+    engine.add_source_node(dc(1.0)).unwrap();
+    let ids = source_ids(&engine);
+    assert!(close(settled(&engine), 0.5, 1e-3));
+
+    engine.edit(EditCommand::SetSourceMix { source: 0, gain: 0.5, pan: 0.0 }).unwrap();
+    assert!(close(settled(&engine), 0.25, 1e-3));
+    // a low-pass well above DC leaves the level alone
+    let trim = Some(Trim::new(20_000.0, TrimSlope::Db6));
+    engine.edit(EditCommand::SetSourceTrims { source: 0, high_pass: None, low_pass: trim }).unwrap();
+    engine.edit(EditCommand::SetSourceBus { source: 0, bus: 1 }).unwrap();
+    engine.edit(EditCommand::SetBusReturn { bus: 1, gain: 0.5 }).unwrap();
+    assert!(close(settled(&engine), 0.125, 1e-3));
+    let sends = vec![SendDescriptor { bus: 2, level: 0.4, pre_fader: false }];
+    engine.edit(EditCommand::SetSourceSends { source: 0, sends }).unwrap();
+
+    // the same source throughout, built once
+    assert_eq!(source_ids(&engine), ids);
+    assert_eq!(sources.load(Ordering::Relaxed), 1);
+    assert_eq!(engine.with_processor(|p| p.router().send_level(ids[0], 2)).unwrap(), Some(0.4));
+
+    // undo walks the live source back the same way
+    for _ in 0..5 {
+        assert!(engine.undo().unwrap());
+    }
+    assert_eq!(engine.history().undo_label(), Some("Add source"));
+    assert_eq!(source_ids(&engine), ids);
+    assert_eq!(engine.with_processor(|p| p.router().send_level(ids[0], 2)).unwrap(), None);
+    assert!(close(settled(&engine), 0.5, 1e-3));
+    assert_eq!(sources.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn param_edits_rebuild_only_their_node() {
+    let (mut engine, sources, _) = engine();
+    engine.add_source_node(dc(0.25)).unwrap();
+    engine.add_source_node(dc(0.5)).unwrap();
+    let ids = source_ids(&engine);
+    assert_eq!(sources.load(Ordering::Relaxed), 2);
+
+    let params = vec![("level".to_string(), ParamValue::Float(1.0))];
+    engine.edit(EditCommand::SetParams { target: NodeTarget::Source(1), params }).unwrap();
+    assert_eq!(sources.load(Ordering::Relaxed), 3);
+    // the node is swapped under the same routed source
+    assert_eq!(source_ids(&engine), ids);
+    assert!(close(settled(&engine), 0.625, 1e-3));
+}
+
+#[test]
+fn sources_coming_and_going_leave_the_others_alone() {
+    let (mut engine, sources, _) = engine();
+    for level in [0.1, 0.2, 0.4] {
+        engine.add_source_node(dc(level)).unwrap();
+    }
+    let ids = source_ids(&engine);
+
+    engine.edit(EditCommand::RemoveSource(1)).unwrap();
+    assert!(is_live(&engine, ids[0]) && !is_live(&engine, ids[1]) && is_live(&engine, ids[2]));
+    assert!(close(settled(&engine), 0.25, 1e-3));
+    assert_eq!(sources.load(Ordering::Relaxed), 3);
+
+    // undo builds the removed source again, and only that one
+    assert!(engine.undo().unwrap());
+    assert_eq!(sources.load(Ordering::Relaxed), 4);
+    assert_eq!(engine.with_processor(|p| p.router().num_sources()).unwrap(), 3);
+    assert!(is_live(&engine, ids[0]) && is_live(&engine, ids[2]));
+    assert!(close(settled(&engine), 0.35, 1e-3));
+}
+
+#[test]
+fn bus_effect_edits_rebuild_only_the_changed_effect() {
+    let (mut engine, _, effects) = engine();
+    engine.add_source_node(dc(1.0)).unwrap();
+    for level in [0.5, 0.5] {
+        engine.edit(EditCommand::AddBusEffect { bus: 0, index: None, node: gain(level) }).unwrap();
+    }
+    assert_eq!(effects.load(Ordering::Relaxed), 2);
+    assert!(close(settled(&engine), 0.125, 1e-3));
+
+    let params = vec![("gain".to_string(), ParamValue::Float(1.0))];
+    engine.edit(EditCommand::SetParams { target: NodeTarget::BusEffect { bus: 0, index: 1 }, params }).unwrap();
+    assert_eq!(effects.load(Ordering::Relaxed), 3);
+    assert!(close(settled(&engine), 0.25, 1e-3));
+
+    // inserting and removing builds nothing but the new effect
+    engine.edit(EditCommand::AddBusEffect { bus: 0, index: Some(0), node: gain(2.0) }).unwrap();
+    assert_eq!(effects.load(Ordering::Relaxed), 4);
+    assert!(close(settled(&engine), 0.5, 1e-3));
+    engine.edit(EditCommand::RemoveBusEffect { bus: 0, index: 1 }).unwrap();
+    assert_eq!(effects.load(Ordering::Relaxed), 4);
+    assert!(close(settled(&engine), 1.0, 1e-3));
+    assert!(engine.effect_modulation(0, 1).is_none());
+}
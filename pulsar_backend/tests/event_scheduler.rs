@@ -0,0 +1,143 @@
+//! Sample-accurate scheduling: events sent from other threads are dispatched
+//! at their frame's offset within the block, whatever the block size, and a
+//! `ScheduledPlayer` splits its rendering there.
+
+use std::thread;
+
+use pulsar_backend::rt_processing::notes::NoteEvent;
+use pulsar_backend::rt_processing::routing::AudioSource;
+use pulsar_backend::rt_processing::scheduler::{EventScheduler, ScheduledEvent, ScheduledPlayer};
+use pulsar_backend::rt_processing::voices::{Voice, VoicePool};
+
+/// Plays its velocity times its parameter 0 as DC until released
+struct Dc {
+    level: f32,
+    gain: f32,
+}
+
+impl Voice for Dc {
+    fn note_on(&mut self, _note: u8, velocity: f32) {
+        self.level = velocity;
+    }
+
+    fn note_off(&mut self) {
+        self.level = 0.0;
+    }
+
+    fn nrpn(&mut self, parameter: u16, value: f32) {
+        if parameter == 0 {
+            self.gain = value;
+        }
+    }
+
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        for channel in output.iter_mut() {
+            channel[..frames].fill(self.level * self.gain);
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.level > 0.0
+    }
+
+    fn reset(&mut self) {
+        self.level = 0.0;
+    }
+}
+
+fn player() -> ScheduledPlayer {
+    let voices = (0..4).map(|_| Box::new(Dc { level: 0.0, gain: 1.0 }) as Box<dyn Voice>).collect();
+    ScheduledPlayer::new(EventScheduler::new(64), VoicePool::new(voices, 1, 512))
+}
+
+/// Render `total` frames in blocks of `block`
+fn render(player: &mut ScheduledPlayer, total: usize, block: usize) -> Vec<f32> {
+    let mut output = vec![0.0; total];
+    for chunk in output.chunks_mut(block) {
+        let frames = chunk.len();
+        player.render(&mut [chunk], frames, 48_000.0);
+    }
+    output
+}
+
+#[test]
+fn events_arrive_at_their_offset() {
+    let mut scheduler = EventScheduler::new(16);
+    let sender = scheduler.sender();
+    let on = ScheduledEvent::Note(NoteEvent::NoteOn { note: 60, velocity: 1.0 });
+    let off = ScheduledEvent::Note(NoteEvent::NoteOff { note: 60 });
+    assert!(sender.schedule(70, off));
+    assert!(sender.schedule(10, on));
+    assert!(sender.param(10, 3, 0.5));
+    assert!(sender.note_on(5, 62, 0.8));
+
+    let mut seen = Vec::new();
+    scheduler.dispatch(64, |offset, event| seen.push((offset, event)));
+    // in time order, and in the order sent within a frame
+    let param = ScheduledEvent::Param { param: 3, value: 0.5 };
+    let early = ScheduledEvent::Note(NoteEvent::NoteOn { note: 62, velocity: 0.8 });
+    assert_eq!(seen, [(5, early), (10, on), (10, param)]);
+    assert_eq!((scheduler.pending(), scheduler.position(), sender.position()), (1, 64, 64));
+
+    seen.clear();
+    scheduler.dispatch(64, |offset, event| seen.push((offset, event)));
+    assert_eq!(seen, [(6, off)]);
+}
+
+#[test]
+fn late_and_overflowing_events_are_counted() {
+    let mut scheduler = EventScheduler::new(2);
+    let sender = scheduler.sender();
+    scheduler.dispatch(128, |_, _| {});
+
+    // already played: as soon as possible
+    sender.note_off(100, 60);
+    let mut offsets = Vec::new();
+    scheduler.dispatch(64, |offset, _| offsets.push(offset));
+    assert_eq!((offsets, scheduler.late()), (vec![0], 1));
+
+    assert!(sender.note_on(1000, 60, 1.0));
+    assert!(sender.note_on(1001, 60, 1.0));
+    assert!(!sender.note_on(1002, 60, 1.0));
+    assert_eq!(scheduler.dropped(), 1);
+
+    scheduler.clear();
+    assert_eq!(scheduler.pending(), 0);
+}
+
+#[test]
+fn players_switch_on_the_exact_frame() {
+    for block in [32, 64, 100, 512] {
+        let mut player = player();
+        let sender = player.sender();
+        sender.note_on(100, 60, 1.0);
+        sender.param(230, 0, 0.5);
+        sender.note_off(301, 60);
+
+        let output = render(&mut player, 512, block);
+        assert!(output[..100].iter().all(|&s| s == 0.0), "block {block}");
+        assert!(output[100..230].iter().all(|&s| s == 1.0), "block {block}");
+        assert!(output[230..301].iter().all(|&s| s == 0.5), "block {block}");
+        assert!(output[301..].iter().all(|&s| s == 0.0), "block {block}");
+    }
+}
+
+#[test]
+fn senders_work_from_other_threads() {
+    let mut player = player();
+    let sender = player.sender();
+    let handles: Vec<_> = (0..4u64)
+        .map(|i| {
+            let sender = sender.clone();
+            thread::spawn(move || sender.note_on(10 * (i + 1), 60 + i as u8, 0.25))
+        })
+        .collect();
+    assert!(handles.into_iter().all(|handle| handle.join().unwrap()));
+
+    // four voices join one by one, ten frames apart
+    let output = render(&mut player, 64, 64);
+    for (i, &s) in output.iter().enumerate() {
+        assert_eq!(s, 0.25 * (i / 10).min(4) as f32, "frame {i}");
+    }
+    assert_eq!(player.pool().active_voices(), 4);
+}
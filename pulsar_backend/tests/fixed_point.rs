@@ -0,0 +1,72 @@
+//! Reduced-precision render path: Q15 oscillators and the router's Q15 mix
+//! stay within Q15 resolution of the f32 path, and a full bus saturates.
+#![cfg(feature = "fixed-point")]
+
+mod common;
+
+use pulsar_backend::rt_processing::fixed::{FixedOscillator, from_q15, to_q15};
+use pulsar_backend::rt_processing::routing::{Pan, PanLaw};
+use pulsar_backend::rt_processing::voice_renderer::{AudioSource as WaveformSource, routing_source};
+use pulsar_backend::rt_processing::waveform::oscillators::Oscillator;
+use pulsar_backend::rt_processing::waveform::tables::WaveformType;
+
+use common::{Dc, router};
+
+const FRAMES: usize = 512;
+const SAMPLE_RATE: f32 = 48_000.0;
+
+fn max_difference(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max)
+}
+
+#[test]
+fn q15_oscillators_follow_the_f32_ones() {
+    for waveform in [WaveformType::Sine, WaveformType::Triangle, WaveformType::Sawtooth] {
+        let mut fixed = FixedOscillator::new(waveform, 440.0, FRAMES).with_amplitude(0.5);
+        let mut float = Oscillator::new(waveform, 440.0);
+        let mut q15 = vec![0; FRAMES];
+        fixed.render_q15(&mut q15, SAMPLE_RATE);
+        let fixed: Vec<f32> = q15.iter().map(|&s| from_q15(s)).collect();
+        let mut reference = vec![0.0; FRAMES];
+        float.fill_buffer(&mut reference, SAMPLE_RATE, 1, FRAMES);
+        let difference = max_difference(&fixed, &reference);
+        assert!(difference < 2e-3, "{waveform:?} is off by {difference}");
+    }
+    assert_eq!(to_q15(2.0), i16::MAX);
+    assert_eq!(from_q15(to_q15(-0.25)), -0.25);
+}
+
+/// Two sines panned apart, through a router that mixes in Q15 or not
+fn mix(fixed: bool) -> Vec<f32> {
+    let mut router = router(2, FRAMES);
+    router.set_fixed_point_mix(fixed);
+    assert_eq!(router.fixed_point_mix(), fixed);
+    let pan = |value| Pan { value, law: PanLaw::EqualPower };
+    router.add_source(routing_source(Oscillator::new(WaveformType::Sine, 220.0)), 0.8, pan(-0.5), 0);
+    router.add_source(routing_source(Oscillator::new(WaveformType::Sine, 330.0)), 0.6, pan(0.7), 1);
+    let mut output = vec![0.0; FRAMES * 2];
+    for _ in 0..4 {
+        router.process(&mut output, None);
+    }
+    output
+}
+
+#[test]
+fn the_q15_mix_matches_the_f32_mix() {
+    let difference = max_difference(&mix(true), &mix(false));
+    // a couple of Q15 steps for each rounding of samples and gains
+    assert!(difference < 4.0 / 32768.0, "off by {difference}");
+}
+
+#[test]
+fn a_full_bus_saturates() {
+    let mut router = router(1, FRAMES);
+    router.set_fixed_point_mix(true);
+    // 0.5 per side at the centre, so 1.8 a side in f32
+    for _ in 0..4 {
+        router.add_source(Box::new(Dc(0.9)), 1.0, Pan { value: 0.0, law: PanLaw::Linear }, 0);
+    }
+    let mut output = vec![0.0; FRAMES * 2];
+    router.process(&mut output, None);
+    assert!(output.iter().all(|&s| s == from_q15(i16::MAX)));
+}
@@ -0,0 +1,115 @@
+//! Output format conversion: 16-bit quantization with and without dither and
+//! noise shaping, and the request settings that reach the stream.
+
+use std::f32::consts::TAU;
+
+use cpal::SampleFormat;
+
+use pulsar_backend::audio_device::format_convert::{FormatConversion, FormatConverter, NoiseShaping};
+use pulsar_backend::audio_device::negotiation::{ConfigNegotiator, ConfigurationRequest};
+use pulsar_backend::audio_device::null::null_device;
+
+const SAMPLE_RATE: usize = 48_000;
+const LSB: f32 = 1.0 / 32767.0;
+const PLAIN: FormatConversion = FormatConversion { dither: false, noise_shaping: NoiseShaping::None };
+
+fn convert(conversion: FormatConversion, input: &[f32]) -> Vec<i16> {
+    let mut output = vec![0i16; input.len()];
+    FormatConverter::new(1, conversion).convert(input, &mut output);
+    output
+}
+
+/// Quantization error in LSBs
+fn errors(input: &[f32], output: &[i16]) -> Vec<f32> {
+    input.iter().zip(output).map(|(&x, &q)| q as f32 - x / LSB).collect()
+}
+
+fn power(signal: &[f32]) -> f32 {
+    signal.iter().map(|x| x * x).sum::<f32>() / signal.len() as f32
+}
+
+/// Amplitude of the `hz` component of one second of `signal`
+fn component(signal: &[f32], hz: usize) -> f32 {
+    let (re, im) = signal.iter().enumerate().fold((0.0, 0.0), |(re, im), (i, &x)| {
+        let phase = TAU * ((hz * i) % SAMPLE_RATE) as f32 / SAMPLE_RATE as f32;
+        (re + x * phase.cos(), im + x * phase.sin())
+    });
+    (re * re + im * im).sqrt() / signal.len() as f32
+}
+
+fn sine(amplitude: f32, hz: usize) -> Vec<f32> {
+    (0..SAMPLE_RATE).map(|i| amplitude * (TAU * ((hz * i) % SAMPLE_RATE) as f32 / SAMPLE_RATE as f32).sin()).collect()
+}
+
+#[test]
+fn plain_conversion_rounds_and_clips() {
+    let input = [0.0, 0.5, -0.5, 1.0, -1.0, 1.5, -1.5, 0.4 * LSB, 0.6 * LSB];
+    assert_eq!(convert(PLAIN, &input), [0, 16384, -16384, 32767, -32767, 32767, -32768, 0, 1]);
+
+    // u16 is offset binary
+    let mut unsigned = [0u16; 3];
+    FormatConverter::new(1, PLAIN).convert(&[0.0, 1.0, -1.5], &mut unsigned);
+    assert_eq!(unsigned, [0x8000, 0xffff, 0]);
+
+    // formats finer than 16 bits skip the dither
+    let mut wide = [0.0f64; 2];
+    FormatConverter::new(1, FormatConversion::default()).convert(&[0.25, -0.5], &mut wide);
+    assert_eq!(wide, [0.25, -0.5]);
+}
+
+#[test]
+fn dither_keeps_signals_below_one_lsb() {
+    // a steady quarter of an LSB rounds away without dither
+    let input = vec![0.25 * LSB; SAMPLE_RATE];
+    assert!(convert(PLAIN, &input).iter().all(|&q| q == 0));
+
+    let dithered = convert(FormatConversion::default(), &input);
+    let mean = dithered.iter().map(|&q| q as f32).sum::<f32>() / input.len() as f32;
+    assert!((mean - 0.25).abs() < 0.02, "{mean}");
+    // triangular ±1 LSB: the codes stay within one step of the signal
+    assert!(dithered.iter().all(|&q| (-1..=1).contains(&q)));
+
+    // the error no longer follows a quiet sine: none of its harmonics stand out
+    let quiet = sine(3.0 * LSB, 1000);
+    let plain = errors(&quiet, &convert(PLAIN, &quiet));
+    let dithered = errors(&quiet, &convert(FormatConversion::default(), &quiet));
+    assert!(component(&plain, 3000) > 0.05, "{}", component(&plain, 3000));
+    assert!(component(&dithered, 3000) < 0.01, "{}", component(&dithered, 3000));
+}
+
+#[test]
+fn noise_shaping_moves_the_error_out_of_the_lows() {
+    let loud = sine(0.3, 440);
+    let shaped = |noise_shaping| {
+        let conversion = FormatConversion { dither: true, noise_shaping };
+        errors(&loud, &convert(conversion, &loud))
+    };
+    // error power from 50 Hz to 1 kHz
+    let lows = |error: &[f32]| (1..=20).map(|k| component(error, k * 50).powi(2)).sum::<f32>();
+
+    let white = shaped(NoiseShaping::None);
+    let first = shaped(NoiseShaping::FirstOrder);
+    let second = shaped(NoiseShaping::SecondOrder);
+    assert!(lows(&first) < lows(&white) / 50.0, "{} vs {}", lows(&first), lows(&white));
+    assert!(lows(&second) < lows(&first) / 50.0, "{} vs {}", lows(&second), lows(&first));
+    // paid for with more noise overall: about 3 dB and 8 dB
+    assert!((power(&first) / power(&white) - 2.0).abs() < 0.2);
+    assert!((power(&second) / power(&white) - 6.0).abs() < 0.6);
+}
+
+#[test]
+fn requests_decide_whether_streams_convert() {
+    let device = null_device();
+    let config = ConfigNegotiator::negotiate(&device, &ConfigurationRequest::new()).unwrap();
+    assert_eq!(config.format_conversion, Some(FormatConversion::default()));
+
+    let shaped = FormatConversion { dither: true, noise_shaping: NoiseShaping::SecondOrder };
+    let request = ConfigurationRequest::new().with_format_conversion(shaped);
+    assert_eq!(ConfigNegotiator::negotiate(&device, &request).unwrap().format_conversion, Some(shaped));
+
+    // asking for a format the device lacks, with conversion disallowed, fails
+    // already in negotiation; otherwise the stream won't convert either
+    let strict = ConfigurationRequest::new().allow_format_conversion(false);
+    assert!(ConfigNegotiator::negotiate(&device, &strict.clone().with_sample_format(SampleFormat::I16)).is_err());
+    assert_eq!(ConfigNegotiator::negotiate(&device, &strict).unwrap().format_conversion, None);
+}
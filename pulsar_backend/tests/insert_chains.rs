@@ -0,0 +1,92 @@
+//! Insert chains on sources and buses: where they sit in the signal path and
+//! how the wet/dry mix blends them, set directly or through the command queue.
+
+mod common;
+
+use pulsar_backend::engine::{Engine, EngineConfig};
+use pulsar_backend::project::{NodeDescriptor, ProjectError, SourceDescriptor};
+use pulsar_backend::rt_processing::effects::Effect;
+use pulsar_backend::rt_processing::effects::chain::EffectChain;
+
+use common::{CENTRE, Dc, Gain, block, router};
+
+const FRAMES: usize = 64;
+
+/// Replaces the signal with 1.0 wherever it isn't silent, so it's
+/// only transparent to a unit input
+struct Saturate;
+
+impl Effect for Saturate {
+    fn process(&mut self, buffer: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        for channel in buffer.iter_mut() {
+            channel[..frames].iter_mut().for_each(|s| *s = s.signum());
+        }
+    }
+}
+
+#[test]
+fn source_inserts_run_before_gain_and_only_on_their_source() {
+    let mut router = router(2, FRAMES);
+    let (left, _) = CENTRE.gains();
+    let a = router.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 0);
+    let b = router.add_source(Box::new(Dc(1.0)), 0.5, CENTRE, 0);
+    assert!((block(&mut router) - 1.5 * left).abs() < 1e-6);
+
+    // saturating before the fader: the source's own gain still applies after it
+    assert!(router.add_source_effect(b, Box::new(Gain(4.0))));
+    assert!(router.add_source_effect(b, Box::new(Saturate)));
+    assert!((block(&mut router) - 1.5 * left).abs() < 1e-6);
+
+    assert!(router.set_source_effects(a, EffectChain::new().with(Box::new(Gain(0.5)))));
+    assert!((block(&mut router) - 1.0 * left).abs() < 1e-6);
+    router.clear_sources();
+    assert!(!router.add_source_effect(a, Box::new(Gain(0.0))));
+}
+
+#[test]
+fn wet_dry_mix_blends_and_glides() {
+    let mut router = router(2, FRAMES);
+    let (left, _) = CENTRE.gains();
+    let commands = router.commands();
+    let handle = commands.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 0).unwrap();
+    assert!(handle.set_effects(EffectChain::new().with(Box::new(Gain(0.0))).with_mix(0.25)));
+    // the mix starts where it is set
+    assert!((block(&mut router) - 0.75 * left).abs() < 1e-6);
+
+    // a change glides across one block, then holds
+    assert!(handle.set_effects_mix(1.0));
+    let mut output = vec![0.0; FRAMES * 2];
+    router.process(&mut output, None);
+    assert!(output[0] > 0.7 * left && output[0] < 0.75 * left);
+    assert!(output[(FRAMES - 1) * 2].abs() < 1e-6);
+    assert!(block(&mut router).abs() < 1e-6);
+
+    // bus chains blend the same way; bus 1 feeds the master
+    let mut router = common::router(2, FRAMES);
+    router.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 1);
+    assert!(router.add_bus_effect(1, Box::new(Gain(3.0))));
+    assert!((block(&mut router) - 3.0 * left).abs() < 1e-5);
+    assert!(router.set_bus_effects_mix(1, 0.5));
+    block(&mut router);
+    assert!((block(&mut router) - 2.0 * left).abs() < 1e-5);
+    assert!(!router.set_bus_effects_mix(7, 0.5));
+}
+
+#[test]
+fn project_sources_carry_their_inserts() {
+    let mut engine = Engine::new();
+    engine.configure(EngineConfig::default()).unwrap();
+    engine.start().unwrap();
+    let source = SourceDescriptor::new(NodeDescriptor::new("oscillator"))
+        .with_effect(NodeDescriptor::new("biquad").with_param("cutoff", 800.0))
+        .with_effect(NodeDescriptor::new("stereo_width").with_param("width", 0.5));
+    engine.add_source_node(source).unwrap();
+    assert_eq!(engine.snapshot().sources[0].effects.len(), 2);
+
+    // an insert that fails to build rejects the whole source
+    let broken = SourceDescriptor::new(NodeDescriptor::new("oscillator")).with_effect(NodeDescriptor::new("flanger"));
+    assert!(matches!(engine.add_source_node(broken), Err(ProjectError::UnknownKind(_))));
+    assert_eq!(engine.with_processor(|p| p.router().num_sources()).unwrap(), 1);
+    assert!(engine.undo().unwrap());
+    assert!(engine.snapshot().sources.is_empty());
+}
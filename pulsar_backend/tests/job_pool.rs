@@ -0,0 +1,107 @@
+//! The background worker pool, one per engine: jobs split over items run side by side and
+//! report in item order, a batch runs as one job per file, and a panicking
+//! completion callback leaves the worker that ran it alive.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+
+use pulsar_backend::engine::{Engine, EngineConfig};
+use pulsar_backend::io::wav::{self, SampleFormat, WavSpec};
+use pulsar_backend::jobs::{JobError, JobPriority, PoolConfig, WorkerPool};
+use pulsar_backend::offline::batch::{BatchPreset, BatchProcessor};
+use pulsar_backend::offline::bounce::BouncePreset;
+
+#[test]
+fn each_engine_sizes_its_own_pool() {
+    let (mut a, b) = (Engine::new(), Engine::new());
+    a.configure(EngineConfig { pool: PoolConfig::new(3, 1), ..EngineConfig::default() }).unwrap();
+    assert_eq!(a.pool().config(), PoolConfig::new(3, 1));
+    assert_eq!(b.pool().config(), PoolConfig::default());
+    assert_eq!(a.pool().spawn(JobPriority::Io, "ping", |_| "pong").wait(), Ok("pong"));
+}
+
+#[test]
+fn a_panicking_callback_leaves_the_worker_running() {
+    let pool = WorkerPool::new(PoolConfig::new(1, 0));
+    let (release, gate) = mpsc::channel::<()>();
+    // the job waits so the callback is registered before it finishes
+    let handle = pool.spawn(JobPriority::Io, "gated", move |_| gate.recv().is_ok());
+    handle.on_complete(|_| panic!("callback"));
+    release.send(()).unwrap();
+    assert_eq!(handle.wait(), Ok(true));
+
+    assert_eq!(pool.spawn(JobPriority::Compute, "after", |_| 7).wait(), Ok(7));
+    assert_eq!(pool.stats().threads, 1);
+}
+
+#[test]
+fn split_jobs_report_in_item_order() {
+    let pool = WorkerPool::new(PoolConfig::new(4, 1));
+    let handle = pool.spawn_each(JobPriority::Compute, "double", (0..32).collect(), |item: u32, _| item * 2);
+    assert_eq!(handle.wait(), Ok((0..32).map(|item| item * 2).collect()));
+    assert_eq!(pool.spawn_each(JobPriority::Io, "none", Vec::<u32>::new(), |item, _| item).wait(), Ok(Vec::new()));
+
+    // one item panicking fails the job, but the others still run
+    let ran = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&ran);
+    let handle = pool.spawn_each(JobPriority::Compute, "panics", (0..8).collect(), move |item: u32, _| {
+        counter.fetch_add(1, Ordering::Relaxed);
+        assert_ne!(item, 3, "item three");
+    });
+    assert!(matches!(handle.wait(), Err(JobError::Panicked(msg)) if msg.contains("item three")));
+    assert_eq!(ran.load(Ordering::Relaxed), 8);
+}
+
+#[test]
+fn cancelled_split_jobs_skip_their_queued_items() {
+    let pool = WorkerPool::new(PoolConfig::new(1, 0));
+    let (release, gate) = mpsc::channel::<()>();
+    let busy = pool.spawn(JobPriority::Compute, "busy", move |_| gate.recv().is_ok());
+
+    let ran = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&ran);
+    let handle = pool.spawn_each(JobPriority::Compute, "queued", (0..4).collect(), move |_: u32, _| {
+        counter.fetch_add(1, Ordering::Relaxed);
+    });
+    handle.cancel();
+    release.send(()).unwrap();
+    assert_eq!(busy.wait(), Ok(true));
+    assert_eq!(handle.wait(), Err(JobError::Cancelled));
+    assert_eq!(ran.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn batches_run_one_job_per_file() {
+    let dir = std::env::temp_dir().join(format!("pulsar-batch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let spec = WavSpec { sample_rate: 44_100, format: SampleFormat::Float32, dither: false };
+    let inputs: Vec<PathBuf> = (0..3)
+        .map(|i| {
+            let path = dir.join(format!("take{}.wav", i));
+            let tone: Vec<f32> = (0..4410 * (i + 1)).map(|n| (n as f32 * 0.05).sin() * 0.25).collect();
+            wav::write_file(&path, &[tone.clone(), tone], spec).unwrap();
+            path
+        })
+        .collect();
+
+    let pool = WorkerPool::new(PoolConfig::new(3, 1));
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&reports);
+    let batch = BatchProcessor::new(BatchPreset::new(BouncePreset::cd_stereo()));
+    let results = batch.run(&pool, &inputs, &dir, move |report| {
+        seen.lock().unwrap().push((report.index, report.fraction, report.files_done));
+    });
+
+    assert_eq!(results.len(), 3);
+    for (i, result) in results.into_iter().enumerate() {
+        let written = result.unwrap();
+        assert_eq!(written, vec![dir.join(format!("take{}_cd.wav", i))]);
+        assert!(written[0].exists());
+    }
+    let reports = reports.lock().unwrap();
+    let mut finished: Vec<_> = reports.iter().filter(|(_, fraction, _)| *fraction == 1.0).collect();
+    finished.sort_by_key(|(_, _, done)| *done);
+    assert_eq!(finished.iter().map(|(_, _, done)| *done).collect::<Vec<_>>(), [1, 2, 3]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,150 @@
+//! Lookahead limiter on the master chain: the ceiling holds however many
+//! sources sum, sample peaks and (with true-peak detection) the peaks between
+//! samples.
+
+mod common;
+
+use std::f32::consts::{FRAC_PI_4, TAU};
+
+use pulsar_backend::project::NodeDescriptor;
+use pulsar_backend::project::registry::{NodeContext, NodeRegistry};
+use pulsar_backend::rt_processing::effects::Effect;
+use pulsar_backend::rt_processing::effects::limiter::{Limiter, LimiterParams};
+use pulsar_backend::rt_processing::routing::{AudioSource, Pan, PanLaw};
+
+use common::router;
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const FRAMES: usize = 256;
+
+struct Sine {
+    frequency: f32,
+    phase: f32,
+}
+
+impl AudioSource for Sine {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        for i in 0..frames {
+            let sample = 0.5 * (TAU * self.phase).sin();
+            self.phase = (self.phase + self.frequency / sample_rate).fract();
+            for channel in output.iter_mut() {
+                channel[i] = sample;
+            }
+        }
+    }
+}
+
+fn db(gain: f32) -> f32 {
+    10f32.powf(gain / 20.0)
+}
+
+fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+}
+
+/// Mono signal through `limiter` in blocks
+fn run(limiter: &mut Limiter, input: &[f32]) -> Vec<f32> {
+    let mut output = input.to_vec();
+    for block in output.chunks_mut(FRAMES) {
+        let frames = block.len();
+        limiter.process(&mut [block], frames, SAMPLE_RATE);
+    }
+    output
+}
+
+#[test]
+fn summed_sources_stay_under_the_ceiling() {
+    let mut router = router(1, FRAMES);
+    let centre = Pan { value: 0.0, law: PanLaw::Linear };
+    for n in 0..12 {
+        let sine = Sine { frequency: 110.0 * (n + 1) as f32 * 1.01, phase: n as f32 / 12.0 };
+        router.add_source(Box::new(sine), 1.0, centre, 0);
+    }
+    let params = LimiterParams { ceiling_db: -0.3, ..LimiterParams::default() };
+    let limiter = Limiter::new(2, SAMPLE_RATE, params);
+    let reduction = limiter.gain_reduction_handle();
+    assert!(router.add_bus_effect(0, Box::new(limiter)));
+
+    let mut output = vec![0.0; FRAMES * 2];
+    let mut loudest = 0.0f32;
+    for _ in 0..200 {
+        router.process(&mut output, None);
+        loudest = loudest.max(peak(&output));
+    }
+    assert!(loudest <= db(-0.3) + 1e-6, "{loudest}");
+    // the sum peaks near 3: roughly 10 dB of reduction at times
+    assert!(loudest > db(-0.3) * 0.9, "{loudest}");
+    assert!(reduction.load() > 0.0);
+}
+
+#[test]
+fn quiet_audio_passes_untouched_but_late() {
+    for true_peak in [true, false] {
+        let params = LimiterParams { true_peak, ..LimiterParams::default() };
+        let mut limiter = Limiter::new(1, SAMPLE_RATE, params);
+        // 5 ms lookahead, and half the interpolator's taps for true peaks
+        assert_eq!(limiter.latency(), if true_peak { 244 } else { 240 });
+        assert_eq!(Effect::latency(&limiter), limiter.latency());
+
+        let input: Vec<f32> = (0..4_800).map(|i| 0.8 * (TAU * 440.0 * i as f32 / SAMPLE_RATE).sin()).collect();
+        let output = run(&mut limiter, &input);
+        let latency = limiter.latency();
+        assert!(output[..latency].iter().all(|&s| s == 0.0));
+        assert_eq!(&output[latency..], &input[..input.len() - latency]);
+        assert_eq!(limiter.gain_reduction(), 0.0);
+    }
+}
+
+#[test]
+fn peaks_are_caught_ahead_and_released() {
+    let params = LimiterParams { ceiling_db: -6.0, lookahead: 0.002, release: 0.05, true_peak: false };
+    let mut limiter = Limiter::new(1, SAMPLE_RATE, params);
+    let latency = limiter.latency();
+    // a single full-scale click in quiet audio
+    let mut input = vec![0.1; 16_000];
+    input[1_000] = 1.0;
+    let output = run(&mut limiter, &input);
+
+    assert!((output[1_000 + latency] - db(-6.0)).abs() < 1e-5);
+    assert!(peak(&output) <= db(-6.0) + 1e-6);
+    // the gain ramps down over the lookahead before the click
+    assert_eq!(output[999], 0.1);
+    assert!(output[1_000 + latency / 2] < 0.1 && output[1_000 + latency / 2] > 0.1 * db(-6.0));
+    // and comes back up over the release: 63% of the way in 50 ms
+    let after = 1_000 + latency + 1;
+    assert!(output[after] < 0.06);
+    let recovered = 1.0 - (1.0 - db(-6.0)) * (-1.0f32).exp();
+    assert!((output[after + 2_400] - 0.1 * recovered).abs() < 0.002, "{}", output[after + 2_400]);
+    assert!((output[after + 12_000] - 0.1).abs() < 0.001);
+
+    limiter.reset();
+    assert_eq!(limiter.gain_reduction(), 0.0);
+}
+
+#[test]
+fn true_peaks_between_samples_are_limited() {
+    // a quarter of the sample rate at 45°: samples at ±0.707, the wave itself reaches 1
+    let input: Vec<f32> = (0..4_800).map(|i| (TAU * i as f32 / 4.0 + FRAC_PI_4).sin()).collect();
+    assert!(peak(&input) < 0.71);
+
+    let sample_peaks = LimiterParams { true_peak: false, ..LimiterParams::default() };
+    let output = run(&mut Limiter::new(1, SAMPLE_RATE, sample_peaks), &input);
+    assert_eq!(peak(&output), peak(&input));
+
+    let mut limiter = Limiter::new(1, SAMPLE_RATE, LimiterParams::default());
+    let output = run(&mut limiter, &input);
+    // the samples come down with the wave's true peak held at the ceiling
+    let steady = peak(&output[1_000..]);
+    assert!((steady / peak(&input) - db(-1.0)).abs() < 0.02, "{steady}");
+    assert!((limiter.gain_reduction() - 1.0).abs() < 0.2, "{}", limiter.gain_reduction());
+}
+
+#[test]
+fn the_registry_builds_limiters() {
+    let registry = NodeRegistry::with_builtins();
+    let ctx = NodeContext { channels: 2, sample_rate: SAMPLE_RATE, max_frames: FRAMES, tempo_bpm: 120.0 };
+    let node = NodeDescriptor::new("limiter").with_param("lookahead", 0.001).with_param("true_peak", false);
+    let limiter = registry.build_effect(&node, &ctx).unwrap();
+    assert_eq!(limiter.latency(), 48);
+    assert_eq!(registry.params("limiter").len(), 4);
+}
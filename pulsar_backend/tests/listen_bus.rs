@@ -0,0 +1,91 @@
+//! The listen bus: pre-fader listen hears a source as it renders, ahead of
+//! its trims and fader, after-fader listen hears it as it sits in the mix,
+//! and neither changes the main output.
+
+mod common;
+
+use pulsar_backend::rt_processing::filters::{Trim, TrimSlope};
+use pulsar_backend::rt_processing::routing::{ListenMode, Pan, PanLaw, Router, SourceId};
+
+use common::{CENTRE, Dc, router};
+
+const FRAMES: usize = 256;
+
+/// Left channel of the last frame of the main output and the listen bus,
+/// after enough blocks for the trims to settle
+fn outputs(router: &mut Router) -> (f32, f32) {
+    let (mut output, mut listen) = (vec![0.0; FRAMES * 2], vec![0.0; FRAMES * 2]);
+    for _ in 0..8 {
+        router.process_with_listen(&mut output, &mut listen, None);
+    }
+    (output[(FRAMES - 1) * 2], listen[(FRAMES - 1) * 2])
+}
+
+/// A DC source at half gain with a high-pass trim that takes it out of the
+/// mix, and another at full level; the main output is 1.0 either way
+fn mix() -> (Router, [SourceId; 2]) {
+    let router = router(1, FRAMES);
+    let trimmed = router.add_source(Box::new(Dc(1.0)), 0.5, CENTRE, 0);
+    assert!(router.set_high_pass_trim(trimmed, Some(Trim::new(1_000.0, TrimSlope::Db12))));
+    let plain = router.add_source(Box::new(Dc(2.0)), 1.0, CENTRE, 0);
+    (router, [trimmed, plain])
+}
+
+#[test]
+fn pfl_taps_ahead_of_the_trims_and_fader() {
+    let (mut router, [trimmed, plain]) = mix();
+    // nothing listened: the listen bus follows the main mix
+    assert!(!router.is_listening());
+    let (main, listen) = outputs(&mut router);
+    assert!((main - 1.0).abs() < 1e-3, "{main}");
+    assert_eq!(listen, main);
+
+    // the trim and the half gain don't reach the listen bus, nor the pan
+    assert!(router.set_listen(trimmed, Some(ListenMode::Pfl)));
+    assert!(router.is_listening());
+    let (main, listen) = outputs(&mut router);
+    assert!((main - 1.0).abs() < 1e-3, "{main}");
+    assert!((listen - 1.0).abs() < 1e-6, "{listen}");
+
+    // a muted source is still heard, and the listen bus sums what's listened
+    assert!(router.set_mute(trimmed, true));
+    assert!(router.set_listen(plain, Some(ListenMode::Pfl)));
+    let (main, listen) = outputs(&mut router);
+    assert!((main - 1.0).abs() < 1e-3, "{main}");
+    assert!((listen - 3.0).abs() < 1e-6, "{listen}");
+}
+
+#[test]
+fn afl_taps_after_the_fader_and_pan() {
+    let (mut router, [trimmed, plain]) = mix();
+    let hard_left = Pan { value: -1.0, law: PanLaw::Linear };
+    assert!(router.set_pan(plain, hard_left));
+    assert!(router.set_gain(plain, 0.25));
+    assert!(router.set_listen(plain, Some(ListenMode::Afl)));
+    let (main, listen) = outputs(&mut router);
+    let (left, _) = hard_left.gains();
+    assert!((main - 0.5 * left).abs() < 1e-3, "{main}");
+    assert!((listen - 0.5 * left).abs() < 1e-6, "{listen}");
+
+    // after the trims too: the trimmed source adds nothing once settled
+    assert!(router.set_listen(trimmed, Some(ListenMode::Afl)));
+    let (_, listen) = outputs(&mut router);
+    assert!((listen - 0.5 * left).abs() < 1e-3, "{listen}");
+}
+
+#[test]
+fn listening_leaves_the_main_mix_alone() {
+    let (mut listened, [trimmed, plain]) = mix();
+    let (mut reference, _) = mix();
+    assert!(listened.set_listen(trimmed, Some(ListenMode::Pfl)));
+    assert!(listened.set_listen(plain, Some(ListenMode::Afl)));
+    let (mut output, mut listen) = (vec![0.0; FRAMES * 2], vec![0.0; FRAMES * 2]);
+    let mut expected = vec![0.0; FRAMES * 2];
+    for _ in 0..4 {
+        listened.process_with_listen(&mut output, &mut listen, None);
+        reference.process(&mut expected, None);
+        assert_eq!(output, expected);
+    }
+    assert!(listened.set_listen(trimmed, None) && listened.set_listen(plain, None));
+    assert!(!listened.is_listening());
+}
@@ -0,0 +1,135 @@
+//! EBU R128 loudness of the router output: K-weighting, the momentary,
+//! short-term and integrated windows, and gating.
+
+mod common;
+
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use pulsar_backend::engine::{Engine, EngineConfig};
+use pulsar_backend::rt_processing::filters::RampShape;
+use pulsar_backend::rt_processing::loudness::{LoudnessMeter, LoudnessSnapshot};
+use pulsar_backend::rt_processing::routing::{AudioSource, Pan, PanLaw, Router};
+
+use common::close;
+
+const FRAMES: usize = 480;
+
+/// Sine on every channel, with its peak level in dBFS shared so tests can change it
+struct Sine {
+    frequency: f32,
+    phase: f32,
+    level_db: Arc<AtomicU32>,
+}
+
+impl AudioSource for Sine {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        let amplitude = 10f32.powf(f32::from_bits(self.level_db.load(Ordering::Relaxed)) / 20.0);
+        for i in 0..frames {
+            let sample = amplitude * (TAU * self.phase).sin();
+            self.phase = (self.phase + self.frequency / sample_rate).fract();
+            for channel in output.iter_mut() {
+                channel[i] = sample;
+            }
+        }
+    }
+}
+
+/// Stereo router playing a sine at both channels' full level
+fn router(sample_rate: f32, frequency: f32) -> (Router, Arc<AtomicU32>) {
+    let mut router = Router::new(2, sample_rate, 1, FRAMES);
+    router.set_param_ramp(0.0, RampShape::Linear);
+    let level_db = Arc::new(AtomicU32::new((-23.0f32).to_bits()));
+    let sine = Sine { frequency, phase: 0.0, level_db: Arc::clone(&level_db) };
+    // a centred linear pan halves each side
+    router.add_source(Box::new(sine), 2.0, Pan { value: 0.0, law: PanLaw::Linear }, 0);
+    (router, level_db)
+}
+
+fn play(router: &mut Router, seconds: f32) {
+    let mut output = vec![0.0; FRAMES * 2];
+    let blocks = (seconds * router.sample_rate() / FRAMES as f32).round() as usize;
+    for _ in 0..blocks {
+        router.process(&mut output, None);
+    }
+}
+
+#[test]
+fn a_stereo_tone_reads_its_level() {
+    // EBU Tech 3341 case 1: 1 kHz at -23 dBFS in both channels is -23 LUFS
+    for sample_rate in [48_000.0, 44_100.0, 96_000.0] {
+        let (mut router, _) = router(sample_rate, 1_000.0);
+        let mut meter = LoudnessMeter::new(&router.meters());
+        assert_eq!(meter.read(), LoudnessSnapshot::default());
+        play(&mut router, 5.0);
+        let reading = meter.read();
+        assert!(close(reading.integrated, -23.0, 0.1), "{sample_rate}: {reading:?}");
+        assert!(close(reading.momentary, -23.0, 0.1), "{reading:?}");
+        assert!(close(reading.short_term, -23.0, 0.1), "{reading:?}");
+        assert!(close(reading.max_short_term, -23.0, 0.1), "{reading:?}");
+    }
+
+    // K-weighting, relative to 1 kHz: -6.3 dB at 40 Hz, +3.3 dB at 8 kHz
+    let (mut low, _) = router(48_000.0, 40.0);
+    let mut meter = LoudnessMeter::new(&low.meters());
+    play(&mut low, 2.0);
+    assert!(close(meter.read().integrated, -23.0 - 6.26, 0.1));
+    let (mut high, _) = router(48_000.0, 8_000.0);
+    let mut meter = LoudnessMeter::new(&high.meters());
+    play(&mut high, 2.0);
+    let integrated = meter.read().integrated;
+    assert!(close(integrated, -23.0 + 3.34, 0.1), "{integrated}");
+}
+
+#[test]
+fn quiet_passages_are_gated_out_of_the_integrated_loudness() {
+    let (mut router, level_db) = router(48_000.0, 1_000.0);
+    let mut meter = LoudnessMeter::new(&router.meters());
+    play(&mut router, 5.0);
+
+    // below the absolute gate: ignored, but for the blocks spanning the change
+    level_db.store((-90.0f32).to_bits(), Ordering::Relaxed);
+    play(&mut router, 5.0);
+    let reading = meter.read();
+    assert!(close(reading.integrated, -23.0, 0.2), "{reading:?}");
+    assert!(reading.momentary < -85.0 && reading.short_term < -85.0, "{reading:?}");
+    assert!(close(reading.max_momentary, -23.0, 0.1), "{reading:?}");
+
+    // above it, but more than 10 LU under the rest: ignored as well
+    level_db.store((-40.0f32).to_bits(), Ordering::Relaxed);
+    play(&mut router, 5.0);
+    assert!(close(meter.read().integrated, -23.0, 0.2));
+
+    // within 10 LU it counts: two thirds of the time at -23, a third at -30
+    level_db.store((-30.0f32).to_bits(), Ordering::Relaxed);
+    play(&mut router, 2.5);
+    let integrated = meter.read().integrated;
+    let expected = 10.0 * ((2.0 * 10f32.powf(-2.3) + 10f32.powf(-3.0)) / 3.0).log10();
+    assert!(close(integrated, expected, 0.2), "{integrated} vs {expected}");
+
+    // reset starts over
+    meter.reset();
+    assert_eq!(meter.snapshot(), LoudnessSnapshot::default());
+    play(&mut router, 1.0);
+    assert!(close(meter.read().integrated, -30.0, 0.1));
+}
+
+#[test]
+fn channel_weights_follow_the_layout() {
+    let mut engine = Engine::new();
+    engine.configure(EngineConfig::default()).unwrap();
+    let meters = engine.meters().unwrap();
+    let meter = LoudnessMeter::new(&meters);
+    assert_eq!(meter.channel_weights(), vec![1.0; EngineConfig::default().channels]);
+
+    let surround = Router::new(6, 48_000.0, 1, FRAMES);
+    let meter = LoudnessMeter::new(&surround.meters());
+    assert_eq!(meter.channel_weights(), [1.0, 1.0, 1.0, 0.0, 1.41, 1.41]);
+
+    // only the left channel counts: 3 dB down
+    let (mut router, _) = router(48_000.0, 1_000.0);
+    let mut meter = LoudnessMeter::new(&router.meters()).with_channel_weights(vec![1.0, 0.0]);
+    play(&mut router, 1.0);
+    assert!(close(meter.read().integrated, -26.0, 0.1));
+}
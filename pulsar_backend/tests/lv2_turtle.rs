@@ -0,0 +1,184 @@
+//! The Turtle reader behind LV2 bundle discovery: prefixes and base IRIs,
+//! collections, literals and blank nodes as bundles write them, and errors
+//! with the line they occur on for malformed documents.
+
+use pulsar_backend::plugins::lv2::turtle::{Graph, ParseError, RDF_TYPE, Term};
+
+const BASE: &str = "file:///usr/lib/lv2/amp.lv2/amp.ttl";
+const LV2: &str = "http://lv2plug.in/ns/lv2core#";
+const RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+
+fn parse(text: &str) -> Graph {
+    let mut graph = Graph::new();
+    graph.parse(text, BASE).unwrap();
+    graph
+}
+
+fn parse_error(text: &str) -> ParseError {
+    Graph::new().parse(text, BASE).expect_err("a parse error")
+}
+
+fn iri(iri: &str) -> Term {
+    Term::Iri(iri.to_string())
+}
+
+fn lv2(name: &str) -> String {
+    format!("{}{}", LV2, name)
+}
+
+fn literal(text: &str) -> Term {
+    Term::Literal(text.to_string())
+}
+
+/// The items of the collection starting at `list`
+fn items(graph: &Graph, list: &Term) -> Vec<Term> {
+    let (first, rest) = (format!("{}first", RDF), format!("{}rest", RDF));
+    let mut items = Vec::new();
+    let mut node = list.clone();
+    while node != iri(&format!("{}nil", RDF)) {
+        items.push(graph.object(&node, &first).unwrap().clone());
+        node = graph.object(&node, &rest).unwrap().clone();
+    }
+    items
+}
+
+#[test]
+fn prefixes_and_base_resolve_to_full_iris() {
+    let graph = parse(
+        r#"
+        @prefix lv2: <http://lv2plug.in/ns/lv2core#> .
+        PREFIX doap: <http://usefulinc.com/ns/doap#>
+        @prefix : <#> .
+
+        <http://example.org/amp> a lv2:Plugin , lv2:AmplifierPlugin ;
+            lv2:binary <amp.so> ;
+            doap:license :license .
+
+        @base <http://example.org/other/> .
+        <relative> a lv2:Plugin .
+        # a later prefix shadows an earlier one
+        @prefix lv2: <http://example.org/shadow#> .
+        <relative> lv2:port <> .
+        "#,
+    );
+    let amp = iri("http://example.org/amp");
+    assert!(graph.has(&amp, RDF_TYPE, &iri(&lv2("Plugin"))));
+    assert!(graph.has(&amp, RDF_TYPE, &iri(&lv2("AmplifierPlugin"))));
+    assert_eq!(graph.object(&amp, &lv2("binary")), Some(&iri("file:///usr/lib/lv2/amp.lv2/amp.so")));
+    assert_eq!(
+        graph.object(&amp, "http://usefulinc.com/ns/doap#license"),
+        Some(&iri("file:///usr/lib/lv2/amp.lv2/amp.ttl#license"))
+    );
+
+    let plugins: Vec<_> = graph.instances(&lv2("Plugin")).collect();
+    assert_eq!(plugins, [&amp, &iri("http://example.org/other/relative")]);
+    let relative = iri("http://example.org/other/relative");
+    assert_eq!(graph.object(&relative, "http://example.org/shadow#port"), Some(&iri("http://example.org/other/")));
+}
+
+#[test]
+fn collections_become_linked_lists() {
+    let graph = parse(
+        r#"
+        @prefix lv2: <http://lv2plug.in/ns/lv2core#> .
+        <#amp> lv2:requiredFeature ( lv2:isLive <#boundedBlock> "text" 3 ) ;
+            lv2:optionalFeature () .
+        "#,
+    );
+    let amp = iri(&format!("{}#amp", BASE));
+    let list = graph.object(&amp, &lv2("requiredFeature")).unwrap();
+    assert!(matches!(list, Term::Blank(_)));
+    assert_eq!(
+        items(&graph, list),
+        [iri(&lv2("isLive")), iri(&format!("{}#boundedBlock", BASE)), literal("text"), literal("3")]
+    );
+    assert_eq!(graph.object(&amp, &lv2("optionalFeature")), Some(&iri(&format!("{}nil", RDF))));
+}
+
+#[test]
+fn literals_keep_their_text() {
+    let graph = parse(
+        r#"
+        @prefix lv2: <http://lv2plug.in/ns/lv2core#> .
+        @prefix xsd: <http://www.w3.org/2001/XMLSchema#> .
+        <#amp> lv2:name "Amp \"Classic\"\tone" , 'single'@en ;
+            lv2:documentation """two
+        lines""" ;
+            lv2:default 0.5 , -1.5e3 , +2 , .25 , "7"^^xsd:integer ;
+            lv2:index 0 .
+        <#amp> lv2:toggled true ; lv2:enabled false.
+        "#,
+    );
+    let amp = iri(&format!("{}#amp", BASE));
+    let names: Vec<_> = graph.objects(&amp, &lv2("name")).cloned().collect();
+    assert_eq!(names, [literal("Amp \"Classic\"\tone"), literal("single")]);
+    assert_eq!(graph.object(&amp, &lv2("documentation")), Some(&literal("two\n        lines")));
+
+    let defaults: Vec<_> = graph.objects(&amp, &lv2("default")).filter_map(Term::as_f32).collect();
+    assert_eq!(defaults, [0.5, -1500.0, 2.0, 0.25, 7.0]);
+    // a number ends at the statement's closing dot
+    assert_eq!(graph.object(&amp, &lv2("index")).and_then(Term::as_f32), Some(0.0));
+    assert_eq!(graph.object(&amp, &lv2("toggled")), Some(&literal("true")));
+    assert_eq!(graph.object(&amp, &lv2("enabled")), Some(&literal("false")));
+    assert_eq!(graph.object(&amp, &lv2("name")).and_then(Term::as_iri), None);
+}
+
+#[test]
+fn blank_nodes_nest_and_stay_distinct() {
+    let graph = parse(
+        r#"
+        @prefix lv2: <http://lv2plug.in/ns/lv2core#> .
+        <#amp> lv2:port [
+            a lv2:InputPort , lv2:ControlPort ;
+            lv2:index 0 ;
+            lv2:symbol "gain" ;
+            lv2:scalePoint [ lv2:value -90 ; ] ;
+        ] , [
+            a lv2:OutputPort ;
+            lv2:index 1 ;;
+        ] .
+        [ a lv2:Plugin ] .
+        [] lv2:symbol "anonymous" .
+        "#,
+    );
+    let amp = iri(&format!("{}#amp", BASE));
+    let ports: Vec<_> = graph.objects(&amp, &lv2("port")).cloned().collect();
+    assert_eq!(ports.len(), 2);
+    assert!(ports.iter().all(|port| matches!(port, Term::Blank(_))));
+    assert_ne!(ports[0], ports[1]);
+
+    let input = &ports[0];
+    assert!(graph.has(input, RDF_TYPE, &iri(&lv2("InputPort"))));
+    assert!(graph.has(input, RDF_TYPE, &iri(&lv2("ControlPort"))));
+    assert_eq!(graph.object(input, &lv2("symbol")), Some(&literal("gain")));
+    let point = graph.object(input, &lv2("scalePoint")).unwrap();
+    assert_eq!(graph.object(point, &lv2("value")).and_then(Term::as_f32), Some(-90.0));
+    assert_eq!(graph.object(&ports[1], &lv2("index")).and_then(Term::as_f32), Some(1.0));
+
+    // statements made of a blank node alone, with and without properties
+    let plugins: Vec<_> = graph.instances(&lv2("Plugin")).collect();
+    assert!(matches!(plugins[..], [Term::Blank(_)]));
+    let anonymous = (1..32).map(Term::Blank).find(|node| graph.has(node, &lv2("symbol"), &literal("anonymous")));
+    assert!(anonymous.is_some_and(|node| !ports.contains(&node) && node != *plugins[0]));
+}
+
+#[test]
+fn malformed_documents_report_their_line() {
+    let prefix = "@prefix lv2: <http://lv2plug.in/ns/lv2core#> .\n";
+    // each case starts on line 3; errors are reported where the parser stopped
+    let cases = [
+        ("<#amp> lv2:name \"unterminated .\n", 3, "unterminated string"),
+        ("<#amp> foaf:name \"x\" .\n", 3, "undefined prefix \"foaf\""),
+        ("<#amp> lv2:name \"x\"\n<#other> lv2:name \"y\" .\n", 4, "expected '.'"),
+        ("@version 1.1 .\n", 3, "unknown directive @version"),
+        ("<#amp> lv2:port [ lv2:index 0 .\n", 3, "expected ']'"),
+        ("<#amp> lv2:port ( 1 2\n", 4, "expected an IRI"),
+        ("<#amp> name \"x\" .\n", 3, "expected an IRI, found \"name\""),
+    ];
+    for (text, line, message) in cases {
+        let error = parse_error(&format!("{}\n{}", prefix, text));
+        assert_eq!(error.line, line, "{:?}: {}", text, error);
+        assert!(error.message.starts_with(message), "{:?}: {}", text, error);
+        assert!(error.to_string().starts_with(&format!("line {}: ", line)));
+    }
+}
@@ -0,0 +1,99 @@
+//! Media controls: commands queued from the OS (or anywhere else) driving the
+//! engine's transport, and the transport's state shown back.
+
+use pulsar_backend::engine::{Engine, EngineConfig, EngineError, EngineState};
+use pulsar_backend::media::{MediaCommand, MediaSession, NowPlaying, PlaybackStatus};
+
+fn running() -> Engine {
+    let mut engine = Engine::new();
+    engine.configure(EngineConfig::default()).unwrap();
+    engine.start().unwrap();
+    engine
+}
+
+/// Mix a few blocks, so queued transport changes land and the playhead moves
+fn blocks(engine: &Engine) {
+    let handle = engine.audio_handle().unwrap();
+    let mut output = vec![0.0; 256 * EngineConfig::default().channels];
+    for _ in 0..4 {
+        handle.process(&mut output);
+    }
+}
+
+#[test]
+fn transport_commands_drive_the_transport() {
+    let engine = running();
+    let transport = engine.clock().unwrap();
+    let mut session = MediaSession::new();
+    assert!(!session.is_connected());
+    let sender = session.sender();
+
+    sender.send(MediaCommand::Play).unwrap();
+    assert!(session.apply(&engine).unwrap().is_empty());
+    assert_eq!(session.status(), PlaybackStatus::Playing);
+    blocks(&engine);
+    assert!(transport.is_playing());
+    let paused_at = transport.state().frame;
+    assert!(paused_at > 0);
+
+    // pause stops where the playhead is, and the engine keeps running
+    sender.send(MediaCommand::Pause).unwrap();
+    session.apply(&engine).unwrap();
+    assert_eq!(session.status(), PlaybackStatus::Paused);
+    blocks(&engine);
+    assert!(!transport.is_playing());
+    assert!(transport.state().frame >= paused_at);
+    assert_eq!(engine.state(), EngineState::Running);
+
+    // toggles run in order; repeats are harmless
+    for command in [MediaCommand::PlayPause, MediaCommand::Play, MediaCommand::PlayPause, MediaCommand::PlayPause] {
+        sender.send(command).unwrap();
+    }
+    session.apply(&engine).unwrap();
+    assert_eq!(session.status(), PlaybackStatus::Playing);
+    assert!(session.try_recv().is_none());
+    blocks(&engine);
+    assert!(transport.is_playing());
+
+    // stop goes back to the start
+    sender.send(MediaCommand::Stop).unwrap();
+    session.apply(&engine).unwrap();
+    assert_eq!(session.status(), PlaybackStatus::Stopped);
+    blocks(&engine);
+    assert_eq!((transport.is_playing(), transport.state().frame), (false, 0));
+    assert_eq!(PlaybackStatus::from(transport.state()), PlaybackStatus::Stopped);
+}
+
+#[test]
+fn track_commands_go_back_to_the_caller() {
+    let engine = running();
+    let mut session = MediaSession::new();
+    let sender = session.sender();
+    for command in [MediaCommand::Next, MediaCommand::Pause, MediaCommand::Previous, MediaCommand::Next] {
+        sender.send(command).unwrap();
+    }
+    let unhandled = session.apply(&engine).unwrap();
+    assert_eq!(unhandled, [MediaCommand::Next, MediaCommand::Previous, MediaCommand::Next]);
+    assert!(unhandled.iter().all(|command| !command.is_transport()));
+    // nothing to pause yet
+    assert_eq!(session.status(), PlaybackStatus::Stopped);
+
+    let track = NowPlaying { title: "Backing track".into(), artist: Some("Pulsar".into()), ..NowPlaying::default() };
+    session.set_now_playing(track.clone()).unwrap();
+    assert_eq!(session.now_playing(), &track);
+}
+
+#[test]
+fn there_is_no_transport_before_configuring() {
+    let engine = Engine::new();
+    let mut session = MediaSession::new();
+    session.sender().send(MediaCommand::Play).unwrap();
+    assert!(matches!(session.apply(&engine), Err(EngineError::NotConfigured(_))));
+}
+
+#[cfg(not(all(any(target_os = "linux", target_os = "windows", target_os = "macos"), feature = "media-keys")))]
+#[test]
+fn without_a_backend_connecting_is_unsupported() {
+    use pulsar_backend::media::MediaError;
+    assert_eq!(MediaSession::connect("Pulsar").err(), Some(MediaError::Unsupported));
+}
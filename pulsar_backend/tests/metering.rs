@@ -0,0 +1,132 @@
+//! Level meters: peak and RMS per source, bus and master, and the ballistics
+//! applied when they're read.
+
+mod common;
+
+use std::time::Duration;
+
+use pulsar_backend::engine::{Engine, EngineConfig};
+use pulsar_backend::rt_processing::metering::{MeterBallistics, MeterReader};
+use pulsar_backend::rt_processing::routing::{Pan, PanLaw};
+
+use common::{CENTRE, Square, close, router};
+
+const FRAMES: usize = 256;
+
+#[test]
+fn sources_buses_and_master_are_metered() {
+    let mut router = router(2, FRAMES);
+    let left = Pan { value: -1.0, law: PanLaw::Linear };
+    let quiet = router.add_source(Box::new(Square(0.5)), 0.5, left, 0);
+    let loud = router.add_source(Box::new(Square(0.8)), 1.0, left, 1);
+    assert!(router.set_bus_return(1, 0.5));
+    let mut reader = MeterReader::new(router.meters()).with_ballistics(MeterBallistics::INSTANT);
+
+    // the return gain glides over the first block
+    let mut output = vec![0.0; FRAMES * 2];
+    router.process(&mut output, None);
+    reader.read();
+    router.process(&mut output, None);
+    let snapshot = reader.read();
+
+    // sources post-fader and panned, as they enter their bus
+    let levels = snapshot.source(quiet).unwrap();
+    assert!(close(levels.peak[0], 0.25, 1e-5) && close(levels.rms[0], 0.25, 1e-5), "{levels:?}");
+    assert_eq!((levels.peak[1], levels.rms[1]), (0.0, 0.0));
+    assert!(close(snapshot.source(loud).unwrap().max_peak(), 0.8, 1e-5));
+    // buses before their return gain, master after
+    assert!(close(snapshot.buses[0].peak[0], 0.25, 1e-5));
+    assert!(close(snapshot.buses[1].rms[0], 0.8, 1e-5));
+    assert!(close(snapshot.master.peak[0], 0.25 + 0.4, 1e-5));
+    assert!(close(snapshot.master.max_rms(), 0.65, 1e-5));
+
+    // read again with nothing processed: silence
+    assert_eq!(reader.read().master.max_peak(), 0.0);
+
+    // gone sources drop out of the snapshot
+    assert!(router.remove(quiet));
+    router.process(&mut output, None);
+    let snapshot = reader.read();
+    assert!(snapshot.source(quiet).is_none());
+    assert_eq!(snapshot.sources.len(), 1);
+}
+
+#[test]
+fn peaks_between_reads_are_kept_and_rms_spans_them() {
+    let mut router = router(2, FRAMES);
+    let centre = Pan { value: 0.0, law: PanLaw::Linear };
+    let id = router.add_source(Box::new(Square(1.0)), 1.0, centre, 0);
+    let meters = router.meters();
+    let mut output = vec![0.0; FRAMES * 2];
+    router.process(&mut output, None);
+    // quieter blocks after the loud one
+    router.commands().set_gain(id, 0.0);
+    for _ in 0..3 {
+        router.process(&mut output, None);
+    }
+    let levels = meters.take().master;
+    assert!(close(levels.peak[0], 0.5, 1e-5), "{levels:?}");
+    // a quarter of the frames at 0.5: RMS 0.25
+    assert!(close(levels.rms[0], 0.25, 1e-5), "{levels:?}");
+}
+
+#[test]
+fn ballistics_smooth_on_the_read_side() {
+    let mut router = router(2, FRAMES);
+    let centre = Pan { value: 0.0, law: PanLaw::Linear };
+    let id = router.add_source(Box::new(Square(1.0)), 1.0, centre, 0);
+    let ballistics = MeterBallistics { attack_ms: 10.0, release_ms: 100.0 };
+    let mut reader = MeterReader::new(router.meters()).with_ballistics(ballistics);
+    assert_eq!(reader.ballistics(), ballistics);
+    let mut output = vec![0.0; FRAMES * 2];
+
+    // the first read shows what was measured
+    router.process(&mut output, None);
+    assert!(close(reader.read_after(Duration::ZERO).master.peak[0], 0.5, 1e-5));
+
+    // falling: one time constant takes it 63% of the way down
+    router.commands().set_gain(id, 0.0);
+    router.process(&mut output, None);
+    let peak = reader.read_after(Duration::from_millis(100)).master.peak[0];
+    assert!(close(peak, 0.5 * (-1.0f32).exp(), 1e-5), "{peak}");
+
+    // rising is faster: the same share of the way back up in a tenth of the time
+    router.commands().set_gain(id, 1.0);
+    router.process(&mut output, None);
+    let peak = reader.read_after(Duration::from_millis(10)).source(id).unwrap().peak[1];
+    let low = 0.5 * (-1.0f32).exp();
+    assert!(close(peak, 0.5 - (0.5 - low) * (-1.0f32).exp(), 1e-5), "{peak}");
+}
+
+#[test]
+fn engines_hand_out_their_router_meters() {
+    let mut engine = Engine::new();
+    assert!(engine.meters().is_err());
+    engine.configure(EngineConfig::default()).unwrap();
+    let meters = engine.meters().unwrap();
+    assert_eq!(meters.take().buses.len(), EngineConfig::default().num_buses.max(1));
+}
+
+#[test]
+fn the_output_meter_follows_the_last_block() {
+    let mut engine = Engine::new();
+    let config = EngineConfig::default();
+    engine.configure(config).unwrap();
+    engine.start().unwrap();
+    let square = Box::new(Square(0.5));
+    engine.with_processor(|processor| processor.router_mut().add_source(square, 1.0, CENTRE, 0)).unwrap();
+    let handle = engine.audio_handle().unwrap();
+    let mut output = vec![0.0; 256 * config.channels];
+    for _ in 0..8 {
+        assert!(handle.process(&mut output));
+    }
+
+    let reading = engine.meter_handle().load();
+    assert_eq!(reading.channels, config.channels);
+    for ch in 0..config.channels {
+        assert!(close(reading.peak[ch], 0.25, 1e-5), "{reading:?}");
+        assert!(close(reading.rms[ch], 0.25, 1e-5), "{reading:?}");
+    }
+    engine.stop().unwrap();
+    assert_eq!(engine.meter_handle().load(), Default::default());
+}
@@ -0,0 +1,480 @@
+//! MIDI from raw bytes to sounding voices: parsing, the queue to the audio
+//! thread, the allocator's note, pedal and bend handling, and controller
+//! decoding and smoothing, MIDI 2.0 packets, and chord mode. No MIDI hardware is
+//! involved; the tests push into the queue as an input port would.
+
+use std::sync::Arc;
+
+use spin::Mutex;
+
+use pulsar_backend::midi::{
+    Controller, ControllerDecoder, ControllerSmoothing, ControllerValue, MidiEvent, MidiMessage, MidiQueue,
+    MidiVoiceAllocator, ump,
+};
+use pulsar_backend::rt_processing::chords::{ChordShape, Strum, StrumDirection};
+use pulsar_backend::rt_processing::notes::{NoteExpression, Scale, ScaleMode, SharedScale};
+use pulsar_backend::rt_processing::routing::AudioSource;
+use pulsar_backend::rt_processing::voices::{Voice, VoicePool};
+
+const FRAMES: usize = 64;
+
+/// What the voices have been told, shared with the test
+#[derive(Default)]
+struct Log {
+    held: Vec<u8>,
+    // (note, velocity) of every note-on
+    started: Vec<(u8, f32)>,
+    bend: f32,
+    controllers: Vec<(u8, f32)>,
+    nrpn: Vec<(u16, f32)>,
+    // (note, pressure)
+    pressure: Vec<(u8, f32)>,
+    // (note, expression, value), other than pressure and neutral values
+    expression: Vec<(u8, NoteExpression, f32)>,
+}
+
+/// Sounds while a note is held; releases instantly
+struct LoggingVoice {
+    log: Arc<Mutex<Log>>,
+    note: Option<u8>,
+}
+
+impl Voice for LoggingVoice {
+    fn note_on(&mut self, note: u8, velocity: f32) {
+        self.note = Some(note);
+        let mut log = self.log.lock();
+        log.held.push(note);
+        log.started.push((note, velocity));
+    }
+
+    fn note_off(&mut self) {
+        if let Some(note) = self.note.take() {
+            self.log.lock().held.retain(|&n| n != note);
+        }
+    }
+
+    fn pitch_bend(&mut self, semitones: f32) {
+        self.log.lock().bend = semitones;
+    }
+
+    fn control_change(&mut self, controller: u8, value: f32) {
+        self.log.lock().controllers.push((controller, value));
+    }
+
+    fn nrpn(&mut self, parameter: u16, value: f32) {
+        self.log.lock().nrpn.push((parameter, value));
+    }
+
+    fn expression(&mut self, expression: NoteExpression, value: f32) {
+        // skip the neutral values set before each note
+        match (expression, self.note) {
+            (NoteExpression::Pressure, Some(note)) if value > 0.0 => self.log.lock().pressure.push((note, value)),
+            (NoteExpression::Pressure, _) => {}
+            (_, Some(note)) if value != expression.neutral() => {
+                self.log.lock().expression.push((note, expression, value))
+            }
+            _ => {}
+        }
+    }
+
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        let level = if self.note.is_some() { 0.1 } else { 0.0 };
+        for channel in output.iter_mut() {
+            channel[..frames].fill(level);
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.note.is_some()
+    }
+
+    fn reset(&mut self) {
+        self.note_off();
+    }
+}
+
+fn allocator(voices: usize) -> (MidiQueue, MidiVoiceAllocator, Arc<Mutex<Log>>) {
+    let log = Arc::new(Mutex::new(Log::default()));
+    let voices = (0..voices)
+        .map(|_| Box::new(LoggingVoice { log: Arc::clone(&log), note: None }) as Box<dyn Voice>)
+        .collect();
+    let pool = VoicePool::new(voices, 1, FRAMES).with_steal_fade(0.0);
+    let queue = MidiQueue::new(16);
+    (queue.clone(), MidiVoiceAllocator::new(queue, pool), log)
+}
+
+fn render(allocator: &mut MidiVoiceAllocator) -> f32 {
+    let mut buffer = [0.0; FRAMES];
+    allocator.render(&mut [&mut buffer[..]], FRAMES, 48_000.0);
+    buffer[FRAMES - 1]
+}
+
+fn held(log: &Arc<Mutex<Log>>) -> Vec<u8> {
+    let mut held = log.lock().held.clone();
+    held.sort();
+    held
+}
+
+#[test]
+fn parses_channel_messages() {
+    assert_eq!(
+        MidiMessage::parse(&[0x93, 60, 127]),
+        Some(MidiMessage::NoteOn { channel: 3, note: 60, velocity: 1.0 })
+    );
+    assert_eq!(
+        MidiMessage::parse(&[0x80, 60, 0]),
+        Some(MidiMessage::NoteOff { channel: 0, note: 60, velocity: 0.0 })
+    );
+    // velocity 0 is a note-off with the default release velocity
+    assert_eq!(
+        MidiMessage::parse(&[0x9F, 61, 0]),
+        Some(MidiMessage::NoteOff { channel: 15, note: 61, velocity: 64.0 / 127.0 })
+    );
+    assert_eq!(
+        MidiMessage::parse(&[0xB1, 64, 127]),
+        Some(MidiMessage::ControlChange { channel: 1, controller: 64, value: 127 })
+    );
+    assert_eq!(MidiMessage::parse(&[0xE0, 0x00, 0x40]), Some(MidiMessage::PitchBend { channel: 0, value: 0.0 }));
+    assert_eq!(MidiMessage::parse(&[0xE0, 0x00, 0x00]), Some(MidiMessage::PitchBend { channel: 0, value: -1.0 }));
+    let Some(MidiMessage::PitchBend { value, .. }) = MidiMessage::parse(&[0xE0, 0x7F, 0x7F]) else { panic!() };
+    assert!(value > 0.999 && value < 1.0);
+
+    assert_eq!(
+        MidiMessage::parse(&[0xA2, 60, 127]),
+        Some(MidiMessage::PolyAftertouch { channel: 2, note: 60, pressure: 1.0 })
+    );
+
+    // program change, clock, truncated and data-only messages aren't played
+    assert_eq!(MidiMessage::parse(&[0xC0, 5]), None);
+    assert_eq!(MidiMessage::parse(&[0xF8]), None);
+    assert_eq!(MidiMessage::parse(&[0x90, 60]), None);
+    assert_eq!(MidiMessage::parse(&[0x40, 60, 100]), None);
+}
+
+#[test]
+fn queue_drops_and_counts_when_full() {
+    let queue = MidiQueue::new(2);
+    let note = MidiMessage::NoteOn { channel: 0, note: 60, velocity: 1.0 };
+    assert!(queue.send(note) && queue.send(note));
+    assert!(!queue.push(MidiEvent { timestamp_us: 5, message: note }));
+    assert_eq!((queue.len(), queue.dropped()), (2, 1));
+    assert_eq!(queue.pop().map(|e| e.message), Some(note));
+}
+
+#[test]
+fn notes_play_from_the_queue() {
+    let (queue, mut allocator, log) = allocator(4);
+    assert_eq!(render(&mut allocator), 0.0);
+
+    for bytes in [[0x90, 60, 100], [0x90, 64, 100], [0x90, 67, 100]] {
+        assert!(queue.send(MidiMessage::parse(&bytes).unwrap()));
+    }
+    // nothing reaches the voices until the next block
+    assert!(held(&log).is_empty());
+    assert!((render(&mut allocator) - 0.3).abs() < 1e-6);
+    assert_eq!(held(&log), [60, 64, 67]);
+    assert_eq!(allocator.pool().active_voices(), 3);
+
+    queue.send(MidiMessage::parse(&[0x80, 64, 0]).unwrap());
+    render(&mut allocator);
+    assert_eq!(held(&log), [60, 67]);
+
+    // two more than the free voices: the oldest note is stolen
+    queue.send(MidiMessage::parse(&[0x90, 70, 100]).unwrap());
+    queue.send(MidiMessage::parse(&[0x90, 72, 100]).unwrap());
+    queue.send(MidiMessage::parse(&[0x90, 74, 100]).unwrap());
+    render(&mut allocator);
+    assert_eq!(held(&log), [67, 70, 72, 74]);
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn sustain_pedal_holds_released_notes() {
+    let (queue, mut allocator, log) = allocator(4);
+    for bytes in [[0x90, 60, 100], [0xB0, 64, 127], [0x80, 60, 0], [0x90, 62, 100], [0x80, 62, 0]] {
+        queue.send(MidiMessage::parse(&bytes).unwrap());
+    }
+    render(&mut allocator);
+    assert_eq!(held(&log), [60, 62]);
+
+    // a note struck again under the pedal takes a second voice, and both
+    // outlast the pedal while the key is down
+    queue.send(MidiMessage::parse(&[0x90, 60, 100]).unwrap());
+    queue.send(MidiMessage::parse(&[0xB0, 64, 0]).unwrap());
+    render(&mut allocator);
+    assert_eq!(held(&log), [60, 60]);
+
+    queue.send(MidiMessage::parse(&[0xB0, 123, 0]).unwrap());
+    render(&mut allocator);
+    assert!(held(&log).is_empty());
+    assert_eq!(render(&mut allocator), 0.0);
+}
+
+#[test]
+fn bend_controllers_and_channel_filter() {
+    let (queue, allocator, log) = allocator(2);
+    let mut allocator = allocator.with_channel(Some(1)).with_bend_range(12.0);
+
+    queue.send(MidiMessage::parse(&[0xE1, 0x00, 0x60]).unwrap());
+    queue.send(MidiMessage::parse(&[0xB1, 1, 127]).unwrap());
+    // other channels are ignored
+    queue.send(MidiMessage::parse(&[0x90, 60, 100]).unwrap());
+    queue.send(MidiMessage::parse(&[0xE0, 0x00, 0x00]).unwrap());
+    render(&mut allocator);
+    {
+        let log = log.lock();
+        assert_eq!(log.bend, 6.0);
+        assert!(log.held.is_empty());
+        // every voice hears the controller
+        assert_eq!(log.controllers, [(1, 1.0), (1, 1.0)]);
+    }
+
+    queue.send(MidiMessage::parse(&[0xB1, 121, 0]).unwrap());
+    render(&mut allocator);
+    assert_eq!(log.lock().bend, 0.0);
+}
+
+#[test]
+fn decodes_fourteen_bit_pairs_and_parameter_numbers() {
+    let mut decoder = ControllerDecoder::new();
+    // 7-bit until an LSB has been seen
+    let coarse = decoder.feed(0, 1, 127).unwrap();
+    assert_eq!((coarse.controller, coarse.value, coarse.raw), (Controller::Cc(1), 1.0, 127));
+    let fine = decoder.feed(0, 33, 0x7F).unwrap();
+    assert_eq!((fine.controller, fine.raw, fine.value), (Controller::Cc(1), 16_383, 1.0));
+    // from then on the MSB is the top of a 14-bit value
+    assert_eq!(decoder.feed(0, 1, 64).unwrap().raw, 64 << 7);
+    assert_eq!(decoder.feed(0, 33, 1).unwrap().raw, 64 << 7 | 1);
+    // pairing is per channel
+    assert_eq!(decoder.feed(1, 1, 64).unwrap().raw, 64);
+
+    // NRPN 0x0102: select, then data entry MSB and LSB
+    assert_eq!(decoder.feed(0, 99, 0x02), None);
+    assert_eq!(decoder.feed(0, 98, 0x02), None);
+    let msb = decoder.feed(0, 6, 0x40).unwrap();
+    assert_eq!((msb.controller, msb.raw), (Controller::Nrpn(0x0102), 0x40 << 7));
+    assert_eq!(decoder.feed(0, 38, 0x10).unwrap().raw, 0x40 << 7 | 0x10);
+    // the null RPN ends data entry: CC 6 is a plain controller again
+    decoder.feed(0, 101, 0x7F);
+    decoder.feed(0, 100, 0x7F);
+    assert_eq!(decoder.feed(0, 6, 0x40).unwrap().controller, Controller::Cc(6));
+}
+
+#[test]
+fn controllers_are_smoothed_and_deadbanded() {
+    let (queue, allocator, log) = allocator(1);
+    let smoothing = ControllerSmoothing { time: 0.005, deadband: 2.0 / 127.0 };
+    let mut allocator = allocator.with_controller_smoothing(smoothing);
+
+    // the first value applies at once
+    queue.send(MidiMessage::parse(&[0xB0, 7, 0]).unwrap());
+    render(&mut allocator);
+    assert_eq!(log.lock().controllers, [(7, 0.0)]);
+
+    // a jump glides over several blocks without overshooting
+    queue.send(MidiMessage::parse(&[0xB0, 7, 127]).unwrap());
+    for _ in 0..60 {
+        render(&mut allocator);
+    }
+    let values: Vec<f32> = log.lock().controllers.iter().map(|&(_, v)| v).collect();
+    assert!(values.len() > 3, "{:?}", values);
+    assert!(values.windows(2).all(|w| w[1] > w[0] && w[1] <= 1.0), "{:?}", values);
+    assert_eq!(values.last(), Some(&1.0));
+
+    // one-step jitter stays inside the deadband
+    let before = log.lock().controllers.len();
+    queue.send(MidiMessage::parse(&[0xB0, 7, 126]).unwrap());
+    render(&mut allocator);
+    assert_eq!(log.lock().controllers.len(), before);
+}
+
+#[test]
+fn aftertouch_nrpn_and_bend_range() {
+    let (queue, mut allocator, log) = allocator(2);
+    for bytes in [[0x90, 60, 100], [0x90, 64, 100], [0xA0, 64, 127], [0xA0, 70, 127]] {
+        queue.send(MidiMessage::parse(&bytes).unwrap());
+    }
+    render(&mut allocator);
+    // only the voice on the key feels the pressure
+    assert_eq!(log.lock().pressure, [(64, 1.0)]);
+
+    for bytes in [[0xB0, 99, 0], [0xB0, 98, 5], [0xB0, 6, 127], [0xB0, 38, 127]] {
+        queue.send(MidiMessage::parse(&bytes).unwrap());
+    }
+    render(&mut allocator);
+    assert_eq!(log.lock().nrpn.last(), Some(&(5, 1.0)));
+
+    // RPN 0: 12 semitones 50 cents, applied to the bend already held
+    queue.send(MidiMessage::parse(&[0xE0, 0x00, 0x60]).unwrap());
+    for bytes in [[0xB0, 101, 0], [0xB0, 100, 0], [0xB0, 6, 12], [0xB0, 38, 50]] {
+        queue.send(MidiMessage::parse(&bytes).unwrap());
+    }
+    render(&mut allocator);
+    assert_eq!(allocator.bend_range(), 12.5);
+    assert_eq!(log.lock().bend, 6.25);
+}
+
+#[test]
+fn parses_universal_midi_packets() {
+    // MIDI 1.0 in a packet: group 0, note-on channel 3
+    assert_eq!(ump::parse(&[0x2093_3C7F]), Some(MidiMessage::NoteOn { channel: 3, note: 60, velocity: 1.0 }));
+
+    // MIDI 2.0: 16-bit velocity, and velocity 0 is still a note-on
+    let note_on = ump::channel_voice(5, 0x9, 1, 60, 0);
+    assert_eq!(
+        ump::parse(&[note_on, 0x8000_0000]),
+        Some(MidiMessage::NoteOn { channel: 1, note: 60, velocity: 0x8000 as f32 / 65_535.0 })
+    );
+    assert!(matches!(ump::parse(&[note_on, 0]), Some(MidiMessage::NoteOn { velocity: 0.0, .. })));
+    assert_eq!(
+        ump::parse(&[ump::channel_voice(0, 0xB, 2, 74, 0), u32::MAX]),
+        Some(MidiMessage::Controller {
+            channel: 2,
+            value: ControllerValue { controller: Controller::Cc(74), value: 1.0, raw: 16_383 },
+        })
+    );
+    // RPN 0 in one message: 12 semitones 50 cents at the top of the word
+    let Some(MidiMessage::Controller { value, .. }) =
+        ump::parse(&[ump::channel_voice(0, 0x2, 0, 0, 0), 12 << 25 | 50 << 18])
+    else {
+        panic!()
+    };
+    assert_eq!((value.controller, value.raw), (Controller::Rpn(0), 12 << 7 | 50));
+    assert_eq!(
+        ump::parse(&[ump::channel_voice(0, 0xE, 0, 0, 0), 0x8000_0000]),
+        Some(MidiMessage::PitchBend { channel: 0, value: 0.0 })
+    );
+    assert_eq!(
+        ump::parse(&[ump::channel_voice(0, 0x0, 0, 60, ump::PER_NOTE_PITCH), 61 << 25 | 1 << 24]),
+        Some(MidiMessage::PerNotePitch { channel: 0, note: 60, pitch: 61.5 })
+    );
+    // program change, a system packet and a truncated packet aren't played
+    assert_eq!(ump::parse(&[ump::channel_voice(0, 0xC, 0, 5, 0), 0]), None);
+    assert_eq!(ump::parse(&[0x10F8_0000]), None);
+    assert_eq!(ump::parse(&[note_on]), None);
+
+    // a stream mixing packet sizes; the partial packet at the end is left out
+    let queue = MidiQueue::new(8);
+    assert_eq!(queue.push_ump(7, &[0x2093_3C7F, 0x10F8_0000, note_on, 0xFFFF_0000, note_on]), 2);
+    assert_eq!(queue.pop().map(|e| e.timestamp_us), Some(7));
+    assert_eq!(queue.len(), 1);
+}
+
+#[test]
+fn midi2_messages_play_at_full_resolution() {
+    let (queue, mut allocator, log) = allocator(2);
+    let controller = |index: u8, data: u32| [ump::channel_voice(0, 0xB, 0, index, 0), data];
+    queue.push_ump(0, &[ump::channel_voice(0, 0x9, 0, 60, 0), 0xFFFF_0000]);
+    queue.push_ump(0, &[ump::channel_voice(0, 0x9, 0, 64, 0), 0xFFFF_0000]);
+    // per-note bend up half the 48 semitone range; brightness on the other key
+    queue.push_ump(0, &[ump::channel_voice(0, 0x6, 0, 64, 0), 0xC000_0000]);
+    queue.push_ump(0, &[ump::channel_voice(0, 0x0, 0, 60, 74), u32::MAX]);
+    queue.push_ump(0, &controller(7, u32::MAX / 4));
+    render(&mut allocator);
+    assert_eq!(
+        log.lock().expression,
+        [(64, NoteExpression::PitchOffset, 24.0), (60, NoteExpression::Brightness, 1.0)]
+    );
+    let (cc, value) = *log.lock().controllers.last().unwrap();
+    assert_eq!(cc, 7);
+    assert!((value - 0.25).abs() < 1e-6);
+
+    // the sustain pedal acts as it does in MIDI 1.0
+    queue.push_ump(0, &controller(64, u32::MAX));
+    queue.push_ump(0, &[ump::channel_voice(0, 0x8, 0, 60, 0), 0]);
+    render(&mut allocator);
+    assert_eq!(held(&log), [60, 64]);
+    queue.push_ump(0, &controller(64, 0));
+    render(&mut allocator);
+    assert_eq!(held(&log), [64]);
+
+    // absolute per-note pitch
+    queue.push_ump(0, &[ump::channel_voice(0, 0x0, 0, 64, ump::PER_NOTE_PITCH), 63 << 25 | 1 << 23]);
+    render(&mut allocator);
+    assert_eq!(log.lock().expression.last(), Some(&(64, NoteExpression::PitchOffset, -0.75)));
+}
+
+#[test]
+fn notes_follow_a_shared_scale() {
+    let (queue, allocator, log) = allocator(4);
+    let scale = SharedScale::new(Some(Scale::new(0, ScaleMode::Major)));
+    let mut allocator = allocator.with_scale(scale.clone());
+    for bytes in [[0x90, 61, 100], [0x90, 64, 100]] {
+        queue.send(MidiMessage::parse(&bytes).unwrap());
+    }
+    render(&mut allocator);
+    assert_eq!(held(&log), [60, 64]);
+
+    // a note-off finds the note its key started, though the scale has moved
+    // (D flat major; E is as near E flat as F, so takes the lower)
+    scale.set(Some(Scale::new(1, ScaleMode::Major)));
+    queue.send(MidiMessage::parse(&[0x80, 61, 0]).unwrap());
+    queue.send(MidiMessage::parse(&[0x90, 52, 100]).unwrap());
+    render(&mut allocator);
+    assert_eq!(held(&log), [51, 64]);
+
+    scale.set(None);
+    queue.send(MidiMessage::parse(&[0x90, 61, 100]).unwrap());
+    queue.send(MidiMessage::parse(&[0x80, 52, 0]).unwrap());
+    render(&mut allocator);
+    assert_eq!(held(&log), [61, 64]);
+}
+
+#[test]
+fn chord_mode_plays_a_stored_shape() {
+    let (queue, allocator, log) = allocator(8);
+    let mut allocator = allocator.with_chord(Some(ChordShape::new(&[7, 0, 4, 4])), Strum::default());
+    queue.send(MidiMessage::parse(&[0x90, 60, 100]).unwrap());
+    render(&mut allocator);
+    assert_eq!(held(&log), [60, 64, 67]);
+    // per-note messages for the key reach the whole chord
+    queue.send(MidiMessage::PolyAftertouch { channel: 0, note: 60, pressure: 0.5 });
+    render(&mut allocator);
+    assert_eq!(log.lock().pressure.len(), 3);
+    queue.send(MidiMessage::parse(&[0x80, 60, 0]).unwrap());
+    render(&mut allocator);
+    assert!(held(&log).is_empty());
+
+    // a learnt shape, kept in the scale: diatonic triads
+    let shape = ChordShape::from_notes(&[69, 62, 65]);
+    assert_eq!(shape.intervals(), [0, 3, 7]);
+    allocator.set_chord(Some(ChordShape::new(&[0, 4, 7])));
+    allocator.set_scale(SharedScale::new(Some(Scale::new(0, ScaleMode::Major))));
+    queue.send(MidiMessage::parse(&[0x90, 62, 100]).unwrap());
+    render(&mut allocator);
+    assert_eq!(held(&log), [62, 65, 69]);
+    queue.send(MidiMessage::parse(&[0x80, 62, 0]).unwrap());
+    allocator.set_chord(None);
+    queue.send(MidiMessage::parse(&[0x90, 64, 100]).unwrap());
+    render(&mut allocator);
+    assert_eq!(held(&log), [64]);
+}
+
+#[test]
+fn strummed_chords_start_each_note_on_its_frame() {
+    let (queue, allocator, log) = allocator(8);
+    // 3 ms apart at 48 kHz: 144 frames, inside the third block
+    let strum = Strum { delay: 0.003, velocity_step: -0.25, direction: StrumDirection::Down };
+    let mut allocator = allocator.with_chord(Some(ChordShape::new(&[0, 4, 7])), strum);
+    queue.send(MidiMessage::parse(&[0x90, 60, 127]).unwrap());
+    let mut output = vec![0.0; 6 * FRAMES];
+    for block in output.chunks_mut(FRAMES) {
+        allocator.render(&mut [block], FRAMES, 48_000.0);
+    }
+    assert!((output[143] - 0.1).abs() < 1e-6);
+    assert!((output[144] - 0.2).abs() < 1e-6);
+    assert!((output[287] - 0.2).abs() < 1e-6);
+    assert!((output[288] - 0.3).abs() < 1e-6);
+    assert_eq!(log.lock().started, [(67, 1.0), (64, 0.75), (60, 0.5)]);
+
+    // releasing the key drops the notes not yet strummed
+    queue.send(MidiMessage::parse(&[0x80, 60, 0]).unwrap());
+    queue.send(MidiMessage::parse(&[0x90, 48, 127]).unwrap());
+    render(&mut allocator);
+    queue.send(MidiMessage::parse(&[0x80, 48, 0]).unwrap());
+    for _ in 0..8 {
+        render(&mut allocator);
+    }
+    assert!(held(&log).is_empty());
+    assert_eq!(log.lock().started.len(), 4);
+}
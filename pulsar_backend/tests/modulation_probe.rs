@@ -0,0 +1,104 @@
+//! Modulation probes: modulation sources played as audio on a bus, and their
+//! control updates on a scope tap.
+
+mod common;
+
+use pulsar_backend::rt_processing::modulation::{ModulationMonitor, ModulationProbe, ModulationSource, ScopeTap};
+use pulsar_backend::rt_processing::routing::{AudioSource, Pan, PanLaw};
+use pulsar_backend::rt_processing::waveform::envelopes::ADSREnvelope;
+use pulsar_backend::rt_processing::waveform::oscillators::LFO;
+use pulsar_backend::rt_processing::waveform::tables::WaveformType;
+
+use common::router;
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const FRAMES: usize = 256;
+
+/// Both channels of one block
+fn render(probe: &mut ModulationProbe) -> (Vec<f32>, Vec<f32>) {
+    let (mut left, mut right) = (vec![0.0; FRAMES], vec![0.0; FRAMES]);
+    probe.render(&mut [&mut left, &mut right], FRAMES, SAMPLE_RATE);
+    (left, right)
+}
+
+#[test]
+fn probes_play_the_signal_a_node_would_see() {
+    // at audio rate, exactly the LFO's samples
+    let lfo = || LFO::new(WaveformType::Sine, 50.0).with_depth(0.5);
+    let mut probe = ModulationProbe::new(lfo()).with_interval(1);
+    let (left, right) = render(&mut probe);
+    let mut reference = lfo();
+    let expected: Vec<f32> = (0..FRAMES).map(|_| reference.advance(1, SAMPLE_RATE)).collect();
+    assert_eq!(left, expected);
+    assert_eq!(right, expected);
+    assert!(left.iter().any(|&s| s > 0.4));
+
+    // at control rate, every update goes to the scope
+    let scope = ScopeTap::new(64);
+    let mut probe = ModulationProbe::new(lfo()).with_scope(scope.clone());
+    let (left, _) = render(&mut probe);
+    let mut values = Vec::new();
+    assert_eq!(scope.drain_into(&mut values), FRAMES / 32);
+    let mut reference = lfo();
+    let expected: Vec<f32> =
+        (0..FRAMES / 32).map(|_| ModulationSource::advance(&mut reference, 32, SAMPLE_RATE)).collect();
+    assert_eq!(values, expected);
+    // ramped between updates, one interval behind
+    assert_eq!(left[31], expected[0]);
+    assert!((left[63] - expected[1]).abs() < 1e-6);
+    assert!(scope.is_empty());
+
+    // a full scope keeps the newest values
+    let scope = ScopeTap::new(4);
+    let mut probe = ModulationProbe::new(lfo()).with_scope(scope.clone());
+    render(&mut probe);
+    assert_eq!((scope.len(), scope.overwritten()), (4, 4));
+}
+
+#[test]
+fn envelopes_and_monitored_params_are_sources() {
+    let mut envelope = ADSREnvelope::new(0.01, 0.1, 0.5, 0.1);
+    envelope.note_on();
+    let mut probe = ModulationProbe::new(envelope).with_interval(1);
+    let (attack, _) = render(&mut probe);
+    assert!(attack.windows(2).all(|pair| pair[1] >= pair[0]));
+    assert!(attack[FRAMES - 1] > 0.4);
+
+    let monitor = ModulationMonitor::new(&["rate", "depth"]);
+    monitor.publish(1, 0.5, 0.25);
+    let param = monitor.param("depth").unwrap();
+    assert_eq!(param.name(), "depth");
+    assert!(monitor.param("mix").is_none());
+    let mut probe = ModulationProbe::new(param).with_interval(1);
+    assert!(render(&mut probe).0.iter().all(|&s| s == 0.25));
+    monitor.publish(1, 0.5, -0.5);
+    assert!(render(&mut probe).0.iter().all(|&s| s == -0.5));
+}
+
+#[test]
+fn probes_route_to_a_bus_or_only_to_the_scope() {
+    let mut router = router(3, FRAMES);
+    let centre = Pan { value: 0.0, law: PanLaw::Linear };
+    let monitor = ModulationMonitor::new(&["offset"]);
+    monitor.publish(0, 0.0, 0.5);
+    let scope = ScopeTap::new(256);
+    let probe = ModulationProbe::new(monitor.param("offset").unwrap()).with_scope(scope.clone());
+    let id = router.add_source(Box::new(probe), 1.0, centre, 2);
+
+    // heard on bus 2
+    let mut output = vec![0.0; FRAMES * 2];
+    router.process(&mut output, None);
+    let (left, _) = centre.gains();
+    assert!((output[(FRAMES - 1) * 2] - 0.5 * left).abs() < 1e-6);
+
+    // only watched: the bus doesn't return, the scope still fills
+    assert!(router.set_bus_return(2, 0.0));
+    for _ in 0..2 {
+        router.process(&mut output, None);
+    }
+    assert!(output.iter().all(|&s| s == 0.0));
+    let mut values = Vec::new();
+    assert_eq!(scope.drain_into(&mut values), 3 * FRAMES / 32);
+    assert!(values.iter().all(|&v| v == 0.5));
+    assert!(router.remove(id));
+}
@@ -0,0 +1,125 @@
+//! Notes addressed by id rather than key: two notes on one key are released
+//! and expressed independently, and expression reaches a note whether it is
+//! playing or still waiting for a stolen voice to fade.
+
+use std::sync::Arc;
+
+use spin::Mutex;
+
+use pulsar_backend::plugins::clap;
+use pulsar_backend::rt_processing::notes::{NoteExpression, NoteId, NoteQueue};
+use pulsar_backend::rt_processing::routing::AudioSource;
+use pulsar_backend::rt_processing::voices::{NotePlayer, Voice, VoicePool};
+
+const FRAMES: usize = 64;
+
+/// Key held and expression of each voice, by voice index
+type Log = Arc<Mutex<Vec<(Option<u8>, [f32; NoteExpression::COUNT])>>>;
+
+/// Sounds while a note is held; releases instantly
+struct ExpressiveVoice {
+    index: usize,
+    log: Log,
+}
+
+impl Voice for ExpressiveVoice {
+    fn note_on(&mut self, note: u8, _velocity: f32) {
+        self.log.lock()[self.index].0 = Some(note);
+    }
+
+    fn note_off(&mut self) {
+        self.log.lock()[self.index].0 = None;
+    }
+
+    fn expression(&mut self, expression: NoteExpression, value: f32) {
+        self.log.lock()[self.index].1[expression.index()] = value;
+    }
+
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        let level = if self.is_active() { 0.1 } else { 0.0 };
+        for channel in output.iter_mut() {
+            channel[..frames].fill(level);
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.log.lock()[self.index].0.is_some()
+    }
+
+    fn reset(&mut self) {
+        self.note_off();
+    }
+}
+
+fn player(voices: usize, steal_fade: f32) -> (NoteQueue, NotePlayer, Log) {
+    let log: Log = Arc::new(Mutex::new(vec![(None, [0.0; NoteExpression::COUNT]); voices]));
+    let voices = (0..voices)
+        .map(|index| Box::new(ExpressiveVoice { index, log: Arc::clone(&log) }) as Box<dyn Voice>)
+        .collect();
+    let pool = VoicePool::new(voices, 1, FRAMES).with_steal_fade(steal_fade);
+    let queue = NoteQueue::new(16);
+    (queue.clone(), NotePlayer::new(queue, pool), log)
+}
+
+fn render(player: &mut NotePlayer) {
+    let mut buffer = [0.0; FRAMES];
+    player.render(&mut [&mut buffer[..]], FRAMES, 48_000.0);
+}
+
+#[test]
+fn notes_on_one_key_are_addressed_by_id() {
+    let (queue, mut player, log) = player(2, 0.0);
+    let (a, b) = (NoteId(7), NoteId(8));
+    assert!(queue.note_on(a, 60, 1.0) && queue.note_on(b, 60, 1.0));
+    assert!(queue.expression(b, NoteExpression::PitchOffset, 0.5));
+    assert!(queue.expression(a, NoteExpression::Pressure, 2.0));
+    assert!(queue.expression(NoteId(99), NoteExpression::Pressure, 0.3));
+    render(&mut player);
+    {
+        let log = log.lock();
+        // fresh notes start neutral; pressure is clamped; unknown ids are ignored
+        assert_eq!(log[0], (Some(60), [0.0, 1.0, 0.5]));
+        assert_eq!(log[1], (Some(60), [0.5, 0.0, 0.5]));
+    }
+
+    queue.note_off(a);
+    queue.expression(b, NoteExpression::Brightness, 0.9);
+    render(&mut player);
+    {
+        let log = log.lock();
+        assert_eq!(log[0].0, None);
+        assert_eq!(log[1], (Some(60), [0.5, 0.0, 0.9]));
+    }
+    // a key-addressed note-off still releases every note on the key
+    player.pool_mut().note_off(60);
+    assert_eq!(player.pool().active_voices(), 0);
+    assert_eq!(queue.dropped(), 0);
+}
+
+#[test]
+fn expression_waits_with_a_note_for_a_stolen_voice() {
+    let (queue, mut player, log) = player(1, 0.01);
+    queue.note_on(NoteId(1), 60, 1.0);
+    queue.expression(NoteId(1), NoteExpression::Pressure, 0.8);
+    render(&mut player);
+    assert_eq!(log.lock()[0], (Some(60), [0.0, 0.8, 0.5]));
+
+    // the only voice fades out before the new note takes it over
+    queue.note_on(NoteId(2), 64, 1.0);
+    queue.expression(NoteId(2), NoteExpression::PitchOffset, -1.5);
+    render(&mut player);
+    assert_eq!(log.lock()[0], (Some(60), [0.0, 0.8, 0.5]));
+    for _ in 0..8 {
+        render(&mut player);
+    }
+    assert_eq!(log.lock()[0], (Some(64), [-1.5, 0.0, 0.5]));
+}
+
+#[test]
+fn clap_expressions_map_onto_note_expression() {
+    assert_eq!(clap::note_expression(2), Some(NoteExpression::PitchOffset));
+    assert_eq!(clap::note_expression(5), Some(NoteExpression::Brightness));
+    assert_eq!(clap::note_expression(6), Some(NoteExpression::Pressure));
+    // volume has no per-note counterpart here
+    assert_eq!(clap::note_expression(0), None);
+}
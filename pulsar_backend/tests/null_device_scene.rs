@@ -0,0 +1,270 @@
+//! The whole stack on the null device: negotiation, stream, engine, sources,
+//! bus effects and a recording of the master, driven through a short scripted
+//! scene. The stream renders on the test's thread (`NullStream::render_blocks`)
+//! so every block is accounted for; what the device got is compared with what
+//! the recorder wrote and with the stream's performance counters.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use spin::Mutex;
+
+use pulsar_backend::audio_device::negotiation::{
+    BufferSizePriority, ChannelPriority, ConfigNegotiator, ConfigurationRequest, NegotiatedConfig,
+    SampleRatePriority,
+};
+use pulsar_backend::audio_device::null::{NullPacing, NullStream, null_device};
+use pulsar_backend::audio_device::stream::{StreamError, StreamState};
+use pulsar_backend::engine::{Engine, EngineConfig, EngineState};
+use pulsar_backend::io::recorder::Recorder;
+use pulsar_backend::io::wav::{self, SampleFormat, WavSpec};
+use pulsar_backend::project::{NodeDescriptor, SourceDescriptor};
+use pulsar_backend::rt_processing::callback::CallbackSlot;
+use pulsar_backend::rt_processing::prefault::PrefaultMode;
+use pulsar_backend::rt_processing::routing::PanLaw;
+
+const SAMPLE_RATE: u32 = 48_000;
+const CHANNELS: u16 = 2;
+const BLOCK: u32 = 256;
+/// Blocks per scene step, about 0.1 s
+const STEP_BLOCKS: usize = 20;
+
+fn negotiate(sample_rate: u32, resample: bool) -> NegotiatedConfig {
+    let request = ConfigurationRequest::new()
+        .with_sample_rate(sample_rate)
+        .with_sample_rate_priority(SampleRatePriority::Exact)
+        .with_channels(CHANNELS)
+        .with_channel_priority(ChannelPriority::Exact)
+        .with_buffer_size(BLOCK)
+        .with_buffer_size_priority(BufferSizePriority::Exact)
+        .allow_sample_rate_conversion(resample);
+    ConfigNegotiator::negotiate(&null_device(), &request).expect("null device negotiates")
+}
+
+/// Engine configured for `config` and running, with its audio handle in a slot
+fn running_engine(config: &NegotiatedConfig) -> (Engine, Arc<CallbackSlot>) {
+    let mut engine = Engine::new();
+    engine
+        .configure(EngineConfig {
+            sample_rate: config.engine_sample_rate as f32,
+            channels: config.channels as usize,
+            max_frames: 4096,
+            ..EngineConfig::default()
+        })
+        .unwrap();
+    engine.start().unwrap();
+    let handle = engine.audio_handle().unwrap();
+    let slot = CallbackSlot::new(Box::new(handle), config.engine_sample_rate as f32, config.channels as usize);
+    (engine, Arc::new(slot))
+}
+
+/// Collects everything the device is handed
+fn capture(stream: &mut NullStream) -> Arc<Mutex<Vec<f32>>> {
+    let rendered = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&rendered);
+    stream.on_output(move |block| sink.lock().extend_from_slice(block));
+    rendered
+}
+
+fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0f32, |peak, x| peak.max(x.abs()))
+}
+
+fn recording_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("pulsar-null-scene-{}-{}.wav", name, std::process::id()))
+}
+
+#[test]
+fn negotiation_fits_the_null_device() {
+    let config = negotiate(SAMPLE_RATE, false);
+    assert_eq!(config.sample_rate, SAMPLE_RATE);
+    assert!(!config.is_resampled());
+    assert!(config.sample_rate_matched && config.channels_matched && config.buffer_size_matched);
+    assert_eq!(config.sample_format, cpal::SampleFormat::F32);
+
+    // a slot at another rate is refused, as by a real stream
+    let slot = Arc::new(CallbackSlot::silent(44_100.0, CHANNELS as usize));
+    assert!(matches!(NullStream::new(config, slot), Err(StreamError::ConfigMismatch { .. })));
+}
+
+#[test]
+fn scripted_scene_renders_and_records() {
+    let config = negotiate(SAMPLE_RATE, false);
+    let (mut engine, slot) = running_engine(&config);
+    // the router's buffers were touched before the first callback
+    let prefault = engine.prefault_report().unwrap();
+    assert!(prefault.buffers > 0 && prefault.bytes >= 4096 * CHANNELS as usize * 4);
+    assert_eq!(prefault.locked_bytes, 0);
+    let mut stream = NullStream::new(config, slot).unwrap();
+    assert_eq!(stream.block_frames(), BLOCK as usize);
+    let rendered = capture(&mut stream);
+
+    // effects first: edits rebuild a bus chain and would drop the tap after them
+    engine
+        .add_bus_effect_node(0, NodeDescriptor::new("compressor").with_param("threshold_db", -12.0))
+        .unwrap();
+    engine
+        .add_bus_effect_node(1, NodeDescriptor::new("auto_pan").with_param("rate_hz", 2.0))
+        .unwrap();
+    let path = recording_path("scene");
+    let spec = WavSpec { sample_rate: SAMPLE_RATE, format: SampleFormat::Float32, dither: false };
+    let (tap, mut recorder) = Recorder::create(&path, CHANNELS as usize, spec).unwrap();
+    assert!(engine.with_processor(|p| p.router().add_bus_effect(0, Box::new(tap))).unwrap());
+
+    let step = |stream: &mut NullStream, recorder: &mut Recorder| {
+        let start = rendered.lock().len();
+        stream.render_blocks(STEP_BLOCKS);
+        recorder.drain().unwrap();
+        rendered.lock()[start..].to_vec()
+    };
+
+    // 1: nothing playing yet
+    let silence = step(&mut stream, &mut recorder);
+    assert_eq!(silence.len(), STEP_BLOCKS * BLOCK as usize * CHANNELS as usize);
+    assert_eq!(peak(&silence), 0.0);
+
+    // 2: a voice straight to master
+    engine
+        .add_source_node(
+            SourceDescriptor::new(
+                NodeDescriptor::new("oscillator").with_param("frequency", 220.0).with_param("amplitude", 0.5),
+            )
+            .with_gain(0.8),
+        )
+        .unwrap();
+    let one_voice = step(&mut stream, &mut recorder);
+    assert!(peak(&one_voice) > 0.1, "voice inaudible: peak {}", peak(&one_voice));
+
+    // 3: a second voice through the auto-pan bus, hard left
+    engine
+        .add_source_node(
+            SourceDescriptor::new(
+                NodeDescriptor::new("oscillator")
+                    .with_param("waveform", "square")
+                    .with_param("frequency", 330.0)
+                    .with_param("amplitude", 0.3),
+            )
+            .with_pan(-1.0, PanLaw::EqualPower)
+            .with_bus(1),
+        )
+        .unwrap();
+    let two_voices = step(&mut stream, &mut recorder);
+    assert!(peak(&two_voices) > peak(&one_voice));
+    assert!(peak(&two_voices) <= 1.0, "master clips: peak {}", peak(&two_voices));
+
+    // 4: suspended engines hand the device silence; resuming picks up again
+    engine.suspend().unwrap();
+    assert_eq!(peak(&step(&mut stream, &mut recorder)), 0.0);
+    engine.resume().unwrap();
+    assert_eq!(engine.state(), EngineState::Running);
+    assert!(peak(&step(&mut stream, &mut recorder)) > 0.1);
+
+    let blocks = 5 * STEP_BLOCKS as u64;
+    let frames = blocks * BLOCK as u64;
+    assert_eq!(stream.frames_rendered(), frames);
+    assert_eq!(stream.state(), StreamState::Stopped);
+    let stats = stream.monitor().snapshot(false);
+    assert_eq!(stats.callback_count, blocks);
+    assert_eq!(stats.frames_processed, frames);
+    assert!(stats.max_callback_nanos.is_some());
+    // the first callbacks are warm-up, timed but kept out of the statistics
+    let warmup = stream.monitor().warmup_callbacks();
+    assert!(warmup > 0 && warmup < blocks);
+    assert!(!stats.warmup.active);
+    assert_eq!(stats.warmup.callbacks, warmup);
+    assert_eq!(stats.window.callbacks, blocks - warmup);
+
+    // the master tap sat last in the chain, so the file holds exactly what the
+    // device got, minus the blocks rendered while suspended
+    let info = recorder.finish().unwrap();
+    assert_eq!(info.dropped_frames, 0);
+    assert_eq!(info.frames as u64, frames - (STEP_BLOCKS as u64 * BLOCK as u64));
+    let audio = wav::read_file(&info.path).unwrap();
+    assert_eq!(audio.frames(), info.frames);
+    let rendered = rendered.lock();
+    let per_step = STEP_BLOCKS * BLOCK as usize;
+    let played: Vec<f32> = rendered
+        .chunks(per_step * CHANNELS as usize)
+        .enumerate()
+        .filter(|&(step, _)| step != 3)
+        .flat_map(|(_, samples)| samples.iter().copied())
+        .collect();
+    for (frame, samples) in played.chunks_exact(CHANNELS as usize).enumerate() {
+        for (channel, &sample) in samples.iter().enumerate() {
+            assert_eq!(audio.channels[channel][frame], sample, "frame {} channel {}", frame, channel);
+        }
+    }
+
+    let _ = std::fs::remove_file(&info.path);
+    let _ = std::fs::remove_file(&info.peaks_path);
+}
+
+#[test]
+fn locked_memory_is_reported() {
+    let mut engine = Engine::new();
+    engine.configure(EngineConfig { prefault: PrefaultMode::Lock, ..EngineConfig::default() }).unwrap();
+    engine.start().unwrap();
+    // a low RLIMIT_MEMLOCK refuses some buffers; that's reported, not fatal
+    let report = engine.prefault_report().unwrap();
+    assert!(report.bytes > 0);
+    assert!(report.locked_bytes == report.bytes || report.lock_error.is_some(), "{:?}", report);
+    assert_eq!(engine.state(), EngineState::Running);
+
+    engine.stop().unwrap();
+    engine.configure(EngineConfig { prefault: PrefaultMode::Off, ..EngineConfig::default() }).unwrap();
+    engine.start().unwrap();
+    assert!(engine.prefault_report().is_none());
+}
+
+#[test]
+fn resampled_stream_counts_device_frames() {
+    // 16 kHz is below the device's range, so it runs at its closest rate and
+    // the engine's output is converted on the way
+    let config = negotiate(16_000, true);
+    assert!(config.is_resampled());
+    assert_eq!(config.engine_sample_rate, 16_000);
+    let device_rate = config.sample_rate;
+    let (mut engine, slot) = running_engine(&config);
+    let mut stream = NullStream::new(config, Arc::clone(&slot)).unwrap();
+    let rendered = capture(&mut stream);
+    engine.add_source_node(SourceDescriptor::new(NodeDescriptor::new("test_tone"))).unwrap();
+
+    let blocks = device_rate as usize / BLOCK as usize;
+    stream.render_blocks(blocks);
+    assert_eq!(stream.frames_rendered(), (blocks * BLOCK as usize) as u64);
+    assert!(peak(&rendered.lock()) > 0.1);
+    // about a second at the engine rate, give or take the resampler's buffering
+    let engine_frames = slot.frame_count() as i64;
+    let expected = (blocks * BLOCK as usize) as i64 * 16_000 / device_rate as i64;
+    assert!((engine_frames - expected).abs() <= 2 * BLOCK as i64, "{} vs {}", engine_frames, expected);
+}
+
+#[test]
+fn threaded_stream_runs_until_stopped() {
+    let config = negotiate(SAMPLE_RATE, false);
+    let (_engine, slot) = running_engine(&config);
+    let mut stream = NullStream::new(config, slot).unwrap().with_pacing(NullPacing::FreeRunning);
+
+    stream.start().unwrap();
+    assert!(stream.is_running());
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while stream.frames_rendered() < 100 * BLOCK as u64 && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    // blocks aren't stepped by hand while the thread owns the stream
+    stream.render_blocks(1);
+    stream.pause().unwrap();
+    assert_eq!(stream.state(), StreamState::Paused);
+    let paused_at = stream.frames_rendered();
+    assert!(paused_at >= 100 * BLOCK as u64);
+    assert_eq!(paused_at % BLOCK as u64, 0);
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(stream.frames_rendered(), paused_at);
+
+    stream.render_blocks(2);
+    assert_eq!(stream.frames_rendered(), paused_at + 2 * BLOCK as u64);
+    stream.stop();
+    assert_eq!(stream.state(), StreamState::Stopped);
+    assert_eq!(stream.monitor().snapshot(false).callback_count, stream.frames_rendered() / BLOCK as u64);
+}
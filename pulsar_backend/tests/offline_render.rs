@@ -0,0 +1,70 @@
+//! Rendering without a device: any `AudioCallback` bounced to memory or to a
+//! WAV file, block by block, as fast as the CPU allows.
+
+use std::time::Duration;
+
+use pulsar_backend::engine::{Engine, EngineConfig};
+use pulsar_backend::io::wav::{self, SampleFormat, WavSpec};
+use pulsar_backend::offline::{OFFLINE_BLOCK_FRAMES, render_offline, render_offline_to_file};
+use pulsar_backend::project::{NodeDescriptor, SourceDescriptor};
+use pulsar_backend::rt_processing::callback::AudioCallback;
+
+/// Counts frames, writing the frame number on the first channel and its
+/// negation on the second, and checks every call is a whole, small block
+#[derive(Default)]
+struct Ramp {
+    frames: usize,
+    calls: usize,
+}
+
+impl AudioCallback for Ramp {
+    fn process(&mut self, output: &mut [f32], sample_rate: f32, channels: usize, frames: usize) {
+        assert_eq!(sample_rate, 48_000.0);
+        assert_eq!(output.len(), frames * channels);
+        assert!(frames <= OFFLINE_BLOCK_FRAMES);
+        for frame in output.chunks_exact_mut(channels) {
+            frame[0] = self.frames as f32;
+            frame[1] = -(self.frames as f32);
+            self.frames += 1;
+        }
+        self.calls += 1;
+    }
+}
+
+#[test]
+fn renders_any_callback_in_blocks() {
+    let mut ramp = Ramp::default();
+    let output = render_offline(&mut ramp, Duration::from_millis(100), 48_000.0, 2);
+    assert_eq!(output.len(), 4_800 * 2);
+    assert_eq!(ramp.frames, 4_800);
+    assert_eq!(ramp.calls, 4_800usize.div_ceil(OFFLINE_BLOCK_FRAMES));
+    assert!(output.chunks_exact(2).enumerate().all(|(i, frame)| frame == [i as f32, -(i as f32)]));
+    assert!(render_offline(&mut ramp, Duration::ZERO, 48_000.0, 2).is_empty());
+}
+
+#[test]
+fn bounces_an_engine_to_disk() {
+    let mut engine = Engine::new();
+    engine.configure(EngineConfig::default()).unwrap();
+    engine.start().unwrap();
+    engine.add_source_node(SourceDescriptor::new(NodeDescriptor::new("oscillator")).with_gain(0.5)).unwrap();
+    let mut handle = engine.audio_handle().unwrap();
+    let in_memory = render_offline(&mut handle, Duration::from_millis(250), 48_000.0, 2);
+    assert!(in_memory.iter().any(|s| s.abs() > 0.01));
+
+    // the file holds the same audio as a render from the same start
+    let path = std::env::temp_dir().join(format!("pulsar-offline-{}.wav", std::process::id()));
+    let spec = WavSpec { sample_rate: 48_000, format: SampleFormat::Float32, dither: false };
+    let mut engine = Engine::new();
+    engine.configure(EngineConfig::default()).unwrap();
+    engine.start().unwrap();
+    engine.add_source_node(SourceDescriptor::new(NodeDescriptor::new("oscillator")).with_gain(0.5)).unwrap();
+    let mut handle = engine.audio_handle().unwrap();
+    render_offline_to_file(&mut handle, Duration::from_millis(250), 2, &path, spec).unwrap();
+    let written = wav::read_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(written.frames(), 12_000);
+    for (i, frame) in in_memory.chunks_exact(2).enumerate() {
+        assert_eq!([written.channels[0][i], written.channels[1][i]], frame);
+    }
+}
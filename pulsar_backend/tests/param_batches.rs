@@ -0,0 +1,92 @@
+//! Grouped parameter changes: a batch reaches the audio path at one block
+//! boundary, whole, both through the router's lock-free command queue and
+//! through engine edits.
+
+mod common;
+
+use pulsar_backend::engine::{Engine, EngineConfig, NodeTarget, ParamBatch};
+use pulsar_backend::project::{NodeDescriptor, ParamValue, ProjectError, SourceDescriptor};
+use pulsar_backend::rt_processing::routing::{ROUTER_COMMAND_CAPACITY, RouterCommand};
+
+use common::{CENTRE, Dc, block, router};
+
+const FRAMES: usize = 64;
+
+#[test]
+fn router_batches_land_in_one_block() {
+    let mut router = router(1, FRAMES);
+    let commands = router.commands();
+    let a = commands.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 0).unwrap();
+    let b = commands.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 0).unwrap();
+    let (left, _) = CENTRE.gains();
+    assert!((block(&mut router) - 2.0 * left).abs() < 1e-6);
+
+    let batch = vec![
+        RouterCommand::SetGain { id: a.id(), gain: 0.25 },
+        RouterCommand::SetGain { id: b.id(), gain: 0.5 },
+        RouterCommand::SetMute { id: b.id(), mute: true },
+        RouterCommand::SetMute { id: b.id(), mute: false },
+    ];
+    assert!(commands.send_batch(batch).is_ok());
+    // one queue slot, however many commands
+    assert_eq!(commands.pending(), 1);
+    assert!((block(&mut router) - 0.75 * left).abs() < 1e-6);
+    assert_eq!(commands.pending(), 0);
+
+    // a full queue hands the batch back intact
+    for _ in 0..ROUTER_COMMAND_CAPACITY {
+        assert!(commands.set_gain(a.id(), 1.0));
+    }
+    let batch = vec![RouterCommand::SetGain { id: a.id(), gain: 0.0 }, RouterCommand::RemoveSource(b.id())];
+    let Err(returned) = commands.send_batch(batch) else { panic!("queue should be full") };
+    assert_eq!(returned.len(), 2);
+}
+
+fn engine_with_two_sources() -> Engine {
+    let mut engine = Engine::new();
+    engine.configure(EngineConfig::default()).unwrap();
+    engine.start().unwrap();
+    for frequency in [220.0, 330.0] {
+        let node = NodeDescriptor::new("oscillator").with_param("frequency", frequency);
+        engine.add_source_node(SourceDescriptor::new(node)).unwrap();
+    }
+    engine
+}
+
+fn param(engine: &Engine, source: usize, name: &str) -> Option<ParamValue> {
+    engine.snapshot().sources[source].node.params.get(name).cloned()
+}
+
+#[test]
+fn param_batches_apply_whole_or_not_at_all() {
+    let mut engine = engine_with_two_sources();
+    // the second node's waveform is invalid, so neither node changes
+    let bad = ParamBatch::new()
+        .with_param(NodeTarget::Source(0), "frequency", 440.0)
+        .with_param(NodeTarget::Source(1), "waveform", "noise");
+    assert!(matches!(engine.apply_params("Preset", bad), Err(ProjectError::InvalidParam { .. })));
+    assert_eq!(param(&engine, 0, "frequency"), Some(ParamValue::Float(220.0)));
+    assert_eq!(engine.history().undo_label(), Some("Add source"));
+    // a target that doesn't exist fails before anything is built
+    let missing = ParamBatch::new().with_param(NodeTarget::Source(5), "amplitude", 0.1);
+    assert!(matches!(engine.apply_params("Preset", missing), Err(ProjectError::InvalidSource(5))));
+
+    let preset = ParamBatch::new()
+        .with_param(NodeTarget::Source(0), "frequency", 440.0)
+        .with_param(NodeTarget::Source(1), "waveform", "square")
+        .with_param(NodeTarget::Source(0), "amplitude", 0.2)
+        .with_param(NodeTarget::Source(0), "frequency", 880.0);
+    assert_eq!(preset.len(), 3);
+    assert_eq!(preset.clone().into_commands().len(), 2);
+    engine.apply_params("Preset", preset).unwrap();
+    assert_eq!(param(&engine, 0, "frequency"), Some(ParamValue::Float(880.0)));
+    assert_eq!(param(&engine, 0, "amplitude"), Some(ParamValue::Float(0.2)));
+    assert_eq!(param(&engine, 1, "waveform"), Some(ParamValue::Text("square".into())));
+    assert_eq!(engine.with_processor(|p| p.router().num_sources()).unwrap(), 2);
+
+    // one undo step for the whole preset
+    assert_eq!(engine.history().undo_label(), Some("Preset"));
+    assert!(engine.undo().unwrap());
+    assert_eq!(param(&engine, 0, "frequency"), Some(ParamValue::Float(220.0)));
+    assert_eq!(param(&engine, 1, "waveform"), None);
+}
@@ -0,0 +1,83 @@
+//! The reclaimer: whatever the router retires is dropped by whoever collects
+//! it, not by the thread that processed the block, and once the queue is full
+//! retiring drops in place and counts the overflow.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+
+use pulsar_backend::rt_processing::reclaim::{RECLAIM_CAPACITY, Reclaimer, Retired};
+use pulsar_backend::rt_processing::routing::AudioSource;
+
+use common::{CENTRE, block, router};
+
+const FRAMES: usize = 64;
+
+/// Threads that dropped a `Counted`, in order
+type Drops = Arc<Mutex<Vec<ThreadId>>>;
+
+/// Silent source that records which thread drops it
+struct Counted(Drops);
+
+impl AudioSource for Counted {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        for channel in output.iter_mut() {
+            channel[..frames].fill(0.0);
+        }
+    }
+}
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.0.lock().unwrap().push(thread::current().id());
+    }
+}
+
+#[test]
+fn removed_sources_drop_on_the_collector_thread() {
+    let drops = Drops::default();
+    let reclaimer = Reclaimer::new(RECLAIM_CAPACITY);
+    let mut router = router(1, FRAMES);
+    router.set_reclaimer(reclaimer.clone());
+    let commands = router.commands();
+    let kept = commands.add_source(Box::new(Counted(drops.clone())), 1.0, CENTRE, 0).unwrap();
+    let removed = commands.add_source(Box::new(Counted(drops.clone())), 1.0, CENTRE, 0).unwrap();
+    block(&mut router);
+
+    // removing and replacing both retire a source at the next block
+    assert!(removed.remove());
+    assert!(commands.replace_source(kept.id(), Box::new(Counted(drops.clone()))));
+    block(&mut router);
+    assert!(drops.lock().unwrap().is_empty());
+    assert_eq!(reclaimer.pending(), 2);
+
+    let collector = thread::spawn(move || reclaimer.collect());
+    let collector_id = collector.thread().id();
+    assert_eq!(collector.join().unwrap(), 2);
+    assert_eq!(*drops.lock().unwrap(), [collector_id; 2]);
+}
+
+#[test]
+fn a_full_queue_drops_in_place_and_counts_it() {
+    let drops = Drops::default();
+    let reclaimer = Reclaimer::new(RECLAIM_CAPACITY);
+    for _ in 0..RECLAIM_CAPACITY {
+        reclaimer.retire(Retired::Other(Box::new(Counted(drops.clone()))));
+    }
+    assert_eq!(reclaimer.pending(), RECLAIM_CAPACITY);
+    assert_eq!(reclaimer.overflows(), 0);
+    assert!(drops.lock().unwrap().is_empty());
+
+    // one more doesn't fit: it's dropped by the caller
+    reclaimer.retire(Retired::Other(Box::new(Counted(drops.clone()))));
+    assert_eq!(reclaimer.pending(), RECLAIM_CAPACITY);
+    assert_eq!(reclaimer.overflows(), 1);
+    assert_eq!(*drops.lock().unwrap(), [thread::current().id()]);
+
+    // the queue still holds the first 1024, and takes more once emptied
+    assert_eq!(reclaimer.collect(), RECLAIM_CAPACITY);
+    assert_eq!(drops.lock().unwrap().len(), RECLAIM_CAPACITY + 1);
+    reclaimer.retire(Retired::Other(Box::new(Counted(drops.clone()))));
+    assert_eq!((reclaimer.pending(), reclaimer.overflows()), (1, 1));
+}
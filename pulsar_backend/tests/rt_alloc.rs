@@ -0,0 +1,144 @@
+//! No heap allocation on the audio path: this binary installs `RtAllocCheck`
+//! as its allocator, so any allocation or free the router makes while mixing
+//! panics, then drives a router through queued commands, parameter changes
+//! and effect swaps, and an input source through its analyzers.
+
+use std::f32::consts::TAU;
+use std::sync::Arc;
+
+use pulsar_backend::audio_device::input::{InputRing, InputSource};
+use pulsar_backend::rt_processing::alloc_check::{RtAllocCheck, enter_audio_path, is_on_audio_path};
+use pulsar_backend::rt_processing::analysis::pitch::{PitchDetector, PitchDetectorConfig};
+use pulsar_backend::rt_processing::effects::{Effect, LfoRate};
+use pulsar_backend::rt_processing::effects::chain::EffectChain;
+use pulsar_backend::rt_processing::effects::compressor::{Compressor, CompressorParams};
+use pulsar_backend::rt_processing::effects::tremolo::Tremolo;
+use pulsar_backend::rt_processing::filters::{RampShape, Trim, TrimSlope};
+#[cfg(feature = "fixed-point")]
+use pulsar_backend::rt_processing::fixed::FixedOscillator;
+use pulsar_backend::rt_processing::routing::{AudioSource, Pan, PanLaw, Router, RouterCommand, TagChange};
+use pulsar_backend::rt_processing::voice_renderer::routing_source;
+use pulsar_backend::rt_processing::waveform::oscillators::Oscillator;
+use pulsar_backend::rt_processing::waveform::tables::WaveformType;
+
+#[global_allocator]
+static ALLOC: RtAllocCheck = RtAllocCheck;
+
+const FRAMES: usize = 256;
+const SAMPLE_RATE: f32 = 48_000.0;
+
+fn pan(value: f32) -> Pan {
+    Pan { value, law: PanLaw::EqualPower }
+}
+
+fn inserts() -> EffectChain {
+    EffectChain::new()
+        .with(Box::new(Tremolo::new(WaveformType::Sine, LfoRate::Hz(5.0))))
+        .with(Box::new(Compressor::new(CompressorParams::default())))
+}
+
+fn block(router: &mut Router, output: &mut [f32]) {
+    router.process(output, None);
+    assert!(!is_on_audio_path());
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "heap allocation")]
+fn the_check_catches_allocations() {
+    let _audio_path = enter_audio_path();
+    std::hint::black_box(vec![0u8; 16]);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "heap deallocation")]
+fn the_check_catches_dropping_an_effect() {
+    let effect: Box<dyn Effect> = Box::new(Compressor::new(CompressorParams::default()));
+    let _audio_path = enter_audio_path();
+    drop(effect);
+}
+
+#[test]
+fn the_router_mixes_without_allocating() {
+    let mut router = Router::new(2, SAMPLE_RATE, 3, FRAMES);
+    router.set_param_ramp(5.0, RampShape::Linear);
+    router.set_bus_effects(1, inserts());
+    let commands = router.commands();
+    let mut output = vec![0.0; FRAMES * 2];
+
+    let sine = commands.add_source(routing_source(Oscillator::sine(220.0)), 0.5, pan(-0.3), 0).unwrap();
+    let saw = commands.add_source(routing_source(Oscillator::sawtooth(110.0)), 0.5, pan(0.3), 1).unwrap();
+    #[cfg(feature = "fixed-point")]
+    {
+        router.set_fixed_point_mix(true);
+        commands.add_source(Box::new(FixedOscillator::new(WaveformType::Triangle, 330.0, FRAMES)), 0.3, pan(0.0), 1);
+    }
+    block(&mut router, &mut output);
+
+    for round in 0..8 {
+        let level = 0.2 + round as f32 * 0.1;
+        // parameter changes, alone and batched
+        assert!(sine.set_gain(level));
+        assert!(commands.set_pan(saw.id(), pan(-level)));
+        assert!(commands.set_send(sine.id(), 2, level, round % 2 == 0));
+        assert!(commands.set_effects_mix(saw.id(), level));
+        let batch = vec![
+            RouterCommand::SetGain { id: saw.id(), gain: level },
+            RouterCommand::SetMute { id: sine.id(), mute: round % 3 == 0 },
+        ];
+        assert!(commands.send_batch(batch).is_ok());
+        block(&mut router, &mut output);
+
+        // effect swaps, on a source and on a bus
+        assert!(commands.set_effects(sine.id(), inserts()));
+        assert!(router.set_bus_effects(2, inserts()));
+        block(&mut router, &mut output);
+
+        // the engine's edits: nodes swapped and effects spliced in place
+        let tremolo = || Box::new(Tremolo::new(WaveformType::Triangle, LfoRate::Hz(3.0))) as Box<dyn Effect>;
+        let batch = vec![
+            commands.replace_source_command(saw.id(), routing_source(Oscillator::sawtooth(55.0 * level))),
+            RouterCommand::SetTrims { id: saw.id(), high_pass: Some(Trim::new(40.0, TrimSlope::Db12)), low_pass: None },
+            RouterCommand::SetBusReturn { bus: 1, gain: level },
+        ];
+        assert!(commands.send_batch(batch).is_ok());
+        assert!(commands.splice_effects(sine.id(), 0..1, vec![tremolo(), tremolo()]));
+        assert!(commands.splice_bus_effects(1, 1..2, Vec::new()));
+        assert!(commands.splice_bus_effects(1, 0..0, vec![tremolo()]));
+        assert!(commands.set_bus_effects(2, inserts()));
+        block(&mut router, &mut output);
+
+        // tags, bus moves and a source coming and going
+        assert!(commands.set_tags(saw.id(), &["pad"]));
+        assert!(commands.change_tagged("pad", TagChange::Gain(level)));
+        assert!(commands.set_bus(saw.id(), round % 3));
+        let shot = commands.add_source(routing_source(Oscillator::square(440.0)), 0.2, pan(0.0), 0).unwrap();
+        block(&mut router, &mut output);
+        assert!(shot.remove());
+        block(&mut router, &mut output);
+    }
+    assert!(output.iter().all(|s| s.is_finite()));
+}
+
+#[test]
+fn an_input_source_detects_pitch_without_allocating() {
+    let ring = Arc::new(InputRing::new(1, FRAMES * 4));
+    let detector = PitchDetector::new(PitchDetectorConfig::voice());
+    let reading = detector.reading_handle();
+    let mut source = InputSource::new(Arc::clone(&ring), FRAMES * 2).with_analyzer(detector);
+    let mut outputs = vec![vec![0.0; FRAMES]; 2];
+    let mut captured = vec![0.0; FRAMES];
+
+    // the voice settings detect on every block
+    for block in 0..32 {
+        for (i, sample) in captured.iter_mut().enumerate() {
+            *sample = 0.5 * (TAU * 220.0 * (block * FRAMES + i) as f32 / SAMPLE_RATE).sin();
+        }
+        ring.write(&captured, |s| s);
+        let mut views: Vec<&mut [f32]> = outputs.iter_mut().map(|o| o.as_mut_slice()).collect();
+        let _audio_path = enter_audio_path();
+        source.render(&mut views, FRAMES, SAMPLE_RATE);
+    }
+    assert!((reading.load().frequency - 220.0).abs() < 1.0, "{:?}", reading.load());
+}
@@ -0,0 +1,96 @@
+//! Sample playback: pitch and sample-rate conversion, loop points, one-shots
+//! and loading from WAV.
+
+use std::sync::Arc;
+
+use pulsar_backend::io::sample_cache::SampleBuffer;
+use pulsar_backend::io::wav::{self, SampleFormat, WavAudio, WavSpec};
+use pulsar_backend::rt_processing::voice_renderer::AudioSource;
+use pulsar_backend::rt_processing::waveform::sampler::SamplePlayer;
+
+const SAMPLE_RATE: f32 = 48_000.0;
+
+/// Mono sample whose frame `i` holds `i`
+fn ramp(frames: usize, sample_rate: u32) -> Arc<SampleBuffer> {
+    let audio = WavAudio { channels: vec![(0..frames).map(|i| i as f32).collect()], sample_rate };
+    Arc::new(SampleBuffer::from_audio("ramp", audio))
+}
+
+/// Left channel of `frames` stereo frames
+fn play(player: &mut SamplePlayer, frames: usize) -> Vec<f32> {
+    let mut output = vec![0.0; frames * 2];
+    player.fill_buffer(&mut output, SAMPLE_RATE, 2, frames);
+    assert!(output.chunks(2).all(|frame| frame[0] == frame[1]), "mono plays on both channels");
+    output.iter().step_by(2).copied().collect()
+}
+
+#[test]
+fn pitch_and_sample_rate_set_the_playback_rate() {
+    let mut player = SamplePlayer::new(ramp(100, 48_000)).with_gain(0.5);
+    assert_eq!(play(&mut player, 4), [0.0, 0.5, 1.0, 1.5]);
+
+    // an octave up skips every other frame, from where it was
+    player.set_pitch(12.0);
+    assert_eq!(play(&mut player, 3), [2.0, 3.0, 4.0]);
+    assert_eq!(player.position(), 10.0);
+
+    // recorded at half the rate: plays at its own pitch, interpolated
+    let mut player = SamplePlayer::new(ramp(100, 24_000)).with_start(10);
+    assert_eq!(play(&mut player, 4), [10.0, 10.5, 11.0, 11.5]);
+    player.set_pitch(-12.0);
+    assert_eq!(play(&mut player, 2), [12.0, 12.25]);
+    player.set_pitch(100.0);
+    assert_eq!(player.pitch(), 48.0);
+}
+
+#[test]
+fn loop_points_and_one_shots() {
+    let mut player = SamplePlayer::new(ramp(30, 48_000)).with_loop(Some(10..20));
+    let played = play(&mut player, 25);
+    let expected: Vec<f32> = (0..20).chain(10..15).map(|i| i as f32).collect();
+    assert_eq!(played, expected);
+    assert!(player.is_playing());
+
+    // without a loop it stops at the end, and waits for `start`
+    player.set_loop(None);
+    let played = play(&mut player, 20);
+    assert_eq!(played[14], 29.0);
+    assert!(played[15..].iter().all(|&s| s == 0.0));
+    assert!(!player.is_playing() && player.is_active());
+    player.start();
+    assert_eq!(play(&mut player, 2), [0.0, 1.0]);
+    player.stop();
+    assert_eq!(play(&mut player, 2), [0.0, 0.0]);
+
+    // a one-shot drops out once it's done
+    let mut player = SamplePlayer::new(ramp(4, 48_000)).with_one_shot(true);
+    assert!(player.is_active());
+    assert_eq!(play(&mut player, 6), [0.0, 1.0, 2.0, 3.0, 0.0, 0.0]);
+    assert!(!player.is_active());
+    player.reset();
+    assert!(player.is_active());
+
+    // loop points are clamped to the sample; an empty loop plays through
+    player.set_loop(Some(2..99));
+    assert_eq!(player.loop_range(), Some(2..4));
+    player.set_loop(Some(3..3));
+    assert_eq!(player.loop_range(), None);
+}
+
+#[test]
+fn samples_load_from_wav_files() {
+    let path = std::env::temp_dir().join(format!("pulsar-sample-player-{}.wav", std::process::id()));
+    let left: Vec<f32> = (0..8).map(|i| i as f32 / 8.0).collect();
+    let right: Vec<f32> = left.iter().map(|s| -s).collect();
+    let spec = WavSpec { sample_rate: 48_000, format: SampleFormat::Float32, dither: false };
+    wav::write_file(&path, &[left.clone(), right.clone()], spec).unwrap();
+
+    let mut player = SamplePlayer::load(&path).unwrap();
+    assert_eq!(player.sample().path(), path);
+    let mut output = vec![0.0; 8 * 2];
+    player.fill_buffer(&mut output, SAMPLE_RATE, 2, 8);
+    assert_eq!(output.iter().step_by(2).copied().collect::<Vec<_>>(), left);
+    assert_eq!(output.iter().skip(1).step_by(2).copied().collect::<Vec<_>>(), right);
+    std::fs::remove_file(&path).unwrap();
+    assert!(SamplePlayer::load(&path).is_err());
+}
@@ -0,0 +1,47 @@
+//! Pitch quantization to a key and mode, and the shared scale generators
+//! read while the UI changes it.
+
+use pulsar_backend::rt_processing::notes::{Scale, ScaleMode, SharedScale};
+
+#[test]
+fn quantize_moves_to_the_nearest_scale_note() {
+    let c_major = Scale::new(0, ScaleMode::Major);
+    assert!(c_major.contains(64) && !c_major.contains(61));
+    // in-scale notes stay; C# sits between C and D, so goes down
+    assert_eq!(c_major.quantize(64), 64);
+    assert_eq!(c_major.quantize(61), 60);
+    assert_eq!(c_major.quantize(66), 65);
+
+    // A minor pentatonic: A C D E G; B is nearer C than A
+    let pentatonic = Scale::new(9, ScaleMode::MinorPentatonic);
+    assert_eq!(pentatonic.quantize(71), 72);
+    assert_eq!(pentatonic.quantize(70), 69);
+    // across the octave boundary and at the ends of the note range
+    assert_eq!(Scale::new(2, ScaleMode::Major).quantize(60), 59);
+    assert_eq!(Scale::new(2, ScaleMode::Major).quantize(63), 62);
+    assert_eq!(Scale::new(1, ScaleMode::MajorPentatonic).quantize(0), 1);
+    assert_eq!(Scale::new(1, ScaleMode::MajorPentatonic).quantize(127), 125);
+
+    assert_eq!(c_major.quantize_pitch(60.9), 60.0);
+    assert_eq!(c_major.quantize_pitch(61.1), 62.0);
+    assert_eq!(c_major.quantize_pitch(64.5), 64.0);
+    assert_eq!(c_major.quantize_pitch(-0.7), -1.0);
+}
+
+#[test]
+fn shared_scale_changes_for_every_clone() {
+    let scale = SharedScale::default();
+    let generator = scale.clone();
+    assert_eq!(generator.get(), None);
+    assert_eq!(generator.quantize(61), 61);
+
+    for mode in ScaleMode::ALL {
+        scale.set(Some(Scale::new(14, mode)));
+        assert_eq!(generator.get(), Some(Scale::new(2, mode)));
+    }
+    scale.set(Some(Scale::new(0, ScaleMode::Major)));
+    assert_eq!(generator.quantize(61), 60);
+    assert_eq!(generator.quantize_pitch(63.2), 64.0);
+    scale.set(None);
+    assert_eq!(generator.quantize(61), 61);
+}
@@ -0,0 +1,241 @@
+//! Step sequencer timing and pattern generation: Euclidean lanes, ratchets
+//! and probability, plus swing, grooves and Turing-machine pitch lanes,
+//! checked frame by frame on the rendered output.
+
+use pulsar_backend::rt_processing::effects::LfoRate;
+use pulsar_backend::rt_processing::groove::Groove;
+use pulsar_backend::rt_processing::notes::{Scale, ScaleMode, SharedScale};
+use pulsar_backend::rt_processing::routing::AudioSource;
+use pulsar_backend::rt_processing::sequencer::{Sequencer, SequencerLane, Step, euclidean};
+use pulsar_backend::rt_processing::turing::{MAX_TURING_LENGTH, TuringMachine, TuringModulator};
+use pulsar_backend::rt_processing::voices::{Voice, VoicePool};
+
+const SAMPLE_RATE: f32 = 48_000.0;
+/// Doesn't divide a step, so hits fall inside blocks
+const FRAMES: usize = 64;
+/// A 16th at 120 BPM
+const STEP: usize = 6_000;
+
+/// Outputs its note number times the velocity while held; releases instantly
+#[derive(Default)]
+struct HeldVoice {
+    note: Option<u8>,
+    velocity: f32,
+}
+
+impl Voice for HeldVoice {
+    fn note_on(&mut self, note: u8, velocity: f32) {
+        self.note = Some(note);
+        self.velocity = velocity;
+    }
+
+    fn note_off(&mut self) {
+        self.note = None;
+    }
+
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        let level = self.note.map_or(0.0, |note| f32::from(note) * self.velocity);
+        for channel in output.iter_mut() {
+            channel[..frames].fill(level);
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.note.is_some()
+    }
+
+    fn reset(&mut self) {
+        self.note = None;
+    }
+}
+
+fn new_sequencer() -> Sequencer {
+    let voices = (0..4).map(|_| Box::new(HeldVoice::default()) as Box<dyn Voice>).collect();
+    Sequencer::new(VoicePool::new(voices, 1, FRAMES).with_steal_fade(0.0))
+}
+
+fn render(sequencer: &mut Sequencer, frames: usize) -> Vec<f32> {
+    let mut output = vec![0.0; frames.div_ceil(FRAMES) * FRAMES];
+    for block in output.chunks_mut(FRAMES) {
+        sequencer.render(&mut [block], FRAMES, SAMPLE_RATE);
+    }
+    output.truncate(frames);
+    output
+}
+
+/// Frames where the output rises
+fn onsets(output: &[f32]) -> Vec<usize> {
+    (0..output.len()).filter(|&i| output[i] > 0.0 && (i == 0 || output[i - 1] < output[i])).collect()
+}
+
+#[test]
+fn euclidean_patterns() {
+    let pattern = |hits, steps, rotation| -> String {
+        euclidean(hits, steps, rotation).iter().map(|&hit| if hit { 'x' } else { '.' }).collect()
+    };
+    assert_eq!(pattern(3, 8, 0), "x..x..x.");
+    assert_eq!(pattern(4, 16, 0), "x...x...x...x...");
+    assert_eq!(pattern(3, 8, 2), "x.x..x..");
+    assert_eq!(pattern(3, 8, -1), "..x..x.x");
+    assert_eq!(pattern(0, 4, 0), "....");
+    assert_eq!(pattern(9, 4, 0), "xxxx");
+    for (hits, steps) in [(5, 8), (7, 12), (5, 16), (13, 24)] {
+        let pattern = euclidean(hits, steps, 3);
+        assert_eq!(pattern.iter().filter(|&&hit| hit).count(), hits);
+        // evenly spread: gaps differ by at most one step
+        let positions: Vec<usize> = (0..steps).filter(|&i| pattern[i]).collect();
+        let gaps: Vec<usize> = (0..hits).map(|i| (positions[(i + 1) % hits] + steps - positions[i]) % steps).collect();
+        assert!(gaps.iter().max().unwrap() - gaps.iter().min().unwrap() <= 1);
+    }
+}
+
+#[test]
+fn hits_and_ratchets_land_on_the_exact_frame() {
+    let mut lane = SequencerLane::euclidean(36, 3, 8, 0).with_gate(0.5);
+    lane.set_step(3, Step { ratchet: 3, ..Step::hit(1.0) });
+    let mut sequencer = new_sequencer().with_lane(lane);
+    let output = render(&mut sequencer, 8 * STEP);
+    // step 3 plays three 2000-frame hits
+    assert_eq!(onsets(&output), [0, 3 * STEP, 3 * STEP + 2000, 3 * STEP + 4000, 6 * STEP]);
+    // each held for half its length
+    assert_eq!(output[STEP / 2 - 1], 36.0);
+    assert_eq!(output[STEP / 2], 0.0);
+    assert_eq!(output[3 * STEP + 999], 36.0);
+    assert_eq!(output[3 * STEP + 1000], 0.0);
+    assert_eq!(sequencer.position(), 8);
+
+    // lanes of different lengths drift against each other
+    let mut sequencer =
+        new_sequencer().with_lane(SequencerLane::euclidean(1, 1, 3, 0)).with_lane(SequencerLane::euclidean(2, 1, 4, 0));
+    let output = render(&mut sequencer, 12 * STEP);
+    let onsets = onsets(&output);
+    let step_of =
+        |level: f32| -> Vec<usize> { onsets.iter().filter(|&&i| output[i] >= level).map(|&i| i / STEP).collect() };
+    assert_eq!(step_of(1.0), [0, 3, 4, 6, 8, 9]);
+    assert_eq!(step_of(2.0), [0, 4, 8]);
+}
+
+#[test]
+fn probability_is_seeded() {
+    let lane = || {
+        let mut lane = SequencerLane::euclidean(60, 16, 16, 0).with_gate(0.5);
+        lane.set_probability(0.5);
+        lane
+    };
+    let played = |seed| onsets(&render(&mut new_sequencer().with_lane(lane()).with_seed(seed), 64 * STEP));
+    let first = played(7);
+    assert_eq!(first, played(7));
+    assert_ne!(first, played(8));
+    assert!((20..44).contains(&first.len()), "{} of 64", first.len());
+
+    // never and always
+    let mut sequencer = new_sequencer().with_lane(lane());
+    let mut silent = lane();
+    silent.set_probability(0.0);
+    assert!(sequencer.set_lane(0, silent));
+    assert!(onsets(&render(&mut sequencer, 16 * STEP)).is_empty());
+    assert!(!sequencer.set_lane(1, lane()));
+}
+
+#[test]
+fn swing_and_grooves_move_steps_off_the_grid() {
+    // 62.5%: every second step a quarter of a step late, and so shorter
+    let lane = || SequencerLane::euclidean(10, 4, 4, 0).with_gate(0.5);
+    let mut sequencer = new_sequencer().with_lane(lane()).with_groove(Groove::swing(62.5));
+    let output = render(&mut sequencer, 4 * STEP);
+    assert_eq!(onsets(&output), [0, 7_500, 2 * STEP, 2 * STEP + 7_500]);
+    // gates follow the swung lengths: 7500 then 4500 frames
+    assert_eq!(output[3_749], 10.0);
+    assert_eq!(output[3_750], 0.0);
+    assert_eq!(output[7_500 + 2_249], 10.0);
+    assert_eq!(output[7_500 + 2_250], 0.0);
+
+    // early steps, velocity accents and a partial amount
+    let groove = Groove::new(vec![0.0, -0.25], vec![1.0, 0.5]);
+    let mut sequencer = new_sequencer().with_lane(lane()).with_groove(groove.clone());
+    let output = render(&mut sequencer, 4 * STEP);
+    assert_eq!(onsets(&output), [0, 4_500, 2 * STEP, 2 * STEP + 4_500]);
+    assert_eq!(output[4_500], 5.0);
+    let mut sequencer = new_sequencer().with_lane(lane()).with_groove(groove.with_amount(0.5));
+    let output = render(&mut sequencer, 2 * STEP);
+    assert_eq!(onsets(&output), [0, 5_250]);
+    assert_eq!(output[5_250], 7.5);
+
+    assert_eq!(Groove::swing(50.0).offset(1), 0.0);
+    assert_eq!(Groove::swing(90.0).offset(3), 0.5);
+}
+
+#[test]
+fn grooves_are_extracted_from_played_hits() {
+    // every off-beat played 0.3 steps late and at half velocity
+    let hits = [(0.0, 1.0), (1.3, 0.5), (2.0, 0.8), (3.3, 0.5), (4.1, 1.2), (5.3, 0.5)];
+    let groove = Groove::from_hits(&hits, 2);
+    assert_eq!(groove.len(), 2);
+    assert!((groove.offset(0) - 0.1 / 3.0).abs() < 1e-6);
+    assert!((groove.offset(1) - 0.3).abs() < 1e-6);
+    assert_eq!(groove.velocity(0), 1.0);
+    assert!((groove.velocity(1) - 0.5).abs() < 1e-6);
+
+    // unplayed steps stay straight
+    let groove = Groove::from_hits(&[(0.9, 1.0)], 4);
+    assert!((groove.offset(1) + 0.1).abs() < 1e-6);
+    assert_eq!(groove.offset(2), 0.0);
+    assert_eq!(groove.velocity(2), 1.0);
+}
+
+#[test]
+fn turing_machines_loop_and_mutate() {
+    let values = |machine: &mut TuringMachine, steps| -> Vec<f32> { (0..steps).map(|_| machine.step()).collect() };
+    // fully locked, the loop repeats every `length` steps
+    let mut machine = TuringMachine::new(5, 1.0).with_seed(3);
+    let locked = values(&mut machine, 20);
+    assert!((0..15).all(|i| locked[i] == locked[i + 5]));
+    assert!(locked.iter().all(|value| (0.0..=1.0).contains(value)));
+    machine.reset();
+    assert_eq!(values(&mut machine, 20), locked);
+    assert_ne!(values(&mut TuringMachine::new(5, 1.0).with_seed(4), 20), locked);
+
+    // unlocked, it doesn't
+    let mut machine = TuringMachine::new(5, 0.0).with_seed(3);
+    let free = values(&mut machine, 40);
+    assert!((0..35).any(|i| free[i] != free[i + 5]));
+    machine.set_length(99);
+    assert_eq!(machine.length(), MAX_TURING_LENGTH);
+    machine.set_lock(2.0);
+    assert_eq!(machine.lock(), 1.0);
+
+    // as a modulator: 100 Hz steps every 480 frames, held in between
+    let mut modulator = TuringModulator::new(TuringMachine::new(8, 0.0), LfoRate::Hz(100.0)).with_depth(0.5);
+    let outputs: Vec<f32> = (0..240).map(|_| modulator.advance(32, SAMPLE_RATE)).collect();
+    assert!(outputs.iter().all(|output| (-0.5..=0.5).contains(output)));
+    assert!((0..15).all(|i| outputs[i] == outputs[0]));
+    let changes = outputs.windows(2).filter(|pair| pair[0] != pair[1]).count();
+    assert!((8..=16).contains(&changes), "{changes} changes in 16 steps");
+}
+
+#[test]
+fn turing_lanes_play_looping_notes_in_the_scale() {
+    let scale = SharedScale::new(Some(Scale::new(0, ScaleMode::Major)));
+    let machine = TuringMachine::new(4, 1.0).with_seed(5);
+    let lane = SequencerLane::euclidean(48, 8, 8, 0).with_gate(0.5).with_turing(machine, 12);
+    let mut sequencer = new_sequencer().with_lane(lane).with_scale(scale);
+    let notes = |sequencer: &mut Sequencer| -> Vec<f32> {
+        let output = render(sequencer, 16 * STEP);
+        onsets(&output).iter().map(|&i| output[i]).collect()
+    };
+    let played = notes(&mut sequencer);
+    assert_eq!(played.len(), 16);
+    assert!(played.iter().all(|&note| (48.0..=60.0).contains(&note)));
+    assert!(played.iter().all(|&note| Scale::new(0, ScaleMode::Major).contains(note as u8)));
+    assert!((0..12).all(|i| played[i] == played[i + 4]));
+    assert!(played.iter().any(|&note| note != played[0]));
+    sequencer.reset();
+    assert_eq!(notes(&mut sequencer), played);
+
+    // unlocking mid-play lets the loop wander
+    sequencer.turing_mut(0).unwrap().set_lock(0.0);
+    let wandering = notes(&mut sequencer);
+    assert!((0..12).any(|i| wandering[i] != wandering[i + 4]));
+    assert!(wandering.iter().all(|&note| (48.0..=60.0).contains(&note)));
+    assert!(new_sequencer().with_lane(SequencerLane::new(60, 4)).turing_mut(0).is_none());
+}
@@ -0,0 +1,63 @@
+//! Shared-memory rings: the positions in the header come from another
+//! process, so a corrupt header must never make a read or write leave the
+//! ring.
+#![cfg(unix)]
+
+use std::fs::OpenOptions;
+use std::os::unix::fs::FileExt;
+use std::path::PathBuf;
+
+use pulsar_backend::remote::shm::SharedInput;
+use pulsar_backend::rt_processing::routing::AudioSource;
+use pulsar_client::shm::SharedRing;
+
+const CAPACITY: usize = 64;
+const WRITE_POS_OFFSET: u64 = 64;
+const READ_POS_OFFSET: u64 = 128;
+
+fn ring_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("pulsar-ring-test-{}-{name}", std::process::id()))
+}
+
+/// Overwrite a position in the header behind the ring's back
+fn corrupt(ring: &SharedRing, offset: u64, position: u64) {
+    let file = OpenOptions::new().write(true).open(ring.path()).unwrap();
+    file.write_all_at(&position.to_ne_bytes(), offset).unwrap();
+}
+
+#[test]
+fn positions_are_clamped_to_the_ring() {
+    let ring = SharedRing::create(ring_path("clamp"), 2, CAPACITY, 48_000).unwrap();
+    assert_eq!(ring.write(&[0.5; CAPACITY * 4]), CAPACITY);
+    assert_eq!(ring.readable(), CAPACITY);
+
+    // a write position far ahead of the reader reads as a full ring
+    corrupt(&ring, WRITE_POS_OFFSET, u64::MAX / 2);
+    assert_eq!(ring.readable(), CAPACITY);
+    assert_eq!(ring.writable(), 0);
+    let mut block = vec![0.0; CAPACITY * 2 * 8];
+    assert_eq!(ring.read(&mut block), CAPACITY);
+
+    // one behind it reads as empty
+    corrupt(&ring, READ_POS_OFFSET, u64::MAX);
+    assert_eq!(ring.readable(), 0);
+    assert_eq!(ring.read(&mut block), 0);
+    assert_eq!(ring.writable(), CAPACITY);
+}
+
+#[test]
+fn shared_inputs_read_at_most_a_ring_per_block() {
+    let path = ring_path("input");
+    let ring = SharedRing::create(&path, 1, CAPACITY, 48_000).unwrap();
+    let mut input = SharedInput::open(&path).unwrap();
+    ring.write(&[1.0; CAPACITY]);
+    corrupt(&ring, WRITE_POS_OFFSET, u64::MAX / 2);
+
+    // a block longer than the ring gets what the ring holds, then silence
+    let frames = CAPACITY * 3;
+    let mut left = vec![f32::NAN; frames];
+    let mut right = vec![f32::NAN; frames];
+    input.render(&mut [&mut left, &mut right], frames, 48_000.0);
+    assert!(left[..CAPACITY].iter().chain(&right[..CAPACITY]).all(|&s| s == 1.0));
+    assert!(left[CAPACITY..].iter().chain(&right[CAPACITY..]).all(|&s| s == 0.0));
+}
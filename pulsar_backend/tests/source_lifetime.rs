@@ -0,0 +1,87 @@
+//! Source lifetimes: a source with a lifetime in seconds or bars plays for
+//! that long, fades out and leaves the router by itself.
+
+mod common;
+
+use pulsar_backend::rt_processing::routing::{EXPIRY_FADE_SECONDS, Lifetime, Router};
+use pulsar_backend::rt_processing::transport::{TimeSignature, Transport};
+
+use common::{CENTRE, Dc, router};
+
+const FRAMES: usize = 256;
+const SAMPLE_RATE: f32 = 48_000.0;
+
+/// Left channel of the next `blocks` blocks
+fn run(router: &mut Router, blocks: usize) -> Vec<f32> {
+    let mut left = Vec::with_capacity(blocks * FRAMES);
+    let mut output = vec![0.0; FRAMES * 2];
+    for _ in 0..blocks {
+        router.process(&mut output, None);
+        left.extend(output.iter().step_by(2));
+    }
+    left
+}
+
+/// Frame where the output first drops below full level
+fn expiry(output: &[f32]) -> Option<usize> {
+    output.iter().position(|&s| s < 0.5)
+}
+
+#[test]
+fn sources_fade_out_and_leave_when_their_time_is_up() {
+    let mut router = router(1, FRAMES);
+    let id = router.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 0);
+    let forever = router.add_source(Box::new(Dc(1.0)), 0.0, CENTRE, 0);
+    assert!(router.set_lifetime(id, Some(Lifetime::Seconds(0.1))));
+    assert_eq!(router.lifetime(id), Some(Lifetime::Seconds(0.1)));
+
+    let output = run(&mut router, 40);
+    assert_eq!(expiry(&output), Some(4800));
+    let fade = (EXPIRY_FADE_SECONDS * SAMPLE_RATE) as usize;
+    assert!(output[4800..4800 + fade].windows(2).all(|pair| pair[1] <= pair[0]));
+    assert!(output[4800 + fade..].iter().all(|&s| s == 0.0));
+
+    assert_eq!(router.index_of(id), None);
+    assert_eq!(router.index_of(forever), Some(0));
+    assert!(!router.set_lifetime(id, None));
+}
+
+#[test]
+fn bars_follow_the_transport() {
+    // no transport: 4/4 at 120 bpm, an eighth of a bar is a quarter second
+    let mut router = router(1, FRAMES);
+    let id = router.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 0);
+    router.set_lifetime(id, Some(Lifetime::Bars(0.125)));
+    assert_eq!(expiry(&run(&mut router, 60)), Some(12_000));
+
+    // a bar of 3/4 at 180 bpm lasts a second, stopped or not
+    let mut router = router_with_transport(Transport::new(SAMPLE_RATE).with_tempo(180.0));
+    let id = router.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 0);
+    router.set_lifetime(id, Some(Lifetime::Bars(0.5)));
+    assert_eq!(expiry(&run(&mut router, 120)), Some(24_000));
+}
+
+fn router_with_transport(transport: Transport) -> Router {
+    let mut router = router(1, FRAMES);
+    router.set_transport(Some(transport.with_signature(TimeSignature::new(3, 4))));
+    router
+}
+
+#[test]
+fn fire_and_forget_through_the_command_queue() {
+    let mut router = router(1, FRAMES);
+    let commands = router.commands();
+    let shot = commands.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 0).unwrap();
+    assert!(shot.set_lifetime(Some(Lifetime::Seconds(0.05))));
+    let kept = commands.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 0).unwrap();
+    kept.set_lifetime(Some(Lifetime::Seconds(0.05)));
+    run(&mut router, 2);
+    // cancelled before it ran out
+    kept.set_lifetime(None);
+
+    let output = run(&mut router, 20);
+    assert_eq!(output[0], 1.0);
+    assert_eq!(*output.last().unwrap(), 0.5);
+    assert_eq!(router.index_of(shot.id()), None);
+    assert_eq!(router.index_of(kept.id()), Some(0));
+}
@@ -0,0 +1,129 @@
+//! Sources rendering at their own sample rate: the router converts them to
+//! its own, so they keep their pitch and timing.
+
+mod common;
+
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use pulsar_backend::io::recorder::RecordingPlayer;
+use pulsar_backend::io::wav::WavAudio;
+use pulsar_backend::rt_processing::resampler::ResampledSource;
+use pulsar_backend::rt_processing::routing::{AudioSource, Pan, PanLaw, Router};
+
+use common::router;
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const FRAMES: usize = 256;
+
+/// Constant 0.5, recording how much it was asked for and at what rate
+#[derive(Clone, Default)]
+struct Probe {
+    native: Option<u32>,
+    frames: Arc<AtomicUsize>,
+    rate: Arc<AtomicU32>,
+}
+
+impl AudioSource for Probe {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, sample_rate: f32) {
+        self.frames.fetch_add(frames, Ordering::Relaxed);
+        self.rate.store(sample_rate as u32, Ordering::Relaxed);
+        for channel in output.iter_mut() {
+            channel[..frames].fill(0.5);
+        }
+    }
+
+    fn native_sample_rate(&self) -> Option<u32> {
+        self.native
+    }
+}
+
+/// Left channel of `blocks` blocks
+fn run(router: &mut Router, blocks: usize) -> Vec<f32> {
+    let mut left = Vec::new();
+    let mut output = vec![0.0; FRAMES * 2];
+    for _ in 0..blocks {
+        router.process(&mut output, None);
+        left.extend(output.iter().step_by(2));
+    }
+    left
+}
+
+fn hard_left() -> Pan {
+    Pan { value: -1.0, law: PanLaw::Linear }
+}
+
+#[test]
+fn sources_at_another_rate_are_converted() {
+    let mut router = router(1, FRAMES);
+    let probe = Probe { native: Some(24_000), ..Probe::default() };
+    router.add_source(Box::new(probe.clone()), 1.0, hard_left(), 0);
+    let left = run(&mut router, 40);
+
+    // rendered at its own rate, half as many frames
+    assert_eq!(probe.rate.load(Ordering::Relaxed), 24_000);
+    let rendered = probe.frames.load(Ordering::Relaxed);
+    assert!((20 * FRAMES..=20 * FRAMES + 512).contains(&rendered), "{rendered}");
+    // at its level once the kernel has filled
+    assert!(left[64..].iter().all(|&s| (s - 0.5).abs() < 1e-3));
+
+    // at the router's rate, or undeclared, it plays as is
+    for native in [Some(48_000), None] {
+        let mut router = common::router(1, FRAMES);
+        let probe = Probe { native, ..Probe::default() };
+        router.add_source(Box::new(probe.clone()), 1.0, hard_left(), 0);
+        assert!(run(&mut router, 2).iter().all(|&s| s == 0.5));
+        assert_eq!(probe.frames.load(Ordering::Relaxed), 2 * FRAMES);
+        assert_eq!(probe.rate.load(Ordering::Relaxed), 48_000);
+    }
+
+    // sources queued through commands get the same treatment
+    let mut router = common::router(1, FRAMES);
+    let probe = Probe { native: Some(96_000), ..Probe::default() };
+    let handle = router.commands().add_source(Box::new(probe.clone()), 1.0, hard_left(), 0).unwrap();
+    run(&mut router, 4);
+    assert!(handle.is_alive());
+    assert_eq!(probe.rate.load(Ordering::Relaxed), 96_000);
+    assert!(probe.frames.load(Ordering::Relaxed) >= 8 * FRAMES);
+}
+
+/// Frequency of `signal` at 48 kHz, from its rising zero crossings
+fn frequency(signal: &[f32]) -> f32 {
+    let crossings: Vec<usize> = (1..signal.len()).filter(|&i| signal[i - 1] < 0.0 && signal[i] >= 0.0).collect();
+    let (first, last) = (crossings[0], *crossings.last().unwrap());
+    (crossings.len() - 1) as f32 * SAMPLE_RATE / (last - first) as f32
+}
+
+#[test]
+fn recordings_keep_their_pitch() {
+    // 441 Hz recorded at 44.1 kHz; played as is it would come out at 480 Hz
+    let sine: Vec<f32> = (0..44_100).map(|i| (TAU * 441.0 * i as f32 / 44_100.0).sin() * 0.5).collect();
+    let audio = WavAudio { channels: vec![sine], sample_rate: 44_100 };
+    let player = RecordingPlayer::new(audio);
+    assert_eq!(player.native_sample_rate(), Some(44_100));
+
+    let mut router = router(1, FRAMES);
+    router.add_source(Box::new(player), 1.0, hard_left(), 0);
+    let left = run(&mut router, 100);
+    let measured = frequency(&left[1000..]);
+    assert!((measured - 441.0).abs() < 0.5, "{measured}");
+    let peak = left[1000..].iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    assert!((peak - 0.5).abs() < 0.01, "{peak}");
+}
+
+#[test]
+fn resampled_sources_can_be_built_with_a_block_size() {
+    let probe = Probe::default();
+    let mut source = ResampledSource::with_block(Box::new(probe.clone()), 2, 24_000, 48_000, FRAMES, 16);
+    assert_eq!((source.native_rate(), source.sample_rate()), (24_000, 48_000));
+
+    // pulled 16 frames at a time, so the first block asks for barely more than it needs
+    let (mut left, mut right) = (vec![0.0; FRAMES], vec![0.0; FRAMES]);
+    source.render(&mut [&mut left, &mut right], FRAMES, SAMPLE_RATE);
+    let rendered = probe.frames.load(Ordering::Relaxed);
+    assert!((FRAMES / 2..=FRAMES / 2 + 32).contains(&rendered), "{rendered}");
+    assert_eq!(left, right);
+    assert!(left[16..].iter().all(|&s| (s - 0.5).abs() < 1e-3));
+    assert!(source.is_active());
+}
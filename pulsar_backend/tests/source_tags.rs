@@ -0,0 +1,81 @@
+//! Source and bus tags: bulk mute, solo, gain and removal by tag, both on the
+//! router directly and through its command queue.
+
+mod common;
+
+use pulsar_backend::rt_processing::routing::TagChange;
+
+use common::{CENTRE, Dc, block, router};
+
+const FRAMES: usize = 64;
+
+#[test]
+fn bulk_changes_reach_every_tagged_source() {
+    let mut router = router(1, FRAMES);
+    // drums at 1, 2 and 4, a pad at 8; 0.5 per side at the centre
+    let drums: Vec<_> = [1.0, 2.0, 4.0].map(|level| router.add_source(Box::new(Dc(level)), 1.0, CENTRE, 0)).into();
+    let pad = router.add_source(Box::new(Dc(8.0)), 1.0, CENTRE, 0);
+    for &id in &drums {
+        assert!(router.set_tags(id, &["drums", "rhythm"]));
+    }
+    router.set_tags(drums[2], &["drums", "preview"]);
+    router.set_tags(pad, &["preview"]);
+    assert_eq!(router.tags(drums[0]), ["drums", "rhythm"]);
+    assert_eq!(router.tagged("rhythm"), &drums[..2]);
+    assert_eq!(block(&mut router), 7.5);
+
+    assert_eq!(router.set_mute_tagged("drums", true), 3);
+    assert_eq!(block(&mut router), 4.0);
+    assert_eq!(router.set_mute_tagged("drums", false), 3);
+    assert_eq!(router.set_gain_tagged("rhythm", 0.5), 2);
+    assert_eq!(block(&mut router), 6.75);
+    assert_eq!(router.set_solo_tagged("rhythm", true), 2);
+    assert_eq!(block(&mut router), 0.75);
+    router.set_solo_tagged("rhythm", false);
+
+    // removal is by tag too, and unknown tags match nothing
+    assert_eq!(router.remove_tagged("preview"), 2);
+    assert_eq!(router.tagged("drums"), &drums[..2]);
+    assert_eq!(router.remove_tagged("vocals"), 0);
+    assert_eq!(block(&mut router), 0.75);
+}
+
+#[test]
+fn tag_commands_land_in_the_next_block() {
+    let mut router = router(1, FRAMES);
+    let commands = router.commands();
+    let hats = commands.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 0).unwrap();
+    let kick = commands.add_source(Box::new(Dc(2.0)), 1.0, CENTRE, 0).unwrap();
+    assert!(hats.set_tags(&["drums", "preview"]));
+    assert!(commands.set_tags(kick.id(), &["drums"]));
+    assert_eq!(block(&mut router), 1.5);
+    assert_eq!(router.tagged("drums"), [hats.id(), kick.id()]);
+
+    assert!(commands.set_gain_tagged("drums", 2.0));
+    assert!(commands.set_mute_tagged("preview", true));
+    assert_eq!(block(&mut router), 2.0);
+    assert!(commands.change_tagged("preview", TagChange::Remove));
+    assert_eq!(block(&mut router), 2.0);
+    assert_eq!(router.tagged("drums"), [kick.id()]);
+    assert_eq!(router.index_of(hats.id()), None);
+}
+
+#[test]
+fn buses_are_tagged_apart_from_sources() {
+    let mut router = router(4, FRAMES);
+    assert!(router.set_bus_tags(1, &["fx"]));
+    assert!(router.set_bus_tags(2, &["fx", "reverb"]));
+    assert!(router.set_bus_tags(0, &["fx"]));
+    assert!(!router.set_bus_tags(4, &["fx"]));
+    assert_eq!(router.bus_tags(2), ["fx", "reverb"]);
+    assert!(router.bus_tags(4).is_empty());
+    assert_eq!(router.tagged_buses("fx"), [0, 1, 2]);
+    assert!(router.tagged("fx").is_empty());
+
+    // the master bus can't be switched off or have a return
+    assert_eq!(router.set_bus_enabled_tagged("fx", false), 2);
+    assert!(!router.is_bus_enabled(1) && !router.is_bus_enabled(2) && router.is_bus_enabled(3));
+    assert_eq!(router.set_bus_return_tagged("reverb", 0.5), 1);
+    assert_eq!(router.bus_return(2), Some(0.5));
+    assert_eq!(router.bus_return(1), Some(1.0));
+}
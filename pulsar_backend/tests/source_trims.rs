@@ -0,0 +1,63 @@
+//! Per-source settings addressed by `SourceId`: trims and listen land on the
+//! source they name after earlier sources are removed, set on the router
+//! directly and through its command queue.
+
+mod common;
+
+use pulsar_backend::rt_processing::filters::{Trim, TrimSlope};
+use pulsar_backend::rt_processing::routing::{ListenMode, Router, SourceId};
+
+use common::{CENTRE, Dc, block, router};
+
+const FRAMES: usize = 256;
+
+/// High-pass well above anything a DC source plays
+fn block_dc() -> Option<Trim> {
+    Some(Trim::new(1_000.0, TrimSlope::Db12))
+}
+
+/// Level after the trims have settled
+fn settled(router: &mut Router) -> f32 {
+    (0..8).map(|_| block(router)).last().unwrap()
+}
+
+#[test]
+fn trims_follow_the_id_after_removals() {
+    let mut router = router(1, FRAMES);
+    let [a, b, c]: [SourceId; 3] = [1.0, 2.0, 4.0].map(|level| router.add_source(Box::new(Dc(level)), 1.0, CENTRE, 0));
+    assert!(router.remove(a));
+    assert!((settled(&mut router) - 3.0).abs() < 1e-5);
+
+    // `c` is now second in the list, and `b` first
+    assert!(router.set_high_pass_trim(c, block_dc()));
+    assert!((settled(&mut router) - 1.0).abs() < 1e-3);
+    assert!(router.set_high_pass_trim(c, None));
+    assert!(router.set_low_pass_trim(b, Some(Trim::new(10_000.0, TrimSlope::Db12))));
+    assert!((settled(&mut router) - 3.0).abs() < 1e-3);
+    assert!(!router.set_high_pass_trim(a, block_dc()));
+    assert!(!router.set_listen(a, Some(ListenMode::Pfl)));
+
+    assert!(router.set_listen(c, Some(ListenMode::Afl)));
+    assert_eq!(router.listen(c), Some(ListenMode::Afl));
+    assert_eq!(router.listen(b), None);
+}
+
+#[test]
+fn trims_and_listen_go_through_the_queue() {
+    let mut router = router(1, FRAMES);
+    let commands = router.commands();
+    let a = commands.add_source(Box::new(Dc(1.0)), 1.0, CENTRE, 0).unwrap();
+    let b = commands.add_source(Box::new(Dc(2.0)), 1.0, CENTRE, 0).unwrap();
+    assert!((settled(&mut router) - 1.5).abs() < 1e-5);
+    assert!(a.remove());
+
+    assert!(b.set_high_pass_trim(block_dc()));
+    assert!(b.set_listen(Some(ListenMode::Pfl)));
+    assert!(settled(&mut router).abs() < 1e-3);
+    assert_eq!(router.listen(b.id()), Some(ListenMode::Pfl));
+
+    assert!(b.set_trims(None, None));
+    assert!(commands.set_listen(b.id(), None));
+    assert!((settled(&mut router) - 1.0).abs() < 1e-3);
+    assert_eq!(router.listen(b.id()), None);
+}
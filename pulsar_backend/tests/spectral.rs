@@ -0,0 +1,187 @@
+//! STFT effects: overlap-add resynthesis, latency reporting, spectral
+//! processors written as callbacks, and the spectral freeze built on them.
+
+use std::f32::consts::TAU;
+
+use pulsar_backend::rt_processing::effects::chain::EffectChain;
+use pulsar_backend::rt_processing::effects::spectral::{
+    PerBin, SpectralContext, SpectralFreeze, StftConfig, StftEffect,
+};
+use pulsar_backend::rt_processing::effects::Effect;
+use pulsar_backend::rt_processing::fft::{Complex, Window};
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const BLOCK: usize = 100;
+
+/// Two tones and a ramp, so misplaced samples show
+fn signal(len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE;
+            0.4 * (TAU * 440.0 * t).sin() + 0.2 * (TAU * 3_100.0 * t).sin() + (i % 97) as f32 / 970.0
+        })
+        .collect()
+}
+
+/// Run a stereo signal through `effect` in odd-sized blocks; returns the left channel
+fn run(effect: &mut dyn Effect, input: &[f32]) -> Vec<f32> {
+    let (mut left, mut right) = (input.to_vec(), input.to_vec());
+    for start in (0..input.len()).step_by(BLOCK) {
+        let end = (start + BLOCK).min(input.len());
+        let mut buffer = [&mut left[start..end], &mut right[start..end]];
+        effect.process(&mut buffer, end - start, SAMPLE_RATE);
+    }
+    assert_eq!(left, right);
+    left
+}
+
+/// Run a mono signal through `effect` in blocks
+fn run_mono(effect: &mut dyn Effect, input: &[f32]) -> Vec<f32> {
+    let mut output = input.to_vec();
+    for block in output.chunks_mut(BLOCK) {
+        let frames = block.len();
+        effect.process(&mut [block], frames, SAMPLE_RATE);
+    }
+    output
+}
+
+fn identity(_: &mut [Complex], _: &SpectralContext) {}
+
+#[test]
+fn untouched_spectra_reproduce_the_input_late() {
+    let configs = [
+        StftConfig::default(),
+        StftConfig { fft_size: 256, overlap: 2, window: Window::Hamming },
+        StftConfig { fft_size: 512, overlap: 8, window: Window::Blackman },
+        StftConfig { fft_size: 128, overlap: 1, window: Window::Rectangular },
+    ];
+    let input = signal(8_000);
+    for config in configs {
+        let mut effect = StftEffect::new(2, config, identity);
+        let latency = effect.latency();
+        assert_eq!(latency, config.fft_size - 1);
+        assert_eq!(Effect::latency(&effect), latency);
+        let output = run(&mut effect, &input);
+        assert!(output[..latency].iter().all(|&s| s.abs() < 1e-4), "{config:?}");
+        for (i, (&out, &original)) in output[latency..].iter().zip(&input).enumerate() {
+            assert!((out - original).abs() < 1e-4, "{config:?} at {i}: {out} vs {original}");
+        }
+    }
+}
+
+#[test]
+fn sizes_round_up_and_hops_follow_the_overlap() {
+    let effect = StftEffect::new(1, StftConfig { fft_size: 1000, overlap: 4, window: Window::Hann }, identity);
+    assert_eq!((effect.fft_size(), effect.hop()), (1024, 256));
+    let effect = StftEffect::new(1, StftConfig { fft_size: 64, overlap: 0, window: Window::Rectangular }, identity);
+    assert_eq!(effect.hop(), 64);
+}
+
+#[test]
+fn per_bin_callbacks_shape_the_spectrum() {
+    // keep everything below 1 kHz: the 3.1 kHz tone goes, the 440 Hz one stays
+    let low_pass = PerBin(|bin: Complex, index: usize, context: &SpectralContext| {
+        if context.bin_frequency(index) < 1_000.0 { bin } else { Complex::ZERO }
+    });
+    let tones: Vec<f32> = (0..16_000)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE;
+            0.4 * (TAU * 440.0 * t).sin() + 0.4 * (TAU * 3_100.0 * t).sin()
+        })
+        .collect();
+    let mut effect = StftEffect::new(2, StftConfig::default(), low_pass);
+    let latency = effect.latency();
+    let output = run(&mut effect, &tones);
+    let error = output[latency + 2_048..]
+        .iter()
+        .enumerate()
+        .map(|(i, &s)| (s - 0.4 * (TAU * 440.0 * (i + 2_048) as f32 / SAMPLE_RATE).sin()).abs())
+        .fold(0.0, f32::max);
+    assert!(error < 0.02, "{error}");
+
+    // a gate with nothing above its threshold is silent
+    let mut gate = StftEffect::new(2, StftConfig::default(), |bins: &mut [Complex], _: &SpectralContext| {
+        bins.iter_mut().filter(|bin| bin.norm() < 1_000.0).for_each(|bin| *bin = Complex::ZERO)
+    });
+    assert!(run(&mut gate, &tones).iter().all(|&s| s == 0.0));
+}
+
+#[test]
+fn processors_see_every_channel_and_reset_starts_over() {
+    let config = StftConfig { fft_size: 64, overlap: 4, window: Window::Hann };
+    let input = signal(96);
+    let mut frames = Vec::new();
+    let mut effect = StftEffect::new(2, config, |bins: &mut [Complex], context: &SpectralContext| {
+        assert_eq!(bins.len(), 33);
+        frames.push((context.channel, context.frame));
+    });
+    let first = run(&mut effect, &input);
+    effect.reset();
+    assert_eq!(run(&mut effect, &input), first);
+    drop(effect);
+    // a frame every 16-sample hop, the first once one hop has come in; twice over
+    let once: Vec<(usize, u64)> = (0..6).flat_map(|frame| [(0, frame), (1, frame)]).collect();
+    assert_eq!(frames, [once.clone(), once].concat());
+}
+
+#[test]
+fn chains_report_the_sum_of_their_latencies() {
+    let mut chain = EffectChain::new();
+    assert_eq!(chain.latency(), 0);
+    chain.push(Box::new(StftEffect::new(2, StftConfig::default(), identity)));
+    chain.push(Box::new(StftEffect::new(2, StftConfig { fft_size: 256, ..StftConfig::default() }, identity)));
+    assert_eq!(chain.latency(), 1023 + 255);
+    chain.set_bypassed(true);
+    assert_eq!(chain.latency(), 0);
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+/// Strongest frequency in `samples`, to the nearest 10 Hz, by correlation
+fn dominant(samples: &[f32]) -> f32 {
+    (10..200)
+        .map(|n| n as f32 * 10.0)
+        .map(|f| {
+            let (re, im) = samples.iter().enumerate().fold((0.0, 0.0), |(re, im), (i, &s)| {
+                let (sin, cos) = (TAU * f * i as f32 / SAMPLE_RATE).sin_cos();
+                (re + s * cos, im + s * sin)
+            });
+            (f, re * re + im * im)
+        })
+        .fold((0.0, 0.0), |best, (f, power)| if power > best.1 { (f, power) } else { best })
+        .0
+}
+
+#[test]
+fn frozen_spectra_sustain_until_the_gate_closes() {
+    let config = StftConfig::default();
+    assert_eq!(config.bins(), 513);
+    // channels get their own phases, so run one
+    let mut freeze = SpectralFreeze::effect(1, config);
+    let gate = freeze.processor().gate_handle();
+    assert!(!freeze.processor().is_frozen());
+
+    // closed: audio passes through, late
+    let tone: Vec<f32> = (0..9_600).map(|i| 0.5 * (TAU * 440.0 * i as f32 / SAMPLE_RATE).sin()).collect();
+    let output = run_mono(&mut freeze, &tone);
+    let latency = freeze.latency();
+    assert!(output[latency..].iter().zip(&tone).all(|(out, input)| (out - input).abs() < 1e-4));
+
+    // open, then the input stops: the tone carries on
+    gate.store(true, std::sync::atomic::Ordering::Relaxed);
+    assert!(freeze.processor().is_frozen());
+    run_mono(&mut freeze, &tone[..2_048]);
+    let held = run_mono(&mut freeze, &vec![0.0; 48_000]);
+    let level = rms(&held[24_000..]);
+    assert!(level > 0.1 && level < 0.6, "{level}");
+    // within a bin of the tone: random phases blur it across its neighbours
+    let frequency = dominant(&held[40_000..44_096]);
+    assert!((frequency - 440.0).abs() <= SAMPLE_RATE / 1024.0, "{frequency}");
+
+    // closed again: back to the (silent) input within a frame and the latency
+    freeze.processor().set_frozen(false);
+    let released = run_mono(&mut freeze, &vec![0.0; 9_600]);
+    assert!(released[2 * latency..].iter().all(|&s| s.abs() < 1e-4));
+}
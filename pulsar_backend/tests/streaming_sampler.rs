@@ -0,0 +1,112 @@
+//! Streaming playback: files read ahead from disk, looped and converted to
+//! the output rate.
+
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pulsar_backend::io::wav::{self, SampleFormat, WavSpec};
+use pulsar_backend::rt_processing::voice_renderer::AudioSource;
+use pulsar_backend::rt_processing::waveform::sampler::StreamingSamplePlayer;
+
+const BLOCK: usize = 256;
+
+fn write(name: &str, channels: &[Vec<f32>], sample_rate: u32) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("pulsar-streaming-{}-{}.wav", name, std::process::id()));
+    let spec = WavSpec { sample_rate, format: SampleFormat::Float32, dither: false };
+    wav::write_file(&path, channels, spec).unwrap();
+    path
+}
+
+/// Stereo file whose frame `i` holds `i / 1000` and its negation
+fn ramp(name: &str, frames: usize) -> PathBuf {
+    let left: Vec<f32> = (0..frames).map(|i| i as f32 / 1000.0).collect();
+    let right = left.iter().map(|s| -s).collect();
+    write(name, &[left, right], 48_000)
+}
+
+/// One stereo block, once the worker has it ready (or has nothing left)
+fn play(player: &mut StreamingSamplePlayer) -> Vec<f32> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while player.buffered_frames() < BLOCK && !player.is_fully_buffered() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(1));
+    }
+    let mut output = vec![0.0; BLOCK * 2];
+    player.fill_buffer(&mut output, 48_000.0, 2, BLOCK);
+    output
+}
+
+#[test]
+fn long_files_stream_through_a_small_ring() {
+    let frames = 20_000;
+    let path = ramp("plain", frames);
+    // a ring far smaller than the file
+    let mut player = StreamingSamplePlayer::with_read_ahead(&path, 48_000, 0.1).unwrap();
+    assert_eq!(player.channels(), 2);
+    assert!(player.buffered_frames() > 0, "the first read-ahead is queued on open");
+
+    let mut played = Vec::new();
+    while player.is_active() {
+        played.extend(play(&mut player));
+    }
+    assert_eq!(player.underruns(), 0);
+    assert!(player.error().is_none());
+    assert!(played.len() >= frames * 2);
+    for (i, frame) in played.chunks(2).enumerate() {
+        let expected = if i < frames { i as f32 / 1000.0 } else { 0.0 };
+        assert_eq!(frame, [expected, -expected], "frame {i}");
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn looping_pausing_and_restarting() {
+    let frames = 1000;
+    let path = ramp("loop", frames);
+    let mut player = StreamingSamplePlayer::with_read_ahead(&path, 48_000, 0.1).unwrap().with_looping(true);
+    let mut played = Vec::new();
+    for _ in 0..10 {
+        played.extend(play(&mut player));
+    }
+    assert!(player.is_active());
+    for (i, frame) in played.chunks(2).enumerate() {
+        assert_eq!(frame[0], (i % frames) as f32 / 1000.0, "frame {i}");
+    }
+
+    // paused, it plays silence and holds its place
+    player.pause();
+    assert!(play(&mut player).iter().all(|&s| s == 0.0));
+    player.play();
+    assert_eq!(play(&mut player)[0], (10 * BLOCK % frames) as f32 / 1000.0);
+
+    // a restart comes through once what's queued has played
+    player.set_looping(false);
+    player.restart();
+    let mut played = Vec::new();
+    while player.is_active() {
+        played.extend(play(&mut player));
+    }
+    // the whole file, then silence to the end of the block
+    let end = played.iter().rposition(|&s| s != 0.0).unwrap() / 2 + 1;
+    let file: Vec<f32> = played[(end - frames) * 2..end * 2].iter().step_by(2).copied().collect();
+    assert_eq!(file, (0..frames).map(|i| i as f32 / 1000.0).collect::<Vec<_>>());
+    assert_eq!(player.underruns(), 0);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn files_are_converted_to_the_output_rate() {
+    let path = write("rate", &[vec![0.25; 4800]], 24_000);
+    let mut player = StreamingSamplePlayer::open(&path, 48_000).unwrap().with_gain(2.0);
+    let mut played = Vec::new();
+    while player.is_active() {
+        played.extend(play(&mut player));
+    }
+    // mono on both channels, twice the frames, at the file's level
+    assert!(played.chunks(2).all(|frame| frame[0] == frame[1]));
+    let frames = played.len() / 2;
+    assert!((9600..9600 + 2 * BLOCK).contains(&frames), "{frames}");
+    assert!(played[2000..18000].iter().all(|&s| (s - 0.5).abs() < 0.01));
+    std::fs::remove_file(&path).unwrap();
+    assert!(StreamingSamplePlayer::open(&path, 48_000).is_err());
+}
@@ -0,0 +1,96 @@
+//! Talkback: while keyed, the chosen channel of the talkback input is mixed
+//! onto the listen bus and the cue signal under it is ducked; the main output
+//! never hears it and isn't dimmed.
+
+mod common;
+
+use pulsar_backend::rt_processing::routing::{AudioSource, ListenMode, Router};
+
+use common::{CENTRE, Dc, router};
+
+const FRAMES: usize = 256;
+
+/// A microphone at a different level on each channel
+struct Mic;
+
+impl AudioSource for Mic {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        for (channel, level) in output.iter_mut().zip([0.1, 0.3]) {
+            channel[..frames].fill(level);
+        }
+    }
+}
+
+/// Left channel of the last frame of the main output and the listen bus,
+/// after enough blocks for the key ramp to settle
+fn outputs(router: &mut Router) -> (f32, f32) {
+    let (mut output, mut listen) = (vec![0.0; FRAMES * 2], vec![0.0; FRAMES * 2]);
+    for _ in 0..16 {
+        router.process_with_listen(&mut output, &mut listen, None);
+    }
+    (output[(FRAMES - 1) * 2], listen[(FRAMES - 1) * 2])
+}
+
+/// Main mix at 1.0
+fn mix() -> Router {
+    let router = router(1, FRAMES);
+    router.add_source(Box::new(Dc(2.0)), 1.0, CENTRE, 0);
+    router
+}
+
+#[test]
+fn keyed_talkback_reaches_the_listen_bus_only() {
+    let mut router = mix();
+    let talkback = router.set_talkback(Box::new(Mic), 1);
+    assert!(!talkback.is_keyed());
+    assert_eq!(outputs(&mut router), (1.0, 1.0));
+
+    // -20 dB on the cue, the mic's second channel on top
+    talkback.set_keyed(true);
+    let (main, listen) = outputs(&mut router);
+    assert_eq!(main, 1.0);
+    assert!((listen - (0.1 + 0.3)).abs() < 1e-3, "{listen}");
+
+    talkback.set_gain(2.0);
+    talkback.set_duck_db(f32::NEG_INFINITY);
+    let (main, listen) = outputs(&mut router);
+    assert_eq!(main, 1.0);
+    assert!((listen - 0.6).abs() < 1e-3, "{listen}");
+
+    // released, the cue comes back up and the mic goes away
+    talkback.set_keyed(false);
+    let (main, listen) = outputs(&mut router);
+    assert_eq!(main, 1.0);
+    assert!((listen - 1.0).abs() < 1e-3, "{listen}");
+}
+
+#[test]
+fn talkback_ducks_a_listened_source_too() {
+    let mut router = mix();
+    let source = router.add_source(Box::new(Dc(4.0)), 0.0, CENTRE, 0);
+    assert!(router.set_listen(source, Some(ListenMode::Pfl)));
+    let talkback = router.set_talkback(Box::new(Mic), 0);
+    talkback.set_keyed(true);
+    let (main, listen) = outputs(&mut router);
+    assert_eq!(main, 1.0);
+    assert!((listen - (0.4 + 0.1)).abs() < 1e-3, "{listen}");
+}
+
+#[test]
+fn the_main_mix_is_the_same_with_talkback_keyed() {
+    let (mut keyed, mut reference) = (mix(), mix());
+    keyed.set_talkback(Box::new(Mic), 0).set_keyed(true);
+    let (mut output, mut listen) = (vec![0.0; FRAMES * 2], vec![0.0; FRAMES * 2]);
+    let mut expected = vec![0.0; FRAMES * 2];
+    for _ in 0..4 {
+        keyed.process_with_listen(&mut output, &mut listen, None);
+        reference.process(&mut expected, None);
+        assert_eq!(output, expected);
+        assert_ne!(listen, expected);
+    }
+
+    // cleared, the listen bus follows the main mix again
+    keyed.clear_talkback();
+    keyed.process_with_listen(&mut output, &mut listen, None);
+    assert_eq!(listen, output);
+}
@@ -0,0 +1,155 @@
+//! Transport clock: bars and beats conversion, play/stop/locate and tempo
+//! changes landing at block boundaries, and sources reading the playhead of
+//! the block they render.
+
+use std::sync::{Arc, Mutex};
+
+use pulsar_backend::engine::{Engine, EngineConfig, EngineError};
+use pulsar_backend::project::TransportSettings;
+use pulsar_backend::rt_processing::routing::{AudioSource, Pan, PanLaw, Router};
+use pulsar_backend::rt_processing::transport::{MusicalTime, TimeSignature, Transport, TransportState};
+
+const FRAMES: usize = 256;
+const SAMPLE_RATE: f32 = 48_000.0;
+
+/// Transport state and length of every render
+type Renders = Arc<Mutex<Vec<(TransportState, usize)>>>;
+
+/// Silent; notes what it sees in `Renders`
+struct Probe {
+    transport: Transport,
+    seen: Renders,
+}
+
+impl AudioSource for Probe {
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        self.seen.lock().unwrap().push((self.transport.state(), frames));
+        for channel in output.iter_mut() {
+            channel[..frames].fill(0.0);
+        }
+    }
+}
+
+fn router_with_probe(transport: &Transport) -> (Router, Renders) {
+    let mut router = Router::new(2, SAMPLE_RATE, 1, FRAMES);
+    router.set_transport(Some(transport.clone()));
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let probe = Probe { transport: transport.clone(), seen: Arc::clone(&seen) };
+    router.add_source(Box::new(probe), 1.0, Pan { value: 0.0, law: PanLaw::Linear }, 0);
+    (router, seen)
+}
+
+fn block(router: &mut Router) {
+    router.process(&mut vec![0.0; FRAMES * 2], None);
+}
+
+fn time(bar: u64, beat: u32, fraction: f64) -> MusicalTime {
+    MusicalTime { bar, beat, fraction }
+}
+
+#[test]
+fn signatures_group_quarter_notes_into_bars() {
+    let common = TimeSignature::default();
+    assert_eq!(common.bar_length(), 4.0);
+    assert_eq!(common.musical_time(9.5), time(2, 1, 0.5));
+    assert_eq!(common.beats(time(2, 1, 0.5)), 9.5);
+
+    // 6/8 counts eighths: a bar is three quarter notes
+    let compound = TimeSignature::new(6, 8);
+    assert_eq!(compound.bar_length(), 3.0);
+    assert_eq!(compound.musical_time(4.25), time(1, 2, 0.5));
+    assert_eq!(compound.beats(time(1, 2, 0.5)), 4.25);
+
+    let state = TransportState {
+        playing: true,
+        frame: 48_000,
+        beats: 2.0,
+        tempo_bpm: 120.0,
+        signature: common,
+        sample_rate: SAMPLE_RATE,
+    };
+    // two quarter notes a second
+    assert_eq!(state.beats_to_frames(1.0), 24_000.0);
+    assert_eq!(state.beats_at_frame(60_000), 2.5);
+    assert_eq!(state.beats_at_frame(0), 0.0);
+    assert_eq!(state.frame_at_beats(4.0), 96_000.0);
+    assert_eq!(state.musical_time_at_frame(120_000), time(1, 1, 0.0));
+    assert_eq!(state.phase(4.0, 12_000), 0.625);
+}
+
+#[test]
+fn the_router_moves_the_playhead_while_playing() {
+    let transport = Transport::new(SAMPLE_RATE).with_tempo(120.0);
+    let (mut router, seen) = router_with_probe(&transport);
+    block(&mut router);
+    assert_eq!(transport.state().frame, 0);
+    assert!(transport.play());
+    // changes land with the next block
+    assert!(!transport.is_playing());
+    for _ in 0..4 {
+        block(&mut router);
+    }
+    // the router mixes on its control grid, and the probe sees where each
+    // piece starts
+    let mut expected = 0;
+    for (state, frames) in seen.lock().unwrap().iter().skip_while(|(state, _)| !state.playing) {
+        assert!(state.playing);
+        assert_eq!(state.frame, expected);
+        assert!((state.beats - state.frames_to_beats(expected as f64)).abs() < 1e-12);
+        expected += *frames as u64;
+    }
+    assert_eq!(expected, 4 * FRAMES as u64);
+
+    // stopped, it stays where it was
+    transport.stop();
+    block(&mut router);
+    block(&mut router);
+    assert_eq!((transport.state().frame, transport.is_playing()), (expected, false));
+}
+
+#[test]
+fn locates_and_tempo_changes_keep_the_beat() {
+    let transport = Transport::new(SAMPLE_RATE).with_signature(TimeSignature::new(3, 4));
+    let (mut router, _) = router_with_probe(&transport);
+    // whole blocks, so `state` stays at the start of the last one
+    router.set_control_block(0);
+    transport.locate_musical(time(2, 1, 0.0));
+    transport.play();
+    block(&mut router);
+    let state = transport.state();
+    assert_eq!((state.beats, state.musical_time()), (7.0, time(2, 1, 0.0)));
+    assert_eq!(state.frame, 168_000);
+
+    // double time: the playhead keeps its beat and moves twice as fast
+    transport.set_tempo(240.0);
+    block(&mut router);
+    let before = transport.state();
+    assert_eq!(before.tempo_bpm, 240.0);
+    block(&mut router);
+    let after = transport.state();
+    assert!((after.beats - before.beats - FRAMES as f64 * 4.0 / SAMPLE_RATE as f64).abs() < 1e-12);
+
+    // locating by frame takes the beat at the tempo then
+    transport.locate(24_000);
+    block(&mut router);
+    assert_eq!((transport.state().frame, transport.state().beats), (24_000, 2.0));
+}
+
+#[test]
+fn the_engine_clock_follows_the_project() {
+    let mut engine = Engine::new();
+    assert!(matches!(engine.clock(), Err(EngineError::NotConfigured(_))));
+    engine.set_transport(TransportSettings { tempo_bpm: 90.0, beats_per_bar: 7, beat_unit: 8 });
+    engine.configure(EngineConfig::default()).unwrap();
+    let clock = engine.clock().unwrap();
+    assert_eq!(clock.state().tempo_bpm, 90.0);
+    assert_eq!(clock.state().signature, TimeSignature::new(7, 8));
+
+    engine.set_transport(TransportSettings::default());
+    clock.play();
+    let frames = engine.config().unwrap().max_frames;
+    engine.with_processor(|p| p.router_mut().process(&mut vec![0.0; frames * 2], None)).unwrap();
+    let state = clock.state();
+    assert!(state.playing);
+    assert_eq!((state.tempo_bpm, state.signature), (120.0, TimeSignature::default()));
+}
@@ -0,0 +1,99 @@
+//! Voice stealing: a full pool takes the oldest voice, fading it out over
+//! `steal_fade` before the new note starts, and blocks longer than the pool's
+//! `max_frames` are rendered whole.
+
+mod common;
+
+use pulsar_backend::rt_processing::routing::AudioSource;
+use pulsar_backend::rt_processing::voices::{DEFAULT_STEAL_FADE_SECONDS, Voice, VoicePool};
+
+use common::close;
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const MAX_FRAMES: usize = 64;
+
+/// Plays its velocity as DC until released
+struct Dc {
+    level: f32,
+}
+
+impl Voice for Dc {
+    fn note_on(&mut self, _note: u8, velocity: f32) {
+        self.level = velocity;
+    }
+
+    fn note_off(&mut self) {
+        self.level = 0.0;
+    }
+
+    fn render(&mut self, output: &mut [&mut [f32]], frames: usize, _sample_rate: f32) {
+        for channel in output.iter_mut() {
+            channel[..frames].fill(self.level);
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.level > 0.0
+    }
+
+    fn reset(&mut self) {
+        self.level = 0.0;
+    }
+}
+
+fn pool(voices: usize) -> VoicePool {
+    let voices = (0..voices).map(|_| Box::new(Dc { level: 0.0 }) as Box<dyn Voice>).collect();
+    VoicePool::new(voices, 1, MAX_FRAMES)
+}
+
+fn render(pool: &mut VoicePool, frames: usize) -> Vec<f32> {
+    let mut output = vec![f32::NAN; frames];
+    pool.render(&mut [&mut output], frames, SAMPLE_RATE);
+    output
+}
+
+#[test]
+fn the_oldest_voice_is_stolen() {
+    let mut pool = pool(2).with_steal_fade(0.0);
+    pool.note_on(60, 0.1);
+    pool.note_on(61, 0.2);
+    assert!(render(&mut pool, MAX_FRAMES).iter().all(|&s| close(s, 0.3, 1e-6)));
+
+    // a hard cut hands the voice over at once
+    pool.note_on(62, 0.4);
+    assert!(render(&mut pool, MAX_FRAMES).iter().all(|&s| close(s, 0.6, 1e-6)));
+    // the stolen note no longer answers, the others still do
+    pool.note_off(60);
+    assert_eq!(pool.active_voices(), 2);
+    pool.note_off(61);
+    assert!(render(&mut pool, MAX_FRAMES).iter().all(|&s| close(s, 0.4, 1e-6)));
+    assert_eq!(pool.active_voices(), 1);
+}
+
+#[test]
+fn stolen_voices_fade_out_before_the_new_note() {
+    let mut pool = pool(2);
+    assert_eq!(pool.steal_fade(), DEFAULT_STEAL_FADE_SECONDS);
+    let fade = (DEFAULT_STEAL_FADE_SECONDS * SAMPLE_RATE) as usize;
+    pool.note_on(60, 0.1);
+    pool.note_on(61, 0.2);
+    render(&mut pool, MAX_FRAMES);
+    pool.note_on(62, 0.4);
+    assert_eq!(pool.active_voices(), 2);
+
+    // eight pieces of `MAX_FRAMES`: the fade runs into the fourth, and the
+    // new note starts with the fifth
+    let output = render(&mut pool, MAX_FRAMES * 8);
+    assert!(close(output[0], 0.2 + 0.1 * (fade - 1) as f32 / fade as f32, 1e-6));
+    assert!(output[..fade].windows(2).all(|pair| pair[1] < pair[0]));
+    assert!(output[fade - 1..MAX_FRAMES * 4].iter().all(|&s| close(s, 0.2, 1e-6)));
+    assert!(output[MAX_FRAMES * 4..].iter().all(|&s| close(s, 0.6, 1e-6)));
+}
+
+#[test]
+fn long_blocks_are_rendered_whole() {
+    let mut pool = pool(1);
+    pool.note_on(60, 0.5);
+    let output = render(&mut pool, MAX_FRAMES * 3 + 17);
+    assert!(output.iter().all(|&s| s == 0.5));
+}
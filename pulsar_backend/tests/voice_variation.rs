@@ -0,0 +1,145 @@
+//! Trigger variation in the sampler and oscillator voices: every `Humanize`
+//! range reaches the voice, as a start offset, a detune and a round-robin
+//! alternate, and each trigger draws something different within it.
+
+use std::sync::Arc;
+
+use pulsar_backend::io::sample_cache::SampleBuffer;
+use pulsar_backend::io::wav::WavAudio;
+use pulsar_backend::rt_processing::routing::AudioSource;
+use pulsar_backend::rt_processing::voices::{Humanize, TriggerVariation, Voice, VoicePool};
+use pulsar_backend::rt_processing::waveform::envelopes::ADSREnvelope;
+use pulsar_backend::rt_processing::waveform::oscillators::OscillatorVoice;
+use pulsar_backend::rt_processing::waveform::sampler::SamplerVoice;
+use pulsar_backend::rt_processing::waveform::tables::WaveformType;
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const MAX_FRAMES: usize = 64;
+const TRIGGERS: usize = 64;
+
+/// Full level from the first frame until released
+fn gate() -> ADSREnvelope {
+    ADSREnvelope::new(0.0, 0.0, 1.0, 0.0)
+}
+
+/// One second whose value is its time in seconds
+fn ramp() -> Arc<SampleBuffer> {
+    let ramp = (0..48_000).map(|frame| frame as f32 / SAMPLE_RATE).collect();
+    Arc::new(SampleBuffer::from_audio("ramp", WavAudio { channels: vec![ramp], sample_rate: 48_000 }))
+}
+
+fn dc(level: f32) -> Arc<SampleBuffer> {
+    Arc::new(SampleBuffer::from_audio("dc", WavAudio { channels: vec![vec![level; 4800]], sample_rate: 48_000 }))
+}
+
+fn pool(voice: impl Voice + 'static, humanize: Humanize) -> VoicePool {
+    VoicePool::new(vec![Box::new(voice)], 1, MAX_FRAMES).with_steal_fade(0.0).with_humanize(humanize, 7)
+}
+
+/// The first block of each of `TRIGGERS` repeats of `note`
+fn triggers(pool: &mut VoicePool, note: u8) -> Vec<Vec<f32>> {
+    (0..TRIGGERS)
+        .map(|_| {
+            pool.note_on(note, 1.0);
+            let mut output = vec![0.0; MAX_FRAMES];
+            pool.render(&mut [&mut output], MAX_FRAMES, SAMPLE_RATE);
+            output
+        })
+        .collect()
+}
+
+/// Change per frame over a block
+fn slope(block: &[f32]) -> f32 {
+    (block[MAX_FRAMES - 1] - block[0]) / (MAX_FRAMES - 1) as f32
+}
+
+fn cents(ratio: f32) -> f32 {
+    1200.0 * ratio.log2()
+}
+
+/// Every value in `range`, and spread over at least half of it
+fn spans(values: &[f32], low: f32, high: f32) -> bool {
+    let (min, max) = values.iter().fold((f32::MAX, f32::MIN), |(min, max), &v| (min.min(v), max.max(v)));
+    min >= low && max <= high && max - min >= (high - low) / 2.0
+}
+
+#[test]
+fn sampler_voices_start_late_and_detune_within_range() {
+    let humanize = Humanize { start_offset: 0.1, pitch_cents: 50.0, ..Humanize::default() };
+    let mut pool = pool(SamplerVoice::new(ramp(), 60, gate(), MAX_FRAMES), humanize);
+    let blocks = triggers(&mut pool, 60);
+
+    // the ramp reads back the time it starts at, and its slope the rate
+    let offsets: Vec<f32> = blocks.iter().map(|block| block[0]).collect();
+    assert!(spans(&offsets, 0.0, 0.1 + 1e-4), "{offsets:?}");
+    let detunes: Vec<f32> = blocks.iter().map(|block| cents(slope(block) * SAMPLE_RATE)).collect();
+    assert!(spans(&detunes, -50.5, 50.5), "{detunes:?}");
+}
+
+#[test]
+fn sampler_voices_cycle_their_alternates_per_note() {
+    let voice = SamplerVoice::new(ramp(), 60, gate(), MAX_FRAMES).with_alternates(vec![dc(0.1), dc(0.2), dc(0.3)]);
+    let mut pool = pool(voice, Humanize { round_robin: 3, ..Humanize::default() });
+    let played: Vec<f32> = triggers(&mut pool, 60)[..4].iter().map(|block| block[0]).collect();
+    assert_eq!(played, [0.1, 0.2, 0.3, 0.1]);
+    // another note keeps its own place in the cycle
+    assert_eq!(triggers(&mut pool, 62)[0][0], 0.1);
+}
+
+#[test]
+fn the_pool_varies_the_level_of_any_voice() {
+    let humanize = Humanize { level_db: 6.0, ..Humanize::default() };
+    let mut pool = pool(SamplerVoice::new(ramp(), 60, gate(), MAX_FRAMES), humanize);
+    let blocks = triggers(&mut pool, 60);
+    let gains: Vec<f32> = blocks.iter().map(|block| 20.0 * (slope(block) * SAMPLE_RATE).log10()).collect();
+    assert!(spans(&gains, -6.01, 6.01), "{gains:?}");
+}
+
+#[test]
+fn oscillator_voices_start_late_and_detune_within_range() {
+    // a slow saw: the first sample reads back the phase, the slope the rate
+    let voice = OscillatorVoice::new(WaveformType::Sawtooth, gate()).with_amplitude(1.0);
+    let humanize = Humanize { start_offset: 0.05, pitch_cents: 50.0, ..Humanize::default() };
+    let mut pool = pool(voice, humanize);
+    let frequency = 440.0 * 2f32.powf(-69.0 / 12.0);
+    let blocks = triggers(&mut pool, 0);
+
+    let detunes: Vec<f32> = blocks.iter().map(|block| cents(slope(block) / 2.0 * SAMPLE_RATE / frequency)).collect();
+    assert!(spans(&detunes, -51.0, 51.0), "{detunes:?}");
+    let offsets: Vec<f32> = blocks
+        .iter()
+        .zip(&detunes)
+        .map(|(block, cents)| (block[0] + 1.0) / 2.0 / (frequency * 2f32.powf(cents / 1200.0)))
+        .collect();
+    assert!(spans(&offsets, -1e-3, 0.05 + 1e-3), "{offsets:?}");
+}
+
+#[test]
+fn oscillator_voices_cycle_their_alternates() {
+    let voice = OscillatorVoice::new(WaveformType::Sine, gate())
+        .with_amplitude(1.0)
+        .with_alternates(vec![WaveformType::Sawtooth, WaveformType::Square]);
+    let mut pool = pool(voice, Humanize { round_robin: 2, ..Humanize::default() });
+    let blocks = triggers(&mut pool, 60);
+    // a saw from -1 rising, then a square holding high
+    assert!(blocks[0][0] < -0.9 && blocks[0][1] > blocks[0][0]);
+    assert!(blocks[1].iter().all(|&s| s > 0.9));
+    assert!(blocks[2][0] < -0.9);
+}
+
+#[test]
+fn oscillator_voices_apply_a_variation_exactly() {
+    let alternates = vec![WaveformType::Sine, WaveformType::Triangle];
+    let mut voice = OscillatorVoice::new(WaveformType::Sine, gate()).with_alternates(alternates);
+    let variation = TriggerVariation { start_offset: 0.25 / 880.0, gain: 1.0, detune_cents: 1200.0, round_robin: 3 };
+    voice.vary(&variation);
+    voice.note_on(69, 1.0);
+    let oscillator = voice.oscillator();
+    assert!((oscillator.frequency() - 880.0).abs() < 1e-2);
+    assert!((oscillator.current_phase() - 0.25).abs() < 1e-5);
+    assert_eq!(oscillator.waveform(), WaveformType::Triangle);
+
+    // the detune rides along with a bend
+    voice.pitch_bend(-12.0);
+    assert!((voice.oscillator().frequency() - 440.0).abs() < 1e-2);
+}
@@ -0,0 +1,265 @@
+//! Thin client for a Pulsar engine running in another process.
+//!
+//! The engine process (`pulsar-engine`, or any host using
+//! `pulsar_backend::remote::RemoteServer`) owns the audio device, so a GUI
+//! crash never takes the audio down with it. This crate only depends on serde
+//! and memmap2: it speaks the protocol in `protocol` over the transport in
+//! `transport`, and exchanges audio with the engine through `shm` rings.
+
+pub mod protocol;
+pub mod shm;
+pub mod transport;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub use protocol::{Command, Event, Meter, Node, Reply, Target, Value};
+pub use shm::SharedRing;
+pub use transport::Endpoint;
+
+use protocol::{PROTOCOL_VERSION, Request, ServerMessage, read_message, write_message};
+use transport::Connection;
+
+/// How long a request waits for its response by default
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum ClientError {
+    Io(io::Error),
+    /// The server rejected the command
+    Server(String),
+    /// The connection closed before the response arrived
+    Disconnected,
+    Timeout,
+    /// The server answered with a reply of the wrong type
+    UnexpectedReply(Reply),
+    VersionMismatch { client: u32, server: u32 },
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Connection error: {}", e),
+            Self::Server(msg) => write!(f, "Engine error: {}", msg),
+            Self::Disconnected => write!(f, "Engine disconnected"),
+            Self::Timeout => write!(f, "Engine did not respond in time"),
+            Self::UnexpectedReply(reply) => write!(f, "Unexpected reply: {:?}", reply),
+            Self::VersionMismatch { client, server } => {
+                write!(f, "Protocol version mismatch (client {}, server {})", client, server)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+pub type ClientResult<T> = Result<T, ClientError>;
+
+type Pending = Arc<Mutex<HashMap<u64, Sender<Result<Reply, String>>>>>;
+
+/// Connection to a remote engine.
+///
+/// Requests block until their response arrives (or `timeout` passes); events
+/// and meters are read on a background thread and queued on `events()`.
+/// Shareable between threads.
+pub struct Client {
+    writer: Mutex<Connection>,
+    pending: Pending,
+    events: Mutex<Receiver<Event>>,
+    next_id: AtomicU64,
+    timeout: Duration,
+}
+
+impl Client {
+    /// Connect and check the protocol version
+    pub fn connect(endpoint: &Endpoint) -> ClientResult<Self> {
+        let connection = endpoint.connect()?;
+        let reader = connection.try_clone()?;
+        let pending: Pending = Arc::default();
+        let (event_tx, event_rx) = mpsc::channel();
+
+        let reader_pending = Arc::clone(&pending);
+        thread::Builder::new()
+            .name("pulsar-client-reader".into())
+            .spawn(move || reader_loop(reader, &reader_pending, &event_tx))?;
+
+        let client = Self {
+            writer: Mutex::new(connection),
+            pending,
+            events: Mutex::new(event_rx),
+            next_id: AtomicU64::new(1),
+            timeout: DEFAULT_TIMEOUT,
+        };
+        match client.request(Command::Hello { version: PROTOCOL_VERSION })? {
+            Reply::Hello { version } if version == PROTOCOL_VERSION => Ok(client),
+            Reply::Hello { version } => Err(ClientError::VersionMismatch { client: PROTOCOL_VERSION, server: version }),
+            reply => Err(ClientError::UnexpectedReply(reply)),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Send a command and wait for its reply
+    pub fn request(&self, command: Command) -> ClientResult<Reply> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = mpsc::channel();
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).insert(id, tx);
+
+        let sent = write_message(&mut *self.writer.lock().unwrap_or_else(|e| e.into_inner()), &Request { id, command });
+        if let Err(e) = sent {
+            self.pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+            return Err(e.into());
+        }
+        let result = match rx.recv_timeout(self.timeout) {
+            Ok(result) => result.map_err(ClientError::Server),
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(ClientError::Timeout),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(ClientError::Disconnected),
+        };
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+        result
+    }
+
+    /// Next queued event, waiting up to `timeout`
+    pub fn next_event(&self, timeout: Duration) -> Option<Event> {
+        self.events.lock().unwrap_or_else(|e| e.into_inner()).recv_timeout(timeout).ok()
+    }
+
+    /// Every event queued so far, without waiting
+    pub fn drain_events(&self) -> Vec<Event> {
+        self.events.lock().unwrap_or_else(|e| e.into_inner()).try_iter().collect()
+    }
+
+    pub fn configure(&self, sample_rate: f32, channels: usize, max_frames: usize, num_buses: usize) -> ClientResult<()> {
+        self.expect_ok(Command::Configure { sample_rate, channels, max_frames, num_buses })
+    }
+
+    pub fn start(&self) -> ClientResult<()> {
+        self.expect_ok(Command::Start)
+    }
+
+    pub fn suspend(&self) -> ClientResult<()> {
+        self.expect_ok(Command::Suspend)
+    }
+
+    pub fn resume(&self) -> ClientResult<()> {
+        self.expect_ok(Command::Resume)
+    }
+
+    pub fn stop(&self) -> ClientResult<()> {
+        self.expect_ok(Command::Stop)
+    }
+
+    /// Engine state name (`"running"`, `"suspended"`, ...)
+    pub fn state(&self) -> ClientResult<String> {
+        match self.request(Command::State)? {
+            Reply::State(state) => Ok(state),
+            reply => Err(ClientError::UnexpectedReply(reply)),
+        }
+    }
+
+    pub fn add_source(&self, node: Node, gain: f32, pan: f32, bus: usize) -> ClientResult<()> {
+        self.expect_ok(Command::AddSource { node, gain, pan, bus })
+    }
+
+    pub fn remove_source(&self, index: usize) -> ClientResult<()> {
+        self.expect_ok(Command::RemoveSource(index))
+    }
+
+    pub fn set_params(&self, target: Target, params: Vec<(String, Value)>) -> ClientResult<()> {
+        self.expect_ok(Command::SetParams { target, params })
+    }
+
+    pub fn add_effect(&self, bus: usize, node: Node) -> ClientResult<()> {
+        self.expect_ok(Command::AddEffect { bus, node })
+    }
+
+    pub fn remove_effect(&self, bus: usize, index: usize) -> ClientResult<()> {
+        self.expect_ok(Command::RemoveEffect { bus, index })
+    }
+
+    /// Returns `false` if there was nothing to undo
+    pub fn undo(&self) -> ClientResult<bool> {
+        self.expect_done(Command::Undo)
+    }
+
+    pub fn redo(&self) -> ClientResult<bool> {
+        self.expect_done(Command::Redo)
+    }
+
+    /// Replace the engine's graph with a project document (RON)
+    pub fn load_project(&self, project: impl Into<String>) -> ClientResult<()> {
+        self.expect_ok(Command::LoadProject(project.into()))
+    }
+
+    /// The engine's current project document (RON)
+    pub fn save_project(&self) -> ClientResult<String> {
+        match self.request(Command::SaveProject)? {
+            Reply::Project(project) => Ok(project),
+            reply => Err(ClientError::UnexpectedReply(reply)),
+        }
+    }
+
+    /// Receive `Event::Meter` every `interval`; `Duration::ZERO` stops the stream
+    pub fn stream_meters(&self, interval: Duration) -> ClientResult<()> {
+        let interval_ms = interval.as_millis().min(u32::MAX as u128) as u32;
+        self.expect_ok(Command::Meters { interval_ms })
+    }
+
+    /// Ask the engine process to exit
+    pub fn shutdown_server(&self) -> ClientResult<()> {
+        self.expect_ok(Command::Shutdown)
+    }
+
+    fn expect_ok(&self, command: Command) -> ClientResult<()> {
+        match self.request(command)? {
+            Reply::Ok => Ok(()),
+            reply => Err(ClientError::UnexpectedReply(reply)),
+        }
+    }
+
+    fn expect_done(&self, command: Command) -> ClientResult<bool> {
+        match self.request(command)? {
+            Reply::Done(done) => Ok(done),
+            reply => Err(ClientError::UnexpectedReply(reply)),
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // unblocks the reader thread
+        let _ = self.writer.lock().unwrap_or_else(|e| e.into_inner()).shutdown();
+    }
+}
+
+fn reader_loop(mut reader: Connection, pending: &Pending, events: &Sender<Event>) {
+    while let Ok(message) = read_message::<ServerMessage>(&mut reader) {
+        match message {
+            ServerMessage::Response { id, result } => {
+                if let Some(tx) = pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&id) {
+                    let _ = tx.send(result);
+                }
+            }
+            ServerMessage::Event(event) => {
+                let _ = events.send(event);
+            }
+        }
+    }
+    // fail every waiting request
+    pending.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
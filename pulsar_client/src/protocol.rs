@@ -0,0 +1,146 @@
+//! Messages exchanged between a client and a remote engine.
+//!
+//! Every message is a JSON document preceded by its length as a little-endian
+//! `u32`. Clients send `Request`s; the server answers each with a
+//! `ServerMessage::Response` carrying the same id, and pushes
+//! `ServerMessage::Event`s at any time.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Protocol version, checked by `Command::Hello`
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Largest accepted message
+pub const MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+
+/// A node parameter value
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Value {
+    Float(f32),
+    Int(i64),
+    Bool(bool),
+    Text(String),
+}
+
+/// A source or effect by registered kind plus parameters
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    pub kind: String,
+    #[serde(default)]
+    pub params: BTreeMap<String, Value>,
+}
+
+impl Node {
+    pub fn new(kind: impl Into<String>) -> Self {
+        Self { kind: kind.into(), params: BTreeMap::new() }
+    }
+
+    pub fn with_param(mut self, name: impl Into<String>, value: Value) -> Self {
+        self.params.insert(name.into(), value);
+        self
+    }
+}
+
+/// A node addressed by position in the project
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Target {
+    Source(usize),
+    Effect { bus: usize, index: usize },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    /// Version handshake; answered with `Reply::Hello`
+    Hello { version: u32 },
+    Configure { sample_rate: f32, channels: usize, max_frames: usize, num_buses: usize },
+    Start,
+    Suspend,
+    Resume,
+    Stop,
+    /// Current lifecycle state; answered with `Reply::State`
+    State,
+    AddSource { node: Node, gain: f32, pan: f32, bus: usize },
+    RemoveSource(usize),
+    SetParams { target: Target, params: Vec<(String, Value)> },
+    AddEffect { bus: usize, node: Node },
+    RemoveEffect { bus: usize, index: usize },
+    /// Answered with `Reply::Done(false)` when there was nothing to undo
+    Undo,
+    Redo,
+    /// Replace the graph with a project document (RON)
+    LoadProject(String),
+    /// Answered with `Reply::Project` holding the current project (RON)
+    SaveProject,
+    /// Stream output meters every `interval_ms` to this client; 0 stops
+    Meters { interval_ms: u32 },
+    /// Stop the server process
+    Shutdown,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub id: u64,
+    pub command: Command,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Reply {
+    Ok,
+    Hello { version: u32 },
+    State(String),
+    Done(bool),
+    Project(String),
+}
+
+/// Output levels of the last rendered block, linear
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Meter {
+    pub peak: Vec<f32>,
+    pub rms: Vec<f32>,
+    /// Frames rendered since the engine was configured
+    pub frames: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    /// Engine lifecycle transition, states by name
+    State { from: String, to: String },
+    Meter(Meter),
+    /// The server is shutting down
+    Shutdown,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ServerMessage {
+    Response { id: u64, result: Result<Reply, String> },
+    Event(Event),
+}
+
+/// Write one length-prefixed message
+pub fn write_message<T: Serialize>(writer: &mut impl Write, message: &T) -> io::Result<()> {
+    let body = serde_json::to_vec(message).map_err(io::Error::other)?;
+    if body.len() > MAX_MESSAGE_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "message too large"));
+    }
+    writer.write_all(&(body.len() as u32).to_le_bytes())?;
+    writer.write_all(&body)?;
+    writer.flush()
+}
+
+/// Read one length-prefixed message. Fails with `UnexpectedEof` when the peer
+/// closed the connection.
+pub fn read_message<T: DeserializeOwned>(reader: &mut impl Read) -> io::Result<T> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_MESSAGE_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "message too large"));
+    }
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
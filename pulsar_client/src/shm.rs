@@ -0,0 +1,254 @@
+//! Shared-memory audio ring for exchanging audio blocks with the engine
+//! process at low latency.
+//!
+//! A ring is a memory-mapped file (on Linux under `/dev/shm`, so it never
+//! touches a disk) holding a small header and interleaved `f32` frames. It is
+//! single-producer/single-consumer: one process writes, the other reads, and
+//! the two only synchronize through the atomic read/write positions, so
+//! neither side ever waits on the other.
+//!
+//! The external process creates the ring and tells the engine its path (for
+//! example as the `path` parameter of a `shared_input` source or
+//! `shared_output` effect); the engine opens it.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use memmap2::MmapMut;
+
+const MAGIC: u32 = 0x5253_4c50; // "PLSR"
+const VERSION: u32 = 1;
+
+// header layout; the positions sit on their own cache lines
+const MAGIC_OFFSET: usize = 0;
+const VERSION_OFFSET: usize = 4;
+const CHANNELS_OFFSET: usize = 8;
+const CAPACITY_OFFSET: usize = 12;
+const SAMPLE_RATE_OFFSET: usize = 16;
+const WRITE_POS_OFFSET: usize = 64;
+const OVERRUNS_OFFSET: usize = 72;
+const READ_POS_OFFSET: usize = 128;
+const UNDERRUNS_OFFSET: usize = 136;
+const HEADER_BYTES: usize = 256;
+
+/// Directory used for rings by default: `/dev/shm` where it exists, else the temp dir
+pub fn default_dir() -> PathBuf {
+    let shm = Path::new("/dev/shm");
+    if shm.is_dir() { shm.to_path_buf() } else { std::env::temp_dir() }
+}
+
+/// One direction of interleaved audio between two processes.
+///
+/// Positions count frames since creation; a write that doesn't fit is cut
+/// short and the dropped frames are counted as overruns, a read that finds
+/// too little is cut short and the missing frames are counted as underruns.
+pub struct SharedRing {
+    // keeps the mapping alive; all access goes through `base`
+    _map: MmapMut,
+    base: NonNull<u8>,
+    channels: usize,
+    capacity: usize,
+    sample_rate: u32,
+    path: PathBuf,
+    owner: bool,
+}
+
+// SAFETY: the mapping lives as long as the ring. The header fields written
+// after creation are atomics, and frame data is only touched in the region the
+// positions hand to the caller's role (SPSC), so sharing between threads is as
+// safe as sharing between processes.
+unsafe impl Send for SharedRing {}
+unsafe impl Sync for SharedRing {}
+
+impl SharedRing {
+    /// Create a ring at `path`, replacing any existing file. The file is
+    /// removed when this ring is dropped.
+    pub fn create(path: impl AsRef<Path>, channels: usize, capacity_frames: usize, sample_rate: u32) -> io::Result<Self> {
+        if channels == 0 || capacity_frames == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "channels and capacity must be non-zero"));
+        }
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)?;
+        file.set_len((HEADER_BYTES + channels * capacity_frames * size_of::<f32>()) as u64)?;
+        let mut ring = Self::map(file, path, channels, capacity_frames, sample_rate)?;
+
+        ring.u32_at(CHANNELS_OFFSET).store(channels as u32, Ordering::Relaxed);
+        ring.u32_at(CAPACITY_OFFSET).store(capacity_frames as u32, Ordering::Relaxed);
+        ring.u32_at(SAMPLE_RATE_OFFSET).store(sample_rate, Ordering::Relaxed);
+        ring.u32_at(VERSION_OFFSET).store(VERSION, Ordering::Relaxed);
+        // published last: an opener that sees the magic sees the whole header
+        ring.u32_at(MAGIC_OFFSET).store(MAGIC, Ordering::Release);
+        ring.owner = true;
+        Ok(ring)
+    }
+
+    /// Open a ring created by another process
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().read(true).write(true).open(&path)?;
+        let len = file.metadata()?.len() as usize;
+        if len < HEADER_BYTES {
+            return Err(invalid("file too small for a ring header"));
+        }
+        let mut ring = Self::map(file, path, 0, 0, 0)?;
+        if ring.u32_at(MAGIC_OFFSET).load(Ordering::Acquire) != MAGIC {
+            return Err(invalid("not an audio ring"));
+        }
+        if ring.u32_at(VERSION_OFFSET).load(Ordering::Relaxed) != VERSION {
+            return Err(invalid("unsupported ring version"));
+        }
+        ring.channels = ring.u32_at(CHANNELS_OFFSET).load(Ordering::Relaxed) as usize;
+        ring.capacity = ring.u32_at(CAPACITY_OFFSET).load(Ordering::Relaxed) as usize;
+        ring.sample_rate = ring.u32_at(SAMPLE_RATE_OFFSET).load(Ordering::Relaxed);
+        if ring.channels == 0 || ring.capacity == 0 || len < HEADER_BYTES + ring.samples() * size_of::<f32>() {
+            return Err(invalid("ring header does not match the file size"));
+        }
+        Ok(ring)
+    }
+
+    fn map(file: File, path: PathBuf, channels: usize, capacity: usize, sample_rate: u32) -> io::Result<Self> {
+        // SAFETY: the file is only modified through this ring type, by the
+        // creating and the opening process, following the SPSC protocol.
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        let base = NonNull::new(map.as_mut_ptr()).ok_or_else(|| invalid("empty mapping"))?;
+        Ok(Self { _map: map, base, channels, capacity, sample_rate, path, owner: false })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Capacity in frames
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Rate the creator runs at; the ring itself does no conversion
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Frames waiting to be read, never more than the capacity.
+    ///
+    /// The positions live in the other process's memory too, so they are
+    /// checked rather than trusted: a write position behind the read
+    /// position reads as empty, and one more than a ring ahead as full.
+    pub fn readable(&self) -> usize {
+        let write = self.u64_at(WRITE_POS_OFFSET).load(Ordering::Acquire);
+        let read = self.u64_at(READ_POS_OFFSET).load(Ordering::Acquire);
+        match write.checked_sub(read) {
+            Some(pending) => pending.min(self.capacity as u64) as usize,
+            None => 0,
+        }
+    }
+
+    /// Frames that can be written without overrunning the reader
+    pub fn writable(&self) -> usize {
+        self.capacity - self.readable().min(self.capacity)
+    }
+
+    /// Frames dropped because the ring was full
+    pub fn overruns(&self) -> u64 {
+        self.u64_at(OVERRUNS_OFFSET).load(Ordering::Relaxed)
+    }
+
+    /// Frames a reader asked for that weren't there
+    pub fn underruns(&self) -> u64 {
+        self.u64_at(UNDERRUNS_OFFSET).load(Ordering::Relaxed)
+    }
+
+    /// Producer side: append interleaved frames. Returns the frames written;
+    /// the rest are dropped and counted as overruns. Never blocks or allocates.
+    pub fn write(&self, interleaved: &[f32]) -> usize {
+        let requested = interleaved.len() / self.channels;
+        let frames = requested.min(self.writable()).min(self.capacity);
+        let write = self.u64_at(WRITE_POS_OFFSET).load(Ordering::Relaxed);
+
+        let start = (write % self.capacity as u64) as usize;
+        let first = frames.min(self.capacity - start);
+        // SAFETY: [start, start + frames) wraps within the data region and lies
+        // between the write and read positions, which only the producer touches.
+        unsafe {
+            self.copy_in(start, &interleaved[..first * self.channels]);
+            self.copy_in(0, &interleaved[first * self.channels..frames * self.channels]);
+        }
+        self.u64_at(WRITE_POS_OFFSET).store(write + frames as u64, Ordering::Release);
+        if frames < requested {
+            self.u64_at(OVERRUNS_OFFSET).fetch_add((requested - frames) as u64, Ordering::Relaxed);
+        }
+        frames
+    }
+
+    /// Consumer side: fill `interleaved` with the oldest frames. Returns the
+    /// frames read; the caller decides what to do with the rest (usually
+    /// silence), and the shortfall is counted as underruns. Never blocks or
+    /// allocates.
+    pub fn read(&self, interleaved: &mut [f32]) -> usize {
+        let requested = interleaved.len() / self.channels;
+        let frames = requested.min(self.readable()).min(self.capacity);
+        let read = self.u64_at(READ_POS_OFFSET).load(Ordering::Relaxed);
+
+        let start = (read % self.capacity as u64) as usize;
+        let first = frames.min(self.capacity - start);
+        // SAFETY: [start, start + frames) was published by the producer's
+        // Release store of the write position and isn't touched again until
+        // the read position moves past it.
+        unsafe {
+            self.copy_out(start, &mut interleaved[..first * self.channels]);
+            self.copy_out(0, &mut interleaved[first * self.channels..frames * self.channels]);
+        }
+        self.u64_at(READ_POS_OFFSET).store(read + frames as u64, Ordering::Release);
+        if frames < requested {
+            self.u64_at(UNDERRUNS_OFFSET).fetch_add((requested - frames) as u64, Ordering::Relaxed);
+        }
+        frames
+    }
+
+    fn samples(&self) -> usize {
+        self.channels * self.capacity
+    }
+
+    fn data(&self) -> *mut f32 {
+        // SAFETY: the data region starts right after the header
+        unsafe { self.base.as_ptr().add(HEADER_BYTES) as *mut f32 }
+    }
+
+    unsafe fn copy_in(&self, frame: usize, samples: &[f32]) {
+        debug_assert!(frame * self.channels + samples.len() <= self.samples());
+        unsafe { std::ptr::copy_nonoverlapping(samples.as_ptr(), self.data().add(frame * self.channels), samples.len()) }
+    }
+
+    unsafe fn copy_out(&self, frame: usize, samples: &mut [f32]) {
+        debug_assert!(frame * self.channels + samples.len() <= self.samples());
+        unsafe { std::ptr::copy_nonoverlapping(self.data().add(frame * self.channels), samples.as_mut_ptr(), samples.len()) }
+    }
+
+    fn u32_at(&self, offset: usize) -> &AtomicU32 {
+        // SAFETY: offset is a 4-byte aligned header field inside the mapping
+        unsafe { &*(self.base.as_ptr().add(offset) as *const AtomicU32) }
+    }
+
+    fn u64_at(&self, offset: usize) -> &AtomicU64 {
+        // SAFETY: offset is an 8-byte aligned header field inside the mapping
+        unsafe { &*(self.base.as_ptr().add(offset) as *const AtomicU64) }
+    }
+}
+
+impl Drop for SharedRing {
+    fn drop(&mut self) {
+        if self.owner {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
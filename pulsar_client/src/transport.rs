@@ -0,0 +1,185 @@
+//! Local stream transport: a Unix domain socket where available, loopback TCP
+//! elsewhere.
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
+
+/// Port used by `Endpoint::default_local` without Unix sockets
+pub const DEFAULT_PORT: u16 = 47_310;
+
+/// Where a remote engine listens
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    #[cfg(unix)]
+    Unix(PathBuf),
+    Tcp(SocketAddr),
+}
+
+impl Endpoint {
+    /// `pulsar-engine.sock` in the temp dir on Unix, `127.0.0.1:DEFAULT_PORT` elsewhere
+    pub fn default_local() -> Self {
+        #[cfg(unix)]
+        {
+            Endpoint::Unix(std::env::temp_dir().join("pulsar-engine.sock"))
+        }
+        #[cfg(not(unix))]
+        {
+            Endpoint::Tcp(SocketAddr::from(([127, 0, 0, 1], DEFAULT_PORT)))
+        }
+    }
+
+    /// Parse `host:port` as TCP, anything else as a socket path (Unix only)
+    pub fn parse(text: &str) -> io::Result<Self> {
+        if let Ok(addr) = text.parse::<SocketAddr>() {
+            return Ok(Endpoint::Tcp(addr));
+        }
+        #[cfg(unix)]
+        {
+            Ok(Endpoint::Unix(PathBuf::from(text)))
+        }
+        #[cfg(not(unix))]
+        {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, format!("not a socket address: {}", text)))
+        }
+    }
+
+    pub fn connect(&self) -> io::Result<Connection> {
+        match self {
+            #[cfg(unix)]
+            Endpoint::Unix(path) => UnixStream::connect(path).map(Connection::Unix),
+            Endpoint::Tcp(addr) => {
+                let stream = TcpStream::connect(addr)?;
+                stream.set_nodelay(true)?;
+                Ok(Connection::Tcp(stream))
+            }
+        }
+    }
+
+    /// Listen on the endpoint. A stale socket file left by a previous server is
+    /// replaced.
+    pub fn bind(&self) -> io::Result<Listener> {
+        match self {
+            #[cfg(unix)]
+            Endpoint::Unix(path) => {
+                if path.exists() && UnixStream::connect(path).is_err() {
+                    std::fs::remove_file(path)?;
+                }
+                Ok(Listener::Unix(UnixListener::bind(path)?, path.clone()))
+            }
+            Endpoint::Tcp(addr) => Ok(Listener::Tcp(TcpListener::bind(addr)?)),
+        }
+    }
+}
+
+/// A connected stream
+pub enum Connection {
+    #[cfg(unix)]
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+impl Connection {
+    /// Second handle to the same stream, e.g. to read and write from different threads
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            #[cfg(unix)]
+            Connection::Unix(s) => s.try_clone().map(Connection::Unix),
+            Connection::Tcp(s) => s.try_clone().map(Connection::Tcp),
+        }
+    }
+
+    /// Close both directions, unblocking any reader
+    pub fn shutdown(&self) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Connection::Unix(s) => s.shutdown(std::net::Shutdown::Both),
+            Connection::Tcp(s) => s.shutdown(std::net::Shutdown::Both),
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(unix)]
+            Connection::Unix(s) => s.read(buf),
+            Connection::Tcp(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(unix)]
+            Connection::Unix(s) => s.write(buf),
+            Connection::Tcp(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Connection::Unix(s) => s.flush(),
+            Connection::Tcp(s) => s.flush(),
+        }
+    }
+}
+
+/// A bound endpoint. A Unix socket file is removed when the listener drops.
+pub enum Listener {
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+    Tcp(TcpListener),
+}
+
+impl Listener {
+    pub fn accept(&self) -> io::Result<Connection> {
+        match self {
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                Ok(Connection::Unix(stream))
+            }
+            Listener::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                stream.set_nodelay(true)?;
+                Ok(Connection::Tcp(stream))
+            }
+        }
+    }
+
+    /// With `true`, `accept` returns `WouldBlock` instead of waiting
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => listener.set_nonblocking(nonblocking),
+            Listener::Tcp(listener) => listener.set_nonblocking(nonblocking),
+        }
+    }
+
+    /// The endpoint actually bound (resolves port 0)
+    pub fn endpoint(&self) -> io::Result<Endpoint> {
+        match self {
+            #[cfg(unix)]
+            Listener::Unix(_, path) => Ok(Endpoint::Unix(path.clone())),
+            Listener::Tcp(listener) => listener.local_addr().map(Endpoint::Tcp),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}